@@ -0,0 +1,86 @@
+//! # Chunk Key Benchmarks
+//!
+//! Encoding and parsing of chunk keys, which region reads and writes and
+//! chunk listings do once per chunk.
+#![feature(test)]
+
+extern crate test;
+
+use test::Bencher;
+
+use zarr::prelude::*;
+use zarr::smallvec::smallvec;
+use zarr::storage::{
+    get_chunk_key,
+    parse_chunk_key,
+    ChunkKeyFormatter,
+};
+
+fn array_meta() -> ArrayMetadata {
+    ArrayMetadataBuilder::new(smallvec![1 << 20, 1 << 20, 1 << 20], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![64, 64, 64])
+        .build()
+}
+
+#[bench]
+fn encode_chunk_keys_3d(b: &mut Bencher) {
+    let array_meta = array_meta();
+    b.iter(|| {
+        for z in 0..10u64 {
+            for y in 0..10 {
+                for x in 0..10 {
+                    test::black_box(get_chunk_key(
+                        "volumes/raw/s0",
+                        &array_meta,
+                        &[x * 1021, y * 37, z],
+                    ));
+                }
+            }
+        }
+    });
+}
+
+#[bench]
+fn format_chunk_keys_3d(b: &mut Bencher) {
+    let array_meta = array_meta();
+    b.iter(|| {
+        let formatter = ChunkKeyFormatter::new("volumes/raw/s0", &array_meta);
+        for z in 0..10u64 {
+            for y in 0..10 {
+                for x in 0..10 {
+                    test::black_box(formatter.key(&[x * 1021, y * 37, z]));
+                }
+            }
+        }
+    });
+}
+
+#[bench]
+fn format_chunk_keys_3d_reused_buffer(b: &mut Bencher) {
+    let array_meta = array_meta();
+    b.iter(|| {
+        let formatter = ChunkKeyFormatter::new("volumes/raw/s0", &array_meta);
+        let mut key = String::new();
+        for z in 0..10u64 {
+            for y in 0..10 {
+                for x in 0..10 {
+                    formatter.write_key(&mut key, &[x * 1021, y * 37, z]);
+                    test::black_box(&key);
+                }
+            }
+        }
+    });
+}
+
+#[bench]
+fn parse_chunk_keys_3d(b: &mut Bencher) {
+    let array_meta = array_meta();
+    let keys: Vec<String> = (0..1000u64)
+        .map(|i| get_chunk_key("volumes/raw/s0", &array_meta, &[i * 1021, i % 37, i / 10]))
+        .collect();
+    b.iter(|| {
+        for key in &keys {
+            test::black_box(parse_chunk_key("volumes/raw/s0", &array_meta, key));
+        }
+    });
+}
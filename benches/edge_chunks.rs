@@ -0,0 +1,191 @@
+//! # Edge Chunk Benchmarks
+//!
+//! Region writes and reads which only partly cover their chunks, so that
+//! chunks are initialized with the fill value or read back and spliced
+//! with the region. The store keeps values in memory so that this measures
+//! the chunk assembly rather than storage.
+#![feature(test)]
+
+extern crate test;
+
+use std::collections::BTreeMap;
+use std::io::{
+    Cursor,
+    Error,
+    Write,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use ndarray::Array;
+use test::Bencher;
+
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+use zarr::smallvec::smallvec;
+use zarr::storage::{
+    ReadableStore,
+    WriteableStore,
+};
+use zarr::{
+    EntryPointMetadata,
+    Hierarchy,
+    Order,
+};
+
+type Values = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+#[derive(Debug, Default)]
+struct MemoryStore {
+    entry_point_metadata: EntryPointMetadata,
+    values: Values,
+}
+
+struct MemoryWriter {
+    key: String,
+    buffer: Vec<u8>,
+    values: Values,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Drop for MemoryWriter {
+    fn drop(&mut self) {
+        let value = std::mem::take(&mut self.buffer);
+        self.values
+            .lock()
+            .unwrap()
+            .insert(std::mem::take(&mut self.key), value);
+    }
+}
+
+impl Hierarchy for MemoryStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl ReadableStore for MemoryStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.values.lock().unwrap().contains_key(key))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        Ok(self
+            .values
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        Ok(format!("memory:{}", key))
+    }
+}
+
+impl WriteableStore for MemoryStore {
+    type SetWriter = MemoryWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        value(MemoryWriter {
+            key: key.to_owned(),
+            buffer: Vec::new(),
+            values: self.values.clone(),
+        })
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.values.lock().unwrap().remove(key).is_some())
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        let mut values = self.values.lock().unwrap();
+        let len = values.len();
+        values.retain(|key, _| !key.starts_with(key_prefix));
+        Ok(values.len() != len)
+    }
+}
+
+/// A 3D array whose 64^3 chunks a region at `[10, 10, 10]` of shape
+/// `[128, 128, 128]` covers all but one of only partly.
+fn array_meta(order: Order) -> ArrayMetadata {
+    ArrayMetadataBuilder::new(smallvec![300, 300, 300], u16::ZARR_TYPE)
+        .chunk_shape(smallvec![64, 64, 64])
+        .chunk_memory_layout(order)
+        .fill_value(7)
+        .build()
+}
+
+fn region() -> Array<u16, ndarray::IxDyn> {
+    Array::from_shape_fn(ndarray::IxDyn(&[128, 128, 128]), |idx| {
+        (idx[0] * 3 + idx[1] * 5 + idx[2]) as u16
+    })
+}
+
+fn bench_write_edge_chunks(order: Order, existing: bool, b: &mut Bencher) {
+    let store = MemoryStore::default();
+    let array_meta = array_meta(order);
+    store.create_array("a", &array_meta).unwrap();
+    let region = region();
+    if existing {
+        store
+            .write_ndarray("a", &array_meta, smallvec![0, 0, 0], &region)
+            .unwrap();
+        store
+            .write_ndarray("a", &array_meta, smallvec![128, 128, 128], &region)
+            .unwrap();
+    }
+    b.iter(|| {
+        if !existing {
+            store.erase_prefix("/data/").unwrap();
+        }
+        store
+            .write_ndarray("a", &array_meta, smallvec![10, 10, 10], &region)
+            .unwrap();
+    });
+}
+
+#[bench]
+fn write_edge_chunks_fill_3d(b: &mut Bencher) {
+    bench_write_edge_chunks(Order::RowMajor, false, b);
+}
+
+#[bench]
+fn write_edge_chunks_existing_3d(b: &mut Bencher) {
+    bench_write_edge_chunks(Order::RowMajor, true, b);
+}
+
+#[bench]
+fn write_edge_chunks_fill_3d_column_major(b: &mut Bencher) {
+    bench_write_edge_chunks(Order::ColumnMajor, false, b);
+}
+
+#[bench]
+fn read_edge_chunks_3d(b: &mut Bencher) {
+    let store = MemoryStore::default();
+    let array_meta = array_meta(Order::RowMajor);
+    store.create_array("a", &array_meta).unwrap();
+    store
+        .write_ndarray("a", &array_meta, smallvec![0, 0, 0], &region())
+        .unwrap();
+    let bbox = BoundingBox::new(smallvec![10, 10, 10], smallvec![128, 128, 128]);
+    b.iter(|| test::black_box(store.read_ndarray::<u16>("a", &array_meta, &bbox).unwrap()));
+}
@@ -26,7 +26,6 @@ use futures_cpupool::{
     CpuPool,
 };
 use lazy_static::lazy_static;
-use tempdir;
 use test::Bencher;
 use tiff::decoder::{
     Decoder,
@@ -142,7 +141,7 @@ where
     b.iter(|| write(&n, &compression, &chunk_data, pool_size));
 
     b.bytes = (CHUNK_DIM * CHUNK_DIM * CHUNK_DIM) as u64
-        * (N_CHUNKS * N_CHUNKS * N_CHUNKS) as u64
+        * (N_CHUNKS * N_CHUNKS * N_CHUNKS)
         * std::mem::size_of::<T>() as u64;
 }
 
@@ -162,6 +161,7 @@ fn bench_write_i16_gzip_1(b: &mut Bencher) {
     bench_write_dtype_compression::<i16, compression::gzip::GzipCompression>(b, 1);
 }
 
+#[cfg(feature = "xz")]
 #[bench]
 fn bench_write_i16_xz_1(b: &mut Bencher) {
     bench_write_dtype_compression::<i16, compression::xz::XzCompression>(b, 1);
@@ -188,6 +188,7 @@ fn bench_write_i16_gzip_2(b: &mut Bencher) {
     bench_write_dtype_compression::<i16, compression::gzip::GzipCompression>(b, 2);
 }
 
+#[cfg(feature = "xz")]
 #[bench]
 fn bench_write_i16_xz_2(b: &mut Bencher) {
     bench_write_dtype_compression::<i16, compression::xz::XzCompression>(b, 2);
@@ -0,0 +1,56 @@
+//! Decompress arbitrary bytes with each codec.
+//!
+//! The first byte selects the codec and the second the data type passed to
+//! typed codecs.
+#![no_main]
+
+use std::io::Read;
+
+use libfuzzer_sys::fuzz_target;
+use zarr::compression::{
+    gzip::{
+        GzipCompression,
+        GzipFormat,
+    },
+    Compression,
+    CompressionType,
+};
+use zarr::prelude::*;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let codecs = [
+        CompressionType::default(),
+        CompressionType::new::<compression::bzip::Bzip2Compression>(),
+        CompressionType::new::<GzipCompression>(),
+        GzipCompression {
+            format: GzipFormat::Zlib,
+            ..Default::default()
+        }
+        .into(),
+        CompressionType::new::<compression::lz::Lz4Compression>(),
+        CompressionType::new::<compression::xz::XzCompression>(),
+        CompressionType::new::<compression::snappy::SnappyCompression>(),
+        CompressionType::new::<compression::pcodec::PcodecCompression>(),
+        CompressionType::new::<compression::zstd::ZstdCompression>(),
+    ];
+    let data_types = [
+        bool::ZARR_TYPE,
+        u8::ZARR_TYPE,
+        i16::ZARR_TYPE,
+        u32::ZARR_TYPE,
+        i64::ZARR_TYPE,
+        f32::ZARR_TYPE,
+        f64::ZARR_TYPE,
+    ];
+    let codec = &codecs[data[0] as usize % codecs.len()];
+    let data_type = &data_types[data[1] as usize % data_types.len()];
+
+    // Bound output so that compression bombs are not reported as OOMs.
+    let _ = codec
+        .decoder_typed(&data[2..], data_type)
+        .take(1 << 24)
+        .read_to_end(&mut Vec::new());
+});
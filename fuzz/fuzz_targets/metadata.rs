@@ -0,0 +1,27 @@
+//! Parse arbitrary bytes as hierarchy metadata documents, checking that
+//! anything accepted round-trips.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zarr::{
+    ArrayMetadata,
+    EntryPointMetadata,
+    GroupMetadata,
+};
+
+fn round_trip<T>(data: &[u8])
+where
+    T: serde::de::DeserializeOwned + serde::Serialize,
+{
+    if let Ok(parsed) = serde_json::from_slice::<T>(data) {
+        let serialized = serde_json::to_value(&parsed).expect("Serialization failed");
+        let reparsed: T = serde_json::from_value(serialized.clone()).expect("Reparsing failed");
+        assert_eq!(serialized, serde_json::to_value(&reparsed).unwrap());
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    round_trip::<EntryPointMetadata>(data);
+    round_trip::<GroupMetadata>(data);
+    round_trip::<ArrayMetadata>(data);
+});
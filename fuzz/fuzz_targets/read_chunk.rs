@@ -0,0 +1,61 @@
+//! Read arbitrary bytes as a chunk of small arrays with each codec and
+//! filter chain.
+//!
+//! The first byte selects the compressor and the second the filters.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zarr::chunk::{
+    DefaultChunk,
+    DefaultChunkReader,
+};
+use zarr::filter::{
+    astype::AsTypeFilter,
+    packbits::PackBitsFilter,
+    quantize::QuantizeFilter,
+};
+use zarr::prelude::*;
+use zarr::smallvec::smallvec;
+
+fn read<T: ReflectedType>(data: &[u8], compressor: &CompressionType, filters: Vec<FilterType>)
+where
+    VecDataChunk<T>: DataChunk<T> + zarr::chunk::ReadableDataChunk,
+{
+    let array_meta = ArrayMetadataBuilder::new(smallvec![8, 8], T::ZARR_TYPE)
+        .chunk_shape(smallvec![4, 4])
+        .compressor(compressor.clone())
+        .filters(filters)
+        .build();
+    let _ =
+        <DefaultChunk as DefaultChunkReader<T, _>>::read_chunk(data, &array_meta, smallvec![1, 1]);
+}
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let codecs = [
+        CompressionType::default(),
+        CompressionType::new::<compression::gzip::GzipCompression>(),
+        CompressionType::new::<compression::lz::Lz4Compression>(),
+        CompressionType::new::<compression::snappy::SnappyCompression>(),
+        CompressionType::new::<compression::pcodec::PcodecCompression>(),
+        CompressionType::new::<compression::zstd::ZstdCompression>(),
+    ];
+    let compressor = &codecs[data[0] as usize % codecs.len()];
+    let chunk = &data[2..];
+
+    match data[1] % 4 {
+        0 => {
+            read::<i16>(chunk, compressor, vec![]);
+            read::<bool>(chunk, compressor, vec![]);
+        }
+        1 => read::<f64>(chunk, compressor, vec![QuantizeFilter::new(3).into()]),
+        2 => read::<bool>(chunk, compressor, vec![PackBitsFilter::default().into()]),
+        _ => read::<f32>(
+            chunk,
+            compressor,
+            vec![AsTypeFilter::new(u16::ZARR_TYPE, f32::ZARR_TYPE).into()],
+        ),
+    }
+});
@@ -0,0 +1,300 @@
+//! Axis names, units and coordinate transformations of arrays.
+//!
+//! Following OME-NGFF, an array's axes are described by its `"axes"`
+//! attribute and the mapping from voxel to world coordinates by its
+//! `"coordinateTransformations"` attribute, a list of scale and translation
+//! transformations applied in order:
+//!
+//! ```
+//! use zarr::axes::{
+//!     Axis,
+//!     AxisMetadata,
+//!     CoordinateTransformation,
+//! };
+//!
+//! let axis_meta = AxisMetadata {
+//!     axes: vec![
+//!         Axis::space("z", "micrometer"),
+//!         Axis::space("y", "micrometer"),
+//!         Axis::space("x", "micrometer"),
+//!     ],
+//!     coordinate_transformations: vec![
+//!         CoordinateTransformation::Scale {
+//!             scale: vec![2.0, 0.5, 0.5],
+//!         },
+//!         CoordinateTransformation::Translation {
+//!             translation: vec![10.0, 0.0, 0.0],
+//!         },
+//!     ],
+//! };
+//! assert_eq!(axis_meta.voxel_to_world(&[1.0, 2.0, 3.0]), vec![12.0, 1.0, 1.5]);
+//! assert_eq!(axis_meta.world_to_voxel(&[12.0, 1.0, 1.5]), vec![1.0, 2.0, 3.0]);
+//! ```
+//!
+//! Axis metadata is stored with
+//! [`set_attributes`](crate::HierarchyWriter::set_attributes) of
+//! [`to_attributes`](AxisMetadata::to_attributes), and read from array
+//! metadata with [`from_array`](AxisMetadata::from_array).
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    ArrayMetadata,
+    JsonObject,
+};
+
+/// Attribute holding the axes of an array.
+pub const AXES_ATTRIBUTE: &str = "axes";
+
+/// Attribute holding the coordinate transformations of an array.
+pub const COORDINATE_TRANSFORMATIONS_ATTRIBUTE: &str = "coordinateTransformations";
+
+/// An axis of an array.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Axis {
+    pub name: String,
+    /// Kind of the axis, such as `"space"`, `"time"` or `"channel"`.
+    #[serde(rename = "type")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub axis_type: Option<String>,
+    /// Unit of world coordinates along the axis, such as `"micrometer"`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+impl Axis {
+    /// A spatial axis with a unit.
+    pub fn space(name: &str, unit: &str) -> Self {
+        Axis {
+            name: name.to_owned(),
+            axis_type: Some("space".to_owned()),
+            unit: Some(unit.to_owned()),
+        }
+    }
+}
+
+/// A transformation of coordinates, with one factor or offset per axis.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CoordinateTransformation {
+    Identity,
+    Scale { scale: Vec<f64> },
+    Translation { translation: Vec<f64> },
+}
+
+/// Axes and coordinate transformations of an array.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AxisMetadata {
+    pub axes: Vec<Axis>,
+    /// Transformations from voxel to world coordinates, applied in order.
+    pub coordinate_transformations: Vec<CoordinateTransformation>,
+}
+
+impl AxisMetadata {
+    /// Read axis metadata from attributes, or `None` if they have no axes.
+    pub fn from_attributes(attributes: &JsonObject) -> Result<Option<Self>, Error> {
+        let axes = match attributes.get(AXES_ATTRIBUTE) {
+            Some(axes) => serde_json::from_value(axes.clone())?,
+            None => return Ok(None),
+        };
+        let coordinate_transformations = match attributes.get(COORDINATE_TRANSFORMATIONS_ATTRIBUTE)
+        {
+            Some(transformations) => serde_json::from_value(transformations.clone())?,
+            None => vec![],
+        };
+        let axis_meta = AxisMetadata {
+            axes,
+            coordinate_transformations,
+        };
+        axis_meta.validate()?;
+        Ok(Some(axis_meta))
+    }
+
+    /// Read axis metadata from the attributes of an array, or `None` if it
+    /// has no axes. Fails if the number of axes does not match the array.
+    pub fn from_array(array_meta: &ArrayMetadata) -> Result<Option<Self>, Error> {
+        let axis_meta = Self::from_attributes(array_meta.get_attributes())?;
+        if let Some(axis_meta) = &axis_meta {
+            if axis_meta.axes.len() != array_meta.get_ndim() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Number of axes does not match array dimensions",
+                ));
+            }
+        }
+        Ok(axis_meta)
+    }
+
+    /// Attributes holding this axis metadata.
+    pub fn to_attributes(&self) -> Result<JsonObject, Error> {
+        self.validate()?;
+        let mut attributes = JsonObject::new();
+        attributes.insert(AXES_ATTRIBUTE.to_owned(), serde_json::to_value(&self.axes)?);
+        attributes.insert(
+            COORDINATE_TRANSFORMATIONS_ATTRIBUTE.to_owned(),
+            serde_json::to_value(&self.coordinate_transformations)?,
+        );
+        Ok(attributes)
+    }
+
+    /// Check that every transformation has one factor or offset per axis,
+    /// and that scale factors are finite and non-zero so that they can be
+    /// inverted.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidData, message));
+        for transformation in &self.coordinate_transformations {
+            let values = match transformation {
+                CoordinateTransformation::Identity => continue,
+                CoordinateTransformation::Scale { scale } => {
+                    if scale.iter().any(|&s| s == 0.0 || !s.is_finite()) {
+                        return invalid("Scale factors must be finite and non-zero");
+                    }
+                    scale
+                }
+                CoordinateTransformation::Translation { translation } => translation,
+            };
+            if values.len() != self.axes.len() {
+                return invalid("Coordinate transformation does not match number of axes");
+            }
+        }
+        Ok(())
+    }
+
+    /// Units of the axes.
+    pub fn units(&self) -> Vec<Option<&str>> {
+        self.axes.iter().map(|axis| axis.unit.as_deref()).collect()
+    }
+
+    /// The transformations combined into a scale and a translation applied
+    /// after it, so that `world = voxel * scale + translation`.
+    pub fn scale_and_translation(&self) -> (Vec<f64>, Vec<f64>) {
+        let mut scale = vec![1.0; self.axes.len()];
+        let mut translation = vec![0.0; self.axes.len()];
+        for transformation in &self.coordinate_transformations {
+            match transformation {
+                CoordinateTransformation::Identity => {}
+                CoordinateTransformation::Scale { scale: factors } => {
+                    for ((s, t), f) in scale.iter_mut().zip(&mut translation).zip(factors) {
+                        *s *= f;
+                        *t *= f;
+                    }
+                }
+                CoordinateTransformation::Translation {
+                    translation: offsets,
+                } => {
+                    for (t, o) in translation.iter_mut().zip(offsets) {
+                        *t += o;
+                    }
+                }
+            }
+        }
+        (scale, translation)
+    }
+
+    /// Convert voxel coordinates to world coordinates.
+    pub fn voxel_to_world(&self, voxel: &[f64]) -> Vec<f64> {
+        let (scale, translation) = self.scale_and_translation();
+        voxel
+            .iter()
+            .zip(scale.iter().zip(&translation))
+            .map(|(v, (s, t))| v * s + t)
+            .collect()
+    }
+
+    /// Convert world coordinates to voxel coordinates, which are fractional
+    /// between voxel centers.
+    pub fn world_to_voxel(&self, world: &[f64]) -> Vec<f64> {
+        let (scale, translation) = self.scale_and_translation();
+        world
+            .iter()
+            .zip(scale.iter().zip(&translation))
+            .map(|(w, (s, t))| (w - t) / s)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ArrayMetadataBuilder,
+        ReflectedType,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_ngff() {
+        let attributes = json!({
+            "axes": [
+                {"name": "t", "type": "time", "unit": "second"},
+                {"name": "y", "type": "space", "unit": "micrometer"},
+                {"name": "x"},
+            ],
+            "coordinateTransformations": [
+                {"type": "scale", "scale": [60.0, 0.5, 0.5]},
+                {"type": "translation", "translation": [0.0, -1.0, 1.0]},
+                {"type": "scale", "scale": [1.0, 2.0, 2.0]},
+            ],
+        });
+        let attributes = attributes.as_object().unwrap();
+        let axis_meta = AxisMetadata::from_attributes(attributes).unwrap().unwrap();
+        assert_eq!(
+            axis_meta.units(),
+            vec![Some("second"), Some("micrometer"), None]
+        );
+        assert_eq!(axis_meta.axes[2].axis_type, None);
+        assert_eq!(
+            axis_meta.scale_and_translation(),
+            (vec![60.0, 1.0, 1.0], vec![0.0, -2.0, 2.0])
+        );
+        assert_eq!(
+            axis_meta.voxel_to_world(&[2.0, 3.0, 4.0]),
+            vec![120.0, 1.0, 6.0]
+        );
+        assert_eq!(
+            axis_meta.world_to_voxel(&[120.0, 1.0, 6.0]),
+            vec![2.0, 3.0, 4.0]
+        );
+        assert_eq!(&axis_meta.to_attributes().unwrap(), attributes);
+
+        assert_eq!(
+            AxisMetadata::from_attributes(&JsonObject::new()).unwrap(),
+            None
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE).build();
+        assert_eq!(AxisMetadata::from_array(&array_meta).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_transformations() {
+        let axes = vec![Axis::space("y", "nanometer"), Axis::space("x", "nanometer")];
+        for transformation in &[
+            CoordinateTransformation::Scale { scale: vec![1.0] },
+            CoordinateTransformation::Scale {
+                scale: vec![1.0, 0.0],
+            },
+            CoordinateTransformation::Translation {
+                translation: vec![1.0, 2.0, 3.0],
+            },
+        ] {
+            let axis_meta = AxisMetadata {
+                axes: axes.clone(),
+                coordinate_transformations: vec![transformation.clone()],
+            };
+            assert_eq!(
+                axis_meta.to_attributes().unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+}
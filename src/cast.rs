@@ -0,0 +1,217 @@
+//! Reading arrays as a different element type.
+//!
+//! A [`CastReader`] converts elements chunk by chunk as they are decoded, so
+//! reading a `u16` array as `f32` never holds more than one chunk of `u16`
+//! elements alongside the converted output.
+//!
+//! ```no_run
+//! use zarr::cast::ZarrCastReader;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/images.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("raw").unwrap();
+//! let normalized = h
+//!     .cast::<f32>("raw", &array_meta)
+//!     .scale(1.0 / 65535.0, 0.0)
+//!     .read_ndarray(&array_meta.get_bounds())
+//!     .unwrap();
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::marker::PhantomData;
+
+use half::f16;
+use ndarray::{
+    Array,
+    ArrayD,
+    IxDyn,
+    ShapeBuilder,
+    SliceInfo,
+};
+
+use crate::ndarray::BoundingBox;
+use crate::{
+    ArrayMetadata,
+    DataType,
+    FloatSize,
+    GridCoord,
+    HierarchyReader,
+    IntSize,
+    Order,
+    ReflectedType,
+    VecDataChunk,
+};
+
+/// Element types which arrays can be read as and converted from.
+///
+/// Conversions go through `f64`, so 64-bit integers with magnitudes beyond
+/// 2<sup>53</sup> lose precision. Conversions to integers saturate at the
+/// bounds of the type and truncate toward zero.
+pub trait CastElement: ReflectedType + Copy {
+    fn to_f64(self) -> f64;
+
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! cast_element_as {
+    ($($ty:ty),*) => {
+        $(
+            impl CastElement for $ty {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value as $ty
+                }
+            }
+        )*
+    };
+}
+
+cast_element_as!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl CastElement for bool {
+    fn to_f64(self) -> f64 {
+        f64::from(u8::from(self))
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value != 0.0
+    }
+}
+
+impl CastElement for f16 {
+    fn to_f64(self) -> f64 {
+        f16::to_f64(self)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        f16::from_f64(value)
+    }
+}
+
+/// Reader of an array converting its elements to `U`, optionally applying a
+/// linear scale.
+#[derive(Debug)]
+pub struct CastReader<'a, H: ?Sized, U> {
+    hierarchy: &'a H,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    scale: f64,
+    offset: f64,
+    element_type: PhantomData<U>,
+}
+
+impl<'a, H: HierarchyReader + ?Sized, U: CastElement> CastReader<'a, H, U> {
+    /// Convert each element `x` to `x * scale + offset` before casting
+    /// it to `U`.
+    pub fn scale(mut self, scale: f64, offset: f64) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+
+    fn convert<T: CastElement>(&self, value: T) -> U {
+        if self.scale == 1.0 && self.offset == 0.0 {
+            U::from_f64(value.to_f64())
+        } else {
+            U::from_f64(value.to_f64().mul_add(self.scale, self.offset))
+        }
+    }
+
+    /// Read a bounding box of the array as converted elements.
+    ///
+    /// Absent chunks read as the converted fill value.
+    pub fn read_ndarray(&self, bbox: &BoundingBox) -> Result<ArrayD<U>, Error> {
+        data_type_match!(
+            self.array_meta.get_data_type().effective_type()?,
+            DataType::Raw { .. } => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Arrays of raw data types can not be cast",
+            )),
+            self.read_ndarray_as::<RsType>(bbox)
+        )
+    }
+
+    fn read_ndarray_as<T: CastElement>(&self, bbox: &BoundingBox) -> Result<ArrayD<U>, Error>
+    where
+        VecDataChunk<T>: crate::ReadableDataChunk + crate::ReinitDataChunk<T>,
+    {
+        let array_meta = &self.array_meta;
+        if bbox.offset().len() != array_meta.get_ndim() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong number of dimensions",
+            ));
+        }
+        let shape = match array_meta.get_chunk_memory_layout() {
+            Order::ColumnMajor => bbox.shape_ndarray_shape().f(),
+            Order::RowMajor => bbox.shape_ndarray_shape()[..].into_shape(),
+        };
+        let fill_value = self.convert(array_meta.get_effective_fill_value::<T>()?);
+        let mut arr = Array::from_elem(shape, fill_value);
+        let bbox_offset = GridCoord::from(bbox.offset());
+
+        let mut chunk_buff: Option<VecDataChunk<T>> = None;
+        for coord in array_meta.bounded_coord_iter(bbox) {
+            let grid_pos = GridCoord::from(&coord[..]);
+            let is_chunk = match chunk_buff {
+                None => {
+                    chunk_buff =
+                        self.hierarchy
+                            .read_chunk(&self.path_name, array_meta, grid_pos)?;
+                    chunk_buff.is_some()
+                }
+                Some(ref mut chunk) => self
+                    .hierarchy
+                    .read_chunk_into(&self.path_name, array_meta, grid_pos, chunk)?
+                    .is_some(),
+            };
+            let chunk = match chunk_buff {
+                Some(ref chunk) if is_chunk => chunk,
+                _ => continue,
+            };
+
+            let chunk_bb = chunk.get_bounds(array_meta);
+            let mut read_bb = bbox.clone();
+            read_bb.intersect(&chunk_bb);
+            if read_bb.is_empty() {
+                continue;
+            }
+
+            let arr_slice = (read_bb.clone() - &bbox_offset).to_ndarray_slice();
+            let chunk_slice = (read_bb - &GridCoord::from(chunk_bb.offset())).to_ndarray_slice();
+            let chunk_data = chunk.as_ndarray(array_meta);
+            let chunk_view =
+                chunk_data.slice(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
+            arr.slice_mut(SliceInfo::<_, IxDyn>::new(arr_slice).unwrap().as_ref())
+                .zip_mut_with(&chunk_view, |out, &value| *out = self.convert(value));
+        }
+
+        Ok(arr)
+    }
+}
+
+pub trait ZarrCastReader: HierarchyReader {
+    /// Read an array with its elements converted to another type.
+    fn cast<U: CastElement>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> CastReader<'_, Self, U> {
+        CastReader {
+            hierarchy: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            scale: 1.0,
+            offset: 0.0,
+            element_type: PhantomData,
+        }
+    }
+}
+
+impl<T: HierarchyReader> ZarrCastReader for T {}
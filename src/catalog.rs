@@ -0,0 +1,334 @@
+//! Discovery metadata for publishing datasets to catalogs.
+//!
+//! Catalog metadata is stored in a group's (or array's) attributes using the
+//! field names and layout of a [STAC] collection, which Intake and other
+//! catalog tools also recognize, so a published hierarchy describes itself:
+//!
+//! ```json
+//! {
+//!     "title": "Cell atlas",
+//!     "license": "CC-BY-4.0",
+//!     "extent": {
+//!         "spatial": {"bbox": [[-180.0, -90.0, 180.0, 90.0]]},
+//!         "temporal": {"interval": [["2020-01-01T00:00:00Z", null]]}
+//!     }
+//! }
+//! ```
+//!
+//! [STAC]: https://github.com/radiantearth/stac-spec/blob/master/collection-spec/collection-spec.md
+//!
+//! ```
+//! use zarr::catalog::*;
+//!
+//! fn publish<H: CatalogWriter>(h: &H) -> std::io::Result<()> {
+//!     let catalog = CatalogMetadata {
+//!         title: Some("Cell atlas".to_owned()),
+//!         license: Some("CC-BY-4.0".to_owned()),
+//!         extent: Some(Extent {
+//!             spatial: Some(SpatialExtent::from_bbox(&[-180.0, -90.0, 180.0, 90.0])),
+//!             temporal: Some(TemporalExtent::from_interval(
+//!                 Some("2020-01-01T00:00:00Z".to_owned()),
+//!                 None,
+//!             )),
+//!         }),
+//!         ..Default::default()
+//!     };
+//!     h.set_catalog_metadata("", &catalog)
+//! }
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::{
+    HierarchyReader,
+    HierarchyWriter,
+    JsonObject,
+};
+
+/// Discovery metadata of a dataset. Absent fields are omitted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CatalogMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// SPDX license identifier or expression, e.g. `CC-BY-4.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extent: Option<Extent>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spatial: Option<SpatialExtent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temporal: Option<TemporalExtent>,
+}
+
+/// Bounding boxes of a dataset, the first of which covers all others.
+///
+/// Each box is `[west, south, east, north]`, or
+/// `[west, south, min elevation, east, north, max elevation]`, in WGS 84
+/// longitude and latitude unless the dataset documents otherwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpatialExtent {
+    pub bbox: Vec<Vec<f64>>,
+}
+
+impl SpatialExtent {
+    pub fn from_bbox(bbox: &[f64]) -> SpatialExtent {
+        SpatialExtent {
+            bbox: vec![bbox.to_vec()],
+        }
+    }
+}
+
+/// Time intervals of a dataset, the first of which covers all others.
+///
+/// Each interval is a start and end as RFC 3339 date-times, either of which
+/// may be open-ended.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TemporalExtent {
+    pub interval: Vec<[Option<String>; 2]>,
+}
+
+impl TemporalExtent {
+    pub fn from_interval(start: Option<String>, end: Option<String>) -> TemporalExtent {
+        TemporalExtent {
+            interval: vec![[start, end]],
+        }
+    }
+}
+
+impl CatalogMetadata {
+    /// Read catalog metadata from node attributes, ignoring unrelated
+    /// attributes.
+    pub fn from_attributes(attributes: &JsonObject) -> Result<CatalogMetadata, Error> {
+        let catalog: CatalogMetadata = serde_json::from_value(Value::Object(attributes.clone()))
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Ok(catalog)
+    }
+
+    /// Attributes holding this catalog metadata.
+    pub fn to_attributes(&self) -> Result<JsonObject, Error> {
+        match serde_json::to_value(self)? {
+            Value::Object(attributes) => Ok(attributes),
+            _ => unreachable!("Catalog metadata serializes to an object"),
+        }
+    }
+
+    /// Check extents are well-formed: bounding boxes have 4 or 6
+    /// coordinates with minimums no greater than maximums (except longitude,
+    /// for boxes crossing the antimeridian), and intervals are non-empty,
+    /// have at least one bound and do not end before they start.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidInput, message));
+        let extent = match &self.extent {
+            Some(extent) => extent,
+            None => return Ok(()),
+        };
+
+        if let Some(spatial) = &extent.spatial {
+            if spatial.bbox.is_empty() {
+                return invalid("Spatial extent has no bounding boxes");
+            }
+            for bbox in &spatial.bbox {
+                let (min, max) = match bbox.len() {
+                    4 => bbox.split_at(2),
+                    6 => bbox.split_at(3),
+                    _ => return invalid("Bounding boxes must have 4 or 6 coordinates"),
+                };
+                if min.iter().chain(max).any(|c| !c.is_finite())
+                    || min.iter().zip(max).skip(1).any(|(lo, hi)| lo > hi)
+                {
+                    return invalid("Bounding box minimum exceeds maximum");
+                }
+            }
+        }
+
+        if let Some(temporal) = &extent.temporal {
+            if temporal.interval.is_empty() {
+                return invalid("Temporal extent has no intervals");
+            }
+            for interval in &temporal.interval {
+                match interval {
+                    [None, None] => return invalid("Time interval has no bounds"),
+                    [Some(start), Some(end)] if end < start => {
+                        return invalid("Time interval ends before it starts")
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait CatalogReader: HierarchyReader {
+    /// Read the catalog metadata of a group or array.
+    fn get_catalog_metadata(&self, path_name: &str) -> Result<CatalogMetadata, Error> {
+        CatalogMetadata::from_attributes(&self.list_attributes(path_name)?)
+    }
+}
+
+impl<T: HierarchyReader> CatalogReader for T {}
+
+pub trait CatalogWriter: HierarchyWriter {
+    /// Set the catalog metadata of a group or array after validating it.
+    ///
+    /// Fields absent from `catalog` are left as they are in the node's
+    /// attributes, as are all unrelated attributes.
+    fn set_catalog_metadata(
+        &self,
+        path_name: &str,
+        catalog: &CatalogMetadata,
+    ) -> Result<(), Error> {
+        catalog.validate()?;
+        self.set_attributes(path_name, catalog.to_attributes()?)
+    }
+}
+
+impl<T: HierarchyWriter> CatalogWriter for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> CatalogMetadata {
+        CatalogMetadata {
+            title: Some("Cell atlas".to_owned()),
+            license: Some("CC-BY-4.0".to_owned()),
+            keywords: vec!["microscopy".to_owned()],
+            extent: Some(Extent {
+                spatial: Some(SpatialExtent::from_bbox(&[170.0, -10.0, -170.0, 10.0])),
+                temporal: Some(TemporalExtent::from_interval(
+                    Some("2020-01-01T00:00:00Z".to_owned()),
+                    None,
+                )),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let attributes = catalog().to_attributes().unwrap();
+        assert_eq!(
+            Value::Object(attributes.clone()),
+            serde_json::json!({
+                "title": "Cell atlas",
+                "license": "CC-BY-4.0",
+                "keywords": ["microscopy"],
+                "extent": {
+                    "spatial": {"bbox": [[170.0, -10.0, -170.0, 10.0]]},
+                    "temporal": {"interval": [["2020-01-01T00:00:00Z", null]]},
+                },
+            })
+        );
+        assert_eq!(
+            CatalogMetadata::from_attributes(&attributes).unwrap(),
+            catalog()
+        );
+
+        let mut unrelated = JsonObject::new();
+        unrelated.insert("foo".to_owned(), 1.into());
+        assert_eq!(
+            CatalogMetadata::from_attributes(&unrelated).unwrap(),
+            CatalogMetadata::default()
+        );
+        unrelated.insert("title".to_owned(), 1.into());
+        assert_eq!(
+            CatalogMetadata::from_attributes(&unrelated)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(catalog().validate().is_ok());
+        assert!(CatalogMetadata::default().validate().is_ok());
+
+        let with_extent = |spatial, temporal| CatalogMetadata {
+            extent: Some(Extent { spatial, temporal }),
+            ..Default::default()
+        };
+        assert!(
+            with_extent(Some(SpatialExtent::from_bbox(&[0.0, 1.0, 2.0])), None)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            with_extent(Some(SpatialExtent::from_bbox(&[0.0, 1.0, 2.0, 0.5])), None)
+                .validate()
+                .is_err()
+        );
+        assert!(with_extent(
+            Some(SpatialExtent::from_bbox(&[0.0, 0.0, -5.0, 1.0, 1.0, 5.0])),
+            None
+        )
+        .validate()
+        .is_ok());
+        assert!(
+            with_extent(None, Some(TemporalExtent::from_interval(None, None)))
+                .validate()
+                .is_err()
+        );
+        assert!(with_extent(
+            None,
+            Some(TemporalExtent::from_interval(
+                Some("2021-01-01T00:00:00Z".to_owned()),
+                Some("2020-01-01T00:00:00Z".to_owned()),
+            ))
+        )
+        .validate()
+        .is_err());
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_catalog_metadata() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_catalog_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        h.create_group("atlas").unwrap();
+        h.set_attribute("atlas", "foo".to_owned(), "bar").unwrap();
+
+        h.set_catalog_metadata("atlas", &catalog()).unwrap();
+        assert_eq!(h.get_catalog_metadata("atlas").unwrap(), catalog());
+        assert_eq!(h.list_attributes("atlas").unwrap()["foo"], "bar");
+
+        let update = CatalogMetadata {
+            description: Some("Atlas of cells".to_owned()),
+            ..Default::default()
+        };
+        h.set_catalog_metadata("atlas", &update).unwrap();
+        let updated = h.get_catalog_metadata("atlas").unwrap();
+        assert_eq!(updated.description, update.description);
+        assert_eq!(updated.title, catalog().title);
+
+        let invalid = CatalogMetadata {
+            extent: Some(Extent {
+                spatial: Some(SpatialExtent { bbox: vec![] }),
+                temporal: None,
+            }),
+            ..Default::default()
+        };
+        assert!(h.set_catalog_metadata("atlas", &invalid).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use std::io::{
+    BufRead,
+    BufReader,
     Error,
     ErrorKind,
     Read,
@@ -15,7 +17,15 @@ use byteorder::{
 };
 use half::f16;
 
-use crate::compression::Compression;
+use crate::compression::{
+    max_decompressed_chunk_len,
+    Compression,
+    CompressionType,
+};
+use crate::{
+    config,
+    filter,
+};
 use crate::{
     data_type::Endian,
     ArrayMetadata,
@@ -100,6 +110,80 @@ impl<T: ReflectedType> ReinitDataChunk<T> for VecDataChunk<T> {
     }
 }
 
+/// A data chunk whose elements are allocated with a runtime alignment, such
+/// as the 4096-byte alignment expected for page-locked or DMA transfers.
+///
+/// Alignment is kept when the chunk is reinitialized, so one chunk can be
+/// reused with [`read_chunk_into`](crate::HierarchyReader::read_chunk_into):
+///
+/// ```
+/// # use zarr::prelude::*;
+/// # use zarr::smallvec::smallvec;
+/// use zarr::chunk::AlignedDataChunk;
+///
+/// let mut chunk = AlignedDataChunk::<f32>::with_alignment(4096);
+/// # let array_meta = ArrayMetadataBuilder::new(smallvec![8, 8], f32::ZARR_TYPE).build();
+/// # let h = FilesystemHierarchy::open_or_create(tempdir::TempDir::new("zarr").unwrap().path()).unwrap();
+/// # h.create_array("a", &array_meta).unwrap();
+/// # h.write_chunk("a", &array_meta, &SliceDataChunk::new(smallvec![0, 0], vec![1.0f32; 64])).unwrap();
+/// h.read_chunk_into("a", &array_meta, smallvec![0, 0], &mut chunk).unwrap();
+/// assert_eq!(chunk.get_data().as_ptr() as usize % 4096, 0);
+/// ```
+#[cfg(feature = "aligned_buffers")]
+pub type AlignedDataChunk<T> = SliceDataChunk<T, aligned_vec::AVec<T, aligned_vec::RuntimeAlign>>;
+
+/// A data chunk whose elements are allocated by a user allocator, such as
+/// one returning pinned host memory for GPU uploads.
+#[cfg(feature = "aligned_buffers")]
+pub type AllocatorDataChunk<T, A> = SliceDataChunk<T, allocator_api2::vec::Vec<T, A>>;
+
+#[cfg(feature = "aligned_buffers")]
+impl<T: ReflectedType> AlignedDataChunk<T> {
+    /// Create an empty chunk whose data will be aligned to `align` bytes,
+    /// which must be a power of two.
+    pub fn with_alignment(align: usize) -> Self {
+        SliceDataChunk::new(GridCoord::new(), aligned_vec::AVec::new(align))
+    }
+}
+
+#[cfg(feature = "aligned_buffers")]
+impl<T: ReflectedType, A: allocator_api2::alloc::Allocator> AllocatorDataChunk<T, A> {
+    /// Create an empty chunk whose data will be allocated by `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        SliceDataChunk::new(GridCoord::new(), allocator_api2::vec::Vec::new_in(alloc))
+    }
+}
+
+#[cfg(feature = "aligned_buffers")]
+impl<T: ReflectedType> ReinitDataChunk<T> for AlignedDataChunk<T> {
+    fn reinitialize(&mut self, grid_position: &GridCoord, num_el: u32) {
+        self.grid_position = grid_position.clone();
+        self.data.resize(num_el as usize, T::default());
+    }
+
+    fn reinitialize_with<B: DataChunk<T>>(&mut self, other: &B) {
+        self.grid_position = other.get_grid_position().into();
+        self.data.clear();
+        self.data.extend_from_slice(other.get_data());
+    }
+}
+
+#[cfg(feature = "aligned_buffers")]
+impl<T: ReflectedType, A: allocator_api2::alloc::Allocator> ReinitDataChunk<T>
+    for AllocatorDataChunk<T, A>
+{
+    fn reinitialize(&mut self, grid_position: &GridCoord, num_el: u32) {
+        self.grid_position = grid_position.clone();
+        self.data.resize(num_el as usize, T::default());
+    }
+
+    fn reinitialize_with<B: DataChunk<T>>(&mut self, other: &B) {
+        self.grid_position = other.get_grid_position().into();
+        self.data.clear();
+        self.data.extend_from_slice(other.get_data());
+    }
+}
+
 macro_rules! vec_data_chunk_impl {
     ($ty_name:ty, $bo_read_fn:ident, $bo_write_fn:ident) => {
         impl<C: AsMut<[$ty_name]>> ReadableDataChunk for SliceDataChunk<$ty_name, C> {
@@ -265,6 +349,46 @@ fn check_array_type<T: ReflectedType>(array_meta: &ArrayMetadata) -> Result<()>
     }
 }
 
+/// Decompress and decode the data of the chunk at a grid position from a
+/// reader into a chunk, with the compressor recorded for it if any, and
+/// detecting its compression if the configuration says to.
+pub(crate) fn read_chunk_data<R: Read, B: ReadableDataChunk>(
+    buffer: R,
+    array_meta: &ArrayMetadata,
+    grid_position: &[u64],
+    chunk: &mut B,
+    config: &config::Config,
+) -> Result<()> {
+    let mut buffer = BufReader::new(buffer);
+    let compressor = array_meta.chunk_compressor(grid_position)?;
+    if config.detect_chunk_compression {
+        let compressor = compressor.resolve_for_header(buffer.fill_buf()?);
+        decode_chunk_data(buffer, array_meta, &compressor, chunk)
+    } else {
+        decode_chunk_data(buffer, array_meta, &compressor, chunk)
+    }
+}
+
+fn decode_chunk_data<R: BufRead, B: ReadableDataChunk>(
+    buffer: R,
+    array_meta: &ArrayMetadata,
+    compressor: &CompressionType,
+    chunk: &mut B,
+) -> Result<()> {
+    let data_type = array_meta.data_type.effective_type()?;
+    let encoded_type = filter::encoded_type(&array_meta.filters, &data_type)?;
+    let limit = max_decompressed_chunk_len(array_meta, &encoded_type)?;
+    let mut decompressed = compressor.limited_decoder(buffer, &encoded_type, limit)?;
+    if array_meta.filters.is_empty() {
+        return chunk.read_data(&mut decompressed, array_meta);
+    }
+
+    let mut encoded = Vec::new();
+    decompressed.read_to_end(&mut encoded)?;
+    let data = filter::decode(&array_meta.filters, encoded, &data_type)?;
+    chunk.read_data(&data[..], array_meta)
+}
+
 /// Reads chunks from rust readers.
 pub trait DefaultChunkReader<T: ReflectedType, R: Read> {
     fn read_chunk(
@@ -278,9 +402,14 @@ pub trait DefaultChunkReader<T: ReflectedType, R: Read> {
         check_array_type::<T>(array_meta)?;
 
         let mut chunk =
-            T::create_data_chunk(&grid_position, array_meta.get_chunk_num_elements() as u32);
-        let mut decompressed = array_meta.compressor.decoder(buffer);
-        chunk.read_data(&mut decompressed, array_meta)?;
+            T::create_data_chunk(&grid_position, array_meta.checked_chunk_num_elements()?);
+        read_chunk_data(
+            buffer,
+            array_meta,
+            &grid_position,
+            &mut chunk,
+            &config::config(),
+        )?;
 
         Ok(chunk)
     }
@@ -293,9 +422,8 @@ pub trait DefaultChunkReader<T: ReflectedType, R: Read> {
     ) -> Result<()> {
         check_array_type::<T>(array_meta)?;
 
-        chunk.reinitialize(&grid_position, array_meta.get_chunk_num_elements() as u32);
-        let mut decompressed = array_meta.compressor.decoder(buffer);
-        chunk.read_data(&mut decompressed, array_meta)?;
+        chunk.reinitialize(&grid_position, array_meta.checked_chunk_num_elements()?);
+        read_chunk_data(buffer, array_meta, &grid_position, chunk, &config::config())?;
 
         Ok(())
     }
@@ -303,10 +431,30 @@ pub trait DefaultChunkReader<T: ReflectedType, R: Read> {
 
 /// Writes chunks to rust writers.
 pub trait DefaultChunkWriter<T: ReflectedType, W: Write, B: DataChunk<T> + WriteableDataChunk> {
+    /// Write a chunk with its compressor, which is the array's unless
+    /// another is recorded for it.
     fn write_chunk(buffer: W, array_meta: &ArrayMetadata, chunk: &B) -> Result<()> {
+        let compressor = array_meta.chunk_compressor(chunk.get_grid_position())?;
+        Self::write_chunk_with_compression(buffer, array_meta, chunk, &compressor)
+    }
+
+    /// Write a chunk compressed with a scheme other than the array's.
+    ///
+    /// The choice of compression is not recorded, so such chunks can only be
+    /// read with compression detection enabled
+    /// (see [`Config::detect_chunk_compression`](crate::config::Config)), or
+    /// once recorded as
+    /// [`HierarchyWriter::write_chunk_with_compression`](crate::HierarchyWriter::write_chunk_with_compression)
+    /// does.
+    fn write_chunk_with_compression(
+        buffer: W,
+        array_meta: &ArrayMetadata,
+        chunk: &B,
+        compressor: &CompressionType,
+    ) -> Result<()> {
         check_array_type::<T>(array_meta)?;
 
-        if chunk.get_num_elements() as usize != array_meta.get_chunk_num_elements() {
+        if chunk.get_num_elements() != array_meta.checked_chunk_num_elements()? {
             return Err(Error::new(
                 ErrorKind::InvalidData,
                 format!(
@@ -316,8 +464,18 @@ pub trait DefaultChunkWriter<T: ReflectedType, W: Write, B: DataChunk<T> + Write
                 ),
             ));
         }
-        let mut compressor = array_meta.compressor.encoder(buffer);
-        chunk.write_data(&mut compressor, array_meta)?;
+        let data_type = array_meta.data_type.effective_type()?;
+        if array_meta.filters.is_empty() {
+            let mut compressor = compressor.encoder_typed(buffer, &data_type);
+            chunk.write_data(&mut compressor, array_meta)?;
+        } else {
+            let mut data = Vec::new();
+            chunk.write_data(&mut data, array_meta)?;
+            let encoded = filter::encode(&array_meta.filters, data, &data_type)?;
+            let encoded_type = filter::encoded_type(&array_meta.filters, &data_type)?;
+            let mut compressor = compressor.encoder_typed(buffer, &encoded_type);
+            compressor.write_all(&encoded)?;
+        }
 
         Ok(())
     }
@@ -4,6 +4,7 @@ use std::io::{
     Write,
 };
 
+use lz4::liblz4::BlockChecksum;
 use lz4::{
     BlockMode,
     BlockSize,
@@ -79,13 +80,18 @@ impl Default for Lz4Compression {
 
 impl Compression for Lz4Compression {
     fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
-        Box::new(Decoder::new(r).expect("TODO: LZ4 returns a result here"))
+        // The frame header is read on construction.
+        match Decoder::new(r) {
+            Ok(decoder) => Box::new(decoder),
+            Err(e) => Box::new(super::ErrorReader::new(e)),
+        }
     }
 
     fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a> {
         let encoder = EncoderBuilder::new()
             .block_size(self.get_effective_block_size())
             .block_mode(BlockMode::Independent)
+            .block_checksum(BlockChecksum::NoBlockChecksum)
             .build(w)
             .expect("TODO");
         Box::new(Wrapper { s: Some(encoder) })
@@ -134,4 +140,11 @@ mod tests {
     fn test_rw() {
         crate::tests::test_chunk_compression_rw(CompressionType::Lz4(Lz4Compression::default()));
     }
+
+    #[test]
+    fn test_malformed_header() {
+        let mut decoder = Lz4Compression::default()
+            .decoder(&[0xde, 0xad, 0xbe, 0xef, 0x00, 0x01, 0x02, 0x03][..]);
+        assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+    }
 }
@@ -53,11 +53,11 @@ impl Default for Lz4Compression {
 
 impl Compression for Lz4Compression {
     fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
-        Box::new(
-            LZ4FrameReader::new(r)
-                .expect("TODO: LZ4 returns a result here")
-                .into_read(),
-        )
+        // The frame header is read on construction.
+        match LZ4FrameReader::new(r) {
+            Ok(reader) => Box::new(reader.into_read()),
+            Err(e) => Box::new(super::ErrorReader::new(e)),
+        }
     }
 
     fn encoder<'a, W: Write + 'a>(&self, writer: W) -> Box<dyn Write + 'a> {
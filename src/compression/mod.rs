@@ -1,6 +1,10 @@
 //! Compression for chunk voxel data.
 
+use std::convert::TryFrom;
 use std::io::{
+    BufRead,
+    Error,
+    ErrorKind,
     Read,
     Write,
 };
@@ -10,6 +14,10 @@ use serde::{
     Serialize,
 };
 
+use crate::data_type::DataType;
+use crate::dump::CodecHeader;
+use crate::ArrayMetadata;
+
 #[cfg(feature = "bzip")]
 pub mod bzip;
 #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
@@ -18,19 +26,117 @@ pub mod gzip;
 pub mod lz;
 #[cfg(feature = "lz_pure")]
 pub(self) mod lz_pure;
+#[cfg(feature = "pcodec")]
+pub mod pcodec;
 pub mod raw;
+#[cfg(feature = "snappy")]
+pub mod snappy;
+pub mod suggest;
 #[cfg(feature = "lz_pure")]
 pub mod lz {
     pub use super::lz_pure::*;
 }
 #[cfg(feature = "xz")]
 pub mod xz;
+#[cfg(feature = "zstd")]
+pub mod zstd;
 
 /// Common interface for compressing writers and decompressing readers.
 pub trait Compression: Default {
     fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a>;
 
     fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a>;
+
+    /// Decompressing reader for chunk data of a known (effective) data type.
+    ///
+    /// Codecs which depend on the element type override this; others ignore
+    /// the type and use `decoder`.
+    fn decoder_typed<'a, R: Read + 'a>(&self, r: R, _data_type: &DataType) -> Box<dyn Read + 'a> {
+        self.decoder(r)
+    }
+
+    /// Compressing writer for chunk data of a known (effective) data type.
+    ///
+    /// Codecs which depend on the element type override this; others ignore
+    /// the type and use `encoder`.
+    fn encoder_typed<'a, W: Write + 'a>(&self, w: W, _data_type: &DataType) -> Box<dyn Write + 'a> {
+        self.encoder(w)
+    }
+}
+
+/// Reader which fails with an error, for decoders which could not be created,
+/// for example because a stream header was malformed.
+#[cfg(any(feature = "lz", feature = "lz_pure", feature = "zstd"))]
+pub(crate) struct ErrorReader(Option<std::io::Error>);
+
+#[cfg(any(feature = "lz", feature = "lz_pure", feature = "zstd"))]
+impl ErrorReader {
+    pub(crate) fn new<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> Self {
+        ErrorReader(Some(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            error,
+        )))
+    }
+}
+
+#[cfg(any(feature = "lz", feature = "lz_pure", feature = "zstd"))]
+impl Read for ErrorReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(self
+            .0
+            .take()
+            .unwrap_or_else(|| std::io::ErrorKind::InvalidData.into()))
+    }
+}
+
+/// Reader failing with [`ErrorKind::InvalidData`] once its decompressing
+/// reader yields more than a limit of bytes.
+pub(crate) struct LimitedDecoder<R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<R: Read> Read for LimitedDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Read up to one byte past the limit to tell streams ending at the
+        // limit from those exceeding it.
+        let len = usize::try_from(self.remaining.saturating_add(1))
+            .map_or(buf.len(), |len| len.min(buf.len()));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n as u64 > self.remaining {
+            return Err(decompressed_too_large(None, self.limit));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn decompressed_too_large(declared: Option<u64>, limit: u64) -> Error {
+    let message = match declared {
+        Some(declared) => format!(
+            "Chunk header declares {} decompressed bytes, more than the limit of {}",
+            declared, limit
+        ),
+        None => format!(
+            "Chunk decompresses to more than the limit of {} bytes",
+            limit
+        ),
+    };
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Maximum number of bytes a chunk of an array may decompress to, as given
+/// to its compressor when written, that is still filtered.
+///
+/// See [`Config::max_decompressed_chunk_ratio`](crate::config::Config::max_decompressed_chunk_ratio).
+pub(crate) fn max_decompressed_chunk_len(
+    array_meta: &ArrayMetadata,
+    encoded_type: &DataType,
+) -> std::io::Result<u64> {
+    let chunk_len =
+        u64::from(array_meta.checked_chunk_num_elements()?) * encoded_type.size_of() as u64;
+    Ok(chunk_len.saturating_mul(crate::config::config().max_decompressed_chunk_ratio))
 }
 
 /// Enumeration of known compression schemes.
@@ -47,7 +153,14 @@ pub enum CompressionType {
     #[cfg(any(feature = "lz", feature = "lz_pure"))]
     Lz4(lz::Lz4Compression),
     #[cfg(feature = "xz")]
+    #[serde(alias = "lzma")]
     Xz(xz::XzCompression),
+    #[cfg(feature = "snappy")]
+    Snappy(snappy::SnappyCompression),
+    #[cfg(feature = "pcodec")]
+    Pcodec(pcodec::PcodecCompression),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::ZstdCompression),
 }
 
 impl CompressionType {
@@ -61,6 +174,103 @@ impl CompressionType {
     pub fn is_default(&self) -> bool {
         *self == Self::default()
     }
+
+    /// Detect the compression of a chunk from the magic bytes at its start.
+    ///
+    /// Only compression schemes with distinctive headers which are enabled
+    /// in this build are detected, with their default configuration. Raw data
+    /// is never detected, though raw data may by chance begin with a magic
+    /// sequence of another scheme.
+    pub fn detect(header: &[u8]) -> Option<CompressionType> {
+        #[cfg(feature = "bzip")]
+        {
+            if header.starts_with(b"BZh") {
+                return Some(Self::new::<bzip::Bzip2Compression>());
+            }
+        }
+        #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+        {
+            if header.starts_with(&[0x1f, 0x8b]) {
+                return Some(Self::new::<gzip::GzipCompression>());
+            }
+        }
+        #[cfg(feature = "xz")]
+        {
+            if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+                return Some(Self::new::<xz::XzCompression>());
+            }
+        }
+        #[cfg(any(feature = "lz", feature = "lz_pure"))]
+        {
+            if header.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+                return Some(Self::new::<lz::Lz4Compression>());
+            }
+        }
+        #[cfg(feature = "snappy")]
+        {
+            if header.starts_with(&[0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y']) {
+                return Some(Self::new::<snappy::SnappyCompression>());
+            }
+        }
+        #[cfg(feature = "pcodec")]
+        {
+            if header.starts_with(b"pco!") {
+                return Some(Self::new::<pcodec::PcodecCompression>());
+            }
+        }
+        #[cfg(feature = "zstd")]
+        {
+            if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+                return Some(Self::new::<zstd::ZstdCompression>());
+            }
+        }
+        let _ = header;
+        None
+    }
+
+    /// Choose the compression to read a chunk with, preferring a detected
+    /// scheme over this one if the chunk's header indicates a different
+    /// scheme.
+    pub(crate) fn resolve_for_header(&self, header: &[u8]) -> CompressionType {
+        match Self::detect(header) {
+            Some(detected) if std::mem::discriminant(&detected) != std::mem::discriminant(self) => {
+                detected
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl CompressionType {
+    /// Decompressing reader for chunk data failing once more than `limit`
+    /// bytes are decompressed, or before decompressing if the stream's
+    /// header declares more.
+    pub(crate) fn limited_decoder<'a, R: BufRead + 'a>(
+        &self,
+        mut r: R,
+        data_type: &DataType,
+        limit: u64,
+    ) -> std::io::Result<LimitedDecoder<Box<dyn Read + 'a>>> {
+        // Headers cut short by a short read are only checked as far as
+        // they go, the limit on the decompressed stream still applying.
+        // Headers of other formats are ignored, since for example raw
+        // chunks may begin with any bytes.
+        let header = r.fill_buf()?;
+        let declared = match Self::detect(header) {
+            Some(detected) if std::mem::discriminant(&detected) == std::mem::discriminant(self) => {
+                CodecHeader::parse(header).and_then(|header| header.content_size())
+            }
+            _ => None,
+        };
+        if let Some(declared) = declared.filter(|&declared| declared > limit) {
+            return Err(decompressed_too_large(Some(declared), limit));
+        }
+        Ok(LimitedDecoder {
+            inner: self.decoder_typed(r, data_type),
+            remaining: limit,
+            limit,
+        })
+    }
 }
 
 impl Default for CompressionType {
@@ -85,6 +295,15 @@ impl Compression for CompressionType {
 
             #[cfg(any(feature = "lz", feature = "lz_pure"))]
             CompressionType::Lz4(ref c) => c.decoder(r),
+
+            #[cfg(feature = "snappy")]
+            CompressionType::Snappy(ref c) => c.decoder(r),
+
+            #[cfg(feature = "pcodec")]
+            CompressionType::Pcodec(ref c) => c.decoder(r),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(ref c) => c.decoder(r),
         }
     }
 
@@ -103,6 +322,69 @@ impl Compression for CompressionType {
 
             #[cfg(any(feature = "lz", feature = "lz_pure"))]
             CompressionType::Lz4(ref c) => c.encoder(w),
+
+            #[cfg(feature = "snappy")]
+            CompressionType::Snappy(ref c) => c.encoder(w),
+
+            #[cfg(feature = "pcodec")]
+            CompressionType::Pcodec(ref c) => c.encoder(w),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(ref c) => c.encoder(w),
+        }
+    }
+
+    fn decoder_typed<'a, R: Read + 'a>(&self, r: R, data_type: &DataType) -> Box<dyn Read + 'a> {
+        match *self {
+            CompressionType::Raw(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(feature = "bzip")]
+            CompressionType::Bzip2(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+            CompressionType::Gzip(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(feature = "xz")]
+            CompressionType::Xz(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(any(feature = "lz", feature = "lz_pure"))]
+            CompressionType::Lz4(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(feature = "snappy")]
+            CompressionType::Snappy(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(feature = "pcodec")]
+            CompressionType::Pcodec(ref c) => c.decoder_typed(r, data_type),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(ref c) => c.decoder_typed(r, data_type),
+        }
+    }
+
+    fn encoder_typed<'a, W: Write + 'a>(&self, w: W, data_type: &DataType) -> Box<dyn Write + 'a> {
+        match *self {
+            CompressionType::Raw(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(feature = "bzip")]
+            CompressionType::Bzip2(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+            CompressionType::Gzip(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(feature = "xz")]
+            CompressionType::Xz(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(any(feature = "lz", feature = "lz_pure"))]
+            CompressionType::Lz4(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(feature = "snappy")]
+            CompressionType::Snappy(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(feature = "pcodec")]
+            CompressionType::Pcodec(ref c) => c.encoder_typed(w, data_type),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(ref c) => c.encoder_typed(w, data_type),
         }
     }
 }
@@ -126,6 +408,15 @@ impl std::fmt::Display for CompressionType {
 
                 #[cfg(any(feature = "lz", feature = "lz_pure"))]
                 CompressionType::Lz4(_) => "Lz4",
+
+                #[cfg(feature = "snappy")]
+                CompressionType::Snappy(_) => "Snappy",
+
+                #[cfg(feature = "pcodec")]
+                CompressionType::Pcodec(_) => "Pcodec",
+
+                #[cfg(feature = "zstd")]
+                CompressionType::Zstd(_) => "Zstd",
             }
         )
     }
@@ -145,11 +436,20 @@ impl std::str::FromStr for CompressionType {
             "gzip" => Ok(Self::new::<gzip::GzipCompression>()),
 
             #[cfg(feature = "xz")]
-            "xz" => Ok(Self::new::<xz::XzCompression>()),
+            "xz" | "lzma" => Ok(Self::new::<xz::XzCompression>()),
 
             #[cfg(feature = "lz")]
             "lz4" => Ok(Self::new::<lz::Lz4Compression>()),
 
+            #[cfg(feature = "snappy")]
+            "snappy" => Ok(Self::new::<snappy::SnappyCompression>()),
+
+            #[cfg(feature = "pcodec")]
+            "pcodec" => Ok(Self::new::<pcodec::PcodecCompression>()),
+
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(Self::new::<zstd::ZstdCompression>()),
+
             _ => Err(std::io::ErrorKind::InvalidInput.into()),
         }
     }
@@ -174,3 +474,9 @@ compression_from_impl!(Gzip, gzip::GzipCompression);
 compression_from_impl!(Xz, xz::XzCompression);
 #[cfg(any(feature = "lz", feature = "lz_pure"))]
 compression_from_impl!(Lz4, lz::Lz4Compression);
+#[cfg(feature = "snappy")]
+compression_from_impl!(Snappy, snappy::SnappyCompression);
+#[cfg(feature = "pcodec")]
+compression_from_impl!(Pcodec, pcodec::PcodecCompression);
+#[cfg(feature = "zstd")]
+compression_from_impl!(Zstd, zstd::ZstdCompression);
@@ -0,0 +1,409 @@
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+
+use byteorder::{
+    BigEndian,
+    ByteOrder,
+    LittleEndian,
+};
+use pco::data_types::Number;
+use pco::standalone::{
+    simple_compress,
+    FileDecompressor,
+};
+use pco::{
+    ChunkConfig,
+    DeltaSpec,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::Compression;
+use crate::data_type::{
+    DataType,
+    Endian,
+    FloatSize,
+    IntSize,
+};
+
+/// Delta encoding strategy, using numcodecs' names.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PcodecDeltaSpec {
+    /// Let pcodec detect a good delta encoding.
+    #[default]
+    Auto,
+    /// Never delta encode.
+    None,
+    /// Try consecutive deltas of order `delta_encoding_order`.
+    TryConsecutive,
+    /// Try delta encoding against earlier elements.
+    TryLookback,
+}
+
+/// Pcodec compression for numeric chunks, compatible with numcodecs'
+/// `pcodec` codec.
+///
+/// Pcodec compresses sequences of numbers rather than bytes, so it requires
+/// the chunk's data type and only supports integer and 32- and 64-bit float
+/// types.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct PcodecCompression {
+    /// Compression level in [0, 12].
+    #[serde(default = "default_pcodec_level")]
+    pub level: usize,
+    #[serde(default)]
+    pub delta_spec: PcodecDeltaSpec,
+    /// Delta order in [0, 7] for [`PcodecDeltaSpec::TryConsecutive`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_encoding_order: Option<usize>,
+}
+
+fn default_pcodec_level() -> usize {
+    pco::DEFAULT_COMPRESSION_LEVEL
+}
+
+impl Default for PcodecCompression {
+    fn default() -> PcodecCompression {
+        PcodecCompression {
+            level: default_pcodec_level(),
+            delta_spec: PcodecDeltaSpec::default(),
+            delta_encoding_order: None,
+        }
+    }
+}
+
+impl PcodecCompression {
+    fn chunk_config(&self) -> ChunkConfig {
+        let delta_spec = match self.delta_spec {
+            PcodecDeltaSpec::Auto => DeltaSpec::Auto,
+            PcodecDeltaSpec::None => DeltaSpec::NoOp,
+            PcodecDeltaSpec::TryConsecutive => {
+                DeltaSpec::TryConsecutive(self.delta_encoding_order.unwrap_or(1))
+            }
+            PcodecDeltaSpec::TryLookback => DeltaSpec::TryLookback,
+        };
+        ChunkConfig::default()
+            .with_compression_level(self.level)
+            .with_delta_spec(delta_spec)
+            .with_enable_8_bit(true)
+    }
+}
+
+/// Numbers pcodec can compress, with conversion from and to chunk bytes.
+trait PcodecNumber: Number {
+    fn read_into(src: &[u8], endian: Endian, dst: &mut [Self]);
+
+    fn write_into(src: &[Self], endian: Endian, dst: &mut [u8]);
+}
+
+macro_rules! pcodec_number_impl {
+    ($ty_name:ty, $bo_read_fn:ident, $bo_write_fn:ident) => {
+        impl PcodecNumber for $ty_name {
+            fn read_into(src: &[u8], endian: Endian, dst: &mut [Self]) {
+                match endian {
+                    Endian::Big => BigEndian::$bo_read_fn(src, dst),
+                    Endian::Little => LittleEndian::$bo_read_fn(src, dst),
+                }
+            }
+
+            fn write_into(src: &[Self], endian: Endian, dst: &mut [u8]) {
+                match endian {
+                    Endian::Big => BigEndian::$bo_write_fn(src, dst),
+                    Endian::Little => LittleEndian::$bo_write_fn(src, dst),
+                }
+            }
+        }
+    };
+}
+
+pcodec_number_impl!(u16, read_u16_into, write_u16_into);
+pcodec_number_impl!(u32, read_u32_into, write_u32_into);
+pcodec_number_impl!(u64, read_u64_into, write_u64_into);
+pcodec_number_impl!(i16, read_i16_into, write_i16_into);
+pcodec_number_impl!(i32, read_i32_into, write_i32_into);
+pcodec_number_impl!(i64, read_i64_into, write_i64_into);
+pcodec_number_impl!(f32, read_f32_into, write_f32_into);
+pcodec_number_impl!(f64, read_f64_into, write_f64_into);
+
+impl PcodecNumber for u8 {
+    fn read_into(src: &[u8], _endian: Endian, dst: &mut [Self]) {
+        dst.copy_from_slice(src);
+    }
+
+    fn write_into(src: &[Self], _endian: Endian, dst: &mut [u8]) {
+        dst.copy_from_slice(src);
+    }
+}
+
+impl PcodecNumber for i8 {
+    fn read_into(src: &[u8], _endian: Endian, dst: &mut [Self]) {
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d = *s as i8;
+        }
+    }
+
+    fn write_into(src: &[Self], _endian: Endian, dst: &mut [u8]) {
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d = *s as u8;
+        }
+    }
+}
+
+/// Bound on preallocated decompressed bytes per compressed byte.
+const MAX_PREALLOC_RATIO: usize = 64;
+
+fn compress_as<T: PcodecNumber>(
+    bytes: &[u8],
+    config: &ChunkConfig,
+    endian: Endian,
+) -> std::io::Result<Vec<u8>> {
+    let size = std::mem::size_of::<T>();
+    if !bytes.len().is_multiple_of(size) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Chunk data is not a whole number of elements",
+        ));
+    }
+    let mut nums = vec![T::default(); bytes.len() / size];
+    T::read_into(bytes, endian, &mut nums);
+    simple_compress(&nums, config).map_err(Error::other)
+}
+
+fn decompress_as<T: PcodecNumber>(compressed: &[u8], endian: Endian) -> std::io::Result<Vec<u8>> {
+    let invalid = |e| Error::new(ErrorKind::InvalidData, e);
+    let (decompressor, body) = FileDecompressor::new(compressed).map_err(invalid)?;
+    // The element count in the header is untrusted, so do not let it reserve
+    // more memory than the compressed data could plausibly expand to.
+    let nums = decompressor
+        .with_max_prealloc(compressed.len().saturating_mul(MAX_PREALLOC_RATIO))
+        .simple_decompress::<T>(body)
+        .map_err(invalid)?;
+    let mut bytes = vec![0; nums.len() * std::mem::size_of::<T>()];
+    T::write_into(&nums, endian, &mut bytes);
+    Ok(bytes)
+}
+
+macro_rules! pcodec_type_match {
+    ($data_type:expr, $fn:ident ( $($arg:expr),* )) => {
+        match *$data_type {
+            DataType::UInt { size: IntSize::B1, endian } => $fn::<u8>($($arg,)* endian),
+            DataType::UInt { size: IntSize::B2, endian } => $fn::<u16>($($arg,)* endian),
+            DataType::UInt { size: IntSize::B4, endian } => $fn::<u32>($($arg,)* endian),
+            DataType::UInt { size: IntSize::B8, endian } => $fn::<u64>($($arg,)* endian),
+            DataType::Int { size: IntSize::B1, endian } => $fn::<i8>($($arg,)* endian),
+            DataType::Int { size: IntSize::B2, endian } => $fn::<i16>($($arg,)* endian),
+            DataType::Int { size: IntSize::B4, endian } => $fn::<i32>($($arg,)* endian),
+            DataType::Int { size: IntSize::B8, endian } => $fn::<i64>($($arg,)* endian),
+            DataType::Float { size: FloatSize::B4, endian } => $fn::<f32>($($arg,)* endian),
+            DataType::Float { size: FloatSize::B8, endian } => $fn::<f64>($($arg,)* endian),
+            ref other => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Pcodec does not support data type {}", other),
+            )),
+        }
+    };
+}
+
+fn compress_data(
+    bytes: &[u8],
+    config: &ChunkConfig,
+    data_type: &DataType,
+) -> std::io::Result<Vec<u8>> {
+    pcodec_type_match!(data_type, compress_as(bytes, config))
+}
+
+fn decompress_data(compressed: &[u8], data_type: &DataType) -> std::io::Result<Vec<u8>> {
+    pcodec_type_match!(data_type, decompress_as(compressed))
+}
+
+fn untyped_error() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "Pcodec requires the data type of the chunk",
+    )
+}
+
+/// Reads and decompresses the whole stream on first read.
+struct PcodecDecoder<R> {
+    inner: Option<R>,
+    data_type: Option<DataType>,
+    decoded: Cursor<Vec<u8>>,
+}
+
+impl<R: Read> Read for PcodecDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(mut inner) = self.inner.take() {
+            let data_type = self.data_type.ok_or_else(untyped_error)?;
+            let mut compressed = Vec::new();
+            inner.read_to_end(&mut compressed)?;
+            self.decoded = Cursor::new(decompress_data(&compressed, &data_type)?);
+        }
+        self.decoded.read(buf)
+    }
+}
+
+/// Buffers all chunk data and compresses it when dropped.
+struct PcodecEncoder<W: Write> {
+    inner: W,
+    data_type: Option<DataType>,
+    config: ChunkConfig,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Write for PcodecEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.data_type.is_none() {
+            return Err(untyped_error());
+        }
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for PcodecEncoder<W> {
+    fn drop(&mut self) {
+        // As with other compressing writers, errors finishing the stream
+        // on drop are not reported.
+        if let Some(data_type) = self.data_type {
+            if let Ok(compressed) = compress_data(&self.buffer, &self.config, &data_type) {
+                let _ = self.inner.write_all(&compressed);
+            }
+        }
+    }
+}
+
+impl Compression for PcodecCompression {
+    fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
+        Box::new(PcodecDecoder {
+            inner: Some(r),
+            data_type: None,
+            decoded: Cursor::new(Vec::new()),
+        })
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a> {
+        Box::new(PcodecEncoder {
+            inner: w,
+            data_type: None,
+            config: self.chunk_config(),
+            buffer: Vec::new(),
+        })
+    }
+
+    fn decoder_typed<'a, R: Read + 'a>(&self, r: R, data_type: &DataType) -> Box<dyn Read + 'a> {
+        Box::new(PcodecDecoder {
+            inner: Some(r),
+            data_type: Some(*data_type),
+            decoded: Cursor::new(Vec::new()),
+        })
+    }
+
+    fn encoder_typed<'a, W: Write + 'a>(&self, w: W, data_type: &DataType) -> Box<dyn Write + 'a> {
+        Box::new(PcodecEncoder {
+            inner: w,
+            data_type: Some(*data_type),
+            config: self.chunk_config(),
+            buffer: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionType;
+    use crate::ReflectedType;
+
+    #[test]
+    fn test_rw() {
+        crate::tests::test_chunk_compression_rw(CompressionType::Pcodec(
+            PcodecCompression::default(),
+        ));
+        crate::tests::test_chunk_compression_rw(CompressionType::Pcodec(PcodecCompression {
+            level: 3,
+            delta_spec: PcodecDeltaSpec::TryConsecutive,
+            delta_encoding_order: Some(2),
+        }));
+    }
+
+    #[test]
+    fn test_types() {
+        let compression = PcodecCompression::default();
+        let data: Vec<u8> = (0..64u8).collect();
+
+        for data_type in &[
+            u8::ZARR_TYPE,
+            i16::ZARR_TYPE,
+            u32::ZARR_TYPE,
+            DataType::Int {
+                size: IntSize::B8,
+                endian: Endian::Little,
+            },
+            f32::ZARR_TYPE,
+            f64::ZARR_TYPE,
+        ] {
+            let mut compressed = Vec::new();
+            compression
+                .encoder_typed(&mut compressed, data_type)
+                .write_all(&data)
+                .unwrap();
+            let mut decompressed = Vec::new();
+            compression
+                .decoder_typed(&compressed[..], data_type)
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, data, "{}", data_type);
+        }
+
+        assert!(compression
+            .encoder_typed(Vec::new(), &bool::ZARR_TYPE)
+            .write_all(&data)
+            .is_ok());
+        assert!(compression
+            .decoder_typed(&[0u8; 4][..], &bool::ZARR_TYPE)
+            .read_to_end(&mut Vec::new())
+            .is_err());
+        assert!(compression
+            .decoder(&[0u8; 4][..])
+            .read_to_end(&mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_numcodecs_config() {
+        let compression: CompressionType = serde_json::from_str(
+            r#"{
+                "codec": "pcodec",
+                "configuration": {
+                    "level": 4,
+                    "mode_spec": "auto",
+                    "delta_spec": "try_consecutive",
+                    "paging_spec": "equal_pages_up_to",
+                    "delta_encoding_order": 1,
+                    "equal_pages_up_to": 262144
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            compression,
+            CompressionType::Pcodec(PcodecCompression {
+                level: 4,
+                delta_spec: PcodecDeltaSpec::TryConsecutive,
+                delta_encoding_order: Some(1),
+            })
+        );
+    }
+}
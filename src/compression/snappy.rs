@@ -0,0 +1,73 @@
+use std::io::{
+    Read,
+    Write,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use snap::read::FrameDecoder;
+use snap::write::FrameEncoder;
+
+use super::Compression;
+
+/// Snappy compression using the framed stream format, as written by the
+/// Hadoop/Spark and Java snappy framing implementations.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct SnappyCompression {}
+
+impl Compression for SnappyCompression {
+    fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
+        Box::new(FrameDecoder::new(r))
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a> {
+        Box::new(FrameEncoder::new(w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionType;
+
+    // Example from the zarr documentation spec. Snappy stores the chunk
+    // uncompressed since it is too small to benefit from compression.
+    #[rustfmt::skip]
+    const TEST_CHUNK_I16_SNAPPY: [u8; 30] = [
+        // Stream identifier.
+        0xff, 0x06, 0x00, 0x00,
+        0x73, 0x4e, 0x61, 0x50,
+        0x70, 0x59,
+        // Uncompressed chunk header and masked CRC-32C.
+        0x01, 0x10, 0x00, 0x00,
+        0xd5, 0x14, 0xb5, 0xa7,
+        0x00, 0x01, 0x00, 0x02,
+        0x00, 0x03, 0x00, 0x04,
+        0x00, 0x05, 0x00, 0x06,
+    ];
+
+    #[test]
+    fn test_read_doc_spec_chunk() {
+        crate::tests::test_read_doc_spec_chunk(
+            TEST_CHUNK_I16_SNAPPY.as_ref(),
+            CompressionType::Snappy(SnappyCompression::default()),
+        );
+    }
+
+    #[test]
+    fn test_write_doc_spec_chunk() {
+        crate::tests::test_write_doc_spec_chunk(
+            TEST_CHUNK_I16_SNAPPY.as_ref(),
+            CompressionType::Snappy(SnappyCompression::default()),
+        );
+    }
+
+    #[test]
+    fn test_rw() {
+        crate::tests::test_chunk_compression_rw(CompressionType::Snappy(
+            SnappyCompression::default(),
+        ));
+    }
+}
@@ -0,0 +1,400 @@
+//! Choosing a compressor for an array by trialling candidates on samples of
+//! its chunks.
+//!
+//! ```
+//! use zarr::compression::suggest::{
+//!     default_candidates,
+//!     suggest_compressor,
+//! };
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//!
+//! let data_type = u16::ZARR_TYPE;
+//! let samples: Vec<Vec<u8>> = (0..4u16)
+//!     .map(|i| (0..4096u16).flat_map(|j| (i * j / 64).to_le_bytes()).collect())
+//!     .collect();
+//! let report = suggest_compressor(&samples, &data_type, &default_candidates(&data_type)).unwrap();
+//! for trial in report.get_trials() {
+//!     println!("{}: {:.2}x", trial.compressor, trial.ratio());
+//! }
+//! let array_meta = report
+//!     .apply_to(ArrayMetadataBuilder::new(smallvec![4, 4096], data_type))
+//!     .build();
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use super::{
+    raw,
+    Compression,
+    CompressionType,
+};
+use crate::data_type::DataType;
+use crate::storage::{
+    read_decompressed_chunk,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    ArrayMetadataBuilder,
+    Hierarchy,
+};
+
+/// Outcome of compressing and decompressing all samples with one candidate.
+#[derive(Clone, Debug)]
+pub struct CompressorTrial {
+    pub compressor: CompressionType,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    pub encode_time: Duration,
+    pub decode_time: Duration,
+}
+
+impl CompressorTrial {
+    /// Uncompressed size over compressed size.
+    pub fn ratio(&self) -> f64 {
+        self.uncompressed_bytes as f64 / self.compressed_bytes.max(1) as f64
+    }
+
+    /// Compression speed in uncompressed bytes per second.
+    pub fn encode_throughput(&self) -> f64 {
+        throughput(self.uncompressed_bytes, self.encode_time)
+    }
+
+    /// Decompression speed in uncompressed bytes per second.
+    pub fn decode_throughput(&self) -> f64 {
+        throughput(self.uncompressed_bytes, self.decode_time)
+    }
+}
+
+fn throughput(bytes: u64, time: Duration) -> f64 {
+    bytes as f64 / time.as_secs_f64().max(f64::MIN_POSITIVE)
+}
+
+/// Trials of all candidates, from smallest to largest compressed size.
+#[derive(Clone, Debug)]
+pub struct CompressorReport {
+    trials: Vec<CompressorTrial>,
+}
+
+impl CompressorReport {
+    pub fn get_trials(&self) -> &[CompressorTrial] {
+        &self.trials
+    }
+
+    /// The candidate which compressed the samples smallest.
+    pub fn smallest(&self) -> &CompressorTrial {
+        &self.trials[0]
+    }
+
+    /// The candidate fastest to compress and decompress the samples among
+    /// those compressing to within a fraction `tolerance` of the smallest
+    /// size, e.g. 0.1 to accept results 10% larger than the smallest.
+    pub fn fastest_within(&self, tolerance: f64) -> &CompressorTrial {
+        let limit = self.smallest().compressed_bytes as f64 * (1.0 + tolerance);
+        self.trials
+            .iter()
+            .filter(|trial| trial.compressed_bytes as f64 <= limit)
+            .min_by_key(|trial| trial.encode_time + trial.decode_time)
+            .unwrap_or_else(|| self.smallest())
+    }
+
+    /// Set the compressor of an array builder to the smallest candidate.
+    pub fn apply_to(&self, builder: ArrayMetadataBuilder) -> ArrayMetadataBuilder {
+        builder.compressor(self.smallest().compressor.clone())
+    }
+}
+
+/// Candidate compressors for a data type: no compression, then each enabled
+/// codec at a fast and a strong level.
+#[cfg_attr(not(feature = "pcodec"), allow(unused_variables))]
+pub fn default_candidates(data_type: &DataType) -> Vec<CompressionType> {
+    // Only pushed to if any codec is enabled.
+    #[allow(unused_mut)]
+    let mut candidates = vec![CompressionType::Raw(raw::RawCompression)];
+    #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+    for level in [1, 9] {
+        candidates.push(CompressionType::Gzip(super::gzip::GzipCompression {
+            level,
+            ..Default::default()
+        }));
+    }
+    #[cfg(feature = "bzip")]
+    candidates.push(CompressionType::new::<super::bzip::Bzip2Compression>());
+    #[cfg(any(feature = "lz", feature = "lz_pure"))]
+    candidates.push(CompressionType::new::<super::lz::Lz4Compression>());
+    #[cfg(feature = "xz")]
+    for preset in [1, 9] {
+        candidates.push(CompressionType::Xz(super::xz::XzCompression::new(
+            preset,
+            Default::default(),
+            Default::default(),
+        )));
+    }
+    #[cfg(feature = "snappy")]
+    candidates.push(CompressionType::new::<super::snappy::SnappyCompression>());
+    #[cfg(feature = "pcodec")]
+    {
+        use crate::data_type::FloatSize;
+        if matches!(
+            data_type,
+            DataType::Int { .. }
+                | DataType::UInt { .. }
+                | DataType::Float {
+                    size: FloatSize::B4 | FloatSize::B8,
+                    ..
+                }
+        ) {
+            candidates.push(CompressionType::new::<super::pcodec::PcodecCompression>());
+        }
+    }
+    #[cfg(feature = "zstd")]
+    for level in [1, 19] {
+        candidates.push(CompressionType::Zstd(super::zstd::ZstdCompression::new(
+            level,
+        )));
+    }
+    candidates
+}
+
+/// Compress and decompress samples of uncompressed chunk data of a data type
+/// with each candidate compressor.
+///
+/// Fails if there are no samples or candidates, or if any candidate fails to
+/// compress the samples or to decompress them to the original bytes.
+pub fn suggest_compressor<T: AsRef<[u8]>>(
+    samples: &[T],
+    data_type: &DataType,
+    candidates: &[CompressionType],
+) -> Result<CompressorReport, Error> {
+    if samples.is_empty() || candidates.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Compressor suggestion needs samples and candidates",
+        ));
+    }
+
+    let mut trials = candidates
+        .iter()
+        .map(|compressor| trial(compressor, samples, data_type))
+        .collect::<Result<Vec<_>, Error>>()?;
+    trials.sort_by_key(|trial| trial.compressed_bytes);
+    Ok(CompressorReport { trials })
+}
+
+/// Suggest a compressor for an existing array from a sample of its chunks,
+/// as [`suggest_compressor`]. Absent chunks are skipped, and grid positions
+/// outside the array are an error.
+///
+/// Samples are the chunks' bytes after any filters, so compressors are tried
+/// with the filters' encoded data type.
+pub fn suggest_compressor_for_array<S, I, C>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    grid_positions: I,
+    candidates: &[CompressionType],
+) -> Result<CompressorReport, Error>
+where
+    S: ReadableStore + Hierarchy,
+    I: IntoIterator<Item = C>,
+    C: AsRef<[u64]>,
+{
+    let mut samples = Vec::new();
+    for grid_position in grid_positions {
+        if let Some(sample) =
+            read_decompressed_chunk(store, path_name, array_meta, grid_position.as_ref())?
+        {
+            samples.push(sample);
+        }
+    }
+    let data_type = array_meta.get_data_type().effective_type()?;
+    let encoded_type = crate::filter::encoded_type(&array_meta.filters, &data_type)?;
+    suggest_compressor(&samples, &encoded_type, candidates)
+}
+
+fn trial<T: AsRef<[u8]>>(
+    compressor: &CompressionType,
+    samples: &[T],
+    data_type: &DataType,
+) -> Result<CompressorTrial, Error> {
+    let mut trial = CompressorTrial {
+        compressor: compressor.clone(),
+        uncompressed_bytes: 0,
+        compressed_bytes: 0,
+        encode_time: Duration::ZERO,
+        decode_time: Duration::ZERO,
+    };
+    for sample in samples {
+        let sample = sample.as_ref();
+        let start = Instant::now();
+        let mut compressed = Vec::new();
+        {
+            let mut encoder = compressor.encoder_typed(&mut compressed, data_type);
+            encoder.write_all(sample)?;
+            encoder.flush()?;
+        }
+        trial.encode_time += start.elapsed();
+
+        let start = Instant::now();
+        let mut decompressed = Vec::with_capacity(sample.len());
+        compressor
+            .decoder_typed(&compressed[..], data_type)
+            .read_to_end(&mut decompressed)?;
+        trial.decode_time += start.elapsed();
+        if decompressed != sample {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} did not decompress to the original data", compressor),
+            ));
+        }
+
+        trial.uncompressed_bytes += sample.len() as u64;
+        trial.compressed_bytes += compressed.len() as u64;
+    }
+    Ok(trial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        (0..4u16)
+            .map(|i| {
+                (0..4096u16)
+                    .flat_map(|j| (i * 1000 + j / 16).to_le_bytes())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_suggest_compressor() {
+        let data_type = u16::ZARR_TYPE;
+        let candidates = default_candidates(&data_type);
+        let report = suggest_compressor(&samples(), &data_type, &candidates).unwrap();
+        let trials = report.get_trials();
+        assert_eq!(trials.len(), candidates.len());
+        assert!(trials
+            .windows(2)
+            .all(|w| w[0].compressed_bytes <= w[1].compressed_bytes));
+        assert!(trials
+            .iter()
+            .all(|trial| trial.uncompressed_bytes == 4 * 4096 * 2));
+
+        assert_ne!(
+            report.smallest().compressor,
+            CompressionType::Raw(raw::RawCompression)
+        );
+        assert!(report.smallest().ratio() > 1.0);
+        let fastest = report.fastest_within(0.0);
+        assert_eq!(fastest.compressed_bytes, report.smallest().compressed_bytes);
+        assert!(report.fastest_within(f64::INFINITY).compressed_bytes <= 4 * 4096 * 2);
+
+        let array_meta = report
+            .apply_to(ArrayMetadataBuilder::new(smallvec![4, 4096], data_type))
+            .build();
+        assert_eq!(array_meta.get_compressor(), &report.smallest().compressor);
+
+        let no_samples: &[Vec<u8>] = &[];
+        assert!(suggest_compressor(no_samples, &data_type, &candidates).is_err());
+        assert!(suggest_compressor(&samples(), &data_type, &[]).is_err());
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_suggest_compressor_for_array() {
+        let dir = tempdir::TempDir::new("rust_zarr_suggest_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4096], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![1, 4096])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        for (i, sample) in samples().into_iter().enumerate() {
+            let data: Vec<u16> = sample
+                .chunks(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            h.write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(smallvec![i as u64, 0], data),
+            )
+            .unwrap();
+        }
+
+        let candidates = vec![
+            CompressionType::Raw(raw::RawCompression),
+            array_meta.get_compressor().clone(),
+        ];
+        let positions: Vec<[u64; 2]> = (0..4).map(|i| [i, 0]).collect();
+        let report =
+            suggest_compressor_for_array(&h, "a", &array_meta, &positions, &candidates).unwrap();
+        let raw = report
+            .get_trials()
+            .iter()
+            .find(|trial| trial.compressor == CompressionType::Raw(raw::RawCompression))
+            .unwrap();
+        assert_eq!(raw.uncompressed_bytes, 4 * 4096 * 2);
+        assert_eq!(raw.compressed_bytes, raw.uncompressed_bytes);
+
+        let beyond: Vec<[u64; 2]> = (0..8).map(|i| [i, 0]).collect();
+        let err =
+            suggest_compressor_for_array(&h, "a", &array_meta, &beyond, &candidates).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        h.delete_chunk("a", &array_meta, &[3, 0]).unwrap();
+        let report =
+            suggest_compressor_for_array(&h, "a", &array_meta, &positions, &candidates).unwrap();
+        assert_eq!(report.get_trials()[0].uncompressed_bytes, 3 * 4096 * 2);
+    }
+
+    #[cfg(all(feature = "filesystem", feature = "pcodec"))]
+    #[test]
+    fn test_suggest_compressor_for_filtered_array() {
+        use crate::filter::{
+            astype::AsTypeFilter,
+            FilterType,
+        };
+
+        let dir = tempdir::TempDir::new("rust_zarr_suggest_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let pcodec = CompressionType::new::<crate::compression::pcodec::PcodecCompression>();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![2, 1024], f64::ZARR_TYPE)
+            .chunk_shape(smallvec![1, 1024])
+            .filters(vec![FilterType::AsType(AsTypeFilter::new(
+                f32::ZARR_TYPE,
+                f64::ZARR_TYPE,
+            ))])
+            .compressor(pcodec.clone())
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        for i in 0..2 {
+            let data: Vec<f64> = (0..1024).map(|j| f64::from(j) * 0.5 + i as f64).collect();
+            h.write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(smallvec![i, 0], data),
+            )
+            .unwrap();
+        }
+
+        let candidates = vec![CompressionType::Raw(raw::RawCompression), pcodec];
+        let report =
+            suggest_compressor_for_array(&h, "a", &array_meta, [[0, 0], [1, 0]], &candidates)
+                .unwrap();
+        for trial in report.get_trials() {
+            assert_eq!(trial.uncompressed_bytes, 2 * 1024 * 4);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::io::{
     Read,
     Write,
@@ -5,40 +6,183 @@ use std::io::{
 
 use serde::{
     Deserialize,
+    Deserializer,
     Serialize,
 };
 use xz2::read::XzDecoder;
+use xz2::stream::{
+    Check,
+    LzmaOptions,
+    Stream,
+};
 use xz2::write::XzEncoder;
 
 use super::Compression;
 
+/// Flag which may be combined with a preset level for slower, possibly
+/// better compression.
+pub const PRESET_EXTREME: u32 = 1 << 31;
+
+/// Container format, using numcodecs' (and Python `lzma`'s) numbering.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum XzFormat {
+    /// `.xz` container. numcodecs' `FORMAT_XZ`.
+    #[default]
+    Xz,
+    /// Legacy `.lzma` container, which has no integrity check.
+    /// numcodecs' `FORMAT_ALONE`.
+    Alone,
+}
+
+impl TryFrom<i32> for XzFormat {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(XzFormat::Xz),
+            2 => Ok(XzFormat::Alone),
+            3 => Err("Raw LZMA format is not supported".to_owned()),
+            _ => Err(format!("Unknown LZMA format: {}", value)),
+        }
+    }
+}
+
+impl From<XzFormat> for i32 {
+    fn from(format: XzFormat) -> i32 {
+        match format {
+            XzFormat::Xz => 1,
+            XzFormat::Alone => 2,
+        }
+    }
+}
+
+/// Integrity check of `.xz` streams, using numcodecs' numbering.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum XzCheck {
+    /// The format's default, CRC64 for `.xz`.
+    #[default]
+    Default,
+    None,
+    Crc32,
+    Crc64,
+    Sha256,
+}
+
+impl TryFrom<i32> for XzCheck {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            -1 => Ok(XzCheck::Default),
+            0 => Ok(XzCheck::None),
+            1 => Ok(XzCheck::Crc32),
+            4 => Ok(XzCheck::Crc64),
+            10 => Ok(XzCheck::Sha256),
+            _ => Err(format!("Unknown LZMA check: {}", value)),
+        }
+    }
+}
+
+impl From<XzCheck> for i32 {
+    fn from(check: XzCheck) -> i32 {
+        match check {
+            XzCheck::Default => -1,
+            XzCheck::None => 0,
+            XzCheck::Crc32 => 1,
+            XzCheck::Crc64 => 4,
+            XzCheck::Sha256 => 10,
+        }
+    }
+}
+
+impl From<XzCheck> for Check {
+    fn from(check: XzCheck) -> Check {
+        match check {
+            XzCheck::None => Check::None,
+            XzCheck::Crc32 => Check::Crc32,
+            XzCheck::Default | XzCheck::Crc64 => Check::Crc64,
+            XzCheck::Sha256 => Check::Sha256,
+        }
+    }
+}
+
+/// Xz/LZMA compression, compatible with numcodecs' `lzma` codec.
+///
+/// Custom filter chains and the raw format are not supported.
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct XzCompression {
-    #[serde(default = "default_xz_preset")]
-    preset: i32,
+    /// Preset level in [0, 9], optionally combined with [`PRESET_EXTREME`].
+    #[serde(default = "default_xz_preset", deserialize_with = "deserialize_preset")]
+    preset: u32,
+    #[serde(default, skip_serializing_if = "is_default")]
+    format: XzFormat,
+    /// Ignored for the [`XzFormat::Alone`] format, which has no check.
+    #[serde(default, skip_serializing_if = "is_default")]
+    check: XzCheck,
 }
 
-fn default_xz_preset() -> i32 {
+fn default_xz_preset() -> u32 {
     6
 }
 
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// numcodecs writes a `null` preset for the default.
+fn deserialize_preset<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let preset = Option::<u32>::deserialize(deserializer)?.unwrap_or_else(default_xz_preset);
+    if preset & !PRESET_EXTREME > 9 {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid LZMA preset: {}",
+            preset
+        )));
+    }
+    Ok(preset)
+}
+
+impl XzCompression {
+    pub fn new(preset: u32, format: XzFormat, check: XzCheck) -> XzCompression {
+        assert!(preset & !PRESET_EXTREME <= 9, "Invalid LZMA preset");
+        XzCompression {
+            preset,
+            format,
+            check,
+        }
+    }
+}
+
 impl Default for XzCompression {
     fn default() -> XzCompression {
         XzCompression {
             preset: default_xz_preset(),
+            format: XzFormat::default(),
+            check: XzCheck::default(),
         }
     }
 }
 
 impl Compression for XzCompression {
     fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
-        Box::new(XzDecoder::new(r))
+        let stream = match self.format {
+            XzFormat::Xz => Stream::new_stream_decoder(u64::MAX, 0),
+            XzFormat::Alone => Stream::new_lzma_decoder(u64::MAX),
+        }
+        .expect("Failed to create LZMA decoder");
+        Box::new(XzDecoder::new_stream(r, stream))
     }
 
     fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a> {
-        // TODO: check that preset is non-negative.s
-        Box::new(XzEncoder::new(w, self.preset as u32))
+        let stream = match self.format {
+            XzFormat::Xz => Stream::new_easy_encoder(self.preset, self.check.into()),
+            XzFormat::Alone => LzmaOptions::new_preset(self.preset)
+                .and_then(|options| Stream::new_lzma_encoder(&options)),
+        }
+        .expect("Failed to create LZMA encoder");
+        Box::new(XzEncoder::new_stream(w, stream))
     }
 }
 
@@ -74,6 +218,18 @@ mod tests {
         0x00, 0x04, 0x59, 0x5a,
     ];
 
+    #[rustfmt::skip]
+    const TEST_ALONE_PYTHON: [u8; 29] = [
+        0x5d, 0x00, 0x00, 0x80,
+        0x00, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff,
+        0xff, 0x00, 0x00, 0x80,
+        0x9d, 0x61, 0xd3, 0x0d,
+        0x6c, 0x1b, 0xc6, 0x37,
+        0xff, 0xf8, 0xff, 0xa0,
+        0x00,
+    ];
+
     #[test]
     fn test_read_doc_spec_chunk() {
         crate::tests::test_read_doc_spec_chunk(
@@ -93,5 +249,55 @@ mod tests {
     #[test]
     fn test_rw() {
         crate::tests::test_chunk_compression_rw(CompressionType::Xz(XzCompression::default()));
+        crate::tests::test_chunk_compression_rw(CompressionType::Xz(XzCompression::new(
+            1 | PRESET_EXTREME,
+            XzFormat::Xz,
+            XzCheck::Sha256,
+        )));
+        crate::tests::test_chunk_compression_rw(CompressionType::Xz(XzCompression::new(
+            3,
+            XzFormat::Alone,
+            XzCheck::Default,
+        )));
+    }
+
+    #[test]
+    fn test_numcodecs_config() {
+        let compression: CompressionType = serde_json::from_str(
+            r#"{
+                "codec": "lzma",
+                "configuration": {"format": 2, "check": -1, "preset": null, "filters": null}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            compression,
+            CompressionType::Xz(XzCompression::new(6, XzFormat::Alone, XzCheck::Default))
+        );
+
+        let default: CompressionType =
+            serde_json::from_str(r#"{"codec": "xz", "configuration": {}}"#).unwrap();
+        assert_eq!(default, CompressionType::Xz(XzCompression::default()));
+        assert_eq!(
+            serde_json::to_value(&default).unwrap(),
+            serde_json::json!({"codec": "xz", "configuration": {"preset": 6}})
+        );
+
+        for invalid in &[r#"{"format": 3}"#, r#"{"check": 2}"#, r#"{"preset": 10}"#] {
+            let json = format!(r#"{{"codec": "lzma", "configuration": {}}}"#, invalid);
+            assert!(serde_json::from_str::<CompressionType>(&json).is_err());
+        }
+    }
+
+    /// Streams written by Python's `lzma` module, which numcodecs uses:
+    /// `lzma.compress(bytes(range(1, 7)), format=lzma.FORMAT_ALONE)`.
+    #[test]
+    fn test_read_python_alone() {
+        let mut decoded = Vec::new();
+        XzCompression::new(6, XzFormat::Alone, XzCheck::Default)
+            .decoder(&TEST_ALONE_PYTHON[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, [1, 2, 3, 4, 5, 6]);
     }
 }
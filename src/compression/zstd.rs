@@ -0,0 +1,312 @@
+use std::io::{
+    BufReader,
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    Compression,
+    ErrorReader,
+};
+use crate::storage::{
+    read_decompressed_chunk,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    Hierarchy,
+};
+
+/// Zstandard compression, optionally with a dictionary.
+///
+/// Dictionaries capture what the chunks of an array have in common, so
+/// greatly improve compression of arrays of many small chunks, which on their
+/// own have too little data to compress well. A dictionary is trained from a
+/// sample of chunks with [`train_dictionary`] and is stored in the array's
+/// compressor configuration, so it is available to every reader of the
+/// array.
+///
+/// ```
+/// use zarr::compression::zstd::ZstdCompression;
+///
+/// let samples: Vec<Vec<u8>> = (0..1000u32)
+///     .map(|i| format!("{{\"id\": {}, \"label\": \"cell\", \"valid\": true}}", i).into_bytes())
+///     .collect();
+/// let dictionary = ZstdCompression::train(&samples, 1024).unwrap();
+/// let compression = ZstdCompression::new(3).with_dictionary(dictionary);
+/// ```
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ZstdCompression {
+    #[serde(default = "default_zstd_level")]
+    level: i32,
+    /// Dictionary, encoded as base64 in the configuration.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "base64_dictionary"
+    )]
+    dictionary: Option<Vec<u8>>,
+}
+
+fn default_zstd_level() -> i32 {
+    3
+}
+
+mod base64_dictionary {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{
+        Deserialize,
+        Deserializer,
+        Serializer,
+    };
+
+    pub fn serialize<S: Serializer>(
+        dictionary: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match dictionary {
+            Some(dictionary) => serializer.serialize_str(&STANDARD.encode(dictionary)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| STANDARD.decode(encoded).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+impl ZstdCompression {
+    /// Compression at a level in [1, 22], or negative for faster
+    /// compression.
+    pub fn new(level: i32) -> ZstdCompression {
+        ZstdCompression {
+            level,
+            dictionary: None,
+        }
+    }
+
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> ZstdCompression {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    pub fn get_level(&self) -> i32 {
+        self.level
+    }
+
+    pub fn get_dictionary(&self) -> Option<&[u8]> {
+        self.dictionary.as_deref()
+    }
+
+    /// Train a dictionary of at most `max_size` bytes from samples of
+    /// uncompressed data.
+    ///
+    /// Training needs many samples, typically hundreds, totalling around
+    /// 100 times the dictionary size.
+    pub fn train<T: AsRef<[u8]>>(samples: &[T], max_size: usize) -> Result<Vec<u8>, Error> {
+        zstd::dict::from_samples(samples, max_size)
+    }
+}
+
+impl Default for ZstdCompression {
+    fn default() -> ZstdCompression {
+        ZstdCompression::new(default_zstd_level())
+    }
+}
+
+impl Compression for ZstdCompression {
+    fn decoder<'a, R: Read + 'a>(&self, r: R) -> Box<dyn Read + 'a> {
+        let decoder = zstd::stream::read::Decoder::with_dictionary(
+            BufReader::new(r),
+            self.dictionary.as_deref().unwrap_or_default(),
+        );
+        match decoder {
+            Ok(decoder) => Box::new(decoder),
+            Err(e) => Box::new(ErrorReader::new(e)),
+        }
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: W) -> Box<dyn Write + 'a> {
+        Box::new(
+            zstd::stream::write::Encoder::with_dictionary(
+                w,
+                self.level,
+                self.dictionary.as_deref().unwrap_or_default(),
+            )
+            .expect("Failed to create zstd encoder")
+            .auto_finish(),
+        )
+    }
+}
+
+/// Train a dictionary of at most `max_size` bytes from chunks of an existing
+/// array.
+///
+/// Chunks are decompressed with the array's compressor, so the dictionary is
+/// trained on the bytes a zstd compressor of the array would be given.
+/// Absent chunks are skipped.
+pub fn train_dictionary<S, I, C>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    grid_positions: I,
+    max_size: usize,
+) -> Result<Vec<u8>, Error>
+where
+    S: ReadableStore + Hierarchy,
+    I: IntoIterator<Item = C>,
+    C: AsRef<[u64]>,
+{
+    let mut samples = Vec::new();
+    for grid_position in grid_positions {
+        if let Some(sample) =
+            read_decompressed_chunk(store, path_name, array_meta, grid_position.as_ref())?
+        {
+            samples.push(sample);
+        }
+    }
+
+    if samples.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "No chunks to train a dictionary from",
+        ));
+    }
+    ZstdCompression::train(&samples, max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionType;
+
+    fn samples() -> Vec<Vec<u8>> {
+        (0..1000u32)
+            .map(|i| {
+                format!(
+                    "{{\"id\": {}, \"label\": \"cell\", \"area\": {}, \"valid\": true}}",
+                    i,
+                    i * 7 % 113
+                )
+                .into_bytes()
+            })
+            .collect()
+    }
+
+    fn compressed_len(compression: &ZstdCompression, value: &[u8]) -> usize {
+        let mut compressed = Vec::new();
+        {
+            let mut encoder = compression.encoder(&mut compressed);
+            encoder.write_all(value).unwrap();
+        }
+        let mut decompressed = Vec::new();
+        compression
+            .decoder(&compressed[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, value);
+        compressed.len()
+    }
+
+    #[test]
+    fn test_rw() {
+        crate::tests::test_chunk_compression_rw(CompressionType::Zstd(ZstdCompression::default()));
+        let dictionary = ZstdCompression::train(&samples(), 1024).unwrap();
+        crate::tests::test_chunk_compression_rw(CompressionType::Zstd(
+            ZstdCompression::new(19).with_dictionary(dictionary),
+        ));
+    }
+
+    #[test]
+    fn test_dictionary_improves_ratio() {
+        let samples = samples();
+        let dictionary = ZstdCompression::train(&samples[..900], 1024).unwrap();
+        let plain = ZstdCompression::default();
+        let trained = ZstdCompression::default().with_dictionary(dictionary);
+        let plain_len: usize = samples[900..]
+            .iter()
+            .map(|s| compressed_len(&plain, s))
+            .sum();
+        let trained_len: usize = samples[900..]
+            .iter()
+            .map(|s| compressed_len(&trained, s))
+            .sum();
+        assert!(trained_len * 2 < plain_len);
+
+        // Values compressed with a dictionary can not be read without it.
+        let mut compressed = Vec::new();
+        trained
+            .encoder(&mut compressed)
+            .write_all(&samples[0])
+            .unwrap();
+        let mut decompressed = Vec::new();
+        assert!(plain
+            .decoder(&compressed[..])
+            .read_to_end(&mut decompressed)
+            .is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let dictionary = vec![0x37, 0xa4, 0x30, 0xec, 1, 2, 3];
+        let compression =
+            CompressionType::Zstd(ZstdCompression::new(5).with_dictionary(dictionary));
+        let json = serde_json::to_value(&compression).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "codec": "zstd",
+                "configuration": {"level": 5, "dictionary": "N6Qw7AECAw=="}
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<CompressionType>(json).unwrap(),
+            compression
+        );
+        assert_eq!(
+            serde_json::to_value(CompressionType::new::<ZstdCompression>()).unwrap(),
+            serde_json::json!({"codec": "zstd", "configuration": {"level": 3}})
+        );
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_train_dictionary_from_chunks() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_zstd_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![1000, 64], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![1, 64])
+            .compressor(CompressionType::Raw(Default::default()))
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        for (i, sample) in samples().into_iter().enumerate() {
+            let mut data = sample;
+            data.resize(64, b' ');
+            h.write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(smallvec![i as u64, 0], data),
+            )
+            .unwrap();
+        }
+
+        let positions: Vec<[u64; 2]> = (0..1000).map(|i| [i, 0]).collect();
+        let dictionary = train_dictionary(&h, "a", &array_meta, &positions, 1024).unwrap();
+        assert!(!dictionary.is_empty() && dictionary.len() <= 1024);
+        assert!(train_dictionary(&h, "a", &array_meta, vec![[2000, 0]], 1024).is_err());
+    }
+}
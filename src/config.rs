@@ -0,0 +1,218 @@
+//! Crate-wide defaults for array creation and IO.
+//!
+//! Applications can set policy once with [`set_config`] instead of
+//! repeating it at every array creation site. The initial configuration is
+//! read from the environment:
+//!
+//! - `ZARR_DEFAULT_COMPRESSOR`: name of the default compressor, e.g. `gzip`.
+//! - `ZARR_CHUNK_TARGET_BYTES`: target uncompressed chunk size in bytes.
+//! - `ZARR_CONCURRENCY`: maximum number of threads for parallel IO.
+//! - `ZARR_DETECT_CHUNK_COMPRESSION`: `true` to detect chunks whose
+//!   compression differs from their array's metadata.
+//! - `ZARR_MATERIALIZE_IMPLICIT_GROUPS`: `true` to write metadata for the
+//!   implicit parent groups of created nodes.
+//! - `ZARR_MAX_DECOMPRESSED_CHUNK_RATIO`: maximum size of a decompressed
+//!   chunk as a multiple of its array's chunk size.
+//! - `ZARR_HASH_ALGORITHM`: name of the hash algorithm, e.g. `sha256`.
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::sync::{
+    Arc,
+    OnceLock,
+    RwLock,
+};
+
+use crate::compression::CompressionType;
+use crate::hash::HashAlgorithm;
+
+const DEFAULT_COMPRESSOR_VAR: &str = "ZARR_DEFAULT_COMPRESSOR";
+const CHUNK_TARGET_BYTES_VAR: &str = "ZARR_CHUNK_TARGET_BYTES";
+const CONCURRENCY_VAR: &str = "ZARR_CONCURRENCY";
+const DETECT_CHUNK_COMPRESSION_VAR: &str = "ZARR_DETECT_CHUNK_COMPRESSION";
+const MATERIALIZE_IMPLICIT_GROUPS_VAR: &str = "ZARR_MATERIALIZE_IMPLICIT_GROUPS";
+const MAX_DECOMPRESSED_CHUNK_RATIO_VAR: &str = "ZARR_MAX_DECOMPRESSED_CHUNK_RATIO";
+const HASH_ALGORITHM_VAR: &str = "ZARR_HASH_ALGORITHM";
+
+/// Crate-wide defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Compressor for arrays created without an explicit compressor.
+    pub default_compressor: CompressionType,
+    /// Target uncompressed size in bytes of automatically chosen chunks.
+    pub chunk_target_bytes: usize,
+    /// Maximum number of threads used by operations which parallelize IO.
+    pub concurrency: usize,
+    /// Whether to read chunks with the compression indicated by their magic
+    /// bytes when it differs from the array's metadata, for example in
+    /// stores whose compressor changed after some chunks were written.
+    ///
+    /// See [`CompressionType::detect`].
+    pub detect_chunk_compression: bool,
+    /// Whether creating a group or array also writes group metadata for
+    /// each of its parents which has none, so that readers requiring
+    /// explicit groups, such as some written for object stores, can
+    /// traverse to it.
+    pub materialize_implicit_groups: bool,
+    /// Maximum number of bytes a chunk may decompress to, as a multiple of
+    /// the size of a chunk declared by its array's metadata.
+    ///
+    /// Chunks exceeding it, or whose compression header declares a larger
+    /// size, fail to read before the memory is allocated, so that a
+    /// malicious store cannot exhaust the memory of a server reading it.
+    pub max_decompressed_chunk_ratio: u64,
+    /// Hash algorithm of operations which compare or address values by
+    /// their contents, such as [`sync_prefix_by_digest`](crate::sync::sync_prefix_by_digest).
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_compressor: CompressionType::default(),
+            chunk_target_bytes: 1 << 20,
+            concurrency: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            detect_chunk_compression: false,
+            materialize_implicit_groups: false,
+            max_decompressed_chunk_ratio: 2,
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Create a configuration from defaults overridden by any environment
+    /// variables that are set.
+    pub fn from_env() -> Result<Config, Error> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Config, Error> {
+        fn invalid(name: &str, value: &str) -> Error {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid value for {}: {}", name, value),
+            )
+        }
+
+        let mut config = Config::default();
+
+        if let Some(value) = var(DEFAULT_COMPRESSOR_VAR) {
+            config.default_compressor = value
+                .parse()
+                .map_err(|_| invalid(DEFAULT_COMPRESSOR_VAR, &value))?;
+        }
+        if let Some(value) = var(CHUNK_TARGET_BYTES_VAR) {
+            config.chunk_target_bytes = match value.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(invalid(CHUNK_TARGET_BYTES_VAR, &value)),
+            };
+        }
+        if let Some(value) = var(CONCURRENCY_VAR) {
+            config.concurrency = match value.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(invalid(CONCURRENCY_VAR, &value)),
+            };
+        }
+
+        if let Some(value) = var(DETECT_CHUNK_COMPRESSION_VAR) {
+            config.detect_chunk_compression = value
+                .parse()
+                .map_err(|_| invalid(DETECT_CHUNK_COMPRESSION_VAR, &value))?;
+        }
+        if let Some(value) = var(MATERIALIZE_IMPLICIT_GROUPS_VAR) {
+            config.materialize_implicit_groups = value
+                .parse()
+                .map_err(|_| invalid(MATERIALIZE_IMPLICIT_GROUPS_VAR, &value))?;
+        }
+        if let Some(value) = var(MAX_DECOMPRESSED_CHUNK_RATIO_VAR) {
+            config.max_decompressed_chunk_ratio = match value.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(invalid(MAX_DECOMPRESSED_CHUNK_RATIO_VAR, &value)),
+            };
+        }
+        if let Some(value) = var(HASH_ALGORITHM_VAR) {
+            config.hash_algorithm = value
+                .parse()
+                .map_err(|_| invalid(HASH_ALGORITHM_VAR, &value))?;
+        }
+
+        Ok(config)
+    }
+}
+
+fn global() -> &'static RwLock<Arc<Config>> {
+    static CONFIG: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+    // Invalid environment values fall back to defaults rather than panicking
+    // in library code. Use `Config::from_env` directly to surface them.
+    CONFIG.get_or_init(|| RwLock::new(Arc::new(Config::from_env().unwrap_or_default())))
+}
+
+/// Get the current crate-wide configuration.
+///
+/// The configuration is shared rather than copied, so that getting it for
+/// each chunk read does not copy its compressors. It is unaffected by later
+/// calls to [`set_config`].
+pub fn config() -> Arc<Config> {
+    global().read().expect("Config lock poisoned").clone()
+}
+
+/// Replace the crate-wide configuration.
+pub fn set_config(config: Config) {
+    *global().write().expect("Config lock poisoned") = Arc::new(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_vars() {
+        let config = Config::from_vars(|_| None).unwrap();
+        assert_eq!(config, Config::default());
+
+        let config = Config::from_vars(|name| match name {
+            DEFAULT_COMPRESSOR_VAR => Some("RAW".to_owned()),
+            CHUNK_TARGET_BYTES_VAR => Some("4096".to_owned()),
+            CONCURRENCY_VAR => Some("3".to_owned()),
+            DETECT_CHUNK_COMPRESSION_VAR => Some("true".to_owned()),
+            MATERIALIZE_IMPLICIT_GROUPS_VAR => Some("true".to_owned()),
+            MAX_DECOMPRESSED_CHUNK_RATIO_VAR => Some("8".to_owned()),
+            HASH_ALGORITHM_VAR => Some("FNV1A".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(config.default_compressor, CompressionType::default());
+        assert_eq!(config.chunk_target_bytes, 4096);
+        assert_eq!(config.concurrency, 3);
+        assert!(config.detect_chunk_compression);
+        assert!(config.materialize_implicit_groups);
+        assert_eq!(config.max_decompressed_chunk_ratio, 8);
+        assert_eq!(config.hash_algorithm, HashAlgorithm::Fnv1a);
+
+        assert!(Config::from_vars(|name| match name {
+            DEFAULT_COMPRESSOR_VAR => Some("foo".to_owned()),
+            _ => None,
+        })
+        .is_err());
+        assert!(Config::from_vars(|name| match name {
+            CONCURRENCY_VAR => Some("0".to_owned()),
+            _ => None,
+        })
+        .is_err());
+        assert!(Config::from_vars(|name| match name {
+            MAX_DECOMPRESSED_CHUNK_RATIO_VAR => Some("0".to_owned()),
+            _ => None,
+        })
+        .is_err());
+        assert!(Config::from_vars(|name| match name {
+            HASH_ALGORITHM_VAR => Some("md5".to_owned()),
+            _ => None,
+        })
+        .is_err());
+    }
+}
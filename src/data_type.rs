@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::io::Write;
 
 use half::f16;
@@ -6,6 +7,7 @@ use serde::{
     Serialize,
 };
 
+use crate::fill_value::FillValue;
 use crate::{
     GridCoord,
     MetadataError,
@@ -156,8 +158,10 @@ impl Serialize for DataType {
                 std::str::from_utf8(&buf[..3]).unwrap()
             }
             Raw { size } => {
-                write!(&mut buf[..], "r{}", size).expect("TODO");
-                std::str::from_utf8(&buf[..]).unwrap()
+                let mut cursor = &mut buf[..];
+                write!(cursor, "r{}", size).expect("usize fits in buffer");
+                let len = 32 - cursor.len();
+                std::str::from_utf8(&buf[..len]).unwrap()
             }
         };
         serializer.serialize_str(s)
@@ -206,27 +210,31 @@ impl<'de> serde::de::Visitor<'de> for DataTypeVisitor {
                 }
             }
             dtype if dtype.len() == 3 => {
+                let invalid =
+                    || serde::de::Error::invalid_value(serde::de::Unexpected::Str(value), &self);
                 let mut chars = dtype.chars();
-                let endian = Endian::deserial_char(chars.next().unwrap()).expect("TODO");
-                match chars.next().unwrap() {
-                    'i' => {
-                        let size = IntSize::deserial_char(chars.next().unwrap()).unwrap();
-                        DataType::Int { size, endian }
-                    }
-                    'u' => {
-                        let size = IntSize::deserial_char(chars.next().unwrap()).unwrap();
-                        DataType::UInt { size, endian }
-                    }
-                    'f' => {
-                        let size = FloatSize::deserial_char(chars.next().unwrap()).unwrap();
-                        DataType::Float { size, endian }
-                    }
-                    _ => {
-                        return Err(serde::de::Error::invalid_value(
-                            serde::de::Unexpected::Str(value),
-                            &self,
-                        ))
-                    }
+                let (endian, kind, size) = match (
+                    chars.next().and_then(Endian::deserial_char),
+                    chars.next(),
+                    chars.next(),
+                ) {
+                    (Some(endian), Some(kind), Some(size)) => (endian, kind, size),
+                    _ => return Err(invalid()),
+                };
+                match kind {
+                    'i' => DataType::Int {
+                        size: IntSize::deserial_char(size).ok_or_else(invalid)?,
+                        endian,
+                    },
+                    'u' => DataType::UInt {
+                        size: IntSize::deserial_char(size).ok_or_else(invalid)?,
+                        endian,
+                    },
+                    'f' => DataType::Float {
+                        size: FloatSize::deserial_char(size).ok_or_else(invalid)?,
+                        endian,
+                    },
+                    _ => return Err(invalid()),
                 }
             }
             _ => {
@@ -304,7 +312,9 @@ impl ExtensibleDataType {
             Extended {
                 fallback: Some(d), ..
             } => Ok(*d),
-            _ => todo!(),
+            Extended { extension, .. } => {
+                Err(MetadataError::UnknownDataTypeExtension(extension.clone()))
+            }
         }
     }
 }
@@ -460,6 +470,11 @@ pub trait ReflectedType:
 {
     const ZARR_TYPE: DataType;
 
+    /// Convert a typed fill value to an element of this type.
+    fn from_fill_value(fill_value: &FillValue) -> std::io::Result<Self> {
+        Ok(serde_json::from_value(fill_value.to_json())?)
+    }
+
     fn create_data_chunk(grid_position: &GridCoord, num_el: u32) -> VecDataChunk<Self> {
         VecDataChunk::<Self>::new(
             grid_position.clone(),
@@ -474,6 +489,33 @@ macro_rules! reflected_type {
             const ZARR_TYPE: DataType = $d_name;
         }
     };
+    ($d_name:expr, $d_type:ty, $from_fill_value:expr) => {
+        impl ReflectedType for $d_type {
+            const ZARR_TYPE: DataType = $d_name;
+
+            fn from_fill_value(fill_value: &FillValue) -> std::io::Result<Self> {
+                $from_fill_value(fill_value)
+            }
+        }
+    };
+}
+
+/// Floats also accept non-finite fill values, which JSON numbers cannot be.
+fn float_fill_value<T>(fill_value: &FillValue, from_f64: fn(f64) -> T) -> std::io::Result<T> {
+    match *fill_value {
+        FillValue::Float(f) => Ok(from_f64(f)),
+        FillValue::Int(i) => Ok(from_f64(i as f64)),
+        FillValue::UInt(u) => Ok(from_f64(u as f64)),
+        _ => Err(MetadataError::UnexpectedType(fill_value.to_json()).into()),
+    }
+}
+
+fn raw_fill_value<const N: usize>(fill_value: &FillValue) -> std::io::Result<[u8; N]> {
+    match fill_value {
+        FillValue::Raw(bytes) => <[u8; N]>::try_from(bytes.as_slice())
+            .map_err(|_| MetadataError::UnexpectedType(fill_value.to_json()).into()),
+        _ => Err(MetadataError::UnexpectedType(fill_value.to_json()).into()),
+    }
 }
 
 #[rustfmt::skip] reflected_type!(DataType::Bool, bool);
@@ -485,15 +527,15 @@ macro_rules! reflected_type {
 #[rustfmt::skip] reflected_type!(DataType::Int {size: IntSize::B2, endian: NATIVE_ENDIAN}, i16);
 #[rustfmt::skip] reflected_type!(DataType::Int {size: IntSize::B4, endian: NATIVE_ENDIAN}, i32);
 #[rustfmt::skip] reflected_type!(DataType::Int {size: IntSize::B8, endian: NATIVE_ENDIAN}, i64);
-#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B2, endian: NATIVE_ENDIAN}, f16);
-#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B4, endian: NATIVE_ENDIAN}, f32);
-#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B8, endian: NATIVE_ENDIAN}, f64);
+#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B2, endian: NATIVE_ENDIAN}, f16, |f| float_fill_value(f, f16::from_f64));
+#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B4, endian: NATIVE_ENDIAN}, f32, |f| float_fill_value(f, |f| f as f32));
+#[rustfmt::skip] reflected_type!(DataType::Float {size: FloatSize::B8, endian: NATIVE_ENDIAN}, f64, |f| float_fill_value(f, |f| f));
 
 // TODO: As example
-#[rustfmt::skip] reflected_type!(DataType::Raw {size: 8}, [u8; 1]);
-#[rustfmt::skip] reflected_type!(DataType::Raw {size: 16}, [u8; 2]);
-#[rustfmt::skip] reflected_type!(DataType::Raw {size: 24}, [u8; 3]);
-#[rustfmt::skip] reflected_type!(DataType::Raw {size: 32}, [u8; 4]);
+#[rustfmt::skip] reflected_type!(DataType::Raw {size: 8}, [u8; 1], raw_fill_value);
+#[rustfmt::skip] reflected_type!(DataType::Raw {size: 16}, [u8; 2], raw_fill_value);
+#[rustfmt::skip] reflected_type!(DataType::Raw {size: 24}, [u8; 3], raw_fill_value);
+#[rustfmt::skip] reflected_type!(DataType::Raw {size: 32}, [u8; 4], raw_fill_value);
 
 #[cfg(test)]
 mod tests {
@@ -522,4 +564,36 @@ mod tests {
         test_data_type_reflection::<[u8; 3]>();
         test_data_type_reflection::<[u8; 4]>();
     }
+
+    #[test]
+    fn test_data_type_serialization() {
+        for dtype in &["bool", "i1", "u1", "<i2", ">u8", "<f2", ">f8", "r8", "r24"] {
+            let parsed: DataType = serde_json::from_str(&format!("\"{}\"", dtype)).unwrap();
+            assert_eq!(
+                serde_json::to_string(&parsed).unwrap(),
+                format!("\"{}\"", dtype)
+            );
+        }
+
+        for invalid in &["", "r", "r7", "<i3", "<x4", "?i4", "<é", "é1", "<f1", "<u"] {
+            assert!(serde_json::from_str::<DataType>(&format!("\"{}\"", invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_extended_data_type_fallback() {
+        let with_fallback = ExtensibleDataType::Extended {
+            extension: "http://example.org/zarr/extension/foo".to_owned(),
+            type_string: "foo".to_owned(),
+            fallback: Some(u8::ZARR_TYPE),
+        };
+        assert_eq!(with_fallback.effective_type().unwrap(), u8::ZARR_TYPE);
+
+        let without_fallback = ExtensibleDataType::Extended {
+            extension: "http://example.org/zarr/extension/foo".to_owned(),
+            type_string: "foo".to_owned(),
+            fallback: None,
+        };
+        assert!(without_fallback.effective_type().is_err());
+    }
 }
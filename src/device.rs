@@ -0,0 +1,312 @@
+//! Decoding chunks on a device such as a GPU.
+//!
+//! A [`DeviceDecoder`] is handed the bytes of a chunk as they are stored,
+//! along with the [`DecodePlan`] of steps which turn them into elements in
+//! native byte order, and decodes them into a buffer of its own, such as GPU
+//! memory. Visualization engines can so upload stored chunks as they are and
+//! skip decoding on the host.
+//!
+//! Chunks of arrays with filters, or with steps the decoder does not
+//! [support](DeviceDecoder::supports), are not decoded on the device and
+//! must be read as usual with
+//! [`read_chunk`](crate::HierarchyReader::read_chunk).
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+};
+
+use crate::compression::CompressionType;
+use crate::storage::{
+    find_chunk_key,
+    ReadableStore,
+};
+use crate::{
+    config,
+    ArrayMetadata,
+    DataType,
+    Hierarchy,
+    NATIVE_ENDIAN,
+};
+
+/// A step in decoding stored chunk bytes into native elements, in the order
+/// they are applied.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeStep {
+    /// Decompress with a codec. Raw chunks have no decompression step.
+    Decompress(CompressionType),
+    /// Reverse the bytes of each element of `element_size` bytes.
+    ByteSwap { element_size: usize },
+}
+
+/// Steps to decode the stored bytes of a chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodePlan {
+    data_type: DataType,
+    num_elements: u32,
+    steps: Vec<DecodeStep>,
+}
+
+impl DecodePlan {
+    /// Plan decoding the chunks of an array which are compressed with
+    /// `compressor`, or `None` if the array has filters, which are only
+    /// decoded on the host.
+    pub fn new(
+        array_meta: &ArrayMetadata,
+        compressor: &CompressionType,
+    ) -> Result<Option<Self>, Error> {
+        if !array_meta.get_filters().is_empty() {
+            return Ok(None);
+        }
+        let data_type = array_meta.get_data_type().effective_type()?;
+        let mut steps = Vec::new();
+        if *compressor != CompressionType::Raw(Default::default()) {
+            steps.push(DecodeStep::Decompress(compressor.clone()));
+        }
+        let element_size = data_type.size_of();
+        match data_type {
+            DataType::Int { endian, .. }
+            | DataType::UInt { endian, .. }
+            | DataType::Float { endian, .. }
+                if endian != NATIVE_ENDIAN && element_size > 1 =>
+            {
+                steps.push(DecodeStep::ByteSwap { element_size })
+            }
+            _ => (),
+        }
+
+        Ok(Some(DecodePlan {
+            data_type,
+            num_elements: array_meta.checked_chunk_num_elements()?,
+            steps,
+        }))
+    }
+
+    /// Data type of the stored elements.
+    pub fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    /// Number of elements in a decoded chunk.
+    pub fn get_num_elements(&self) -> u32 {
+        self.num_elements
+    }
+
+    /// Number of bytes in a decoded chunk.
+    pub fn get_decoded_len(&self) -> usize {
+        self.num_elements as usize * self.data_type.size_of()
+    }
+
+    pub fn get_steps(&self) -> &[DecodeStep] {
+        &self.steps
+    }
+}
+
+/// Decodes stored chunk bytes into a buffer, typically in device memory.
+pub trait DeviceDecoder {
+    /// Buffer decoded chunks are written to.
+    type Buffer;
+
+    /// Whether this decoder can perform a decoding step.
+    fn supports(&self, step: &DecodeStep) -> bool;
+
+    /// Decode the stored bytes of a chunk into a buffer, applying every step
+    /// of the plan.
+    fn decode(
+        &self,
+        encoded: &[u8],
+        plan: &DecodePlan,
+        buffer: &mut Self::Buffer,
+    ) -> Result<(), Error>;
+}
+
+/// Decoder running on the host, decoding to native-endian bytes.
+///
+/// This supports all steps, so can be used as a fallback where no device is
+/// available, or as a reference for device decoders.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HostDecoder;
+
+impl DeviceDecoder for HostDecoder {
+    type Buffer = Vec<u8>;
+
+    fn supports(&self, _step: &DecodeStep) -> bool {
+        true
+    }
+
+    fn decode(&self, encoded: &[u8], plan: &DecodePlan, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        buffer.clear();
+        buffer.extend_from_slice(encoded);
+        for step in plan.get_steps() {
+            match step {
+                DecodeStep::Decompress(compressor) => {
+                    let limit = (plan.get_decoded_len() as u64)
+                        .saturating_mul(config::config().max_decompressed_chunk_ratio);
+                    let mut decompressed = Vec::with_capacity(plan.get_decoded_len());
+                    compressor
+                        .limited_decoder(&buffer[..], plan.get_data_type(), limit)?
+                        .read_to_end(&mut decompressed)?;
+                    *buffer = decompressed;
+                }
+                DecodeStep::ByteSwap { element_size } => {
+                    buffer
+                        .chunks_exact_mut(*element_size)
+                        .for_each(<[u8]>::reverse);
+                }
+            }
+        }
+
+        if buffer.len() != plan.get_decoded_len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Decoded chunk has {} bytes, expected {}",
+                    buffer.len(),
+                    plan.get_decoded_len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reading of chunks from stores through a [`DeviceDecoder`].
+///
+/// Chunks are decoded with the compressor recorded for them by
+/// [`HierarchyWriter::write_chunk_with_compression`](crate::HierarchyWriter::write_chunk_with_compression),
+/// else with the array's. Chunks compressed otherwise without a record, such
+/// as those written before the array's compressor changed, are decoded
+/// correctly only with
+/// [`detect_chunk_compression`](crate::config::Config::detect_chunk_compression)
+/// enabled.
+pub trait ZarrDeviceReader: ReadableStore + Hierarchy {
+    /// Read a single array chunk, decoding it with a device decoder.
+    ///
+    /// Returns `Ok(None)` if the chunk does not exist, and an error of kind
+    /// [`Unsupported`](ErrorKind::Unsupported) if the decoder can not decode
+    /// the chunk, in which case it should be read on the host instead.
+    fn read_chunk_to_device<D: DeviceDecoder>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+        decoder: &D,
+        buffer: &mut D::Buffer,
+    ) -> Result<Option<()>, Error> {
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        let mut encoded = Vec::new();
+        match self.get(&chunk_key)? {
+            Some(mut reader) => reader.read_to_end(&mut encoded)?,
+            None => return Ok(None),
+        };
+
+        let compressor = array_meta.chunk_compressor(grid_position)?;
+        let compressor = if config::config().detect_chunk_compression {
+            compressor.resolve_for_header(&encoded)
+        } else {
+            compressor.into_owned()
+        };
+        let plan = DecodePlan::new(array_meta, &compressor)?
+            .filter(|plan| plan.get_steps().iter().all(|step| decoder.supports(step)))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Unsupported,
+                    "Chunk can not be decoded by this decoder",
+                )
+            })?;
+
+        decoder.decode(&encoded, &plan, buffer).map(Some)
+    }
+}
+
+impl<S: ReadableStore + Hierarchy> ZarrDeviceReader for S {}
+
+#[cfg(all(test, feature = "filesystem", feature = "gzip"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::{
+        Endian,
+        IntSize,
+    };
+
+    /// Decoder which only byte swaps.
+    struct SwapDecoder;
+
+    impl DeviceDecoder for SwapDecoder {
+        type Buffer = Vec<u8>;
+
+        fn supports(&self, step: &DecodeStep) -> bool {
+            matches!(step, DecodeStep::ByteSwap { .. })
+        }
+
+        fn decode(
+            &self,
+            encoded: &[u8],
+            plan: &DecodePlan,
+            buffer: &mut Vec<u8>,
+        ) -> Result<(), Error> {
+            HostDecoder.decode(encoded, plan, buffer)
+        }
+    }
+
+    #[test]
+    fn test_read_chunk_to_device() {
+        let dir = tempdir::TempDir::new("rust_zarr_device_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let data_type = DataType::Int {
+            size: IntSize::B4,
+            endian: Endian::Big,
+        };
+        let raw = ArrayMetadataBuilder::new(smallvec![4, 4], data_type)
+            .chunk_shape(smallvec![2, 4])
+            .compressor(CompressionType::Raw(Default::default()))
+            .build();
+        let compressed = ArrayMetadataBuilder::new(smallvec![4, 4], data_type)
+            .chunk_shape(smallvec![2, 4])
+            .compressor(CompressionType::new::<
+                crate::compression::gzip::GzipCompression,
+            >())
+            .build();
+
+        let chunk_data: Vec<i32> = (0..8).map(|i| i * 1000 - 3).collect();
+        let expected: Vec<u8> = chunk_data.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        for (path, array_meta) in &[("raw", &raw), ("compressed", &compressed)] {
+            h.create_array(path, array_meta).unwrap();
+            h.write_chunk(
+                path,
+                array_meta,
+                &SliceDataChunk::new(smallvec![1, 0], &chunk_data),
+            )
+            .unwrap();
+
+            let mut buffer = Vec::new();
+            assert!(h
+                .read_chunk_to_device(path, array_meta, &[1, 0], &HostDecoder, &mut buffer)
+                .unwrap()
+                .is_some());
+            assert_eq!(buffer, expected);
+            assert!(h
+                .read_chunk_to_device(path, array_meta, &[0, 0], &HostDecoder, &mut buffer)
+                .unwrap()
+                .is_none());
+        }
+
+        let mut buffer = Vec::new();
+        h.read_chunk_to_device("raw", &raw, &[1, 0], &SwapDecoder, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, expected);
+        let err = h
+            .read_chunk_to_device(
+                "compressed",
+                &compressed,
+                &[1, 0],
+                &SwapDecoder,
+                &mut buffer,
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+}
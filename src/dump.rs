@@ -0,0 +1,447 @@
+//! Inspection of stored chunks for debugging interoperability problems.
+//!
+//! ```no_run
+//! use zarr::dump::ZarrChunkDump;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/from_python.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("raw").unwrap();
+//! let dump = h.dump_chunk_raw("raw", &array_meta, &[0, 0, 0]).unwrap().unwrap();
+//! println!("{}", dump);
+//! ```
+
+use std::fmt;
+use std::io::{
+    Error,
+    Read,
+};
+
+use crate::compression::{
+    Compression,
+    CompressionType,
+};
+use crate::storage::{
+    find_chunk_key,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    Hierarchy,
+};
+
+/// Fields of the header of a compressed stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodecHeader {
+    /// Name of the format the header was recognized as.
+    pub format: &'static str,
+    /// Names and values of the header's fields, in the order they are
+    /// stored.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl CodecHeader {
+    /// Parse the header at the start of a compressed stream, if it is in a
+    /// format with a recognizable header.
+    ///
+    /// Formats are recognized whether or not their compression is enabled in
+    /// this build. Fields are parsed only as far as the bytes given allow.
+    pub fn parse(bytes: &[u8]) -> Option<CodecHeader> {
+        let (format, fields) = if bytes.starts_with(&[0x1f, 0x8b]) {
+            ("gzip", gzip_fields(bytes))
+        } else if bytes.starts_with(b"BZh") {
+            ("bzip2", bzip2_fields(bytes))
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            ("xz", xz_fields(bytes))
+        } else if bytes.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            ("lz4", lz4_fields(bytes))
+        } else if bytes.starts_with(&[0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y']) {
+            ("snappy", snappy_fields(bytes))
+        } else if bytes.starts_with(b"pco!") {
+            ("pcodec", vec![])
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            ("zstd", zstd_fields(bytes))
+        } else {
+            return None;
+        };
+        Some(CodecHeader { format, fields })
+    }
+
+    /// Decompressed size of the stream, for formats whose header declares
+    /// it.
+    pub fn content_size(&self) -> Option<u64> {
+        self.fields
+            .iter()
+            .find(|(name, _)| *name == "content_size")
+            .and_then(|(_, value)| value.parse().ok())
+    }
+}
+
+impl fmt::Display for CodecHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)?;
+        for (name, value) in &self.fields {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn flag(byte: u8, bit: u8) -> String {
+    ((byte >> bit) & 1 == 1).to_string()
+}
+
+fn le_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| (value << 8) | u64::from(byte))
+}
+
+fn gzip_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    let mut fields = vec![];
+    if bytes.len() < 10 {
+        return fields;
+    }
+    let method = match bytes[2] {
+        8 => "deflate".to_owned(),
+        other => format!("unknown ({})", other),
+    };
+    fields.push(("method", method));
+    let flags = bytes[3];
+    fields.push(("text", flag(flags, 0)));
+    fields.push(("header_crc", flag(flags, 1)));
+    fields.push(("extra", flag(flags, 2)));
+    fields.push(("name", flag(flags, 3)));
+    fields.push(("comment", flag(flags, 4)));
+    fields.push(("mtime", le_uint(&bytes[4..8]).to_string()));
+    let extra_flags = match bytes[8] {
+        2 => "best compression".to_owned(),
+        4 => "fastest".to_owned(),
+        other => other.to_string(),
+    };
+    fields.push(("extra_flags", extra_flags));
+    fields.push(("os", bytes[9].to_string()));
+    fields
+}
+
+fn bzip2_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    match bytes.get(3) {
+        Some(level @ b'1'..=b'9') => vec![
+            ("level", char::from(*level).to_string()),
+            (
+                "block_size",
+                (u32::from(level - b'0') * 100_000).to_string(),
+            ),
+        ],
+        _ => vec![],
+    }
+}
+
+fn xz_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    let check = match bytes.get(7) {
+        Some(flags) => match flags & 0x0f {
+            0x00 => "none".to_owned(),
+            0x01 => "CRC32".to_owned(),
+            0x04 => "CRC64".to_owned(),
+            0x0a => "SHA-256".to_owned(),
+            other => format!("unknown ({})", other),
+        },
+        None => return vec![],
+    };
+    vec![("check", check)]
+}
+
+fn lz4_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    let mut fields = vec![];
+    let (flags, block) = match (bytes.get(4), bytes.get(5)) {
+        (Some(&flags), Some(&block)) => (flags, block),
+        _ => return fields,
+    };
+    fields.push(("version", (flags >> 6).to_string()));
+    fields.push(("independent_blocks", flag(flags, 5)));
+    fields.push(("block_checksum", flag(flags, 4)));
+    fields.push(("content_checksum", flag(flags, 2)));
+    let block_max_size = match (block >> 4) & 0x07 {
+        4 => "64KiB".to_owned(),
+        5 => "256KiB".to_owned(),
+        6 => "1MiB".to_owned(),
+        7 => "4MiB".to_owned(),
+        other => format!("unknown ({})", other),
+    };
+    fields.push(("block_max_size", block_max_size));
+    let has_content_size = (flags >> 3) & 1 == 1;
+    if has_content_size {
+        if let Some(size) = bytes.get(6..14) {
+            fields.push(("content_size", le_uint(size).to_string()));
+        }
+    }
+    let dictionary_offset = if has_content_size { 14 } else { 6 };
+    if flags & 1 == 1 {
+        if let Some(id) = bytes.get(dictionary_offset..dictionary_offset + 4) {
+            fields.push(("dictionary_id", le_uint(id).to_string()));
+        }
+    }
+    fields
+}
+
+fn snappy_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    let chunk_type = match bytes.get(10) {
+        Some(0x00) => "compressed".to_owned(),
+        Some(0x01) => "uncompressed".to_owned(),
+        Some(other) => format!("0x{:02x}", other),
+        None => return vec![],
+    };
+    vec![("first_chunk", chunk_type)]
+}
+
+fn zstd_fields(bytes: &[u8]) -> Vec<(&'static str, String)> {
+    let mut fields = vec![];
+    let descriptor = match bytes.get(4) {
+        Some(&descriptor) => descriptor,
+        None => return fields,
+    };
+    let single_segment = (descriptor >> 5) & 1 == 1;
+    fields.push(("single_segment", single_segment.to_string()));
+    fields.push(("checksum", flag(descriptor, 2)));
+
+    let mut offset = 5;
+    if !single_segment {
+        match bytes.get(offset) {
+            Some(window) => {
+                let exponent = u32::from(window >> 3) + 10;
+                let base = 1u64 << exponent;
+                let size = base + (base / 8) * u64::from(window & 0x07);
+                fields.push(("window_size", size.to_string()));
+            }
+            None => return fields,
+        }
+        offset += 1;
+    }
+    let dictionary_id_len = [0, 1, 2, 4][usize::from(descriptor & 0x03)];
+    if dictionary_id_len > 0 {
+        match bytes.get(offset..offset + dictionary_id_len) {
+            Some(id) => fields.push(("dictionary_id", le_uint(id).to_string())),
+            None => return fields,
+        }
+        offset += dictionary_id_len;
+    }
+    let content_size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    if content_size_len > 0 {
+        if let Some(size) = bytes.get(offset..offset + content_size_len) {
+            // Two byte sizes are stored offset by 256.
+            let size = le_uint(size) + if content_size_len == 2 { 256 } else { 0 };
+            fields.push(("content_size", size.to_string()));
+        }
+    }
+    fields
+}
+
+/// A chunk as stored, with what can be determined of its encoding.
+#[derive(Clone, Debug)]
+pub struct RawChunkDump {
+    pub key: String,
+    /// The chunk's stored, compressed bytes.
+    pub stored: Vec<u8>,
+    /// Compression given by the array's metadata.
+    pub compressor: CompressionType,
+    /// Compression indicated by the chunk's magic bytes, if any.
+    pub detected: Option<CompressionType>,
+    pub header: Option<CodecHeader>,
+    /// Size of the chunk decompressed with the array's compression, or
+    /// `None` if it could not be decompressed.
+    pub decompressed_size: Option<u64>,
+    /// Error decompressing the chunk with the array's compression, if any.
+    pub decompress_error: Option<String>,
+    /// Decompressed size the chunk's metadata implies, which filters
+    /// changing the size of the data may legitimately differ from.
+    pub expected_size: u64,
+}
+
+impl fmt::Display for RawChunkDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "key: {}", self.key)?;
+        writeln!(f, "stored size: {}", self.stored.len())?;
+        writeln!(f, "compressor: {}", self.compressor)?;
+        match &self.detected {
+            Some(detected) => writeln!(f, "detected compressor: {}", detected)?,
+            None => writeln!(f, "detected compressor: none")?,
+        }
+        match &self.header {
+            Some(header) => writeln!(f, "header: {}", header)?,
+            None => writeln!(f, "header: none")?,
+        }
+        match (self.decompressed_size, &self.decompress_error) {
+            (Some(size), _) => writeln!(f, "decompressed size: {}", size)?,
+            (None, Some(error)) => writeln!(f, "decompression failed: {}", error)?,
+            (None, None) => {}
+        }
+        write!(f, "expected size: {}", self.expected_size)?;
+        let head: Vec<String> = self
+            .stored
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        write!(f, "\nfirst bytes: {}", head.join(" "))
+    }
+}
+
+pub trait ZarrChunkDump: ReadableStore + Hierarchy {
+    /// Read a chunk's stored bytes and describe their encoding, or `None` if
+    /// the chunk is absent.
+    ///
+    /// Failing to decompress the chunk is reported in the dump rather than
+    /// as an error, since it is often what is being debugged.
+    fn dump_chunk_raw(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<Option<RawChunkDump>, Error> {
+        let key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        let mut stored = Vec::new();
+        match self.get(&key)? {
+            Some(mut reader) => reader.read_to_end(&mut stored)?,
+            None => return Ok(None),
+        };
+
+        let data_type = array_meta.get_data_type().effective_type()?;
+        let compressor = array_meta.chunk_compressor(grid_position)?.into_owned();
+        let mut decompressed = Vec::new();
+        let (decompressed_size, decompress_error) = match compressor
+            .decoder_typed(&stored[..], &data_type)
+            .read_to_end(&mut decompressed)
+        {
+            Ok(size) => (Some(size as u64), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        Ok(Some(RawChunkDump {
+            key,
+            detected: CompressionType::detect(&stored),
+            header: CodecHeader::parse(&stored),
+            compressor,
+            decompressed_size,
+            decompress_error,
+            expected_size: (array_meta.get_chunk_num_elements() * data_type.size_of()) as u64,
+            stored,
+        }))
+    }
+}
+
+impl<S: ReadableStore + Hierarchy> ZarrChunkDump for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(CodecHeader::parse(b"raw data"), None);
+
+        let gzip = CodecHeader::parse(&[0x1f, 0x8b, 8, 0x08, 1, 0, 0, 0, 2, 3]).unwrap();
+        assert_eq!(
+            gzip.to_string(),
+            "gzip method=deflate text=false header_crc=false extra=false name=true \
+             comment=false mtime=1 extra_flags=best compression os=3"
+        );
+        assert_eq!(
+            CodecHeader::parse(b"BZh9").unwrap().fields,
+            vec![
+                ("level", "9".to_owned()),
+                ("block_size", "900000".to_owned())
+            ]
+        );
+
+        // Single segment frame with a one byte content size.
+        let zstd = CodecHeader::parse(&[0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x40]).unwrap();
+        assert_eq!(
+            zstd.fields,
+            vec![
+                ("single_segment", "true".to_owned()),
+                ("checksum", "true".to_owned()),
+                ("content_size", "64".to_owned()),
+            ]
+        );
+        // Windowed frame with a two byte dictionary ID.
+        let zstd = CodecHeader::parse(&[0x28, 0xb5, 0x2f, 0xfd, 0x02, 0x58, 0x34, 0x12]).unwrap();
+        assert_eq!(
+            zstd.fields,
+            vec![
+                ("single_segment", "false".to_owned()),
+                ("checksum", "false".to_owned()),
+                ("window_size", (1u64 << 21).to_string()),
+                ("dictionary_id", 0x1234.to_string()),
+            ]
+        );
+
+        let lz4 =
+            CodecHeader::parse(&[0x04, 0x22, 0x4d, 0x18, 0x68, 0x40, 16, 0, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+        assert_eq!(
+            lz4.fields,
+            vec![
+                ("version", "1".to_owned()),
+                ("independent_blocks", "true".to_owned()),
+                ("block_checksum", "false".to_owned()),
+                ("content_checksum", "false".to_owned()),
+                ("block_max_size", "64KiB".to_owned()),
+                ("content_size", "16".to_owned()),
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "filesystem", feature = "gzip", feature = "bzip"))]
+    #[test]
+    fn test_dump_chunk_raw() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_dump_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(CompressionType::new::<
+                crate::compression::gzip::GzipCompression,
+            >())
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0, 1], vec![7u16; 4]),
+        )
+        .unwrap();
+
+        let dump = h
+            .dump_chunk_raw("a", &array_meta, &[0, 1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(dump.key, "/data/root/a/c0/1");
+        assert_eq!(dump.detected, Some(dump.compressor.clone()));
+        assert_eq!(dump.header.as_ref().unwrap().format, "gzip");
+        assert_eq!(dump.decompressed_size, Some(8));
+        assert_eq!(dump.expected_size, 8);
+        assert!(dump.to_string().contains("first bytes: 1f 8b"));
+        assert!(h
+            .dump_chunk_raw("a", &array_meta, &[0, 0])
+            .unwrap()
+            .is_none());
+
+        // Misconfigured compression is reported rather than failing.
+        let raw_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(CompressionType::new::<
+                crate::compression::bzip::Bzip2Compression,
+            >())
+            .build();
+        let dump = h.dump_chunk_raw("a", &raw_meta, &[0, 1]).unwrap().unwrap();
+        assert!(dump.decompressed_size.is_none());
+        assert!(dump.decompress_error.is_some());
+    }
+}
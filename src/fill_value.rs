@@ -0,0 +1,276 @@
+//! Typed fill values of arrays.
+//!
+//! Metadata stores the fill value as JSON, whose form depends on the array's
+//! data type:
+//!
+//! - booleans as `true` or `false`,
+//! - integers as numbers,
+//! - floats as numbers, as `"NaN"`, `"Infinity"` or `"-Infinity"`, or as
+//!   the hexadecimal bit pattern of the value, such as `"0x7fc00000"`,
+//! - complex numbers as a `[real, imaginary]` pair of floats,
+//! - raw bytes as an array of byte values or as a base64 string.
+//!
+//! ```
+//! use zarr::fill_value::FillValue;
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//!
+//! let array_meta = ArrayMetadataBuilder::new(smallvec![10], f32::ZARR_TYPE)
+//!     .fill_value(FillValue::Float(f32::NAN.into()))
+//!     .build();
+//! assert_eq!(array_meta.get_fill_value(), Some(&serde_json::json!("NaN")));
+//! assert!(array_meta.get_effective_fill_value::<f32>().unwrap().is_nan());
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use half::f16;
+use serde_json::Value;
+
+use crate::{
+    DataType,
+    FloatSize,
+    MetadataError,
+};
+
+/// Fill value of an array, parsed according to its data type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FillValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    /// A float of any size, which may be NaN or infinite.
+    Float(f64),
+    Complex(f64, f64),
+    Raw(Vec<u8>),
+}
+
+impl FillValue {
+    /// Parse the JSON form of a fill value for elements of a data type.
+    ///
+    /// Complex fill values are accepted for float data types, of the width
+    /// of each part, and for 64 and 128 bit raw data types, since complex
+    /// arrays are stored as extended types with one of these as fallback.
+    pub fn from_json(value: &Value, data_type: DataType) -> Result<Self, MetadataError> {
+        let unexpected = || MetadataError::UnexpectedType(value.clone());
+        match (data_type, value) {
+            (DataType::Bool, Value::Bool(b)) => Ok(FillValue::Bool(*b)),
+            (DataType::Int { .. }, Value::Number(n)) => {
+                n.as_i64().map(FillValue::Int).ok_or_else(unexpected)
+            }
+            (DataType::UInt { .. }, Value::Number(n)) => {
+                n.as_u64().map(FillValue::UInt).ok_or_else(unexpected)
+            }
+            (DataType::Float { size, .. }, Value::Array(parts)) if parts.len() == 2 => {
+                parse_complex(parts, size).ok_or_else(unexpected)
+            }
+            (DataType::Raw { size: 64 }, Value::Array(parts)) if parts.len() == 2 => {
+                parse_complex(parts, FloatSize::B4).ok_or_else(unexpected)
+            }
+            (DataType::Raw { size: 128 }, Value::Array(parts)) if parts.len() == 2 => {
+                parse_complex(parts, FloatSize::B8).ok_or_else(unexpected)
+            }
+            (DataType::Float { size, .. }, value) => parse_float(value, size)
+                .map(FillValue::Float)
+                .ok_or_else(unexpected),
+            (DataType::Raw { size }, Value::Array(bytes)) => {
+                let bytes = bytes
+                    .iter()
+                    .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(unexpected)?;
+                check_raw_len(bytes, size).ok_or_else(unexpected)
+            }
+            (DataType::Raw { size }, Value::String(encoded)) => STANDARD
+                .decode(encoded)
+                .ok()
+                .and_then(|bytes| check_raw_len(bytes, size))
+                .ok_or_else(unexpected),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// The JSON form of this fill value.
+    ///
+    /// Non-finite floats are written as `"NaN"`, `"Infinity"` and
+    /// `"-Infinity"`, and raw bytes as an array of byte values.
+    pub fn to_json(&self) -> Value {
+        match self {
+            FillValue::Bool(b) => Value::Bool(*b),
+            FillValue::Int(i) => Value::from(*i),
+            FillValue::UInt(u) => Value::from(*u),
+            FillValue::Float(f) => float_to_json(*f),
+            FillValue::Complex(real, imaginary) => {
+                Value::Array(vec![float_to_json(*real), float_to_json(*imaginary)])
+            }
+            FillValue::Raw(bytes) => Value::Array(bytes.iter().map(|&b| b.into()).collect()),
+        }
+    }
+}
+
+impl From<FillValue> for Value {
+    fn from(fill_value: FillValue) -> Value {
+        fill_value.to_json()
+    }
+}
+
+impl fmt::Display for FillValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+fn parse_float(value: &Value, size: FloatSize) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => {
+                let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+                // The bit pattern must have exactly the float's width.
+                if hex.len() != 2 * size_of_float(size) {
+                    return None;
+                }
+                let bits = u64::from_str_radix(hex, 16).ok()?;
+                Some(match size {
+                    FloatSize::B2 => f16::from_bits(bits as u16).to_f64(),
+                    FloatSize::B4 => f64::from(f32::from_bits(bits as u32)),
+                    FloatSize::B8 => f64::from_bits(bits),
+                })
+            }
+        },
+        _ => None,
+    }
+}
+
+fn parse_complex(parts: &[Value], size: FloatSize) -> Option<FillValue> {
+    Some(FillValue::Complex(
+        parse_float(&parts[0], size)?,
+        parse_float(&parts[1], size)?,
+    ))
+}
+
+fn size_of_float(size: FloatSize) -> usize {
+    match size {
+        FloatSize::B2 => 2,
+        FloatSize::B4 => 4,
+        FloatSize::B8 => 8,
+    }
+}
+
+fn float_to_json(f: f64) -> Value {
+    if f.is_nan() {
+        Value::from("NaN")
+    } else if f == f64::INFINITY {
+        Value::from("Infinity")
+    } else if f == f64::NEG_INFINITY {
+        Value::from("-Infinity")
+    } else {
+        Value::from(f)
+    }
+}
+
+/// Raw fill values must hold exactly one element of `size` bits.
+fn check_raw_len(bytes: Vec<u8>, size: usize) -> Option<FillValue> {
+    if bytes.len() * 8 == size {
+        Some(FillValue::Raw(bytes))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Endian,
+        IntSize,
+    };
+    use serde_json::json;
+
+    const F4: DataType = DataType::Float {
+        size: FloatSize::B4,
+        endian: Endian::Little,
+    };
+
+    #[test]
+    fn test_fill_value_parsing() {
+        let parse = |value: Value, data_type| FillValue::from_json(&value, data_type);
+
+        assert_eq!(
+            parse(json!(true), DataType::Bool).unwrap(),
+            FillValue::Bool(true)
+        );
+        let i2 = DataType::Int {
+            size: IntSize::B2,
+            endian: Endian::Big,
+        };
+        assert_eq!(parse(json!(-3), i2).unwrap(), FillValue::Int(-3));
+        assert!(parse(json!(1.5), i2).is_err());
+        assert!(parse(json!("NaN"), i2).is_err());
+
+        assert!(matches!(parse(json!("NaN"), F4).unwrap(), FillValue::Float(f) if f.is_nan()));
+        assert_eq!(
+            parse(json!("-Infinity"), F4).unwrap(),
+            FillValue::Float(f64::NEG_INFINITY)
+        );
+        assert_eq!(
+            parse(json!("0x3fc00000"), F4).unwrap(),
+            FillValue::Float(1.5)
+        );
+        // 8 hex digits are an `f4`, not an `f8`, bit pattern.
+        assert!(parse(
+            json!("0x3fc00000"),
+            DataType::Float {
+                size: FloatSize::B8,
+                endian: Endian::Little
+            }
+        )
+        .is_err());
+        assert_eq!(
+            parse(json!([1.0, "-Infinity"]), F4).unwrap(),
+            FillValue::Complex(1.0, f64::NEG_INFINITY)
+        );
+
+        assert_eq!(
+            parse(json!([0, "NaN"]), DataType::Raw { size: 128 })
+                .unwrap()
+                .to_json(),
+            json!([0.0, "NaN"])
+        );
+
+        let r16 = DataType::Raw { size: 16 };
+        assert_eq!(
+            parse(json!([1, 255]), r16).unwrap(),
+            FillValue::Raw(vec![1, 255])
+        );
+        assert_eq!(
+            parse(json!("Af8="), r16).unwrap(),
+            FillValue::Raw(vec![1, 255])
+        );
+        assert!(parse(json!([1, 2, 3]), r16).is_err());
+        assert!(parse(json!("not base64"), r16).is_err());
+    }
+
+    #[test]
+    fn test_fill_value_round_trip() {
+        for fill_value in &[
+            FillValue::Float(f64::INFINITY),
+            FillValue::Float(-2.5),
+            FillValue::Complex(0.5, f64::NAN),
+            FillValue::Raw(vec![0, 7]),
+        ] {
+            let data_type = match fill_value {
+                FillValue::Raw(_) => DataType::Raw { size: 16 },
+                _ => F4,
+            };
+            let parsed = FillValue::from_json(&fill_value.to_json(), data_type).unwrap();
+            assert_eq!(parsed.to_json(), fill_value.to_json());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::{
+    Error,
+    ErrorKind,
+    Result,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    read_numbers,
+    write_numbers,
+    Filter,
+};
+use crate::data_type::DataType;
+
+/// Filter storing data as a different data type than the array's, compatible
+/// with numcodecs' `astype` filter.
+///
+/// Values are cast as numpy does, so narrowing casts are lossy. For example,
+/// floats are truncated toward zero when stored as integers.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct AsTypeFilter {
+    /// Data type of encoded data.
+    pub encode_dtype: DataType,
+    /// Data type of decoded data, which must match the array's type up to
+    /// endianness.
+    pub decode_dtype: DataType,
+}
+
+impl AsTypeFilter {
+    pub fn new(encode_dtype: DataType, decode_dtype: DataType) -> AsTypeFilter {
+        AsTypeFilter {
+            encode_dtype,
+            decode_dtype,
+        }
+    }
+}
+
+impl Filter for AsTypeFilter {
+    fn encoded_type(&self, data_type: &DataType) -> Result<DataType> {
+        if self.decode_dtype.eq_modulo_endian(data_type) {
+            Ok(self.encode_dtype)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Astype filter decode type {} does not match {}",
+                    self.decode_dtype, data_type
+                ),
+            ))
+        }
+    }
+
+    fn encode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        let encoded_type = self.encoded_type(data_type)?;
+        write_numbers(&read_numbers(data, data_type)?, &encoded_type)
+    }
+
+    fn decode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        let encoded_type = self.encoded_type(data_type)?;
+        write_numbers(&read_numbers(data, &encoded_type)?, data_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_type::{
+        Endian,
+        IntSize,
+    };
+    use crate::filter::FilterType;
+    use crate::ReflectedType;
+
+    #[test]
+    fn test_astype_float_to_int() {
+        let filter: FilterType = serde_json::from_str(
+            r#"{
+                "codec": "astype",
+                "configuration": {"encode_dtype": ">i2", "decode_dtype": "<f8"}
+            }"#,
+        )
+        .unwrap();
+        let data_type = f64::ZARR_TYPE;
+        let data = crate::filter::write_floats(&[0.0, 1.5, 2.7, -1.2, 300.9], &data_type).unwrap();
+
+        let encoded = filter.encode(&data, &data_type).unwrap();
+        assert_eq!(encoded, [0, 0, 0, 1, 0, 2, 0xff, 0xff, 0x01, 0x2c]);
+        let decoded = filter.decode(&encoded, &data_type).unwrap();
+        assert_eq!(
+            crate::filter::read_floats(&decoded, &data_type).unwrap(),
+            [0.0, 1.0, 2.0, -1.0, 300.0]
+        );
+    }
+
+    #[test]
+    fn test_astype_int_casts() {
+        let u16_be = DataType::UInt {
+            size: IntSize::B2,
+            endian: Endian::Big,
+        };
+        let filter = AsTypeFilter::new(u16_be, i32::ZARR_TYPE);
+        let mut data = Vec::new();
+        for v in &[70000i32, -1, 7] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let encoded = filter.encode(&data, &i32::ZARR_TYPE).unwrap();
+        // Wraps like numpy: 70000 % 65536 == 4464.
+        assert_eq!(encoded, [0x11, 0x70, 0xff, 0xff, 0x00, 0x07]);
+
+        let widened = AsTypeFilter::new(f32::ZARR_TYPE, u8::ZARR_TYPE);
+        let encoded = widened.encode(&[0, 255], &u8::ZARR_TYPE).unwrap();
+        assert_eq!(
+            crate::filter::read_floats(&encoded, &f32::ZARR_TYPE).unwrap(),
+            [0.0, 255.0]
+        );
+        assert_eq!(widened.decode(&encoded, &u8::ZARR_TYPE).unwrap(), [0, 255]);
+
+        assert!(filter.encoded_type(&u8::ZARR_TYPE).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! Filters transforming chunk data before compression.
+//!
+//! Filters are applied in order when writing a chunk, followed by the array's
+//! compressor. When reading, the compressor is reversed first, followed by
+//! the filters in reverse order. Unlike compressors, filters operate on
+//! whole chunks and may change the data type of the encoded representation.
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Result,
+};
+
+use byteorder::{
+    BigEndian,
+    ByteOrder,
+    LittleEndian,
+};
+use half::f16;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::data_type::{
+    DataType,
+    Endian,
+    FloatSize,
+};
+
+pub mod astype;
+pub mod packbits;
+pub mod quantize;
+
+/// Common interface for filters over chunk data.
+///
+/// Chunk data is passed as bytes in the layout of the given data type.
+pub trait Filter {
+    /// Data type of the encoded data, given the data type being encoded.
+    fn encoded_type(&self, data_type: &DataType) -> Result<DataType> {
+        Ok(*data_type)
+    }
+
+    /// Encode chunk data of `data_type`.
+    fn encode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>>;
+
+    /// Decode chunk data into `data_type`, the type which was encoded.
+    fn decode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>>;
+}
+
+/// Enumeration of known filters.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+#[serde(tag = "codec", content = "configuration")]
+pub enum FilterType {
+    Quantize(quantize::QuantizeFilter),
+    PackBits(packbits::PackBitsFilter),
+    AsType(astype::AsTypeFilter),
+}
+
+impl Filter for FilterType {
+    fn encoded_type(&self, data_type: &DataType) -> Result<DataType> {
+        match *self {
+            FilterType::Quantize(ref f) => f.encoded_type(data_type),
+            FilterType::PackBits(ref f) => f.encoded_type(data_type),
+            FilterType::AsType(ref f) => f.encoded_type(data_type),
+        }
+    }
+
+    fn encode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        match *self {
+            FilterType::Quantize(ref f) => f.encode(data, data_type),
+            FilterType::PackBits(ref f) => f.encode(data, data_type),
+            FilterType::AsType(ref f) => f.encode(data, data_type),
+        }
+    }
+
+    fn decode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        match *self {
+            FilterType::Quantize(ref f) => f.decode(data, data_type),
+            FilterType::PackBits(ref f) => f.decode(data, data_type),
+            FilterType::AsType(ref f) => f.decode(data, data_type),
+        }
+    }
+}
+
+macro_rules! filter_from_impl {
+    ($variant:ident, $f_type:ty) => {
+        impl std::convert::From<$f_type> for FilterType {
+            fn from(f: $f_type) -> Self {
+                FilterType::$variant(f)
+            }
+        }
+    };
+}
+
+filter_from_impl!(Quantize, quantize::QuantizeFilter);
+filter_from_impl!(PackBits, packbits::PackBitsFilter);
+filter_from_impl!(AsType, astype::AsTypeFilter);
+
+/// Data type after applying a chain of filters to data of `data_type`.
+pub fn encoded_type(filters: &[FilterType], data_type: &DataType) -> Result<DataType> {
+    filters
+        .iter()
+        .try_fold(*data_type, |data_type, f| f.encoded_type(&data_type))
+}
+
+/// Apply a chain of filters to chunk data of `data_type`.
+pub fn encode(filters: &[FilterType], data: Vec<u8>, data_type: &DataType) -> Result<Vec<u8>> {
+    let mut data_type = *data_type;
+    filters.iter().try_fold(data, |data, f| {
+        let encoded = f.encode(&data, &data_type)?;
+        data_type = f.encoded_type(&data_type)?;
+        Ok(encoded)
+    })
+}
+
+/// Reverse a chain of filters, yielding chunk data of `data_type`.
+pub fn decode(filters: &[FilterType], data: Vec<u8>, data_type: &DataType) -> Result<Vec<u8>> {
+    let mut types = Vec::with_capacity(filters.len());
+    let mut current = *data_type;
+    for f in filters {
+        types.push(current);
+        current = f.encoded_type(&current)?;
+    }
+    filters
+        .iter()
+        .zip(types.iter())
+        .rev()
+        .try_fold(data, |data, (f, data_type)| f.decode(&data, data_type))
+}
+
+fn unsupported_type(filter: &str, data_type: &DataType) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("Filter {} does not support data type {}", filter, data_type),
+    )
+}
+
+fn check_len(data: &[u8], data_type: &DataType) -> Result<usize> {
+    let size = data_type.size_of();
+    if size == 0 || !data.len().is_multiple_of(size) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Chunk data of {} bytes is not a whole number of {} elements",
+                data.len(),
+                data_type
+            ),
+        ));
+    }
+    Ok(data.len() / size)
+}
+
+/// Read float chunk data as `f64`, which represents all float types exactly.
+pub(crate) fn read_floats(data: &[u8], data_type: &DataType) -> Result<Vec<f64>> {
+    let n = check_len(data, data_type)?;
+    let (size, endian) = match *data_type {
+        DataType::Float { size, endian } => (size, endian),
+        _ => return Err(unsupported_type("float", data_type)),
+    };
+    let mut values = vec![0.; n];
+    match size {
+        FloatSize::B2 => {
+            let mut bits = vec![0; n];
+            match endian {
+                Endian::Big => BigEndian::read_u16_into(data, &mut bits),
+                Endian::Little => LittleEndian::read_u16_into(data, &mut bits),
+            }
+            for (v, b) in values.iter_mut().zip(bits) {
+                *v = f16::from_bits(b).to_f64();
+            }
+        }
+        FloatSize::B4 => {
+            let mut floats = vec![0.; n];
+            match endian {
+                Endian::Big => BigEndian::read_f32_into(data, &mut floats),
+                Endian::Little => LittleEndian::read_f32_into(data, &mut floats),
+            }
+            for (v, f) in values.iter_mut().zip(floats) {
+                *v = f64::from(f);
+            }
+        }
+        FloatSize::B8 => match endian {
+            Endian::Big => BigEndian::read_f64_into(data, &mut values),
+            Endian::Little => LittleEndian::read_f64_into(data, &mut values),
+        },
+    }
+    Ok(values)
+}
+
+/// Write `f64` values as float chunk data, rounding to the nearest
+/// representable value of narrower types.
+pub(crate) fn write_floats(values: &[f64], data_type: &DataType) -> Result<Vec<u8>> {
+    let (size, endian) = match *data_type {
+        DataType::Float { size, endian } => (size, endian),
+        _ => return Err(unsupported_type("float", data_type)),
+    };
+    let mut data = vec![0; values.len() * data_type.size_of()];
+    match size {
+        FloatSize::B2 => {
+            let bits: Vec<u16> = values.iter().map(|&v| f16::from_f64(v).to_bits()).collect();
+            match endian {
+                Endian::Big => BigEndian::write_u16_into(&bits, &mut data),
+                Endian::Little => LittleEndian::write_u16_into(&bits, &mut data),
+            }
+        }
+        FloatSize::B4 => {
+            let floats: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+            match endian {
+                Endian::Big => BigEndian::write_f32_into(&floats, &mut data),
+                Endian::Little => LittleEndian::write_f32_into(&floats, &mut data),
+            }
+        }
+        FloatSize::B8 => match endian {
+            Endian::Big => BigEndian::write_f64_into(values, &mut data),
+            Endian::Little => LittleEndian::write_f64_into(values, &mut data),
+        },
+    }
+    Ok(data)
+}
+
+/// Numeric chunk data widened to types which represent all elements exactly.
+#[derive(PartialEq, Debug)]
+pub(crate) enum Numbers {
+    Ints(Vec<i128>),
+    Floats(Vec<f64>),
+}
+
+/// Read boolean, integer or float chunk data.
+pub(crate) fn read_numbers(data: &[u8], data_type: &DataType) -> Result<Numbers> {
+    fn read_ints<B: ByteOrder>(data: &[u8], size: usize, signed: bool) -> Vec<i128> {
+        data.chunks_exact(size)
+            .map(|b| {
+                if signed {
+                    i128::from(B::read_int(b, size))
+                } else {
+                    i128::from(B::read_uint(b, size))
+                }
+            })
+            .collect()
+    }
+
+    check_len(data, data_type)?;
+    let size = data_type.size_of();
+    Ok(match *data_type {
+        DataType::Bool => Numbers::Ints(data.iter().map(|&b| i128::from(b)).collect()),
+        DataType::Int { endian, .. } | DataType::UInt { endian, .. } => {
+            let signed = matches!(data_type, DataType::Int { .. });
+            Numbers::Ints(match endian {
+                Endian::Big => read_ints::<BigEndian>(data, size, signed),
+                Endian::Little => read_ints::<LittleEndian>(data, size, signed),
+            })
+        }
+        DataType::Float { .. } => Numbers::Floats(read_floats(data, data_type)?),
+        DataType::Raw { .. } => return Err(unsupported_type("numeric", data_type)),
+    })
+}
+
+/// Write numbers as chunk data of `data_type`, casting as numpy does.
+///
+/// Integers wrap to the width of integer types and floats are truncated
+/// toward zero when cast to integers. Casts of out-of-range floats to
+/// integers are not specified.
+pub(crate) fn write_numbers(numbers: &Numbers, data_type: &DataType) -> Result<Vec<u8>> {
+    fn write_ints<B: ByteOrder>(ints: &[i128], size: usize) -> Vec<u8> {
+        let mut data = vec![0; ints.len() * size];
+        for (b, &v) in data.chunks_exact_mut(size).zip(ints) {
+            // Truncate to the low `size` bytes, which wraps as numpy does.
+            B::write_uint(b, v as u64 & (u64::MAX >> (64 - 8 * size)), size);
+        }
+        data
+    }
+
+    match (numbers, *data_type) {
+        (Numbers::Floats(floats), DataType::Float { .. }) => write_floats(floats, data_type),
+        (Numbers::Ints(ints), DataType::Float { size, .. }) => {
+            // Round directly to narrow types to avoid double rounding.
+            let floats: Vec<f64> = match size {
+                FloatSize::B4 => ints.iter().map(|&v| f64::from(v as f32)).collect(),
+                _ => ints.iter().map(|&v| v as f64).collect(),
+            };
+            write_floats(&floats, data_type)
+        }
+        (Numbers::Floats(floats), _) => {
+            let ints: Vec<i128> = floats.iter().map(|&v| v as i128).collect();
+            write_numbers(&Numbers::Ints(ints), data_type)
+        }
+        (Numbers::Ints(ints), DataType::Bool) => {
+            Ok(ints.iter().map(|&v| u8::from(v != 0)).collect())
+        }
+        (Numbers::Ints(ints), DataType::Int { endian, .. })
+        | (Numbers::Ints(ints), DataType::UInt { endian, .. }) => {
+            let size = data_type.size_of();
+            Ok(match endian {
+                Endian::Big => write_ints::<BigEndian>(ints, size),
+                Endian::Little => write_ints::<LittleEndian>(ints, size),
+            })
+        }
+        (_, DataType::Raw { .. }) => Err(unsupported_type("numeric", data_type)),
+    }
+}
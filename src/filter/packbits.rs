@@ -0,0 +1,99 @@
+use std::io::{
+    Error,
+    ErrorKind,
+    Result,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    unsupported_type,
+    Filter,
+};
+use crate::data_type::{
+    DataType,
+    IntSize,
+    NATIVE_ENDIAN,
+};
+
+/// Filter packing boolean data 8 elements per byte, compatible with
+/// numcodecs' `packbits` filter.
+///
+/// The encoded data is a byte counting the padding bits in the final byte,
+/// followed by the bits with the first element in the most significant bit.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct PackBitsFilter {}
+
+impl Filter for PackBitsFilter {
+    fn encoded_type(&self, data_type: &DataType) -> Result<DataType> {
+        match data_type {
+            DataType::Bool => Ok(DataType::UInt {
+                size: IntSize::B1,
+                endian: NATIVE_ENDIAN,
+            }),
+            _ => Err(unsupported_type("packbits", data_type)),
+        }
+    }
+
+    fn encode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        self.encoded_type(data_type)?;
+        let padding = (8 - data.len() % 8) % 8;
+        let mut encoded = Vec::with_capacity(1 + data.len().div_ceil(8));
+        encoded.push(padding as u8);
+        for bits in data.chunks(8) {
+            let byte = bits
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &b)| byte | (u8::from(b != 0) << (7 - i)));
+            encoded.push(byte);
+        }
+        Ok(encoded)
+    }
+
+    fn decode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        self.encoded_type(data_type)?;
+        let (&padding, bytes) = data
+            .split_first()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Packbits data is missing header"))?;
+        let padding = usize::from(padding);
+        if padding > 7 || (bytes.is_empty() && padding != 0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid packbits padding: {}", padding),
+            ));
+        }
+        let n = bytes.len() * 8 - padding;
+        Ok((0..n).map(|i| (bytes[i / 8] >> (7 - i % 8)) & 1).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterType;
+    use crate::ReflectedType;
+
+    #[test]
+    fn test_packbits() {
+        let filter: FilterType =
+            serde_json::from_str(r#"{"codec": "packbits", "configuration": {}}"#).unwrap();
+        let data = [1, 0, 1, 1, 0, 0, 0, 0, 1, 1];
+        // As written by numcodecs.
+        let expected = [6, 0b1011_0000, 0b1100_0000];
+
+        let encoded = filter.encode(&data, &DataType::Bool).unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(filter.decode(&encoded, &DataType::Bool).unwrap(), data);
+
+        let aligned = filter.encode(&data[..8], &DataType::Bool).unwrap();
+        assert_eq!(aligned, [0, 0b1011_0000]);
+        assert_eq!(filter.encode(&[], &DataType::Bool).unwrap(), [0]);
+
+        assert!(filter.decode(&[], &DataType::Bool).is_err());
+        assert!(filter.decode(&[8, 0], &DataType::Bool).is_err());
+        assert!(filter.encoded_type(&u8::ZARR_TYPE).is_err());
+    }
+}
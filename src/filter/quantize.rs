@@ -0,0 +1,187 @@
+use std::io::{
+    Error,
+    ErrorKind,
+    Result,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    read_floats,
+    unsupported_type,
+    write_floats,
+    Filter,
+};
+use crate::data_type::DataType;
+
+/// Lossy filter keeping a number of significant decimal digits of float data,
+/// compatible with numcodecs' `quantize` filter.
+///
+/// Values are rounded to the nearest multiple of a power of two no coarser
+/// than `10^-digits`, so the result compresses well.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct QuantizeFilter {
+    /// Number of decimal digits after the point to keep.
+    pub digits: i32,
+    /// Data type of decoded data. If set, it must match the array's type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtype: Option<DataType>,
+    /// Data type of encoded data. Defaults to the decoded type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub astype: Option<DataType>,
+}
+
+impl QuantizeFilter {
+    pub fn new(digits: i32) -> QuantizeFilter {
+        QuantizeFilter {
+            digits,
+            dtype: None,
+            astype: None,
+        }
+    }
+
+    /// Power of two scale to round values to, following numcodecs.
+    fn scale(&self) -> f64 {
+        let precision = 10f64.powi(-self.digits);
+        // Python's `math.log(x, 10)`, which differs from `log10` in the last
+        // bit for some inputs.
+        let exp = precision.ln() / 10f64.ln();
+        let exp = if exp < 0. { exp.floor() } else { exp.ceil() };
+        let bits = (10f64.powf(-exp).ln() / 2f64.ln()).ceil();
+        2f64.powf(bits)
+    }
+
+    fn check_type(&self, data_type: &DataType) -> Result<()> {
+        if !matches!(data_type, DataType::Float { .. }) {
+            return Err(unsupported_type("quantize", data_type));
+        }
+        match self.dtype {
+            Some(ref dtype) if dtype != data_type => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Quantize filter data type {} does not match {}",
+                    dtype, data_type
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Filter for QuantizeFilter {
+    fn encoded_type(&self, data_type: &DataType) -> Result<DataType> {
+        self.check_type(data_type)?;
+        let astype = self.astype.unwrap_or(*data_type);
+        if !matches!(astype, DataType::Float { .. }) {
+            return Err(unsupported_type("quantize", &astype));
+        }
+        Ok(astype)
+    }
+
+    fn encode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        let astype = self.encoded_type(data_type)?;
+        let scale = self.scale();
+        let mut values = read_floats(data, data_type)?;
+        for v in &mut values {
+            *v = (scale * *v).round_ties_even() / scale;
+        }
+        write_floats(&values, &astype)
+    }
+
+    fn decode(&self, data: &[u8], data_type: &DataType) -> Result<Vec<u8>> {
+        let astype = self.encoded_type(data_type)?;
+        write_floats(&read_floats(data, &astype)?, data_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_type::{
+        Endian,
+        FloatSize,
+    };
+    use crate::filter::FilterType;
+    use crate::ReflectedType;
+
+    const DATA: [f64; 6] = [0.0, 0.1234, 3.3333, -2.5e-3, 100.5, -7.77];
+
+    /// Expected values computed with numcodecs' algorithm.
+    #[test]
+    fn test_quantize_digits() {
+        let expected: [(i32, [f64; 6]); 5] = [
+            (0, [0.0, 0.0, 3.0, 0.0, 100.0, -8.0]),
+            (1, [0.0, 0.125, 3.3125, 0.0, 100.5, -7.75]),
+            (2, [0.0, 0.125, 3.3359375, 0.0, 100.5, -7.7734375]),
+            (
+                3,
+                [
+                    0.0,
+                    0.123046875,
+                    3.3330078125,
+                    -0.0029296875,
+                    100.5,
+                    -7.76953125,
+                ],
+            ),
+            (
+                5,
+                [
+                    0.0,
+                    0.1233978271484375,
+                    3.3332977294921875,
+                    -0.00250244140625,
+                    100.5,
+                    -7.769996643066406,
+                ],
+            ),
+        ];
+        let data_type = f64::ZARR_TYPE;
+        let data = write_floats(&DATA, &data_type).unwrap();
+
+        for (digits, expected) in &expected {
+            let filter = QuantizeFilter::new(*digits);
+            let encoded = filter.encode(&data, &data_type).unwrap();
+            assert_eq!(read_floats(&encoded, &data_type).unwrap(), expected);
+            assert_eq!(filter.decode(&encoded, &data_type).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn test_quantize_astype() {
+        let data_type = DataType::Float {
+            size: FloatSize::B8,
+            endian: Endian::Little,
+        };
+        let astype = DataType::Float {
+            size: FloatSize::B4,
+            endian: Endian::Little,
+        };
+        let filter: FilterType = serde_json::from_str(
+            r#"{
+                "codec": "quantize",
+                "configuration": {"digits": 2, "dtype": "<f8", "astype": "<f4"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(filter.encoded_type(&data_type).unwrap(), astype);
+
+        let data = write_floats(&DATA, &data_type).unwrap();
+        let encoded = filter.encode(&data, &data_type).unwrap();
+        assert_eq!(encoded.len(), DATA.len() * 4);
+        let decoded = read_floats(&filter.decode(&encoded, &data_type).unwrap(), &data_type);
+        assert_eq!(decoded.unwrap()[2], 3.3359375);
+
+        let big_endian = DataType::Float {
+            size: FloatSize::B8,
+            endian: Endian::Big,
+        };
+        assert!(filter.encoded_type(&big_endian).is_err());
+        assert!(QuantizeFilter::new(2)
+            .encoded_type(&i32::ZARR_TYPE)
+            .is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Mounting a store as a read-only local directory with FUSE.
+//!
+//! Each key of the store appears as a file at the same path below the mount
+//! point, for example `meta/root/raw.array.json` and `data/root/raw/c0/0`,
+//! with directories for key prefixes. Tools that only read local files can
+//! then open remote hierarchies, whose values are fetched as files are read.
+//!
+//! Mounting requires FUSE and its `fusermount3` (or `fusermount`) helper.
+//!
+//! ```no_run
+//! use zarr::fuse::StoreFilesystem;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! // Blocks until unmounted, for example with `fusermount3 -u /mnt/volume`.
+//! StoreFilesystem::new(h).mount("/mnt/volume").unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::path::Path;
+use std::time::{
+    Duration,
+    SystemTime,
+};
+
+use fuser::{
+    BackgroundSession,
+    FileAttr,
+    FileType,
+    Filesystem,
+    MountOption,
+    ReplyAttr,
+    ReplyData,
+    ReplyDirectory,
+    ReplyEntry,
+    Request,
+    FUSE_ROOT_ID,
+};
+
+use crate::storage::{
+    ListableStore,
+    PartialReadStore,
+    ReadableStore,
+};
+
+/// Time the kernel may cache attributes and lookups for.
+const TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: u32 = 4096;
+
+#[derive(Debug)]
+struct Node {
+    /// Key of a file, or key prefix ending in `/` of a directory.
+    key: String,
+    parent: u64,
+    kind: FileType,
+}
+
+/// A FUSE filesystem exposing the keys of a store as read-only files.
+#[derive(Debug)]
+pub struct StoreFilesystem<S> {
+    store: S,
+    /// Nodes by inode number less one.
+    nodes: Vec<Node>,
+    inodes: HashMap<String, u64>,
+    mounted: SystemTime,
+}
+
+impl<S: ReadableStore + PartialReadStore + ListableStore> StoreFilesystem<S> {
+    pub fn new(store: S) -> Self {
+        let root = Node {
+            key: "/".to_owned(),
+            parent: FUSE_ROOT_ID,
+            kind: FileType::Directory,
+        };
+        let mut inodes = HashMap::new();
+        inodes.insert(root.key.clone(), FUSE_ROOT_ID);
+        StoreFilesystem {
+            store,
+            nodes: vec![root],
+            inodes,
+            mounted: SystemTime::now(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    /// Mount at a directory, serving requests until unmounted.
+    pub fn mount<P: AsRef<Path>>(self, mountpoint: P) -> Result<(), Error> {
+        fuser::mount2(self, mountpoint, &mount_options())
+    }
+
+    /// Mount at a directory, serving requests in a background thread until
+    /// the returned session is dropped.
+    pub fn spawn_mount<P: AsRef<Path>>(self, mountpoint: P) -> Result<BackgroundSession, Error>
+    where
+        S: Send + 'static,
+    {
+        fuser::spawn_mount2(self, mountpoint, &mount_options())
+    }
+
+    fn node(&self, ino: u64) -> Result<&Node, Error> {
+        ino.checked_sub(1)
+            .and_then(|i| usize::try_from(i).ok())
+            .and_then(|i| self.nodes.get(i))
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))
+    }
+
+    fn intern(&mut self, key: String, parent: u64, kind: FileType) -> u64 {
+        if let Some(&ino) = self.inodes.get(&key) {
+            return ino;
+        }
+        self.nodes.push(Node {
+            key: key.clone(),
+            parent,
+            kind,
+        });
+        let ino = self.nodes.len() as u64;
+        self.inodes.insert(key, ino);
+        ino
+    }
+
+    /// Inode of an entry of a directory, which is a file if the store has
+    /// its key and a directory if the store has keys below it.
+    fn lookup_entry(&mut self, parent: u64, name: &str) -> Result<u64, Error> {
+        let prefix = self.node(parent)?.key.clone();
+        if name.contains('/') {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+        let key = format!("{}{}", prefix, name);
+        if let Some(&ino) = self.inodes.get(&key) {
+            return Ok(ino);
+        }
+        if ReadableStore::exists(&self.store, &key)? {
+            return Ok(self.intern(key, parent, FileType::RegularFile));
+        }
+        let dir_key = key + "/";
+        match self.store.list_dir(&dir_key) {
+            Ok((keys, prefixes)) if !keys.is_empty() || !prefixes.is_empty() => {
+                Ok(self.intern(dir_key, parent, FileType::Directory))
+            }
+            _ => Err(Error::from(ErrorKind::NotFound)),
+        }
+    }
+
+    /// Entries of a directory, including `.` and `..`.
+    fn list_entries(&mut self, ino: u64) -> Result<Vec<(u64, FileType, String)>, Error> {
+        let node = self.node(ino)?;
+        if node.kind != FileType::Directory {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+        let (prefix, parent) = (node.key.clone(), node.parent);
+        let (keys, prefixes) = self.store.list_dir(&prefix)?;
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_owned()),
+            (parent, FileType::Directory, "..".to_owned()),
+        ];
+        for key in keys {
+            let name = key[prefix.len()..].to_owned();
+            entries.push((
+                self.intern(key, ino, FileType::RegularFile),
+                FileType::RegularFile,
+                name,
+            ));
+        }
+        for dir_key in prefixes {
+            let name = dir_key[prefix.len()..].trim_end_matches('/').to_owned();
+            entries.push((
+                self.intern(dir_key, ino, FileType::Directory),
+                FileType::Directory,
+                name,
+            ));
+        }
+        Ok(entries)
+    }
+
+    fn attr(&self, ino: u64, req: &Request<'_>) -> Result<FileAttr, Error> {
+        let node = self.node(ino)?;
+        let (size, perm, nlink) = match node.kind {
+            FileType::Directory => (0, 0o555, 2),
+            _ => {
+                let size = self
+                    .store
+                    .size(&node.key)?
+                    .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+                (size, 0o444, 1)
+            }
+        };
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(u64::from(BLOCK_SIZE)),
+            atime: self.mounted,
+            mtime: self.mounted,
+            ctime: self.mounted,
+            crtime: self.mounted,
+            kind: node.kind,
+            perm,
+            nlink,
+            uid: req.uid(),
+            gid: req.gid(),
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+
+    fn read_file(&self, ino: u64, offset: u64, size: u32) -> Result<Vec<u8>, Error> {
+        let node = self.node(ino)?;
+        if node.kind == FileType::Directory {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+        self.store
+            .get_range(&node.key, offset, Some(u64::from(size)))?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))
+    }
+}
+
+fn mount_options() -> Vec<MountOption> {
+    vec![
+        MountOption::RO,
+        MountOption::FSName("zarr".to_owned()),
+        MountOption::Subtype("zarr".to_owned()),
+    ]
+}
+
+/// Error number a failed store operation is reported to the kernel with.
+fn errno(error: &Error) -> libc::c_int {
+    match error.kind() {
+        ErrorKind::NotFound => libc::ENOENT,
+        ErrorKind::PermissionDenied => libc::EACCES,
+        ErrorKind::InvalidInput => libc::EINVAL,
+        _ => libc::EIO,
+    }
+}
+
+impl<S: ReadableStore + PartialReadStore + ListableStore> Filesystem for StoreFilesystem<S> {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return reply.error(libc::ENOENT),
+        };
+        match self
+            .lookup_entry(parent, name)
+            .and_then(|ino| self.attr(ino, req))
+        {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino, req) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let offset = match u64::try_from(offset) {
+            Ok(offset) => offset,
+            Err(_) => return reply.error(libc::EINVAL),
+        };
+        match self.read_file(ino, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = match self.list_entries(ino) {
+            Ok(entries) => entries,
+            Err(e) => return reply.error(errno(&e)),
+        };
+        let skip = usize::try_from(offset).unwrap_or_default();
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(skip) {
+            // The offset of an entry is that of the entry following it.
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_store_filesystem() {
+        let dir = tempdir::TempDir::new("rust_zarr_fuse_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        h.write_chunk(
+            "g/a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1, 0], vec![1u8; 4]),
+        )
+        .unwrap();
+
+        let mut fs = StoreFilesystem::new(h);
+        fn names<S: ReadableStore + PartialReadStore + ListableStore>(
+            fs: &mut StoreFilesystem<S>,
+            ino: u64,
+        ) -> Vec<String> {
+            let mut names = fs
+                .list_entries(ino)
+                .unwrap()
+                .into_iter()
+                .map(|(_, _, name)| name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        }
+        assert_eq!(
+            names(&mut fs, FUSE_ROOT_ID),
+            vec![".", "..", "data", "meta", "zarr.json"]
+        );
+
+        let meta = fs.lookup_entry(FUSE_ROOT_ID, "meta").unwrap();
+        let root = fs.lookup_entry(meta, "root").unwrap();
+        assert_eq!(names(&mut fs, root), vec![".", "..", "g"]);
+        let g = fs.lookup_entry(root, "g").unwrap();
+        let array_file = fs.lookup_entry(g, "a.array.json").unwrap();
+        assert_eq!(fs.node(array_file).unwrap().kind, FileType::RegularFile);
+        assert_eq!(fs.lookup_entry(root, "g").unwrap(), g);
+        assert_eq!(
+            fs.lookup_entry(root, "missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let document = fs.read_file(array_file, 0, 1 << 20).unwrap();
+        let read_meta: ArrayMetadata = serde_json::from_slice(&document).unwrap();
+        assert_eq!(read_meta, array_meta);
+        assert_eq!(fs.read_file(array_file, 2, 3).unwrap(), &document[2..5]);
+        assert!(fs.read_file(g, 0, 1).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Garbage collection of chunks orphaned by metadata changes.
+//!
+//! Shrinking an array, or rewriting its metadata with another chunk grid,
+//! leaves chunks in the store which no grid position of the current
+//! metadata addresses. These are never read again, so only take space:
+//!
+//! ```
+//! use zarr::gc::HierarchyGc;
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//! use zarr::storage::ZarrConditionalWriter;
+//!
+//! let dir = tempdir::TempDir::new("zarr").unwrap();
+//! let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+//! let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+//!     .chunk_shape(smallvec![2])
+//!     .build();
+//! h.create_array("a", &array_meta).unwrap();
+//! for i in 0..2 {
+//!     let chunk = SliceDataChunk::new(smallvec![i], vec![0u8; 2]);
+//!     h.write_chunk("a", &array_meta, &chunk).unwrap();
+//! }
+//! h.resize_array("a", smallvec![2]).unwrap();
+//!
+//! // Report what would be deleted, then delete it.
+//! let garbage = h.collect_garbage("", true).unwrap();
+//! assert_eq!(garbage[0].keys, vec!["/data/root/a/c1"]);
+//! h.collect_garbage("", false).unwrap();
+//! assert!(h.collect_garbage("", true).unwrap().is_empty());
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use crate::{
+    storage::{
+        check_writeable,
+        get_chunk_key,
+        parse_decimal,
+        ListableStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    usage::array_paths,
+    ArrayMetadata,
+    GridCoord,
+    Hierarchy,
+    HierarchyReader,
+};
+
+/// Orphaned chunks of an array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrayGarbage {
+    /// Path of the array in the hierarchy.
+    pub path: String,
+    /// Keys of the orphaned chunks, in ascending order.
+    pub keys: Vec<String>,
+    /// Total stored size of the orphaned chunks in bytes.
+    pub bytes: u64,
+}
+
+/// Garbage collection for hierarchies over listable stores.
+pub trait HierarchyGc: HierarchyReader {
+    /// Find the orphaned chunks of each array at or below a path, deleting
+    /// them unless this is a dry run. Arrays without orphaned chunks are
+    /// not reported.
+    ///
+    /// Before deleting, each array's metadata is read again and only chunks
+    /// which it does not address either are deleted, so that chunks written
+    /// after a concurrent resize are kept. Deleting the chunks of read-only
+    /// arrays fails with
+    /// [`PermissionDenied`](std::io::ErrorKind::PermissionDenied).
+    fn collect_garbage(&self, path_name: &str, dry_run: bool) -> Result<Vec<ArrayGarbage>, Error>;
+
+    /// Keys of the chunks of an array in the store which its metadata does
+    /// not address, in ascending order.
+    ///
+    /// These are keys in the array's chunk key format whose grid position
+    /// is out of bounds or has the wrong number of dimensions, or which use
+    /// another separator. Keys not in the chunk key format are left alone.
+    fn orphaned_chunks(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Vec<String>, Error>;
+}
+
+/// Whether a key of an array's data is a chunk its metadata does not
+/// address.
+///
+/// Only keys whose coordinates are all decimal integers are chunks; this
+/// accepts any number of dimensions and either separator, so that chunks
+/// of an earlier chunk grid are recognized.
+fn is_orphan(path_name: &str, array_meta: &ArrayMetadata, key: &str) -> bool {
+    let coords = match key.strip_prefix(get_chunk_key(path_name, array_meta, &[]).as_str()) {
+        Some(coords) => coords,
+        None => return false,
+    };
+    let grid_position = if coords.is_empty() {
+        GridCoord::new()
+    } else {
+        match coords
+            .split(['/', '.'])
+            .map(parse_decimal)
+            .collect::<Option<GridCoord>>()
+        {
+            Some(grid_position) => grid_position,
+            None => return false,
+        }
+    };
+    let other_separator = match array_meta.get_separator() {
+        Some(separator) => coords
+            .matches(['/', '.'])
+            .any(|coord_separator| coord_separator != separator),
+        None => false,
+    };
+    other_separator || !array_meta.in_bounds(&grid_position)
+}
+
+impl<S: ReadableStore + ListableStore + WriteableStore + Hierarchy> HierarchyGc for S {
+    fn collect_garbage(&self, path_name: &str, dry_run: bool) -> Result<Vec<ArrayGarbage>, Error> {
+        let mut garbage = vec![];
+        for array_path in array_paths(self, path_name)? {
+            let array_meta = self.get_array_metadata(&array_path)?;
+            let mut keys = self.orphaned_chunks(&array_path, &array_meta)?;
+            if !dry_run && !keys.is_empty() {
+                let array_meta = self.get_array_metadata(&array_path)?;
+                check_writeable(&array_meta)?;
+                keys.retain(|key| is_orphan(&array_path, &array_meta, key));
+            }
+            if keys.is_empty() {
+                continue;
+            }
+
+            let mut bytes = 0;
+            for key in &keys {
+                bytes += self.size(key)?.unwrap_or(0);
+                if !dry_run {
+                    self.erase(key)?;
+                }
+            }
+            garbage.push(ArrayGarbage {
+                path: array_path,
+                keys,
+                bytes,
+            });
+        }
+        Ok(garbage)
+    }
+
+    fn orphaned_chunks(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Vec<String>, Error> {
+        let data_prefix = format!("{}/", self.data_path_key(path_name).to_str().expect("TODO"));
+        let keys = match self.list_prefix(&data_prefix) {
+            Ok(keys) => keys,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut orphans: Vec<String> = keys
+            .into_iter()
+            .filter(|key| is_orphan(path_name, array_meta, key))
+            .collect();
+        orphans.sort();
+        Ok(orphans)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::storage::ZarrConditionalWriter;
+
+    #[test]
+    fn test_collect_garbage() {
+        let dir = tempdir::TempDir::new("rust_zarr_gc_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.create_array("g/b", &array_meta).unwrap();
+        for coord in &[[0, 0], [1, 0], [1, 1]] {
+            let chunk = SliceDataChunk::new(smallvec![coord[0], coord[1]], vec![0u8; 4]);
+            h.write_chunk("a", &array_meta, &chunk).unwrap();
+            h.write_chunk("g/b", &array_meta, &chunk).unwrap();
+        }
+        // Keys outside the chunk key format are not chunks.
+        h.set("/data/root/a/notes.txt", |_| Ok(())).unwrap();
+        h.set("/data/root/a/c3/0/0", |_| Ok(())).unwrap();
+        h.set("/data/root/a/cfoo", |_| Ok(())).unwrap();
+        h.set("/data/root/a/c1/0.bak", |_| Ok(())).unwrap();
+        h.set("/data/root/a/c1/-1", |_| Ok(())).unwrap();
+        h.resize_array("a", smallvec![2, 4]).unwrap();
+
+        let garbage = h.collect_garbage("", true).unwrap();
+        assert_eq!(
+            garbage,
+            vec![ArrayGarbage {
+                path: "a".to_owned(),
+                keys: vec![
+                    "/data/root/a/c1/0".to_owned(),
+                    "/data/root/a/c1/1".to_owned(),
+                    "/data/root/a/c3/0/0".to_owned(),
+                ],
+                bytes: 2 * 4,
+            }]
+        );
+        assert!(h.chunk_exists("a", &array_meta, &[1, 0]).unwrap());
+
+        assert_eq!(h.collect_garbage("a", false).unwrap(), garbage);
+        assert!(!h.chunk_exists("a", &array_meta, &[1, 0]).unwrap());
+        assert!(h.chunk_exists("a", &array_meta, &[0, 0]).unwrap());
+        assert!(h.chunk_exists("g/b", &array_meta, &[1, 1]).unwrap());
+        for key in &[
+            "/data/root/a/notes.txt",
+            "/data/root/a/cfoo",
+            "/data/root/a/c1/0.bak",
+            "/data/root/a/c1/-1",
+        ] {
+            assert!(ReadableStore::exists(&h, key).unwrap());
+        }
+        assert!(h.collect_garbage("", true).unwrap().is_empty());
+
+        let chunk = SliceDataChunk::new(smallvec![0, 1], vec![0u8; 4]);
+        h.write_chunk("a", &array_meta, &chunk).unwrap();
+        h.resize_array("a", smallvec![2, 2]).unwrap();
+        h.seal("a").unwrap();
+        assert_eq!(h.collect_garbage("a", true).unwrap()[0].keys.len(), 1);
+        assert_eq!(
+            h.collect_garbage("a", false).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+    }
+}
@@ -0,0 +1,506 @@
+//! Handles to open arrays for long-running services.
+//!
+//! An [`ArrayHandle`] caches an array's metadata so a server need not reread
+//! it for every request, and notices when another process changes it, for
+//! example by resizing the array or updating its attributes, without
+//! reopening the store.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use zarr::handle::ArrayHandle;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/live.zr3").unwrap();
+//! // Check for changes at most once a second.
+//! let array = ArrayHandle::open(&h, "acquisition")
+//!     .unwrap()
+//!     .with_auto_refresh(Duration::from_secs(1));
+//! let shape = array.get_array_metadata().unwrap().get_shape().to_vec();
+//! ```
+//!
+//! Handles also read and write single elements, keeping the most recently
+//! used chunk decoded so that nearby elements are not reread:
+//!
+//! ```no_run
+//! # use zarr::handle::ArrayHandle;
+//! # use zarr::prelude::*;
+//! # let h = FilesystemHierarchy::open("/tmp/live.zr3").unwrap();
+//! let array = ArrayHandle::open(&h, "labels").unwrap();
+//! let label: u64 = array.get(&[120, 45, 6]).unwrap();
+//! array.set(&[120, 45, 7], label).unwrap();
+//! ```
+
+use std::any::Any;
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+    RwLock,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::chunk::{
+    DataChunk,
+    ReadableDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+use crate::metadata::{
+    decode_attributes,
+    split_attributes,
+};
+use crate::storage::{
+    content_etag,
+    read_array_metadata,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    GridCoord,
+    Hierarchy,
+    HierarchyReader,
+    HierarchyWriter,
+    JsonObject,
+    ReflectedType,
+};
+
+#[derive(Debug)]
+struct HandleState {
+    /// Metadata without its attributes, which are decoded on demand.
+    array_meta: Arc<ArrayMetadata>,
+    /// Entity tag of the metadata document `array_meta` was read from.
+    etag: String,
+    /// Undecoded attributes of the metadata document.
+    attributes_document: Option<Vec<u8>>,
+    /// Attributes decoded from `attributes_document`, once requested.
+    attributes: Option<Arc<JsonObject>>,
+    /// `array_meta` with `attributes`, once requested.
+    full_array_meta: Option<Arc<ArrayMetadata>>,
+    checked: Instant,
+}
+
+/// Grid position and data of the chunk most recently used by a handle, as
+/// an `Option<VecDataChunk<T>>` for the element type it was read as, with
+/// `None` if the chunk does not exist.
+type CachedChunk = (GridCoord, Box<dyn Any + Send + Sync>);
+
+/// An open array, caching its metadata until it is refreshed.
+#[derive(Debug)]
+pub struct ArrayHandle<'a, H: ?Sized> {
+    hierarchy: &'a H,
+    path_name: String,
+    refresh_interval: Option<Duration>,
+    state: RwLock<HandleState>,
+    chunk: Mutex<Option<CachedChunk>>,
+}
+
+impl<'a, H: ReadableStore + Hierarchy + ?Sized> ArrayHandle<'a, H> {
+    pub fn open(hierarchy: &'a H, path_name: &str) -> Result<Self, Error> {
+        let path_name = crate::canonicalize_path(path_name).to_owned();
+        let array_key = hierarchy.array_metadata_key(&path_name);
+        let array_key = array_key.to_str().expect("TODO");
+        let mut document = Vec::new();
+        hierarchy
+            .get(array_key)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?
+            .read_to_end(&mut document)?;
+        Self::with_document(hierarchy, path_name, array_key, &document)
+    }
+
+    /// Open handles to several arrays, fetching their metadata with one
+    /// [`get_many`](ReadableStore::get_many), as for the scales of a
+    /// multiscale pyramid.
+    ///
+    /// Fails with [`ErrorKind::NotFound`] if any of the arrays does not
+    /// exist.
+    pub fn open_many(hierarchy: &'a H, path_names: &[&str]) -> Result<Vec<Self>, Error> {
+        let path_names: Vec<String> = path_names
+            .iter()
+            .map(|path_name| crate::canonicalize_path(path_name).to_owned())
+            .collect();
+        let array_keys: Vec<String> = path_names
+            .iter()
+            .map(|path_name| {
+                hierarchy
+                    .array_metadata_key(path_name)
+                    .to_str()
+                    .expect("TODO")
+                    .to_owned()
+            })
+            .collect();
+        let documents = hierarchy.get_many(&array_keys)?;
+        path_names
+            .into_iter()
+            .zip(array_keys.iter().zip(documents))
+            .map(|(path_name, (array_key, document))| {
+                let document = document.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+                Self::with_document(hierarchy, path_name, array_key, &document)
+            })
+            .collect()
+    }
+
+    fn with_document(
+        hierarchy: &'a H,
+        path_name: String,
+        array_key: &str,
+        document: &[u8],
+    ) -> Result<Self, Error> {
+        let (array_meta, attributes_document) = read_lazily(hierarchy, array_key, document)?;
+        Ok(ArrayHandle {
+            hierarchy,
+            path_name,
+            refresh_interval: None,
+            state: RwLock::new(HandleState {
+                array_meta: Arc::new(array_meta),
+                etag: content_etag(document),
+                attributes_document,
+                attributes: None,
+                full_array_meta: None,
+                checked: Instant::now(),
+            }),
+            chunk: Mutex::new(None),
+        })
+    }
+
+    /// Refresh metadata automatically when it is accessed more than an
+    /// interval after it was last checked for changes.
+    pub fn with_auto_refresh(mut self, interval: Duration) -> Self {
+        self.refresh_interval = Some(interval);
+        self
+    }
+
+    pub fn get_path_name(&self) -> &str {
+        &self.path_name
+    }
+
+    /// The array's metadata, including its attributes, refreshed first if
+    /// automatic refresh is enabled and due.
+    ///
+    /// The attributes are decoded as for [`attrs`](Self::attrs), so callers
+    /// which only need the array's shape or chunk grid need not avoid this.
+    pub fn get_array_metadata(&self) -> Result<Arc<ArrayMetadata>, Error> {
+        self.refresh_if_due()?;
+        if let Some(array_meta) = &self.state.read().unwrap().full_array_meta {
+            return Ok(Arc::clone(array_meta));
+        }
+        let mut state = self.state.write().unwrap();
+        if let Some(array_meta) = &state.full_array_meta {
+            return Ok(Arc::clone(array_meta));
+        }
+        let attributes = decoded_attributes(&mut state)?;
+        let mut array_meta = ArrayMetadata::clone(&state.array_meta);
+        array_meta.attributes = JsonObject::clone(&attributes);
+        let array_meta = Arc::new(array_meta);
+        state.full_array_meta = Some(Arc::clone(&array_meta));
+        Ok(array_meta)
+    }
+
+    /// The array's attributes, decoded from its metadata document when first
+    /// requested and cached until the metadata changes, so that opening
+    /// many arrays does not pay for attributes that are never read.
+    ///
+    /// Metadata is refreshed first if automatic refresh is enabled and due.
+    pub fn attrs(&self) -> Result<Arc<JsonObject>, Error> {
+        self.refresh_if_due()?;
+        if let Some(attributes) = &self.state.read().unwrap().attributes {
+            return Ok(Arc::clone(attributes));
+        }
+        decoded_attributes(&mut self.state.write().unwrap())
+    }
+
+    /// The array's metadata without its attributes, for element access.
+    fn array_meta(&self) -> Result<Arc<ArrayMetadata>, Error> {
+        self.refresh_if_due()?;
+        Ok(Arc::clone(&self.state.read().unwrap().array_meta))
+    }
+
+    fn refresh_if_due(&self) -> Result<(), Error> {
+        if let Some(interval) = self.refresh_interval {
+            let due = self.state.read().unwrap().checked.elapsed() >= interval;
+            if due {
+                self.refresh_metadata()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reread the array's metadata from the store, returning whether it
+    /// changed since it was last read.
+    ///
+    /// Fails with [`ErrorKind::NotFound`] if the array has been removed, in
+    /// which case the previous metadata is kept.
+    ///
+    /// The chunk kept decoded for element access is discarded, so elements
+    /// changed by other writers are seen once refreshed.
+    pub fn refresh_metadata(&self) -> Result<bool, Error> {
+        self.chunk.lock().unwrap().take();
+        let array_key = self.hierarchy.array_metadata_key(&self.path_name);
+        let mut document = Vec::new();
+        self.hierarchy
+            .get(array_key.to_str().expect("TODO"))?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Array no longer exists"))?
+            .read_to_end(&mut document)?;
+        let etag = content_etag(&document);
+
+        let mut state = self.state.write().unwrap();
+        state.checked = Instant::now();
+        if state.etag == etag {
+            return Ok(false);
+        }
+        let (array_meta, attributes_document) =
+            read_lazily(self.hierarchy, array_key.to_str().expect("TODO"), &document)?;
+        let changed =
+            *state.array_meta != array_meta || state.attributes_document != attributes_document;
+        state.array_meta = Arc::new(array_meta);
+        state.full_array_meta = None;
+        state.etag = etag;
+        if state.attributes_document != attributes_document {
+            state.attributes_document = attributes_document;
+            state.attributes = None;
+        }
+        Ok(changed)
+    }
+
+    /// Read the element at a position, or the fill value if its chunk does
+    /// not exist.
+    pub fn get<T: ReflectedType>(&self, position: &[u64]) -> Result<T, Error>
+    where
+        H: HierarchyReader,
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+    {
+        let array_meta = self.array_meta()?;
+        let (grid_position, index) = locate(&array_meta, position)?;
+        let mut cached = self.chunk.lock().unwrap();
+        let chunk = self.cached_chunk::<T>(&mut cached, &array_meta, grid_position)?;
+        match chunk {
+            Some(chunk) => element(chunk, index).cloned(),
+            None => array_meta.get_effective_fill_value(),
+        }
+    }
+
+    /// Write the element at a position, creating its chunk filled with the
+    /// fill value if it does not exist.
+    pub fn set<T: ReflectedType>(&self, position: &[u64], value: T) -> Result<(), Error>
+    where
+        H: HierarchyReader + HierarchyWriter,
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+    {
+        let array_meta = self.array_meta()?;
+        let (grid_position, index) = locate(&array_meta, position)?;
+        let mut cached = self.chunk.lock().unwrap();
+        let chunk = self
+            .cached_chunk::<T>(&mut cached, &array_meta, grid_position.clone())?
+            .take();
+        // Until the chunk is written the cached copy would be wrong.
+        cached.take();
+
+        let mut data = match chunk {
+            Some(chunk) => chunk.into_data(),
+            None => vec![
+                array_meta.get_effective_fill_value::<T>()?;
+                array_meta.get_chunk_num_elements()
+            ],
+        };
+        *data.get_mut(index).ok_or_else(short_chunk)? = value;
+        let chunk = VecDataChunk::new(grid_position.clone(), data);
+        self.hierarchy
+            .write_chunk(&self.path_name, &array_meta, &chunk)?;
+        *cached = Some((grid_position, Box::new(Some(chunk))));
+        Ok(())
+    }
+
+    /// The data of a chunk, from the cache if it holds the chunk for this
+    /// element type and otherwise read into the cache.
+    fn cached_chunk<'c, T: ReflectedType>(
+        &self,
+        cached: &'c mut Option<CachedChunk>,
+        array_meta: &ArrayMetadata,
+        grid_position: GridCoord,
+    ) -> Result<&'c mut Option<VecDataChunk<T>>, Error>
+    where
+        H: HierarchyReader,
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+    {
+        let hit = matches!(
+            cached,
+            Some((position, chunk))
+                if *position == grid_position && chunk.is::<Option<VecDataChunk<T>>>()
+        );
+        if !hit {
+            let chunk = self.hierarchy.read_chunk::<T>(
+                &self.path_name,
+                array_meta,
+                grid_position.clone(),
+            )?;
+            *cached = Some((grid_position, Box::new(chunk)));
+        }
+        Ok(cached
+            .as_mut()
+            .and_then(|(_, chunk)| chunk.downcast_mut())
+            .expect("Chunk was just cached"))
+    }
+}
+
+/// The attributes of a handle's metadata, decoding them if they are not yet.
+fn decoded_attributes(state: &mut HandleState) -> Result<Arc<JsonObject>, Error> {
+    if let Some(attributes) = &state.attributes {
+        return Ok(Arc::clone(attributes));
+    }
+    let attributes = Arc::new(decode_attributes(state.attributes_document.as_deref())?);
+    state.attributes = Some(Arc::clone(&attributes));
+    Ok(attributes)
+}
+
+fn locate(array_meta: &ArrayMetadata, position: &[u64]) -> Result<(GridCoord, usize), Error> {
+    array_meta.element_location(position).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Element position {:?} is outside the array", position),
+        )
+    })
+}
+
+fn element<T: ReflectedType>(chunk: &VecDataChunk<T>, index: usize) -> Result<&T, Error>
+where
+    VecDataChunk<T>: DataChunk<T>,
+{
+    chunk.get_data().get(index).ok_or_else(short_chunk)
+}
+
+fn short_chunk() -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        "Chunk has fewer elements than its shape",
+    )
+}
+
+/// Read array metadata from a document without decoding its attributes,
+/// returning the undecoded attributes.
+fn read_lazily<H: ReadableStore + Hierarchy + ?Sized>(
+    hierarchy: &H,
+    array_key: &str,
+    document: &[u8],
+) -> Result<(ArrayMetadata, Option<Vec<u8>>), Error> {
+    let (document, attributes) = split_attributes(document);
+    let array_meta = read_array_metadata(hierarchy, array_key, &document[..])?;
+    Ok((array_meta, attributes.map(<[u8]>::to_vec)))
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::storage::ZarrConditionalWriter;
+
+    #[test]
+    fn test_refresh_metadata() {
+        let dir = tempdir::TempDir::new("rust_zarr_handle_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+
+        let handle = ArrayHandle::open(&h, "/a").unwrap();
+        assert_eq!(handle.get_path_name(), "a");
+        assert_eq!(*handle.get_array_metadata().unwrap(), array_meta);
+        assert!(!handle.refresh_metadata().unwrap());
+
+        let handles = ArrayHandle::open_many(&h, &["a", "/a"]).unwrap();
+        assert_eq!(handles[1].get_path_name(), "a");
+        assert_eq!(*handles[1].get_array_metadata().unwrap(), array_meta);
+        assert_eq!(
+            ArrayHandle::open_many(&h, &["a", "missing"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+
+        // Changes by another writer are only seen once refreshed.
+        let other = FilesystemHierarchy::open(dir.path()).unwrap();
+        let resized = other.resize_array("a", smallvec![8, 4]).unwrap();
+        assert_eq!(handle.get_array_metadata().unwrap().get_shape(), &[4, 4]);
+        assert!(handle.refresh_metadata().unwrap());
+        assert_eq!(*handle.get_array_metadata().unwrap(), resized);
+        assert!(!handle.refresh_metadata().unwrap());
+
+        let auto = ArrayHandle::open(&h, "a")
+            .unwrap()
+            .with_auto_refresh(Duration::ZERO);
+        other
+            .set_attribute("a", "updated".to_owned(), true)
+            .unwrap();
+        assert_eq!(auto.attrs().unwrap()["updated"], true);
+        assert_eq!(
+            auto.get_array_metadata().unwrap().get_attributes()["updated"],
+            true
+        );
+        other
+            .set_attribute("a", "updated".to_owned(), false)
+            .unwrap();
+        assert_eq!(
+            auto.get_array_metadata().unwrap().get_attributes()["updated"],
+            false
+        );
+        assert_eq!(auto.attrs().unwrap()["updated"], false);
+
+        other.remove("a").unwrap();
+        assert_eq!(
+            handle.refresh_metadata().unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(*handle.get_array_metadata().unwrap(), resized);
+    }
+
+    #[test]
+    fn test_get_set_elements() {
+        let dir = tempdir::TempDir::new("rust_zarr_handle_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![5, 6], i16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 3])
+            .fill_value(-1)
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        let data: Vec<i16> = (0..6).collect();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1, 1], data),
+        )
+        .unwrap();
+
+        let handle = ArrayHandle::open(&h, "a").unwrap();
+        // Column-major within the chunk at (1, 1), offset (1, 1).
+        assert_eq!(handle.get::<i16>(&[3, 4]).unwrap(), 3);
+        assert_eq!(handle.get::<i16>(&[0, 0]).unwrap(), -1);
+        assert_eq!(
+            handle.get::<i16>(&[5, 0]).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            handle.get::<i16>(&[0]).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        handle.set(&[0, 1], 7i16).unwrap();
+        handle.set(&[3, 3], 8i16).unwrap();
+        assert_eq!(handle.get::<i16>(&[0, 1]).unwrap(), 7);
+        assert_eq!(handle.get::<i16>(&[0, 2]).unwrap(), -1);
+        assert_eq!(handle.get::<i16>(&[3, 3]).unwrap(), 8);
+        assert_eq!(handle.get::<i16>(&[3, 4]).unwrap(), 3);
+
+        let created = h
+            .read_chunk::<i16>("a", &array_meta, smallvec![0, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(created.get_data(), &[-1, -1, 7, -1, -1, -1]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Hashing of values for content addresses, entity tags and comparisons.
+//!
+//! Which hash suits a deployment is a policy choice, so callers pick a
+//! [`HashAlgorithm`], or set it crate-wide with
+//! [`Config::hash_algorithm`](crate::config::Config::hash_algorithm):
+//!
+//! - [`HashAlgorithm::Fnv1a`]: 64-bit FNV-1a, always available. Fast for
+//!   small values but not collision resistant.
+//! - [`HashAlgorithm::Sha256`]: SHA-256, with the `sha256` feature. Slower,
+//!   but collision resistant, for content addresses of untrusted values.
+//!
+//! ```
+//! use zarr::hash::HashAlgorithm;
+//!
+//! let hasher = HashAlgorithm::Fnv1a.hasher();
+//! assert_eq!(hasher.hex_digest(b"a"), "af63dc4c8601ec8c");
+//! assert_eq!(hasher.content_address(b"a"), "fnv1a:af63dc4c8601ec8c");
+//! ```
+
+use std::fmt;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::str::FromStr;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::storage::stable_hash;
+
+/// A hash function over values.
+pub trait Hasher: fmt::Debug + Send + Sync {
+    /// The algorithm of this hasher.
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Digest of a value.
+    fn digest(&self, value: &[u8]) -> Vec<u8>;
+
+    /// Digest of a value in lowercase hex.
+    fn hex_digest(&self, value: &[u8]) -> String {
+        use std::fmt::Write;
+
+        let digest = self.digest(value);
+        let mut hex = String::with_capacity(2 * digest.len());
+        for byte in digest {
+            write!(hex, "{:02x}", byte).unwrap();
+        }
+        hex
+    }
+
+    /// Address of a value by its content, the name of the algorithm and the
+    /// hex digest, so that addresses of different algorithms never collide.
+    fn content_address(&self, value: &[u8]) -> String {
+        format!("{}:{}", self.algorithm(), self.hex_digest(value))
+    }
+
+    /// Entity tag of a value, its length and hex digest.
+    ///
+    /// See [`content_etag`](crate::storage::content_etag).
+    fn etag(&self, value: &[u8]) -> String {
+        format!("{:x}-{}", value.len(), self.hex_digest(value))
+    }
+}
+
+/// Hash algorithms, by name in configuration and serialized metadata.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Fnv1a,
+    #[cfg(feature = "sha256")]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Name of the algorithm, as in content addresses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Fnv1a => "fnv1a",
+            #[cfg(feature = "sha256")]
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// A hasher of this algorithm.
+    pub fn hasher(&self) -> &'static dyn Hasher {
+        match self {
+            HashAlgorithm::Fnv1a => &Fnv1a,
+            #[cfg(feature = "sha256")]
+            HashAlgorithm::Sha256 => &Sha256,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    /// Algorithms whose feature is not enabled fail to parse with
+    /// [`ErrorKind::Unsupported`].
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "fnv1a" => Ok(HashAlgorithm::Fnv1a),
+            #[cfg(feature = "sha256")]
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            #[cfg(not(feature = "sha256"))]
+            "sha256" => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Hashing with sha256 requires the sha256 feature of zarr",
+            )),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown hash algorithm: {}", s),
+            )),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, with big-endian digests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fnv1a;
+
+impl Hasher for Fnv1a {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Fnv1a
+    }
+
+    fn digest(&self, value: &[u8]) -> Vec<u8> {
+        stable_hash(value).to_be_bytes().to_vec()
+    }
+}
+
+/// SHA-256.
+#[cfg(feature = "sha256")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256;
+
+#[cfg(feature = "sha256")]
+impl Hasher for Sha256 {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha256
+    }
+
+    fn digest(&self, value: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+
+        sha2::Sha256::digest(value).to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashers() {
+        assert_eq!(
+            Fnv1a.etag(b"abc"),
+            crate::storage::content_etag(b"abc"),
+            "Content entity tags are unchanged"
+        );
+        assert_eq!(Fnv1a.hex_digest(b""), "cbf29ce484222325");
+        #[cfg(feature = "sha256")]
+        assert_eq!(
+            Sha256.content_address(b""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_parse_algorithm() {
+        assert_eq!(
+            "FNV1A".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Fnv1a
+        );
+        assert_eq!(
+            "md5".parse::<HashAlgorithm>().unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        for algorithm in ["sha256"].iter() {
+            if let Ok(parsed) = algorithm.parse::<HashAlgorithm>() {
+                assert_eq!(parsed.name(), *algorithm);
+                assert_eq!(parsed.hasher().algorithm(), parsed);
+            }
+        }
+        assert_eq!(
+            serde_json::to_string(&HashAlgorithm::Fnv1a).unwrap(),
+            "\"fnv1a\""
+        );
+    }
+}
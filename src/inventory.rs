@@ -0,0 +1,290 @@
+//! Machine-readable inventories of hierarchies for catalogs and data portals.
+//!
+//! ```
+//! use zarr::inventory::HierarchyInventory;
+//!
+//! fn publish<H: HierarchyInventory>(h: &H) -> std::io::Result<()> {
+//!     let inventory = h.inventory("")?;
+//!     std::fs::write("inventory.json", serde_json::to_vec_pretty(&inventory)?)?;
+//!     std::fs::write("inventory.yaml", inventory.to_yaml()?)?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Error;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::compression::CompressionType;
+use crate::filter::FilterType;
+use crate::storage::{
+    ListableStore,
+    ReadableStore,
+};
+use crate::usage::HierarchyUsage;
+use crate::{
+    ExtensibleDataType,
+    Hierarchy,
+    HierarchyLister,
+    HierarchyReader,
+    JsonObject,
+};
+
+/// Description of the groups and arrays at or below a path of a hierarchy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    pub groups: Vec<GroupInventory>,
+    pub arrays: Vec<ArrayInventory>,
+    /// Total stored size of all arrays' chunks in bytes.
+    pub stored_bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupInventory {
+    pub path: String,
+    /// Whether the group has no metadata document of its own, existing only
+    /// because nodes exist below it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub implicit: bool,
+    pub attributes: JsonObject,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArrayInventory {
+    pub path: String,
+    pub shape: Vec<u64>,
+    pub chunk_shape: Vec<u32>,
+    pub data_type: ExtensibleDataType,
+    pub compressor: CompressionType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterType>,
+    pub fill_value: Option<Value>,
+    /// Number of chunks in the array's grid.
+    pub chunks_expected: u64,
+    /// Number of chunks present in the store.
+    pub chunks_present: u64,
+    /// Total stored size of present chunks in bytes.
+    pub stored_bytes: u64,
+    /// Total uncompressed size of present chunks in bytes.
+    pub uncompressed_bytes: u64,
+    pub attributes: JsonObject,
+}
+
+impl Inventory {
+    /// The inventory as a YAML document.
+    pub fn to_yaml(&self) -> Result<String, Error> {
+        let mut yaml = yaml_lines(&serde_json::to_value(self)?).join("\n");
+        yaml.push('\n');
+        Ok(yaml)
+    }
+}
+
+/// Lines of a value in YAML block style. Scalars and empty collections are
+/// written in their JSON form, which is also valid YAML.
+fn yaml_lines(value: &Value) -> Vec<String> {
+    let mut lines = vec![];
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let key = yaml_key(key);
+                if is_yaml_block(value) {
+                    lines.push(format!("{}:", key));
+                    lines.extend(yaml_lines(value).into_iter().map(|l| format!("  {}", l)));
+                } else {
+                    lines.push(format!("{}: {}", key, value));
+                }
+            }
+        }
+        Value::Array(values) if !values.is_empty() => {
+            for value in values {
+                if is_yaml_block(value) {
+                    let mut item = yaml_lines(value).into_iter();
+                    lines.extend(item.next().map(|l| format!("- {}", l)));
+                    lines.extend(item.map(|l| format!("  {}", l)));
+                } else {
+                    lines.push(format!("- {}", value));
+                }
+            }
+        }
+        scalar => lines.push(scalar.to_string()),
+    }
+    lines
+}
+
+fn is_yaml_block(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(values) => !values.is_empty(),
+        _ => false,
+    }
+}
+
+/// Keys are written plainly if they could not be mistaken for another type
+/// of scalar, and quoted otherwise.
+fn yaml_key(key: &str) -> String {
+    const RESERVED: &[&str] = &["true", "false", "null", "yes", "no", "on", "off", "y", "n"];
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&key.to_ascii_lowercase().as_str());
+    if plain {
+        key.to_owned()
+    } else {
+        Value::String(key.to_owned()).to_string()
+    }
+}
+
+/// Inventories of hierarchies over listable stores.
+pub trait HierarchyInventory: HierarchyUsage {
+    /// Describe the groups and arrays at or below a path, sorted by path.
+    fn inventory(&self, path_name: &str) -> Result<Inventory, Error>;
+}
+
+impl<S: ReadableStore + ListableStore + Hierarchy> HierarchyInventory for S {
+    fn inventory(&self, path_name: &str) -> Result<Inventory, Error> {
+        let mut groups = vec![];
+        let mut arrays = vec![];
+        let mut to_visit = vec![crate::canonicalize_path(path_name).to_owned()];
+
+        while let Some(path_name) = to_visit.pop() {
+            let array_key = self.array_metadata_key(&path_name);
+            if ReadableStore::exists(self, array_key.to_str().expect("TODO"))? {
+                let array_meta = self.get_array_metadata(&path_name)?;
+                let usage = self.array_usage(&path_name, &array_meta)?;
+                arrays.push(ArrayInventory {
+                    path: usage.path,
+                    shape: array_meta.get_shape().to_vec(),
+                    chunk_shape: array_meta.get_chunk_shape().to_vec(),
+                    data_type: array_meta.get_data_type().clone(),
+                    compressor: array_meta.get_compressor().clone(),
+                    filters: array_meta.get_filters().to_vec(),
+                    fill_value: array_meta.get_fill_value().cloned(),
+                    chunks_expected: usage.chunks_expected,
+                    chunks_present: usage.chunks_present,
+                    stored_bytes: usage.stored_bytes,
+                    uncompressed_bytes: usage.uncompressed_bytes,
+                    attributes: array_meta.attributes,
+                });
+                continue;
+            }
+
+            let group_key = self.group_metadata_key(&path_name);
+            let implicit = !ReadableStore::exists(self, group_key.to_str().expect("TODO"))?;
+            let attributes = if implicit {
+                JsonObject::new()
+            } else {
+                self.get_group_metadata(&path_name)?.attributes
+            };
+            for name in HierarchyLister::list_nodes(self, &path_name)? {
+                to_visit.push(if path_name.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", path_name, name)
+                });
+            }
+            groups.push(GroupInventory {
+                path: path_name,
+                implicit,
+                attributes,
+            });
+        }
+
+        groups.sort_by(|a, b| a.path.cmp(&b.path));
+        arrays.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Inventory {
+            stored_bytes: arrays.iter().map(|a| a.stored_bytes).sum(),
+            groups,
+            arrays,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml() {
+        let value = serde_json::json!({
+            "path": "a/b",
+            "shape": [4, 5],
+            "empty": [],
+            "attributes": {"title": "Cells: \"fixed\"", "true": null},
+            "nested": [{"x": 1, "z": [true]}, 2],
+        });
+        assert_eq!(
+            yaml_lines(&value).join("\n"),
+            "attributes:\n  title: \"Cells: \\\"fixed\\\"\"\n  \"true\": null\nempty: []\n\
+             nested:\n  - x: 1\n    z:\n      - true\n  - 2\npath: \"a/b\"\nshape:\n  - 4\n  - 5"
+        );
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_inventory() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_inventory_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(CompressionType::Raw(Default::default()))
+            .build();
+        h.create_group("g").unwrap();
+        let mut attributes = JsonObject::new();
+        attributes.insert("title".to_owned(), "Example".into());
+        h.set_attributes("g", attributes.clone()).unwrap();
+        h.create_array("g/a", &array_meta).unwrap();
+        h.create_array("implicit/b", &array_meta).unwrap();
+        h.write_chunk(
+            "g/a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0, 0], vec![1u16; 4]),
+        )
+        .unwrap();
+
+        let inventory = h.inventory("").unwrap();
+        assert_eq!(
+            inventory
+                .groups
+                .iter()
+                .map(|g| (g.path.as_str(), g.implicit))
+                .collect::<Vec<_>>(),
+            vec![("", true), ("g", false), ("implicit", true)]
+        );
+        assert_eq!(inventory.groups[1].attributes, attributes);
+        assert_eq!(
+            inventory
+                .arrays
+                .iter()
+                .map(|a| a.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["g/a", "implicit/b"]
+        );
+        let a = &inventory.arrays[0];
+        assert_eq!(a.shape, vec![4, 4]);
+        assert_eq!(a.chunk_shape, vec![2, 2]);
+        assert_eq!(a.chunks_expected, 4);
+        assert_eq!(a.chunks_present, 1);
+        assert_eq!(a.stored_bytes, 8);
+        assert_eq!(inventory.stored_bytes, 8);
+
+        let json = serde_json::to_value(&inventory).unwrap();
+        assert_eq!(json["arrays"][0]["data_type"], "<u2");
+        assert_eq!(json["arrays"][0]["compressor"]["codec"], "raw");
+        assert_eq!(
+            serde_json::from_value::<Inventory>(json).unwrap(),
+            inventory
+        );
+        let yaml = inventory.to_yaml().unwrap();
+        assert!(yaml.contains("\n    path: \"g/a\"\n    shape:\n      - 4\n"));
+
+        assert_eq!(h.inventory("g").unwrap().arrays, inventory.arrays[..1]);
+    }
+}
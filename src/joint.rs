@@ -0,0 +1,210 @@
+//! Reading aligned chunks and regions of several arrays together.
+//!
+//! Arrays sharing a chunk grid, such as raw data with its labels and mask,
+//! are often consumed together. A [`JointReader`] reads the same chunk or
+//! region of each array concurrently, yielding them as a tuple typed by the
+//! arrays' element types.
+//!
+//! ```no_run
+//! use zarr::joint::ZarrJointReader;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/training.zr3").unwrap();
+//! let raw = h.get_array_metadata("raw").unwrap();
+//! let labels = h.get_array_metadata("labels").unwrap();
+//! let mask = h.get_array_metadata("mask").unwrap();
+//! let reader = h
+//!     .joint::<(u8, u64, bool)>(&[("raw", &raw), ("labels", &labels), ("mask", &mask)])
+//!     .unwrap();
+//! for block in reader.regions() {
+//!     let (bbox, (raw, labels, mask)) = block.unwrap();
+//!     // ...
+//! }
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::marker::PhantomData;
+use std::thread::ScopedJoinHandle;
+
+use ndarray::ArrayD;
+
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+};
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    GridCoord,
+    HierarchyReader,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    VecDataChunk,
+};
+
+/// Tuples of element types of arrays read jointly, one per array.
+///
+/// Implemented for tuples of one to four element types.
+pub trait JointElements {
+    const NUM_ARRAYS: usize;
+    /// Chunk of each array at a grid position, `None` where absent.
+    type Chunks;
+    /// Region of each array in a bounding box.
+    type Regions;
+
+    fn read_chunks<H: HierarchyReader + Sync>(
+        hierarchy: &H,
+        arrays: &[(String, ArrayMetadata)],
+        grid_position: &GridCoord,
+    ) -> Result<Self::Chunks, Error>;
+
+    fn read_regions<H: HierarchyReader + Sync>(
+        hierarchy: &H,
+        arrays: &[(String, ArrayMetadata)],
+        bbox: &BoundingBox,
+    ) -> Result<Self::Regions, Error>;
+}
+
+fn join<T>(handle: ScopedJoinHandle<'_, Result<T, Error>>) -> Result<T, Error> {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+macro_rules! joint_elements_tuple {
+    ($n:expr; $($ty:ident $i:tt),+) => {
+        impl<$($ty: ReflectedType),+> JointElements for ($($ty,)+)
+        where
+            $(VecDataChunk<$ty>: DataChunk<$ty> + ReinitDataChunk<$ty> + ReadableDataChunk,)+
+        {
+            const NUM_ARRAYS: usize = $n;
+            type Chunks = ($(Option<VecDataChunk<$ty>>,)+);
+            type Regions = ($(ArrayD<$ty>,)+);
+
+            fn read_chunks<H: HierarchyReader + Sync>(
+                hierarchy: &H,
+                arrays: &[(String, ArrayMetadata)],
+                grid_position: &GridCoord,
+            ) -> Result<Self::Chunks, Error> {
+                std::thread::scope(|scope| {
+                    let handles = ($(
+                        scope.spawn(|| {
+                            let (path_name, array_meta) = &arrays[$i];
+                            hierarchy.read_chunk::<$ty>(path_name, array_meta, grid_position.clone())
+                        }),
+                    )+);
+                    Ok(($(join(handles.$i)?,)+))
+                })
+            }
+
+            fn read_regions<H: HierarchyReader + Sync>(
+                hierarchy: &H,
+                arrays: &[(String, ArrayMetadata)],
+                bbox: &BoundingBox,
+            ) -> Result<Self::Regions, Error> {
+                std::thread::scope(|scope| {
+                    let handles = ($(
+                        scope.spawn(|| {
+                            let (path_name, array_meta) = &arrays[$i];
+                            hierarchy.read_ndarray::<$ty>(path_name, array_meta, bbox)
+                        }),
+                    )+);
+                    Ok(($(join(handles.$i)?,)+))
+                })
+            }
+        }
+    };
+}
+
+joint_elements_tuple!(1; A 0);
+joint_elements_tuple!(2; A 0, B 1);
+joint_elements_tuple!(3; A 0, B 1, C 2);
+joint_elements_tuple!(4; A 0, B 1, C 2, D 3);
+
+/// Reader of the same chunks and regions of several arrays sharing a chunk
+/// grid, reading from each array concurrently.
+#[derive(Debug)]
+pub struct JointReader<'a, H, J> {
+    hierarchy: &'a H,
+    arrays: Vec<(String, ArrayMetadata)>,
+    elements: PhantomData<J>,
+}
+
+impl<'a, H: HierarchyReader + Sync, J: JointElements> JointReader<'a, H, J> {
+    /// Shape and chunk grid shared by the arrays.
+    pub fn get_array_metadata(&self) -> &ArrayMetadata {
+        &self.arrays[0].1
+    }
+
+    /// Read the chunk of each array at a grid position.
+    pub fn read_chunks(&self, grid_position: GridCoord) -> Result<J::Chunks, Error> {
+        J::read_chunks(self.hierarchy, &self.arrays, &grid_position)
+    }
+
+    /// Read a bounding box of each array.
+    pub fn read_regions(&self, bbox: &BoundingBox) -> Result<J::Regions, Error> {
+        J::read_regions(self.hierarchy, &self.arrays, bbox)
+    }
+
+    /// Chunks of the arrays at every grid position, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = Result<(GridCoord, J::Chunks), Error>> + '_ {
+        self.get_array_metadata().coord_iter().map(move |coord| {
+            let grid_position = GridCoord::from(coord);
+            let chunks = self.read_chunks(grid_position.clone())?;
+            Ok((grid_position, chunks))
+        })
+    }
+
+    /// Regions of the arrays covered by each chunk, clipped to the array
+    /// bounds, in grid order.
+    pub fn regions(&self) -> impl Iterator<Item = Result<(BoundingBox, J::Regions), Error>> + '_ {
+        let array_meta = self.get_array_metadata();
+        let bounds = array_meta.get_bounds();
+        array_meta.coord_iter().map(move |coord| {
+            let mut bbox = array_meta.get_chunk_bounds(&coord);
+            bbox.intersect(&bounds);
+            let regions = self.read_regions(&bbox)?;
+            Ok((bbox, regions))
+        })
+    }
+}
+
+pub trait ZarrJointReader: HierarchyReader + Sync + Sized {
+    /// Read several arrays of the same shape and chunk shape together, with
+    /// the element type of each array given by a tuple.
+    fn joint<J: JointElements>(
+        &self,
+        arrays: &[(&str, &ArrayMetadata)],
+    ) -> Result<JointReader<'_, Self, J>, Error> {
+        if arrays.len() != J::NUM_ARRAYS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Number of arrays does not match the number of element types",
+            ));
+        }
+        let (_, first) = arrays[0];
+        if arrays.iter().any(|(_, array_meta)| {
+            array_meta.get_shape() != first.get_shape()
+                || array_meta.get_chunk_shape() != first.get_chunk_shape()
+        }) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Arrays read jointly must share a shape and chunk grid",
+            ));
+        }
+        Ok(JointReader {
+            hierarchy: self,
+            arrays: arrays
+                .iter()
+                .map(|(path_name, array_meta)| (path_name.to_string(), (*array_meta).clone()))
+                .collect(),
+            elements: PhantomData,
+        })
+    }
+}
+
+impl<T: HierarchyReader + Sync> ZarrJointReader for T {}
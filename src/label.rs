@@ -0,0 +1,253 @@
+//! Summaries of integer label arrays, such as segmentations.
+//!
+//! Labels are summarized a chunk at a time, so memory use is bounded by the
+//! size of a chunk and the number of distinct labels rather than the size of
+//! the array. Elements of chunks which do not exist are the array's fill
+//! value, so the fill value is counted like any other label.
+//!
+//! ```no_run
+//! use zarr::label::ZarrLabelReader;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("segmentation").unwrap();
+//! let stats = h
+//!     .label_stats::<u64>("segmentation", &array_meta, &array_meta.get_bounds())
+//!     .unwrap();
+//! for (label, stats) in stats.iter().filter(|(&label, _)| label != 0) {
+//!     println!("{}: {} voxels in {:?}", label, stats.count, stats.bbox);
+//! }
+//! ```
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+    VecDeque,
+};
+use std::hash::Hash;
+use std::io::Error;
+
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+};
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    GridCoord,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    VecDataChunk,
+};
+
+/// Number of elements and bounds of a label.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelStats {
+    /// Number of elements with the label.
+    pub count: u64,
+    /// Smallest box containing every element with the label.
+    pub bbox: BoundingBox,
+}
+
+/// Iterator over the distinct labels of a region, each yielded once when
+/// first found. See [`ZarrLabelReader::unique_labels`].
+pub struct UniqueLabels<'a, H: ?Sized, T> {
+    h: &'a H,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    extents: std::vec::IntoIter<BoundingBox>,
+    seen: HashSet<T>,
+    found: VecDeque<T>,
+}
+
+impl<'a, H: ?Sized, T> std::fmt::Debug for UniqueLabels<'a, H, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UniqueLabels")
+            .field("path_name", &self.path_name)
+            .field("chunks_remaining", &self.extents.len())
+            .field("labels_found", &self.seen.len())
+            .finish()
+    }
+}
+
+impl<'a, H, T> Iterator for UniqueLabels<'a, H, T>
+where
+    H: ZarrNdarrayReader + ?Sized,
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+    T: ReflectedType + Eq + Hash,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.found.is_empty() {
+            let extent = self.extents.next()?;
+            let data = match self
+                .h
+                .read_ndarray::<T>(&self.path_name, &self.array_meta, &extent)
+            {
+                Ok(data) => data,
+                Err(e) => {
+                    // Stop after an error rather than skipping the chunk.
+                    self.extents = Vec::new().into_iter();
+                    return Some(Err(e));
+                }
+            };
+            for label in data.iter() {
+                if self.seen.insert(label.clone()) {
+                    self.found.push_back(label.clone());
+                }
+            }
+        }
+        self.found.pop_front().map(Ok)
+    }
+}
+
+pub trait ZarrLabelReader: ZarrNdarrayReader {
+    /// Iterate the distinct labels in a region of an array, reading chunks
+    /// as the iterator advances. Labels are yielded in the order they are
+    /// first found, chunk by chunk.
+    fn unique_labels<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        region: &BoundingBox,
+    ) -> UniqueLabels<'_, Self, T> {
+        UniqueLabels {
+            h: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            extents: array_meta
+                .chunk_extents_in(region)
+                .map(|(_, extent)| extent)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            seen: HashSet::new(),
+            found: VecDeque::new(),
+        }
+    }
+
+    /// Count the elements with each label in a region of an array and find
+    /// the bounds of each label, by label.
+    fn label_stats<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        region: &BoundingBox,
+    ) -> Result<BTreeMap<T, LabelStats>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + Ord + Hash,
+    {
+        let mut stats: BTreeMap<T, LabelStats> = BTreeMap::new();
+        for (_, extent) in array_meta.chunk_extents_in(region) {
+            let data = self.read_ndarray::<T>(path_name, array_meta, &extent)?;
+
+            // Count and bound labels within the chunk first, so that the
+            // labels of the array are only looked up once per chunk.
+            let mut chunk_stats: HashMap<T, (u64, GridCoord, GridCoord)> = HashMap::new();
+            let ndim = data.ndim();
+            for (index, label) in data.indexed_iter() {
+                let position = (0..ndim).map(|d| index[d] as u64);
+                match chunk_stats.get_mut(label) {
+                    Some((count, min, max)) => {
+                        *count += 1;
+                        for ((p, lo), hi) in position.zip(min.iter_mut()).zip(max.iter_mut()) {
+                            *lo = (*lo).min(p);
+                            *hi = (*hi).max(p);
+                        }
+                    }
+                    None => {
+                        let position: GridCoord = position.collect();
+                        chunk_stats.insert(label.clone(), (1, position.clone(), position));
+                    }
+                }
+            }
+
+            for (label, (count, min, max)) in chunk_stats {
+                let offset: GridCoord = min
+                    .iter()
+                    .zip(extent.offset())
+                    .map(|(lo, o)| lo + o)
+                    .collect();
+                let shape = min.iter().zip(&max).map(|(lo, hi)| hi - lo + 1).collect();
+                let bbox = BoundingBox::new(offset, shape);
+                match stats.get_mut(&label) {
+                    Some(label_stats) => {
+                        label_stats.count += count;
+                        label_stats.bbox.union(&bbox);
+                    }
+                    None => {
+                        stats.insert(label, LabelStats { count, bbox });
+                    }
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+impl<T: ZarrNdarrayReader> ZarrLabelReader for T {}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::ndarray::ZarrNdarrayWriter;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_labels() {
+        let dir = tempdir::TempDir::new("rust_zarr_label_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![5, 6], u32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .build();
+        h.create_array("seg", &array_meta).unwrap();
+        #[rustfmt::skip]
+        let data = ndarray::Array::from_shape_vec(vec![3, 5], vec![
+            7u32, 7, 0, 0, 3,
+            0, 7, 0, 0, 3,
+            0, 0, 0, 0, 3,
+        ])
+        .unwrap()
+        .into_dyn();
+        h.write_ndarray("seg", &array_meta, smallvec![1, 0], &data)
+            .unwrap();
+
+        let bounds = array_meta.get_bounds();
+        let mut labels = h
+            .unique_labels::<u32>("seg", &array_meta, &bounds)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        labels.sort_unstable();
+        assert_eq!(labels, vec![0, 3, 7]);
+
+        let stats = h.label_stats::<u32>("seg", &array_meta, &bounds).unwrap();
+        assert_eq!(stats.keys().collect::<Vec<_>>(), vec![&0, &3, &7]);
+        assert_eq!(stats[&0].count, 30 - 3 - 3);
+        assert_eq!(stats[&0].bbox, bounds);
+        assert_eq!(
+            stats[&3],
+            LabelStats {
+                count: 3,
+                bbox: BoundingBox::new(smallvec![1, 4], smallvec![3, 1]),
+            }
+        );
+        assert_eq!(
+            stats[&7],
+            LabelStats {
+                count: 3,
+                bbox: BoundingBox::new(smallvec![1, 0], smallvec![2, 2]),
+            }
+        );
+
+        let region = BoundingBox::new(smallvec![2, 3], smallvec![3, 3]);
+        let stats = h.label_stats::<u32>("seg", &array_meta, &region).unwrap();
+        assert_eq!(
+            stats[&3].bbox,
+            BoundingBox::new(smallvec![2, 4], smallvec![2, 1])
+        );
+        assert!(!stats.contains_key(&7));
+    }
+}
@@ -6,9 +6,14 @@
 #[cfg(all(doctest, feature = "filesystem"))]
 doc_comment::doctest!("../README.md");
 
+#[cfg(feature = "aligned_buffers")]
+pub extern crate aligned_vec;
+#[cfg(feature = "aligned_buffers")]
+pub extern crate allocator_api2;
 #[macro_use]
 pub extern crate smallvec;
 
+use std::convert::TryFrom;
 use std::io::Error;
 use std::path::PathBuf;
 use std::time::SystemTime;
@@ -29,17 +34,58 @@ use crate::chunk::{
     VecDataChunk,
     WriteableDataChunk,
 };
+use crate::fill_value::FillValue;
 
 pub mod chunk;
 pub mod compression;
+pub mod config;
 #[macro_use]
 pub mod data_type;
 pub use data_type::*;
+pub mod axes;
+// After `data_type`, whose macros it uses.
+#[cfg(feature = "use_ndarray")]
+pub mod cast;
+pub mod catalog;
+pub mod device;
+pub mod dump;
+pub mod fill_value;
+pub mod filter;
+#[cfg(feature = "fuse")]
+pub mod fuse;
+pub mod gc;
+pub mod handle;
+pub mod hash;
+pub mod inventory;
+#[cfg(feature = "use_ndarray")]
+pub mod joint;
+#[cfg(feature = "use_ndarray")]
+pub mod label;
+#[cfg(feature = "medical")]
+pub mod medical;
+pub mod metadata;
 #[cfg(feature = "use_ndarray")]
 pub mod ndarray;
+#[cfg(feature = "use_ndarray")]
+pub mod plan;
+pub mod precomputed;
 pub mod prelude;
+#[cfg(feature = "use_ndarray")]
+pub mod reshape;
+pub mod sample;
+#[cfg(feature = "use_ndarray")]
+pub mod scan;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "use_ndarray")]
+pub mod stats;
 pub mod storage;
 pub mod store;
+#[cfg(feature = "use_ndarray")]
+pub mod stream;
+pub mod sync;
+pub mod tree;
+pub mod usage;
 
 #[cfg(test)]
 #[macro_use]
@@ -93,6 +139,14 @@ pub enum MetadataError {
     UnexpectedType(serde_json::Value),
     #[error("Encountered an unknown extension that must be understood: {}", .0.extension)]
     UnknownRequiredExtension(ExtensionMetadata),
+    #[error("Encountered an unknown data type extension without a fallback: {0}")]
+    UnknownDataTypeExtension(String),
+    #[error("chunk shape {0:?} does not have a positive length for each array dimension")]
+    InvalidChunkShape(ChunkCoord),
+    #[error("array shape {0:?} has more elements than can be counted")]
+    ShapeOverflow(GridCoord),
+    #[error("chunk shape {0:?} has more elements than a chunk can hold")]
+    ChunkTooLarge(ChunkCoord),
 }
 
 impl From<MetadataError> for std::io::Error {
@@ -101,8 +155,10 @@ impl From<MetadataError> for std::io::Error {
         use MetadataError::*;
 
         match e {
-            UnexpectedType(..) => Error::new(ErrorKind::InvalidData, e),
-            UnknownRequiredExtension(..) => Error::new(ErrorKind::Other, e),
+            UnexpectedType(..) | InvalidChunkShape(..) | ShapeOverflow(..) | ChunkTooLarge(..) => {
+                Error::new(ErrorKind::InvalidData, e)
+            }
+            UnknownRequiredExtension(..) | UnknownDataTypeExtension(..) => Error::other(e),
         }
     }
 }
@@ -161,6 +217,59 @@ pub struct ExtensionMetadata {
     pub configuration: Option<JsonObject>,
 }
 
+/// URI of the array extension recording the compressors of chunks written
+/// with schemes other than their array's by
+/// [`HierarchyWriter::write_chunk_with_compression`].
+///
+/// Its configuration maps the comma-separated grid positions of these
+/// chunks to their compressors under `chunks`. Other implementations must
+/// understand it, since they would otherwise decode these chunks with the
+/// array's compressor.
+pub const CHUNK_COMPRESSORS_EXTENSION: &str =
+    "https://github.com/aschampion/rust-zarr/extensions/chunk-compressors/1.0";
+
+/// URIs of extensions understood by this library.
+const SUPPORTED_EXTENSIONS: &[&str] = &[CHUNK_COMPRESSORS_EXTENSION];
+
+impl ExtensionMetadata {
+    /// Whether this library understands this extension.
+    pub fn is_supported(&self) -> bool {
+        SUPPORTED_EXTENSIONS.contains(&self.extension.as_str())
+    }
+}
+
+/// Check that every extension which must be understood is supported.
+///
+/// Extensions which need not be understood are passed through.
+fn check_extensions(extensions: &[ExtensionMetadata]) -> Result<(), MetadataError> {
+    match extensions
+        .iter()
+        .find(|e| e.must_understand && !e.is_supported())
+    {
+        Some(ext) => Err(MetadataError::UnknownRequiredExtension(ext.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Check that an array's chunk shape has a positive length for each of its
+/// dimensions.
+///
+/// Arrays may have zero-length dimensions, but their chunks may not.
+fn check_chunk_shape(array_meta: &ArrayMetadata) -> Result<(), MetadataError> {
+    let chunk_shape = &array_meta.chunk_grid.chunk_shape;
+    if chunk_shape.len() != array_meta.shape.len() || chunk_shape.contains(&0) {
+        return Err(MetadataError::InvalidChunkShape(chunk_shape.clone()));
+    }
+    array_meta.checked_num_elements()?;
+    array_meta.checked_chunk_num_elements()?;
+    Ok(())
+}
+
+/// Multiply dimensions together, or `None` if the product overflows `u64`.
+pub(crate) fn checked_product<I: IntoIterator<Item = u64>>(dims: I) -> Option<u64> {
+    dims.into_iter().try_fold(1u64, u64::checked_mul)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EntryPointMetadata {
     zarr_format: String,
@@ -168,6 +277,16 @@ pub struct EntryPointMetadata {
     metadata_key_suffix: String,
     // TODO
     extensions: Vec<ExtensionMetadata>,
+    /// Unrecognized fields, preserved for round-tripping.
+    #[serde(flatten)]
+    extra_fields: JsonObject,
+}
+
+impl EntryPointMetadata {
+    /// Get the extensions declared for the hierarchy.
+    pub fn get_extensions(&self) -> &[ExtensionMetadata] {
+        &self.extensions
+    }
 }
 
 impl Default for EntryPointMetadata {
@@ -177,6 +296,7 @@ impl Default for EntryPointMetadata {
             metadata_encoding: "https://purl.org/zarr/spec/protocol/core/3.0".to_owned(),
             metadata_key_suffix: ".json".to_owned(),
             extensions: vec![],
+            extra_fields: JsonObject::new(),
         }
     }
 }
@@ -214,22 +334,64 @@ pub trait Hierarchy {
     }
 }
 
+/// Kind of a node with metadata in a hierarchy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Array,
+    Group,
+}
+
 /// Non-mutating operations on Zarr hierarchys.
 pub trait HierarchyReader: Hierarchy {
     /// Get the Zarr specification version of the hierarchy.
     fn get_version(&self) -> Result<VersionReq, Error>;
 
     /// Get metadata for an array.
+    ///
+    /// Fails if the array declares an extension that must be understood but
+    /// is not supported by this library.
     fn get_array_metadata(&self, path_name: &str) -> Result<ArrayMetadata, Error>;
 
+    /// Get metadata for an explicit group.
+    ///
+    /// Fails if the group declares an extension that must be understood but
+    /// is not supported by this library.
+    fn get_group_metadata(&self, path_name: &str) -> Result<GroupMetadata, Error>;
+
     /// Test whether a group or array exists.
     fn exists(&self, path_name: &str) -> Result<bool, Error>;
 
     /// Test whether an array exists.
+    ///
+    /// Hierarchies backed by stores test for the presence of metadata
+    /// without reading it, as do [`group_exists`](Self::group_exists) and
+    /// [`node_kind`](Self::node_kind).
     fn array_exists(&self, path_name: &str) -> Result<bool, Error> {
         Ok(self.exists(path_name)? && self.get_array_metadata(path_name).is_ok())
     }
 
+    /// Test whether an explicit group exists.
+    fn group_exists(&self, path_name: &str) -> Result<bool, Error> {
+        Ok(self.node_kind(path_name)? == Some(NodeKind::Group))
+    }
+
+    /// Get the kind of the node at a path, or `None` if there is no node
+    /// with metadata there.
+    ///
+    /// Implicit groups have no metadata, so are not found. See
+    /// [`ZarrImplicitGroupReader`](crate::storage::ZarrImplicitGroupReader).
+    fn node_kind(&self, path_name: &str) -> Result<Option<NodeKind>, Error> {
+        Ok(if !self.exists(path_name)? {
+            None
+        } else if self.get_array_metadata(path_name).is_ok() {
+            Some(NodeKind::Array)
+        } else if self.get_group_metadata(path_name).is_ok() {
+            Some(NodeKind::Group)
+        } else {
+            None
+        })
+    }
+
     /// Get a URI string for a data chunk.
     ///
     /// Whether this requires that the array and chunk exist is currently
@@ -262,6 +424,66 @@ pub trait HierarchyReader: Hierarchy {
         chunk: &mut B,
     ) -> Result<Option<()>, Error>;
 
+    /// Read several array chunks into linear vecs, in the order of their
+    /// grid positions.
+    ///
+    /// The default implementation reads one chunk at a time. Hierarchies
+    /// over stores fetch the values of all the chunks with a single
+    /// [`get_many`](storage::ReadableStore::get_many).
+    fn read_chunks<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_positions: &[GridCoord],
+    ) -> Result<Vec<Option<VecDataChunk<T>>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        grid_positions
+            .iter()
+            .map(|grid_position| self.read_chunk(path_name, array_meta, grid_position.clone()))
+            .collect()
+    }
+
+    /// Read several array chunks in turn into an existing buffer, in the
+    /// order of their grid positions, passing the index of each chunk and
+    /// whether it exists to a function before reading the next.
+    ///
+    /// The default implementation reads one chunk at a time. Hierarchies
+    /// over stores fetch the values of all the chunks with a single
+    /// [`get_many`](storage::ReadableStore::get_many), as
+    /// [`read_chunks`](HierarchyReader::read_chunks) does.
+    fn read_chunks_into<T, B, F>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_positions: &[GridCoord],
+        chunk: &mut B,
+        mut visit: F,
+    ) -> Result<(), Error>
+    where
+        T: ReflectedType,
+        B: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        F: FnMut(usize, &mut B, bool) -> Result<(), Error>,
+    {
+        for (i, grid_position) in grid_positions.iter().enumerate() {
+            let exists = self
+                .read_chunk_into(path_name, array_meta, grid_position.clone(), chunk)?
+                .is_some();
+            visit(i, chunk, exists)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a chunk is present in the store.
+    fn chunk_exists(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<bool, Error>;
+
     /// Read store metadata about a chunk.
     fn store_chunk_metadata(
         &self,
@@ -278,6 +500,17 @@ pub trait HierarchyReader: Hierarchy {
 pub trait HierarchyLister {
     /// List all groups (including arrays) in a group.
     fn list_nodes(&self, prefix_path: &str) -> Result<Vec<String>, Error>;
+
+    /// List the grid positions of all chunks of an array present in the
+    /// store, in ascending order.
+    ///
+    /// This lists the store rather than probing for each chunk in the grid,
+    /// so is efficient for sparse arrays.
+    fn initialized_chunks(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Vec<GridCoord>, Error>;
 }
 
 /// Mutating operations on Zarr hierarchys.
@@ -308,6 +541,15 @@ pub trait HierarchyWriter: HierarchyReader {
     /// but not populate any chunk data.
     fn create_array(&self, path_name: &str, array_meta: &ArrayMetadata) -> Result<(), Error>;
 
+    /// Seal an array, marking it read-only in its metadata and, where the
+    /// store supports it, making its metadata and chunks read-only in the
+    /// store, for example to publish a finalized dataset.
+    ///
+    /// Writes with the returned metadata, or metadata read afterwards, fail
+    /// with [`PermissionDenied`](std::io::ErrorKind::PermissionDenied)
+    /// before reaching the store. Sealing a sealed array does nothing.
+    fn seal(&self, path_name: &str) -> Result<ArrayMetadata, Error>;
+
     /// Remove the Zarr hierarchy.
     fn remove_all(&self) -> Result<(), Error> {
         self.remove("")
@@ -325,6 +567,42 @@ pub trait HierarchyWriter: HierarchyReader {
         chunk: &B,
     ) -> Result<(), Error>;
 
+    /// Write several chunks.
+    ///
+    /// The default implementation writes one chunk at a time. Hierarchies
+    /// over stores write the encoded chunks with a single
+    /// [`put_many`](storage::WriteableStore::put_many), so after an error
+    /// which chunks were written is unspecified.
+    fn write_chunks<T: ReflectedType, B: DataChunk<T> + WriteableDataChunk>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunks: &[B],
+    ) -> Result<(), Error> {
+        for chunk in chunks {
+            self.write_chunk(path_name, array_meta, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Write a chunk compressed with a scheme other than the array's, for
+    /// example while migrating a store between compressors, returning the
+    /// array's updated metadata.
+    ///
+    /// The chunk's compressor is recorded in the array's metadata, with the
+    /// [`CHUNK_COMPRESSORS_EXTENSION`], before the chunk is written, so
+    /// reading the chunk with the returned metadata, or metadata read
+    /// afterwards, decodes it with this compressor. Later writes of the
+    /// chunk with such metadata use it too. Writing with the array's own
+    /// compressor removes the record.
+    fn write_chunk_with_compression<T: ReflectedType, B: DataChunk<T> + WriteableDataChunk>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunk: &B,
+        compressor: &compression::CompressionType,
+    ) -> Result<ArrayMetadata, Error>;
+
     /// Delete a chunk from an array.
     ///
     /// Returns `true` if the chunk does not exist on the backend at the
@@ -337,15 +615,26 @@ pub trait HierarchyWriter: HierarchyReader {
     ) -> Result<bool, Error>;
 }
 
-fn u64_ceil_div(a: u64, b: u64) -> u64 {
-    (a + 1) / b + (if a % b != 0 { 1 } else { 0 })
+/// Name of the map from chunks to their compressors in the configuration
+/// of the [`CHUNK_COMPRESSORS_EXTENSION`].
+const CHUNKS_NAME: &str = "chunks";
+
+fn chunk_compressor_key(grid_position: &[u64]) -> String {
+    grid_position
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 /// Metadata for groups.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct GroupMetadata {
-    extensions: Vec<serde_json::Value>,
+    extensions: Vec<ExtensionMetadata>,
     attributes: JsonObject,
+    /// Unrecognized fields, preserved for round-tripping.
+    #[serde(flatten)]
+    extra_fields: JsonObject,
 }
 
 impl Default for GroupMetadata {
@@ -353,10 +642,22 @@ impl Default for GroupMetadata {
         GroupMetadata {
             extensions: Vec::new(),
             attributes: JsonObject::new(),
+            extra_fields: JsonObject::new(),
         }
     }
 }
 
+impl GroupMetadata {
+    /// Get the extensions declared for this group.
+    pub fn get_extensions(&self) -> &[ExtensionMetadata] {
+        &self.extensions
+    }
+
+    pub fn get_attributes(&self) -> &JsonObject {
+        &self.attributes
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct ChunkGridMetadata {
     /// TODO
@@ -364,12 +665,23 @@ pub struct ChunkGridMetadata {
     grid_type: String,
     /// Shape of each chunk, in voxels.
     chunk_shape: ChunkCoord,
-    /// TODO
+    /// Separator between the coordinates of chunk keys, or empty if the
+    /// metadata does not specify one, as for older stores. Chunks of such
+    /// arrays are written with `/` and read with either `/` or `.`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     separator: String,
+    /// Unrecognized fields, preserved for round-tripping.
+    #[serde(flatten)]
+    extra_fields: JsonObject,
 }
 
 const REGULAR_GRID_TYPE: &str = "regular";
 
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum Order {
     #[serde(rename = "C")]
@@ -399,6 +711,21 @@ pub struct ArrayMetadata {
     #[serde(default)]
     #[serde(skip_serializing_if = "compression::CompressionType::is_default")]
     compressor: compression::CompressionType,
+    /// Filters applied to chunk data before compression, in order.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    filters: Vec<filter::FilterType>,
+    /// Whether the array's chunks, attributes and metadata must not be
+    /// modified.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    read_only: bool,
+    /// Unrecognized fields, such as those written by other implementations.
+    ///
+    /// These are preserved so that rewriting metadata does not silently
+    /// drop them.
+    #[serde(flatten)]
+    extra_fields: JsonObject,
 }
 
 impl ArrayMetadata {
@@ -420,12 +747,16 @@ impl ArrayMetadata {
                 grid_type: REGULAR_GRID_TYPE.to_owned(),
                 chunk_shape,
                 separator: "/".to_owned(),
+                extra_fields: JsonObject::new(),
             },
             chunk_memory_layout: Order::ColumnMajor,
             fill_value: None,
             extensions: vec![],
             attributes: JsonObject::new(),
             compressor,
+            filters: vec![],
+            read_only: false,
+            extra_fields: JsonObject::new(),
         }
     }
 
@@ -437,6 +768,12 @@ impl ArrayMetadata {
         &self.chunk_grid.chunk_shape
     }
 
+    /// Get the separator between the coordinates of chunk keys, or `None`
+    /// if the metadata does not specify one.
+    pub fn get_separator(&self) -> Option<&str> {
+        Some(self.chunk_grid.separator.as_str()).filter(|s| !s.is_empty())
+    }
+
     pub fn get_chunk_memory_layout(&self) -> &Order {
         &self.chunk_memory_layout
     }
@@ -445,12 +782,22 @@ impl ArrayMetadata {
         self.fill_value.as_ref()
     }
 
+    /// Get the fill value parsed according to the array's data type.
+    pub fn get_typed_fill_value(&self) -> Result<Option<FillValue>, Error> {
+        match &self.fill_value {
+            Some(value) => Ok(Some(FillValue::from_json(
+                value,
+                self.data_type.effective_type()?,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn get_effective_fill_value<T: ReflectedType>(&self) -> Result<T, Error> {
-        Ok(self
-            .get_fill_value()
-            .map(|v| serde_json::from_value(v.clone()))
-            .transpose()?
-            .unwrap_or_else(T::default))
+        match self.get_typed_fill_value()? {
+            Some(fill_value) => T::from_fill_value(&fill_value),
+            None => Ok(T::default()),
+        }
     }
 
     pub fn get_data_type(&self) -> &ExtensibleDataType {
@@ -461,22 +808,172 @@ impl ArrayMetadata {
         &self.compressor
     }
 
+    /// Get the filters applied to chunk data before compression.
+    pub fn get_filters(&self) -> &[filter::FilterType] {
+        &self.filters
+    }
+
+    /// Whether the array must not be modified, either because its metadata
+    /// says so or because the store does not permit writing its metadata.
+    ///
+    /// Writes to read-only arrays fail with
+    /// [`PermissionDenied`](std::io::ErrorKind::PermissionDenied).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Get the extensions declared for this array.
+    pub fn get_extensions(&self) -> &[ExtensionMetadata] {
+        &self.extensions
+    }
+
+    /// Get the compressor recorded for a chunk written with a scheme other
+    /// than the array's, if any. See
+    /// [`HierarchyWriter::write_chunk_with_compression`].
+    pub fn get_chunk_compressor(
+        &self,
+        grid_position: &[u64],
+    ) -> Result<Option<compression::CompressionType>, Error> {
+        let recorded = self
+            .chunk_compressors()
+            .and_then(|chunks| chunks.get(&chunk_compressor_key(grid_position)));
+        match recorded {
+            Some(compressor) => Ok(Some(serde_json::from_value(compressor.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The compressor a chunk is stored with, which is the array's unless
+    /// another is recorded for it.
+    pub(crate) fn chunk_compressor(
+        &self,
+        grid_position: &[u64],
+    ) -> Result<std::borrow::Cow<'_, compression::CompressionType>, Error> {
+        Ok(match self.get_chunk_compressor(grid_position)? {
+            Some(compressor) => std::borrow::Cow::Owned(compressor),
+            None => std::borrow::Cow::Borrowed(&self.compressor),
+        })
+    }
+
+    /// Record the compressor of a chunk, removing the record if it is the
+    /// array's compressor.
+    pub(crate) fn set_chunk_compressor(
+        &mut self,
+        grid_position: &[u64],
+        compressor: &compression::CompressionType,
+    ) -> Result<(), Error> {
+        let key = chunk_compressor_key(grid_position);
+        let position = self
+            .extensions
+            .iter()
+            .position(|e| e.extension == CHUNK_COMPRESSORS_EXTENSION);
+        if *compressor == self.compressor {
+            if let Some(position) = position {
+                let extension = &mut self.extensions[position];
+                let chunks = extension
+                    .configuration
+                    .as_mut()
+                    .and_then(|configuration| configuration.get_mut(CHUNKS_NAME))
+                    .and_then(Value::as_object_mut);
+                if let Some(chunks) = chunks {
+                    chunks.remove(&key);
+                    if chunks.is_empty() {
+                        self.extensions.remove(position);
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let position = position.unwrap_or_else(|| {
+            self.extensions.push(ExtensionMetadata {
+                extension: CHUNK_COMPRESSORS_EXTENSION.to_owned(),
+                must_understand: true,
+                configuration: None,
+            });
+            self.extensions.len() - 1
+        });
+        let chunks = self.extensions[position]
+            .configuration
+            .get_or_insert_with(JsonObject::new)
+            .entry(CHUNKS_NAME)
+            .or_insert_with(|| Value::Object(JsonObject::new()));
+        match chunks {
+            Value::Object(chunks) => {
+                chunks.insert(key, serde_json::to_value(compressor)?);
+                Ok(())
+            }
+            _ => Err(Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Malformed chunk compressors extension",
+            )),
+        }
+    }
+
+    /// The recorded compressors of chunks, by their keys.
+    fn chunk_compressors(&self) -> Option<&JsonObject> {
+        self.extensions
+            .iter()
+            .find(|e| e.extension == CHUNK_COMPRESSORS_EXTENSION)?
+            .configuration
+            .as_ref()?
+            .get(CHUNKS_NAME)?
+            .as_object()
+    }
+
+    pub fn get_attributes(&self) -> &JsonObject {
+        &self.attributes
+    }
+
+    /// Get metadata fields not recognized by this library.
+    pub fn get_extra_fields(&self) -> &JsonObject {
+        &self.extra_fields
+    }
+
     pub fn get_ndim(&self) -> usize {
         self.shape.len()
     }
 
     /// Get the total number of elements possible given the shape.
-    pub fn get_num_elements(&self) -> usize {
-        self.shape.iter().map(|&d| d as usize).product()
+    ///
+    /// # Panics
+    ///
+    /// If the count overflows `u64`, which metadata read from or written to a
+    /// hierarchy can not. See
+    /// [`checked_num_elements`](ArrayMetadata::checked_num_elements).
+    pub fn get_num_elements(&self) -> u64 {
+        self.checked_num_elements()
+            .expect("Array element count overflows")
+    }
+
+    /// Get the total number of elements possible given the shape, or an
+    /// error if the count overflows `u64`.
+    pub fn checked_num_elements(&self) -> Result<u64, MetadataError> {
+        checked_product(self.shape.iter().cloned())
+            .ok_or_else(|| MetadataError::ShapeOverflow(self.shape.clone()))
     }
 
     /// Get the total number of elements possible in a chunk.
+    ///
+    /// # Panics
+    ///
+    /// If a chunk can not hold that many elements, which can not be the case
+    /// for metadata read from or written to a hierarchy. See
+    /// [`checked_chunk_num_elements`](ArrayMetadata::checked_chunk_num_elements).
     pub fn get_chunk_num_elements(&self) -> usize {
-        self.chunk_grid
-            .chunk_shape
-            .iter()
-            .map(|&d| d as usize)
-            .product()
+        self.checked_chunk_num_elements()
+            .expect("Chunk element count overflows") as usize
+    }
+
+    /// Get the total number of elements possible in a chunk, or an error if
+    /// that is more than a chunk can hold. Chunks hold at most `u32::MAX`
+    /// elements, and no more than `usize` can address.
+    pub fn checked_chunk_num_elements(&self) -> Result<u32, MetadataError> {
+        let chunk_shape = &self.chunk_grid.chunk_shape;
+        checked_product(chunk_shape.iter().cloned().map(u64::from))
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|&n| usize::try_from(n).is_ok())
+            .ok_or_else(|| MetadataError::ChunkTooLarge(chunk_shape.clone()))
     }
 
     /// Get the upper bound extent of grid coordinates.
@@ -484,7 +981,7 @@ impl ArrayMetadata {
         self.shape
             .iter()
             .zip(self.chunk_grid.chunk_shape.iter().cloned().map(u64::from))
-            .map(|(d, b)| u64_ceil_div(*d, b))
+            .map(|(d, b)| d.div_ceil(b))
             .collect()
     }
 
@@ -525,4 +1022,189 @@ impl ArrayMetadata {
                 .zip(grid_position.iter())
                 .all(|(&bound, &coord)| coord < bound)
     }
+
+    /// Locate an element, as the grid position of the chunk holding it and
+    /// its index in that chunk's data, or `None` if it is out of bounds.
+    /// ```
+    /// use zarr::prelude::*;
+    /// use zarr::smallvec::smallvec;
+    /// let attrs = ArrayMetadata::new(
+    ///     smallvec![50, 40],
+    ///     smallvec![10, 10],
+    ///     i8::ZARR_TYPE,
+    ///     zarr::compression::CompressionType::default(),
+    /// );
+    /// assert_eq!(attrs.element_location(&[12, 31]), Some((smallvec![1, 3], 12)));
+    /// assert_eq!(attrs.element_location(&[50, 0]), None);
+    /// ```
+    pub fn element_location(&self, position: &[u64]) -> Option<(GridCoord, usize)> {
+        if position.len() != self.shape.len()
+            || position.iter().zip(self.shape.iter()).any(|(p, s)| p >= s)
+        {
+            return None;
+        }
+        let chunk_shape = &self.chunk_grid.chunk_shape;
+        let grid_position = position
+            .iter()
+            .zip(chunk_shape.iter())
+            .map(|(&p, &c)| p / u64::from(c))
+            .collect();
+        let offsets = position
+            .iter()
+            .zip(chunk_shape.iter())
+            .map(|(&p, &c)| ((p % u64::from(c)) as usize, c as usize));
+        let index = match self.chunk_memory_layout {
+            Order::RowMajor => offsets.fold(0, |i, (o, c)| i * c + o),
+            Order::ColumnMajor => offsets.rev().fold(0, |i, (o, c)| i * c + o),
+        };
+        Some((grid_position, index))
+    }
+}
+
+/// Suggest a chunk shape for an array whose chunks are near a target
+/// uncompressed size in bytes.
+///
+/// Starting from the full array shape, axes are halved in turn until a chunk
+/// is smaller than one and a half times the target, similar to
+/// zarr-python's automatic chunking.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::suggest_chunks;
+/// assert_eq!(&suggest_chunks(&[1000, 1000], f64::ZARR_TYPE, 1 << 20)[..], &[250, 500]);
+/// assert_eq!(&suggest_chunks(&[10, 10], u8::ZARR_TYPE, 1 << 20)[..], &[10, 10]);
+/// ```
+pub fn suggest_chunks(shape: &[u64], data_type: DataType, target_bytes: usize) -> ChunkCoord {
+    let elem_size = std::cmp::max(data_type.size_of(), 1) as u64;
+    let target_bytes = std::cmp::max(target_bytes as u64, elem_size);
+    let max_bytes = target_bytes.saturating_add(target_bytes / 2);
+    let mut chunk_shape: GridCoord = shape.iter().map(|&d| std::cmp::max(d, 1)).collect();
+
+    let mut axis = 0;
+    while !chunk_shape.is_empty() {
+        let chunk_bytes = chunk_shape
+            .iter()
+            .fold(elem_size, |acc, &c| acc.saturating_mul(c));
+        let fits = chunk_shape.iter().all(|&c| c <= u64::from(u32::MAX));
+        if (fits && chunk_bytes < max_bytes) || chunk_shape.iter().all(|&c| c == 1) {
+            break;
+        }
+        let c = &mut chunk_shape[axis % shape.len()];
+        *c = c.div_ceil(2);
+        axis += 1;
+    }
+
+    chunk_shape.iter().map(|&c| c as u32).collect()
+}
+
+/// Builder for [`ArrayMetadata`].
+///
+/// Options which are not set fall back to the crate-wide
+/// [`config::Config`].
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::smallvec::smallvec;
+/// let array_meta = ArrayMetadataBuilder::new(smallvec![100, 200], i16::ZARR_TYPE)
+///     .chunk_shape(smallvec![10, 20])
+///     .build();
+/// assert_eq!(array_meta.get_chunk_shape(), &[10, 20]);
+/// assert_eq!(
+///     array_meta.get_compressor(),
+///     &zarr::config::config().default_compressor,
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct ArrayMetadataBuilder {
+    shape: GridCoord,
+    data_type: ExtensibleDataType,
+    chunk_shape: Option<ChunkCoord>,
+    chunk_memory_layout: Order,
+    separator: String,
+    fill_value: Option<Value>,
+    compressor: Option<compression::CompressionType>,
+    filters: Vec<filter::FilterType>,
+    read_only: bool,
+}
+
+impl ArrayMetadataBuilder {
+    pub fn new<D: Into<ExtensibleDataType>>(shape: GridCoord, data_type: D) -> Self {
+        ArrayMetadataBuilder {
+            shape,
+            data_type: data_type.into(),
+            chunk_shape: None,
+            chunk_memory_layout: Order::ColumnMajor,
+            separator: "/".to_owned(),
+            fill_value: None,
+            compressor: None,
+            filters: vec![],
+            read_only: false,
+        }
+    }
+
+    /// Set the chunk shape. If unset, a chunk shape is suggested by
+    /// [`suggest_chunks`] for the configured target chunk size.
+    pub fn chunk_shape(mut self, chunk_shape: ChunkCoord) -> Self {
+        self.chunk_shape = Some(chunk_shape);
+        self
+    }
+
+    pub fn chunk_memory_layout(mut self, order: Order) -> Self {
+        self.chunk_memory_layout = order;
+        self
+    }
+
+    /// Set the separator between the coordinates of chunk keys, `/` by
+    /// default. Chunks are nested in directories with `/` and flat with `.`.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_owned();
+        self
+    }
+
+    /// Set the fill value, either as JSON or as a typed [`FillValue`].
+    pub fn fill_value<V: Into<Value>>(mut self, fill_value: V) -> Self {
+        self.fill_value = Some(fill_value.into());
+        self
+    }
+
+    /// Set the compressor. If unset, the configured default compressor is
+    /// used.
+    pub fn compressor(mut self, compressor: compression::CompressionType) -> Self {
+        self.compressor = Some(compressor);
+        self
+    }
+
+    /// Set the filters applied to chunk data before compression.
+    pub fn filters(mut self, filters: Vec<filter::FilterType>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Mark the array as read-only once created.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn build(self) -> ArrayMetadata {
+        let shape = self.shape;
+        let data_type = &self.data_type;
+        let chunk_shape = self.chunk_shape.unwrap_or_else(|| {
+            // Extended types without a known size are treated as bytes.
+            let effective_type = data_type
+                .effective_type()
+                .unwrap_or(DataType::Raw { size: 8 });
+            suggest_chunks(&shape, effective_type, config::config().chunk_target_bytes)
+        });
+        let compressor = self
+            .compressor
+            .unwrap_or_else(|| config::config().default_compressor.clone());
+        let mut array_meta = ArrayMetadata::new(shape, chunk_shape, self.data_type, compressor);
+        array_meta.chunk_memory_layout = self.chunk_memory_layout;
+        array_meta.chunk_grid.separator = self.separator;
+        array_meta.fill_value = self.fill_value;
+        array_meta.filters = self.filters;
+        array_meta.read_only = self.read_only;
+        array_meta
+    }
 }
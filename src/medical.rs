@@ -0,0 +1,703 @@
+//! Importers for medical imaging volumes.
+//!
+//! NIfTI-1 volumes and series of uncompressed single-frame DICOM images are
+//! converted into new arrays. Spatial metadata is stored in the array's
+//! attributes under `"nifti"` or `"dicom"` respectively. Voxel values are
+//! stored as in the source, without applying any rescaling, which is recorded
+//! in the attributes instead.
+//!
+//! ```no_run
+//! use zarr::medical::import_nifti;
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open_or_create("/tmp/scans.zr3").unwrap();
+//! let array_meta = import_nifti(&h, "subject1/t1", "/data/subject1/t1.nii.gz").unwrap();
+//! println!("Imported {:?}", array_meta.get_shape());
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::path::Path;
+
+use dicom_dictionary_std::tags;
+use half::f16;
+use ndarray::ShapeBuilder;
+use nifti::NiftiObject;
+use serde_json::{
+    json,
+    Value,
+};
+
+use crate::ndarray::ZarrNdarrayWriter;
+use crate::{
+    ArrayMetadata,
+    ArrayMetadataBuilder,
+    DataChunk,
+    DataType,
+    Endian,
+    FloatSize,
+    GridCoord,
+    HierarchyWriter,
+    IntSize,
+    ReadableDataChunk,
+    ReflectedType,
+    SliceDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+
+/// Attribute holding NIfTI spatial metadata.
+pub const NIFTI_ATTRIBUTE: &str = "nifti";
+
+/// Attribute holding DICOM spatial metadata.
+pub const DICOM_ATTRIBUTE: &str = "dicom";
+
+/// Transfer syntaxes of uncompressed pixel data.
+const NATIVE_TRANSFER_SYNTAXES: &[&str] = &[
+    "1.2.840.10008.1.2",
+    "1.2.840.10008.1.2.1",
+    "1.2.840.10008.1.2.2",
+];
+
+fn unsupported_data_type() -> Error {
+    Error::new(ErrorKind::InvalidData, "Unsupported voxel data type")
+}
+
+/// Decode voxel values from bytes in a data type's byte order.
+fn decode<T: ReflectedType>(bytes: &[u8], data_type: DataType) -> Result<Vec<T>, Error>
+where
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+{
+    let num_el = bytes.len() / std::cmp::max(data_type.size_of(), 1);
+    let decode_meta = ArrayMetadataBuilder::new(smallvec::smallvec![num_el as u64], data_type)
+        .chunk_shape(smallvec::smallvec![1])
+        .build();
+    let mut chunk = SliceDataChunk::new(GridCoord::new(), vec![T::default(); num_el]);
+    chunk.read_data(bytes, &decode_meta)?;
+    Ok(chunk.into_data())
+}
+
+fn nifti_error(e: nifti::NiftiError) -> Error {
+    match e {
+        nifti::NiftiError::Io(e) => e,
+        e => Error::new(ErrorKind::InvalidData, e),
+    }
+}
+
+/// Import a NIfTI-1 volume, optionally gzip-compressed, into a new array.
+///
+/// The array has the shape of the volume's dimensions in file order,
+/// `[x, y, z, ...]`, and a chunk shape and compressor chosen as for
+/// [`ArrayMetadataBuilder`]. The `"nifti"` attribute holds the voxel
+/// spacing, units, voxel-to-world affine, intensity scaling and description.
+pub fn import_nifti<H: HierarchyWriter, P: AsRef<Path>>(
+    h: &H,
+    path_name: &str,
+    file: P,
+) -> Result<ArrayMetadata, Error> {
+    let object = nifti::ReaderOptions::new()
+        .read_file(file)
+        .map_err(nifti_error)?;
+    let header = object.header().clone();
+
+    let endian = match header.endianness {
+        nifti::Endianness::Little => Endian::Little,
+        nifti::Endianness::Big => Endian::Big,
+    };
+    let file_type = match header.data_type().map_err(nifti_error)? {
+        nifti::NiftiType::Uint8 => DataType::UInt {
+            size: IntSize::B1,
+            endian,
+        },
+        nifti::NiftiType::Uint16 => DataType::UInt {
+            size: IntSize::B2,
+            endian,
+        },
+        nifti::NiftiType::Uint32 => DataType::UInt {
+            size: IntSize::B4,
+            endian,
+        },
+        nifti::NiftiType::Uint64 => DataType::UInt {
+            size: IntSize::B8,
+            endian,
+        },
+        nifti::NiftiType::Int8 => DataType::Int {
+            size: IntSize::B1,
+            endian,
+        },
+        nifti::NiftiType::Int16 => DataType::Int {
+            size: IntSize::B2,
+            endian,
+        },
+        nifti::NiftiType::Int32 => DataType::Int {
+            size: IntSize::B4,
+            endian,
+        },
+        nifti::NiftiType::Int64 => DataType::Int {
+            size: IntSize::B8,
+            endian,
+        },
+        nifti::NiftiType::Float32 => DataType::Float {
+            size: FloatSize::B4,
+            endian,
+        },
+        nifti::NiftiType::Float64 => DataType::Float {
+            size: FloatSize::B8,
+            endian,
+        },
+        _ => return Err(unsupported_data_type()),
+    };
+    let shape: GridCoord = header
+        .dim()
+        .map_err(nifti_error)?
+        .iter()
+        .map(|&d| u64::from(d))
+        .collect();
+
+    let raw_data = object.into_volume().into_raw_data();
+    let num_bytes = shape.iter().product::<u64>() as usize * file_type.size_of();
+    if raw_data.len() < num_bytes {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "NIfTI volume has fewer voxels than its dimensions",
+        ));
+    }
+
+    let array_meta = crate::data_type_match!(
+        file_type,
+        DataType::Raw { .. } => unreachable!(),
+        write_volume::<RsType, _>(h, path_name, &shape, &raw_data[..num_bytes], file_type)?
+    );
+    h.set_attribute(
+        path_name,
+        NIFTI_ATTRIBUTE.to_owned(),
+        nifti_attributes(&header),
+    )?;
+
+    Ok(array_meta)
+}
+
+/// Create an array for a column-major volume and write its data.
+fn write_volume<T: ReflectedType, H: HierarchyWriter>(
+    h: &H,
+    path_name: &str,
+    shape: &[u64],
+    bytes: &[u8],
+    file_type: DataType,
+) -> Result<ArrayMetadata, Error>
+where
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+{
+    let array_meta = ArrayMetadataBuilder::new(shape.into(), T::ZARR_TYPE).build();
+    h.create_array(path_name, &array_meta)?;
+
+    let data = decode::<T>(bytes, file_type)?;
+    let ndarray_shape: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+    let array = ndarray::Array::from_shape_vec(ndarray_shape.f(), data)
+        .expect("Voxel count matches volume dimensions");
+    h.write_ndarray(
+        path_name,
+        &array_meta,
+        smallvec::smallvec![0; shape.len()],
+        &array,
+    )?;
+
+    Ok(array_meta)
+}
+
+fn nifti_attributes(header: &nifti::NiftiHeader) -> Value {
+    let ndim = header.dim[0].clamp(1, 7) as usize;
+    let space_units = match header.xyzt_units & 0x07 {
+        1 => Some("m"),
+        2 => Some("mm"),
+        3 => Some("um"),
+        _ => None,
+    };
+    let time_units = match header.xyzt_units & 0x38 {
+        8 => Some("s"),
+        16 => Some("ms"),
+        24 => Some("us"),
+        _ => None,
+    };
+    let description = String::from_utf8_lossy(&header.descrip);
+
+    json!({
+        "pixdim": &header.pixdim[1..=ndim],
+        "space_units": space_units,
+        "time_units": time_units,
+        "qform_code": header.qform_code,
+        "sform_code": header.sform_code,
+        "affine": nifti_affine(header),
+        "scl_slope": header.scl_slope,
+        "scl_inter": header.scl_inter,
+        "description": description.trim_end_matches('\0'),
+    })
+}
+
+/// Voxel-to-world affine of a NIfTI header, from the sform if set, otherwise
+/// the qform if set, otherwise from voxel spacing alone.
+fn nifti_affine(header: &nifti::NiftiHeader) -> [[f64; 4]; 4] {
+    let to_f64 = |row: [f32; 4]| [row[0].into(), row[1].into(), row[2].into(), row[3].into()];
+    let [_, dx, dy, dz, ..] = header.pixdim.map(f64::from);
+
+    if header.sform_code > 0 {
+        return [
+            to_f64(header.srow_x),
+            to_f64(header.srow_y),
+            to_f64(header.srow_z),
+            [0., 0., 0., 1.],
+        ];
+    }
+    if header.qform_code <= 0 {
+        return [
+            [dx, 0., 0., 0.],
+            [0., dy, 0., 0.],
+            [0., 0., dz, 0.],
+            [0., 0., 0., 1.],
+        ];
+    }
+
+    let (b, c, d) = (
+        f64::from(header.quatern_b),
+        f64::from(header.quatern_c),
+        f64::from(header.quatern_d),
+    );
+    let a = (1. - (b * b + c * c + d * d)).max(0.).sqrt();
+    let qfac = if header.pixdim[0] < 0. { -1. } else { 1. };
+    let dz = dz * qfac;
+    [
+        [
+            (a * a + b * b - c * c - d * d) * dx,
+            2. * (b * c - a * d) * dy,
+            2. * (b * d + a * c) * dz,
+            header.quatern_x.into(),
+        ],
+        [
+            2. * (b * c + a * d) * dx,
+            (a * a + c * c - b * b - d * d) * dy,
+            2. * (c * d - a * b) * dz,
+            header.quatern_y.into(),
+        ],
+        [
+            2. * (b * d - a * c) * dx,
+            2. * (c * d + a * b) * dy,
+            (a * a + d * d - c * c - b * b) * dz,
+            header.quatern_z.into(),
+        ],
+        [0., 0., 0., 1.],
+    ]
+}
+
+fn dicom_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> Error {
+    Error::new(ErrorKind::InvalidData, e)
+}
+
+/// A decoded slice of a DICOM series.
+#[derive(Debug)]
+struct DicomSlice {
+    object: dicom_object::DefaultDicomObject,
+    rows: u16,
+    columns: u16,
+    data_type: DataType,
+    position: Option<[f64; 3]>,
+}
+
+impl DicomSlice {
+    fn open(file: &Path) -> Result<Self, Error> {
+        let object = dicom_object::open_file(file).map_err(dicom_error)?;
+
+        let transfer_syntax = object.meta().transfer_syntax().trim_end_matches('\0');
+        if !NATIVE_TRANSFER_SYNTAXES.contains(&transfer_syntax) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported DICOM transfer syntax: {}", transfer_syntax),
+            ));
+        }
+        let get_int = |tag, default: Option<u16>| -> Result<u16, Error> {
+            match object.element_opt(tag).map_err(dicom_error)? {
+                Some(element) => element.to_int::<u16>().map_err(dicom_error),
+                None => default.ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Missing DICOM element {}", tag),
+                    )
+                }),
+            }
+        };
+        if get_int(tags::SAMPLES_PER_PIXEL, Some(1))? != 1
+            || get_int(tags::NUMBER_OF_FRAMES, Some(1))? != 1
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Only single-frame monochrome DICOM images are supported",
+            ));
+        }
+        let rows = get_int(tags::ROWS, None)?;
+        let columns = get_int(tags::COLUMNS, None)?;
+        let signed = get_int(tags::PIXEL_REPRESENTATION, Some(0))? == 1;
+        // Parsed pixel data is in native byte order.
+        let endian = crate::NATIVE_ENDIAN;
+        let size = match get_int(tags::BITS_ALLOCATED, None)? {
+            8 => IntSize::B1,
+            16 => IntSize::B2,
+            32 => IntSize::B4,
+            _ => return Err(unsupported_data_type()),
+        };
+        let data_type = if signed {
+            DataType::Int { size, endian }
+        } else {
+            DataType::UInt { size, endian }
+        };
+        let position = get_floats(&object, tags::IMAGE_POSITION_PATIENT)?
+            .map(|p| three(&p))
+            .transpose()?;
+
+        Ok(DicomSlice {
+            object,
+            rows,
+            columns,
+            data_type,
+            position,
+        })
+    }
+
+    fn pixel_data(&self) -> Result<std::borrow::Cow<'_, [u8]>, Error> {
+        self.object
+            .element(tags::PIXEL_DATA)
+            .map_err(dicom_error)?
+            .to_bytes()
+            .map_err(dicom_error)
+    }
+}
+
+fn get_floats(
+    object: &dicom_object::DefaultDicomObject,
+    tag: dicom_object::Tag,
+) -> Result<Option<Vec<f64>>, Error> {
+    object
+        .element_opt(tag)
+        .map_err(dicom_error)?
+        .map(|element| element.to_multi_float64().map_err(dicom_error))
+        .transpose()
+}
+
+fn three(values: &[f64]) -> Result<[f64; 3], Error> {
+    match values {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            "Expected a DICOM vector of three values",
+        )),
+    }
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Import a series of uncompressed single-frame DICOM images into a new
+/// array of shape `[slice, row, column]`.
+///
+/// Slices are ordered along the normal of the image orientation by their
+/// image position, or kept in the given order if positions are missing. The
+/// `"dicom"` attribute holds the voxel spacing, the position of the first
+/// voxel, the image orientation, intensity rescaling, modality and series
+/// UID.
+pub fn import_dicom_series<H: HierarchyWriter, P: AsRef<Path>>(
+    h: &H,
+    path_name: &str,
+    files: &[P],
+) -> Result<ArrayMetadata, Error> {
+    let mut slices = files
+        .iter()
+        .map(|file| DicomSlice::open(file.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = slices
+        .first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "DICOM series is empty"))?;
+    if slices.iter().any(|slice| {
+        (slice.rows, slice.columns, slice.data_type) != (first.rows, first.columns, first.data_type)
+    }) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "DICOM series slices differ in shape or data type",
+        ));
+    }
+
+    let orientation = get_floats(&first.object, tags::IMAGE_ORIENTATION_PATIENT)?
+        .unwrap_or_else(|| vec![1., 0., 0., 0., 1., 0.]);
+    if orientation.len() != 6 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "DICOM image orientation must have six values",
+        ));
+    }
+    let (row_dir, column_dir) = (three(&orientation[..3])?, three(&orientation[3..])?);
+    let normal = [
+        row_dir[1] * column_dir[2] - row_dir[2] * column_dir[1],
+        row_dir[2] * column_dir[0] - row_dir[0] * column_dir[2],
+        row_dir[0] * column_dir[1] - row_dir[1] * column_dir[0],
+    ];
+    let positions: Option<Vec<f64>> = slices
+        .iter()
+        .map(|slice| slice.position.map(|p| dot(&p, &normal)))
+        .collect();
+    let mut slice_spacing = None;
+    if let Some(positions) = positions {
+        let mut keyed: Vec<(f64, DicomSlice)> = positions.into_iter().zip(slices).collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if keyed.len() > 1 {
+            slice_spacing = Some(keyed[1].0 - keyed[0].0);
+        }
+        slices = keyed.into_iter().map(|(_, slice)| slice).collect();
+    }
+    let first = &slices[0];
+    if slice_spacing.is_none() {
+        slice_spacing =
+            get_floats(&first.object, tags::SLICE_THICKNESS)?.and_then(|t| t.first().copied());
+    }
+    let pixel_spacing = get_floats(&first.object, tags::PIXEL_SPACING)?;
+    let rescale_slope = get_floats(&first.object, tags::RESCALE_SLOPE)?;
+    let rescale_intercept = get_floats(&first.object, tags::RESCALE_INTERCEPT)?;
+    let get_str = |tag| -> Result<Option<String>, Error> {
+        Ok(first
+            .object
+            .element_opt(tag)
+            .map_err(dicom_error)?
+            .and_then(|element| element.to_str().ok())
+            .map(|s| s.trim_end_matches(['\0', ' ']).to_owned()))
+    };
+    let attributes = json!({
+        "spacing": [
+            slice_spacing,
+            pixel_spacing.as_ref().and_then(|s| s.first()),
+            pixel_spacing.as_ref().and_then(|s| s.get(1)),
+        ],
+        "origin": first.position,
+        "orientation": orientation,
+        "rescale_slope": rescale_slope.and_then(|s| s.first().copied()),
+        "rescale_intercept": rescale_intercept.and_then(|s| s.first().copied()),
+        "modality": get_str(tags::MODALITY)?,
+        "series_instance_uid": get_str(tags::SERIES_INSTANCE_UID)?,
+    });
+
+    let data_type = first.data_type;
+    let array_meta = crate::data_type_match!(
+        data_type,
+        DataType::Raw { .. } => unreachable!(),
+        write_slices::<RsType, _>(h, path_name, &slices)?
+    );
+    h.set_attribute(path_name, DICOM_ATTRIBUTE.to_owned(), attributes)?;
+
+    Ok(array_meta)
+}
+
+/// Create an array for a stack of slices and write each slice.
+fn write_slices<T: ReflectedType, H: HierarchyWriter>(
+    h: &H,
+    path_name: &str,
+    slices: &[DicomSlice],
+) -> Result<ArrayMetadata, Error>
+where
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+{
+    let (rows, columns) = (slices[0].rows as usize, slices[0].columns as usize);
+    let shape = smallvec::smallvec![slices.len() as u64, rows as u64, columns as u64];
+    let array_meta = ArrayMetadataBuilder::new(shape, T::ZARR_TYPE).build();
+    h.create_array(path_name, &array_meta)?;
+
+    let num_bytes = rows * columns * T::ZARR_TYPE.size_of();
+    for (i, slice) in slices.iter().enumerate() {
+        let pixel_data = slice.pixel_data()?;
+        if pixel_data.len() < num_bytes {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "DICOM pixel data has fewer pixels than its dimensions",
+            ));
+        }
+        let data = decode::<T>(&pixel_data[..num_bytes], slice.data_type)?;
+        let array = ndarray::Array::from_shape_vec(vec![1, rows, columns], data)
+            .expect("Pixel count matches image dimensions");
+        h.write_ndarray(
+            path_name,
+            &array_meta,
+            smallvec::smallvec![i as u64, 0, 0],
+            &array,
+        )?;
+    }
+
+    Ok(array_meta)
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::ndarray::prelude::*;
+    use crate::prelude::*;
+
+    use byteorder::{
+        LittleEndian,
+        WriteBytesExt,
+    };
+    use dicom_core::{
+        dicom_value,
+        DataElement,
+        PrimitiveValue,
+        VR,
+    };
+
+    /// Write a minimal single-file NIfTI-1 volume of little-endian `i16`.
+    fn write_nifti(path: &Path, dim: &[u16], data: &[i16]) {
+        let mut header = vec![0u8; 352];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            header[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        put(0, &348i32.to_le_bytes());
+        let mut dims = vec![dim.len() as u16];
+        dims.extend_from_slice(dim);
+        dims.resize(8, 1);
+        for (i, d) in dims.iter().enumerate() {
+            put(40 + 2 * i, &d.to_le_bytes());
+        }
+        put(70, &4i16.to_le_bytes());
+        put(72, &16i16.to_le_bytes());
+        for (i, p) in [1f32, 0.5, 0.75, 2.0].iter().enumerate() {
+            put(76 + 4 * i, &p.to_le_bytes());
+        }
+        put(108, &352f32.to_le_bytes());
+        put(112, &2f32.to_le_bytes());
+        put(123, &[2]);
+        put(254, &1i16.to_le_bytes());
+        for (row, values) in [
+            [0.5f32, 0., 0., -10.],
+            [0., 0.75, 0., -20.],
+            [0., 0., 2., -30.],
+        ]
+        .iter()
+        .enumerate()
+        {
+            for (i, v) in values.iter().enumerate() {
+                put(280 + 16 * row + 4 * i, &v.to_le_bytes());
+            }
+        }
+        put(344, b"n+1\0");
+
+        for v in data {
+            header.write_i16::<LittleEndian>(*v).unwrap();
+        }
+        std::fs::write(path, header).unwrap();
+    }
+
+    #[test]
+    fn test_import_nifti() {
+        let dir = tempdir::TempDir::new("rust_zarr_medical_tests").unwrap();
+        let file = dir.path().join("volume.nii");
+        let data: Vec<i16> = (0..24).map(|i| i - 12).collect();
+        write_nifti(&file, &[4, 3, 2], &data);
+
+        let h = FilesystemHierarchy::open_or_create(dir.path().join("h.zr3")).unwrap();
+        let array_meta = import_nifti(&h, "t1", &file).unwrap();
+        assert_eq!(array_meta.get_shape(), &[4, 3, 2]);
+        assert_eq!(
+            array_meta.get_data_type().effective_type().unwrap(),
+            i16::ZARR_TYPE
+        );
+
+        let array = h
+            .read_ndarray::<i16>("t1", &array_meta, &array_meta.get_bounds())
+            .unwrap();
+        // NIfTI voxels are stored with the first axis fastest.
+        assert_eq!(array[[1, 0, 0]], -11);
+        assert_eq!(array[[0, 1, 0]], -8);
+        assert_eq!(array[[0, 0, 1]], 0);
+
+        let attributes = h.list_attributes("t1").unwrap();
+        let nifti = &attributes[NIFTI_ATTRIBUTE];
+        assert_eq!(nifti["pixdim"], json!([0.5, 0.75, 2.0]));
+        assert_eq!(nifti["space_units"], "mm");
+        assert_eq!(nifti["scl_slope"], 2.0);
+        assert_eq!(nifti["affine"][0], json!([0.5, 0.0, 0.0, -10.0]));
+        assert_eq!(nifti["affine"][2], json!([0.0, 0.0, 2.0, -30.0]));
+    }
+
+    fn write_dicom(path: &Path, z: f64, pixels: Vec<u16>) {
+        let mut obj = dicom_object::InMemDicomObject::new_empty();
+        obj.put(DataElement::new(tags::MODALITY, VR::CS, "CT"));
+        obj.put(DataElement::new(
+            tags::ROWS,
+            VR::US,
+            PrimitiveValue::from(2_u16),
+        ));
+        obj.put(DataElement::new(
+            tags::COLUMNS,
+            VR::US,
+            PrimitiveValue::from(3_u16),
+        ));
+        obj.put(DataElement::new(
+            tags::BITS_ALLOCATED,
+            VR::US,
+            PrimitiveValue::from(16_u16),
+        ));
+        obj.put(DataElement::new(
+            tags::PIXEL_REPRESENTATION,
+            VR::US,
+            PrimitiveValue::from(0_u16),
+        ));
+        obj.put(DataElement::new(
+            tags::PIXEL_SPACING,
+            VR::DS,
+            dicom_value!(Strs, ["0.5", "0.25"]),
+        ));
+        obj.put(DataElement::new(
+            tags::IMAGE_POSITION_PATIENT,
+            VR::DS,
+            PrimitiveValue::Strs(vec!["1".to_owned(), "2".to_owned(), z.to_string()].into()),
+        ));
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OW,
+            PrimitiveValue::U16(pixels.into()),
+        ));
+        obj.with_meta(
+            dicom_object::FileMetaTableBuilder::new()
+                .transfer_syntax("1.2.840.10008.1.2.1")
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid(format!("1.2.3.{}", z)),
+        )
+        .unwrap()
+        .write_to_file(path)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_import_dicom_series() {
+        let dir = tempdir::TempDir::new("rust_zarr_medical_tests").unwrap();
+        let files: Vec<_> = [5.0, 0.0, 2.5]
+            .iter()
+            .map(|&z| {
+                let file = dir.path().join(format!("{}.dcm", z));
+                write_dicom(&file, z, (0..6).map(|i| i + z as u16 * 10).collect());
+                file
+            })
+            .collect();
+
+        let h = FilesystemHierarchy::open_or_create(dir.path().join("h.zr3")).unwrap();
+        let array_meta = import_dicom_series(&h, "ct", &files).unwrap();
+        assert_eq!(array_meta.get_shape(), &[3, 2, 3]);
+
+        let array = h
+            .read_ndarray::<u16>("ct", &array_meta, &array_meta.get_bounds())
+            .unwrap();
+        assert_eq!(array[[0, 0, 0]], 0);
+        assert_eq!(array[[1, 0, 1]], 21);
+        assert_eq!(array[[2, 1, 2]], 55);
+
+        let attributes = h.list_attributes("ct").unwrap();
+        let dicom = &attributes[DICOM_ATTRIBUTE];
+        assert_eq!(dicom["spacing"], json!([2.5, 0.5, 0.25]));
+        assert_eq!(dicom["origin"], json!([1.0, 2.0, 0.0]));
+        assert_eq!(dicom["modality"], "CT");
+    }
+}
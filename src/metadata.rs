@@ -0,0 +1,357 @@
+//! Reading the metadata of several nodes in one round trip.
+//!
+//! Opening a node through [`HierarchyReader`] makes a request per question:
+//! whether it is an array, whether it is a group, then its metadata document.
+//! [`ZarrMetadataReader`] instead fetches the array and group metadata
+//! documents of one or many nodes with a single
+//! [`get_many`](ReadableStore::get_many), which stores with a high latency per
+//! request, such as HTTP stores, make in parallel, cutting the latency of
+//! opening hierarchies cold.
+//!
+//! ```no_run
+//! use zarr::metadata::{
+//!     NodeMetadata,
+//!     ZarrMetadataReader,
+//! };
+//! use zarr::prelude::*;
+//!
+//! let h = FilesystemHierarchy::open("/mnt/remote/volume.zr3").unwrap();
+//! let scales = ["raw/s0", "raw/s1", "raw/s2"];
+//! for (path, metadata) in scales.iter().zip(h.get_many_node_metadata(&scales).unwrap()) {
+//!     if let Some(NodeMetadata::Array(array_meta)) = metadata {
+//!         println!("{} {:?}", path, array_meta.get_shape());
+//!     }
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::io::Error;
+
+use crate::storage::{
+    read_array_metadata,
+    read_group_metadata,
+    ReadableStore,
+    ATTRIBUTES_NAME,
+};
+use crate::{
+    ArrayMetadata,
+    GroupMetadata,
+    Hierarchy,
+    JsonObject,
+    MetadataError,
+    NodeKind,
+};
+
+/// Metadata of a group or array.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeMetadata {
+    Array(Box<ArrayMetadata>),
+    Group(GroupMetadata),
+}
+
+impl NodeMetadata {
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            NodeMetadata::Array(_) => NodeKind::Array,
+            NodeMetadata::Group(_) => NodeKind::Group,
+        }
+    }
+
+    pub fn get_attributes(&self) -> &JsonObject {
+        match self {
+            NodeMetadata::Array(array_meta) => array_meta.get_attributes(),
+            NodeMetadata::Group(group_meta) => group_meta.get_attributes(),
+        }
+    }
+}
+
+/// Reading of the metadata of nodes with one
+/// [`get_many`](ReadableStore::get_many) per call.
+pub trait ZarrMetadataReader: ReadableStore + Hierarchy {
+    /// Get the metadata of the node at a path, or `None` if there is no node
+    /// with metadata there, fetching its array and group metadata together.
+    fn get_node_metadata(&self, path_name: &str) -> Result<Option<NodeMetadata>, Error> {
+        Ok(self.get_many_node_metadata(&[path_name])?.remove(0))
+    }
+
+    /// Get the metadata of the nodes at several paths, in the order of the
+    /// paths, with `None` for paths with no node with metadata.
+    ///
+    /// Fails if any node's metadata is invalid or declares an extension that
+    /// must be understood but is not supported by this library.
+    fn get_many_node_metadata(
+        &self,
+        path_names: &[&str],
+    ) -> Result<Vec<Option<NodeMetadata>>, Error> {
+        let keys: Vec<String> = path_names
+            .iter()
+            .flat_map(|path_name| {
+                vec![
+                    self.array_metadata_key(path_name),
+                    self.group_metadata_key(path_name),
+                ]
+            })
+            .map(|key| key.to_str().expect("TODO").to_owned())
+            .collect();
+        let values = self.get_many(&keys)?;
+        keys.chunks(2)
+            .zip(values.chunks(2))
+            .map(|(keys, values)| match values {
+                [Some(array_document), _] => {
+                    read_array_metadata(self, &keys[0], &array_document[..])
+                        .map(|array_meta| Some(NodeMetadata::Array(Box::new(array_meta))))
+                }
+                [None, Some(group_document)] => read_group_metadata(&group_document[..])
+                    .map(|group_meta| Some(NodeMetadata::Group(group_meta))),
+                _ => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Get the metadata of the arrays at several paths, in the order of the
+    /// paths, with `None` for paths with no array.
+    fn get_many_array_metadata(
+        &self,
+        path_names: &[&str],
+    ) -> Result<Vec<Option<ArrayMetadata>>, Error> {
+        let keys: Vec<String> = path_names
+            .iter()
+            .map(|path_name| {
+                self.array_metadata_key(path_name)
+                    .to_str()
+                    .expect("TODO")
+                    .to_owned()
+            })
+            .collect();
+        keys.iter()
+            .zip(self.get_many(&keys)?)
+            .map(|(key, value)| {
+                value
+                    .map(|document| read_array_metadata(self, key, &document[..]))
+                    .transpose()
+            })
+            .collect()
+    }
+}
+
+impl<S: ReadableStore + Hierarchy> ZarrMetadataReader for S {}
+
+/// Index just past the end of the JSON string starting at `start`.
+fn string_end(document: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < document.len() {
+        match document[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Index just past the end of the JSON value starting at `start`.
+fn value_end(document: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < document.len() {
+        match document[i] {
+            b'"' => {
+                i = string_end(document, i)?;
+                if depth == 0 {
+                    return Some(i);
+                }
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth == 0 => return Some(i),
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            b',' if depth == 0 => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    (depth == 0).then_some(document.len())
+}
+
+fn skip_whitespace(document: &[u8], mut i: usize) -> usize {
+    while document.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    i
+}
+
+/// Decode attributes split from a metadata document by [`split_attributes`].
+pub(crate) fn decode_attributes(attributes: Option<&[u8]>) -> Result<JsonObject, Error> {
+    match attributes {
+        Some(attributes) => match serde_json::from_slice(attributes)? {
+            serde_json::Value::Object(attributes) => Ok(attributes),
+            value => Err(MetadataError::UnexpectedType(value).into()),
+        },
+        None => Ok(JsonObject::new()),
+    }
+}
+
+/// Split the undecoded value of the top-level `attributes` member from a
+/// metadata document, replacing it by an empty object, so that the rest of
+/// the document can be decoded without decoding the attributes.
+///
+/// Documents which are not well-formed enough to find the member are
+/// returned whole, leaving serde to decode or reject them.
+pub(crate) fn split_attributes(document: &[u8]) -> (Cow<'_, [u8]>, Option<&[u8]>) {
+    let start = skip_whitespace(document, 0);
+    if document.get(start) != Some(&b'{') {
+        return (Cow::Borrowed(document), None);
+    }
+    let mut i = skip_whitespace(document, start + 1);
+    while document.get(i) == Some(&b'"') {
+        let span = string_end(document, i).and_then(|key_end| {
+            let colon = skip_whitespace(document, key_end);
+            if document.get(colon) != Some(&b':') {
+                return None;
+            }
+            let value_start = skip_whitespace(document, colon + 1);
+            let value_end = value_end(document, value_start)?;
+            Some((&document[i + 1..key_end - 1], value_start, value_end))
+        });
+        let (key, value_start, value_end) = match span {
+            Some(span) => span,
+            None => break,
+        };
+        if key == ATTRIBUTES_NAME.as_bytes() {
+            let mut rest = document[..value_start].to_vec();
+            rest.extend_from_slice(b"{}");
+            rest.extend_from_slice(&document[value_end..]);
+            return (Cow::Owned(rest), Some(&document[value_start..value_end]));
+        }
+        i = skip_whitespace(document, value_end);
+        if document.get(i) != Some(&b',') {
+            break;
+        }
+        i = skip_whitespace(document, i + 1);
+    }
+    (Cow::Borrowed(document), None)
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    use crate::prelude::*;
+    use crate::EntryPointMetadata;
+
+    /// A hierarchy counting the requests made of it, where fetching several
+    /// values with `get_many` is one request.
+    #[derive(Debug)]
+    struct Counted {
+        hierarchy: FilesystemHierarchy,
+        requests: AtomicUsize,
+    }
+
+    impl Hierarchy for Counted {
+        fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+            self.hierarchy.get_entry_point_metadata()
+        }
+    }
+
+    impl ReadableStore for Counted {
+        type GetReader = <FilesystemHierarchy as ReadableStore>::GetReader;
+
+        fn exists(&self, key: &str) -> Result<bool, Error> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            ReadableStore::exists(&self.hierarchy, key)
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            self.hierarchy.get(key)
+        }
+
+        fn uri(&self, key: &str) -> Result<String, Error> {
+            self.hierarchy.uri(key)
+        }
+
+        fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            self.hierarchy.get_many(keys)
+        }
+    }
+
+    #[test]
+    fn test_split_attributes() {
+        let document =
+            br#" {"shape": [4], "attributes": {"a": "}\"", "b": [{}]}, "fill_value": 0}"#;
+        let (rest, attributes) = split_attributes(document);
+        assert_eq!(
+            &rest[..],
+            &br#" {"shape": [4], "attributes": {}, "fill_value": 0}"#[..]
+        );
+        assert_eq!(attributes, Some(&br#"{"a": "}\"", "b": [{}]}"#[..]));
+        let attributes = decode_attributes(attributes).unwrap();
+        assert_eq!(attributes["a"], "}\"");
+
+        let (rest, attributes) =
+            split_attributes(br#"{"shape": [4], "nested": {"attributes": 1}}"#);
+        assert!(matches!(rest, Cow::Borrowed(_)));
+        assert_eq!(attributes, None);
+        assert_eq!(split_attributes(br#"{"shape": [4"#).1, None);
+        assert_eq!(
+            decode_attributes(Some(b"[]")).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_get_many_node_metadata() {
+        let dir = tempdir::TempDir::new("rust_zarr_metadata_tests").unwrap();
+        let hierarchy = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        hierarchy.create_group("g").unwrap();
+        hierarchy.create_array("g/a", &array_meta).unwrap();
+        let mut attributes = JsonObject::new();
+        attributes.insert("name".to_owned(), "a".into());
+        hierarchy.set_attributes("g/a", attributes.clone()).unwrap();
+
+        let h = Counted {
+            hierarchy,
+            requests: AtomicUsize::new(0),
+        };
+        let metadata = h.get_many_node_metadata(&["g", "g/a", "missing"]).unwrap();
+        assert_eq!(h.requests.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            metadata[0],
+            Some(NodeMetadata::Group(GroupMetadata::default()))
+        );
+        match &metadata[1] {
+            Some(NodeMetadata::Array(read)) => assert_eq!(read.get_shape(), &[4, 4]),
+            metadata => panic!("Expected array metadata, not {:?}", metadata),
+        }
+        assert_eq!(metadata[1].as_ref().unwrap().kind(), NodeKind::Array);
+        assert_eq!(metadata[1].as_ref().unwrap().get_attributes(), &attributes);
+        assert_eq!(metadata[2], None);
+
+        assert_eq!(
+            h.get_many_array_metadata(&["g", "g/a"])
+                .unwrap()
+                .iter()
+                .map(Option::is_some)
+                .collect::<Vec<_>>(),
+            vec![false, true]
+        );
+        assert_eq!(h.get_node_metadata("missing").unwrap(), None);
+
+        h.requests.store(0, Ordering::Relaxed);
+        assert_eq!(h.list_attributes("g/a").unwrap(), attributes);
+        assert_eq!(h.requests.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::io::{
     Error,
     ErrorKind,
@@ -8,12 +9,14 @@ use itertools::Itertools;
 use ndarray::{
     Array,
     ArrayView,
+    ArrayViewMut,
     IxDyn,
     ShapeBuilder,
     SliceInfo,
 };
 
 use crate::{
+    checked_product,
     ArrayMetadata,
     ChunkCoord,
     CoordVec,
@@ -33,11 +36,46 @@ use crate::{
 pub mod prelude {
     pub use super::{
         BoundingBox,
+        MissingChunkPolicy,
+        SparseBlock,
         ZarrNdarrayReader,
         ZarrNdarrayWriter,
     };
 }
 
+/// A block of a sparse read: the bounds of a present chunk clipped to the
+/// requested region, and its data.
+pub type SparseBlock<T> = (
+    BoundingBox,
+    ndarray::Array<T, ndarray::Dim<ndarray::IxDynImpl>>,
+);
+
+/// Callback providing the data of a missing chunk, or `None` to fill it.
+pub type MissingChunkFn<'a, T> = &'a dyn Fn(&[u64]) -> Result<Option<VecDataChunk<T>>, Error>;
+
+/// What to do on reading a region including chunks absent from the store.
+#[derive(Default)]
+pub enum MissingChunkPolicy<'a, T: ReflectedType> {
+    /// Fill the chunk's part of the region with the array's fill value.
+    #[default]
+    FillValue,
+    /// Fail with an error of kind [`NotFound`](ErrorKind::NotFound).
+    Error,
+    /// Provide the chunk at a grid position, for example by reading it from
+    /// a secondary source.
+    Callback(MissingChunkFn<'a, T>),
+}
+
+impl<'a, T: ReflectedType> std::fmt::Debug for MissingChunkPolicy<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissingChunkPolicy::FillValue => f.write_str("FillValue"),
+            MissingChunkPolicy::Error => f.write_str("Error"),
+            MissingChunkPolicy::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
 /// Specifes the extents of an axis-aligned bounding box.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BoundingBox {
@@ -52,6 +90,14 @@ impl BoundingBox {
         BoundingBox { offset, shape }
     }
 
+    pub fn offset(&self) -> &[u64] {
+        &self.offset
+    }
+
+    pub fn shape(&self) -> &[u64] {
+        &self.shape
+    }
+
     pub fn shape_chunk(&self) -> ChunkCoord {
         self.shape.iter().map(|n| *n as u32).collect()
     }
@@ -150,7 +196,7 @@ impl Sub<&GridCoord> for BoundingBox {
 
 pub trait ZarrNdarrayReader: HierarchyReader {
     /// Read an arbitrary bounding box from an Zarr volume into an ndarray,
-    /// reading chunks in serial as necessary.
+    /// reading chunks in batches as necessary.
     fn read_ndarray<T>(
         &self,
         path_name: &str,
@@ -161,20 +207,11 @@ pub trait ZarrNdarrayReader: HierarchyReader {
         VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
         T: ReflectedType,
     {
-        let chunk_shape = match array_meta.get_chunk_memory_layout() {
-            Order::ColumnMajor => bbox.shape_ndarray_shape().f(),
-            Order::RowMajor => bbox.shape_ndarray_shape()[..].into_shape(),
-        };
-        let fill_value = array_meta.get_effective_fill_value()?;
-        let mut arr = Array::from_elem(chunk_shape, fill_value);
-
-        self.read_ndarray_into(path_name, array_meta, bbox, arr.view_mut())?;
-
-        Ok(arr)
+        self.read_ndarray_with_policy(path_name, array_meta, bbox, &MissingChunkPolicy::FillValue)
     }
 
     /// Read an arbitrary bounding box from an Zarr volume into an existing
-    /// ndarray view, reading chunks in serial as necessary.
+    /// ndarray view, reading chunks in batches as necessary.
     fn read_ndarray_into<'a, T>(
         &self,
         path_name: &str,
@@ -190,15 +227,86 @@ pub trait ZarrNdarrayReader: HierarchyReader {
     }
 
     /// Read an arbitrary bounding box from an Zarr volume into an existing
-    /// ndarray view, reading chunks in serial as necessary into a provided
-    /// buffer.
+    /// ndarray view, reading chunks in batches as necessary and leaving the
+    /// last chunk read in a provided buffer.
     fn read_ndarray_into_with_buffer<'a, T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        arr: ndarray::ArrayViewMut<'a, T, ndarray::Dim<ndarray::IxDynImpl>>,
+        chunk_buff_opt: &mut Option<VecDataChunk<T>>,
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        self.read_ndarray_into_with_policy(
+            path_name,
+            array_meta,
+            bbox,
+            arr,
+            chunk_buff_opt,
+            &MissingChunkPolicy::FillValue,
+        )
+    }
+
+    /// Read an arbitrary bounding box from an Zarr volume into an ndarray,
+    /// handling absent chunks according to a policy.
+    fn read_ndarray_with_policy<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        policy: &MissingChunkPolicy<T>,
+    ) -> Result<ndarray::Array<T, ndarray::Dim<ndarray::IxDynImpl>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        if checked_product(bbox.shape().iter().cloned())
+            .and_then(|n| usize::try_from(n).ok())
+            .is_none()
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Bounding box has more elements than can be held in memory",
+            ));
+        }
+        let fill_value = array_meta.get_effective_fill_value()?;
+        let mut arr = Array::from_elem(chunk_shape(array_meta, bbox), fill_value);
+
+        self.read_ndarray_into_with_policy(
+            path_name,
+            array_meta,
+            bbox,
+            arr.view_mut(),
+            &mut None,
+            policy,
+        )?;
+
+        Ok(arr)
+    }
+
+    /// Read an arbitrary bounding box from an Zarr volume into an existing
+    /// ndarray view, reading chunks in batches as necessary, leaving the last
+    /// chunk read in a provided buffer and handling absent chunks according
+    /// to a policy.
+    ///
+    /// Each batch of chunks is read with a single
+    /// [`HierarchyReader::read_chunks_into`], so stores fetch the chunks of
+    /// a batch together, and each chunk is decoded into the buffer.
+    ///
+    /// With [`MissingChunkPolicy::FillValue`] the parts of the view for
+    /// absent chunks are left unchanged.
+    fn read_ndarray_into_with_policy<'a, T>(
         &self,
         path_name: &str,
         array_meta: &ArrayMetadata,
         bbox: &BoundingBox,
         mut arr: ndarray::ArrayViewMut<'a, T, ndarray::Dim<ndarray::IxDynImpl>>,
         chunk_buff_opt: &mut Option<VecDataChunk<T>>,
+        policy: &MissingChunkPolicy<T>,
     ) -> Result<(), Error>
     where
         VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
@@ -218,53 +326,185 @@ pub trait ZarrNdarrayReader: HierarchyReader {
             ));
         }
 
-        for coord in array_meta.bounded_coord_iter(bbox) {
-            let grid_pos = GridCoord::from(&coord[..]);
-            let is_chunk = match chunk_buff_opt {
-                None => {
-                    *chunk_buff_opt = self.read_chunk(path_name, array_meta, grid_pos)?;
-                    chunk_buff_opt.is_some()
-                }
-                Some(ref mut chunk_buff) => self
-                    .read_chunk_into(path_name, array_meta, grid_pos, chunk_buff)?
-                    .is_some(),
-            };
-
-            // TODO: cannot combine this into condition below until `let_chains` stabilizes.
-            if !is_chunk {
-                continue;
+        // Chunks are decoded into the caller's buffer, created on first use.
+        let had_buffer = chunk_buff_opt.is_some();
+        let mut chunk_buff = chunk_buff_opt
+            .take()
+            .unwrap_or_else(|| T::create_data_chunk(&GridCoord::new(), 0));
+        let mut any_read = false;
+        let mut read_batches = || -> Result<(), Error> {
+            for batch in &array_meta
+                .bounded_coord_iter(bbox)
+                .chunks(chunk_batch_size())
+            {
+                let batch: Vec<Vec<u64>> = batch.collect();
+                let grid_positions: Vec<GridCoord> = batch
+                    .iter()
+                    .map(|coord| GridCoord::from(&coord[..]))
+                    .collect();
+                self.read_chunks_into(
+                    path_name,
+                    array_meta,
+                    &grid_positions,
+                    &mut chunk_buff,
+                    |i, chunk, exists| {
+                        let coord = &batch[i];
+                        if !exists {
+                            match policy {
+                                MissingChunkPolicy::FillValue => return Ok(()),
+                                MissingChunkPolicy::Error => {
+                                    return Err(Error::new(
+                                        ErrorKind::NotFound,
+                                        format!("Chunk {:?} of {} is missing", coord, path_name),
+                                    ))
+                                }
+                                MissingChunkPolicy::Callback(provide) => match provide(coord)? {
+                                    Some(provided) => {
+                                        if provided.get_grid_position() != &coord[..]
+                                            || provided.get_data().len()
+                                                != array_meta.get_chunk_num_elements()
+                                        {
+                                            return Err(Error::new(
+                                                ErrorKind::InvalidData,
+                                                format!(
+                                                    "Chunk provided for {:?} does not match the array",
+                                                    coord
+                                                ),
+                                            ));
+                                        }
+                                        *chunk = provided;
+                                    }
+                                    None => return Ok(()),
+                                },
+                            }
+                        }
+                        any_read = true;
+
+                        let chunk_bb = chunk.get_bounds(array_meta);
+                        let mut read_bb = bbox.clone();
+                        read_bb.intersect(&chunk_bb);
+
+                        // It may be the case the while the chunk's potential bounds are
+                        // in the request region, the chunk is smaller such that it does
+                        // not intersect.
+                        if read_bb.is_empty() {
+                            return Ok(());
+                        }
+
+                        let arr_read_bb = read_bb.clone() - &bbox.offset;
+                        let chunk_read_bb = read_bb.clone() - &chunk_bb.offset;
+
+                        let arr_slice = arr_read_bb.to_ndarray_slice();
+                        let arr_view =
+                            arr.slice_mut(SliceInfo::<_, IxDyn>::new(arr_slice).unwrap().as_ref());
+
+                        let chunk_slice = chunk_read_bb.to_ndarray_slice();
+
+                        let chunk_data = chunk.as_ndarray(array_meta);
+                        let chunk_view = chunk_data
+                            .slice(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
+
+                        assign_contiguous(arr_view, chunk_view);
+                        Ok(())
+                    },
+                )?;
             }
+            Ok(())
+        };
+        let result = read_batches();
+        if had_buffer || any_read {
+            *chunk_buff_opt = Some(chunk_buff);
+        }
+        result
+    }
+
+    /// Read a bounding box in chunk-aligned tiles which each fit a memory
+    /// budget, passing each tile and its bounds to a callback before reading
+    /// the next, so the peak memory of the read is about the budget however
+    /// large the bounding box is.
+    ///
+    /// Only the part of the bounding box within the array is read. See
+    /// [`ArrayMetadata::tiles_within_budget`] for how tiles are chosen;
+    /// this fails before reading anything if the budget is too small.
+    fn read_ndarray_bounded<T, F>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        memory_budget: usize,
+        mut tile: F,
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+        F: FnMut(
+            BoundingBox,
+            ndarray::Array<T, ndarray::Dim<ndarray::IxDynImpl>>,
+        ) -> Result<(), Error>,
+    {
+        let tiles =
+            array_meta.tiles_within_budget(bbox, memory_budget, std::mem::size_of::<T>())?;
+        for tile_bb in tiles {
+            let arr = self.read_ndarray(path_name, array_meta, &tile_bb)?;
+            tile(tile_bb, arr)?;
+        }
+        Ok(())
+    }
+
+    /// Read only the chunks present in a Zarr volume within a bounding box.
+    ///
+    /// Absent chunks are skipped rather than filled, which for sparse arrays
+    /// avoids materializing a mostly empty dense array. Each present chunk
+    /// is clipped to the bounding box and the array bounds, and returned
+    /// with its bounds in array coordinates.
+    fn read_ndarray_sparse<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+    ) -> Result<Vec<SparseBlock<T>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        if bbox.offset.len() != array_meta.get_ndim() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong number of dimensions",
+            ));
+        }
 
-            if let Some(ref chunk) = chunk_buff_opt {
+        let mut read_bounds = array_meta.get_bounds();
+        read_bounds.intersect(bbox);
+
+        let mut blocks = vec![];
+        for batch in &array_meta
+            .bounded_coord_iter(&read_bounds)
+            .chunks(chunk_batch_size())
+        {
+            let grid_positions: Vec<GridCoord> =
+                batch.map(|coord| GridCoord::from(&coord[..])).collect();
+            let chunks = self.read_chunks::<T>(path_name, array_meta, &grid_positions)?;
+            for chunk in chunks.into_iter().flatten() {
                 let chunk_bb = chunk.get_bounds(array_meta);
-                let mut read_bb = bbox.clone();
-                read_bb.intersect(&chunk_bb);
+                let mut block_bb = read_bounds.clone();
+                block_bb.intersect(&chunk_bb);
 
-                // It may be the case the while the chunk's potential bounds are
-                // in the request region, the chunk is smaller such that it does
-                // not intersect.
-                if read_bb.is_empty() {
+                if block_bb.is_empty() {
                     continue;
                 }
 
-                let arr_read_bb = read_bb.clone() - &bbox.offset;
-                let chunk_read_bb = read_bb.clone() - &chunk_bb.offset;
-
-                let arr_slice = arr_read_bb.to_ndarray_slice();
-                let mut arr_view =
-                    arr.slice_mut(SliceInfo::<_, IxDyn>::new(arr_slice).unwrap().as_ref());
-
-                let chunk_slice = chunk_read_bb.to_ndarray_slice();
-
+                let chunk_slice = (block_bb.clone() - &chunk_bb.offset).to_ndarray_slice();
                 let chunk_data = chunk.as_ndarray(array_meta);
-                let chunk_view =
-                    chunk_data.slice(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
+                let block = chunk_data
+                    .slice(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref())
+                    .to_owned();
 
-                arr_view.assign(&chunk_view);
+                blocks.push((block_bb, block));
             }
         }
 
-        Ok(())
+        Ok(blocks)
     }
 }
 
@@ -272,7 +512,11 @@ impl<T: HierarchyReader> ZarrNdarrayReader for T {}
 
 pub trait ZarrNdarrayWriter: HierarchyWriter {
     /// Write an arbitrary bounding box from an ndarray into an Zarr volume,
-    /// writing chunks in serial as necessary.
+    /// writing chunks in batches as necessary.
+    ///
+    /// Each batch of chunks is written with a single
+    /// [`HierarchyWriter::write_chunks`], after reading the chunks it
+    /// partially overwrites with a single [`HierarchyReader::read_chunks`].
     fn write_ndarray<'a, T, A>(
         &self,
         path_name: &str,
@@ -299,86 +543,79 @@ pub trait ZarrNdarrayWriter: HierarchyWriter {
         };
         let fill_value: T = array_meta.get_effective_fill_value()?;
 
-        let mut chunk_vec: Vec<T> = Vec::new();
-        let mut existing_chunk_vec: Vec<T> = Vec::new();
-
-        let extend_from_array =
-            |v: &mut Vec<T>, view: ArrayView<T, IxDyn>, array_meta: &ArrayMetadata| {
-                match array_meta.chunk_memory_layout {
-                    Order::RowMajor => match view.as_slice() {
-                        Some(s) => v.extend_from_slice(s),
-                        None => v.extend(view.iter().cloned()),
-                    },
-                    // TODO: could check if array is F-ordered and use `extend_from_slice`.
-                    Order::ColumnMajor => v.extend(view.t().iter().cloned()),
-                }
-            };
-
-        for coord in array_meta.bounded_coord_iter(&bbox) {
-            let grid_coord = GridCoord::from(&coord[..]);
-            let nom_chunk_bb = array_meta.get_chunk_bounds(&grid_coord);
-            let mut write_bb = nom_chunk_bb.clone();
-            write_bb.intersect(&bbox);
-            let arr_bb = write_bb.clone() - &bbox.offset;
-
-            let arr_slice = arr_bb.to_ndarray_slice();
-            let arr_view = array.slice(SliceInfo::<_, IxDyn>::new(arr_slice).unwrap().as_ref());
-
-            if write_bb == nom_chunk_bb {
-                // No need to read whether there is an extant chunk if it is
-                // going to be entirely overwriten.
-                chunk_vec.clear();
-                extend_from_array(&mut chunk_vec, arr_view, array_meta);
-                let chunk = VecDataChunk::new(coord.into(), chunk_vec);
-
-                self.write_chunk(path_name, array_meta, &chunk)?;
-                chunk_vec = chunk.into_data();
-            } else {
-                let mut existing_chunk = VecDataChunk::new(grid_coord.clone(), existing_chunk_vec);
-                let chunk_opt = self.read_chunk_into(
-                    path_name,
-                    array_meta,
-                    grid_coord.clone(),
-                    &mut existing_chunk,
-                )?;
+        for batch in &array_meta
+            .bounded_coord_iter(&bbox)
+            .chunks(chunk_batch_size())
+        {
+            let batch: Vec<(Vec<u64>, BoundingBox, BoundingBox)> = batch
+                .map(|coord| {
+                    let nom_chunk_bb = array_meta.get_chunk_bounds(&coord);
+                    let mut write_bb = nom_chunk_bb.clone();
+                    write_bb.intersect(&bbox);
+                    (coord, nom_chunk_bb, write_bb)
+                })
+                .collect();
 
-                let (chunk_bb, mut chunk_array) = match chunk_opt {
-                    Some(()) => {
-                        let chunk_bb = existing_chunk.get_bounds(array_meta);
-                        let chunk_array = existing_chunk.into_ndarray(array_meta);
-                        (chunk_bb, chunk_array)
-                    }
-                    None => {
-                        // If no chunk exists, need to write from its origin.
-                        // In Zarr this simply means the chunk must be full.
-                        let chunk_shape_usize = nom_chunk_bb.shape_ndarray_shape();
-                        existing_chunk_vec = existing_chunk.into_data();
-                        existing_chunk_vec.clear();
-                        existing_chunk_vec
-                            .resize(chunk_shape_usize.iter().product(), fill_value.clone());
-
-                        let chunk_array =
-                            Array::from_shape_vec(&chunk_shape_usize[..], existing_chunk_vec)
-                                .expect("TODO: chunk ndarray failed");
-                        (nom_chunk_bb, chunk_array)
+            // No need to read whether there is an extant chunk if it is
+            // going to be entirely overwriten.
+            let partial: Vec<GridCoord> = batch
+                .iter()
+                .filter(|(_, nom_chunk_bb, write_bb)| write_bb != nom_chunk_bb)
+                .map(|(coord, _, _)| GridCoord::from(&coord[..]))
+                .collect();
+            let mut existing_chunks = self
+                .read_chunks::<T>(path_name, array_meta, &partial)?
+                .into_iter();
+
+            let mut chunks = Vec::with_capacity(batch.len());
+            for (coord, nom_chunk_bb, write_bb) in batch {
+                let arr_bb = write_bb.clone() - &bbox.offset;
+                let arr_slice = arr_bb.to_ndarray_slice();
+                let arr_view = array.slice(SliceInfo::<_, IxDyn>::new(arr_slice).unwrap().as_ref());
+
+                let chunk_vec = if write_bb == nom_chunk_bb {
+                    let mut chunk_vec = Vec::with_capacity(arr_view.len());
+                    match array_meta.chunk_memory_layout {
+                        Order::RowMajor => extend_by_rows(&mut chunk_vec, arr_view),
+                        Order::ColumnMajor => extend_by_rows(&mut chunk_vec, arr_view.t()),
                     }
+                    chunk_vec
+                } else {
+                    let existing_chunk = existing_chunks
+                        .next()
+                        .expect("Each partially written chunk is read");
+                    // Either way the data is already in the chunk's memory
+                    // layout, so only the written part needs copying.
+                    let (chunk_bb, mut chunk_vec) = match existing_chunk {
+                        Some(existing_chunk) => (
+                            existing_chunk.get_bounds(array_meta),
+                            existing_chunk.into_data(),
+                        ),
+                        None => {
+                            // If no chunk exists, need to write from its origin.
+                            // In Zarr this simply means the chunk must be full.
+                            let num_elements = array_meta.get_chunk_num_elements();
+                            (nom_chunk_bb, vec![fill_value.clone(); num_elements])
+                        }
+                    };
+
+                    let chunk_write_bb = write_bb.clone() - &chunk_bb.offset;
+                    let chunk_slice = chunk_write_bb.to_ndarray_slice();
+                    let mut chunk_array = ArrayViewMut::from_shape(
+                        chunk_shape(array_meta, &chunk_bb),
+                        &mut chunk_vec,
+                    )
+                    .expect("TODO: chunk ndarray failed");
+                    let chunk_view = chunk_array
+                        .slice_mut(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
+
+                    assign_contiguous(chunk_view, arr_view);
+                    chunk_vec
                 };
-
-                let chunk_write_bb = write_bb.clone() - &chunk_bb.offset;
-                let chunk_slice = chunk_write_bb.to_ndarray_slice();
-                let mut chunk_view = chunk_array
-                    .slice_mut(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
-
-                chunk_view.assign(&arr_view);
-
-                chunk_vec.clear();
-                extend_from_array(&mut chunk_vec, chunk_array.view(), array_meta);
-                let chunk = VecDataChunk::new(coord.into(), chunk_vec);
-
-                self.write_chunk(path_name, array_meta, &chunk)?;
-                chunk_vec = chunk.into_data();
-                existing_chunk_vec = chunk_array.into_raw_vec();
+                chunks.push(VecDataChunk::new(coord.into(), chunk_vec));
             }
+
+            self.write_chunks(path_name, array_meta, &chunks)?;
         }
 
         Ok(())
@@ -388,12 +625,12 @@ pub trait ZarrNdarrayWriter: HierarchyWriter {
 impl<T: HierarchyWriter> ZarrNdarrayWriter for T {}
 
 impl ArrayMetadata {
-    pub fn coord_iter(&self) -> impl Iterator<Item = Vec<u64>> + ExactSizeIterator {
+    pub fn coord_iter(&self) -> impl ExactSizeIterator<Item = Vec<u64>> {
         let coord_ceil = self
             .get_shape()
             .iter()
             .zip(self.get_chunk_shape().iter())
-            .map(|(&d, &s)| (d + u64::from(s) - 1) / u64::from(s))
+            .map(|(&d, &s)| d.div_ceil(u64::from(s)))
             .collect::<GridCoord>();
 
         CoordIterator::new(&coord_ceil)
@@ -402,7 +639,7 @@ impl ArrayMetadata {
     pub fn bounded_coord_iter(
         &self,
         unbounded_bbox: &BoundingBox,
-    ) -> impl Iterator<Item = Vec<u64>> + ExactSizeIterator {
+    ) -> impl ExactSizeIterator<Item = Vec<u64>> {
         let mut bbox = self.get_bounds();
         bbox.intersect(unbounded_bbox);
         let floor_coord: GridCoord = bbox
@@ -416,7 +653,7 @@ impl ArrayMetadata {
             .iter()
             .zip(&bbox.shape)
             .zip(self.chunk_grid.chunk_shape.iter().cloned().map(u64::from))
-            .map(|((&o, &s), cs)| (o + s + cs - 1) / cs)
+            .map(|((&o, &s), cs)| (o + s).div_ceil(cs))
             .collect();
 
         CoordIterator::floor_ceil(&floor_coord, &ceil_coord)
@@ -441,6 +678,228 @@ impl ArrayMetadata {
         // array bounds, as boundary chunks may overhang.
         BoundingBox { offset, shape }
     }
+
+    /// Get the grid positions of chunks intersecting a region, each with
+    /// the part of the region, within the array bounds, that it holds.
+    ///
+    /// ```
+    /// # use zarr::prelude::*;
+    /// # use zarr::ndarray::BoundingBox;
+    /// # use zarr::smallvec::smallvec;
+    /// let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], u8::ZARR_TYPE)
+    ///     .chunk_shape(smallvec![4, 4])
+    ///     .build();
+    /// let region = BoundingBox::new(smallvec![3, 6], smallvec![2, 5]);
+    /// let extents: Vec<_> = array_meta.chunk_extents_in(&region).collect();
+    /// assert_eq!(extents, vec![
+    ///     (vec![0, 1], BoundingBox::new(smallvec![3, 6], smallvec![1, 2])),
+    ///     (vec![0, 2], BoundingBox::new(smallvec![3, 8], smallvec![1, 2])),
+    ///     (vec![1, 1], BoundingBox::new(smallvec![4, 6], smallvec![1, 2])),
+    ///     (vec![1, 2], BoundingBox::new(smallvec![4, 8], smallvec![1, 2])),
+    /// ]);
+    /// ```
+    pub fn chunk_extents_in<'a>(
+        &'a self,
+        region: &BoundingBox,
+    ) -> impl ExactSizeIterator<Item = (Vec<u64>, BoundingBox)> + 'a {
+        let mut region = region.clone();
+        region.intersect(&self.get_bounds());
+        self.bounded_coord_iter(&region).map(move |coord| {
+            let mut extent = self.get_chunk_bounds(&coord);
+            extent.intersect(&region);
+            (coord, extent)
+        })
+    }
+
+    /// Get the smallest chunk-aligned region containing a region, within the
+    /// array bounds. The end of the array counts as a chunk boundary.
+    ///
+    /// ```
+    /// # use zarr::prelude::*;
+    /// # use zarr::ndarray::BoundingBox;
+    /// # use zarr::smallvec::smallvec;
+    /// let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], u8::ZARR_TYPE)
+    ///     .chunk_shape(smallvec![4, 4])
+    ///     .build();
+    /// let region = BoundingBox::new(smallvec![3, 6], smallvec![2, 3]);
+    /// assert_eq!(
+    ///     array_meta.round_out_to_chunks(&region),
+    ///     BoundingBox::new(smallvec![0, 4], smallvec![8, 6]),
+    /// );
+    /// ```
+    pub fn round_out_to_chunks(&self, region: &BoundingBox) -> BoundingBox {
+        self.align_to_chunks(region, |o, cs| o / cs * cs, |e, cs| e.div_ceil(cs) * cs)
+    }
+
+    /// Get the largest chunk-aligned region contained in a region, within
+    /// the array bounds. The end of the array counts as a chunk boundary.
+    ///
+    /// ```
+    /// # use zarr::prelude::*;
+    /// # use zarr::ndarray::BoundingBox;
+    /// # use zarr::smallvec::smallvec;
+    /// let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], u8::ZARR_TYPE)
+    ///     .chunk_shape(smallvec![4, 4])
+    ///     .build();
+    /// let region = BoundingBox::new(smallvec![3, 2], smallvec![6, 8]);
+    /// assert_eq!(
+    ///     array_meta.round_in_to_chunks(&region),
+    ///     BoundingBox::new(smallvec![4, 4], smallvec![4, 6]),
+    /// );
+    /// // Regions smaller than a chunk round in to nothing.
+    /// let region = BoundingBox::new(smallvec![1, 1], smallvec![2, 2]);
+    /// assert!(array_meta.round_in_to_chunks(&region).is_empty());
+    /// ```
+    pub fn round_in_to_chunks(&self, region: &BoundingBox) -> BoundingBox {
+        self.align_to_chunks(region, |o, cs| o.div_ceil(cs) * cs, |e, cs| e / cs * cs)
+    }
+
+    /// Split a region, within the array bounds, into chunk-aligned tiles
+    /// which can each be read within a memory budget, in row-major order.
+    ///
+    /// The memory to read a tile is estimated as its elements, of
+    /// `element_size` bytes each, plus a chunk's encoded and decoded
+    /// buffers. Tiles are as large as fit the budget, spanning the region
+    /// along the last axes first. Fails with [`ErrorKind::OutOfMemory`] if
+    /// a tile of a single chunk does not fit.
+    ///
+    /// ```
+    /// # use zarr::prelude::*;
+    /// # use zarr::ndarray::BoundingBox;
+    /// # use zarr::smallvec::smallvec;
+    /// let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], u8::ZARR_TYPE)
+    ///     .chunk_shape(smallvec![4, 4])
+    ///     .build();
+    /// // Rows of chunks take 40 bytes, and the chunk buffers 32 bytes.
+    /// let tiles: Vec<_> = array_meta
+    ///     .tiles_within_budget(&array_meta.get_bounds(), 80, 1)
+    ///     .unwrap()
+    ///     .collect();
+    /// assert_eq!(tiles, vec![
+    ///     BoundingBox::new(smallvec![0, 0], smallvec![4, 10]),
+    ///     BoundingBox::new(smallvec![4, 0], smallvec![4, 10]),
+    ///     BoundingBox::new(smallvec![8, 0], smallvec![2, 10]),
+    /// ]);
+    /// ```
+    pub fn tiles_within_budget(
+        &self,
+        region: &BoundingBox,
+        memory_budget: usize,
+        element_size: usize,
+    ) -> Result<impl ExactSizeIterator<Item = BoundingBox>, Error> {
+        let mut region = region.clone();
+        region.intersect(&self.get_bounds());
+        let chunk_shape: GridCoord = self
+            .get_chunk_shape()
+            .iter()
+            .cloned()
+            .map(u64::from)
+            .collect();
+        let floor: GridCoord = region
+            .offset
+            .iter()
+            .zip(&chunk_shape)
+            .map(|(&o, &cs)| o / cs)
+            .collect();
+        let spans: GridCoord = region
+            .offset
+            .iter()
+            .zip(region.end())
+            .zip(&chunk_shape)
+            .map(|((&o, e), &cs)| if e > o { e.div_ceil(cs) - o / cs } else { 0 })
+            .collect();
+
+        let element_size = element_size as u64;
+        let buffers = checked_product(chunk_shape.iter().cloned())
+            .and_then(|n| n.checked_mul(element_size)?.checked_mul(2));
+        let fits = |tile_chunks: &[u64]| {
+            checked_product(
+                tile_chunks
+                    .iter()
+                    .zip(&chunk_shape)
+                    .zip(&region.shape)
+                    .map(|((&n, &cs), &s)| n.saturating_mul(cs).min(s)),
+            )
+            .and_then(|n| n.checked_mul(element_size)?.checked_add(buffers?))
+            .is_some_and(|bytes| bytes <= memory_budget as u64)
+        };
+
+        let mut tile_chunks: GridCoord = smallvec![1; chunk_shape.len()];
+        if !region.is_empty() && !fits(&tile_chunks) {
+            return Err(Error::new(
+                ErrorKind::OutOfMemory,
+                format!(
+                    "Chunks of {:?} cannot be read within a memory budget of {} bytes",
+                    self.get_chunk_shape(),
+                    memory_budget
+                ),
+            ));
+        }
+        for axis in (0..tile_chunks.len()).rev() {
+            // Find the most chunks along this axis which fit.
+            let (mut low, mut high) = (1, spans[axis].max(1));
+            while low < high {
+                let mid = low + (high - low).div_ceil(2);
+                tile_chunks[axis] = mid;
+                if fits(&tile_chunks) {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            tile_chunks[axis] = low;
+            if low < spans[axis] {
+                break;
+            }
+        }
+
+        let tile_grid: GridCoord = spans
+            .iter()
+            .zip(&tile_chunks)
+            .map(|(&span, &n)| span.div_ceil(n))
+            .collect();
+        Ok(CoordIterator::new(&tile_grid).map(move |tile| {
+            let (offset, shape) = tile
+                .iter()
+                .zip(&tile_chunks)
+                .zip(&floor)
+                .zip(&chunk_shape)
+                .zip(region.offset.iter().zip(region.end()))
+                .map(|((((&t, &n), &f), &cs), (&o, e))| {
+                    let start = ((f + t * n) * cs).max(o);
+                    let end = ((f + (t + 1) * n) * cs).min(e);
+                    (start, end - start)
+                })
+                .unzip();
+            BoundingBox { offset, shape }
+        }))
+    }
+
+    fn align_to_chunks(
+        &self,
+        region: &BoundingBox,
+        align_offset: impl Fn(u64, u64) -> u64,
+        align_end: impl Fn(u64, u64) -> u64,
+    ) -> BoundingBox {
+        let mut region = region.clone();
+        region.intersect(&self.get_bounds());
+        if region.is_empty() {
+            return region;
+        }
+        let (offset, shape) = region
+            .offset
+            .iter()
+            .zip(region.end())
+            .zip(self.shape.iter())
+            .zip(self.chunk_grid.chunk_shape.iter().cloned().map(u64::from))
+            .map(|(((&o, e), &d), cs)| {
+                let offset = align_offset(o, cs).min(d);
+                let end = if e == d { d } else { align_end(e, cs).min(d) };
+                (offset, end.saturating_sub(offset))
+            })
+            .unzip();
+        BoundingBox { offset, shape }
+    }
 }
 
 impl<T: ReflectedType, C: AsRef<[T]>> SliceDataChunk<T, C> {
@@ -454,17 +913,13 @@ impl<T: ReflectedType, C: AsRef<[T]>> SliceDataChunk<T, C> {
         &self,
         array_meta: &ArrayMetadata,
     ) -> ndarray::Shape<ndarray::Dim<ndarray::IxDynImpl>> {
-        let chunk_bb = self.get_bounds(array_meta);
-        match array_meta.get_chunk_memory_layout() {
-            Order::ColumnMajor => chunk_bb.shape_ndarray_shape().f(),
-            Order::RowMajor => chunk_bb.shape_ndarray_shape()[..].into_shape(),
-        }
+        chunk_shape(array_meta, &self.get_bounds(array_meta))
     }
 
     pub fn as_ndarray(
         &self,
         array_meta: &ArrayMetadata,
-    ) -> ArrayView<T, ndarray::Dim<ndarray::IxDynImpl>> {
+    ) -> ArrayView<'_, T, ndarray::Dim<ndarray::IxDynImpl>> {
         let chunk_shape = self.shape_ndarray_shape(array_meta);
         ArrayView::from_shape(chunk_shape, self.get_data()).expect("TODO: chunk ndarray failed")
     }
@@ -480,6 +935,70 @@ impl<T: ReflectedType> VecDataChunk<T> {
     }
 }
 
+/// The shape of a bounding box in the array's chunk memory layout.
+fn chunk_shape(
+    array_meta: &ArrayMetadata,
+    bbox: &BoundingBox,
+) -> ndarray::Shape<ndarray::Dim<ndarray::IxDynImpl>> {
+    match array_meta.get_chunk_memory_layout() {
+        Order::ColumnMajor => bbox.shape_ndarray_shape().f(),
+        Order::RowMajor => bbox.shape_ndarray_shape()[..].into_shape(),
+    }
+}
+
+/// Append the elements of a view in logical order, copying whole rows where
+/// they are contiguous rather than an element at a time.
+fn extend_by_rows<T: Clone>(v: &mut Vec<T>, view: ArrayView<T, IxDyn>) {
+    if let Some(s) = view.as_slice() {
+        v.extend_from_slice(s);
+        return;
+    }
+    if view.ndim() == 0 {
+        v.extend(view.iter().cloned());
+        return;
+    }
+    v.reserve(view.len());
+    for row in view.genrows() {
+        match row.as_slice() {
+            Some(s) => v.extend_from_slice(s),
+            None => v.extend(row.iter().cloned()),
+        }
+    }
+}
+
+/// Assign a view to another of the same shape, reversing the axes of both
+/// first if they are column-major, so that the copy runs along contiguous
+/// rows rather than striding through memory an element at a time.
+fn assign_contiguous<T: Clone>(mut dst: ArrayViewMut<T, IxDyn>, src: ArrayView<T, IxDyn>) {
+    let ndim = dst.ndim();
+    let unit_stride = |strides: &[isize], axis: usize| strides[axis] == 1;
+    if ndim > 1
+        && !unit_stride(dst.strides(), ndim - 1)
+        && unit_stride(dst.strides(), 0)
+        && unit_stride(src.strides(), 0)
+    {
+        dst.reversed_axes().assign(&src.reversed_axes());
+    } else {
+        dst.assign(&src);
+    }
+}
+
+/// Number of chunks region reads and writes fetch or store together, enough
+/// to keep each of the configured
+/// [`concurrency`](crate::config::Config::concurrency) requests busy while
+/// bounding the memory of chunks in flight.
+fn chunk_batch_size() -> usize {
+    crate::config::config().concurrency.max(1) * 4
+}
+
+/// Number of coordinates in a range of the given extents, saturating at
+/// `usize::MAX` since more could never be iterated.
+fn saturating_len<I: IntoIterator<Item = u64>>(extents: I) -> usize {
+    checked_product(extents)
+        .and_then(|n| usize::try_from(n).ok())
+        .unwrap_or(usize::MAX)
+}
+
 /// Iterator wrapper to provide exact size when iterating over coordinate
 /// ranges.
 struct CoordIterator<T: Iterator<Item = Vec<u64>>> {
@@ -493,16 +1012,17 @@ impl CoordIterator<itertools::MultiProduct<std::ops::Range<u64>>> {
         CoordIterator {
             iter: ceil.iter().map(|&c| 0..c).multi_cartesian_product(),
             accumulator: 0,
-            total_coords: ceil.iter().product::<u64>() as usize,
+            total_coords: saturating_len(ceil.iter().cloned()),
         }
     }
 
     fn floor_ceil(floor: &[u64], ceil: &[u64]) -> Self {
-        let total_coords = floor
-            .iter()
-            .zip(ceil.iter())
-            .map(|(&f, &c)| c.saturating_sub(f))
-            .product::<u64>() as usize;
+        let total_coords = saturating_len(
+            floor
+                .iter()
+                .zip(ceil.iter())
+                .map(|(&f, &c)| c.saturating_sub(f)),
+        );
         CoordIterator {
             iter: floor
                 .iter()
@@ -519,8 +1039,12 @@ impl<T: Iterator<Item = Vec<u64>>> Iterator for CoordIterator<T> {
     type Item = Vec<u64>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.accumulator >= self.total_coords {
+            return None;
+        }
         self.accumulator += 1;
-        self.iter.next()
+        // The product of no ranges is a single, empty, coordinate.
+        Some(self.iter.next().unwrap_or_default())
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -555,4 +1079,95 @@ pub(crate) mod tests {
 
         assert_eq!(coords, expected);
     }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_write_ndarray_edge_chunks() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let region = Array::from_shape_fn(IxDyn(&[4, 5, 3]), |idx| {
+            (idx[0] * 100 + idx[1] * 10 + idx[2]) as i32
+        });
+        for order in [Order::RowMajor, Order::ColumnMajor].iter().cloned() {
+            let path_name = format!("{:?}", order);
+            let array_meta = ArrayMetadataBuilder::new(smallvec![7, 8, 9], i32::ZARR_TYPE)
+                .chunk_shape(smallvec![3, 4, 2])
+                .chunk_memory_layout(order)
+                .fill_value(-1)
+                .build();
+            h.create_array(&path_name, &array_meta).unwrap();
+            h.write_ndarray(&path_name, &array_meta, smallvec![1, 2, 3], &region)
+                .unwrap();
+            // Splice into the partial chunks just written.
+            h.write_ndarray(
+                &path_name,
+                &array_meta,
+                smallvec![4, 6, 5],
+                &Array::from_elem(IxDyn(&[1, 1, 1]), 42),
+            )
+            .unwrap();
+
+            let mut expected = Array::from_elem(IxDyn(&[7, 8, 9]), -1);
+            let region_slice = BoundingBox::new(smallvec![1, 2, 3], smallvec![4, 5, 3]);
+            expected
+                .slice_mut(
+                    SliceInfo::<_, IxDyn>::new(region_slice.to_ndarray_slice())
+                        .unwrap()
+                        .as_ref(),
+                )
+                .assign(&region);
+            expected[[4, 6, 5]] = 42;
+            let bbox = array_meta.get_bounds();
+            let read = h
+                .read_ndarray::<i32>(&path_name, &array_meta, &bbox)
+                .unwrap();
+            assert_eq!(read, expected, "{}", path_name);
+        }
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_read_ndarray_into_with_buffer() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![8, 8], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![4, 4])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        let data = Array::from_shape_fn(IxDyn(&[8, 8]), |idx| (idx[0] * 8 + idx[1]) as u16);
+        h.write_ndarray("a", &array_meta, smallvec![0, 0], &data)
+            .unwrap();
+
+        let mut buffer = Some(VecDataChunk::new(smallvec![0, 0], vec![0u16; 16]));
+        let allocation = buffer.as_ref().unwrap().get_data().as_ptr();
+        let mut read = Array::zeros(IxDyn(&[8, 8]));
+        h.read_ndarray_into_with_buffer(
+            "a",
+            &array_meta,
+            &array_meta.get_bounds(),
+            read.view_mut(),
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(read, data);
+        // Each chunk is decoded into the buffer, which holds the last one.
+        let buffer = buffer.unwrap();
+        assert_eq!(buffer.get_data().as_ptr(), allocation);
+        assert_eq!(buffer.get_grid_position(), &[1, 1]);
+
+        let mut buffer = None;
+        h.read_ndarray_into_with_buffer(
+            "a",
+            &array_meta,
+            &array_meta.get_bounds(),
+            read.view_mut(),
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(buffer.unwrap().get_grid_position(), &[1, 1]);
+    }
 }
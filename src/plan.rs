@@ -0,0 +1,219 @@
+//! Plans of the chunk reads needed to read a region of an array.
+//!
+//! A [`ReadPlan`] lists the stored chunks a region read touches, with the
+//! byte range of each and the codecs decoding it, without reading any chunk.
+//! External schedulers and reference tools can use it to fetch and decode
+//! chunks themselves:
+//!
+//! ```no_run
+//! use zarr::ndarray::BoundingBox;
+//! use zarr::plan::HierarchyReadPlan;
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("intensity").unwrap();
+//! let region = BoundingBox::new(smallvec![0, 0, 0], smallvec![64, 512, 512]);
+//! let plan = h.plan_read("intensity", &array_meta, &region).unwrap();
+//! println!("{}", serde_json::to_string_pretty(&plan).unwrap());
+//! ```
+
+use std::io::Error;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::compression::CompressionType;
+use crate::filter::FilterType;
+use crate::ndarray::BoundingBox;
+use crate::storage::{
+    ChunkKeyFormatter,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    ExtensibleDataType,
+    Hierarchy,
+    HierarchyReader,
+    Order,
+};
+
+/// A contiguous range of the bytes of a stored value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// A chunk read needed by a region read.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRead {
+    pub grid_position: Vec<u64>,
+    /// Store key of the chunk.
+    pub key: String,
+    /// Bytes of the stored chunk to read, or `None` if the chunk does not
+    /// exist, in which case its elements are the fill value.
+    pub byte_range: Option<ByteRange>,
+    /// Offset of the part of the region held by the chunk, relative to the
+    /// chunk's origin.
+    pub chunk_offset: Vec<u64>,
+    /// Offset of the part of the region held by the chunk, relative to the
+    /// region's origin.
+    pub region_offset: Vec<u64>,
+    /// Shape of the part of the region held by the chunk.
+    pub shape: Vec<u64>,
+}
+
+/// The chunk reads needed to read a region of an array, and how to decode
+/// them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadPlan {
+    pub path: String,
+    /// Offset of the region, within the array bounds.
+    pub offset: Vec<u64>,
+    /// Shape of the region, within the array bounds.
+    pub shape: Vec<u64>,
+    pub chunk_shape: Vec<u32>,
+    pub data_type: ExtensibleDataType,
+    /// Memory layout of decoded chunks.
+    pub order: Order,
+    pub fill_value: Option<Value>,
+    /// Compressor decoding a stored chunk, applied first.
+    pub compressor: CompressionType,
+    /// Filters applied to chunk data before compression, so decoding a
+    /// chunk applies them in reverse after the compressor.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterType>,
+    /// Chunk reads in chunk grid order.
+    pub chunks: Vec<ChunkRead>,
+}
+
+impl ReadPlan {
+    /// Total number of stored bytes the plan reads.
+    pub fn bytes(&self) -> u64 {
+        self.chunks
+            .iter()
+            .filter_map(|chunk| chunk.byte_range)
+            .map(|range| range.length)
+            .sum()
+    }
+}
+
+fn offset_from(offset: &[u64], origin: &[u64]) -> Vec<u64> {
+    offset
+        .iter()
+        .zip(origin)
+        .map(|(o, origin)| o - origin)
+        .collect()
+}
+
+/// Read plans for hierarchies over readable stores.
+pub trait HierarchyReadPlan: HierarchyReader {
+    /// Plan the chunk reads needed to read a region of an array, without
+    /// reading any chunk.
+    fn plan_read(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        region: &BoundingBox,
+    ) -> Result<ReadPlan, Error>;
+}
+
+impl<S: ReadableStore + Hierarchy> HierarchyReadPlan for S {
+    fn plan_read(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        region: &BoundingBox,
+    ) -> Result<ReadPlan, Error> {
+        let mut bounded = region.clone();
+        bounded.intersect(&array_meta.get_bounds());
+
+        let formatter = ChunkKeyFormatter::new(path_name, array_meta);
+        let mut chunks = vec![];
+        for (grid_position, extent) in array_meta.chunk_extents_in(&bounded) {
+            let key = formatter.find(self, &grid_position)?;
+            let byte_range = self.stat(&key)?.map(|stat| ByteRange {
+                offset: 0,
+                length: stat.size,
+            });
+            let chunk_bb = array_meta.get_chunk_bounds(&grid_position);
+            chunks.push(ChunkRead {
+                key,
+                byte_range,
+                chunk_offset: offset_from(extent.offset(), chunk_bb.offset()),
+                region_offset: offset_from(extent.offset(), bounded.offset()),
+                shape: extent.shape().to_vec(),
+                grid_position,
+            });
+        }
+
+        Ok(ReadPlan {
+            path: crate::canonicalize_path(path_name).to_owned(),
+            offset: bounded.offset().to_vec(),
+            shape: bounded.shape().to_vec(),
+            chunk_shape: array_meta.get_chunk_shape().to_vec(),
+            data_type: array_meta.get_data_type().clone(),
+            order: array_meta.get_chunk_memory_layout().clone(),
+            fill_value: array_meta.get_fill_value().cloned(),
+            compressor: array_meta.get_compressor().clone(),
+            filters: array_meta.get_filters().to_vec(),
+            chunks,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_plan_read() {
+        let dir = tempdir::TempDir::new("rust_zarr_plan_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![5, 6], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![1, 1], vec![1u16; 8]);
+        h.write_chunk("a", &array_meta, &chunk).unwrap();
+
+        let region = BoundingBox::new(smallvec![1, 3], smallvec![3, 5]);
+        let plan = h.plan_read("a", &array_meta, &region).unwrap();
+        assert_eq!(plan.offset, vec![1, 3]);
+        assert_eq!(plan.shape, vec![3, 3]);
+        assert_eq!(
+            plan.chunks
+                .iter()
+                .map(|chunk| chunk.grid_position.clone())
+                .collect::<Vec<_>>(),
+            vec![vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1]]
+        );
+
+        let stored = &plan.chunks[3];
+        assert_eq!(
+            stored.key,
+            crate::storage::get_chunk_key("a", &array_meta, &[1, 1])
+        );
+        assert_eq!(
+            stored.byte_range,
+            Some(ByteRange {
+                offset: 0,
+                length: 16,
+            })
+        );
+        assert_eq!(stored.chunk_offset, vec![0, 0]);
+        assert_eq!(stored.region_offset, vec![1, 1]);
+        assert_eq!(stored.shape, vec![2, 2]);
+        assert_eq!(plan.chunks[0].byte_range, None);
+        assert_eq!(plan.chunks[0].chunk_offset, vec![1, 3]);
+        assert_eq!(plan.bytes(), 16);
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(serde_json::from_value::<ReadPlan>(json).unwrap(), plan);
+    }
+}
@@ -0,0 +1,1009 @@
+//! Interoperability with Neuroglancer precomputed volumes.
+//!
+//! A [`PrecomputedVolume`] presents each scale of a precomputed volume as a
+//! read-only array behind the usual [`HierarchyReader`] API, so volumes can be
+//! read with the same chunk and ndarray methods as Zarr arrays. The array path
+//! of a scale is its key, and its shape is `[x, y, z, channel]`, the native
+//! axis order of precomputed volumes. Only the `raw` encoding of unsharded
+//! scales is supported.
+//!
+//! Arrays can be exported to a new precomputed volume with
+//! [`export_precomputed`], and regions of multi-scale volumes read at the
+//! scale best matching a voxel size with
+//! [`PrecomputedVolume::read_at_resolution`].
+//!
+//! ```no_run
+//! use zarr::ndarray::prelude::*;
+//! use zarr::precomputed::PrecomputedVolume;
+//! use zarr::prelude::*;
+//!
+//! let store = FilesystemHierarchy::open_or_create("/tmp/volumes").unwrap();
+//! let volume = PrecomputedVolume::open(store, "em").unwrap();
+//! let scale = volume.get_info().scales[0].key.clone();
+//! let array_meta = volume.get_array_metadata(&scale).unwrap();
+//! let data = volume
+//!     .read_ndarray::<u8>(&scale, &array_meta, &array_meta.get_bounds())
+//!     .unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use semver::VersionReq;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    compression::CompressionType,
+    storage::{
+        ListableStore,
+        ReadableStore,
+    },
+    ArrayMetadata,
+    ArrayMetadataBuilder,
+    DataChunk,
+    DataType,
+    Endian,
+    EntryPointMetadata,
+    FloatSize,
+    GridCoord,
+    GroupMetadata,
+    Hierarchy,
+    HierarchyLister,
+    HierarchyReader,
+    IntSize,
+    JsonObject,
+    Order,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    SliceDataChunk,
+    StoreNodeMetadata,
+    VecDataChunk,
+};
+
+/// Key of the volume metadata, relative to the volume prefix.
+const INFO_KEY: &str = "info";
+
+/// The only supported chunk encoding.
+const RAW_ENCODING: &str = "raw";
+
+/// Kind of data in a precomputed volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeType {
+    Image,
+    Segmentation,
+}
+
+/// Metadata of a precomputed volume, stored as its `info` file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PrecomputedInfo {
+    #[serde(rename = "type")]
+    pub volume_type: VolumeType,
+    pub data_type: String,
+    pub num_channels: u64,
+    pub scales: Vec<PrecomputedScale>,
+    /// Unrecognized fields, preserved for round-tripping.
+    #[serde(flatten)]
+    pub extra_fields: JsonObject,
+}
+
+impl PrecomputedInfo {
+    /// Find a scale by its key.
+    pub fn get_scale(&self, key: &str) -> Option<&PrecomputedScale> {
+        self.scales.iter().find(|scale| scale.key == key)
+    }
+
+    /// Select the scale to read at a target voxel size: the coarsest scale
+    /// whose voxels are no larger than the target along any axis, or the
+    /// finest scale if every scale is coarser than the target.
+    pub fn select_scale(&self, target_resolution: [f64; 3]) -> Option<&PrecomputedScale> {
+        let volume = |scale: &&PrecomputedScale| scale.resolution.iter().product::<f64>();
+        let by_volume = |a: &&PrecomputedScale, b: &&PrecomputedScale| {
+            volume(a)
+                .partial_cmp(&volume(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        self.scales
+            .iter()
+            .filter(|scale| {
+                scale
+                    .resolution
+                    .iter()
+                    .zip(&target_resolution)
+                    .all(|(&r, &target)| r <= target * (1.0 + RESOLUTION_TOLERANCE))
+            })
+            .max_by(by_volume)
+            .or_else(|| self.scales.iter().min_by(by_volume))
+    }
+}
+
+/// Relative tolerance of comparisons of voxel sizes and positions, so that
+/// rounding in resolutions does not change the selected scale or region.
+const RESOLUTION_TOLERANCE: f64 = 1e-9;
+
+/// Metadata of one scale of a precomputed volume.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PrecomputedScale {
+    /// Key of the scale's chunks, relative to the volume prefix.
+    pub key: String,
+    /// Extent in voxels along `x`, `y` and `z`.
+    pub size: [u64; 3],
+    /// Voxel size in nanometers.
+    pub resolution: [f64; 3],
+    /// Position of the first voxel.
+    #[serde(default)]
+    pub voxel_offset: [i64; 3],
+    /// Supported chunk shapes. Only the first is used.
+    pub chunk_sizes: Vec<[u32; 3]>,
+    pub encoding: String,
+    /// Unrecognized fields, preserved for round-tripping.
+    #[serde(flatten)]
+    pub extra_fields: JsonObject,
+}
+
+impl PrecomputedScale {
+    /// Describe this scale as an array of a volume of a data type and number
+    /// of channels.
+    fn array_metadata(
+        &self,
+        data_type: DataType,
+        num_channels: u64,
+    ) -> Result<ArrayMetadata, Error> {
+        if self.encoding != RAW_ENCODING {
+            return Err(Error::other(format!(
+                "Unsupported precomputed encoding: {}",
+                self.encoding
+            )));
+        }
+        if self.extra_fields.contains_key("sharding") {
+            return Err(Error::other("Sharded precomputed scales are not supported"));
+        }
+        let chunk_size = self.chunk_sizes.first().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "Precomputed scale has no chunk sizes",
+            )
+        })?;
+        let channel_chunk = u32::try_from(num_channels)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Too many channels"))?;
+
+        let mut shape: GridCoord = self.size[..].into();
+        shape.push(num_channels);
+        let mut chunk_shape: crate::ChunkCoord = chunk_size[..].into();
+        chunk_shape.push(channel_chunk);
+
+        Ok(ArrayMetadataBuilder::new(shape, data_type)
+            .chunk_shape(chunk_shape)
+            .chunk_memory_layout(Order::ColumnMajor)
+            .compressor(CompressionType::default())
+            .read_only(true)
+            .build())
+    }
+
+    /// Name of the chunk at a grid position, as `x0-x1_y0-y1_z0-z1`.
+    fn chunk_name(&self, array_meta: &ArrayMetadata, grid_position: &[u64]) -> String {
+        let extents = chunk_extents(array_meta, grid_position);
+        (0..3)
+            .map(|d| {
+                let start = grid_position[d] * u64::from(array_meta.get_chunk_shape()[d]);
+                let offset = self.voxel_offset[d];
+                format!(
+                    "{}-{}",
+                    offset + start as i64,
+                    offset + (start + extents[d]) as i64
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    /// Parse a chunk name into a grid position, if it is aligned to the grid.
+    fn parse_chunk_name(&self, array_meta: &ArrayMetadata, name: &str) -> Option<GridCoord> {
+        let mut grid_position = GridCoord::new();
+        let mut ranges = name.split('_');
+        for d in 0..3 {
+            let (start, _) = ranges.next()?.split_once('-')?;
+            let start = start.parse::<i64>().ok()? - self.voxel_offset[d];
+            let chunk = i64::from(array_meta.get_chunk_shape()[d]);
+            if start < 0 || start % chunk != 0 {
+                return None;
+            }
+            grid_position.push((start / chunk) as u64);
+        }
+        if ranges.next().is_some() {
+            return None;
+        }
+        grid_position.push(0);
+        Some(grid_position)
+    }
+}
+
+/// Map a precomputed data type name to a little-endian data type.
+fn parse_data_type(name: &str) -> Option<DataType> {
+    let endian = Endian::Little;
+    Some(match name {
+        "uint8" => DataType::UInt {
+            size: IntSize::B1,
+            endian,
+        },
+        "uint16" => DataType::UInt {
+            size: IntSize::B2,
+            endian,
+        },
+        "uint32" => DataType::UInt {
+            size: IntSize::B4,
+            endian,
+        },
+        "uint64" => DataType::UInt {
+            size: IntSize::B8,
+            endian,
+        },
+        "int8" => DataType::Int {
+            size: IntSize::B1,
+            endian,
+        },
+        "int16" => DataType::Int {
+            size: IntSize::B2,
+            endian,
+        },
+        "int32" => DataType::Int {
+            size: IntSize::B4,
+            endian,
+        },
+        "int64" => DataType::Int {
+            size: IntSize::B8,
+            endian,
+        },
+        "float32" => DataType::Float {
+            size: FloatSize::B4,
+            endian,
+        },
+        _ => return None,
+    })
+}
+
+/// Map a data type to its precomputed name, if precomputed supports it.
+#[cfg(feature = "use_ndarray")]
+fn data_type_name(data_type: &DataType) -> Option<&'static str> {
+    Some(match data_type {
+        DataType::UInt {
+            size: IntSize::B1, ..
+        } => "uint8",
+        DataType::UInt {
+            size: IntSize::B2, ..
+        } => "uint16",
+        DataType::UInt {
+            size: IntSize::B4, ..
+        } => "uint32",
+        DataType::UInt {
+            size: IntSize::B8, ..
+        } => "uint64",
+        DataType::Int {
+            size: IntSize::B1, ..
+        } => "int8",
+        DataType::Int {
+            size: IntSize::B2, ..
+        } => "int16",
+        DataType::Int {
+            size: IntSize::B4, ..
+        } => "int32",
+        DataType::Int {
+            size: IntSize::B8, ..
+        } => "int64",
+        DataType::Float {
+            size: FloatSize::B4,
+            ..
+        } => "float32",
+        _ => return None,
+    })
+}
+
+/// Shape of the stored part of a chunk, which is truncated at the upper
+/// bounds of the volume rather than padded.
+fn chunk_extents(array_meta: &ArrayMetadata, grid_position: &[u64]) -> GridCoord {
+    array_meta
+        .get_shape()
+        .iter()
+        .zip(array_meta.get_chunk_shape())
+        .zip(grid_position)
+        .map(|((&dim, &chunk), &pos)| {
+            let start = pos * u64::from(chunk);
+            std::cmp::min(u64::from(chunk), dim.saturating_sub(start))
+        })
+        .collect()
+}
+
+/// Pad column-major data of a truncated chunk to the full chunk shape.
+fn pad_column_major<T: Clone + Default>(
+    data: &[T],
+    extents: &[u64],
+    chunk_shape: &[u32],
+) -> Vec<T> {
+    let mut padded = vec![T::default(); chunk_shape.iter().map(|&s| s as usize).product()];
+    let row = extents[0] as usize;
+    if row == 0 {
+        return padded;
+    }
+    for (i, run) in data.chunks(row).enumerate() {
+        let mut rest = i;
+        let mut offset = 0;
+        let mut stride = chunk_shape[0] as usize;
+        for d in 1..extents.len() {
+            offset += (rest % extents[d] as usize) * stride;
+            rest /= extents[d] as usize;
+            stride *= chunk_shape[d] as usize;
+        }
+        padded[offset..offset + row].clone_from_slice(run);
+    }
+    padded
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// A Neuroglancer precomputed volume in a store, read as a hierarchy with
+/// one array per scale.
+#[derive(Clone, Debug)]
+pub struct PrecomputedVolume<S> {
+    store: S,
+    prefix: String,
+    info: PrecomputedInfo,
+    data_type: DataType,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl<S: ReadableStore> PrecomputedVolume<S> {
+    /// Open the volume whose `info` is at a key prefix of a store.
+    pub fn open(store: S, prefix: &str) -> Result<Self, Error> {
+        let info_reader = store
+            .get(&join_key(prefix, INFO_KEY))?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Precomputed info does not exist"))?;
+        let info: PrecomputedInfo = serde_json::from_reader(info_reader)?;
+        let data_type = parse_data_type(&info.data_type).ok_or_else(|| {
+            Error::other(format!(
+                "Unsupported precomputed data type: {}",
+                info.data_type
+            ))
+        })?;
+
+        Ok(PrecomputedVolume {
+            store,
+            prefix: prefix.to_owned(),
+            info,
+            data_type,
+            entry_point_metadata: EntryPointMetadata::default(),
+        })
+    }
+
+    pub fn get_info(&self) -> &PrecomputedInfo {
+        &self.info
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    fn get_scale(&self, path_name: &str) -> Result<&PrecomputedScale, Error> {
+        self.info
+            .get_scale(crate::canonicalize_path(path_name))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Precomputed scale does not exist"))
+    }
+
+    fn chunk_key(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<String, Error> {
+        let scale = self.get_scale(path_name)?;
+        Ok(join_key(
+            &join_key(&self.prefix, &scale.key),
+            &scale.chunk_name(array_meta, grid_position),
+        ))
+    }
+}
+
+impl<S> Hierarchy for PrecomputedVolume<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl<S: ReadableStore> HierarchyReader for PrecomputedVolume<S> {
+    fn get_version(&self) -> Result<VersionReq, Error> {
+        Ok(VersionReq::exact(&crate::VERSION))
+    }
+
+    fn get_array_metadata(&self, path_name: &str) -> Result<ArrayMetadata, Error> {
+        self.get_scale(path_name)?
+            .array_metadata(self.data_type, self.info.num_channels)
+    }
+
+    fn get_group_metadata(&self, path_name: &str) -> Result<GroupMetadata, Error> {
+        if crate::canonicalize_path(path_name).is_empty() {
+            Ok(GroupMetadata::default())
+        } else {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
+
+    fn exists(&self, path_name: &str) -> Result<bool, Error> {
+        Ok(crate::canonicalize_path(path_name).is_empty() || self.get_scale(path_name).is_ok())
+    }
+
+    fn get_chunk_uri(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<String, Error> {
+        self.store
+            .uri(&self.chunk_key(path_name, array_meta, grid_position)?)
+    }
+
+    fn read_chunk<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: GridCoord,
+    ) -> Result<Option<VecDataChunk<T>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        let mut chunk = T::create_data_chunk(&grid_position, 0);
+        Ok(self
+            .read_chunk_into(path_name, array_meta, grid_position, &mut chunk)?
+            .map(|()| chunk))
+    }
+
+    fn read_chunk_into<
+        T: ReflectedType,
+        B: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+    >(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: GridCoord,
+        chunk: &mut B,
+    ) -> Result<Option<()>, Error> {
+        if !self.data_type.eq_modulo_endian(&T::ZARR_TYPE) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Attempt to create data chunk for wrong type.",
+            ));
+        }
+        if !array_meta.in_bounds(&grid_position) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Chunk grid position is out of bounds",
+            ));
+        }
+
+        let chunk_key = self.chunk_key(path_name, array_meta, &grid_position)?;
+        let reader = match self.store.get(&chunk_key)? {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+
+        let extents = chunk_extents(array_meta, &grid_position);
+        chunk.reinitialize(&grid_position, extents.iter().product::<u64>() as u32);
+        chunk.read_data(reader, array_meta)?;
+
+        let chunk_shape = array_meta.get_chunk_shape();
+        if extents
+            .iter()
+            .zip(chunk_shape)
+            .any(|(&e, &s)| e != u64::from(s))
+        {
+            let padded = pad_column_major(chunk.get_data(), &extents, chunk_shape);
+            chunk.reinitialize_with(&SliceDataChunk::new(grid_position, padded));
+        }
+
+        Ok(Some(()))
+    }
+
+    fn chunk_exists(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<bool, Error> {
+        self.store
+            .exists(&self.chunk_key(path_name, array_meta, grid_position)?)
+    }
+
+    fn store_chunk_metadata(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<Option<StoreNodeMetadata>, Error> {
+        let chunk_key = self.chunk_key(path_name, array_meta, grid_position)?;
+        Ok(self.store.stat(&chunk_key)?.map(|stat| StoreNodeMetadata {
+            created: None,
+            accessed: None,
+            modified: stat.last_modified,
+            size: Some(stat.size),
+        }))
+    }
+
+    fn list_attributes(&self, path_name: &str) -> Result<JsonObject, Error> {
+        let value = if crate::canonicalize_path(path_name).is_empty() {
+            serde_json::json!({
+                "type": self.info.volume_type,
+                "num_channels": self.info.num_channels,
+            })
+        } else {
+            let scale = self.get_scale(path_name)?;
+            serde_json::json!({
+                "resolution": scale.resolution,
+                "voxel_offset": scale.voxel_offset,
+            })
+        };
+        match value {
+            serde_json::Value::Object(map) => Ok(map),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<S: ReadableStore + ListableStore> HierarchyLister for PrecomputedVolume<S> {
+    fn list_nodes(&self, prefix_path: &str) -> Result<Vec<String>, Error> {
+        if crate::canonicalize_path(prefix_path).is_empty() {
+            Ok(self
+                .info
+                .scales
+                .iter()
+                .map(|scale| scale.key.clone())
+                .collect())
+        } else {
+            self.get_scale(prefix_path).map(|_| vec![])
+        }
+    }
+
+    fn initialized_chunks(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Vec<GridCoord>, Error> {
+        let scale = self.get_scale(path_name)?;
+        let scale_prefix = join_key(&self.prefix, &scale.key) + "/";
+        let keys = match self.store.list_dir(&scale_prefix) {
+            Ok((keys, _)) => keys,
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+
+        let mut positions: Vec<GridCoord> = keys
+            .iter()
+            .filter_map(|key| key.strip_prefix(&scale_prefix))
+            .filter_map(|name| scale.parse_chunk_name(array_meta, name))
+            .filter(|position| array_meta.in_bounds(position))
+            .collect();
+        positions.sort();
+        Ok(positions)
+    }
+}
+
+#[cfg(feature = "use_ndarray")]
+mod export {
+    use super::*;
+
+    use crate::chunk::WriteableDataChunk;
+    use crate::ndarray::{
+        BoundingBox,
+        ZarrNdarrayReader,
+    };
+    use crate::storage::WriteableStore;
+    use half::f16;
+
+    /// Export an array to a new precomputed volume with a single scale at a
+    /// key prefix of a store, returning the volume's metadata.
+    ///
+    /// The array must have shape `[x, y, z]` or `[x, y, z, channel]` and a
+    /// data type supported by precomputed volumes. Chunks of the volume match
+    /// the spatial chunks of the array and include all channels. Chunks
+    /// absent from the array are not written.
+    pub fn export_precomputed<H: ZarrNdarrayReader, S: WriteableStore>(
+        h: &H,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        store: &S,
+        prefix: &str,
+        volume_type: VolumeType,
+        resolution: [f64; 3],
+    ) -> Result<PrecomputedInfo, Error> {
+        let data_type = array_meta.get_data_type().effective_type()?;
+        let type_name = data_type_name(&data_type).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "Data type is not supported by precomputed volumes",
+            )
+        })?;
+        let num_channels = match array_meta.get_shape() {
+            [_, _, _] => 1,
+            [_, _, _, channels] => *channels,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Precomputed volumes must have 3 or 4 dimensions",
+                ))
+            }
+        };
+
+        let shape = array_meta.get_shape();
+        let chunk_shape = array_meta.get_chunk_shape();
+        let scale = PrecomputedScale {
+            key: resolution
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join("_"),
+            size: [shape[0], shape[1], shape[2]],
+            resolution,
+            voxel_offset: [0; 3],
+            chunk_sizes: vec![[chunk_shape[0], chunk_shape[1], chunk_shape[2]]],
+            encoding: RAW_ENCODING.to_owned(),
+            extra_fields: JsonObject::new(),
+        };
+        let mut extra_fields = JsonObject::new();
+        extra_fields.insert("@type".to_owned(), "neuroglancer_multiscale_volume".into());
+        let info = PrecomputedInfo {
+            volume_type,
+            data_type: type_name.to_owned(),
+            num_channels,
+            scales: vec![scale],
+            extra_fields,
+        };
+
+        store.set(&join_key(prefix, INFO_KEY), |writer| {
+            Ok(serde_json::to_writer(writer, &info)?)
+        })?;
+
+        let scale_meta = info.scales[0].array_metadata(
+            parse_data_type(type_name).expect("Precomputed data type names are parseable"),
+            num_channels,
+        )?;
+        data_type_match!(
+            data_type,
+            DataType::Raw { .. } => unreachable!(),
+            export_chunks::<RsType, _, _>(h, path_name, array_meta, store, prefix, &info.scales[0], &scale_meta)?
+        );
+
+        Ok(info)
+    }
+
+    fn export_chunks<T, H: ZarrNdarrayReader, S: WriteableStore>(
+        h: &H,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        store: &S,
+        prefix: &str,
+        scale: &PrecomputedScale,
+        scale_meta: &ArrayMetadata,
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+        T: ReflectedType,
+    {
+        let scale_prefix = join_key(prefix, &scale.key);
+        for coord in array_meta.coord_iter() {
+            if coord[3..].iter().any(|&c| c != 0)
+                || !h.chunk_exists(path_name, array_meta, &coord)?
+            {
+                continue;
+            }
+
+            let mut grid_position: GridCoord = coord[..3].into();
+            grid_position.push(0);
+            let extents = chunk_extents(scale_meta, &grid_position);
+            let offset = grid_position
+                .iter()
+                .zip(scale_meta.get_chunk_shape())
+                .map(|(&pos, &chunk)| pos * u64::from(chunk))
+                .take(array_meta.get_ndim())
+                .collect();
+            let bbox = BoundingBox::new(offset, extents[..array_meta.get_ndim()].into());
+
+            // Reversing the axes makes the logical order column-major.
+            let data: Vec<T> = h
+                .read_ndarray::<T>(path_name, array_meta, &bbox)?
+                .t()
+                .iter()
+                .cloned()
+                .collect();
+            let chunk = SliceDataChunk::new(grid_position.clone(), data);
+            let chunk_key = join_key(&scale_prefix, &scale.chunk_name(scale_meta, &grid_position));
+            store.set(&chunk_key, |writer| chunk.write_data(writer, scale_meta))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "use_ndarray")]
+pub use export::export_precomputed;
+
+#[cfg(feature = "use_ndarray")]
+mod multiscale {
+    use super::*;
+
+    use crate::ndarray::{
+        BoundingBox,
+        ZarrNdarrayReader,
+    };
+
+    /// A region read from the scale of a volume best matching a voxel size.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct ScaledRegion<T> {
+        /// Key of the scale the region was read from.
+        pub scale: String,
+        /// Bounds of the region in voxels of that scale.
+        pub bbox: BoundingBox,
+        pub data: ndarray::Array<T, ndarray::Dim<ndarray::IxDynImpl>>,
+    }
+
+    impl PrecomputedScale {
+        /// Bounds in voxels of this scale covering a region in voxels of
+        /// another scale, clamped to the extent of this scale. The channel
+        /// axis is unchanged.
+        pub fn scale_region(&self, from: &PrecomputedScale, region: &BoundingBox) -> BoundingBox {
+            let mut offset = GridCoord::new();
+            let mut shape = GridCoord::new();
+            for d in 0..3 {
+                let to_self = |voxel: u64| {
+                    (voxel as f64 + from.voxel_offset[d] as f64) * from.resolution[d]
+                        / self.resolution[d]
+                        - self.voxel_offset[d] as f64
+                };
+                let clamp = |voxel: f64| voxel.max(0.0).min(self.size[d] as f64) as u64;
+                let start = clamp((to_self(region.offset()[d]) + RESOLUTION_TOLERANCE).floor());
+                let end = clamp(
+                    (to_self(region.offset()[d] + region.shape()[d]) - RESOLUTION_TOLERANCE).ceil(),
+                );
+                offset.push(start);
+                shape.push(end.saturating_sub(start));
+            }
+            offset.extend_from_slice(&region.offset()[3..]);
+            shape.extend_from_slice(&region.shape()[3..]);
+            BoundingBox::new(offset, shape)
+        }
+    }
+
+    impl<S: ReadableStore> PrecomputedVolume<S> {
+        /// Read a region at the scale selected for a target voxel size by
+        /// [`PrecomputedInfo::select_scale`].
+        ///
+        /// The region is in voxels of the first scale of the volume,
+        /// conventionally the finest, with shape `[x, y, z, channel]`, and is
+        /// scaled to the voxels of the selected scale.
+        pub fn read_at_resolution<T>(
+            &self,
+            region: &BoundingBox,
+            target_resolution: [f64; 3],
+        ) -> Result<ScaledRegion<T>, Error>
+        where
+            VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+            T: ReflectedType,
+        {
+            if region.offset().len() != 4 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Regions of precomputed volumes must have 4 dimensions",
+                ));
+            }
+            let (base, scale) = match (
+                self.info.scales.first(),
+                self.info.select_scale(target_resolution),
+            ) {
+                (Some(base), Some(scale)) => (base, scale),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        "Precomputed volume has no scales",
+                    ))
+                }
+            };
+
+            let bbox = scale.scale_region(base, region);
+            let array_meta = self.get_array_metadata(&scale.key)?;
+            let data = self.read_ndarray(&scale.key, &array_meta, &bbox)?;
+            Ok(ScaledRegion {
+                scale: scale.key.clone(),
+                bbox,
+                data,
+            })
+        }
+    }
+}
+
+#[cfg(feature = "use_ndarray")]
+pub use multiscale::ScaledRegion;
+
+#[cfg(all(test, feature = "filesystem", feature = "use_ndarray"))]
+mod tests {
+    use super::*;
+    use crate::ndarray::prelude::*;
+    use crate::prelude::*;
+    use crate::storage::WriteableStore;
+
+    #[test]
+    fn test_export_and_read() {
+        let src_dir = tempdir::TempDir::new("rust_zarr_precomputed_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(src_dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![10, 7, 5], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![4, 4, 3])
+            .compressor(CompressionType::default())
+            .build();
+        h.create_array("em", &array_meta).unwrap();
+        let data = ndarray::Array::from_shape_vec(vec![10, 7, 5], (0..350).collect::<Vec<u16>>())
+            .unwrap()
+            .into_dyn();
+        h.write_ndarray("em", &array_meta, smallvec![0, 0, 0], &data)
+            .unwrap();
+
+        let dst_dir = tempdir::TempDir::new("rust_zarr_precomputed_tests").unwrap();
+        let store = FilesystemHierarchy::open_or_create(dst_dir.path()).unwrap();
+        let info = export_precomputed(
+            &h,
+            "em",
+            &array_meta,
+            &store,
+            "vol",
+            VolumeType::Image,
+            [8.0, 8.0, 40.0],
+        )
+        .unwrap();
+        assert_eq!(info.scales[0].key, "8_8_40");
+        assert_eq!(info.scales[0].chunk_sizes, vec![[4, 4, 3]]);
+        // Edge chunks are truncated rather than padded.
+        assert_eq!(
+            store.size("vol/8_8_40/8-10_4-7_3-5").unwrap(),
+            Some(2 * 3 * 2 * 2)
+        );
+
+        let volume = PrecomputedVolume::open(store, "vol").unwrap();
+        assert_eq!(volume.get_info(), &info);
+        assert_eq!(volume.list_nodes("").unwrap(), vec!["8_8_40"]);
+        let scale_meta = volume.get_array_metadata("8_8_40").unwrap();
+        assert_eq!(scale_meta.get_shape(), &[10, 7, 5, 1]);
+        assert_eq!(
+            volume
+                .initialized_chunks("8_8_40", &scale_meta)
+                .unwrap()
+                .len(),
+            12
+        );
+        let edge_chunk = volume
+            .store_chunk_metadata("8_8_40", &scale_meta, &[2, 1, 1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(edge_chunk.size, Some(2 * 3 * 2 * 2));
+        assert!(edge_chunk.modified.is_some());
+        assert!(volume
+            .store_chunk_metadata("8_8_40", &scale_meta, &[3, 0, 0, 0])
+            .unwrap()
+            .is_none());
+
+        let read = volume
+            .read_ndarray::<u16>("8_8_40", &scale_meta, &scale_meta.get_bounds())
+            .unwrap();
+        assert_eq!(read, data.insert_axis(ndarray::Axis(3)));
+    }
+
+    #[test]
+    fn test_read_at_resolution() {
+        let dir = tempdir::TempDir::new("rust_zarr_precomputed_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path().join("src")).unwrap();
+        let store = FilesystemHierarchy::open_or_create(dir.path().join("dst")).unwrap();
+        let mut infos = vec![];
+        for (i, &(width, resolution)) in [(8, 8.0), (4, 16.0)].iter().enumerate() {
+            let array_meta = ArrayMetadataBuilder::new(smallvec![width, width, 2], u8::ZARR_TYPE)
+                .chunk_shape(smallvec![2, 2, 2])
+                .build();
+            let path = format!("s{}", i);
+            h.create_array(&path, &array_meta).unwrap();
+            let data = ndarray::Array::from_elem(vec![width as usize, width as usize, 2], i as u8);
+            h.write_ndarray(&path, &array_meta, smallvec![0, 0, 0], &data.into_dyn())
+                .unwrap();
+            infos.push(
+                export_precomputed(
+                    &h,
+                    &path,
+                    &array_meta,
+                    &store,
+                    "vol",
+                    VolumeType::Image,
+                    [resolution, resolution, 40.0],
+                )
+                .unwrap(),
+            );
+        }
+        let mut info = infos[0].clone();
+        info.scales.push(infos[1].scales[0].clone());
+        store
+            .set("vol/info", |writer| {
+                Ok(serde_json::to_writer(writer, &info)?)
+            })
+            .unwrap();
+        let volume = PrecomputedVolume::open(store, "vol").unwrap();
+
+        let key = |target| volume.get_info().select_scale(target).unwrap().key.as_str();
+        assert_eq!(key([16.0, 16.0, 40.0]), "16_16_40");
+        assert_eq!(key([100.0, 100.0, 100.0]), "16_16_40");
+        assert_eq!(key([10.0, 10.0, 40.0]), "8_8_40");
+        assert_eq!(key([4.0, 4.0, 40.0]), "8_8_40");
+
+        let region = BoundingBox::new(smallvec![3, 2, 0, 0], smallvec![4, 6, 2, 1]);
+        let read = volume
+            .read_at_resolution::<u8>(&region, [20.0, 20.0, 40.0])
+            .unwrap();
+        assert_eq!(read.scale, "16_16_40");
+        assert_eq!(
+            read.bbox,
+            BoundingBox::new(smallvec![1, 1, 0, 0], smallvec![3, 3, 2, 1])
+        );
+        assert_eq!(read.data.shape(), &[3, 3, 2, 1]);
+        assert!(read.data.iter().all(|&v| v == 1));
+
+        let read = volume
+            .read_at_resolution::<u8>(&region, [8.0, 8.0, 40.0])
+            .unwrap();
+        assert_eq!(read.scale, "8_8_40");
+        assert_eq!(read.bbox, region);
+        assert!(read.data.iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let info: PrecomputedInfo = serde_json::from_value(serde_json::json!({
+            "@type": "neuroglancer_multiscale_volume",
+            "type": "segmentation",
+            "data_type": "uint64",
+            "num_channels": 1,
+            "mesh": "mesh",
+            "scales": [{
+                "key": "4_4_40",
+                "size": [100, 200, 30],
+                "resolution": [4, 4, 40],
+                "voxel_offset": [10, 20, 0],
+                "chunk_sizes": [[64, 64, 16]],
+                "encoding": "raw",
+            }],
+        }))
+        .unwrap();
+        assert_eq!(info.volume_type, VolumeType::Segmentation);
+        assert_eq!(info.extra_fields["mesh"], "mesh");
+
+        let scale = info.get_scale("4_4_40").unwrap();
+        let array_meta = scale
+            .array_metadata(parse_data_type(&info.data_type).unwrap(), 1)
+            .unwrap();
+        assert_eq!(array_meta.get_chunk_shape(), &[64, 64, 16, 1]);
+
+        let name = scale.chunk_name(&array_meta, &[1, 3, 1, 0]);
+        assert_eq!(name, "74-110_212-220_16-30");
+        assert_eq!(
+            scale.parse_chunk_name(&array_meta, &name),
+            Some(smallvec![1, 3, 1, 0])
+        );
+        assert_eq!(
+            scale.parse_chunk_name(&array_meta, "75-110_212-220_16-30"),
+            None
+        );
+    }
+}
@@ -14,10 +14,17 @@ pub use crate::compression::{
     self,
     CompressionType,
 };
+#[doc(no_inline)]
+pub use crate::filter::{
+    self,
+    FilterType,
+};
 #[cfg(feature = "filesystem")]
 #[doc(no_inline)]
 pub use crate::store::filesystem::FilesystemHierarchy;
 #[doc(no_inline)]
+pub use crate::store::read_only::ReadOnly;
+#[doc(no_inline)]
 pub use crate::{
     chunk::{
         DataChunk,
@@ -25,12 +32,14 @@ pub use crate::{
         VecDataChunk,
     },
     ArrayMetadata,
+    ArrayMetadataBuilder,
     ChunkCoord,
     DataType,
     GridCoord,
     HierarchyLister,
     HierarchyReader,
     HierarchyWriter,
+    NodeKind,
     ReflectedType,
     StoreNodeMetadata,
 };
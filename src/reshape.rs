@@ -0,0 +1,660 @@
+//! Reshaping arrays, following the row-major (C order) element order of
+//! numpy's and zarr-python's `reshape`.
+//!
+//! A [`ReshapedArray`] is a view of an array in another shape, which reads
+//! the corresponding elements of the array without moving any data. An
+//! array's metadata can be rewritten to the new shape with
+//! [`ZarrReshapeWriter::reshape_in_place`] when its chunks hold the same
+//! elements in both shapes, and otherwise its elements can be copied to an
+//! array of the new shape with [`ZarrReshapeWriter::reshape_into`].
+//!
+//! An [`AxesView`] drops or inserts axes of length 1, like numpy's `squeeze`
+//! and `expand_dims`, for both reads and writes.
+//!
+//! ```no_run
+//! use zarr::ndarray::BoundingBox;
+//! use zarr::prelude::*;
+//! use zarr::reshape::ZarrReshapeReader;
+//! use zarr::smallvec::smallvec;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/images.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("stack").unwrap();
+//! // View a stack of 100 images of 64 x 64 as a 100 x 4096 matrix.
+//! let matrix = h.reshape("stack", &array_meta, smallvec![100, 4096]).unwrap();
+//! let rows = matrix
+//!     .read_ndarray::<u8>(&BoundingBox::new(smallvec![10, 0], smallvec![2, 4096]))
+//!     .unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use half::f16;
+use ndarray::{
+    ArrayD,
+    ArrayViewD,
+    Axis,
+    IxDyn,
+};
+
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+    ZarrNdarrayWriter,
+};
+use crate::storage::WriteableStore;
+use crate::{
+    ArrayMetadata,
+    ChunkCoord,
+    DataChunk,
+    DataType,
+    FloatSize,
+    GridCoord,
+    HierarchyReader,
+    HierarchyWriter,
+    IntSize,
+    Order,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+
+/// View of an array in another shape with the same number of elements.
+#[derive(Debug)]
+pub struct ReshapedArray<'a, H: ?Sized> {
+    hierarchy: &'a H,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    shape: GridCoord,
+}
+
+impl<'a, H: HierarchyReader> ReshapedArray<'a, H> {
+    pub fn get_shape(&self) -> &[u64] {
+        &self.shape
+    }
+
+    pub fn get_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            GridCoord::from(vec![0; self.shape.len()]),
+            self.shape.clone(),
+        )
+    }
+
+    /// Read a bounding box of the view.
+    ///
+    /// Each run of elements contiguous in row-major order in both shapes is
+    /// read from the array separately, so boxes spanning whole trailing
+    /// dimensions of the view read most efficiently.
+    pub fn read_ndarray<T>(&self, bbox: &BoundingBox) -> Result<ArrayD<T>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        if bbox.offset().len() != self.shape.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong number of dimensions",
+            ));
+        }
+        if bbox
+            .end()
+            .zip(self.shape.iter())
+            .any(|(end, extent)| end > *extent)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Bounding box is outside the reshaped array",
+            ));
+        }
+
+        let mut values = Vec::with_capacity(bbox.shape().iter().product::<u64>() as usize);
+        for (start, end) in linear_runs(&self.shape, bbox) {
+            for source_bbox in linear_boxes(self.array_meta.get_shape(), start, end) {
+                let source = self.hierarchy.read_ndarray::<T>(
+                    &self.path_name,
+                    &self.array_meta,
+                    &source_bbox,
+                )?;
+                values.extend(source.iter().cloned());
+            }
+        }
+        ArrayD::from_shape_vec(IxDyn(&bbox.shape_ndarray_shape()), values)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// View of an array with axes of length 1 dropped or inserted, which maps
+/// reads and writes to the array without copying elements.
+///
+/// Views are built from [`ZarrReshapeReader::axes`], for example
+/// `h.axes("ome", &array_meta).squeeze()` views a `[1, 1, 1, y, x]` array as
+/// a `[y, x]` plane.
+#[derive(Clone, Debug)]
+pub struct AxesView<'a, H: ?Sized> {
+    hierarchy: &'a H,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    /// Array axis of each view axis, or `None` for inserted axes.
+    axes: Vec<Option<usize>>,
+}
+
+impl<'a, H: HierarchyReader> AxesView<'a, H> {
+    pub fn get_shape(&self) -> GridCoord {
+        self.axes
+            .iter()
+            .map(|axis| axis.map_or(1, |a| self.array_meta.get_shape()[a]))
+            .collect()
+    }
+
+    pub fn get_bounds(&self) -> BoundingBox {
+        BoundingBox::new(GridCoord::from(vec![0; self.axes.len()]), self.get_shape())
+    }
+
+    /// Drop all axes of length 1.
+    pub fn squeeze(mut self) -> Self {
+        let shape = self.array_meta.get_shape();
+        self.axes.retain(|axis| axis.is_some_and(|a| shape[a] != 1));
+        self
+    }
+
+    /// Drop an axis of the view, which must have length 1.
+    pub fn squeeze_axis(mut self, axis: usize) -> Result<Self, Error> {
+        if self.get_shape().get(axis) != Some(&1) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Only axes of length 1 can be squeezed",
+            ));
+        }
+        self.axes.remove(axis);
+        Ok(self)
+    }
+
+    /// Insert an axis of length 1 at a position in the view.
+    pub fn expand_dims(mut self, axis: usize) -> Result<Self, Error> {
+        if axis > self.axes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Axis is beyond the dimensions of the view",
+            ));
+        }
+        self.axes.insert(axis, None);
+        Ok(self)
+    }
+
+    /// Bounding box in the array of a bounding box in the view.
+    fn array_bbox(&self, bbox: &BoundingBox) -> Result<BoundingBox, Error> {
+        if bbox.offset().len() != self.axes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong number of dimensions",
+            ));
+        }
+        if bbox
+            .end()
+            .zip(self.get_shape().iter())
+            .any(|(end, extent)| end > *extent)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Bounding box is outside the view",
+            ));
+        }
+
+        let ndim = self.array_meta.get_ndim();
+        let mut offset = GridCoord::from(vec![0; ndim]);
+        let mut shape = GridCoord::from(vec![1; ndim]);
+        for (v, axis) in self.axes.iter().enumerate() {
+            if let Some(a) = *axis {
+                offset[a] = bbox.offset()[v];
+                shape[a] = bbox.shape()[v];
+            }
+        }
+        Ok(BoundingBox::new(offset, shape))
+    }
+
+    fn is_dropped(&self, array_axis: usize) -> bool {
+        !self.axes.contains(&Some(array_axis))
+    }
+
+    /// Read a bounding box of the view.
+    pub fn read_ndarray<T>(&self, bbox: &BoundingBox) -> Result<ArrayD<T>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        let array_bbox = self.array_bbox(bbox)?;
+        let mut array =
+            self.hierarchy
+                .read_ndarray::<T>(&self.path_name, &self.array_meta, &array_bbox)?;
+        for a in (0..self.array_meta.get_ndim()).rev() {
+            if self.is_dropped(a) {
+                array = array.index_axis_move(Axis(a), 0);
+            }
+        }
+        for (v, axis) in self.axes.iter().enumerate() {
+            if axis.is_none() {
+                array = array.insert_axis(Axis(v));
+            }
+        }
+        Ok(array)
+    }
+}
+
+impl<'a, H: HierarchyWriter> AxesView<'a, H> {
+    /// Write an ndarray of the view's dimensions at an offset in the view.
+    pub fn write_ndarray<'b, T, A>(&self, offset: GridCoord, array: A) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+        T: ReflectedType,
+        A: ndarray::AsArray<'b, T, IxDyn>,
+    {
+        let mut array: ArrayViewD<'b, T> = array.into();
+        if array.ndim() != self.axes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong number of dimensions",
+            ));
+        }
+        let bbox = BoundingBox::new(offset, array.shape().iter().map(|&n| n as u64).collect());
+        let array_bbox = self.array_bbox(&bbox)?;
+        if bbox.is_empty() {
+            return Ok(());
+        }
+
+        for (v, axis) in self.axes.iter().enumerate().rev() {
+            if axis.is_none() {
+                array = array.index_axis_move(Axis(v), 0);
+            }
+        }
+        for a in 0..self.array_meta.get_ndim() {
+            if self.is_dropped(a) {
+                array = array.insert_axis(Axis(a));
+            }
+        }
+        self.hierarchy.write_ndarray(
+            &self.path_name,
+            &self.array_meta,
+            GridCoord::from(array_bbox.offset()),
+            array,
+        )
+    }
+}
+
+pub trait ZarrReshapeReader: HierarchyReader {
+    /// View an array in another shape with the same number of elements.
+    fn reshape(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        shape: GridCoord,
+    ) -> Result<ReshapedArray<'_, Self>, Error> {
+        check_num_elements(array_meta, &shape)?;
+        Ok(ReshapedArray {
+            hierarchy: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            shape,
+        })
+    }
+
+    /// View an array with all its axes, to squeeze or expand.
+    fn axes(&self, path_name: &str, array_meta: &ArrayMetadata) -> AxesView<'_, Self> {
+        AxesView {
+            hierarchy: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            axes: (0..array_meta.get_ndim()).map(Some).collect(),
+        }
+    }
+}
+
+impl<T: HierarchyReader> ZarrReshapeReader for T {}
+
+pub trait ZarrReshapeWriter: ZarrNdarrayWriter + WriteableStore {
+    /// Change the shape of an array by rewriting only its metadata.
+    ///
+    /// This is possible when the chunks of both shapes hold the same runs of
+    /// elements in row-major order under the same keys (see
+    /// [`ArrayMetadata::reshape_metadata`]); other reshapes fail with
+    /// [`ErrorKind::InvalidInput`] and need [`reshape_into`](Self::reshape_into).
+    fn reshape_in_place(&self, path_name: &str, shape: GridCoord) -> Result<ArrayMetadata, Error> {
+        let array_meta = self.get_array_metadata(path_name)?;
+        if array_meta.is_read_only() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Array is read-only",
+            ));
+        }
+        check_num_elements(&array_meta, &shape)?;
+        let reshaped = array_meta.reshape_metadata(shape).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "Reshape needs the array's chunks to be rewritten",
+            )
+        })?;
+        if reshaped != array_meta {
+            let metadata_key = self.array_metadata_key(path_name);
+            self.set(metadata_key.to_str().expect("TODO"), |writer| {
+                Ok(serde_json::to_writer(writer, &reshaped)?)
+            })?;
+        }
+        Ok(reshaped)
+    }
+
+    /// Copy the elements of an array in row-major order into an existing
+    /// array of another shape, with the same number of elements and data
+    /// type, chunk by chunk of the destination.
+    fn reshape_into(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        dest_path_name: &str,
+        dest_array_meta: &ArrayMetadata,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        if array_meta.get_data_type() != dest_array_meta.get_data_type() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Reshaped array must have the same data type",
+            ));
+        }
+        let view = self.reshape(
+            path_name,
+            array_meta,
+            GridCoord::from(dest_array_meta.get_shape()),
+        )?;
+        data_type_match!(
+            array_meta.get_data_type().effective_type()?,
+            DataType::Raw { .. } => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Arrays of raw data types can not be reshaped",
+            )),
+            copy_reshaped::<RsType, _>(self, &view, dest_path_name, dest_array_meta)
+        )
+    }
+}
+
+impl<T: ZarrNdarrayWriter + WriteableStore> ZarrReshapeWriter for T {}
+
+fn copy_reshaped<T, H: ZarrNdarrayWriter>(
+    hierarchy: &H,
+    view: &ReshapedArray<'_, H>,
+    dest_path_name: &str,
+    dest_array_meta: &ArrayMetadata,
+) -> Result<(), Error>
+where
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+    T: ReflectedType,
+{
+    let bounds = dest_array_meta.get_bounds();
+    for coord in dest_array_meta.coord_iter() {
+        let mut chunk_bbox = dest_array_meta.get_chunk_bounds(&coord);
+        chunk_bbox.intersect(&bounds);
+        if chunk_bbox.is_empty() {
+            continue;
+        }
+        let data = view.read_ndarray::<T>(&chunk_bbox)?;
+        hierarchy.write_ndarray(
+            dest_path_name,
+            dest_array_meta,
+            GridCoord::from(chunk_bbox.offset()),
+            &data,
+        )?;
+    }
+    Ok(())
+}
+
+fn check_num_elements(array_meta: &ArrayMetadata, shape: &[u64]) -> Result<(), Error> {
+    if shape.iter().product::<u64>() == array_meta.get_num_elements() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Reshaped array must have the same number of elements",
+        ))
+    }
+}
+
+impl ArrayMetadata {
+    /// Metadata for this array reshaped to `shape` without rewriting its
+    /// chunks, or `None` if its chunks would not hold the same elements.
+    ///
+    /// This requires the same number of dimensions and chunks along each
+    /// dimension in both shapes, and that chunks in both shapes are runs of
+    /// elements contiguous in row-major order, for example when reshaping
+    /// `[6, 4]` with chunks of `[2, 4]` to `[3, 8]` with chunks of `[1, 8]`.
+    pub fn reshape_metadata(&self, shape: GridCoord) -> Option<ArrayMetadata> {
+        if shape == self.shape {
+            return Some(self.clone());
+        }
+        if shape.len() != self.get_ndim()
+            || shape.iter().product::<u64>() != self.get_num_elements()
+            || !self.has_linear_chunks()
+        {
+            return None;
+        }
+
+        let grid_extent = self.get_grid_extent();
+        let chunk_num_elements = self.get_chunk_num_elements() as u64;
+        (0..shape.len()).find_map(|split| {
+            let tail: u64 = shape[split + 1..].iter().product();
+            if tail == 0 || !chunk_num_elements.is_multiple_of(tail) {
+                return None;
+            }
+            let chunk_shape = std::iter::repeat_n(1, split)
+                .chain(std::iter::once(chunk_num_elements / tail))
+                .chain(shape[split + 1..].iter().cloned())
+                .map(u32::try_from)
+                .collect::<Result<ChunkCoord, _>>()
+                .ok()?;
+
+            let mut reshaped = self.clone();
+            reshaped.shape = shape.clone();
+            reshaped.chunk_grid.chunk_shape = chunk_shape;
+            (reshaped.has_linear_chunks() && reshaped.get_grid_extent() == grid_extent)
+                .then_some(reshaped)
+        })
+    }
+
+    /// Whether each chunk holds the run of elements of the array contiguous
+    /// in row-major order following those of the chunk before it.
+    fn has_linear_chunks(&self) -> bool {
+        let chunk_shape = self.get_chunk_shape();
+        if self.chunk_memory_layout == Order::ColumnMajor
+            && chunk_shape.iter().filter(|&&c| c > 1).count() > 1
+        {
+            return false;
+        }
+
+        // Chunks are whole in all dimensions after some split dimension and
+        // single elements in all dimensions before it.
+        let split = self
+            .shape
+            .iter()
+            .zip(chunk_shape.iter())
+            .rposition(|(&s, &c)| s != u64::from(c))
+            .unwrap_or(0);
+        chunk_shape[..split].iter().all(|&c| c == 1)
+            && (split == 0 || self.shape[split].is_multiple_of(u64::from(chunk_shape[split])))
+    }
+}
+
+/// Runs of elements of a bounding box contiguous in row-major order in an
+/// array of a shape, as ranges of linear indices.
+fn linear_runs(shape: &[u64], bbox: &BoundingBox) -> Vec<(u64, u64)> {
+    let ndim = shape.len();
+    if bbox.is_empty() {
+        return vec![];
+    }
+    if ndim == 0 {
+        return vec![(0, 1)];
+    }
+
+    let strides = row_major_strides(shape);
+    let offset = bbox.offset();
+    let row_len = bbox.shape()[ndim - 1];
+    let mut row = GridCoord::from(offset);
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    loop {
+        let start = row
+            .iter()
+            .zip(strides.iter())
+            .map(|(i, s)| i * s)
+            .sum::<u64>();
+        match runs.last_mut() {
+            Some(last) if last.1 == start => last.1 += row_len,
+            _ => runs.push((start, start + row_len)),
+        }
+
+        // Advance to the next row of the box, outer dimensions slowest.
+        let mut d = ndim - 1;
+        loop {
+            if d == 0 {
+                return runs;
+            }
+            d -= 1;
+            row[d] += 1;
+            if row[d] < offset[d] + bbox.shape()[d] {
+                break;
+            }
+            row[d] = offset[d];
+        }
+    }
+}
+
+/// Decompose a range of linear indices of an array of a shape into as few
+/// bounding boxes as possible, in row-major order.
+fn linear_boxes(shape: &[u64], mut start: u64, end: u64) -> Vec<BoundingBox> {
+    let ndim = shape.len();
+    if ndim == 0 {
+        return if start < end {
+            vec![BoundingBox::new(GridCoord::new(), GridCoord::new())]
+        } else {
+            vec![]
+        };
+    }
+
+    let strides = row_major_strides(shape);
+    let mut boxes = Vec::new();
+    while start < end {
+        // Take whole blocks of the outermost dimension possible.
+        let dim = (0..ndim)
+            .find(|&d| start.is_multiple_of(strides[d]) && strides[d] <= end - start)
+            .expect("Innermost dimension has unit stride");
+        let offset: GridCoord = strides
+            .iter()
+            .zip(shape)
+            .map(|(&stride, &extent)| start / stride % extent)
+            .collect();
+        let count = ((end - start) / strides[dim]).min(shape[dim] - offset[dim]);
+        let box_shape: GridCoord = (0..ndim)
+            .map(|d| match d.cmp(&dim) {
+                std::cmp::Ordering::Less => 1,
+                std::cmp::Ordering::Equal => count,
+                std::cmp::Ordering::Greater => shape[d],
+            })
+            .collect();
+        boxes.push(BoundingBox::new(offset, box_shape));
+        start += count * strides[dim];
+    }
+    boxes
+}
+
+fn row_major_strides(shape: &[u64]) -> GridCoord {
+    let mut strides = GridCoord::from(vec![1; shape.len()]);
+    for d in (0..shape.len().saturating_sub(1)).rev() {
+        strides[d] = strides[d + 1] * shape[d + 1];
+    }
+    strides
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::ArrayMetadataBuilder;
+
+    #[test]
+    fn test_linear_boxes() {
+        let shape = [3, 4, 5];
+        for (start, end) in [(0, 60), (7, 53), (20, 40), (0, 1), (59, 60), (3, 18)] {
+            let boxes = linear_boxes(&shape, start, end);
+            assert!(boxes.len() < 2 * shape.len());
+            let mut next = start;
+            for bbox in boxes {
+                let first: u64 = bbox
+                    .offset()
+                    .iter()
+                    .zip(row_major_strides(&shape).iter())
+                    .map(|(i, s)| i * s)
+                    .sum();
+                assert_eq!(first, next);
+                next += bbox.shape().iter().product::<u64>();
+            }
+            assert_eq!(next, end);
+        }
+        assert_eq!(linear_boxes(&shape, 0, 60).len(), 1);
+    }
+
+    #[test]
+    fn test_linear_runs() {
+        let shape = [3, 4, 5];
+        let rows = BoundingBox::new(smallvec![1, 0, 0], smallvec![2, 4, 5]);
+        assert_eq!(linear_runs(&shape, &rows), vec![(20, 60)]);
+        let columns = BoundingBox::new(smallvec![0, 1, 2], smallvec![2, 2, 2]);
+        assert_eq!(
+            linear_runs(&shape, &columns),
+            vec![(7, 9), (12, 14), (27, 29), (32, 34)]
+        );
+    }
+
+    #[test]
+    fn test_reshape_metadata() {
+        let array_meta = ArrayMetadataBuilder::new(smallvec![6, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .chunk_memory_layout(Order::RowMajor)
+            .build();
+        let reshaped = array_meta.reshape_metadata(smallvec![3, 8]).unwrap();
+        assert_eq!(reshaped.get_shape(), &[3, 8]);
+        assert_eq!(reshaped.get_chunk_shape(), &[1, 8]);
+        // Chunks are not contiguous once split across rows of the new shape.
+        assert_eq!(array_meta.reshape_metadata(smallvec![4, 6]), None);
+        assert_eq!(array_meta.reshape_metadata(smallvec![24]), None);
+        assert_eq!(array_meta.reshape_metadata(smallvec![5, 5]), None);
+        assert_eq!(
+            array_meta.reshape_metadata(smallvec![6, 4]),
+            Some(array_meta.clone())
+        );
+
+        let blocked = ArrayMetadataBuilder::new(smallvec![6, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .chunk_memory_layout(Order::RowMajor)
+            .build();
+        assert_eq!(blocked.reshape_metadata(smallvec![3, 8]), None);
+
+        // Column-major chunks are only contiguous with a single non-unit
+        // dimension.
+        let column_major = ArrayMetadataBuilder::new(smallvec![6, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .chunk_memory_layout(Order::ColumnMajor)
+            .build();
+        assert_eq!(column_major.reshape_metadata(smallvec![3, 8]), None);
+        let rows = ArrayMetadataBuilder::new(smallvec![6, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![1, 4])
+            .chunk_memory_layout(Order::ColumnMajor)
+            .build();
+        assert_eq!(
+            rows.reshape_metadata(smallvec![24, 1])
+                .map(|m| m.get_chunk_shape().to_vec()),
+            Some(vec![4, 1])
+        );
+    }
+}
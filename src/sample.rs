@@ -0,0 +1,135 @@
+//! Reading the elements of an array at scattered points.
+//!
+//! Tracing skeletons or sampling meshes reads a few elements from each of
+//! many chunks. [`ZarrPointSampler::sample_points`] groups points by the
+//! chunk holding them so that each chunk is read once however many points
+//! fall in it.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::sample::ZarrPointSampler;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("labels").unwrap();
+//! let nodes = [[120, 45, 6], [121, 46, 6], [980, 13, 70]];
+//! let labels: Vec<u64> = h.sample_points("labels", &array_meta, &nodes).unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use crate::chunk::{
+    DataChunk,
+    ReadableDataChunk,
+    VecDataChunk,
+};
+use crate::{
+    ArrayMetadata,
+    GridCoord,
+    HierarchyReader,
+    ReflectedType,
+};
+
+pub trait ZarrPointSampler: HierarchyReader {
+    /// Read the elements at points of an array, in the order of the points.
+    ///
+    /// Elements of chunks which do not exist are the array's fill value.
+    /// Fails with [`ErrorKind::InvalidInput`] if a point is outside the
+    /// array.
+    fn sample_points<T, const N: usize>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        points: &[[u64; N]],
+    ) -> Result<Vec<T>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        // Indices into `points` and into its chunk's data of each point,
+        // by chunk.
+        let mut by_chunk: BTreeMap<GridCoord, Vec<(usize, usize)>> = BTreeMap::new();
+        for (i, point) in points.iter().enumerate() {
+            let (grid_position, index) = array_meta.element_location(point).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Point {:?} is outside the array", point),
+                )
+            })?;
+            by_chunk.entry(grid_position).or_default().push((i, index));
+        }
+
+        let fill_value: T = array_meta.get_effective_fill_value()?;
+        let mut values = vec![fill_value; points.len()];
+        for (grid_position, indices) in by_chunk {
+            let chunk = match self.read_chunk::<T>(path_name, array_meta, grid_position)? {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            let data = chunk.get_data();
+            for (i, index) in indices {
+                values[i] = data
+                    .get(index)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidData,
+                            "Chunk has fewer elements than its shape",
+                        )
+                    })?
+                    .clone();
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl<T: HierarchyReader> ZarrPointSampler for T {}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::Order;
+
+    #[test]
+    fn test_sample_points() {
+        let dir = tempdir::TempDir::new("rust_zarr_sample_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .chunk_memory_layout(Order::RowMajor)
+            .fill_value(9)
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        for grid_position in &[[0u64, 0], [1, 1]] {
+            let base = (grid_position[0] * 10 + grid_position[1]) as u32 * 10;
+            let data: Vec<u32> = (0..4).map(|i| base + i).collect();
+            h.write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(grid_position[..].into(), data),
+            )
+            .unwrap();
+        }
+
+        let points = [[3, 2], [0, 1], [2, 0], [1, 0], [3, 3]];
+        let values: Vec<u32> = h.sample_points("a", &array_meta, &points).unwrap();
+        assert_eq!(values, vec![112, 1, 9, 2, 113]);
+
+        assert_eq!(
+            h.sample_points::<u32, 2>("a", &array_meta, &[[0, 4]])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            h.sample_points::<u32, 3>("a", &array_meta, &[[0, 0, 0]])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Scanning arrays for elements matching a predicate.
+//!
+//! [`HierarchyScan::scan_where`] reads an array a chunk at a time and
+//! yields the coordinates and values of matching elements. Predicates which
+//! can judge a chunk by its [stored minimum and maximum](crate::stats) skip
+//! chunks which can not match without decoding them, so sparse queries over
+//! large arrays decode only the chunks holding matches:
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::scan::{
+//!     HierarchyScan,
+//!     Predicate,
+//! };
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("intensity").unwrap();
+//! for element in h
+//!     .scan_where("intensity", &array_meta, Predicate::GreaterThan(1000u16))
+//!     .unwrap()
+//! {
+//!     let (coord, value) = element.unwrap();
+//!     println!("{:?}: {}", coord, value);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io::Error;
+
+use crate::stats::{
+    current_stats,
+    present_chunks,
+    ChunkStats,
+    HierarchyChunkStats,
+};
+use crate::storage::{
+    find_chunk_key,
+    ListableStore,
+    ReadableStore,
+};
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    GridCoord,
+    Hierarchy,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    VecDataChunk,
+};
+
+/// A predicate over elements, which may also rule out chunks by their
+/// stats.
+///
+/// Closures over elements are predicates which never rule out chunks.
+pub trait ScanPredicate<T> {
+    /// Whether an element matches.
+    fn matches(&self, value: &T) -> bool;
+
+    /// Whether a chunk with these stats may hold matching elements. Chunks
+    /// for which this is `false` are not read.
+    fn may_match(&self, _stats: &ChunkStats<T>) -> bool {
+        true
+    }
+}
+
+impl<T, F: Fn(&T) -> bool> ScanPredicate<T> for F {
+    fn matches(&self, value: &T) -> bool {
+        self(value)
+    }
+}
+
+/// Comparisons of elements with constants.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate<T> {
+    Equal(T),
+    GreaterThan(T),
+    LessThan(T),
+    /// Between a minimum and a maximum, inclusive.
+    Between(T, T),
+}
+
+impl<T: PartialOrd> ScanPredicate<T> for Predicate<T> {
+    fn matches(&self, value: &T) -> bool {
+        match self {
+            Predicate::Equal(x) => value == x,
+            Predicate::GreaterThan(x) => value > x,
+            Predicate::LessThan(x) => value < x,
+            Predicate::Between(min, max) => value >= min && value <= max,
+        }
+    }
+
+    fn may_match(&self, stats: &ChunkStats<T>) -> bool {
+        match self {
+            Predicate::Equal(x) => stats.min <= *x && stats.max >= *x,
+            Predicate::GreaterThan(x) => stats.max > *x,
+            Predicate::LessThan(x) => stats.min < *x,
+            Predicate::Between(min, max) => stats.min <= *max && stats.max >= *min,
+        }
+    }
+}
+
+/// Iterator over the coordinates and values of the matching elements of an
+/// array, chunk by chunk. See [`HierarchyScan::scan_where`].
+pub struct ScanMatches<'a, S, T, P> {
+    store: &'a S,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    predicate: P,
+    /// Grid positions of chunks not yet scanned.
+    grid_positions: Box<dyn Iterator<Item = GridCoord> + 'a>,
+    fill_value: T,
+    matches: VecDeque<(GridCoord, T)>,
+    chunks_skipped: u64,
+}
+
+impl<'a, S, T, P> ScanMatches<'a, S, T, P> {
+    /// Number of chunks skipped so far by their stats, without being decoded.
+    pub fn chunks_skipped(&self) -> u64 {
+        self.chunks_skipped
+    }
+}
+
+impl<'a, S, T, P> std::fmt::Debug for ScanMatches<'a, S, T, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScanMatches")
+            .field("path_name", &self.path_name)
+            .field("matches_buffered", &self.matches.len())
+            .field("chunks_skipped", &self.chunks_skipped)
+            .finish()
+    }
+}
+
+impl<'a, S, T, P> ScanMatches<'a, S, T, P>
+where
+    S: ReadableStore + Hierarchy,
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+    T: ReflectedType + PartialOrd,
+    P: ScanPredicate<T>,
+{
+    /// Scan a chunk, buffering its matching elements.
+    fn scan_chunk(&mut self, grid_position: GridCoord) -> Result<(), Error> {
+        let chunk_key = find_chunk_key(
+            self.store,
+            &self.path_name,
+            &self.array_meta,
+            &grid_position,
+        )?;
+        if let Some(Some(stats)) =
+            current_stats::<_, T>(self.store, &self.path_name, &chunk_key, &grid_position)?
+        {
+            if !self.predicate.may_match(&stats) {
+                self.chunks_skipped += 1;
+                return Ok(());
+            }
+        }
+
+        let chunk_bb = self.array_meta.get_chunk_bounds(&grid_position);
+        let mut extent = chunk_bb.clone();
+        extent.intersect(&self.array_meta.get_bounds());
+        let chunk = crate::HierarchyReader::read_chunk::<T>(
+            self.store,
+            &self.path_name,
+            &self.array_meta,
+            grid_position,
+        )?;
+        let chunk = match chunk {
+            Some(chunk) => chunk.into_ndarray(&self.array_meta),
+            None => ndarray::Array::from_elem(
+                chunk_bb.shape_ndarray_shape().as_slice(),
+                self.fill_value.clone(),
+            ),
+        };
+        for (index, value) in chunk.indexed_iter() {
+            let coord: GridCoord = (0..chunk.ndim())
+                .map(|d| chunk_bb.offset()[d] + index[d] as u64)
+                .collect();
+            let in_bounds = coord.iter().zip(extent.end()).all(|(&c, end)| c < end);
+            if in_bounds && self.predicate.matches(value) {
+                self.matches.push_back((coord, value.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S, T, P> Iterator for ScanMatches<'a, S, T, P>
+where
+    S: ReadableStore + Hierarchy,
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+    T: ReflectedType + PartialOrd,
+    P: ScanPredicate<T>,
+{
+    type Item = Result<(GridCoord, T), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.matches.is_empty() {
+            let grid_position = self.grid_positions.next()?;
+            if let Err(e) = self.scan_chunk(grid_position) {
+                // Stop after an error rather than skipping the chunk.
+                self.grid_positions = Box::new(std::iter::empty());
+                return Some(Err(e));
+            }
+        }
+        self.matches.pop_front().map(Ok)
+    }
+}
+
+/// Predicate scans for hierarchies over listable stores.
+pub trait HierarchyScan: HierarchyChunkStats {
+    /// Scan an array for elements matching a predicate, yielding their
+    /// coordinates and values in chunk order as the iterator advances.
+    ///
+    /// Chunks which do not exist are only scanned if their fill value
+    /// matches, in which case every chunk of the grid is visited.
+    fn scan_where<T, P>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        predicate: P,
+    ) -> Result<ScanMatches<'_, Self, T, P>, Error>
+    where
+        Self: Sized,
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd,
+        P: ScanPredicate<T>;
+}
+
+impl<S: ReadableStore + ListableStore + Hierarchy> HierarchyScan for S {
+    fn scan_where<T, P>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        predicate: P,
+    ) -> Result<ScanMatches<'_, Self, T, P>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd,
+        P: ScanPredicate<T>,
+    {
+        let fill_value: T = array_meta.get_effective_fill_value()?;
+        let grid_positions: Box<dyn Iterator<Item = GridCoord>> = if predicate.matches(&fill_value)
+        {
+            Box::new(array_meta.coord_iter().map(GridCoord::from))
+        } else {
+            let chunks = present_chunks(self, path_name, array_meta)?;
+            Box::new(chunks.into_iter().map(|(_, grid_position)| grid_position))
+        };
+
+        Ok(ScanMatches {
+            store: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            predicate,
+            grid_positions,
+            fill_value,
+            matches: VecDeque::new(),
+            chunks_skipped: 0,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::stats::HierarchyChunkStatsWriter;
+
+    #[test]
+    fn test_scan_where() {
+        let dir = tempdir::TempDir::new("rust_zarr_scan_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![3, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        // Column-major chunks, so [0, 0] holds 1 at [0, 0] and 9 at [1, 1].
+        h.write_chunk_with_stats(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0, 0], vec![1u8, 0, 0, 9]),
+        )
+        .unwrap();
+        h.write_chunk_with_stats(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0, 1], vec![2u8, 3, 0, 0]),
+        )
+        .unwrap();
+        // The overhang of the edge chunk is not scanned.
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1, 1], vec![0u8, 7, 8, 7]),
+        )
+        .unwrap();
+
+        let mut scan = h
+            .scan_where("a", &array_meta, Predicate::GreaterThan(5u8))
+            .unwrap();
+        let matches = scan.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                (GridCoord::from(&[1, 1][..]), 9),
+                (GridCoord::from(&[2, 3][..]), 8),
+            ]
+        );
+        assert_eq!(scan.chunks_skipped(), 1);
+
+        let matches = h
+            .scan_where("a", &array_meta, |&v: &u8| v == 0)
+            .unwrap()
+            .count();
+        assert_eq!(matches, 12 - 5);
+        let matches = h
+            .scan_where("a", &array_meta, Predicate::Between(2u8, 3))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(matches.len(), 2);
+    }
+}
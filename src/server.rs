@@ -0,0 +1,1038 @@
+//! A lightweight HTTP server exposing a store to viewers and remote readers.
+//!
+//! Each key of the store is served at the same URL path, for example
+//! `/meta/root/raw.array.json`, so browser viewers such as neuroglancer and
+//! vizarr and HTTP stores can read hierarchies directly. `GET` and `HEAD`
+//! requests are supported, including single byte ranges for partial reads of
+//! chunks.
+//!
+//! An [`ArrayServer`] instead serves regions of arrays, decoded, for clients
+//! without a zarr implementation of their own.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::server::StoreServer;
+//!
+//! let h = FilesystemHierarchy::open("/tmp/volume.zr3").unwrap();
+//! StoreServer::bind(h, "127.0.0.1:8000")
+//!     .unwrap()
+//!     .with_allowed_origin("*")
+//!     .serve()
+//!     .unwrap();
+//! ```
+
+#[cfg(feature = "use_ndarray")]
+use std::convert::TryFrom;
+use std::io::{
+    BufRead,
+    BufReader,
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+use std::net::{
+    SocketAddr,
+    TcpListener,
+    TcpStream,
+    ToSocketAddrs,
+};
+use std::sync::{
+    Condvar,
+    Mutex,
+};
+use std::time::Duration;
+
+#[cfg(feature = "use_ndarray")]
+use half::f16;
+
+#[cfg(feature = "use_ndarray")]
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+    ZarrNdarrayWriter,
+};
+use crate::storage::PartialReadStore;
+#[cfg(feature = "use_ndarray")]
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    DataType,
+    FloatSize,
+    GridCoord,
+    HierarchyReader,
+    HierarchyWriter,
+    IntSize,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+
+/// Longest request line or header line accepted, in bytes.
+const MAX_LINE_LENGTH: u64 = 8192;
+/// Largest request body accepted, in bytes, enough for regions of several
+/// typical chunks.
+const MAX_BODY_LENGTH: u64 = 64 << 20;
+/// Most headers accepted in a request.
+const MAX_HEADERS: usize = 100;
+/// Time a persistent connection may be idle before it is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Most connections served at once. Further connections wait to be accepted
+/// until others close.
+const MAX_CONNECTIONS: usize = 64;
+/// Time waited after failing to accept a connection before trying again.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Content type a key is served with.
+pub fn content_type(key: &str) -> &'static str {
+    if key.ends_with(".json") {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// HTTP server for the keys of a store, handling each connection in its own
+/// thread.
+#[derive(Debug)]
+pub struct StoreServer<S> {
+    store: S,
+    listener: TcpListener,
+    allowed_origin: Option<String>,
+}
+
+impl<S: PartialReadStore + Sync> StoreServer<S> {
+    /// Listen for connections on an address, which may have port 0 to use
+    /// any free port.
+    pub fn bind<A: ToSocketAddrs>(store: S, addr: A) -> Result<Self, Error> {
+        Ok(StoreServer {
+            store,
+            listener: TcpListener::bind(addr)?,
+            allowed_origin: None,
+        })
+    }
+
+    /// Allow cross-origin requests from an origin, or `*` for any, as
+    /// browser viewers hosted elsewhere require.
+    pub fn with_allowed_origin(mut self, origin: &str) -> Self {
+        self.allowed_origin = Some(origin.to_owned());
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    /// Serve connections indefinitely.
+    pub fn serve(&self) -> Result<(), Error> {
+        serve(&self.listener, self.allowed_origin.as_deref(), self)
+    }
+
+    fn respond_get(&self, request: &Request) -> Result<Response, Error> {
+        let key = &request.key;
+        let size = self
+            .store
+            .size(key)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        let (status, reason, offset, length) = match request.range.map(|r| r.resolve(size)) {
+            None | Some(RangeResolution::Whole) => (200, "OK", 0, size),
+            Some(RangeResolution::Bytes(start, end)) => {
+                (206, "Partial Content", start, end - start + 1)
+            }
+            Some(RangeResolution::Unsatisfiable) => {
+                let mut response = Response::status(416, "Range Not Satisfiable");
+                response
+                    .headers
+                    .push(("Content-Range", format!("bytes */{}", size)));
+                return Ok(response);
+            }
+        };
+        let body = if request.method == "HEAD" {
+            vec![]
+        } else {
+            self.store
+                .get_range(key, offset, Some(length))?
+                .ok_or_else(|| Error::from(ErrorKind::NotFound))?
+        };
+
+        let mut headers = vec![
+            ("Content-Type", content_type(key).to_owned()),
+            ("Accept-Ranges", "bytes".to_owned()),
+        ];
+        if status == 206 {
+            headers.push((
+                "Content-Range",
+                format!("bytes {}-{}/{}", offset, offset + length - 1, size),
+            ));
+        }
+        Ok(Response {
+            status,
+            reason,
+            headers,
+            content_length: length,
+            body,
+        })
+    }
+}
+
+impl<S: PartialReadStore + Sync> Respond for StoreServer<S> {
+    fn respond(&self, request: &Request) -> Response {
+        match request.method.as_str() {
+            "GET" | "HEAD" => {}
+            "OPTIONS" => return Response::allow(204, "No Content", "GET, HEAD, OPTIONS"),
+            _ => return Response::allow(405, "Method Not Allowed", "GET, HEAD, OPTIONS"),
+        }
+        self.respond_get(request)
+            .unwrap_or_else(|e| Response::error(&e))
+    }
+}
+
+/// Writer of a region of an array from the bytes of its elements.
+#[cfg(feature = "use_ndarray")]
+type RegionWriter<H> = fn(&H, &str, &ArrayMetadata, &BoundingBox, &[u8]) -> Result<(), Error>;
+
+/// HTTP server for regions of a hierarchy's arrays, so clients in any
+/// language can read and write arrays without decoding chunks themselves.
+///
+/// Each array is served at its path. A `GET` of the path returns the array's
+/// metadata, while a `GET` with `offset` and `shape` query parameters, such
+/// as `/raw?offset=0,64&shape=32,32`, returns the elements of that region as
+/// uncompressed bytes in row-major order. The element data type and region
+/// shape are given by the `Zarr-Data-Type` and `Zarr-Shape` headers. If
+/// writes are enabled, a `PUT` of a region with a body in the same form
+/// writes it.
+///
+/// Regions read are limited in size, by default to 64 MiB, so that a single
+/// request can not read a whole large array into memory; see
+/// [`with_max_region_bytes`](Self::with_max_region_bytes).
+///
+/// This is plain HTTP rather than an Arrow Flight or gRPC service, which
+/// would need an async runtime and generated code the crate does not
+/// otherwise use.
+#[cfg(feature = "use_ndarray")]
+#[derive(Debug)]
+pub struct ArrayServer<H> {
+    hierarchy: H,
+    listener: TcpListener,
+    allowed_origin: Option<String>,
+    write_region: Option<RegionWriter<H>>,
+    max_region_bytes: u64,
+}
+
+#[cfg(feature = "use_ndarray")]
+impl<H: HierarchyReader + Sync> ArrayServer<H> {
+    /// Listen for connections on an address, which may have port 0 to use
+    /// any free port.
+    pub fn bind<A: ToSocketAddrs>(hierarchy: H, addr: A) -> Result<Self, Error> {
+        Ok(ArrayServer {
+            hierarchy,
+            listener: TcpListener::bind(addr)?,
+            allowed_origin: None,
+            write_region: None,
+            max_region_bytes: MAX_BODY_LENGTH,
+        })
+    }
+
+    /// Allow cross-origin requests from an origin, or `*` for any.
+    pub fn with_allowed_origin(mut self, origin: &str) -> Self {
+        self.allowed_origin = Some(origin.to_owned());
+        self
+    }
+
+    /// Accept `PUT` requests writing regions of arrays.
+    pub fn with_writes(mut self) -> Self
+    where
+        H: HierarchyWriter,
+    {
+        self.write_region = Some(write_region_bytes);
+        self
+    }
+
+    /// Limit the size of regions read to a number of bytes. Larger reads
+    /// fail with `400 Bad Request`. Regions written are always limited to
+    /// the largest request body accepted, 64 MiB.
+    pub fn with_max_region_bytes(mut self, max_region_bytes: u64) -> Self {
+        self.max_region_bytes = max_region_bytes;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr()
+    }
+
+    pub fn get_ref(&self) -> &H {
+        &self.hierarchy
+    }
+
+    /// Serve connections indefinitely.
+    pub fn serve(&self) -> Result<(), Error> {
+        serve(&self.listener, self.allowed_origin.as_deref(), self)
+    }
+
+    fn respond_array(&self, request: &Request) -> Result<Response, Error> {
+        let path_name = crate::canonicalize_path(&request.key);
+        let array_meta = self.hierarchy.get_array_metadata(path_name)?;
+        let bbox = match &request.query {
+            Some(query) => parse_region(query, &array_meta)?,
+            None if request.method == "PUT" => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Writes must give a region's offset and shape",
+                ))
+            }
+            None => {
+                return Ok(Response::ok(
+                    "application/json",
+                    serde_json::to_vec(&array_meta)?,
+                ))
+            }
+        };
+
+        if request.method == "PUT" {
+            let write_region = self.write_region.expect("Only accepted when writable");
+            write_region(
+                &self.hierarchy,
+                path_name,
+                &array_meta,
+                &bbox,
+                &request.body,
+            )?;
+            return Ok(Response::status(204, "No Content"));
+        }
+
+        let body = read_region_bytes(
+            &self.hierarchy,
+            path_name,
+            &array_meta,
+            &bbox,
+            self.max_region_bytes,
+        )?;
+        let mut response = Response::ok("application/octet-stream", body);
+        let data_type = serde_json::to_value(array_meta.get_data_type())?;
+        response.headers.push((
+            "Zarr-Data-Type",
+            data_type
+                .as_str()
+                .map_or(data_type.to_string(), str::to_owned),
+        ));
+        response
+            .headers
+            .push(("Zarr-Shape", join_coords(bbox.shape())));
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "use_ndarray")]
+impl<H: HierarchyReader + Sync> Respond for ArrayServer<H> {
+    fn respond(&self, request: &Request) -> Response {
+        let methods = if self.write_region.is_some() {
+            "GET, HEAD, PUT, OPTIONS"
+        } else {
+            "GET, HEAD, OPTIONS"
+        };
+        match request.method.as_str() {
+            "GET" | "HEAD" => {}
+            "PUT" if self.write_region.is_some() => {}
+            "OPTIONS" => return Response::allow(204, "No Content", methods),
+            _ => return Response::allow(405, "Method Not Allowed", methods),
+        }
+        self.respond_array(request)
+            .unwrap_or_else(|e| Response::error(&e))
+    }
+}
+
+/// Bounding box given by `offset` and `shape` query parameters of
+/// comma-separated coordinates, which must be within an array.
+#[cfg(feature = "use_ndarray")]
+fn parse_region(query: &str, array_meta: &ArrayMetadata) -> Result<BoundingBox, Error> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidInput, message);
+    let (mut offset, mut shape) = (None, None);
+    for parameter in query.split('&') {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let coords = || {
+            value
+                .split(',')
+                .map(|c| c.trim().parse::<u64>())
+                .collect::<Result<GridCoord, _>>()
+                .map_err(|_| invalid("Malformed region coordinates"))
+        };
+        match name {
+            "offset" => offset = Some(coords()?),
+            "shape" => shape = Some(coords()?),
+            _ => {}
+        }
+    }
+    let (offset, shape) = match (offset, shape) {
+        (Some(offset), Some(shape)) => (offset, shape),
+        _ => return Err(invalid("Regions must give both an offset and shape")),
+    };
+    if offset.len() != array_meta.get_ndim() || shape.len() != array_meta.get_ndim() {
+        return Err(invalid("Region has the wrong number of dimensions"));
+    }
+    if offset
+        .iter()
+        .zip(&shape)
+        .zip(array_meta.get_shape())
+        .any(|((o, s), a)| o.checked_add(*s).is_none_or(|end| end > *a))
+    {
+        return Err(invalid("Region is outside the array"));
+    }
+    Ok(BoundingBox::new(offset, shape))
+}
+
+#[cfg(feature = "use_ndarray")]
+fn join_coords(coords: &[u64]) -> String {
+    coords
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Number of bytes of the elements of a region, failing if it is more than a
+/// limit.
+#[cfg(feature = "use_ndarray")]
+fn region_len<T>(bbox: &BoundingBox, limit: u64) -> Result<usize, Error> {
+    bbox.shape()
+        .iter()
+        .try_fold(std::mem::size_of::<T>() as u64, |len, &s| {
+            len.checked_mul(s)
+        })
+        .filter(|&len| len <= limit)
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Region is too large"))
+}
+
+#[cfg(feature = "use_ndarray")]
+fn unsupported_raw() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "Arrays of raw data types can not be served by region",
+    )
+}
+
+/// Elements of a region of an array of at most `max_bytes`, in row-major
+/// order and the array's byte order.
+#[cfg(feature = "use_ndarray")]
+fn read_region_bytes<H: HierarchyReader>(
+    hierarchy: &H,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    bbox: &BoundingBox,
+    max_bytes: u64,
+) -> Result<Vec<u8>, Error> {
+    fn read<T: ReflectedType, H: HierarchyReader>(
+        hierarchy: &H,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        max_bytes: u64,
+    ) -> Result<Vec<u8>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+    {
+        region_len::<T>(bbox, max_bytes)?;
+        let array = hierarchy.read_ndarray::<T>(path_name, array_meta, bbox)?;
+        let elements = VecDataChunk::<T>::new(GridCoord::new(), array.iter().cloned().collect());
+        let mut bytes = Vec::new();
+        elements.write_data(&mut bytes, array_meta)?;
+        Ok(bytes)
+    }
+
+    data_type_match!(
+        array_meta.get_data_type().effective_type()?,
+        DataType::Raw { .. } => Err(unsupported_raw()),
+        read::<RsType, H>(hierarchy, path_name, array_meta, bbox, max_bytes)
+    )
+}
+
+/// Write a region of an array from its elements, in row-major order and the
+/// array's byte order.
+#[cfg(feature = "use_ndarray")]
+fn write_region_bytes<H: HierarchyWriter>(
+    hierarchy: &H,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    bbox: &BoundingBox,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    fn write<T: ReflectedType, H: HierarchyWriter>(
+        hierarchy: &H,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        mut bytes: &[u8],
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+    {
+        // The body must match the region before the region is allocated.
+        let len = region_len::<T>(bbox, u64::MAX)?;
+        if len != bytes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Body does not match the size of the region",
+            ));
+        }
+        let num_el = len / std::mem::size_of::<T>();
+        let mut elements = VecDataChunk::<T>::new(GridCoord::new(), vec![T::default(); num_el]);
+        elements.read_data(&mut bytes, array_meta)?;
+        let array = ndarray::Array::from_shape_vec(
+            bbox.shape_ndarray_shape().as_slice(),
+            elements.into_data(),
+        )
+        .expect("Element count matches the region");
+        hierarchy.write_ndarray(path_name, array_meta, bbox.offset().into(), &array)
+    }
+
+    data_type_match!(
+        array_meta.get_data_type().effective_type()?,
+        DataType::Raw { .. } => Err(unsupported_raw()),
+        write::<RsType, H>(hierarchy, path_name, array_meta, bbox, bytes)
+    )
+}
+
+/// Responder to the requests of a server's connections.
+trait Respond: Sync {
+    fn respond(&self, request: &Request) -> Response;
+}
+
+/// Accept connections, serving each in its own thread, up to
+/// [`MAX_CONNECTIONS`] at once.
+fn serve<R: Respond>(
+    listener: &TcpListener,
+    allowed_origin: Option<&str>,
+    responder: &R,
+) -> Result<(), Error> {
+    let connections = (Mutex::new(0), Condvar::new());
+    std::thread::scope(|scope| loop {
+        {
+            let (count, closed) = &connections;
+            let mut count = count.lock().unwrap();
+            while *count >= MAX_CONNECTIONS {
+                count = closed.wait(count).unwrap();
+            }
+        }
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            // Failures such as running out of file descriptors pass once
+            // connections close, so do not stop the server.
+            Err(e) => {
+                log::warn!("Failed to accept a connection: {}", e);
+                std::thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+        };
+        *connections.0.lock().unwrap() += 1;
+        let connections = &connections;
+        scope.spawn(move || {
+            // Errors are specific to a client, such as it disconnecting,
+            // so do not stop the server.
+            let _ = serve_connection(stream, allowed_origin, responder);
+            *connections.0.lock().unwrap() -= 1;
+            connections.1.notify_one();
+        });
+    })
+}
+
+/// Serve requests on a connection until the client closes it.
+fn serve_connection<R: Respond>(
+    stream: TcpStream,
+    allowed_origin: Option<&str>,
+    responder: &R,
+) -> Result<(), Error> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let request = match Request::read(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                let response = Response::error(&e);
+                return write_response(&mut writer, &response, false, allowed_origin);
+            }
+            Err(e) => return Err(e),
+        };
+        let response = responder.respond(&request);
+        write_response(
+            &mut writer,
+            &response,
+            request.method == "HEAD",
+            allowed_origin,
+        )?;
+        if !request.keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+fn write_response<W: Write>(
+    writer: &mut W,
+    response: &Response,
+    head: bool,
+    allowed_origin: Option<&str>,
+) -> Result<(), Error> {
+    let mut message = format!("HTTP/1.1 {} {}\r\n", response.status, response.reason);
+    for (name, value) in &response.headers {
+        message.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some(origin) = allowed_origin {
+        message.push_str(&format!(
+            "Access-Control-Allow-Origin: {}\r\n\
+             Access-Control-Allow-Headers: Range, Content-Type\r\n\
+             Access-Control-Expose-Headers: Content-Range, Content-Length, Zarr-Data-Type, \
+             Zarr-Shape\r\n",
+            origin
+        ));
+    }
+    message.push_str(&format!(
+        "Content-Length: {}\r\n\r\n",
+        response.content_length
+    ));
+    writer.write_all(message.as_bytes())?;
+    if !head {
+        writer.write_all(&response.body)?;
+    }
+    writer.flush()
+}
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    key: String,
+    #[cfg_attr(not(feature = "use_ndarray"), allow(dead_code))]
+    query: Option<String>,
+    range: Option<ByteRange>,
+    keep_alive: bool,
+    #[cfg_attr(not(feature = "use_ndarray"), allow(dead_code))]
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Read the next request from a connection, or `None` if the client has
+    /// closed it. Malformed requests fail with [`ErrorKind::InvalidData`].
+    fn read<R: BufRead>(reader: &mut R) -> Result<Option<Request>, Error> {
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message);
+        let request_line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        let mut parts = request_line.split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) => (method, target, version),
+            _ => return Err(invalid("Malformed request line")),
+        };
+        let target = target.split('#').next().unwrap_or_default();
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_owned())),
+            None => (target, None),
+        };
+        if !path.starts_with('/') {
+            return Err(invalid("Request target must be a path"));
+        }
+
+        let mut request = Request {
+            method: method.to_owned(),
+            key: percent_decode(path).ok_or_else(|| invalid("Malformed request path"))?,
+            query,
+            range: None,
+            keep_alive: version == "HTTP/1.1",
+            body: vec![],
+        };
+        let mut content_length = 0;
+        for _ in 0..=MAX_HEADERS {
+            let line = read_line(reader)?.ok_or_else(|| invalid("Incomplete request"))?;
+            if line.is_empty() {
+                if content_length > 0 {
+                    if request.method != "PUT" {
+                        return Err(invalid("Only PUT requests may have a body"));
+                    }
+                    if content_length > MAX_BODY_LENGTH {
+                        return Err(invalid("Request body too large"));
+                    }
+                    // The body grows as it arrives rather than trusting
+                    // the length given.
+                    reader.take(content_length).read_to_end(&mut request.body)?;
+                    if (request.body.len() as u64) < content_length {
+                        return Err(Error::from(ErrorKind::UnexpectedEof));
+                    }
+                }
+                return Ok(Some(request));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("Malformed header"))?;
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "connection" if value.eq_ignore_ascii_case("close") => request.keep_alive = false,
+                "connection" if value.eq_ignore_ascii_case("keep-alive") => {
+                    request.keep_alive = true
+                }
+                // Ranges that cannot be parsed, including multiple ranges,
+                // are ignored and the whole value served.
+                "range" => request.range = ByteRange::parse(value),
+                "content-length" => {
+                    content_length = value
+                        .parse()
+                        .map_err(|_| invalid("Malformed content length"))?
+                }
+                _ => {}
+            }
+        }
+        Err(invalid("Too many headers"))
+    }
+}
+
+/// A line without its terminator, or `None` at the end of the stream.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+    let read = reader.take(MAX_LINE_LENGTH).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err(Error::new(ErrorKind::InvalidData, "Line too long"));
+    }
+    let trimmed = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// A single range of a `bytes` range header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// First and optional last byte offset, inclusive.
+    FromOffset(u64, Option<u64>),
+    /// Number of bytes at the end of the value.
+    Suffix(u64),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeResolution {
+    Whole,
+    /// First and last byte offset, inclusive.
+    Bytes(u64, u64),
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    fn parse(header: &str) -> Option<ByteRange> {
+        let spec = header.trim().strip_prefix("bytes=")?;
+        let (first, last) = spec.trim().split_once('-')?;
+        match (first.trim(), last.trim()) {
+            ("", suffix) => Some(ByteRange::Suffix(suffix.parse().ok()?)),
+            (first, "") => Some(ByteRange::FromOffset(first.parse().ok()?, None)),
+            (first, last) => {
+                let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+                if last < first {
+                    return None;
+                }
+                Some(ByteRange::FromOffset(first, Some(last)))
+            }
+        }
+    }
+
+    fn resolve(self, size: u64) -> RangeResolution {
+        match self {
+            ByteRange::FromOffset(first, _) if first >= size => RangeResolution::Unsatisfiable,
+            ByteRange::FromOffset(first, last) => {
+                RangeResolution::Bytes(first, last.map_or(size - 1, |last| last.min(size - 1)))
+            }
+            ByteRange::Suffix(0) => RangeResolution::Unsatisfiable,
+            ByteRange::Suffix(_) if size == 0 => RangeResolution::Whole,
+            ByteRange::Suffix(length) => {
+                RangeResolution::Bytes(size.saturating_sub(length), size - 1)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    reason: &'static str,
+    headers: Vec<(&'static str, String)>,
+    content_length: u64,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn status(status: u16, reason: &'static str) -> Response {
+        Response {
+            status,
+            reason,
+            headers: vec![],
+            content_length: 0,
+            body: vec![],
+        }
+    }
+
+    fn allow(status: u16, reason: &'static str, methods: &str) -> Response {
+        let mut response = Response::status(status, reason);
+        response.headers.push(("Allow", methods.to_owned()));
+        response
+    }
+
+    fn ok(content_type: &str, body: Vec<u8>) -> Response {
+        Response {
+            status: 200,
+            reason: "OK",
+            headers: vec![("Content-Type", content_type.to_owned())],
+            content_length: body.len() as u64,
+            body,
+        }
+    }
+
+    /// Response to a request which failed, describing the error for
+    /// malformed requests.
+    fn error(error: &Error) -> Response {
+        match error.kind() {
+            ErrorKind::NotFound => Response::status(404, "Not Found"),
+            ErrorKind::InvalidInput | ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+                let mut response = Response::ok("text/plain", error.to_string().into_bytes());
+                response.status = 400;
+                response.reason = "Bad Request";
+                response
+            }
+            _ => Response::status(500, "Internal Server Error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(
+            ByteRange::parse("bytes=2-5"),
+            Some(ByteRange::FromOffset(2, Some(5)))
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=2-"),
+            Some(ByteRange::FromOffset(2, None))
+        );
+        assert_eq!(ByteRange::parse("bytes=-3"), Some(ByteRange::Suffix(3)));
+        assert_eq!(ByteRange::parse("bytes=5-2"), None);
+        assert_eq!(ByteRange::parse("bytes=0-1,4-5"), None);
+        assert_eq!(ByteRange::parse("items=0-1"), None);
+
+        let resolve = |header| ByteRange::parse(header).unwrap().resolve(10);
+        assert_eq!(resolve("bytes=2-5"), RangeResolution::Bytes(2, 5));
+        assert_eq!(resolve("bytes=2-50"), RangeResolution::Bytes(2, 9));
+        assert_eq!(resolve("bytes=7-"), RangeResolution::Bytes(7, 9));
+        assert_eq!(resolve("bytes=-3"), RangeResolution::Bytes(7, 9));
+        assert_eq!(resolve("bytes=-30"), RangeResolution::Bytes(0, 9));
+        assert_eq!(resolve("bytes=10-"), RangeResolution::Unsatisfiable);
+    }
+
+    #[cfg(feature = "filesystem")]
+    #[test]
+    fn test_serve() {
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_server_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![4])
+            .compressor(crate::compression::CompressionType::Raw(Default::default()))
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0], vec![1u8, 2, 3, 4]),
+        )
+        .unwrap();
+        let chunk = h.get_range("/data/root/a/c0", 0, None).unwrap().unwrap();
+
+        let server = StoreServer::bind(h, "127.0.0.1:0")
+            .unwrap()
+            .with_allowed_origin("*");
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        let request = |request: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = vec![];
+            stream.read_to_end(&mut response).unwrap();
+            let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let head = String::from_utf8(response[..split + 2].to_vec()).unwrap();
+            (head, response[split + 4..].to_vec())
+        };
+
+        let (head, body) =
+            request("GET /meta/root/a.array.json HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: application/json\r\n"));
+        assert!(head.contains("Access-Control-Allow-Origin: *\r\n"));
+        let served: ArrayMetadata = serde_json::from_slice(&body).unwrap();
+        assert_eq!(served, array_meta);
+
+        let (head, body) = request(
+            "GET /data/root/a/c0 HTTP/1.1\r\nRange: bytes=1-2\r\nConnection: close\r\n\r\n",
+        );
+        assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(head.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(head.contains(&format!("Content-Range: bytes 1-2/{}\r\n", chunk.len())));
+        assert_eq!(body, &chunk[1..3]);
+
+        let (head, body) = request("HEAD /data/root/a/c0 HTTP/1.0\r\n\r\n");
+        assert!(head.contains(&format!("Content-Length: {}\r\n", chunk.len())));
+        assert!(body.is_empty());
+
+        let (head, _) = request("GET /data/root/a/c0 HTTP/1.0\r\nRange: bytes=100-\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 416 "));
+        let (head, _) = request("GET /data/root/a/c1 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 404 "));
+        let (head, _) = request("PUT /data/root/a/c0 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 405 "));
+        // Bodies of other methods, and large bodies, are refused before
+        // they are read.
+        let (head, _) = request("GET /zarr.json HTTP/1.0\r\nContent-Length: 1073741824\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        let (head, _) = request("PUT /zarr.json HTTP/1.0\r\nContent-Length: 1073741824\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+
+        // Persistent connections serve several requests.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /zarr.json HTTP/1.1\r\n\r\nGET /zarr%2Ejson HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(response.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+
+        // Connections beyond the limit wait until others close.
+        let open: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(addr).unwrap())
+            .collect();
+        let mut waiting = TcpStream::connect(addr).unwrap();
+        waiting
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        waiting
+            .write_all(b"GET /zarr.json HTTP/1.0\r\n\r\n")
+            .unwrap();
+        let mut response = vec![];
+        let timed_out = waiting.read_to_end(&mut response).unwrap_err().kind();
+        assert!(matches!(
+            timed_out,
+            ErrorKind::WouldBlock | ErrorKind::TimedOut
+        ));
+        drop(open);
+        waiting.set_read_timeout(None).unwrap();
+        waiting.read_to_end(&mut response).unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[cfg(all(feature = "filesystem", feature = "use_ndarray"))]
+    #[test]
+    fn test_array_server() {
+        use crate::ndarray::ZarrNdarrayReader;
+        use crate::prelude::*;
+
+        let dir = tempdir::TempDir::new("rust_zarr_server_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 5], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![3, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+
+        let server = ArrayServer::bind(h.clone(), "127.0.0.1:0")
+            .unwrap()
+            .with_writes()
+            .with_max_region_bytes(32);
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+        let request = |request: &[u8]| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request).unwrap();
+            let mut response = vec![];
+            stream.read_to_end(&mut response).unwrap();
+            let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let head = String::from_utf8(response[..split + 2].to_vec()).unwrap();
+            (head, response[split + 4..].to_vec())
+        };
+
+        let (head, body) = request(b"GET /g/a HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        let served: ArrayMetadata = serde_json::from_slice(&body).unwrap();
+        assert_eq!(served, array_meta);
+
+        let values: Vec<u16> = (0..6).collect();
+        let mut put =
+            b"PUT /g/a?offset=1,2&shape=2,3 HTTP/1.0\r\nContent-Length: 12\r\n\r\n".to_vec();
+        put.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        let (head, _) = request(&put);
+        assert!(head.starts_with("HTTP/1.1 204 "));
+        let written = h
+            .read_ndarray::<u16>(
+                "g/a",
+                &array_meta,
+                &BoundingBox::new(smallvec![1, 2], smallvec![2, 3]),
+            )
+            .unwrap();
+        assert_eq!(written.iter().cloned().collect::<Vec<_>>(), values);
+
+        let (head, body) = request(b"GET /g/a?offset=1,3&shape=3,2 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Zarr-Data-Type: <u2\r\n"));
+        assert!(head.contains("Zarr-Shape: 3,2\r\n"));
+        let region: Vec<u16> = body
+            .chunks(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(region, vec![1, 2, 4, 5, 0, 0]);
+
+        let (head, _) = request(b"GET /g/a?offset=3,0&shape=2,1 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        let (head, _) = request(b"GET /g/a?offset=0&shape=1 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        let (head, _) =
+            request(b"PUT /g/a?offset=0,0&shape=1,1 HTTP/1.0\r\nContent-Length: 3\r\n\r\nabc");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        let (head, _) =
+            request(b"PUT /g/a?offset=0,0&shape=4,5 HTTP/1.0\r\nContent-Length: 2\r\n\r\nab");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        // Regions larger than the limit are not read.
+        let (head, body) = request(b"GET /g/a?offset=0,0&shape=4,5 HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 400 "));
+        assert_eq!(body, b"Region is too large");
+        let (head, _) = request(b"GET /g/missing HTTP/1.0\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 404 "));
+
+        let read_only = ArrayServer::bind(h, "127.0.0.1:0").unwrap();
+        let addr = read_only.local_addr().unwrap();
+        std::thread::spawn(move || read_only.serve());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(&put).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 405 "));
+    }
+}
@@ -0,0 +1,594 @@
+//! Minimum and maximum of each chunk, stored beside the chunks.
+//!
+//! Chunks written with
+//! [`write_chunk_with_stats`](HierarchyChunkStatsWriter::write_chunk_with_stats),
+//! or whose stats are computed afterwards with
+//! [`backfill_chunk_stats`](HierarchyChunkStatsWriter::backfill_chunk_stats),
+//! have the minimum and maximum of their elements within the array bounds
+//! stored under `/stats/root`, apart from the chunks and metadata so that
+//! they are not taken for either. Threshold queries then skip chunks
+//! which can not hold matching elements without decoding them, and the
+//! minimum and maximum of the array are found without decoding any chunk:
+//!
+//! ```
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//! use zarr::stats::{
+//!     HierarchyChunkStats,
+//!     HierarchyChunkStatsWriter,
+//! };
+//!
+//! let dir = tempdir::TempDir::new("zarr").unwrap();
+//! let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+//! let array_meta = ArrayMetadataBuilder::new(smallvec![4], f32::ZARR_TYPE)
+//!     .chunk_shape(smallvec![2])
+//!     .build();
+//! h.create_array("a", &array_meta).unwrap();
+//! for (i, data) in [[0.5f32, 1.0], [2.0, 9.0]].iter().enumerate() {
+//!     let chunk = SliceDataChunk::new(smallvec![i as u64], &data[..]);
+//!     h.write_chunk_with_stats("a", &array_meta, &chunk).unwrap();
+//! }
+//!
+//! let stats = h.array_min_max::<f32>("a", &array_meta).unwrap().unwrap();
+//! assert_eq!((stats.min, stats.max), (0.5, 9.0));
+//! let candidates = h
+//!     .candidate_chunks::<f32>("a", &array_meta, &5.0, &f32::INFINITY)
+//!     .unwrap();
+//! assert_eq!(candidates, vec![GridCoord::from(&[1][..])]);
+//! ```
+//!
+//! Stats record the content hash of the chunk they were computed from and are
+//! ignored once the chunk changes, so chunks rewritten without stats are
+//! read rather than skipped. The chunk is compared by its entity tag on
+//! stores which have them, and otherwise is read to be hashed, though not
+//! decoded.
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+};
+
+use ndarray::{
+    IxDyn,
+    SliceInfo,
+};
+use serde::{
+    de::DeserializeOwned,
+    Deserialize,
+    Serialize,
+};
+
+use crate::storage::{
+    check_writeable,
+    content_etag,
+    find_chunk_key,
+    list_chunk_keys,
+    KeyStat,
+    ListableStore,
+    ReadableStore,
+    WriteableStore,
+};
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    GridCoord,
+    Hierarchy,
+    HierarchyReader,
+    HierarchyWriter,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    SliceDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+
+/// Minimum and maximum of some elements.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkStats<T> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: PartialOrd + Clone> ChunkStats<T> {
+    /// Minimum and maximum of elements, ignoring those which are unordered,
+    /// such as NaN, or `None` if no ordered elements remain.
+    pub fn of<'a, I: IntoIterator<Item = &'a T>>(values: I) -> Option<Self>
+    where
+        T: 'a,
+    {
+        let mut stats: Option<Self> = None;
+        for value in values {
+            if value.partial_cmp(value).is_none() {
+                continue;
+            }
+            match &mut stats {
+                Some(stats) => stats.include(value),
+                None => {
+                    stats = Some(ChunkStats {
+                        min: value.clone(),
+                        max: value.clone(),
+                    })
+                }
+            }
+        }
+        stats
+    }
+
+    fn include(&mut self, value: &T) {
+        if *value < self.min {
+            self.min = value.clone();
+        }
+        if *value > self.max {
+            self.max = value.clone();
+        }
+    }
+
+    /// Extend these stats to include those of other elements.
+    pub fn merge(&mut self, other: &ChunkStats<T>) {
+        self.include(&other.min);
+        self.include(&other.max);
+    }
+
+    /// Whether elements with these stats may lie between a minimum and a
+    /// maximum, inclusive.
+    pub fn overlaps(&self, min: &T, max: &T) -> bool {
+        self.min <= *max && self.max >= *min
+    }
+}
+
+const STATS_ROOT_PATH: &str = "/stats/root";
+const STATS_KEY_EXT: &str = "stats";
+
+/// Stats of a chunk as stored, with the stat and content hash of the chunk
+/// they were computed from.
+#[derive(Serialize, Deserialize)]
+struct StoredStats<T> {
+    /// `None` if the chunk has no ordered elements.
+    stats: Option<ChunkStats<T>>,
+    chunk: KeyStat,
+    /// [`content_etag`] of the chunk.
+    hash: String,
+}
+
+/// Key of the stats of a chunk, under a root apart from the data and
+/// metadata roots.
+///
+/// The array's path is suffixed like metadata keys are, so that the stats
+/// of an array's chunks and of a child array's chunks do not collide.
+fn stats_key(path_name: &str, grid_position: &[u64]) -> String {
+    let coords: Vec<String> = grid_position.iter().map(|c| c.to_string()).collect();
+    format!(
+        "{}/{}.{}/c{}",
+        STATS_ROOT_PATH,
+        crate::canonicalize_path(path_name),
+        STATS_KEY_EXT,
+        coords.join("/")
+    )
+}
+
+/// Content hash of the value at a key, or `None` if it does not exist.
+fn content_hash<S: ReadableStore + ?Sized>(store: &S, key: &str) -> Result<Option<String>, Error> {
+    let mut value = Vec::new();
+    match store.get(key)? {
+        Some(mut reader) => reader.read_to_end(&mut value)?,
+        None => return Ok(None),
+    };
+    Ok(Some(content_etag(&value)))
+}
+
+/// Stats of the elements of a chunk within the array bounds, excluding the
+/// overhang of edge chunks.
+fn in_bounds_stats<T, C>(
+    array_meta: &ArrayMetadata,
+    chunk: &SliceDataChunk<T, C>,
+) -> Option<ChunkStats<T>>
+where
+    T: ReflectedType + PartialOrd,
+    C: AsRef<[T]>,
+{
+    let chunk_bb = chunk.get_bounds(array_meta);
+    let mut extent = chunk_bb.clone();
+    extent.intersect(&array_meta.get_bounds());
+    let chunk_slice = (extent - &GridCoord::from(chunk_bb.offset())).to_ndarray_slice();
+    let data = chunk.as_ndarray(array_meta);
+    let view = data.slice(SliceInfo::<_, IxDyn>::new(chunk_slice).unwrap().as_ref());
+    ChunkStats::of(view.iter())
+}
+
+/// Reading of chunk stats for hierarchies over listable stores.
+pub trait HierarchyChunkStats: HierarchyReader {
+    /// Read the stored stats of a chunk, or `None` if the chunk does not
+    /// exist, has changed since its stats were stored, has no stats or has
+    /// no ordered elements.
+    fn read_chunk_stats<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<Option<ChunkStats<T>>, Error>
+    where
+        T: ReflectedType + PartialOrd;
+
+    /// Grid positions of the chunks of an array which may hold elements
+    /// between a minimum and a maximum, inclusive, in ascending order.
+    ///
+    /// Chunks whose stored stats exclude the range are skipped without
+    /// being decoded. Chunks without current stats are included. Chunks which
+    /// do not exist are not included, since their elements are the array's
+    /// fill value.
+    fn candidate_chunks<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        min: &T,
+        max: &T,
+    ) -> Result<Vec<GridCoord>, Error>
+    where
+        T: ReflectedType + PartialOrd;
+
+    /// Minimum and maximum of an array, or `None` if it has no ordered
+    /// elements.
+    ///
+    /// Only chunks without current stats are read. The fill value is
+    /// included if any chunk of the array does not exist.
+    fn array_min_max<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Option<ChunkStats<T>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd;
+}
+
+/// Writing of chunk stats for hierarchies over listable stores.
+pub trait HierarchyChunkStatsWriter: HierarchyChunkStats + HierarchyWriter {
+    /// Write a chunk and store its stats.
+    fn write_chunk_with_stats<T, C>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunk: &SliceDataChunk<T, C>,
+    ) -> Result<(), Error>
+    where
+        SliceDataChunk<T, C>: WriteableDataChunk,
+        T: ReflectedType + PartialOrd + Serialize,
+        C: AsRef<[T]>;
+
+    /// Compute and store the stats of every existing chunk of an array
+    /// which has no current stats, returning the number of chunks read.
+    fn backfill_chunk_stats<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<u64, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd + Serialize;
+}
+
+/// Read the stored stats of a chunk at a key, or `None` if there are none
+/// for its current value.
+pub(crate) fn current_stats<S, T>(
+    store: &S,
+    path_name: &str,
+    chunk_key: &str,
+    grid_position: &[u64],
+) -> Result<Option<Option<ChunkStats<T>>>, Error>
+where
+    S: ReadableStore + Hierarchy,
+    T: DeserializeOwned,
+{
+    let stored: StoredStats<T> = match store.get(&stats_key(path_name, grid_position))? {
+        Some(reader) => serde_json::from_reader(reader)?,
+        None => return Ok(None),
+    };
+    let chunk = match store.stat(chunk_key)? {
+        Some(chunk) => chunk,
+        None => return Ok(None),
+    };
+    // Sizes and entity tags are trusted to show a change, but modification
+    // times may not have changed with the chunk.
+    let current = match (&chunk.etag, &stored.chunk.etag) {
+        _ if chunk.size != stored.chunk.size => false,
+        (Some(a), Some(b)) => a == b,
+        _ => content_hash(store, chunk_key)?.as_ref() == Some(&stored.hash),
+    };
+    Ok(if current { Some(stored.stats) } else { None })
+}
+
+/// Store the stats of the chunk at a key.
+fn store_stats<S, T>(
+    store: &S,
+    path_name: &str,
+    chunk_key: &str,
+    grid_position: &[u64],
+    stats: Option<ChunkStats<T>>,
+) -> Result<(), Error>
+where
+    S: ReadableStore + WriteableStore + Hierarchy,
+    T: Serialize,
+{
+    let not_found = || {
+        Error::new(
+            ErrorKind::NotFound,
+            "Chunk does not exist to store stats of",
+        )
+    };
+    let chunk = store.stat(chunk_key)?.ok_or_else(not_found)?;
+    let hash = content_hash(store, chunk_key)?.ok_or_else(not_found)?;
+    let stored = StoredStats { stats, chunk, hash };
+    store.set(&stats_key(path_name, grid_position), |writer| {
+        Ok(serde_json::to_writer(writer, &stored)?)
+    })
+}
+
+/// Keys and grid positions of the existing chunks of an array within its
+/// bounds.
+pub(crate) fn present_chunks<S: ListableStore + Hierarchy>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+) -> Result<Vec<(String, GridCoord)>, Error> {
+    let mut chunks = list_chunk_keys(store, path_name, array_meta)?;
+    chunks.retain(|(_, grid_position)| array_meta.in_bounds(grid_position));
+    chunks.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(chunks)
+}
+
+impl<S: ReadableStore + ListableStore + Hierarchy> HierarchyChunkStats for S {
+    fn read_chunk_stats<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<Option<ChunkStats<T>>, Error>
+    where
+        T: ReflectedType + PartialOrd,
+    {
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        Ok(current_stats(self, path_name, &chunk_key, grid_position)?.flatten())
+    }
+
+    fn candidate_chunks<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        min: &T,
+        max: &T,
+    ) -> Result<Vec<GridCoord>, Error>
+    where
+        T: ReflectedType + PartialOrd,
+    {
+        let mut candidates = vec![];
+        for (chunk_key, grid_position) in present_chunks(self, path_name, array_meta)? {
+            let candidate =
+                match current_stats::<_, T>(self, path_name, &chunk_key, &grid_position)? {
+                    Some(Some(stats)) => stats.overlaps(min, max),
+                    Some(None) => false,
+                    None => true,
+                };
+            if candidate {
+                candidates.push(grid_position);
+            }
+        }
+        Ok(candidates)
+    }
+
+    fn array_min_max<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Option<ChunkStats<T>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd,
+    {
+        let chunks = present_chunks(self, path_name, array_meta)?;
+        let mut array_stats: Option<ChunkStats<T>> = None;
+        if (chunks.len() as u64) < array_meta.get_num_chunks() {
+            let fill_value: T = array_meta.get_effective_fill_value()?;
+            array_stats = ChunkStats::of(std::iter::once(&fill_value));
+        }
+
+        for (chunk_key, grid_position) in chunks {
+            let stats = match current_stats(self, path_name, &chunk_key, &grid_position)? {
+                Some(stats) => stats,
+                None => self
+                    .read_chunk::<T>(path_name, array_meta, grid_position)?
+                    .and_then(|chunk| in_bounds_stats(array_meta, &chunk)),
+            };
+            match (&mut array_stats, stats) {
+                (Some(array_stats), Some(stats)) => array_stats.merge(&stats),
+                (None, stats) => array_stats = stats,
+                (_, None) => {}
+            }
+        }
+        Ok(array_stats)
+    }
+}
+
+impl<S: ReadableStore + ListableStore + WriteableStore + Hierarchy> HierarchyChunkStatsWriter
+    for S
+{
+    fn write_chunk_with_stats<T, C>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunk: &SliceDataChunk<T, C>,
+    ) -> Result<(), Error>
+    where
+        SliceDataChunk<T, C>: WriteableDataChunk,
+        T: ReflectedType + PartialOrd + Serialize,
+        C: AsRef<[T]>,
+    {
+        self.write_chunk(path_name, array_meta, chunk)?;
+        let grid_position = chunk.get_grid_position();
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        store_stats(
+            self,
+            path_name,
+            &chunk_key,
+            grid_position,
+            in_bounds_stats(array_meta, chunk),
+        )
+    }
+
+    fn backfill_chunk_stats<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<u64, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        T: ReflectedType + PartialOrd + Serialize,
+    {
+        check_writeable(array_meta)?;
+        let mut read = 0;
+        for (chunk_key, grid_position) in present_chunks(self, path_name, array_meta)? {
+            if current_stats::<_, T>(self, path_name, &chunk_key, &grid_position)?.is_some() {
+                continue;
+            }
+            if let Some(chunk) =
+                self.read_chunk::<T>(path_name, array_meta, grid_position.clone())?
+            {
+                let stats = in_bounds_stats(array_meta, &chunk);
+                store_stats(self, path_name, &chunk_key, &grid_position, stats)?;
+                read += 1;
+            }
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::Order;
+
+    #[test]
+    fn test_chunk_stats() {
+        let dir = tempdir::TempDir::new("rust_zarr_stats_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![3, 3], i32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .chunk_memory_layout(Order::RowMajor)
+            .fill_value(-5)
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        assert_eq!(
+            h.array_min_max::<i32>("a", &array_meta).unwrap(),
+            Some(ChunkStats { min: -5, max: -5 })
+        );
+
+        // The overhang of the edge chunk at [0, 1] is not counted.
+        let chunk = SliceDataChunk::new(smallvec![0, 1], vec![3, 100, 4, 100]);
+        h.write_chunk_with_stats("a", &array_meta, &chunk).unwrap();
+        assert_eq!(
+            h.read_chunk_stats::<i32>("a", &array_meta, &[0, 1])
+                .unwrap(),
+            Some(ChunkStats { min: 3, max: 4 })
+        );
+        let chunk = SliceDataChunk::new(smallvec![0, 0], vec![1, 2, 3, 4]);
+        h.write_chunk("a", &array_meta, &chunk).unwrap();
+        assert_eq!(
+            h.read_chunk_stats::<i32>("a", &array_meta, &[0, 0])
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(
+            h.candidate_chunks::<i32>("a", &array_meta, &5, &10)
+                .unwrap(),
+            vec![GridCoord::from(&[0, 0][..])]
+        );
+        assert_eq!(
+            h.array_min_max::<i32>("a", &array_meta).unwrap(),
+            Some(ChunkStats { min: -5, max: 4 })
+        );
+
+        assert_eq!(h.backfill_chunk_stats::<i32>("a", &array_meta).unwrap(), 1);
+        assert_eq!(h.backfill_chunk_stats::<i32>("a", &array_meta).unwrap(), 0);
+        assert!(h
+            .candidate_chunks::<i32>("a", &array_meta, &5, &10)
+            .unwrap()
+            .is_empty());
+
+        // Stats of chunks which no longer exist are ignored.
+        h.erase("/data/root/a/c0/1").unwrap();
+        assert!(ReadableStore::exists(&h, "/stats/root/a.stats/c0/1").unwrap());
+        assert_eq!(
+            h.read_chunk_stats::<i32>("a", &array_meta, &[0, 1])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            h.array_min_max::<i32>("a", &array_meta).unwrap(),
+            Some(ChunkStats { min: -5, max: 4 })
+        );
+    }
+
+    #[test]
+    fn test_chunk_stats_keys() {
+        let dir = tempdir::TempDir::new("rust_zarr_stats_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], i32::ZARR_TYPE)
+            .chunk_shape(smallvec![2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.create_array("a/stats", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![0], vec![1, 2]);
+        h.write_chunk_with_stats("a", &array_meta, &chunk).unwrap();
+
+        // Stats are neither chunks of the array nor of a child array.
+        assert_eq!(
+            list_chunk_keys(&h, "a", &array_meta).unwrap(),
+            vec![("/data/root/a/c0".to_owned(), GridCoord::from(&[0][..]))]
+        );
+        assert!(list_chunk_keys(&h, "a/stats", &array_meta)
+            .unwrap()
+            .is_empty());
+        let chunk = SliceDataChunk::new(smallvec![0], vec![7, 8]);
+        h.write_chunk_with_stats("a/stats", &array_meta, &chunk)
+            .unwrap();
+        assert_eq!(
+            h.read_chunk_stats::<i32>("a", &array_meta, &[0]).unwrap(),
+            Some(ChunkStats { min: 1, max: 2 })
+        );
+
+        // A rewrite of the same size is noticed even if the stat of the
+        // chunk is unchanged, as with a coarse modification time.
+        let stats_key = "/stats/root/a.stats/c0";
+        let mut stored: serde_json::Value =
+            serde_json::from_reader(h.get(stats_key).unwrap().unwrap()).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![0], vec![5, 6]);
+        h.write_chunk("a", &array_meta, &chunk).unwrap();
+        stored["chunk"] = serde_json::to_value(h.stat("/data/root/a/c0").unwrap()).unwrap();
+        h.set(stats_key, |writer| {
+            Ok(serde_json::to_writer(writer, &stored)?)
+        })
+        .unwrap();
+        assert_eq!(
+            h.read_chunk_stats::<i32>("a", &array_meta, &[0]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stats_of() {
+        assert_eq!(
+            ChunkStats::of(&[2.0, f64::NAN, -1.0]),
+            Some(ChunkStats {
+                min: -1.0,
+                max: 2.0
+            })
+        );
+        assert_eq!(ChunkStats::<f64>::of(&[f64::NAN]), None);
+        let stats = ChunkStats { min: 1, max: 3 };
+        assert!(stats.overlaps(&3, &5));
+        assert!(!stats.overlaps(&4, &5));
+    }
+}
@@ -1,15 +1,38 @@
+//! Store capabilities underlying Zarr hierarchies.
+//!
+//! Each capability of a key-value store is a separate trait, so backends
+//! only implement what they support and callers can require capabilities at
+//! compile time:
+//!
+//! - [`ReadableStore`]: reading whole values.
+//! - [`PartialReadStore`]: reading byte ranges of values.
+//! - [`ListableStore`]: listing keys.
+//! - [`WriteableStore`]: writing and erasing values.
+//! - [`ConditionalWriteStore`]: writing values only if unchanged since read.
+//!
+//! Stores which are also a [`Hierarchy`] are Zarr hierarchies, with reading,
+//! listing and writing provided by blanket implementations of
+//! [`HierarchyReader`], [`HierarchyLister`] and [`HierarchyWriter`].
+
 use std::io::{
     Error,
     ErrorKind,
     Read,
     Write,
 };
+use std::time::SystemTime;
 
 use semver::VersionReq;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use serde_json::Value;
 
 use crate::{
     canonicalize_path,
+    check_chunk_shape,
+    check_extensions,
     chunk::{
         DataChunk,
         ReadableDataChunk,
@@ -17,6 +40,7 @@ use crate::{
         VecDataChunk,
         WriteableDataChunk,
     },
+    compression::CompressionType,
     ArrayMetadata,
     GridCoord,
     GroupMetadata,
@@ -26,6 +50,7 @@ use crate::{
     HierarchyWriter,
     JsonObject,
     MetadataError,
+    NodeKind,
     ReflectedType,
     StoreNodeMetadata,
 };
@@ -40,6 +65,217 @@ pub trait ReadableStore {
 
     /// TODO: not in zarr spec
     fn uri(&self, key: &str) -> Result<String, Error>;
+
+    /// Size in bytes of the value at a key, or `None` if it does not exist.
+    ///
+    /// The default implementation reads the whole value.
+    ///
+    /// TODO: not in zarr spec
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.get(key)?
+            .map(|mut reader| std::io::copy(&mut reader, &mut std::io::sink()))
+            .transpose()
+    }
+
+    /// Size and version of the value at a key, or `None` if it does not
+    /// exist, without reading the value where the store supports it.
+    ///
+    /// The default implementation has only the size.
+    ///
+    /// TODO: not in zarr spec
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        Ok(self.size(key)?.map(KeyStat::new))
+    }
+
+    /// Whether the value at a key may not be modified, for example because
+    /// of filesystem permissions.
+    ///
+    /// TODO: not in zarr spec
+    fn is_read_only(&self, _key: &str) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Read the whole values at several keys, in the order of the keys, with
+    /// `None` for keys which do not exist.
+    ///
+    /// The default implementation reads one value at a time. Stores with a
+    /// high latency per request read the values in parallel instead.
+    ///
+    /// TODO: not in zarr spec
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        keys.iter().map(|key| read_value(self, key)).collect()
+    }
+}
+
+/// Read the whole value at a key.
+pub(crate) fn read_value<S: ReadableStore + ?Sized>(
+    store: &S,
+    key: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    store
+        .get(key)?
+        .map(|mut reader| {
+            let mut value = vec![];
+            reader.read_to_end(&mut value)?;
+            Ok(value)
+        })
+        .transpose()
+}
+
+/// Apply a fallible function to each of several items using the configured
+/// [`concurrency`](crate::config::Config::concurrency), returning the
+/// results in the order of the items or the first error.
+#[cfg(any(
+    feature = "filesystem",
+    feature = "http",
+    feature = "ipfs",
+    feature = "sftp",
+    feature = "webdav",
+    feature = "webhdfs"
+))]
+pub(crate) fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    f: impl Fn(&T) -> Result<R, Error> + Sync,
+) -> Result<Vec<R>, Error> {
+    use std::sync::atomic::{
+        AtomicBool,
+        Ordering,
+    };
+    use std::sync::Mutex;
+
+    let workers = std::cmp::min(crate::config::config().concurrency.max(1), items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let queue = Mutex::new(items.iter().enumerate());
+    let failed = AtomicBool::new(false);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    while !failed.load(Ordering::SeqCst) {
+                        let next = queue.lock().unwrap().next();
+                        let (i, item) = match next {
+                            Some(entry) => entry,
+                            None => break,
+                        };
+                        match f(item) {
+                            Ok(result) => results.push((i, result)),
+                            Err(e) => {
+                                failed.store(true, Ordering::SeqCst);
+                                return Err(e);
+                            }
+                        }
+                    }
+                    Ok(results)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Result<Vec<_>, Error>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect();
+    results.sort_unstable_by_key(|(i, _)| *i);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// The keys and prefixes directly below a prefix, as
+/// [`ListableStore::list_dir`] lists them, of stores listing a sorted set of
+/// keys rather than directories.
+#[cfg(any(
+    feature = "ipfs",
+    feature = "pack",
+    feature = "redis",
+    feature = "sled",
+    feature = "sqlite",
+    feature = "tar"
+))]
+pub(crate) fn list_dir_of_keys<'a>(
+    prefix: &str,
+    sorted_keys: impl IntoIterator<Item = &'a str>,
+) -> (Vec<String>, Vec<String>) {
+    let dir = if prefix.is_empty() || prefix.ends_with('/') {
+        prefix.to_owned()
+    } else {
+        format!("{}/", prefix)
+    };
+    let mut keys = vec![];
+    let mut prefixes: Vec<String> = vec![];
+    for name in sorted_keys
+        .into_iter()
+        .filter_map(|key| key.strip_prefix(dir.as_str()))
+    {
+        match name.split_once('/') {
+            Some((child, _)) => {
+                let child = format!("{}{}/", prefix, child);
+                // Keys below a child are consecutive, since they are sorted.
+                if prefixes.last() != Some(&child) {
+                    prefixes.push(child);
+                }
+            }
+            None => keys.push(format!("{}{}", prefix, name)),
+        }
+    }
+    (keys, prefixes)
+}
+
+/// Store metadata about the value at a key, from [`ReadableStore::stat`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyStat {
+    /// Size of the value in bytes.
+    pub size: u64,
+    pub last_modified: Option<SystemTime>,
+    /// Opaque tag which changes whenever the value does, such as an HTTP
+    /// `ETag`, for stores which have one without reading the value.
+    pub etag: Option<String>,
+}
+
+impl KeyStat {
+    /// Stat of a value of which only the size is known.
+    pub fn new(size: u64) -> Self {
+        KeyStat {
+            size,
+            last_modified: None,
+            etag: None,
+        }
+    }
+
+    /// Whether two stats of a key may be of different values, judging by
+    /// the entity tag if both have one, else by the modification time if
+    /// both have one, and otherwise by size alone.
+    pub fn may_differ(&self, other: &KeyStat) -> bool {
+        if self.size != other.size {
+            return true;
+        }
+        if let (Some(a), Some(b)) = (&self.etag, &other.etag) {
+            return a != b;
+        }
+        if let (Some(a), Some(b)) = (self.last_modified, other.last_modified) {
+            return a != b;
+        }
+        false
+    }
+}
+
+/// Stores which can read part of a value without reading all of it.
+pub trait PartialReadStore: ReadableStore {
+    /// Read up to `length` bytes of the value at a key starting at `offset`,
+    /// or through the end of the value if `length` is `None`, or `None` if
+    /// the key does not exist.
+    ///
+    /// Ranges extending past the end of the value are truncated.
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error>;
 }
 
 pub trait ListableStore {
@@ -81,6 +317,96 @@ pub trait WriteableStore {
 
     // TODO
     fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error>;
+
+    /// Write the values of several keys.
+    ///
+    /// The default implementation writes one value at a time, stopping at
+    /// the first error. Stores with a high latency per request write the
+    /// values in parallel instead, so after an error which values were
+    /// written is unspecified.
+    ///
+    /// TODO: not in zarr spec
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        for (key, value) in pairs {
+            self.set(key, |mut writer| writer.write_all(value))?;
+        }
+        Ok(())
+    }
+
+    /// Make the values with keys below a prefix read-only in the store, so
+    /// that writing them fails, where the store supports it.
+    ///
+    /// Returns whether the store supports it. The default implementation
+    /// does not.
+    ///
+    /// TODO: not in zarr spec
+    fn make_read_only(&self, _key_prefix: &str) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Update the JSON metadata document at a key without losing concurrent
+    /// updates by other writers, where the store supports conditional
+    /// writes, see [`ConditionalWriteStore`].
+    ///
+    /// Returns whether the store supports it, in which case [`HierarchyWriter`]
+    /// metadata updates go through it. The default implementation does not,
+    /// and writes nothing.
+    ///
+    /// TODO: not in zarr spec
+    fn update_metadata_document(
+        &self,
+        _key: &str,
+        _update: &mut dyn FnMut(&mut JsonObject) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+/// Stores which can write a value only if it has not changed since it was
+/// read, so that concurrent writers do not lose each other's updates.
+pub trait ConditionalWriteStore: ReadableStore + WriteableStore {
+    /// Read the whole value at a key along with its entity tag, an opaque
+    /// string which changes whenever the value does.
+    fn get_with_etag(&self, key: &str) -> Result<Option<(Vec<u8>, String)>, Error>;
+
+    /// Write a value only if the key does not exist.
+    ///
+    /// Returns `false` without writing if the key exists.
+    fn put_if_not_exists<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<bool, Error>;
+
+    /// Write a value only if the entity tag of the value at the key is still
+    /// `etag`.
+    ///
+    /// Returns `false` without writing if the value has changed or no longer
+    /// exists.
+    fn put_if_match<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        etag: &str,
+        value: F,
+    ) -> Result<bool, Error>;
+}
+
+/// Entity tag of a value derived from its contents, for stores without
+/// native version tags.
+///
+/// This always hashes with [`Fnv1a`](crate::hash::Fnv1a), whatever the
+/// configured [`HashAlgorithm`](crate::hash::HashAlgorithm), so that
+/// processes configured differently agree on the tags of shared stores.
+pub fn content_etag(value: &[u8]) -> String {
+    crate::hash::Hasher::etag(&crate::hash::Fnv1a, value)
+}
+
+/// 64-bit FNV-1a hash, which unlike the standard library's hashers is stable
+/// across platforms and releases, so can be persisted.
+pub(crate) fn stable_hash(value: &[u8]) -> u64 {
+    value.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 /// TODO
@@ -107,26 +433,392 @@ pub trait WriteableStore {
 /// assert_eq!(get_chunk_key("/foo/baz", &meta, &[]), "/data/root/foo/baz/c");
 /// ```
 pub fn get_chunk_key(base_path: &str, array_meta: &ArrayMetadata, grid_position: &[u64]) -> String {
-    use std::fmt::Write;
     // TODO: normalize relative or absolute paths
     let canon_path = canonicalize_path(base_path);
-    let mut chunk_key = if canon_path.is_empty() {
-        format!("{}/c", crate::DATA_ROOT_PATH,)
+    let separator = array_meta.get_separator().unwrap_or("/");
+    // Keys are built with exact-ish capacity and without the formatting
+    // machinery, since region reads and writes build one per chunk.
+    let mut chunk_key = String::with_capacity(
+        crate::DATA_ROOT_PATH.len()
+            + canon_path.len()
+            + 3
+            + grid_position.len() * (MAX_DECIMAL_DIGITS / 2 + separator.len()),
+    );
+    chunk_key.push_str(crate::DATA_ROOT_PATH);
+    if !canon_path.is_empty() {
+        chunk_key.push('/');
+        chunk_key.push_str(canon_path);
+    }
+    chunk_key.push_str("/c");
+
+    write_chunk_coords(&mut chunk_key, grid_position, separator);
+    chunk_key
+}
+
+/// Number of decimal digits of `u64::MAX`.
+const MAX_DECIMAL_DIGITS: usize = 20;
+
+/// Append the decimal digits of an integer.
+fn push_decimal(key: &mut String, mut n: u64) {
+    let mut digits = [0u8; MAX_DECIMAL_DIGITS];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    // Digits are ASCII.
+    key.push_str(std::str::from_utf8(&digits[start..]).expect("ASCII digits"));
+}
+
+/// Parse the decimal digits of a chunk coordinate, rejecting signs,
+/// whitespace and overflow.
+pub(crate) fn parse_decimal(digits: &str) -> Option<u64> {
+    if digits.is_empty() {
+        return None;
+    }
+    digits.bytes().try_fold(0u64, |n, digit| {
+        if digit.is_ascii_digit() {
+            n.checked_mul(10)?.checked_add(u64::from(digit - b'0'))
+        } else {
+            None
+        }
+    })
+}
+
+fn write_chunk_coords(chunk_key: &mut String, grid_position: &[u64], separator: &str) {
+    for (i, &coord) in grid_position.iter().enumerate() {
+        if i > 0 {
+            chunk_key.push_str(separator)
+        }
+        push_decimal(chunk_key, coord);
+    }
+}
+
+/// Formats chunk keys of one array, for loops over many of its chunks.
+///
+/// The array's key prefix and separator are resolved once, so each key
+/// costs one allocation and its digits, rather than path canonicalization
+/// and formatting of the whole key. [`ChunkKeyFormatter::write_key`] reuses
+/// a caller's buffer to avoid even the allocation.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::storage::{
+///     get_chunk_key,
+///     ChunkKeyFormatter,
+/// };
+/// use zarr::smallvec::smallvec;
+/// let meta = ArrayMetadata::new(
+///     smallvec![50, 40, 30],
+///     smallvec![11, 10, 10],
+///     i8::ZARR_TYPE,
+///     zarr::compression::CompressionType::default(),
+/// );
+/// let keys = ChunkKeyFormatter::new("/foo/baz", &meta);
+/// assert_eq!(keys.key(&[1, 2, 3]), get_chunk_key("/foo/baz", &meta, &[1, 2, 3]));
+///
+/// let mut key = String::new();
+/// keys.write_key(&mut key, &[4, 0, 2]);
+/// assert_eq!(key, "/data/root/foo/baz/c4/0/2");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkKeyFormatter<'a> {
+    prefix: String,
+    separator: &'a str,
+    /// Whether chunks may instead be stored with a `.` separator.
+    dotted_fallback: bool,
+}
+
+impl<'a> ChunkKeyFormatter<'a> {
+    pub fn new(path_name: &str, array_meta: &'a ArrayMetadata) -> Self {
+        ChunkKeyFormatter {
+            prefix: get_chunk_key(path_name, array_meta, &[]),
+            separator: array_meta.get_separator().unwrap_or("/"),
+            dotted_fallback: array_meta.get_separator().is_none() && array_meta.get_ndim() >= 2,
+        }
+    }
+
+    /// The key of a chunk, as given by [`get_chunk_key`].
+    pub fn key(&self, grid_position: &[u64]) -> String {
+        let mut key = String::new();
+        self.write_key(&mut key, grid_position);
+        key
+    }
+
+    /// Replace the contents of a buffer with the key of a chunk.
+    pub fn write_key(&self, key: &mut String, grid_position: &[u64]) {
+        self.write_key_with(key, grid_position, self.separator)
+    }
+
+    fn write_key_with(&self, key: &mut String, grid_position: &[u64], separator: &str) {
+        key.clear();
+        key.reserve(
+            self.prefix.len() + grid_position.len() * (MAX_DECIMAL_DIGITS / 2 + separator.len()),
+        );
+        key.push_str(&self.prefix);
+        write_chunk_coords(key, grid_position, separator);
+    }
+
+    /// The key of a chunk with a `.` separator, as older stores wrote.
+    fn dotted_key(&self, grid_position: &[u64]) -> String {
+        let mut key = String::new();
+        self.write_key_with(&mut key, grid_position, ".");
+        key
+    }
+
+    /// Settle on the separator an array's chunks are stored with by probing
+    /// for one of them, so that the keys of many chunks are found without
+    /// probing for each. This assumes the array's chunks share a separator.
+    pub(crate) fn resolve_separator<S: ReadableStore + ?Sized>(
+        &mut self,
+        store: &S,
+        grid_position: &[u64],
+    ) -> Result<(), Error> {
+        if self.dotted_fallback {
+            if !store.exists(&self.key(grid_position))?
+                && store.exists(&self.dotted_key(grid_position))?
+            {
+                self.separator = ".";
+            }
+            self.dotted_fallback = false;
+        }
+        Ok(())
+    }
+
+    /// Find the key a chunk is stored at, see [`find_chunk_key`].
+    pub(crate) fn find<S: ReadableStore + ?Sized>(
+        &self,
+        store: &S,
+        grid_position: &[u64],
+    ) -> Result<String, Error> {
+        let chunk_key = self.key(grid_position);
+        if !self.dotted_fallback || store.exists(&chunk_key)? {
+            return Ok(chunk_key);
+        }
+        let dotted_key = self.dotted_key(grid_position);
+        if store.exists(&dotted_key)? {
+            Ok(dotted_key)
+        } else {
+            Ok(chunk_key)
+        }
+    }
+}
+
+/// Find the key a chunk is stored at.
+///
+/// This is the key given by [`get_chunk_key`] unless the array's metadata
+/// does not specify a separator, in which case the chunk may instead be
+/// stored with a `.` separator, as older stores did. If it is stored with
+/// neither, the key it would be written at is returned.
+pub(crate) fn find_chunk_key<S: ReadableStore + ?Sized>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    grid_position: &[u64],
+) -> Result<String, Error> {
+    ChunkKeyFormatter::new(path_name, array_meta).find(store, grid_position)
+}
+
+/// Read the values of several chunks, at the keys [`find_chunk_key`] would
+/// find, with batched reads rather than probing for each chunk's key.
+fn get_chunk_values<S: ReadableStore + ?Sized>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    grid_positions: &[GridCoord],
+) -> Result<Vec<Option<Vec<u8>>>, Error> {
+    let formatter = ChunkKeyFormatter::new(path_name, array_meta);
+    let keys: Vec<String> = grid_positions
+        .iter()
+        .map(|grid_position| formatter.key(grid_position))
+        .collect();
+    let mut values = store.get_many(&keys)?;
+    if formatter.dotted_fallback {
+        let missing: Vec<usize> = (0..values.len()).filter(|&i| values[i].is_none()).collect();
+        if !missing.is_empty() {
+            let dotted_keys: Vec<String> = missing
+                .iter()
+                .map(|&i| formatter.dotted_key(&grid_positions[i]))
+                .collect();
+            for (i, value) in missing.into_iter().zip(store.get_many(&dotted_keys)?) {
+                values[i] = value;
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Fail with `InvalidInput` if any of several grid positions is outside an
+/// array.
+fn check_grid_positions(
+    array_meta: &ArrayMetadata,
+    grid_positions: &[GridCoord],
+) -> Result<(), Error> {
+    match grid_positions
+        .iter()
+        .find(|grid_position| !array_meta.in_bounds(grid_position))
+    {
+        Some(grid_position) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Chunk grid position {:?} is out of bounds", grid_position),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Read a chunk's bytes as given to its compressor when written, that is
+/// decompressed but still filtered, or `None` if the chunk is absent.
+///
+/// Fails if the grid position is outside the array.
+pub(crate) fn read_decompressed_chunk<S: ReadableStore + Hierarchy + ?Sized>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    grid_position: &[u64],
+) -> Result<Option<Vec<u8>>, Error> {
+    if !array_meta.in_bounds(&grid_position.into()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Chunk grid position {:?} is out of bounds", grid_position),
+        ));
+    }
+    let chunk_key = find_chunk_key(store, path_name, array_meta, grid_position)?;
+    let mut stored = Vec::new();
+    match store.get(&chunk_key)? {
+        Some(mut reader) => reader.read_to_end(&mut stored)?,
+        None => return Ok(None),
+    };
+
+    let compressor = array_meta.chunk_compressor(grid_position)?;
+    let compressor = if crate::config::config().detect_chunk_compression {
+        compressor.resolve_for_header(&stored)
     } else {
-        format!("{}/{}/c", crate::DATA_ROOT_PATH, canon_path)
+        compressor.into_owned()
     };
+    let data_type = array_meta.get_data_type().effective_type()?;
+    let encoded_type = crate::filter::encoded_type(&array_meta.filters, &data_type)?;
+    let limit = crate::compression::max_decompressed_chunk_len(array_meta, &encoded_type)?;
+    let mut decompressed = Vec::new();
+    compressor
+        .limited_decoder(&stored[..], &encoded_type, limit)?
+        .read_to_end(&mut decompressed)?;
+    Ok(Some(decompressed))
+}
 
-    for (i, coord) in grid_position.iter().enumerate() {
-        write!(chunk_key, "{}", coord).unwrap();
-        if i < grid_position.len() - 1 {
-            chunk_key.push_str(&array_meta.chunk_grid.separator)
-        }
+/// Parse the grid position of a chunk of an array from its key, the
+/// inverse of [`get_chunk_key`].
+///
+/// Returns `None` if the key is not a chunk key of this array, for example
+/// if it belongs to a nested array.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::storage::parse_chunk_key;
+/// use zarr::smallvec::smallvec;
+/// let meta = ArrayMetadata::new(
+///     smallvec![50, 40, 30],
+///     smallvec![11, 10, 10],
+///     i8::ZARR_TYPE,
+///     zarr::compression::CompressionType::default(),
+/// );
+/// assert_eq!(
+///     parse_chunk_key("/foo/baz", &meta, "/data/root/foo/baz/c1/2/3"),
+///     Some(smallvec![1, 2, 3]),
+/// );
+/// assert_eq!(parse_chunk_key("/foo/baz", &meta, "/data/root/foo/baz/c1/2"), None);
+/// assert_eq!(parse_chunk_key("/foo", &meta, "/data/root/foo/baz/c1/2/3"), None);
+/// assert_eq!(parse_chunk_key("/foo/baz", &meta, "/data/root/foo/baz/c1/+2/3"), None);
+/// let far = zarr::storage::get_chunk_key("/foo/baz", &meta, &[0, 7, u64::MAX]);
+/// assert_eq!(
+///     parse_chunk_key("/foo/baz", &meta, &far),
+///     Some(smallvec![0, 7, u64::MAX]),
+/// );
+/// assert_eq!(
+///     parse_chunk_key("/foo/baz", &meta, "/data/root/foo/baz/c0/7/18446744073709551616"),
+///     None,
+/// );
+/// ```
+pub fn parse_chunk_key(
+    base_path: &str,
+    array_meta: &ArrayMetadata,
+    key: &str,
+) -> Option<GridCoord> {
+    let canon_path = canonicalize_path(base_path);
+    let rest = key.strip_prefix(crate::DATA_ROOT_PATH)?;
+    let rest = if canon_path.is_empty() {
+        rest
+    } else {
+        rest.strip_prefix('/')?.strip_prefix(canon_path)?
+    };
+    let coords = rest.strip_prefix("/c")?;
+    let ndim = array_meta.get_ndim();
+    if ndim == 0 {
+        return if coords.is_empty() {
+            Some(GridCoord::new())
+        } else {
+            None
+        };
     }
+    let grid_position = match array_meta.get_separator() {
+        Some(separator) => coords
+            .split(separator)
+            .map(parse_decimal)
+            .collect::<Option<GridCoord>>()?,
+        None => coords
+            .split(['/', '.'])
+            .map(parse_decimal)
+            .collect::<Option<GridCoord>>()?,
+    };
+    if grid_position.len() == ndim {
+        Some(grid_position)
+    } else {
+        None
+    }
+}
 
-    chunk_key
+/// List the keys and grid positions of all chunks of an array present in a
+/// store.
+pub(crate) fn list_chunk_keys<S: ListableStore + Hierarchy>(
+    store: &S,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+) -> Result<Vec<(String, GridCoord)>, Error> {
+    let data_prefix = format!(
+        "{}/",
+        store.data_path_key(path_name).to_str().expect("TODO")
+    );
+    let keys = match store.list_prefix(&data_prefix) {
+        Ok(keys) => keys,
+        Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e),
+    };
+
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            parse_chunk_key(path_name, array_meta, &key).map(|grid_position| (key, grid_position))
+        })
+        .collect())
 }
 
-const ATTRIBUTES_NAME: &str = "attributes";
+pub(crate) const ATTRIBUTES_NAME: &str = "attributes";
+const READ_ONLY_NAME: &str = "read_only";
+const EXTENSIONS_NAME: &str = "extensions";
+
+pub(crate) fn check_writeable(array_meta: &ArrayMetadata) -> Result<(), Error> {
+    if array_meta.is_read_only() {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Array is read-only",
+        ))
+    } else {
+        Ok(())
+    }
+}
 
 fn merge_top_level(a: &mut Value, b: JsonObject) {
     match a {
@@ -141,6 +833,37 @@ fn merge_top_level(a: &mut Value, b: JsonObject) {
     }
 }
 
+/// Buffer a whole metadata document, since parsing from a slice is several
+/// times faster than parsing byte by byte from a reader.
+fn read_document<R: Read>(mut value_reader: R) -> Result<Vec<u8>, Error> {
+    let mut document = Vec::new();
+    value_reader.read_to_end(&mut document)?;
+    Ok(document)
+}
+
+/// Parse and check an array metadata document read from a key of a store.
+pub(crate) fn read_array_metadata<S: ReadableStore + ?Sized, R: Read>(
+    store: &S,
+    array_key: &str,
+    value_reader: R,
+) -> Result<ArrayMetadata, Error> {
+    let mut metadata: ArrayMetadata = serde_json::from_slice(&read_document(value_reader)?)?;
+    metadata.read_only |= store.is_read_only(array_key)?;
+    // TODO: erring immediately when encountering unknown extensions, while
+    // it may be more appropriate to do so only when doing chunk IO.
+    // TODO: returning an io::Error wrapped custom error, rather than other
+    // way around.
+    check_extensions(&metadata.extensions)?;
+    check_chunk_shape(&metadata)?;
+    Ok(metadata)
+}
+
+pub(crate) fn read_group_metadata<R: Read>(value_reader: R) -> Result<GroupMetadata, Error> {
+    let metadata: GroupMetadata = serde_json::from_slice(&read_document(value_reader)?)?;
+    check_extensions(&metadata.extensions)?;
+    Ok(metadata)
+}
+
 impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
     fn get_version(&self) -> Result<VersionReq, Error> {
         let vers_str = self
@@ -164,42 +887,64 @@ impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
 
     fn get_array_metadata(&self, path_name: &str) -> Result<ArrayMetadata, Error> {
         let array_path = self.array_metadata_key(path_name);
-        let value_reader = ReadableStore::get(self, &array_path.to_str().expect("TODO"))?
+        let array_key = array_path.to_str().expect("TODO");
+        let value_reader =
+            ReadableStore::get(self, array_key)?.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        read_array_metadata(self, array_key, value_reader)
+    }
+
+    fn get_group_metadata(&self, path_name: &str) -> Result<GroupMetadata, Error> {
+        let group_path = self.group_metadata_key(path_name);
+        let value_reader = ReadableStore::get(self, group_path.to_str().expect("TODO"))?
             .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
-        let metadata: ArrayMetadata = serde_json::from_reader(value_reader)?;
-        // TODO: erring immediately when encountering unknown extensions, while
-        // it may be more appropriate to do so only when doing chunk IO.
-        if let Some(ext) = metadata.extensions.iter().find(|e| e.must_understand) {
-            // TODO: returning an io::Error wrapped custom error, rather than other
-            // way around.
-            return Err(MetadataError::UnknownRequiredExtension(ext.clone()).into());
-        }
-        Ok(metadata)
+        read_group_metadata(value_reader)
     }
 
     fn exists(&self, path_name: &str) -> Result<bool, Error> {
         // TODO: needless path allocs
         // TODO: should follow spec more closely by using `list_dir` for implicit groups.
-        Ok(
-            self.exists(self.array_metadata_key(path_name).to_str().expect("TODO"))?
-                || self.exists(self.group_metadata_key(path_name).to_str().expect("TODO"))?
-                || self.exists(
-                    self.group_metadata_key(path_name)
-                        .with_extension("")
-                        .with_extension("")
-                        .to_str()
-                        .expect("TODO"),
-                )?,
+        Ok(self.node_kind(path_name)?.is_some()
+            || ReadableStore::exists(
+                self,
+                self.group_metadata_key(path_name)
+                    .with_extension("")
+                    .with_extension("")
+                    .to_str()
+                    .expect("TODO"),
+            )?)
+    }
+
+    fn array_exists(&self, path_name: &str) -> Result<bool, Error> {
+        ReadableStore::exists(
+            self,
+            self.array_metadata_key(path_name).to_str().expect("TODO"),
+        )
+    }
+
+    fn group_exists(&self, path_name: &str) -> Result<bool, Error> {
+        ReadableStore::exists(
+            self,
+            self.group_metadata_key(path_name).to_str().expect("TODO"),
         )
     }
 
+    fn node_kind(&self, path_name: &str) -> Result<Option<NodeKind>, Error> {
+        Ok(if self.array_exists(path_name)? {
+            Some(NodeKind::Array)
+        } else if self.group_exists(path_name)? {
+            Some(NodeKind::Group)
+        } else {
+            None
+        })
+    }
+
     fn get_chunk_uri(
         &self,
         path_name: &str,
         array_meta: &ArrayMetadata,
         grid_position: &[u64],
     ) -> Result<String, Error> {
-        let chunk_key = get_chunk_key(path_name, array_meta, &grid_position);
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
         self.uri(&chunk_key)
     }
 
@@ -217,7 +962,7 @@ impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
         assert!(array_meta.in_bounds(&grid_position));
 
         // Construct chunk path string
-        let chunk_key = get_chunk_key(path_name, array_meta, &grid_position);
+        let chunk_key = find_chunk_key(self, path_name, array_meta, &grid_position)?;
 
         // Get key from store
         let value_reader = ReadableStore::get(self, &chunk_key)?;
@@ -234,6 +979,70 @@ impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
             .transpose()
     }
 
+    fn read_chunks<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_positions: &[GridCoord],
+    ) -> Result<Vec<Option<VecDataChunk<T>>>, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+        T: ReflectedType,
+    {
+        check_grid_positions(array_meta, grid_positions)?;
+
+        let values = get_chunk_values(self, path_name, array_meta, grid_positions)?;
+        values
+            .into_iter()
+            .zip(grid_positions)
+            .map(|(value, grid_position)| {
+                value
+                    .map(|value| {
+                        <crate::chunk::DefaultChunk as crate::chunk::DefaultChunkReader<T, _>>::read_chunk(
+                            &value[..],
+                            array_meta,
+                            grid_position.clone(),
+                        )
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    fn read_chunks_into<T, B, F>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_positions: &[GridCoord],
+        chunk: &mut B,
+        mut visit: F,
+    ) -> Result<(), Error>
+    where
+        T: ReflectedType,
+        B: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+        F: FnMut(usize, &mut B, bool) -> Result<(), Error>,
+    {
+        check_grid_positions(array_meta, grid_positions)?;
+
+        let values = get_chunk_values(self, path_name, array_meta, grid_positions)?;
+        for (i, (value, grid_position)) in values.into_iter().zip(grid_positions).enumerate() {
+            let exists = match value {
+                Some(value) => {
+                    <crate::chunk::DefaultChunk as crate::chunk::DefaultChunkReader<T, _>>::read_chunk_into(
+                        &value[..],
+                        array_meta,
+                        grid_position.clone(),
+                        chunk,
+                    )?;
+                    true
+                }
+                None => false,
+            };
+            visit(i, chunk, exists)?;
+        }
+        Ok(())
+    }
+
     fn read_chunk_into<
         T: ReflectedType,
         B: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
@@ -248,7 +1057,7 @@ impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
         assert!(array_meta.in_bounds(&grid_position));
 
         // Construct chunk path string
-        let chunk_key = get_chunk_key(path_name, array_meta, &grid_position);
+        let chunk_key = find_chunk_key(self, path_name, array_meta, &grid_position)?;
 
         // Get key from store
         let value_reader = ReadableStore::get(self, &chunk_key)?;
@@ -266,34 +1075,51 @@ impl<S: ReadableStore + Hierarchy> HierarchyReader for S {
             .transpose()
     }
 
+    fn chunk_exists(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_position: &[u64],
+    ) -> Result<bool, Error> {
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        ReadableStore::exists(self, &chunk_key)
+    }
+
     fn store_chunk_metadata(
         &self,
         path_name: &str,
         array_meta: &ArrayMetadata,
         grid_position: &[u64],
     ) -> Result<Option<StoreNodeMetadata>, Error> {
-        todo!()
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
+        Ok(self.stat(&chunk_key)?.map(|stat| StoreNodeMetadata {
+            created: None,
+            accessed: None,
+            modified: stat.last_modified,
+            size: Some(stat.size),
+        }))
     }
 
     fn list_attributes(&self, path_name: &str) -> Result<JsonObject, Error> {
-        // TODO: wasteful path recomputation
-        let metadata_key =
-            if self.exists(self.array_metadata_key(path_name).to_str().expect("TODO"))? {
-                self.array_metadata_key(path_name)
-            } else if self.exists(self.group_metadata_key(path_name).to_str().expect("TODO"))? {
-                self.group_metadata_key(path_name)
-            } else {
-                return Err(Error::new(
-                    ErrorKind::NotFound,
-                    "Node does not exist at path",
-                ));
-            };
+        // Array and group metadata are fetched together, so that stores with
+        // a high latency per request make one round trip.
+        let keys = [
+            self.array_metadata_key(path_name),
+            self.group_metadata_key(path_name),
+        ]
+        .iter()
+        .map(|key| key.to_str().expect("TODO").to_owned())
+        .collect::<Vec<_>>();
 
         // TODO: determine proper missing behavior for implicit groups.
         // For now return an error.
-        let value_reader = ReadableStore::get(self, &metadata_key.to_str().expect("TODO"))?
-            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
-        let mut value: serde_json::Value = serde_json::from_reader(value_reader)?;
+        let document = self
+            .get_many(&keys)?
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Node does not exist at path"))?;
+        let mut value: serde_json::Value = serde_json::from_slice(&document)?;
         let attrs = match value
             .as_object_mut()
             .and_then(|o| o.remove(ATTRIBUTES_NAME))
@@ -332,9 +1158,7 @@ impl<S: ListableStore + Hierarchy> HierarchyLister for S {
         );
 
         // Remove metadata extensions from keys to partially convert to node paths.
-        let suffix_len = self.get_entry_point_metadata().metadata_key_suffix.len()
-            + crate::ARRAY_METADATA_KEY_EXT.len()
-            + 1;
+        let suffix_len = crate::ARRAY_METADATA_KEY_EXT.len() + 1;
         keys.iter_mut()
             .for_each(|k| k.truncate(k.len() - suffix_len));
 
@@ -342,7 +1166,7 @@ impl<S: ListableStore + Hierarchy> HierarchyLister for S {
         prefixes
             .iter_mut()
             .for_each(|p| p.truncate(p.trim_end_matches('/').len()));
-        keys.extend(prefixes.drain(..));
+        keys.append(&mut prefixes);
 
         // Remove duplicates.
         keys.sort();
@@ -355,6 +1179,81 @@ impl<S: ListableStore + Hierarchy> HierarchyLister for S {
 
         Ok(keys)
     }
+
+    fn initialized_chunks(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<Vec<GridCoord>, Error> {
+        let mut grid_positions: Vec<GridCoord> = list_chunk_keys(self, path_name, array_meta)?
+            .into_iter()
+            .map(|(_, grid_position)| grid_position)
+            .collect();
+        grid_positions.sort();
+        Ok(grid_positions)
+    }
+}
+
+/// Reading of hierarchies whose groups may lack metadata.
+pub trait ZarrImplicitGroupReader: HierarchyReader + HierarchyLister {
+    /// Get metadata for a group, explicit or implicit.
+    ///
+    /// Implicit groups have no metadata of their own but have nodes below
+    /// them, as is common for hierarchies written to object stores, and are
+    /// given default metadata. The root group always exists.
+    fn get_group_metadata_or_implicit(&self, path_name: &str) -> Result<GroupMetadata, Error> {
+        match self.get_group_metadata(path_name) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            result => return result,
+        }
+        if self.array_exists(path_name)? {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "Node at group path is an array",
+            ));
+        }
+        let has_children = match self.list_nodes(path_name) {
+            Ok(children) => !children.is_empty(),
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        if has_children || canonicalize_path(path_name).is_empty() {
+            Ok(GroupMetadata::default())
+        } else {
+            Err(Error::from(ErrorKind::NotFound))
+        }
+    }
+}
+
+impl<T: HierarchyReader + HierarchyLister> ZarrImplicitGroupReader for T {}
+
+/// Write default group metadata for each parent of a node, up to the root,
+/// which has neither group nor array metadata.
+pub(crate) fn materialize_implicit_groups<S: ReadableStore + WriteableStore + Hierarchy>(
+    store: &S,
+    path_name: &str,
+) -> Result<(), Error> {
+    let path_name = canonicalize_path(path_name);
+    if path_name.is_empty() {
+        return Ok(());
+    }
+    let parents =
+        std::iter::once("").chain(path_name.match_indices('/').map(|(i, _)| &path_name[..i]));
+    for parent in parents {
+        let group_key = store.group_metadata_key(parent);
+        let group_key = group_key.to_str().expect("TODO");
+        if !ReadableStore::exists(store, group_key)?
+            && !ReadableStore::exists(
+                store,
+                store.array_metadata_key(parent).to_str().expect("TODO"),
+            )?
+        {
+            store.set(group_key, |writer| {
+                Ok(serde_json::to_writer(writer, &GroupMetadata::default())?)
+            })?;
+        }
+    }
+    Ok(())
 }
 
 impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
@@ -376,25 +1275,18 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
                 ));
             };
 
-        // TODO: race condition
-        let value_reader = ReadableStore::get(self, &metadata_key.to_str().expect("TODO"))?
-            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
-        let existing: JsonObject = serde_json::from_reader(value_reader)?;
-
-        // TODO: determine whether attribute merging is still necessary for zarr
-        let mut merged = existing.clone();
-        match merged.get_mut(ATTRIBUTES_NAME) {
-            Some(merged_attr) => merge_top_level(merged_attr, attributes),
-            None => {
-                merged.insert(ATTRIBUTES_NAME.into(), Value::Object(attributes));
+        let metadata_key = metadata_key.to_str().expect("TODO");
+        let mut merge = |metadata: &mut JsonObject| {
+            // TODO: determine whether attribute merging is still necessary for zarr
+            match metadata.get_mut(ATTRIBUTES_NAME) {
+                Some(merged_attr) => merge_top_level(merged_attr, attributes.clone()),
+                None => {
+                    metadata.insert(ATTRIBUTES_NAME.into(), Value::Object(attributes.clone()));
+                }
             }
-        }
-        if merged != existing {
-            self.set(metadata_key.to_str().expect("TODO"), |writer| {
-                Ok(serde_json::to_writer(writer, &merged)?)
-            })?;
-        }
-        Ok(())
+            Ok(())
+        };
+        rewrite_metadata(self, metadata_key, &mut merge)
     }
 
     fn create_group(&self, path_name: &str) -> Result<(), Error> {
@@ -413,6 +1305,9 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
         } else if self.exists(metadata_key.to_str().expect("TODO"))? {
             Ok(())
         } else {
+            if crate::config::config().materialize_implicit_groups {
+                materialize_implicit_groups(self, path_name)?;
+            }
             self.set(metadata_key.to_str().expect("TODO"), |writer| {
                 Ok(serde_json::to_writer(writer, &GroupMetadata::default())?)
             })
@@ -426,6 +1321,7 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
         // if let Some(parent) = path_buf.parent() {
         //     self.create_group(parent.to_str().expect("TODO"))?;
         // }
+        check_chunk_shape(array_meta)?;
         let metadata_key = self.array_metadata_key(path_name);
         if self.exists(self.group_metadata_key(path_name).to_str().expect("TODO"))?
             || self.exists(metadata_key.to_str().expect("TODO"))?
@@ -435,13 +1331,37 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
                 "Node already exists at array path",
             ))
         } else {
+            if crate::config::config().materialize_implicit_groups {
+                materialize_implicit_groups(self, path_name)?;
+            }
             self.set(metadata_key.to_str().expect("TODO"), |writer| {
                 Ok(serde_json::to_writer(writer, array_meta)?)
             })
         }
     }
 
+    fn seal(&self, path_name: &str) -> Result<ArrayMetadata, Error> {
+        let metadata_key = self.array_metadata_key(path_name);
+        let metadata_key = metadata_key.to_str().expect("TODO");
+        let value_reader = ReadableStore::get(self, metadata_key)?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Array does not exist at path"))?;
+        let mut document: JsonObject = serde_json::from_reader(value_reader)?;
+        if document.get(READ_ONLY_NAME) != Some(&Value::Bool(true)) {
+            document.insert(READ_ONLY_NAME.into(), Value::Bool(true));
+            self.set(metadata_key, |writer| {
+                Ok(serde_json::to_writer(writer, &document)?)
+            })?;
+        }
+
+        self.make_read_only(self.data_path_key(path_name).to_str().expect("TODO"))?;
+        self.make_read_only(metadata_key)?;
+        self.get_array_metadata(path_name)
+    }
+
     fn remove(&self, path_name: &str) -> Result<(), Error> {
+        if self.exists(self.array_metadata_key(path_name).to_str().expect("TODO"))? {
+            check_writeable(&self.get_array_metadata(path_name)?)?;
+        }
         // TODO: needless allocs
         let metadata_key = self.group_metadata_key(path_name);
         self.erase(metadata_key.to_str().expect("TODO"))?;
@@ -461,7 +1381,8 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
     ) -> Result<(), Error> {
         // TODO convert assert
         // assert!(array_meta.in_bounds(chunk.get_grid_position()));
-        let chunk_key = get_chunk_key(path_name, array_meta, chunk.get_grid_position());
+        check_writeable(array_meta)?;
+        let chunk_key = find_chunk_key(self, path_name, array_meta, chunk.get_grid_position())?;
         self.set(&chunk_key, |writer| {
             <crate::chunk::DefaultChunk as crate::chunk::DefaultChunkWriter<T, _, _>>::write_chunk(
                 writer, array_meta, chunk,
@@ -469,13 +1390,195 @@ impl<S: ReadableStore + WriteableStore + Hierarchy> HierarchyWriter for S {
         })
     }
 
+    fn write_chunks<T: ReflectedType, B: DataChunk<T> + WriteableDataChunk>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunks: &[B],
+    ) -> Result<(), Error> {
+        check_writeable(array_meta)?;
+        let mut formatter = ChunkKeyFormatter::new(path_name, array_meta);
+        if let Some(chunk) = chunks.first() {
+            formatter.resolve_separator(self, chunk.get_grid_position())?;
+        }
+        let pairs = chunks
+            .iter()
+            .map(|chunk| {
+                let chunk_key = formatter.find(self, chunk.get_grid_position())?;
+                let mut value = vec![];
+                <crate::chunk::DefaultChunk as crate::chunk::DefaultChunkWriter<T, _, _>>::write_chunk(
+                    &mut value, array_meta, chunk,
+                )?;
+                Ok((chunk_key, value))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.put_many(&pairs)
+    }
+
+    fn write_chunk_with_compression<T: ReflectedType, B: DataChunk<T> + WriteableDataChunk>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        chunk: &B,
+        compressor: &CompressionType,
+    ) -> Result<ArrayMetadata, Error> {
+        check_writeable(array_meta)?;
+        let metadata_key = self.array_metadata_key(path_name);
+        let mut recorded = None;
+        rewrite_metadata(
+            self,
+            metadata_key.to_str().expect("TODO"),
+            &mut |metadata| {
+                let mut array_meta: ArrayMetadata =
+                    serde_json::from_value(Value::Object(metadata.clone()))?;
+                array_meta.set_chunk_compressor(chunk.get_grid_position(), compressor)?;
+                metadata.insert(
+                    EXTENSIONS_NAME.into(),
+                    serde_json::to_value(array_meta.get_extensions())?,
+                );
+                recorded = Some(array_meta);
+                Ok(())
+            },
+        )?;
+        let array_meta = recorded.expect("Updated metadata is set");
+        self.write_chunk(path_name, &array_meta, chunk)?;
+        Ok(array_meta)
+    }
+
     fn delete_chunk(
         &self,
         path_name: &str,
         array_meta: &ArrayMetadata,
         grid_position: &[u64],
     ) -> Result<bool, Error> {
-        let chunk_key = get_chunk_key(path_name, array_meta, grid_position);
+        check_writeable(array_meta)?;
+        let chunk_key = find_chunk_key(self, path_name, array_meta, grid_position)?;
         self.erase(&chunk_key)
     }
 }
+
+/// Number of times a conditional metadata update is retried after losing a
+/// race with another writer.
+const CONDITIONAL_UPDATE_ATTEMPTS: usize = 16;
+
+/// Metadata updates which do not lose concurrent updates by other writers.
+///
+/// These read the metadata document, modify it and write it back only if it
+/// is unchanged, retrying from a fresh read otherwise. Unlike
+/// [`HierarchyWriter::set_attributes`], two processes merging attributes
+/// into the same node concurrently each see the other's attributes.
+pub trait ZarrConditionalWriter: ConditionalWriteStore + Hierarchy {
+    /// Merge a map of attributes into those of a group or array.
+    fn merge_attributes(&self, path_name: &str, attributes: JsonObject) -> Result<(), Error> {
+        let array_key = self.array_metadata_key(path_name);
+        let metadata_key = if self.exists(array_key.to_str().expect("TODO"))? {
+            array_key
+        } else {
+            self.group_metadata_key(path_name)
+        };
+        update_metadata(self, metadata_key.to_str().expect("TODO"), |metadata| {
+            match metadata.get_mut(ATTRIBUTES_NAME) {
+                Some(merged_attr) => merge_top_level(merged_attr, attributes.clone()),
+                None => {
+                    metadata.insert(ATTRIBUTES_NAME.into(), Value::Object(attributes.clone()));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Change the shape of an array, returning its updated metadata.
+    ///
+    /// Chunks outside the new bounds are not removed, so reappear if the
+    /// array is grown again.
+    fn resize_array(&self, path_name: &str, shape: GridCoord) -> Result<ArrayMetadata, Error> {
+        let metadata_key = self.array_metadata_key(path_name);
+        let mut resized = None;
+        update_metadata(self, metadata_key.to_str().expect("TODO"), |metadata| {
+            let mut array_meta: ArrayMetadata =
+                serde_json::from_value(Value::Object(metadata.clone()))?;
+            if shape.len() != array_meta.get_ndim() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Wrong number of dimensions",
+                ));
+            }
+            array_meta.shape = shape.clone();
+            check_chunk_shape(&array_meta)?;
+            metadata.insert("shape".into(), serde_json::to_value(&shape)?);
+            resized = Some(array_meta);
+            Ok(())
+        })?;
+        Ok(resized.expect("Updated metadata is set"))
+    }
+}
+
+impl<S: ConditionalWriteStore + Hierarchy> ZarrConditionalWriter for S {}
+
+/// Apply an update to the metadata document at a key, conditionally if the
+/// store supports it and otherwise by reading and rewriting it.
+fn rewrite_metadata<S: ReadableStore + WriteableStore + ?Sized>(
+    store: &S,
+    metadata_key: &str,
+    update: &mut dyn FnMut(&mut JsonObject) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if store.update_metadata_document(metadata_key, update)? {
+        return Ok(());
+    }
+
+    // Without conditional writes, an update by another writer between
+    // reading and writing the document is lost.
+    let value_reader =
+        ReadableStore::get(store, metadata_key)?.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    let existing: JsonObject = serde_json::from_reader(value_reader)?;
+    if existing.get(READ_ONLY_NAME) == Some(&Value::Bool(true)) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "Array is read-only",
+        ));
+    }
+
+    let mut updated = existing.clone();
+    update(&mut updated)?;
+    if updated != existing {
+        store.set(metadata_key, |writer| {
+            Ok(serde_json::to_writer(writer, &updated)?)
+        })?;
+    }
+    Ok(())
+}
+
+/// Apply an update to the metadata document at a key, retrying if another
+/// writer changes it concurrently.
+pub(crate) fn update_metadata<S: ConditionalWriteStore + ?Sized>(
+    store: &S,
+    metadata_key: &str,
+    mut update: impl FnMut(&mut JsonObject) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for _ in 0..CONDITIONAL_UPDATE_ATTEMPTS {
+        let (value, etag) = store
+            .get_with_etag(metadata_key)?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Node does not exist at path"))?;
+        let existing: JsonObject = serde_json::from_slice(&value)?;
+        if existing.get(READ_ONLY_NAME) == Some(&Value::Bool(true)) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Array is read-only",
+            ));
+        }
+
+        let mut updated = existing.clone();
+        update(&mut updated)?;
+        if updated == existing
+            || store.put_if_match(metadata_key, &etag, |writer| {
+                Ok(serde_json::to_writer(writer, &updated)?)
+            })?
+        {
+            return Ok(());
+        }
+    }
+
+    Err(Error::other(
+        "Metadata was modified concurrently by other writers too often",
+    ))
+}
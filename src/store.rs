@@ -1,2 +1,36 @@
+pub mod credentials;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+pub mod faulty;
 #[cfg(feature = "filesystem")]
 pub mod filesystem;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "ipfs")]
+pub mod ipfs;
+pub mod key_transform;
+pub mod observed;
+#[cfg(feature = "pack")]
+pub mod pack;
+pub mod plugin;
+pub mod prefetch;
+pub mod read_only;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod replay;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+#[cfg(feature = "sled")]
+pub mod sled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "tar")]
+pub mod tar;
+pub mod url;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "webdav")]
+pub mod webdav;
+#[cfg(feature = "webhdfs")]
+pub mod webhdfs;
+pub mod write_buffer;
@@ -0,0 +1,522 @@
+//! Credentials of remote stores, and providers renewing them.
+//!
+//! Remote stores ask their [`CredentialsProvider`] for credentials before
+//! each request rather than once when opened, so long-running services can
+//! use providers of short-lived credentials, such as
+//! [`RefreshingCredentials`], without reopening their stores.
+//!
+//! ```
+//! use std::time::{
+//!     Duration,
+//!     SystemTime,
+//! };
+//! use zarr::store::credentials::*;
+//!
+//! let provider = RefreshingCredentials::new(|| {
+//!     // For example, exchange a service account's key for a token.
+//!     Ok(ExpiringCredentials {
+//!         credentials: Credentials::Token("token".to_owned()),
+//!         expires: Some(SystemTime::now() + Duration::from_secs(3600)),
+//!     })
+//! });
+//! assert!(matches!(
+//!     provider.credentials().unwrap().credentials,
+//!     Credentials::Token(_)
+//! ));
+//! ```
+
+#[cfg(feature = "http")]
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::sync::Mutex;
+#[cfg(feature = "http")]
+use std::time::UNIX_EPOCH;
+use std::time::{
+    Duration,
+    SystemTime,
+};
+
+/// Credentials authorizing requests to a remote store.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// No credentials, for public data.
+    Anonymous,
+    /// An access key pair, with a session token if the keys are temporary,
+    /// as used by S3 and S3-compatible stores.
+    Keys {
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    },
+    /// An OAuth 2 bearer token, as used by GCS and Azure.
+    Token(String),
+}
+
+/// Secrets are not included, so that credentials can be logged.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Anonymous => f.write_str("Anonymous"),
+            Credentials::Keys { access_key_id, .. } => f
+                .debug_struct("Keys")
+                .field("access_key_id", access_key_id)
+                .finish_non_exhaustive(),
+            Credentials::Token(_) => f.write_str("Token(..)"),
+        }
+    }
+}
+
+/// Credentials and when they expire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiringCredentials {
+    pub credentials: Credentials,
+    /// `None` if the credentials do not expire.
+    pub expires: Option<SystemTime>,
+}
+
+impl From<Credentials> for ExpiringCredentials {
+    fn from(credentials: Credentials) -> Self {
+        ExpiringCredentials {
+            credentials,
+            expires: None,
+        }
+    }
+}
+
+pub trait CredentialsProvider: fmt::Debug + Send + Sync {
+    /// Credentials for a request, which are valid until they expire.
+    fn credentials(&self) -> Result<ExpiringCredentials, Error>;
+}
+
+/// Fixed credentials are their own provider.
+impl CredentialsProvider for Credentials {
+    fn credentials(&self) -> Result<ExpiringCredentials, Error> {
+        Ok(self.clone().into())
+    }
+}
+
+/// Credentials read from environment variables on each request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnvCredentials {
+    /// Keys from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, if set,
+    /// `AWS_SESSION_TOKEN`.
+    Aws,
+    /// A bearer token from the named variable.
+    Token(String),
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> Result<ExpiringCredentials, Error> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("Credentials variable {} is not set", name),
+                )
+            })
+        };
+        let credentials = match self {
+            EnvCredentials::Aws => Credentials::Keys {
+                access_key_id: var("AWS_ACCESS_KEY_ID")?,
+                secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+                session_token: var("AWS_SESSION_TOKEN").ok(),
+            },
+            EnvCredentials::Token(name) => Credentials::Token(var(name)?),
+        };
+        Ok(credentials.into())
+    }
+}
+
+/// Time before credentials expire at which providers renew them, so that
+/// credentials do not expire during a request.
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Return the cached credentials, or refresh them if there are none or they
+/// expire within `margin`.
+fn cached_or_refresh(
+    cache: &Mutex<Option<ExpiringCredentials>>,
+    margin: Duration,
+    refresh: impl FnOnce() -> Result<ExpiringCredentials, Error>,
+) -> Result<ExpiringCredentials, Error> {
+    let mut cached = cache.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(credentials) = &*cached {
+        match credentials.expires {
+            Some(expires) if SystemTime::now() + margin >= expires => {}
+            _ => return Ok(credentials.clone()),
+        }
+    }
+    let credentials = refresh()?;
+    *cached = Some(credentials.clone());
+    Ok(credentials)
+}
+
+/// Credentials from a callback, which is called again shortly before the
+/// credentials it returned expire.
+pub struct RefreshingCredentials<F> {
+    refresh: F,
+    margin: Duration,
+    cached: Mutex<Option<ExpiringCredentials>>,
+}
+
+impl<F> RefreshingCredentials<F>
+where
+    F: Fn() -> Result<ExpiringCredentials, Error> + Send + Sync,
+{
+    pub fn new(refresh: F) -> Self {
+        RefreshingCredentials {
+            refresh,
+            margin: DEFAULT_REFRESH_MARGIN,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Refresh credentials this long before they expire, by default a
+    /// minute.
+    pub fn with_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+}
+
+impl<F> fmt::Debug for RefreshingCredentials<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshingCredentials")
+            .field("margin", &self.margin)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> CredentialsProvider for RefreshingCredentials<F>
+where
+    F: Fn() -> Result<ExpiringCredentials, Error> + Send + Sync,
+{
+    fn credentials(&self) -> Result<ExpiringCredentials, Error> {
+        cached_or_refresh(&self.cached, self.margin, &self.refresh)
+    }
+}
+
+/// Cloud services whose instances get credentials from a metadata service.
+#[cfg(feature = "http")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstanceMetadataService {
+    /// Keys of the IAM role of an EC2 instance.
+    Aws,
+    /// A token of the service account of a GCE instance.
+    Gcp,
+    /// A token for Azure Storage of the managed identity of an Azure VM.
+    Azure,
+}
+
+/// Credentials of the cloud instance the process runs on, from the
+/// metadata service of its cloud, renewed before they expire.
+#[cfg(feature = "http")]
+#[derive(Debug)]
+pub struct InstanceMetadataCredentials {
+    service: InstanceMetadataService,
+    endpoint: String,
+    agent: ureq::Agent,
+    cached: Mutex<Option<ExpiringCredentials>>,
+}
+
+#[cfg(feature = "http")]
+impl InstanceMetadataCredentials {
+    pub fn new(service: InstanceMetadataService) -> Self {
+        let endpoint = match service {
+            InstanceMetadataService::Aws | InstanceMetadataService::Azure => {
+                "http://169.254.169.254"
+            }
+            InstanceMetadataService::Gcp => "http://metadata.google.internal",
+        };
+        InstanceMetadataCredentials {
+            service,
+            endpoint: endpoint.to_owned(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(5))
+                .build(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Use a metadata service at another URL, such as an emulator.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_owned();
+        self
+    }
+
+    fn get(&self, path: &str, header: Option<(&str, &str)>) -> Result<String, Error> {
+        let mut request = self.agent.get(&format!("{}{}", self.endpoint, path));
+        if let Some((name, value)) = header {
+            request = request.set(name, value);
+        }
+        request
+            .call()
+            .map_err(|e| Error::new(ErrorKind::PermissionDenied, e))?
+            .into_string()
+    }
+
+    fn fetch(&self) -> Result<ExpiringCredentials, Error> {
+        let invalid = |e| Error::new(ErrorKind::InvalidData, e);
+        match self.service {
+            InstanceMetadataService::Aws => {
+                // Sessions of IMDSv2 are optional, since older and emulated
+                // services only support IMDSv1.
+                let token = self
+                    .agent
+                    .put(&format!("{}/latest/api/token", self.endpoint))
+                    .set("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+                    .call()
+                    .ok()
+                    .and_then(|response| response.into_string().ok());
+                let header = token
+                    .as_deref()
+                    .map(|token| ("X-aws-ec2-metadata-token", token));
+                let path = "/latest/meta-data/iam/security-credentials/";
+                let roles = self.get(path, header)?;
+                let role = roles
+                    .lines()
+                    .next()
+                    .ok_or_else(|| Error::new(ErrorKind::NotFound, "Instance has no IAM role"))?;
+                let body = self.get(&format!("{}{}", path, role), header)?;
+                parse_aws_credentials(&serde_json::from_str(&body)?)
+                    .ok_or_else(|| invalid("Malformed AWS instance credentials"))
+            }
+            InstanceMetadataService::Gcp => {
+                let body = self.get(
+                    "/computeMetadata/v1/instance/service-accounts/default/token",
+                    Some(("Metadata-Flavor", "Google")),
+                )?;
+                let value: serde_json::Value = serde_json::from_str(&body)?;
+                let expires_in = value["expires_in"].as_u64();
+                token_credentials(&value, expires_in.map(|s| SystemTime::now() + secs(s)))
+                    .ok_or_else(|| invalid("Malformed GCP instance token"))
+            }
+            InstanceMetadataService::Azure => {
+                let body = self.get(
+                    "/metadata/identity/oauth2/token?api-version=2018-02-01\
+                     &resource=https%3A%2F%2Fstorage.azure.com%2F",
+                    Some(("Metadata", "true")),
+                )?;
+                let value: serde_json::Value = serde_json::from_str(&body)?;
+                // Azure gives the expiry as a string of seconds since the
+                // epoch.
+                let expires_on = value["expires_on"].as_str().and_then(|s| s.parse().ok());
+                token_credentials(&value, expires_on.map(|s| UNIX_EPOCH + secs(s)))
+                    .ok_or_else(|| invalid("Malformed Azure instance token"))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl CredentialsProvider for InstanceMetadataCredentials {
+    fn credentials(&self) -> Result<ExpiringCredentials, Error> {
+        cached_or_refresh(&self.cached, DEFAULT_REFRESH_MARGIN, || self.fetch())
+    }
+}
+
+#[cfg(feature = "http")]
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[cfg(feature = "http")]
+fn token_credentials(
+    value: &serde_json::Value,
+    expires: Option<SystemTime>,
+) -> Option<ExpiringCredentials> {
+    Some(ExpiringCredentials {
+        credentials: Credentials::Token(value["access_token"].as_str()?.to_owned()),
+        expires: Some(expires?),
+    })
+}
+
+#[cfg(feature = "http")]
+fn parse_aws_credentials(value: &serde_json::Value) -> Option<ExpiringCredentials> {
+    let field = |name: &str| value[name].as_str().map(str::to_owned);
+    Some(ExpiringCredentials {
+        credentials: Credentials::Keys {
+            access_key_id: field("AccessKeyId")?,
+            secret_access_key: field("SecretAccessKey")?,
+            session_token: field("Token"),
+        },
+        expires: Some(parse_utc_time(&field("Expiration")?)?),
+    })
+}
+
+/// Days since the Unix epoch of a date in the proleptic Gregorian calendar.
+#[cfg(feature = "http")]
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of a number of days since the Unix epoch.
+#[cfg(feature = "http")]
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parse a UTC time such as `2024-05-01T12:30:00Z`, ignoring fractions of
+/// seconds.
+#[cfg(feature = "http")]
+fn parse_utc_time(time: &str) -> Option<SystemTime> {
+    let time = time.strip_suffix('Z')?;
+    let (date, time) = time.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let year = date.next()?.parse().ok()?;
+    let month = date.next()?.parse().ok()?;
+    let day = date.next()?.parse().ok()?;
+    let mut time = time.splitn(3, ':');
+    let hours: u64 = time.next()?.parse().ok()?;
+    let minutes: u64 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let seconds = days * 86_400 + hours * 3_600 + minutes * 60 + seconds as u64;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_utc_time() {
+        assert_eq!(parse_utc_time("1970-01-01T00:00:00Z"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse_utc_time("2024-02-29T12:30:15.25Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_209_815))
+        );
+        assert_eq!(parse_utc_time("2024-02-29T12:30:15"), None);
+        assert_eq!(parse_utc_time("2024-13-01T00:00:00Z"), None);
+        for &days in &[0, 19_782, 11_016, 47_541] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn test_refreshing_credentials() {
+        let refreshes = AtomicUsize::new(0);
+        let lifetime = Duration::from_secs(3600);
+        let provider = RefreshingCredentials::new(|| {
+            let n = refreshes.fetch_add(1, Ordering::SeqCst);
+            Ok(ExpiringCredentials {
+                credentials: Credentials::Token(n.to_string()),
+                expires: Some(SystemTime::now() + lifetime),
+            })
+        });
+        let token = || match provider.credentials().unwrap().credentials {
+            Credentials::Token(token) => token,
+            credentials => panic!("Unexpected credentials {:?}", credentials),
+        };
+        assert_eq!(token(), "0");
+        assert_eq!(token(), "0");
+
+        // Credentials expiring within the margin are renewed.
+        let provider = provider.with_margin(lifetime);
+        assert_eq!(
+            provider.credentials().unwrap().credentials,
+            Credentials::Token("1".into())
+        );
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_credentials_debug_hides_secrets() {
+        let keys = Credentials::Keys {
+            access_key_id: "AKID".to_owned(),
+            secret_access_key: "secret".to_owned(),
+            session_token: Some("session".to_owned()),
+        };
+        let debug = format!("{:?} {:?}", keys, Credentials::Token("token".to_owned()));
+        assert!(debug.contains("AKID"));
+        assert!(!debug.contains("secret"));
+        assert!(!debug.contains("session"));
+        assert!(!debug.contains("token\""));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_parse_aws_credentials() {
+        let value = serde_json::json!({
+            "Code": "Success",
+            "AccessKeyId": "AKID",
+            "SecretAccessKey": "secret",
+            "Token": "session",
+            "Expiration": "2024-02-29T12:30:15Z",
+        });
+        assert_eq!(
+            parse_aws_credentials(&value),
+            Some(ExpiringCredentials {
+                credentials: Credentials::Keys {
+                    access_key_id: "AKID".to_owned(),
+                    secret_access_key: "secret".to_owned(),
+                    session_token: Some("session".to_owned()),
+                },
+                expires: Some(UNIX_EPOCH + Duration::from_secs(1_709_209_815)),
+            })
+        );
+    }
+
+    #[cfg(all(feature = "http", feature = "filesystem", feature = "server"))]
+    #[test]
+    fn test_instance_metadata_credentials() {
+        use crate::prelude::*;
+        use crate::server::StoreServer;
+        use crate::storage::WriteableStore;
+        use std::io::Write;
+
+        let dir = tempdir::TempDir::new("rust_zarr_credentials_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        h.set(
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+            |mut writer| writer.write_all(br#"{"access_token":"ya29","expires_in":3599}"#),
+        )
+        .unwrap();
+        let server = StoreServer::bind(h, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        let provider = InstanceMetadataCredentials::new(InstanceMetadataService::Gcp)
+            .with_endpoint(&format!("http://{}/", addr));
+        let credentials = provider.credentials().unwrap();
+        assert_eq!(credentials.credentials, Credentials::Token("ya29".into()));
+        assert!(credentials.expires.unwrap() > SystemTime::now() + Duration::from_secs(3000));
+
+        let provider = InstanceMetadataCredentials::new(InstanceMetadataService::Aws)
+            .with_endpoint(&format!("http://{}/", addr));
+        assert!(provider.credentials().is_err());
+    }
+}
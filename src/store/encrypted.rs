@@ -0,0 +1,480 @@
+//! A store wrapper encrypting values at rest.
+//!
+//! Each value is sealed with an authenticated cipher under a fresh random
+//! 96-bit nonce. The stored value is prefixed by a header naming the cipher,
+//! the identifier of the key and the nonce:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 4     | Magic `ZENC` |
+//! | 1     | Format version, `1` |
+//! | 1     | [`Cipher`] |
+//! | 1     | Length of the key identifier |
+//! | *n*   | Key identifier, UTF-8 |
+//! | 12    | Nonce |
+//! | rest  | Ciphertext and tag |
+//!
+//! The header and the key the value is stored at are authenticated along
+//! with the ciphertext, so a value can neither be tampered with nor moved to
+//! another key, such as swapping chunks or replacing array metadata with
+//! that of another array, without failing to decrypt.
+//!
+//! Random nonces should not be used for more than 2<sup>32</sup> values
+//! under one key, so long-lived hierarchies should rotate keys through a
+//! [`KeyProvider`].
+
+use std::convert::TryFrom;
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{
+    Aead,
+    KeyInit,
+    OsRng,
+    Payload,
+};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::ChaCha20Poly1305;
+
+use crate::{
+    storage::{
+        KeyStat,
+        ListableStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::write_buffer::BufferWriter,
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+const MAGIC: &[u8; 4] = b"ZENC";
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+
+/// Length of keys for all ciphers, in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Authenticated cipher values are encrypted with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    /// AES-256 in Galois/Counter Mode, fastest on CPUs with AES instructions.
+    Aes256Gcm = 1,
+    /// ChaCha20-Poly1305, fast in software on any CPU.
+    ChaCha20Poly1305 = 2,
+}
+
+impl Cipher {
+    fn from_id(id: u8) -> Result<Self, Error> {
+        match id {
+            1 => Ok(Cipher::Aes256Gcm),
+            2 => Ok(Cipher::ChaCha20Poly1305),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown cipher {} of encrypted value", id),
+            )),
+        }
+    }
+
+    fn seal(&self, key: &[u8; KEY_LEN], nonce: &[u8], payload: Payload) -> Result<Vec<u8>, Error> {
+        match self {
+            Cipher::Aes256Gcm => seal::<Aes256Gcm>(key, nonce, payload),
+            Cipher::ChaCha20Poly1305 => seal::<ChaCha20Poly1305>(key, nonce, payload),
+        }
+    }
+
+    fn open(&self, key: &[u8; KEY_LEN], nonce: &[u8], payload: Payload) -> Result<Vec<u8>, Error> {
+        match self {
+            Cipher::Aes256Gcm => open::<Aes256Gcm>(key, nonce, payload),
+            Cipher::ChaCha20Poly1305 => open::<ChaCha20Poly1305>(key, nonce, payload),
+        }
+    }
+}
+
+fn seal<C: Aead + KeyInit>(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8],
+    payload: Payload,
+) -> Result<Vec<u8>, Error> {
+    C::new_from_slice(key)
+        .expect("Key length is valid")
+        .encrypt(aes_gcm::aead::Nonce::<C>::from_slice(nonce), payload)
+        .map_err(|_| Error::other("Value could not be encrypted"))
+}
+
+fn open<C: Aead + KeyInit>(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8],
+    payload: Payload,
+) -> Result<Vec<u8>, Error> {
+    C::new_from_slice(key)
+        .expect("Key length is valid")
+        .decrypt(aes_gcm::aead::Nonce::<C>::from_slice(nonce), payload)
+        .map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "Encrypted value failed authentication",
+            )
+        })
+}
+
+/// Source of the keys values are encrypted with, such as a key management
+/// service.
+pub trait KeyProvider {
+    /// Identifier of the key new values are encrypted with, at most 255
+    /// bytes long.
+    fn current_key_id(&self) -> &str;
+
+    /// Key with an identifier, which existing values may have been
+    /// encrypted with.
+    fn get_key(&self, key_id: &str) -> Result<[u8; KEY_LEN], Error>;
+}
+
+/// A single key provided by the caller.
+#[derive(Clone)]
+pub struct StaticKey {
+    key_id: String,
+    key: [u8; KEY_LEN],
+}
+
+impl StaticKey {
+    pub fn new(key_id: &str, key: [u8; KEY_LEN]) -> Self {
+        StaticKey {
+            key_id: key_id.to_owned(),
+            key,
+        }
+    }
+}
+
+impl std::fmt::Debug for StaticKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticKey")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyProvider for StaticKey {
+    fn current_key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn get_key(&self, key_id: &str) -> Result<[u8; KEY_LEN], Error> {
+        if key_id == self.key_id {
+            Ok(self.key)
+        } else {
+            Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No key with identifier {:?}", key_id),
+            ))
+        }
+    }
+}
+
+/// A store wrapper encrypting every value written and decrypting every value
+/// read, so hierarchies can be kept on shared storage which should not see
+/// their contents.
+///
+/// Keys and the key structure of the hierarchy, such as array paths and
+/// chunk grid positions, are not encrypted.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::store::encrypted::{
+///     Cipher,
+///     EncryptedStore,
+/// };
+///
+/// let dir = tempdir::TempDir::new("zarr").unwrap();
+/// let h = EncryptedStore::with_key(
+///     FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+///     Cipher::ChaCha20Poly1305,
+///     [7; 32],
+/// );
+/// h.create_group("foo").unwrap();
+/// h.set_attribute("foo", "secret".to_owned(), 42).unwrap();
+/// assert_eq!(h.list_attributes("foo").unwrap()["secret"], 42);
+/// ```
+#[derive(Debug)]
+pub struct EncryptedStore<S, K> {
+    store: S,
+    cipher: Cipher,
+    keys: K,
+}
+
+impl<S, K: KeyProvider> EncryptedStore<S, K> {
+    /// Wrap a store encrypting new values with `cipher` under the current
+    /// key of a key provider.
+    pub fn new(store: S, cipher: Cipher, keys: K) -> Self {
+        EncryptedStore {
+            store,
+            cipher,
+            keys,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    fn encrypt(&self, key: &str, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let key_id = self.keys.current_key_id();
+        let key_id_len = u8::try_from(key_id.len()).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "Key identifiers must be at most 255 bytes",
+            )
+        })?;
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut sealed = Vec::with_capacity(MAGIC.len() + 3 + key_id.len() + NONCE_LEN);
+        sealed.extend_from_slice(MAGIC);
+        sealed.push(FORMAT_VERSION);
+        sealed.push(self.cipher as u8);
+        sealed.push(key_id_len);
+        sealed.extend_from_slice(key_id.as_bytes());
+        sealed.extend_from_slice(&nonce);
+
+        let aad = [key.as_bytes(), &sealed].concat();
+        let ciphertext = self.cipher.seal(
+            &self.keys.get_key(key_id)?,
+            &nonce,
+            Payload {
+                msg: value,
+                aad: &aad,
+            },
+        )?;
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    fn decrypt(&self, key: &str, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+        let malformed = || Error::new(ErrorKind::InvalidData, "Value is not encrypted");
+        let header = sealed.get(..MAGIC.len() + 3).ok_or_else(malformed)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(malformed());
+        }
+        if header[4] != FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported encryption format version {}", header[4]),
+            ));
+        }
+        let cipher = Cipher::from_id(header[5])?;
+        let key_id_end = header.len() + usize::from(header[6]);
+        let header_len = key_id_end + NONCE_LEN;
+        if sealed.len() < header_len {
+            return Err(malformed());
+        }
+        let key_id =
+            std::str::from_utf8(&sealed[header.len()..key_id_end]).map_err(|_| malformed())?;
+
+        let aad = [key.as_bytes(), &sealed[..header_len]].concat();
+        cipher.open(
+            &self.keys.get_key(key_id)?,
+            &sealed[key_id_end..header_len],
+            Payload {
+                msg: &sealed[header_len..],
+                aad: &aad,
+            },
+        )
+    }
+}
+
+impl<S> EncryptedStore<S, StaticKey> {
+    /// Wrap a store encrypting values with a single key.
+    pub fn with_key(store: S, cipher: Cipher, key: [u8; KEY_LEN]) -> Self {
+        EncryptedStore::new(store, cipher, StaticKey::new("", key))
+    }
+}
+
+impl<S: Hierarchy, K> Hierarchy for EncryptedStore<S, K> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.store.get_entry_point_metadata()
+    }
+}
+
+impl<S: ReadableStore, K: KeyProvider> ReadableStore for EncryptedStore<S, K> {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        let mut reader = match self.store.get(key)? {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+        let mut sealed = Vec::new();
+        reader.read_to_end(&mut sealed)?;
+        self.decrypt(key, &sealed)
+            .map(|value| Some(Cursor::new(value)))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.store.uri(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        let stat = match self.store.stat(key)? {
+            Some(stat) => stat,
+            None => return Ok(None),
+        };
+        // The size of a decrypted value is only known by decrypting it.
+        Ok(self.size(key)?.map(|size| KeyStat { size, ..stat }))
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.store.is_read_only(key)
+    }
+}
+
+impl<S: ListableStore, K> ListableStore for EncryptedStore<S, K> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.store.list()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.store.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.store.list_dir(prefix)
+    }
+}
+
+impl<S: WriteableStore, K: KeyProvider> WriteableStore for EncryptedStore<S, K> {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let sealed = self.encrypt(key, &buffer.lock().unwrap())?;
+        self.store.set(key, |mut writer| {
+            std::io::Write::write_all(&mut writer, &sealed)
+        })
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        self.store.erase(key)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.store.erase_prefix(key_prefix)
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.store.make_read_only(key_prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::prelude::*;
+
+    /// Provider of two keys, encrypting with the second.
+    struct RotatedKeys;
+
+    impl KeyProvider for RotatedKeys {
+        fn current_key_id(&self) -> &str {
+            "new"
+        }
+
+        fn get_key(&self, key_id: &str) -> Result<[u8; KEY_LEN], Error> {
+            match key_id {
+                "old" => Ok([1; KEY_LEN]),
+                "new" => Ok([2; KEY_LEN]),
+                _ => Err(Error::from(ErrorKind::NotFound)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        for &cipher in &[Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let dir = tempdir::TempDir::new("rust_zarr_encrypted_tests").unwrap();
+            let h = EncryptedStore::with_key(
+                FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+                cipher,
+                [3; KEY_LEN],
+            );
+            let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+                .chunk_shape(smallvec![4])
+                .build();
+            h.create_array("a", &array_meta).unwrap();
+            let data = vec![42u8; 4];
+            h.write_chunk("a", &array_meta, &SliceDataChunk::new(smallvec![0], &data))
+                .unwrap();
+
+            assert_eq!(h.get_array_metadata("a").unwrap(), array_meta);
+            let chunk = h
+                .read_chunk::<u8>("a", &array_meta, smallvec![0])
+                .unwrap()
+                .unwrap();
+            assert_eq!(chunk.get_data(), &data[..]);
+            assert!(h.get_ref().get_array_metadata("a").is_err());
+        }
+    }
+
+    #[test]
+    fn test_encrypted_authentication() {
+        let dir = tempdir::TempDir::new("rust_zarr_encrypted_tests").unwrap();
+        let fs = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let old = EncryptedStore::new(
+            fs.clone(),
+            Cipher::Aes256Gcm,
+            StaticKey::new("old", [1; KEY_LEN]),
+        );
+        old.set("/a", |mut w| w.write_all(b"first")).unwrap();
+        old.set("/b", |mut w| w.write_all(b"second")).unwrap();
+
+        // Values written under a previous key are still readable.
+        let rotated = EncryptedStore::new(fs.clone(), Cipher::ChaCha20Poly1305, RotatedKeys);
+        let mut value = String::new();
+        rotated
+            .get("/a")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut value)
+            .unwrap();
+        assert_eq!(value, "first");
+
+        // Values moved to another key fail authentication.
+        let mut sealed = Vec::new();
+        fs.get("/b")
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut sealed)
+            .unwrap();
+        fs.set("/a", |mut w| w.write_all(&sealed)).unwrap();
+        assert_eq!(old.get("/a").unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // Tampered values fail authentication.
+        *sealed.last_mut().unwrap() ^= 1;
+        fs.set("/b", |mut w| w.write_all(&sealed)).unwrap();
+        assert_eq!(old.get("/b").unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(old.get("/c").unwrap().is_none());
+    }
+}
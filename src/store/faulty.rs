@@ -0,0 +1,361 @@
+//! A store wrapper injecting latency, failures and short reads for tests.
+//!
+//! Faults are deterministic, so tests of retries, partial reads and error
+//! handling, of this crate or of applications using it, fail the same way on
+//! every run.
+//!
+//! ```
+//! use std::io::ErrorKind;
+//! use zarr::prelude::*;
+//! use zarr::storage::ReadableStore;
+//! use zarr::store::faulty::FaultyStore;
+//!
+//! let dir = tempdir::TempDir::new("zarr").unwrap();
+//! let h = FaultyStore::new(FilesystemHierarchy::open_or_create(dir.path()).unwrap())
+//!     .with_failures(2, ErrorKind::TimedOut)
+//!     .with_short_reads(7);
+//! assert!(ReadableStore::exists(&h, "/zarr.json").unwrap());
+//! let e = ReadableStore::exists(&h, "/zarr.json").unwrap_err();
+//! assert_eq!(e.kind(), ErrorKind::TimedOut);
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+use std::path::PathBuf;
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    storage::{
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A store wrapper failing some operations, delaying each, splitting reads
+/// into short reads and limiting the bytes written.
+///
+/// Each store operation, such as a `get` or `set`, counts as one operation,
+/// while reading from or writing to a value's reader or writer does not.
+#[derive(Debug)]
+pub struct FaultyStore<S> {
+    store: S,
+    latency: Option<Duration>,
+    /// Fail every nth operation with an error of this kind.
+    failures: Option<(u64, ErrorKind)>,
+    max_read: Option<usize>,
+    write_quota: Option<u64>,
+    operations: AtomicU64,
+    written: Arc<AtomicU64>,
+}
+
+impl<S> FaultyStore<S> {
+    /// Wrap a store, initially without faults.
+    pub fn new(store: S) -> Self {
+        FaultyStore {
+            store,
+            latency: None,
+            failures: None,
+            max_read: None,
+            write_quota: None,
+            operations: AtomicU64::new(0),
+            written: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Delay each operation.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Fail every `every`th operation, counting from the first, with an
+    /// error of a kind.
+    pub fn with_failures(mut self, every: u64, kind: ErrorKind) -> Self {
+        assert!(every > 0, "Failures must be of every 1 or more operations");
+        self.failures = Some((every, kind));
+        self
+    }
+
+    /// Return at most `max_read` bytes from each read of a value's reader.
+    pub fn with_short_reads(mut self, max_read: usize) -> Self {
+        assert!(max_read > 0, "Short reads must read at least 1 byte");
+        self.max_read = Some(max_read);
+        self
+    }
+
+    /// Fail writes with [`ErrorKind::StorageFull`] once this many bytes have
+    /// been written in total. Erasing values does not free quota.
+    pub fn with_write_quota(mut self, bytes: u64) -> Self {
+        self.write_quota = Some(bytes);
+        self
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Number of operations so far, including failed ones.
+    pub fn operations(&self) -> u64 {
+        self.operations.load(Ordering::SeqCst)
+    }
+
+    /// Number of bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.written.load(Ordering::SeqCst)
+    }
+
+    /// Start an operation, delaying it and failing it if it is due to fail.
+    fn operation(&self, name: &str, key: &str) -> Result<(), Error> {
+        if let Some(latency) = self.latency {
+            std::thread::sleep(latency);
+        }
+        let n = self.operations.fetch_add(1, Ordering::SeqCst) + 1;
+        match self.failures {
+            Some((every, kind)) if n.is_multiple_of(every) => Err(Error::new(
+                kind,
+                format!("Injected failure of operation {} ({} {})", n, name, key),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: Hierarchy> Hierarchy for FaultyStore<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.store.get_entry_point_metadata()
+    }
+
+    fn array_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.array_metadata_key(path_name)
+    }
+
+    fn group_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.group_metadata_key(path_name)
+    }
+
+    fn data_path_key(&self, path_name: &str) -> PathBuf {
+        self.store.data_path_key(path_name)
+    }
+}
+
+/// Reader returning at most a number of bytes from each read.
+#[derive(Debug)]
+pub struct ShortReader<R> {
+    reader: R,
+    max_read: Option<usize>,
+}
+
+impl<R: Read> Read for ShortReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.max_read.map_or(buf.len(), |max| max.min(buf.len()));
+        self.reader.read(&mut buf[..len])
+    }
+}
+
+impl<S: ReadableStore> ReadableStore for FaultyStore<S> {
+    type GetReader = ShortReader<S::GetReader>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.operation("exists", key)?;
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        self.operation("get", key)?;
+        Ok(self.store.get(key)?.map(|reader| ShortReader {
+            reader,
+            max_read: self.max_read,
+        }))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.store.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.operation("size", key)?;
+        self.store.size(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.operation("stat", key)?;
+        self.store.stat(key)
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.store.is_read_only(key)
+    }
+}
+
+impl<S: PartialReadStore> PartialReadStore for FaultyStore<S> {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.operation("get_range", key)?;
+        self.store.get_range(key, offset, length)
+    }
+}
+
+impl<S: ListableStore> ListableStore for FaultyStore<S> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.operation("list", "")?;
+        self.store.list()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.operation("list_prefix", prefix)?;
+        self.store.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.operation("list_dir", prefix)?;
+        self.store.list_dir(prefix)
+    }
+}
+
+/// Writer failing once the store's write quota is used up.
+#[derive(Debug)]
+pub struct QuotaWriter<W> {
+    writer: W,
+    written: Arc<AtomicU64>,
+    quota: Option<u64>,
+}
+
+impl<W: Write> Write for QuotaWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if let Some(quota) = self.quota {
+            let written = self.written.load(Ordering::SeqCst);
+            if written + buf.len() as u64 > quota {
+                return Err(Error::new(
+                    ErrorKind::StorageFull,
+                    format!("Write quota of {} bytes exceeded", quota),
+                ));
+            }
+        }
+        let n = self.writer.write(buf)?;
+        self.written.fetch_add(n as u64, Ordering::SeqCst);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+impl<S: WriteableStore> WriteableStore for FaultyStore<S> {
+    type SetWriter = QuotaWriter<S::SetWriter>;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        self.operation("set", key)?;
+        self.store.set(key, |writer| {
+            value(QuotaWriter {
+                writer,
+                written: Arc::clone(&self.written),
+                quota: self.write_quota,
+            })
+        })
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        self.operation("erase", key)?;
+        self.store.erase(key)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.operation("erase_prefix", key_prefix)?;
+        self.store.erase_prefix(key_prefix)
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.operation("make_read_only", key_prefix)?;
+        self.store.make_read_only(key_prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_faulty_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_faulty_tests").unwrap();
+        let h = FaultyStore::new(FilesystemHierarchy::open_or_create(dir.path()).unwrap())
+            .with_short_reads(3);
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u32::ZARR_TYPE)
+            .chunk_shape(smallvec![4])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0], vec![1u32, 2, 3, 4]),
+        )
+        .unwrap();
+
+        // Values are read correctly despite short reads.
+        let chunk = h
+            .read_chunk::<u32>("a", &array_meta, smallvec![0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.get_data(), &[1, 2, 3, 4]);
+        let mut reader = ReadableStore::get(&h, "/meta/root/a.array.json")
+            .unwrap()
+            .unwrap();
+        assert_eq!(reader.read(&mut [0; 10]).unwrap(), 3);
+
+        let h = FaultyStore::new(h.into_inner()).with_failures(2, ErrorKind::TimedOut);
+        let results: Vec<Option<ErrorKind>> = (0..4)
+            .map(|_| {
+                ReadableStore::exists(&h, "/zarr.json")
+                    .err()
+                    .map(|e| e.kind())
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                None,
+                Some(ErrorKind::TimedOut),
+                None,
+                Some(ErrorKind::TimedOut)
+            ]
+        );
+        assert_eq!(h.operations(), 4);
+
+        let h = FaultyStore::new(h.into_inner()).with_write_quota(10);
+        h.set("/x", |mut writer| writer.write_all(&[0; 6])).unwrap();
+        assert_eq!(
+            h.set("/y", |mut writer| writer.write_all(&[0; 6]))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::StorageFull
+        );
+        assert_eq!(h.bytes_written(), 6);
+    }
+}
@@ -9,9 +9,16 @@ use std::io::{
     BufWriter,
     Error,
     ErrorKind,
+    Read,
     Result,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
 };
-use std::path::PathBuf;
 
 use fs2::FileExt;
 use serde_json::{
@@ -23,14 +30,21 @@ use walkdir::WalkDir;
 
 use crate::{
     storage::{
+        content_etag,
+        parallel_map,
+        read_value,
+        update_metadata,
+        ConditionalWriteStore,
+        KeyStat,
         ListableStore,
+        PartialReadStore,
         ReadableStore,
         WriteableStore,
     },
     EntryPointMetadata,
     Hierarchy,
     HierarchyReader,
-    MetadataError,
+    JsonObject,
 };
 
 /// A filesystem-backed Zarr hierarchy.
@@ -47,15 +61,13 @@ impl Hierarchy for FilesystemHierarchy {
 }
 
 impl FilesystemHierarchy {
-    fn read_entry_point_metadata(base_path: &PathBuf) -> Result<EntryPointMetadata> {
+    fn read_entry_point_metadata(base_path: &Path) -> Result<EntryPointMetadata> {
         let entry_point_path = base_path.join(crate::ENTRY_POINT_KEY);
         let reader = BufReader::new(File::open(entry_point_path)?);
         let metadata: EntryPointMetadata = serde_json::from_reader(reader)?;
-        if let Some(ext) = metadata.extensions.iter().find(|e| e.must_understand) {
-            // TODO: returning an io::Error wrapped custom error, rather than other
-            // way around.
-            return Err(MetadataError::UnknownRequiredExtension(ext.clone()).into());
-        }
+        // TODO: returning an io::Error wrapped custom error, rather than other
+        // way around.
+        crate::check_extensions(&metadata.extensions)?;
         Ok(metadata)
     }
 
@@ -72,7 +84,7 @@ impl FilesystemHierarchy {
         let version = reader.get_version()?;
 
         if !version.matches(&crate::VERSION) {
-            return Err(Error::new(ErrorKind::Other, "TODO: Incompatible version"));
+            return Err(Error::other("TODO: Incompatible version"));
         }
 
         Ok(reader)
@@ -94,6 +106,7 @@ impl FilesystemHierarchy {
                 .read(true)
                 .write(true)
                 .create(true)
+                .truncate(true)
                 .open(entry_point_path)?;
             file.lock_exclusive()?;
 
@@ -110,12 +123,17 @@ impl FilesystemHierarchy {
         let version = reader.get_version()?;
 
         if !version.matches(&crate::VERSION) {
-            return Err(Error::new(ErrorKind::Other, "TODO: Incompatible version"));
+            return Err(Error::other("TODO: Incompatible version"));
         }
 
         Ok(reader)
     }
 
+    /// Directory of the hierarchy, as given when it was opened.
+    pub fn get_base_path(&self) -> &Path {
+        &self.base_path
+    }
+
     pub fn get_attributes(&self, key: &str) -> Result<Value> {
         // TODO: no longer used, but should be adapted for getting array/group user attributes
         let path = self.get_path(key)?;
@@ -143,10 +161,7 @@ impl FilesystemHierarchy {
         // Note: cannot use `canonicalize` on both the constructed array path
         // and `base_path` and check `starts_with`, because `canonicalize` also
         // requires the path exist.
-        use std::path::{
-            Component,
-            Path,
-        };
+        use std::path::Component;
 
         // Normalize the path to be relative.
         let mut components = Path::new(key).components();
@@ -220,6 +235,61 @@ impl ReadableStore for FilesystemHierarchy {
                 .map_err(|_| Error::new(ErrorKind::NotFound, "TODO: non-unicode path"))
         })
     }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        let target = self.get_path(key)?;
+        if target.is_file() {
+            Ok(Some(fs::metadata(target)?.len()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Files have no entity tag without reading them, so stats have only
+    /// the size and modification time.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        match fs::metadata(self.get_path(key)?) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(KeyStat {
+                size: metadata.len(),
+                last_modified: metadata.modified().ok(),
+                etag: None,
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool> {
+        match fs::metadata(self.get_path(key)?) {
+            Ok(metadata) => Ok(metadata.permissions().readonly()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Files are read in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency), which hides the
+    /// latency of network filesystems.
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        parallel_map(keys, |key| read_value(self, key))
+    }
+}
+
+impl PartialReadStore for FilesystemHierarchy {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        let mut reader = match self.get(key)? {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut value = Vec::new();
+        match length {
+            Some(length) => reader.take(length).read_to_end(&mut value)?,
+            None => reader.read_to_end(&mut value)?,
+        };
+        Ok(Some(value))
+    }
 }
 
 impl ListableStore for FilesystemHierarchy {
@@ -246,7 +316,7 @@ impl ListableStore for FilesystemHierarchy {
                 keys.push(key);
             } else {
                 // t.is_dir() == true, because symlinks were followed.
-                prefixes.push(key);
+                prefixes.push(key + "/");
             }
         }
 
@@ -269,6 +339,7 @@ impl WriteableStore for FilesystemHierarchy {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(target)?;
         file.lock_exclusive()?;
         // Truncate after the lock is acquired, rather than on opening.
@@ -310,6 +381,112 @@ impl WriteableStore for FilesystemHierarchy {
 
         Ok(!path.exists())
     }
+
+    /// Files are written in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        parallel_map(pairs, |(key, value)| {
+            self.set(key, |mut writer| writer.write_all(value))
+        })?;
+        Ok(())
+    }
+
+    /// Files are made read-only by their permissions, which stop writes by
+    /// any process except those of privileged users.
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool> {
+        let path = self.get_path(key_prefix)?;
+
+        if path.exists() {
+            for entry in WalkDir::new(&path) {
+                let entry = entry?;
+
+                if entry.file_type().is_file() {
+                    let mut permissions = entry.metadata()?.permissions();
+                    permissions.set_readonly(true);
+                    fs::set_permissions(entry.path(), permissions)?;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn update_metadata_document(
+        &self,
+        key: &str,
+        update: &mut dyn FnMut(&mut JsonObject) -> Result<()>,
+    ) -> Result<bool> {
+        update_metadata(self, key, update)?;
+        Ok(true)
+    }
+}
+
+/// Conditional writes hold an exclusive lock on the file while comparing
+/// and writing it, so are atomic with respect to other writers, which also
+/// lock files. Entity tags are derived from file contents.
+impl ConditionalWriteStore for FilesystemHierarchy {
+    fn get_with_etag(&self, key: &str) -> Result<Option<(Vec<u8>, String)>> {
+        let mut reader = match self.get(key)? {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+        let mut value = Vec::new();
+        reader.read_to_end(&mut value)?;
+        let etag = content_etag(&value);
+        Ok(Some((value, etag)))
+    }
+
+    fn put_if_not_exists<F: FnOnce(Self::SetWriter) -> Result<()>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<bool> {
+        let target = self.get_path(key)?;
+        if let Some(parent) = target.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let file = match fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(target)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        file.lock_exclusive()?;
+        value(BufWriter::new(file))?;
+        Ok(true)
+    }
+
+    fn put_if_match<F: FnOnce(Self::SetWriter) -> Result<()>>(
+        &self,
+        key: &str,
+        etag: &str,
+        value: F,
+    ) -> Result<bool> {
+        let target = self.get_path(key)?;
+        let mut file = match fs::OpenOptions::new().read(true).write(true).open(target) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        file.lock_exclusive()?;
+        let mut existing = Vec::new();
+        file.read_to_end(&mut existing)?;
+        if content_etag(&existing) != etag {
+            return Ok(false);
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        value(BufWriter::new(file))?;
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -325,7 +502,9 @@ mod tests {
         data_type::ReflectedType,
         ArrayMetadata,
         HierarchyLister,
+        HierarchyReader,
         HierarchyWriter,
+        JsonObject,
     };
     use tempdir::TempDir;
 
@@ -405,9 +584,7 @@ mod tests {
             smallvec![10, 10, 10],
             smallvec![5, 5, 5],
             i32::ZARR_TYPE,
-            crate::compression::CompressionType::Raw(
-                crate::compression::raw::RawCompression::default(),
-            ),
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
         );
         wrapper
             .zarr
@@ -416,6 +593,63 @@ mod tests {
         assert!(wrapper.zarr.array_exists("linked_array").unwrap());
     }
 
+    #[test]
+    fn list_arrays_and_groups() {
+        let wrapper = FilesystemHierarchy::temp_new_rw();
+        let array_meta = ArrayMetadata::new(
+            smallvec![10, 10, 10],
+            smallvec![5, 5, 5],
+            i32::ZARR_TYPE,
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
+        );
+        wrapper.zarr.create_array("foo/bar", &array_meta).unwrap();
+        wrapper
+            .zarr
+            .create_array("foo/longer_array_name", &array_meta)
+            .unwrap();
+        wrapper.zarr.create_group("foo/group").unwrap();
+        wrapper
+            .zarr
+            .create_array("foo/implicit/array", &array_meta)
+            .unwrap();
+
+        assert_eq!(
+            wrapper.zarr.list_nodes("foo").unwrap(),
+            vec!["bar", "group", "implicit", "longer_array_name"]
+        );
+    }
+
+    #[test]
+    fn read_only_permissions() {
+        let wrapper = FilesystemHierarchy::temp_new_rw();
+        let array_meta = ArrayMetadata::new(
+            smallvec![10, 10, 10],
+            smallvec![5, 5, 5],
+            i32::ZARR_TYPE,
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
+        );
+        wrapper.zarr.create_array("foo", &array_meta).unwrap();
+        assert!(!wrapper
+            .zarr
+            .get_array_metadata("foo")
+            .unwrap()
+            .is_read_only());
+
+        let path = wrapper
+            .zarr
+            .get_path(wrapper.zarr.array_metadata_key("foo").to_str().unwrap())
+            .unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        assert!(wrapper
+            .zarr
+            .get_array_metadata("foo")
+            .unwrap()
+            .is_read_only());
+    }
+
     #[test]
     fn test_get_chunk_uri() {
         let dir = TempDir::new("rust_zarr_tests").unwrap();
@@ -427,19 +661,166 @@ mod tests {
             smallvec![10, 10, 10],
             smallvec![5, 5, 5],
             i32::ZARR_TYPE,
-            crate::compression::CompressionType::Raw(
-                crate::compression::raw::RawCompression::default(),
-            ),
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
         );
         create
             .create_array("/foo/bar", &array_meta)
             .expect("Failed to create array");
         let uri = create
-            .get_chunk_uri("/foo/bar", &array_meta, &vec![1, 2, 3])
+            .get_chunk_uri("/foo/bar", &array_meta, &[1, 2, 3])
             .unwrap();
         assert_eq!(uri, format!("file://{}/data/root/foo/bar/c1/2/3", path_str));
     }
 
+    #[test]
+    fn test_seal_permissions() {
+        let dir = TempDir::new("rust_zarr_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = crate::ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &crate::SliceDataChunk::new(smallvec![1], vec![3u8; 2]),
+        )
+        .unwrap();
+        h.seal("a").unwrap();
+
+        let readonly = |path: &str| {
+            fs::metadata(dir.path().join(path))
+                .unwrap()
+                .permissions()
+                .readonly()
+        };
+        assert!(readonly("meta/root/a.array.json"));
+        assert!(readonly("data/root/a/c1"));
+        assert!(!readonly("zarr.json"));
+    }
+
+    #[test]
+    fn test_chunk_key_separator() {
+        let dir = TempDir::new("rust_zarr_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let flat_meta = crate::ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .separator(".")
+            .build();
+        let chunk = crate::SliceDataChunk::new(smallvec![1, 0], vec![3u8; 4]);
+        h.create_array("flat", &flat_meta).unwrap();
+        h.write_chunk("flat", &flat_meta, &chunk).unwrap();
+        assert!(dir.path().join("data/root/flat/c1.0").is_file());
+
+        // Metadata of older stores may not specify a separator, whose chunks
+        // could be stored with either.
+        h.create_array("legacy", &flat_meta).unwrap();
+        h.write_chunk("legacy", &flat_meta, &chunk).unwrap();
+        let metadata_path = dir.path().join("meta/root/legacy.array.json");
+        let mut document: serde_json::Value =
+            serde_json::from_slice(&fs::read(&metadata_path).unwrap()).unwrap();
+        document["chunk_grid"]
+            .as_object_mut()
+            .unwrap()
+            .remove("separator");
+        fs::write(&metadata_path, serde_json::to_vec(&document).unwrap()).unwrap();
+
+        let legacy_meta = h.get_array_metadata("legacy").unwrap();
+        assert_eq!(legacy_meta.get_separator(), None);
+        let read = h
+            .read_chunk::<u8>("legacy", &legacy_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), chunk.get_data());
+        h.write_chunk(
+            "legacy",
+            &legacy_meta,
+            &crate::SliceDataChunk::new(smallvec![0, 1], vec![5u8; 4]),
+        )
+        .unwrap();
+        assert!(dir.path().join("data/root/legacy/c0/1").is_file());
+        assert_eq!(
+            h.initialized_chunks("legacy", &legacy_meta).unwrap(),
+            vec![
+                crate::GridCoord::from_slice(&[0, 1]),
+                crate::GridCoord::from_slice(&[1, 0]),
+            ]
+        );
+        let batch = h
+            .read_chunks::<u8>(
+                "legacy",
+                &legacy_meta,
+                &[smallvec![1, 0], smallvec![0, 1], smallvec![1, 1]],
+            )
+            .unwrap();
+        let data: Vec<Option<Vec<u8>>> = batch
+            .into_iter()
+            .map(|chunk| chunk.map(|chunk| chunk.into_data()))
+            .collect();
+        assert_eq!(data, vec![Some(vec![3; 4]), Some(vec![5; 4]), None]);
+        // Batched writes keep the separator of the first chunk, found once.
+        h.write_chunks(
+            "legacy",
+            &legacy_meta,
+            &[
+                crate::SliceDataChunk::new(smallvec![1, 0], vec![6u8; 4]),
+                crate::SliceDataChunk::new(smallvec![1, 1], vec![7u8; 4]),
+            ],
+        )
+        .unwrap();
+        assert!(dir.path().join("data/root/legacy/c1.1").is_file());
+        assert!(!dir.path().join("data/root/legacy/c1/1").exists());
+        assert_eq!(
+            h.read_chunk::<u8>("legacy", &legacy_meta, smallvec![1, 0])
+                .unwrap()
+                .unwrap()
+                .get_data(),
+            &[6; 4]
+        );
+        assert!(h.delete_chunk("legacy", &legacy_meta, &[1, 0]).unwrap());
+        assert!(!dir.path().join("data/root/legacy/c1.0").exists());
+    }
+
+    #[test]
+    fn test_implicit_groups() {
+        use crate::storage::{
+            materialize_implicit_groups,
+            ZarrImplicitGroupReader,
+        };
+
+        let wrapper = FilesystemHierarchy::temp_new_rw();
+        let h = wrapper.as_ref();
+        let array_meta = crate::ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE).build();
+        h.create_array("a/b/c", &array_meta).unwrap();
+
+        assert_eq!(
+            h.get_group_metadata("a/b").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        for group in &["", "a", "/a/b/"] {
+            assert_eq!(
+                h.get_group_metadata_or_implicit(group).unwrap(),
+                crate::GroupMetadata::default()
+            );
+        }
+        for missing in &["a/b/c", "a/d"] {
+            assert_eq!(
+                h.get_group_metadata_or_implicit(missing)
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotFound
+            );
+        }
+
+        materialize_implicit_groups(h, "a/b/c/d").unwrap();
+        assert!(h.get_group_metadata("").is_ok());
+        assert!(h.get_group_metadata("a").is_ok());
+        assert!(h.get_group_metadata("a/b").is_ok());
+        // Arrays are not replaced by groups.
+        assert!(h.get_group_metadata("a/b/c").is_err());
+        assert_eq!(h.get_array_metadata("a/b/c").unwrap(), array_meta);
+    }
+
     #[test]
     pub(crate) fn short_chunk_truncation() {
         let wrapper = FilesystemHierarchy::temp_new_rw();
@@ -448,9 +829,7 @@ mod tests {
             smallvec![10, 10, 10],
             smallvec![5, 5, 5],
             i32::ZARR_TYPE,
-            crate::compression::CompressionType::Raw(
-                crate::compression::raw::RawCompression::default(),
-            ),
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
         );
         let chunk_data: Vec<i32> = (0..125_i32).collect();
         let chunk_in = crate::SliceDataChunk::new(smallvec![0, 0, 0], &chunk_data);
@@ -493,4 +872,86 @@ mod tests {
             (chunk_data.len() * std::mem::size_of::<i32>()) as u64
         );
     }
+
+    #[test]
+    fn conditional_writes() {
+        use crate::storage::ZarrConditionalWriter;
+        use std::io::Write;
+
+        let wrapper = FilesystemHierarchy::temp_new_rw();
+        let zarr = &wrapper.zarr;
+        assert!(zarr
+            .put_if_not_exists("foo", |mut w| w.write_all(b"a"))
+            .unwrap());
+        assert!(!zarr
+            .put_if_not_exists("foo", |mut w| w.write_all(b"b"))
+            .unwrap());
+        let (value, etag) = zarr.get_with_etag("foo").unwrap().unwrap();
+        assert_eq!(value, b"a");
+
+        assert!(zarr
+            .put_if_match("foo", &etag, |mut w| w.write_all(b"bc"))
+            .unwrap());
+        assert!(!zarr
+            .put_if_match("foo", &etag, |mut w| w.write_all(b"d"))
+            .unwrap());
+        assert_eq!(zarr.get_with_etag("foo").unwrap().unwrap().0, b"bc");
+        assert!(!zarr
+            .put_if_match("bar", &etag, |mut w| w.write_all(b"d"))
+            .unwrap());
+        assert!(zarr.get_with_etag("bar").unwrap().is_none());
+
+        let array_meta = ArrayMetadata::new(
+            smallvec![10, 10],
+            smallvec![5, 5],
+            i32::ZARR_TYPE,
+            crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
+        );
+        zarr.create_array("arr", &array_meta).unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                scope.spawn(move || {
+                    let attributes = vec![(format!("attr{}", i), i.into())].into_iter().collect();
+                    zarr.merge_attributes("arr", attributes).unwrap();
+                });
+            }
+        });
+        assert_eq!(zarr.list_attributes("arr").unwrap().len(), 8);
+
+        // Attribute updates through the hierarchy writer are conditional too.
+        zarr.create_group("grp").unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                scope.spawn(move || {
+                    for j in 0..4 {
+                        zarr.set_attribute("grp", format!("attr{}_{}", i, j), i)
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(zarr.list_attributes("grp").unwrap().len(), 32);
+
+        let resized = zarr.resize_array("arr", smallvec![20, 5]).unwrap();
+        assert_eq!(resized.get_shape(), &[20, 5]);
+        let reread = zarr.get_array_metadata("arr").unwrap();
+        assert_eq!(reread, resized);
+        assert_eq!(zarr.list_attributes("arr").unwrap().len(), 8);
+        assert!(zarr.resize_array("arr", smallvec![20]).is_err());
+        assert!(zarr.merge_attributes("missing", JsonObject::new()).is_err());
+    }
+
+    #[test]
+    fn partial_reads() {
+        use std::io::Write;
+
+        let wrapper = FilesystemHierarchy::temp_new_rw();
+        let zarr = &wrapper.zarr;
+        zarr.set("foo", |mut w| w.write_all(b"0123456789")).unwrap();
+        assert_eq!(zarr.get_range("foo", 2, Some(3)).unwrap().unwrap(), b"234");
+        assert_eq!(zarr.get_range("foo", 7, None).unwrap().unwrap(), b"789");
+        assert_eq!(zarr.get_range("foo", 8, Some(5)).unwrap().unwrap(), b"89");
+        assert!(zarr.get_range("foo", 20, None).unwrap().unwrap().is_empty());
+        assert!(zarr.get_range("bar", 0, None).unwrap().is_none());
+    }
 }
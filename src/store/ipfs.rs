@@ -0,0 +1,517 @@
+//! An experimental store of a hierarchy published on IPFS.
+//!
+//! Values are content-addressed, so a hierarchy is a [`CidManifest`] mapping
+//! each key to the CID of its value. Values and the manifest are read from an
+//! HTTP gateway and written by adding them through the RPC API of an IPFS
+//! node, such as Kubo. Since values are immutable, writing a key only updates
+//! the store's manifest; [`IpfsStore::publish`] adds the manifest itself,
+//! returning the CID identifying this version of the hierarchy.
+//!
+//! Gateways are trusted: values read are not checked against their CIDs.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::ipfs::{
+//!     IpfsOptions,
+//!     IpfsStore,
+//! };
+//!
+//! let options = IpfsOptions {
+//!     api_url: Some("http://127.0.0.1:5001".to_owned()),
+//!     ..Default::default()
+//! };
+//! let h = IpfsStore::create_with_options("http://127.0.0.1:8080", &options).unwrap();
+//! h.create_group("raw").unwrap();
+//! let cid = h.publish().unwrap();
+//!
+//! let h = IpfsStore::open("https://ipfs.io", &cid).unwrap();
+//! assert!(h.exists("raw").unwrap());
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Result,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+    RwLock,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    storage::{
+        list_dir_of_keys,
+        parallel_map,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::{
+        http::{
+            check_response,
+            read_body,
+            HttpOptions,
+            HttpStore,
+        },
+        write_buffer::BufferWriter,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+/// Options of the requests of an [`IpfsStore`].
+#[derive(Clone, Debug, Default)]
+pub struct IpfsOptions {
+    /// Options of requests to the gateway and the API.
+    pub http: HttpOptions,
+    /// URL of the RPC API of the node adding written values, such as
+    /// `http://127.0.0.1:5001`. Stores without one are read-only.
+    pub api_url: Option<String>,
+}
+
+/// The CIDs of the values of the keys of a hierarchy.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CidManifest {
+    pub keys: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddResponse {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// A store of a hierarchy whose values are read from an IPFS gateway.
+#[derive(Clone, Debug)]
+pub struct IpfsStore {
+    /// Store of the gateway's `/ipfs` path, whose keys are CIDs.
+    gateway: HttpStore,
+    /// Store of the node's `/api/v0` path.
+    api: Option<HttpStore>,
+    manifest: Arc<RwLock<CidManifest>>,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl Hierarchy for IpfsStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl IpfsStore {
+    /// Open the hierarchy of a manifest CID, read-only.
+    pub fn open(gateway_url: &str, manifest_cid: &str) -> Result<IpfsStore> {
+        Self::open_with_options(gateway_url, manifest_cid, &IpfsOptions::default())
+    }
+
+    /// Open the hierarchy of a manifest CID, making requests with the given
+    /// options. Writes update a copy of the manifest, published as a new
+    /// CID.
+    pub fn open_with_options(
+        gateway_url: &str,
+        manifest_cid: &str,
+        options: &IpfsOptions,
+    ) -> Result<IpfsStore> {
+        let mut store = Self::connect(gateway_url, options)?;
+        let manifest = store.fetch(manifest_cid)?;
+        store.manifest = Arc::new(RwLock::new(serde_json::from_slice(&manifest)?));
+        let metadata = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy in manifest {}", manifest_cid),
+            )
+        })?;
+        store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+        Ok(store)
+    }
+
+    /// Create an empty hierarchy, whose values are added through the API of
+    /// the options.
+    pub fn create_with_options(gateway_url: &str, options: &IpfsOptions) -> Result<IpfsStore> {
+        let store = Self::connect(gateway_url, options)?;
+        let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+        store.put(crate::ENTRY_POINT_KEY, &metadata)?;
+        Ok(store)
+    }
+
+    fn connect(gateway_url: &str, options: &IpfsOptions) -> Result<IpfsStore> {
+        let gateway = format!("{}/ipfs", gateway_url.trim_end_matches('/'));
+        let api = options
+            .api_url
+            .as_ref()
+            .map(|api_url| {
+                let api_url = format!("{}/api/v0", api_url.trim_end_matches('/'));
+                HttpStore::connect(&api_url, &options.http)
+            })
+            .transpose()?;
+        Ok(IpfsStore {
+            gateway: HttpStore::connect(&gateway, &options.http)?,
+            api,
+            manifest: Arc::default(),
+            entry_point_metadata: EntryPointMetadata::default(),
+        })
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    /// A copy of the current manifest.
+    pub fn manifest(&self) -> CidManifest {
+        self.manifest.read().unwrap().clone()
+    }
+
+    /// Add the current manifest, returning its CID, which
+    /// [`open`](Self::open) opens this version of the hierarchy by.
+    pub fn publish(&self) -> Result<String> {
+        let manifest = serde_json::to_vec(&*self.manifest.read().unwrap())?;
+        self.add(&manifest)
+    }
+
+    fn cid(&self, key: &str) -> Option<String> {
+        self.manifest.read().unwrap().keys.get(key).cloned()
+    }
+
+    /// Read the whole value of a CID from the gateway.
+    fn fetch(&self, cid: &str) -> Result<Vec<u8>> {
+        let mut value = vec![];
+        self.gateway
+            .get(cid)?
+            .ok_or_else(|| not_on_gateway(cid))?
+            .read_to_end(&mut value)?;
+        Ok(value)
+    }
+
+    /// Add a value through the API, returning its CID.
+    fn add(&self, value: &[u8]) -> Result<String> {
+        let api = self.api.as_ref().ok_or_else(|| {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                "IPFS store has no API to add values",
+            )
+        })?;
+        // The boundary of the form must not occur in the value.
+        let boundary = (0u64..)
+            .map(|i| format!("zarr-ipfs-{:016x}", i))
+            .find(|boundary| {
+                !value
+                    .windows(boundary.len())
+                    .any(|window| window == boundary.as_bytes())
+            })
+            .expect("Some boundary does not occur in the value");
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"value\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            boundary
+        )
+        .into_bytes();
+        body.extend_from_slice(value);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let request = api
+            .request("POST", "add", None)?
+            .query("cid-version", "1")
+            .query("pin", "true")
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", boundary),
+            );
+        let url = request.url().to_owned();
+        let response = check_response(&url, request.send_bytes(&body))?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Not found: {}", url)))?;
+        let added: AddResponse = serde_json::from_slice(&read_body(response)?)?;
+        Ok(added.hash)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let cid = self.add(value)?;
+        self.manifest
+            .write()
+            .unwrap()
+            .keys
+            .insert(key.to_owned(), cid);
+        Ok(())
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.cid(key).map(|cid| self.fetch(&cid)).transpose()
+    }
+}
+
+fn not_on_gateway(cid: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("CID {} is not available from the gateway", cid),
+    )
+}
+
+impl ReadableStore for IpfsStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.cid(key).is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.read(key)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        match self.cid(key) {
+            Some(cid) => Ok(format!("ipfs://{}", cid)),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No key {} in the manifest", key),
+            )),
+        }
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.stat(key)?.map(|stat| stat.size))
+    }
+
+    /// Stats have the CID of the value as their entity tag.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        let cid = match self.cid(key) {
+            Some(cid) => cid,
+            None => return Ok(None),
+        };
+        let stat = self
+            .gateway
+            .stat(&cid)?
+            .ok_or_else(|| not_on_gateway(&cid))?;
+        Ok(Some(KeyStat {
+            etag: Some(cid),
+            ..stat
+        }))
+    }
+
+    fn is_read_only(&self, _key: &str) -> Result<bool> {
+        Ok(self.api.is_none())
+    }
+
+    /// Values are read in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        parallel_map(keys, |key| self.read(key))
+    }
+}
+
+impl PartialReadStore for IpfsStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        match self.cid(key) {
+            Some(cid) => Ok(Some(
+                self.gateway
+                    .get_range(&cid, offset, length)?
+                    .ok_or_else(|| not_on_gateway(&cid))?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ListableStore for IpfsStore {
+    /// Keys are listed from the manifest, without requests.
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let manifest = self.manifest.read().unwrap();
+        Ok(list_dir_of_keys(
+            prefix,
+            manifest.keys.keys().map(String::as_str),
+        ))
+    }
+}
+
+/// Erasing keys removes them from the manifest. Their values remain on IPFS,
+/// as do other versions of the hierarchy.
+impl WriteableStore for IpfsStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        self.put(key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        self.manifest.write().unwrap().keys.remove(key);
+        Ok(true)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        self.manifest
+            .write()
+            .unwrap()
+            .keys
+            .retain(|key, _| !key.starts_with(key_prefix));
+        Ok(true)
+    }
+
+    /// Values are added in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        parallel_map(pairs, |(key, value)| self.put(key, value))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{
+        Hash,
+        Hasher,
+    };
+    use std::io::{
+        BufRead,
+        BufReader,
+        Write,
+    };
+    use std::net::TcpListener;
+
+    use crate::prelude::*;
+
+    /// Serve a minimal IPFS gateway and API of blocks in memory, whose
+    /// CIDs are hashes of the values.
+    fn serve() -> String {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut blocks: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+            for stream in server.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                let mut boundary = String::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    let lower = line.to_ascii_lowercase();
+                    if let Some(length) = lower.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if let Some(i) = line.find("boundary=") {
+                        boundary = line[i + 9..].trim().to_owned();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap();
+                let path = parts.next().unwrap();
+                let (status, body) = if method == "POST" && path.starts_with("/api/v0/add?") {
+                    let start = body.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+                    let end = body.len() - boundary.len() - 8;
+                    let value = body[start..end].to_vec();
+                    let mut hasher = DefaultHasher::new();
+                    value.hash(&mut hasher);
+                    let cid = format!("bafk{:016x}", hasher.finish());
+                    blocks.insert(cid.clone(), value);
+                    let json = format!(r#"{{"Name":"value","Hash":"{}","Size":"1"}}"#, cid);
+                    ("200 OK", json.into_bytes())
+                } else {
+                    match path.strip_prefix("/ipfs/").and_then(|cid| blocks.get(cid)) {
+                        Some(value) => ("200 OK", value.clone()),
+                        None => ("404 Not Found", vec![]),
+                    }
+                };
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                if method != "HEAD" {
+                    reader.get_mut().write_all(&body).unwrap();
+                }
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_ipfs_store() {
+        let url = serve();
+        let options = IpfsOptions {
+            api_url: Some(url.clone()),
+            ..Default::default()
+        };
+        let h = IpfsStore::create_with_options(&url, &options).unwrap();
+
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        for grid_position in [[0, 0], [1, 0]].iter() {
+            let chunk = SliceDataChunk::new(grid_position.to_vec().into(), vec![1u8, 2, 3, 4]);
+            h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+        }
+        let cid = h.publish().unwrap();
+
+        // Equal chunks have the same CID.
+        let manifest = h.manifest();
+        let key = crate::storage::get_chunk_key("g/a", &array_meta, &[1, 0]);
+        assert_eq!(
+            manifest.keys[&key],
+            manifest.keys[&crate::storage::get_chunk_key("g/a", &array_meta, &[0, 0])]
+        );
+
+        let h = IpfsStore::open(&url, &cid).unwrap();
+        assert!(ReadableStore::is_read_only(&h, &key).unwrap());
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), vec![2, 3]);
+        let stat = h.stat(&key).unwrap().unwrap();
+        assert_eq!(stat.size, 4);
+        assert_eq!(stat.etag.as_ref(), Some(&manifest.keys[&key]));
+        assert_eq!(
+            h.uri(&key).unwrap(),
+            format!("ipfs://{}", manifest.keys[&key])
+        );
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.create_group("b").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+
+        // Removing a node publishes a new version, leaving the old one.
+        let h = IpfsStore::open_with_options(&url, &cid, &options).unwrap();
+        h.remove("g/a").unwrap();
+        let removed = IpfsStore::open(&url, &h.publish().unwrap()).unwrap();
+        assert!(!HierarchyReader::exists(&removed, "g/a").unwrap());
+        let original = IpfsStore::open(&url, &cid).unwrap();
+        assert!(HierarchyReader::exists(&original, "g/a").unwrap());
+    }
+}
@@ -0,0 +1,522 @@
+//! A store adapter mapping Zarr keys to different keys of the wrapped store.
+
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use crate::{
+    storage::{
+        stable_hash,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A mapping from the logical keys of a Zarr hierarchy, such as
+/// `/meta/root/foo.array.json`, to the physical keys they are stored at.
+pub trait KeyMapping {
+    /// Physical key a logical key is stored at.
+    fn to_physical(&self, key: &str) -> String;
+
+    /// Logical key stored at a physical key, or `None` if the physical key
+    /// is not part of the hierarchy.
+    fn to_logical(&self, physical_key: &str) -> Option<String>;
+
+    /// Physical prefix under which all keys with a logical prefix are
+    /// stored, or `None` if the mapping does not preserve prefixes.
+    ///
+    /// Listing or erasing a prefix of a mapping which does not preserve
+    /// prefixes lists every key of the wrapped store.
+    fn to_physical_prefix(&self, _prefix: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Stores keys under a fixed prefix, such as a subdirectory or the path of a
+/// hierarchy within a bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixMapping {
+    prefix: String,
+}
+
+impl PrefixMapping {
+    pub fn new(prefix: &str) -> Self {
+        PrefixMapping {
+            prefix: prefix.trim_matches('/').to_owned(),
+        }
+    }
+}
+
+impl KeyMapping for PrefixMapping {
+    fn to_physical(&self, key: &str) -> String {
+        format!("/{}/{}", self.prefix, key.trim_start_matches('/'))
+    }
+
+    fn to_logical(&self, physical_key: &str) -> Option<String> {
+        physical_key
+            .trim_start_matches('/')
+            .strip_prefix(&self.prefix)?
+            .strip_prefix('/')
+            .map(|key| format!("/{}", key))
+    }
+
+    fn to_physical_prefix(&self, prefix: &str) -> Option<String> {
+        Some(self.to_physical(prefix))
+    }
+}
+
+/// Stores keys under directories named for bytes of a hash of the key, such
+/// as `/3f/a2/data/root/foo/c0/0`, as used to spread keys evenly over
+/// directories or object store partitions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashedDirectoryMapping {
+    levels: usize,
+}
+
+impl HashedDirectoryMapping {
+    /// Map keys under `levels` directories, at most 8.
+    pub fn new(levels: usize) -> Self {
+        assert!(levels <= 8, "Hashed directory levels must be at most 8");
+        HashedDirectoryMapping { levels }
+    }
+
+    fn hash_dirs(&self, key: &str) -> String {
+        let hash = stable_hash(key.as_bytes()).to_be_bytes();
+        hash[..self.levels]
+            .iter()
+            .map(|byte| format!("/{:02x}", byte))
+            .collect()
+    }
+}
+
+impl KeyMapping for HashedDirectoryMapping {
+    fn to_physical(&self, key: &str) -> String {
+        let key = format!("/{}", key.trim_start_matches('/'));
+        format!("{}{}", self.hash_dirs(&key), key)
+    }
+
+    fn to_logical(&self, physical_key: &str) -> Option<String> {
+        let physical_key = format!("/{}", physical_key.trim_start_matches('/'));
+        let key = physical_key.get(3 * self.levels..)?;
+        if key.is_empty() || self.to_physical(key) != physical_key {
+            return None;
+        }
+        Some(key.to_owned())
+    }
+}
+
+/// Normalize a key to begin with a single `/` and have no empty components,
+/// such as those of repeated or trailing slashes, keeping a trailing `/` of
+/// a key prefix.
+///
+/// ```
+/// use zarr::store::key_transform::normalize_key;
+///
+/// assert_eq!(normalize_key("meta//root/foo.array.json", false), "/meta/root/foo.array.json");
+/// assert_eq!(normalize_key("//data/root/", false), "/data/root");
+/// assert_eq!(normalize_key("//data/root/", true), "/data/root/");
+/// ```
+pub fn normalize_key(key: &str, is_prefix: bool) -> String {
+    let mut normalized = String::with_capacity(key.len() + 1);
+    for component in key.split('/').filter(|c| !c.is_empty()) {
+        normalized.push('/');
+        normalized.push_str(component);
+    }
+    if is_prefix || normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Stores keys in a normal form, so that keys constructed by hand, for
+/// example from node paths with repeated slashes, match the keys of the
+/// wrapped store.
+///
+/// Keys can also be folded to lowercase, for hierarchies whose keys were
+/// lowercased by case-insensitive tools or filesystems. Keys of the wrapped
+/// store with uppercase characters can then not be read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NormalizedMapping {
+    case_folding: bool,
+}
+
+impl NormalizedMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold keys to lowercase.
+    pub fn with_case_folding(mut self) -> Self {
+        self.case_folding = true;
+        self
+    }
+
+    fn normalize(&self, key: &str, is_prefix: bool) -> String {
+        let normalized = normalize_key(key, is_prefix);
+        if self.case_folding {
+            normalized.to_lowercase()
+        } else {
+            normalized
+        }
+    }
+}
+
+impl KeyMapping for NormalizedMapping {
+    fn to_physical(&self, key: &str) -> String {
+        self.normalize(key, false)
+    }
+
+    fn to_logical(&self, physical_key: &str) -> Option<String> {
+        Some(self.normalize(physical_key, false))
+    }
+
+    fn to_physical_prefix(&self, prefix: &str) -> Option<String> {
+        Some(self.normalize(prefix, true))
+    }
+}
+
+/// A store adapter storing the keys of a Zarr hierarchy at different keys of
+/// the wrapped store, so hierarchies in nonstandard layouts can be opened
+/// without rewriting them.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::store::key_transform::PrefixStore;
+///
+/// let dir = tempdir::TempDir::new("zarr").unwrap();
+/// let h = PrefixStore::with_prefix(
+///     FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+///     "datasets/a",
+/// );
+/// h.create_group("foo").unwrap();
+/// assert!(dir.path().join("datasets/a/meta/root/foo.group.json").exists());
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyTransformStore<S, M> {
+    store: S,
+    mapping: M,
+}
+
+/// Store adapter storing keys under a prefix.
+pub type PrefixStore<S> = KeyTransformStore<S, PrefixMapping>;
+
+/// Store adapter storing keys in a normal form.
+pub type NormalizedStore<S> = KeyTransformStore<S, NormalizedMapping>;
+
+impl<S, M: KeyMapping> KeyTransformStore<S, M> {
+    pub fn new(store: S, mapping: M) -> Self {
+        KeyTransformStore { store, mapping }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    pub fn get_mapping(&self) -> &M {
+        &self.mapping
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+impl<S> PrefixStore<S> {
+    pub fn with_prefix(store: S, prefix: &str) -> Self {
+        KeyTransformStore::new(store, PrefixMapping::new(prefix))
+    }
+}
+
+impl<S> NormalizedStore<S> {
+    pub fn normalized(store: S) -> Self {
+        KeyTransformStore::new(store, NormalizedMapping::new())
+    }
+}
+
+impl<S: ListableStore, M: KeyMapping> KeyTransformStore<S, M> {
+    /// Logical keys with a prefix, found by listing the wrapped store.
+    fn logical_keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let physical_keys = match self.mapping.to_physical_prefix(prefix) {
+            Some(physical_prefix) => self.store.list_prefix(&physical_prefix)?,
+            None => self.store.list()?,
+        };
+        Ok(physical_keys
+            .iter()
+            .filter_map(|key| self.mapping.to_logical(key))
+            .filter(|key| key.starts_with(prefix))
+            .collect())
+    }
+}
+
+impl<S: Hierarchy, M> Hierarchy for KeyTransformStore<S, M> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.store.get_entry_point_metadata()
+    }
+}
+
+impl<S: ReadableStore, M: KeyMapping> ReadableStore for KeyTransformStore<S, M> {
+    type GetReader = S::GetReader;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.store.exists(&self.mapping.to_physical(key))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        self.store.get(&self.mapping.to_physical(key))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.store.uri(&self.mapping.to_physical(key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.store.size(&self.mapping.to_physical(key))
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.store.stat(&self.mapping.to_physical(key))
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.store.is_read_only(&self.mapping.to_physical(key))
+    }
+
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        let keys: Vec<String> = keys
+            .iter()
+            .map(|key| self.mapping.to_physical(key))
+            .collect();
+        self.store.get_many(&keys)
+    }
+}
+
+impl<S: PartialReadStore, M: KeyMapping> PartialReadStore for KeyTransformStore<S, M> {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.store
+            .get_range(&self.mapping.to_physical(key), offset, length)
+    }
+}
+
+impl<S: ListableStore, M: KeyMapping> ListableStore for KeyTransformStore<S, M> {
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.logical_keys_with_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        if let Some(physical_prefix) = self.mapping.to_physical_prefix(prefix) {
+            let (keys, prefixes) = self.store.list_dir(&physical_prefix)?;
+            // Listed keys are the given prefix followed by the name within
+            // it, even if the mapping normalizes the prefix.
+            let to_logical = |keys: Vec<String>| {
+                keys.iter()
+                    .filter_map(|key| key.strip_prefix(physical_prefix.as_str()))
+                    .map(|name| format!("{}{}", prefix, name))
+                    .collect()
+            };
+            return Ok((to_logical(keys), to_logical(prefixes)));
+        }
+
+        // Like directories, prefixes are only matched at separators, and
+        // listed keys are the given prefix followed by the name within it.
+        let dir = match prefix {
+            "" => "/".to_owned(),
+            p if p.ends_with('/') => p.to_owned(),
+            p => format!("{}/", p),
+        };
+        let keys = self.logical_keys_with_prefix(&dir)?;
+        if keys.is_empty() {
+            return Err(Error::new(ErrorKind::NotFound, "Prefix does not exist"));
+        }
+        let mut dir_keys = vec![];
+        let mut dir_prefixes = vec![];
+        for key in keys {
+            let name = &key[dir.len()..];
+            match name.find('/') {
+                Some(i) => dir_prefixes.push(format!("{}{}", prefix, &name[..=i])),
+                None => dir_keys.push(format!("{}{}", prefix, name)),
+            }
+        }
+        dir_keys.sort();
+        dir_prefixes.sort();
+        dir_prefixes.dedup();
+        Ok((dir_keys, dir_prefixes))
+    }
+}
+
+impl<S: WriteableStore + ListableStore, M: KeyMapping> WriteableStore for KeyTransformStore<S, M> {
+    type SetWriter = S::SetWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        self.store.set(&self.mapping.to_physical(key), value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        self.store.erase(&self.mapping.to_physical(key))
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        if let Some(physical_prefix) = self.mapping.to_physical_prefix(key_prefix) {
+            return self.store.erase_prefix(&physical_prefix);
+        }
+
+        let mut erased = true;
+        for key in self.logical_keys_with_prefix(key_prefix)? {
+            erased &= self.erase(&key)?;
+        }
+        Ok(erased)
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        if let Some(physical_prefix) = self.mapping.to_physical_prefix(key_prefix) {
+            return self.store.make_read_only(&physical_prefix);
+        }
+
+        let mut supported = true;
+        for key in self.logical_keys_with_prefix(key_prefix)? {
+            supported &= self.store.make_read_only(&self.mapping.to_physical(&key))?;
+        }
+        Ok(supported)
+    }
+
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        let pairs: Vec<(String, Vec<u8>)> = pairs
+            .iter()
+            .map(|(key, value)| (self.mapping.to_physical(key), value.clone()))
+            .collect();
+        self.store.put_many(&pairs)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_prefix_mapping() {
+        let mapping = PrefixMapping::new("/a/b/");
+        assert_eq!(mapping.to_physical("/meta/root/x"), "/a/b/meta/root/x");
+        assert_eq!(
+            mapping.to_logical("/a/b/meta/root/x").as_deref(),
+            Some("/meta/root/x")
+        );
+        assert_eq!(
+            mapping.to_logical("a/b/meta/root/x").as_deref(),
+            Some("/meta/root/x")
+        );
+        assert_eq!(mapping.to_logical("/a/bc/meta"), None);
+        assert_eq!(mapping.to_logical("/c/meta"), None);
+    }
+
+    #[test]
+    fn test_hashed_directory_mapping() {
+        let mapping = HashedDirectoryMapping::new(2);
+        let physical = mapping.to_physical("/data/root/foo/c0/0");
+        assert_eq!(physical.len(), "/data/root/foo/c0/0".len() + 6);
+        assert!(physical.ends_with("/data/root/foo/c0/0"));
+        assert_eq!(mapping.to_physical("data/root/foo/c0/0"), physical);
+        assert_eq!(
+            mapping.to_logical(&physical).as_deref(),
+            Some("/data/root/foo/c0/0")
+        );
+        assert_eq!(mapping.to_logical("/00/00/data/root/foo/c0/0"), None);
+        assert_eq!(mapping.to_logical("/zarr.json"), None);
+    }
+
+    #[test]
+    fn test_normalized_mapping() {
+        let mapping = NormalizedMapping::new();
+        assert_eq!(
+            mapping.to_physical("meta//root/Foo.array.json/"),
+            "/meta/root/Foo.array.json"
+        );
+        assert_eq!(mapping.to_physical_prefix("").as_deref(), Some("/"));
+        assert_eq!(
+            mapping.to_physical_prefix("/data//root").as_deref(),
+            Some("/data/root/")
+        );
+        let folding = mapping.with_case_folding();
+        assert_eq!(
+            folding.to_physical("/meta/root/Foo.array.json"),
+            "/meta/root/foo.array.json"
+        );
+        assert_eq!(
+            folding.to_logical("/Data/root").as_deref(),
+            Some("/data/root")
+        );
+    }
+
+    #[test]
+    fn test_normalized_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_key_transform_tests").unwrap();
+        let h =
+            NormalizedStore::normalized(FilesystemHierarchy::open_or_create(dir.path()).unwrap());
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE).build();
+        h.create_array("g//a", &array_meta).unwrap();
+        assert!(dir.path().join("meta/root/g/a.array.json").is_file());
+        assert_eq!(h.get_array_metadata("/g/a/").unwrap(), array_meta);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a"]);
+        assert_eq!(h.list_nodes("/g//").unwrap(), vec!["a"]);
+    }
+
+    fn round_trip<M: KeyMapping>(mapping: M) {
+        let dir = tempdir::TempDir::new("rust_zarr_key_transform_tests").unwrap();
+        let h = KeyTransformStore::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            mapping,
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_group("g").unwrap();
+        h.create_array("g/a", &array_meta).unwrap();
+        h.write_chunk(
+            "g/a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1, 0], vec![1u8; 4]),
+        )
+        .unwrap();
+
+        assert!(!HierarchyReader::exists(h.get_ref(), "g/a").unwrap());
+        assert_eq!(h.get_array_metadata("g/a").unwrap(), array_meta);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a"]);
+        assert_eq!(
+            h.initialized_chunks("g/a", &array_meta).unwrap(),
+            vec![GridCoord::from(&[1, 0][..])]
+        );
+        let chunk = h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.get_data(), &[1, 1, 1, 1]);
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h.initialized_chunks("g/a", &array_meta).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prefix_store() {
+        round_trip(PrefixMapping::new("nested/prefix"));
+    }
+
+    #[test]
+    fn test_hashed_directory_store() {
+        round_trip(HashedDirectoryMapping::new(1));
+    }
+}
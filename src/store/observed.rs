@@ -0,0 +1,408 @@
+//! A store wrapper notifying an observer of reads, writes and deletions.
+
+use std::io::{
+    Error,
+    Read,
+    Write,
+};
+use std::path::PathBuf;
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+use std::sync::Arc;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::{
+    storage::{
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// Kind of store operation an event describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreOperation {
+    Get,
+    Set,
+    Erase,
+    ErasePrefix,
+}
+
+/// A completed store operation.
+#[derive(Debug)]
+pub struct StoreEvent<'a> {
+    pub operation: StoreOperation,
+    /// Key, or key prefix for [`ErasePrefix`](StoreOperation::ErasePrefix).
+    pub key: &'a str,
+    /// Bytes read or written, or `None` if the key was absent or the
+    /// operation does not transfer a value.
+    pub size: Option<u64>,
+    /// Time from the operation starting until its value was fully read or
+    /// written.
+    pub elapsed: Duration,
+    /// Error the operation failed with, if any.
+    pub error: Option<&'a Error>,
+}
+
+/// Observer of operations on an [`Observed`] store.
+///
+/// Observers are called synchronously once each operation completes, so
+/// should be quick, for example only recording or queueing the event.
+pub trait StoreObserver {
+    /// Called when a value has been read, or found to be absent.
+    ///
+    /// Values are streamed, so reads are only observed once their reader is
+    /// dropped, with the number of bytes read from it.
+    fn on_read(&self, _event: &StoreEvent) {}
+
+    /// Called when a value has been written.
+    fn on_write(&self, _event: &StoreEvent) {}
+
+    /// Called when a key or key prefix has been erased.
+    fn on_delete(&self, _event: &StoreEvent) {}
+}
+
+/// A store wrapper notifying an observer of each value read, written or
+/// erased, so any store can be audited, replicated or used to warm a cache.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::store::observed::{
+///     Observed,
+///     StoreEvent,
+///     StoreObserver,
+/// };
+///
+/// struct Log;
+///
+/// impl StoreObserver for Log {
+///     fn on_write(&self, event: &StoreEvent) {
+///         println!("wrote {:?} bytes to {}", event.size, event.key);
+///     }
+/// }
+///
+/// let dir = tempdir::TempDir::new("zarr").unwrap();
+/// let h = Observed::new(FilesystemHierarchy::open_or_create(dir.path()).unwrap(), Log);
+/// h.create_group("foo").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Observed<S, O> {
+    store: S,
+    observer: Arc<O>,
+}
+
+impl<S, O> Observed<S, O> {
+    pub fn new(store: S, observer: O) -> Self {
+        Observed {
+            store,
+            observer: Arc::new(observer),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    pub fn get_observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+impl<S: Hierarchy, O> Hierarchy for Observed<S, O> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.store.get_entry_point_metadata()
+    }
+
+    fn array_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.array_metadata_key(path_name)
+    }
+
+    fn group_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.group_metadata_key(path_name)
+    }
+
+    fn data_path_key(&self, path_name: &str) -> PathBuf {
+        self.store.data_path_key(path_name)
+    }
+}
+
+/// Reader notifying an observer of the bytes read once dropped.
+#[derive(Debug)]
+pub struct ObservedReader<R, O: StoreObserver> {
+    reader: R,
+    observer: Arc<O>,
+    key: String,
+    start: Instant,
+    size: u64,
+    error: Option<Error>,
+}
+
+impl<R: Read, O: StoreObserver> Read for ObservedReader<R, O> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.reader.read(buf) {
+            Ok(n) => {
+                self.size += n as u64;
+                Ok(n)
+            }
+            Err(e) => {
+                self.error = Some(Error::new(e.kind(), e.to_string()));
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<R, O: StoreObserver> Drop for ObservedReader<R, O> {
+    fn drop(&mut self) {
+        self.observer.on_read(&StoreEvent {
+            operation: StoreOperation::Get,
+            key: &self.key,
+            size: Some(self.size),
+            elapsed: self.start.elapsed(),
+            error: self.error.as_ref(),
+        });
+    }
+}
+
+impl<S: ReadableStore, O: StoreObserver> ReadableStore for Observed<S, O> {
+    type GetReader = ObservedReader<S::GetReader, O>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        let start = Instant::now();
+        match self.store.get(key) {
+            Ok(Some(reader)) => Ok(Some(ObservedReader {
+                reader,
+                observer: Arc::clone(&self.observer),
+                key: key.to_owned(),
+                start,
+                size: 0,
+                error: None,
+            })),
+            result => {
+                self.observer.on_read(&StoreEvent {
+                    operation: StoreOperation::Get,
+                    key,
+                    size: None,
+                    elapsed: start.elapsed(),
+                    error: result.as_ref().err(),
+                });
+                result.map(|_| None)
+            }
+        }
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.store.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.store.size(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.store.stat(key)
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.store.is_read_only(key)
+    }
+}
+
+impl<S: PartialReadStore, O: StoreObserver> PartialReadStore for Observed<S, O> {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let start = Instant::now();
+        let result = self.store.get_range(key, offset, length);
+        self.observer.on_read(&StoreEvent {
+            operation: StoreOperation::Get,
+            key,
+            size: result
+                .as_ref()
+                .ok()
+                .and_then(|value| value.as_ref().map(|value| value.len() as u64)),
+            elapsed: start.elapsed(),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+}
+
+impl<S: ListableStore, O> ListableStore for Observed<S, O> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.store.list()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.store.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.store.list_dir(prefix)
+    }
+}
+
+/// Writer counting the bytes written through it.
+#[derive(Debug)]
+pub struct CountingWriter<W> {
+    writer: W,
+    count: Arc<AtomicU64>,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let n = self.writer.write(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+impl<S: WriteableStore, O: StoreObserver> WriteableStore for Observed<S, O> {
+    type SetWriter = CountingWriter<S::SetWriter>;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let count = Arc::new(AtomicU64::new(0));
+        let result = self.store.set(key, |writer| {
+            value(CountingWriter {
+                writer,
+                count: Arc::clone(&count),
+            })
+        });
+        self.observer.on_write(&StoreEvent {
+            operation: StoreOperation::Set,
+            key,
+            size: Some(count.load(Ordering::Relaxed)),
+            elapsed: start.elapsed(),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        let start = Instant::now();
+        let result = self.store.erase(key);
+        self.observer.on_delete(&StoreEvent {
+            operation: StoreOperation::Erase,
+            key,
+            size: None,
+            elapsed: start.elapsed(),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        let start = Instant::now();
+        let result = self.store.erase_prefix(key_prefix);
+        self.observer.on_delete(&StoreEvent {
+            operation: StoreOperation::ErasePrefix,
+            key: key_prefix,
+            size: None,
+            elapsed: start.elapsed(),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.store.make_read_only(key_prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::storage::get_chunk_key;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(StoreOperation, String, Option<u64>)>>);
+
+    impl Recorder {
+        fn record(&self, event: &StoreEvent) {
+            assert!(event.error.is_none());
+            self.0
+                .lock()
+                .unwrap()
+                .push((event.operation, event.key.to_owned(), event.size));
+        }
+    }
+
+    impl StoreObserver for Recorder {
+        fn on_read(&self, event: &StoreEvent) {
+            self.record(event);
+        }
+
+        fn on_write(&self, event: &StoreEvent) {
+            self.record(event);
+        }
+
+        fn on_delete(&self, event: &StoreEvent) {
+            self.record(event);
+        }
+    }
+
+    #[test]
+    fn test_observed_events() {
+        let dir = tempdir::TempDir::new("rust_zarr_observed_tests").unwrap();
+        let h = Observed::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            Recorder::default(),
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2])
+            .compressor(crate::compression::CompressionType::Raw(Default::default()))
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.get_observer().0.lock().unwrap().clear();
+
+        let chunk_key = get_chunk_key("a", &array_meta, &[1]);
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1], [3u8, 4]),
+        )
+        .unwrap();
+        h.read_chunk::<u8>("a", &array_meta, smallvec![1]).unwrap();
+        h.read_chunk::<u8>("a", &array_meta, smallvec![0]).unwrap();
+        h.delete_chunk("a", &array_meta, &[1]).unwrap();
+
+        let missing_key = get_chunk_key("a", &array_meta, &[0]);
+        assert_eq!(
+            *h.get_observer().0.lock().unwrap(),
+            vec![
+                (StoreOperation::Set, chunk_key.clone(), Some(2)),
+                (StoreOperation::Get, chunk_key.clone(), Some(2)),
+                (StoreOperation::Get, missing_key, None),
+                (StoreOperation::Erase, chunk_key, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,692 @@
+//! A store of a hierarchy in a single append-only "pack" file.
+//!
+//! Packs suit write-once hierarchies shipped as one artifact: values are
+//! appended as records as they are written and read back at their offsets,
+//! and [`PackStore::finalize`] appends an index of the records, so that
+//! opening the pack later reads only the index rather than every record.
+//!
+//! A pack is laid out as:
+//!
+//! - a header of the magic bytes `ZARRPACK` and a little-endian `u32` format
+//!   version;
+//! - records of a little-endian `u32` key length, a `u64` value length, the
+//!   key and the value, where a value length of `u64::MAX` marks the key
+//!   erased;
+//! - once finalized, the index of the live keys, as a `u64` count followed by
+//!   the `u32` length of each key, the key and the `u64` offset and length of
+//!   its value;
+//! - and a footer of the `u64` offset and length of the index followed by
+//!   the magic bytes.
+//!
+//! Writing to a finalized pack truncates its index, after which it must be
+//! finalized again. Packs which were never finalized, such as those of
+//! interrupted writers, are still opened, by scanning their records. Since
+//! records are only appended, erased and overwritten values take space until
+//! [`PackStore::compact`] rewrites the pack without them.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::pack::PackStore;
+//!
+//! let h = PackStore::open_or_create("/tmp/volume.zr3.pack").unwrap();
+//! h.create_group("raw").unwrap();
+//! h.finalize().unwrap();
+//!
+//! let h = PackStore::open("/tmp/volume.zr3.pack").unwrap();
+//! assert!(h.exists("raw").unwrap());
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs::{
+    File,
+    OpenOptions,
+};
+use std::io::{
+    BufReader,
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Result,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use crate::{
+    storage::{
+        list_dir_of_keys,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::write_buffer::BufferWriter,
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+const MAGIC: &[u8; 8] = b"ZARRPACK";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: u64 = 12;
+const RECORD_HEADER_LEN: u64 = 12;
+const FOOTER_LEN: u64 = 24;
+/// Value length of records erasing their key.
+const ERASED: u64 = u64::MAX;
+
+/// Where the value of a key is in the pack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Entry {
+    offset: u64,
+    length: u64,
+}
+
+fn invalid_pack(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid pack: {}", message))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+/// An open pack file and the index of its live keys.
+#[derive(Debug)]
+struct PackFile {
+    file: File,
+    index: BTreeMap<String, Entry>,
+    /// Offset at which the next record is appended, which is the offset of
+    /// the index of finalized packs.
+    end: u64,
+    finalized: bool,
+    writable: bool,
+}
+
+impl PackFile {
+    fn create(path: &Path) -> Result<PackFile> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        file.write_all(&header)?;
+        Ok(PackFile {
+            file,
+            index: BTreeMap::new(),
+            end: HEADER_LEN,
+            finalized: false,
+            writable: true,
+        })
+    }
+
+    fn open(path: &Path, writable: bool) -> Result<PackFile> {
+        let mut file = OpenOptions::new().read(true).write(writable).open(path)?;
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| invalid_pack("no header"))?;
+        if &header[..8] != MAGIC {
+            return Err(invalid_pack("not a pack file"));
+        }
+        if read_u32(&header[8..]) != FORMAT_VERSION {
+            return Err(invalid_pack("unsupported format version"));
+        }
+        let mut pack = PackFile {
+            file,
+            index: BTreeMap::new(),
+            end: HEADER_LEN,
+            finalized: false,
+            writable,
+        };
+        if !pack.read_index()? {
+            pack.scan()?;
+            if writable {
+                // Drop a partly written last record.
+                pack.file.set_len(pack.end)?;
+            }
+        }
+        Ok(pack)
+    }
+
+    /// Read the index of a finalized pack, returning whether it is
+    /// finalized.
+    fn read_index(&mut self) -> Result<bool> {
+        let len = self.file.metadata()?.len();
+        if len < HEADER_LEN + FOOTER_LEN {
+            return Ok(false);
+        }
+        let mut footer = [0; FOOTER_LEN as usize];
+        self.file.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+        self.file.read_exact(&mut footer)?;
+        let (index_offset, index_len) = (read_u64(&footer), read_u64(&footer[8..]));
+        if &footer[16..] != MAGIC
+            || index_offset < HEADER_LEN
+            || index_offset.checked_add(index_len) != Some(len - FOOTER_LEN)
+        {
+            return Ok(false);
+        }
+
+        let mut index = vec![0; index_len as usize];
+        self.file.seek(SeekFrom::Start(index_offset))?;
+        self.file.read_exact(&mut index)?;
+        let mut index = &index[..];
+        let mut take = |n: usize| {
+            if index.len() < n {
+                return Err(invalid_pack("truncated index"));
+            }
+            let (taken, rest) = index.split_at(n);
+            index = rest;
+            Ok(taken)
+        };
+        let count = read_u64(take(8)?);
+        for _ in 0..count {
+            let key_len = read_u32(take(4)?) as usize;
+            let key = String::from_utf8(take(key_len)?.to_vec())
+                .map_err(|_| invalid_pack("key is not UTF-8"))?;
+            let entry = Entry {
+                offset: read_u64(take(8)?),
+                length: read_u64(take(8)?),
+            };
+            if entry.offset.saturating_add(entry.length) > index_offset {
+                return Err(invalid_pack("value out of bounds"));
+            }
+            self.index.insert(key, entry);
+        }
+        self.end = index_offset;
+        self.finalized = true;
+        Ok(true)
+    }
+
+    /// Index the records of a pack which was not finalized, up to its last
+    /// complete record.
+    fn scan(&mut self) -> Result<()> {
+        let len = self.file.metadata()?.len();
+        self.file.seek(SeekFrom::Start(HEADER_LEN))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut offset = HEADER_LEN;
+        loop {
+            let mut header = [0; RECORD_HEADER_LEN as usize];
+            if reader.read_exact(&mut header).is_err() {
+                break;
+            }
+            let key_len = u64::from(read_u32(&header));
+            let value_len = read_u64(&header[4..]);
+            let stored_len = if value_len == ERASED { 0 } else { value_len };
+            let record_end = offset + RECORD_HEADER_LEN + key_len + stored_len;
+            if record_end > len {
+                break;
+            }
+            let mut key = vec![0; key_len as usize];
+            reader.read_exact(&mut key)?;
+            let key = match String::from_utf8(key) {
+                Ok(key) => key,
+                Err(_) => break,
+            };
+            if value_len == ERASED {
+                self.index.remove(&key);
+            } else {
+                let value_offset = offset + RECORD_HEADER_LEN + key_len;
+                self.index.insert(
+                    key,
+                    Entry {
+                        offset: value_offset,
+                        length: value_len,
+                    },
+                );
+                reader.seek(SeekFrom::Start(record_end))?;
+            }
+            offset = record_end;
+        }
+        self.end = offset;
+        Ok(())
+    }
+
+    fn read(&mut self, entry: Entry, offset: u64, length: Option<u64>) -> Result<Vec<u8>> {
+        let start = std::cmp::min(offset, entry.length);
+        let length = length.map_or(entry.length - start, |length| {
+            std::cmp::min(length, entry.length - start)
+        });
+        let mut value = vec![0; length as usize];
+        self.file.seek(SeekFrom::Start(entry.offset + start))?;
+        self.file.read_exact(&mut value)?;
+        Ok(value)
+    }
+
+    /// Append records of keys and values, or `None` to erase keys.
+    fn append(&mut self, records: &[(&str, Option<&[u8]>)]) -> Result<()> {
+        if !self.writable {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Pack is opened read-only",
+            ));
+        }
+        let mut bytes = vec![];
+        let mut entries = vec![];
+        for (key, value) in records {
+            let key_len: u32 = key
+                .len()
+                .try_into()
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, "Key too long"))?;
+            bytes.extend_from_slice(&key_len.to_le_bytes());
+            bytes.extend_from_slice(
+                &value
+                    .map_or(ERASED, |value| value.len() as u64)
+                    .to_le_bytes(),
+            );
+            bytes.extend_from_slice(key.as_bytes());
+            let offset = self.end + bytes.len() as u64;
+            if let Some(value) = value {
+                bytes.extend_from_slice(value);
+            }
+            entries.push(value.map(|value| Entry {
+                offset,
+                length: value.len() as u64,
+            }));
+        }
+        self.file.seek(SeekFrom::Start(self.end))?;
+        if self.finalized {
+            self.file.set_len(self.end)?;
+            self.finalized = false;
+        }
+        self.file.write_all(&bytes)?;
+        self.end += bytes.len() as u64;
+        for ((key, _), entry) in records.iter().zip(entries) {
+            match entry {
+                Some(entry) => self.index.insert(key.to_string(), entry),
+                None => self.index.remove(*key),
+            };
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        if self.finalized || !self.writable {
+            return Ok(());
+        }
+        let mut index = (self.index.len() as u64).to_le_bytes().to_vec();
+        for (key, entry) in &self.index {
+            index.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            index.extend_from_slice(key.as_bytes());
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.length.to_le_bytes());
+        }
+        index.extend_from_slice(&self.end.to_le_bytes());
+        index.extend_from_slice(&(index.len() as u64 - 8).to_le_bytes());
+        index.extend_from_slice(MAGIC);
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&index)?;
+        self.file.set_len(self.end + index.len() as u64)?;
+        self.file.sync_all()?;
+        self.finalized = true;
+        Ok(())
+    }
+
+    /// Rewrite the live values of the pack at `path` to a new finalized
+    /// pack replacing it, returning the number of bytes reclaimed.
+    fn compact(&mut self, path: &Path) -> Result<u64> {
+        if !self.writable {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Pack is opened read-only",
+            ));
+        }
+        let len = self.file.metadata()?.len();
+        let mut file_name = path.file_name().unwrap_or_default().to_owned();
+        file_name.push(".compacting");
+        let compacted_path = path.with_file_name(file_name);
+        // Remove the leftover of an interrupted compaction.
+        if compacted_path.exists() {
+            std::fs::remove_file(&compacted_path)?;
+        }
+        let compacted = PackFile::create(&compacted_path).and_then(|mut compacted| {
+            let entries: Vec<(String, Entry)> = self
+                .index
+                .iter()
+                .map(|(key, entry)| (key.clone(), *entry))
+                .collect();
+            for (key, entry) in entries {
+                let value = self.read(entry, 0, None)?;
+                compacted.append(&[(&key, Some(&value))])?;
+            }
+            compacted.finalize()?;
+            std::fs::rename(&compacted_path, path)?;
+            Ok(compacted)
+        });
+        match compacted {
+            Ok(compacted) => {
+                let compacted_len = compacted.file.metadata()?.len();
+                *self = compacted;
+                Ok(len.saturating_sub(compacted_len))
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&compacted_path);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A store of a hierarchy in a single append-only pack file.
+#[derive(Clone, Debug)]
+pub struct PackStore {
+    pack: Arc<Mutex<PackFile>>,
+    path: PathBuf,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl Hierarchy for PackStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl PackStore {
+    /// Open the Zarr hierarchy of an existing pack read-only.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PackStore> {
+        let path = path.as_ref();
+        let mut store = Self::with_pack(path, PackFile::open(path, false)?);
+        let metadata = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy in {}", path.display()),
+            )
+        })?;
+        store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+        Ok(store)
+    }
+
+    /// Open the Zarr hierarchy of a pack for writing, creating the pack if
+    /// it does not exist.
+    pub fn open_or_create<P: AsRef<Path>>(path: P) -> Result<PackStore> {
+        let path = path.as_ref();
+        let pack = if path.exists() {
+            PackFile::open(path, true)?
+        } else {
+            PackFile::create(path)?
+        };
+        let mut store = Self::with_pack(path, pack);
+        match store.get(crate::ENTRY_POINT_KEY)? {
+            Some(metadata) => {
+                store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+            }
+            None => {
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                store.put(crate::ENTRY_POINT_KEY, &metadata)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn with_pack(path: &Path, pack: PackFile) -> PackStore {
+        PackStore {
+            pack: Arc::new(Mutex::new(pack)),
+            path: path.to_owned(),
+            entry_point_metadata: EntryPointMetadata::default(),
+        }
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    /// Append the index of the pack and sync it to disk, so that it is
+    /// opened without scanning its records.
+    pub fn finalize(&self) -> Result<()> {
+        self.pack.lock().unwrap().finalize()
+    }
+
+    /// Whether the pack's index is written, that is, whether it was
+    /// finalized since it was last written to.
+    pub fn is_finalized(&self) -> bool {
+        self.pack.lock().unwrap().finalized
+    }
+
+    /// Rewrite the pack with only its live values, in key order, dropping
+    /// the records of erased and overwritten values, and finalize it.
+    /// Returns the number of bytes reclaimed.
+    pub fn compact(&self) -> Result<u64> {
+        self.pack.lock().unwrap().compact(&self.path)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.pack.lock().unwrap().append(&[(key, Some(value))])
+    }
+
+    fn entry(&self, key: &str) -> Option<Entry> {
+        self.pack.lock().unwrap().index.get(key).copied()
+    }
+}
+
+impl ReadableStore for PackStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.entry(key).is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.get_range(key, 0, None)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        Ok(format!("pack:{}!{}", self.path.display(), key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.entry(key).map(|entry| entry.length))
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self.size(key)?.map(KeyStat::new))
+    }
+
+    fn is_read_only(&self, _key: &str) -> Result<bool> {
+        Ok(!self.pack.lock().unwrap().writable)
+    }
+}
+
+impl PartialReadStore for PackStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        let mut pack = self.pack.lock().unwrap();
+        match pack.index.get(key).copied() {
+            Some(entry) => pack.read(entry, offset, length).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ListableStore for PackStore {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let pack = self.pack.lock().unwrap();
+        Ok(list_dir_of_keys(
+            prefix,
+            pack.index.keys().map(String::as_str),
+        ))
+    }
+}
+
+impl WriteableStore for PackStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        self.put(key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        let mut pack = self.pack.lock().unwrap();
+        if pack.index.contains_key(key) {
+            pack.append(&[(key, None)])?;
+        }
+        Ok(true)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        let mut pack = self.pack.lock().unwrap();
+        let keys: Vec<String> = pack
+            .index
+            .keys()
+            .filter(|key| key.starts_with(key_prefix))
+            .cloned()
+            .collect();
+        let records: Vec<(&str, Option<&[u8]>)> =
+            keys.iter().map(|key| (key.as_str(), None)).collect();
+        pack.append(&records)?;
+        Ok(true)
+    }
+
+    /// Values are appended in one write.
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        let records: Vec<(&str, Option<&[u8]>)> = pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), Some(value.as_slice())))
+            .collect();
+        self.pack.lock().unwrap().append(&records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_pack_compact() {
+        let dir = tempdir::TempDir::new("rust_zarr_pack_tests").unwrap();
+        let path = dir.path().join("a.zr3.pack");
+        let h = PackStore::open_or_create(&path).unwrap();
+        h.put("/a", &[1; 1000]).unwrap();
+        h.put("/a", &[2; 10]).unwrap();
+        h.put("/b", &[3; 1000]).unwrap();
+        h.erase("/b").unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        let reclaimed = h.compact().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len - reclaimed);
+        assert!(reclaimed > 1900);
+        assert!(h.is_finalized());
+        assert_eq!(h.get_range("/a", 0, None).unwrap(), Some(vec![2; 10]));
+        assert!(!ReadableStore::exists(&h, "/b").unwrap());
+        assert!(!dir.path().join("a.zr3.pack.compacting").exists());
+
+        h.put("/c", &[4]).unwrap();
+        h.finalize().unwrap();
+        let h = PackStore::open(&path).unwrap();
+        assert_eq!(
+            h.list_prefix("/").unwrap(),
+            vec!["/a".to_owned(), "/c".to_owned()]
+        );
+        assert_eq!(h.compact().unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_pack_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_pack_tests").unwrap();
+        let path = dir.path().join("a.zr3.pack");
+        assert_eq!(
+            PackStore::open(&path).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+        {
+            let h = PackStore::open_or_create(&path).unwrap();
+            h.create_array("g/a", &array_meta).unwrap();
+            h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+            h.put_many(&[("/x/1".to_owned(), vec![1]), ("/x/2".to_owned(), vec![2])])
+                .unwrap();
+            h.erase("/x/1").unwrap();
+            assert!(!h.is_finalized());
+            h.finalize().unwrap();
+            assert!(h.is_finalized());
+        }
+
+        let h = PackStore::open(&path).unwrap();
+        assert!(h.is_finalized());
+        assert!(h.is_read_only("/x/2").unwrap());
+        assert_eq!(
+            h.put_many(&[("/x/3".to_owned(), vec![3])])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        let key = crate::storage::get_chunk_key("g/a", &array_meta, &[1, 0]);
+        let value = h.get(&key).unwrap().unwrap().into_inner();
+        assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), value[1..3]);
+        assert_eq!(h.size(&key).unwrap(), Some(value.len() as u64));
+        assert_eq!(h.list_prefix("/x/").unwrap(), vec!["/x/2".to_owned()]);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/").unwrap(),
+            (vec![], vec!["/meta/root/".to_owned()])
+        );
+
+        // Writing truncates the index, and unfinalized packs are scanned.
+        let h = PackStore::open_or_create(&path).unwrap();
+        h.remove("g/a").unwrap();
+        h.erase_prefix("/x/").unwrap();
+        drop(h);
+        let h = PackStore::open(&path).unwrap();
+        assert!(!h.is_finalized());
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h.list_prefix("/x/").unwrap().is_empty());
+        assert!(ReadableStore::exists(&h, crate::ENTRY_POINT_KEY).unwrap());
+
+        // A partly written record is ignored.
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[5, 0, 0, 0, 9]).unwrap();
+        let h = PackStore::open_or_create(&path).unwrap();
+        assert_eq!(h.pack.lock().unwrap().end, len);
+        h.put("/y", &[1, 2]).unwrap();
+        h.finalize().unwrap();
+        assert_eq!(
+            PackStore::open(&path)
+                .unwrap()
+                .get_range("/y", 0, None)
+                .unwrap(),
+            Some(vec![1, 2])
+        );
+
+        std::fs::write(dir.path().join("other"), b"not a pack").unwrap();
+        assert_eq!(
+            PackStore::open(dir.path().join("other"))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
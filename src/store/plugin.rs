@@ -0,0 +1,157 @@
+//! Stores of URL schemes registered at runtime.
+//!
+//! Crates adding a backend implement [`DynStore`] for it and register a
+//! factory for its URL scheme, after which
+//! [`open_from_url`](crate::store::url::open_from_url) opens URLs of that
+//! scheme with it:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use zarr::store::plugin::{
+//!     register_scheme,
+//!     DynStore,
+//! };
+//! use zarr::store::url::open_from_url;
+//!
+//! # #[derive(Debug)]
+//! # struct IpfsStore(zarr::EntryPointMetadata);
+//! # impl IpfsStore {
+//! #     fn connect(_url: &str) -> std::io::Result<Self> {
+//! #         Err(std::io::Error::new(std::io::ErrorKind::NotFound, "offline"))
+//! #     }
+//! # }
+//! # impl DynStore for IpfsStore {
+//! #     fn get_entry_point_metadata(&self) -> &zarr::EntryPointMetadata {
+//! #         &self.0
+//! #     }
+//! #     fn get(&self, _key: &str) -> std::io::Result<Option<Vec<u8>>> {
+//! #         Ok(None)
+//! #     }
+//! #     fn uri(&self, key: &str) -> std::io::Result<String> {
+//! #         Ok(key.to_owned())
+//! #     }
+//! # }
+//! register_scheme("ipfs", |url| Ok(Arc::new(IpfsStore::connect(url)?)));
+//! assert!(open_from_url("ipfs://bafybeigdyrzt/volume.zr3").is_err());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+use std::sync::{
+    Arc,
+    OnceLock,
+    RwLock,
+};
+
+use crate::storage::KeyStat;
+use crate::EntryPointMetadata;
+
+/// A store usable as a trait object, for backends of registered schemes.
+///
+/// Only reading whole values is required. Listing and writing fail with
+/// [`ErrorKind::Unsupported`] and [`ErrorKind::PermissionDenied`] unless
+/// the store implements them.
+pub trait DynStore: Debug + Send + Sync {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata;
+
+    /// Read the whole value at a key, or `None` if it does not exist.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    fn uri(&self, key: &str) -> Result<String, Error>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// The default implementation reads the whole value.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        Ok(self.get(key)?.map(|value| KeyStat::new(value.len() as u64)))
+    }
+
+    /// The default implementation reads the whole value.
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.get(key)?.map(|value| {
+            let start = std::cmp::min(offset, value.len() as u64);
+            let end = length.map_or(value.len() as u64, |length| {
+                std::cmp::min(start.saturating_add(length), value.len() as u64)
+            });
+            value[start as usize..end as usize].to_vec()
+        }))
+    }
+
+    fn is_read_only(&self, _key: &str) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Retrieve all keys and prefixes with a given prefix and which do not
+    /// contain the character “/” after the given prefix.
+    fn list_dir(&self, _prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        Err(Error::new(ErrorKind::Unsupported, "Store cannot be listed"))
+    }
+
+    /// Write the whole value at a key.
+    fn set(&self, _key: &str, _value: &[u8]) -> Result<(), Error> {
+        Err(read_only())
+    }
+
+    fn erase(&self, _key: &str) -> Result<bool, Error> {
+        Err(read_only())
+    }
+
+    fn erase_prefix(&self, _key_prefix: &str) -> Result<bool, Error> {
+        Err(read_only())
+    }
+}
+
+fn read_only() -> Error {
+    Error::new(ErrorKind::PermissionDenied, "Store is read-only")
+}
+
+/// Opens the store of a URL of a registered scheme.
+pub type StoreFactory = dyn Fn(&str) -> Result<Arc<dyn DynStore>, Error> + Send + Sync;
+
+fn registry() -> &'static RwLock<HashMap<String, Arc<StoreFactory>>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<StoreFactory>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register the factory opening URLs of a scheme, replacing any factory
+/// registered for it before. Schemes are case-insensitive, and registered
+/// schemes take precedence over the schemes of this crate's stores.
+pub fn register_scheme<F>(scheme: &str, factory: F)
+where
+    F: Fn(&str) -> Result<Arc<dyn DynStore>, Error> + Send + Sync + 'static,
+{
+    registry()
+        .write()
+        .unwrap()
+        .insert(scheme.to_ascii_lowercase(), Arc::new(factory));
+}
+
+/// Remove the factory of a scheme, returning whether one was registered.
+pub fn unregister_scheme(scheme: &str) -> bool {
+    registry()
+        .write()
+        .unwrap()
+        .remove(&scheme.to_ascii_lowercase())
+        .is_some()
+}
+
+/// The factory registered for a scheme.
+pub(crate) fn scheme_factory(scheme: &str) -> Option<Arc<StoreFactory>> {
+    registry()
+        .read()
+        .unwrap()
+        .get(&scheme.to_ascii_lowercase())
+        .cloned()
+}
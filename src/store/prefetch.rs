@@ -0,0 +1,781 @@
+//! A caching store wrapper which fetches chunks ahead of reads.
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+use std::io::{
+    Cursor,
+    Error,
+    Read,
+};
+use std::path::PathBuf;
+use std::sync::mpsc::{
+    self,
+    Receiver,
+    Sender,
+};
+use std::sync::{
+    Arc,
+    Condvar,
+    Mutex,
+};
+use std::thread::JoinHandle;
+
+#[cfg(feature = "use_ndarray")]
+use crate::chunk::{
+    DataChunk,
+    ReadableDataChunk,
+    ReinitDataChunk,
+    VecDataChunk,
+};
+#[cfg(feature = "use_ndarray")]
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+};
+use crate::storage::{
+    ChunkKeyFormatter,
+    KeyStat,
+    ListableStore,
+    ReadableStore,
+    WriteableStore,
+};
+#[cfg(feature = "use_ndarray")]
+use crate::ReflectedType;
+use crate::{
+    config,
+    ArrayMetadata,
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A store wrapper caching values read from the wrapped store, which can be
+/// told which chunks will be read next so they are fetched into the cache in
+/// the background.
+///
+/// This is most useful for remote stores, where fetching the next chunks of
+/// a slicing or iteration pattern while the current ones are processed
+/// hides the latency of each request. Values are evicted from the cache in
+/// least recently used order once it holds more than its capacity in bytes.
+///
+/// Writes through the wrapper invalidate cached values, but writes made to
+/// the wrapped store by other means are not seen until evicted, unless
+/// cached values are validated with [`with_validation`](Self::with_validation).
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::smallvec::smallvec;
+/// use zarr::store::prefetch::Prefetch;
+///
+/// let h = Prefetch::new(FilesystemHierarchy::open("tests/data/zarrita.zr3").unwrap(), 1 << 26);
+/// let array_meta = h.get_array_metadata("seq/i2").unwrap();
+/// h.prefetch_chunks("seq/i2", &array_meta, vec![[0, 0, 0], [0, 0, 1]]);
+/// // ...
+/// let chunk = h.read_chunk::<i16>("seq/i2", &array_meta, smallvec![0, 0, 0]).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Prefetch<S> {
+    shared: Arc<Shared<S>>,
+    sender: Option<Sender<String>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Shared<S> {
+    store: S,
+    state: Mutex<State>,
+    idle: Condvar,
+}
+
+/// A cached value, or `None` for a key known to be absent.
+type CachedValue = Option<Arc<[u8]>>;
+
+#[derive(Debug)]
+struct State {
+    capacity: usize,
+    bytes: usize,
+    tick: u64,
+    /// Cached values with their stat when fetched, if validating, and the
+    /// tick of their last use.
+    entries: HashMap<String, (CachedValue, Option<KeyStat>, u64)>,
+    /// Cached keys by the tick of their last use.
+    lru: BTreeMap<u64, String>,
+    /// Keys waiting to be prefetched or being prefetched.
+    pending: HashSet<String>,
+    validate: bool,
+    closed: bool,
+}
+
+fn entry_size(key: &str, value: &CachedValue) -> usize {
+    key.len() + value.as_ref().map_or(0, |v| v.len())
+}
+
+impl State {
+    fn get(&mut self, key: &str) -> Option<(CachedValue, Option<KeyStat>)> {
+        let (value, stat, tick) = self.entries.get_mut(key)?;
+        self.lru.remove(tick);
+        self.tick += 1;
+        *tick = self.tick;
+        self.lru.insert(self.tick, key.to_owned());
+        Some((value.clone(), stat.clone()))
+    }
+
+    fn insert(&mut self, key: String, value: CachedValue, stat: Option<KeyStat>) {
+        self.remove(&key);
+        let size = entry_size(&key, &value);
+        if size > self.capacity {
+            return;
+        }
+        while self.bytes + size > self.capacity {
+            let (_, oldest) = self
+                .lru
+                .pop_first()
+                .expect("Cache accounting is consistent");
+            if let Some((value, _, _)) = self.entries.remove(&oldest) {
+                self.bytes -= entry_size(&oldest, &value);
+            }
+        }
+
+        self.tick += 1;
+        self.bytes += size;
+        self.lru.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, stat, self.tick));
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.pending.remove(key);
+        if let Some((value, _, tick)) = self.entries.remove(key) {
+            self.lru.remove(&tick);
+            self.bytes -= entry_size(key, &value);
+        }
+    }
+}
+
+impl<S: ReadableStore> Shared<S> {
+    /// Fetch a value, and its stat if validating.
+    fn fetch(&self, key: &str, validate: bool) -> Result<(CachedValue, Option<KeyStat>), Error> {
+        // Stat before reading, so that a value changed in between is
+        // refetched rather than kept.
+        let stat = if validate {
+            self.store.stat(key)?
+        } else {
+            None
+        };
+        let value = self
+            .store
+            .get(key)?
+            .map(|mut reader| -> Result<Arc<[u8]>, Error> {
+                let mut value = Vec::new();
+                reader.read_to_end(&mut value)?;
+                Ok(value.into())
+            })
+            .transpose()?;
+        Ok((value, stat))
+    }
+
+    fn work(&self, receiver: &Mutex<Receiver<String>>) {
+        loop {
+            let key = match receiver.lock().expect("Prefetch lock poisoned").recv() {
+                Ok(key) => key,
+                Err(_) => return,
+            };
+            let validate = {
+                let state = self.state.lock().expect("Prefetch lock poisoned");
+                if state.closed {
+                    return;
+                }
+                state.validate
+            };
+
+            // Failed prefetches are left for the read itself to report.
+            let fetched = self.fetch(&key, validate);
+            let mut state = self.state.lock().expect("Prefetch lock poisoned");
+            // Keys written or erased while being fetched are no longer
+            // pending, and their fetched value may be stale.
+            if state.pending.remove(&key) {
+                if let Ok((value, stat)) = fetched {
+                    state.insert(key, value, stat);
+                }
+            }
+            if state.pending.is_empty() {
+                self.idle.notify_all();
+            }
+        }
+    }
+}
+
+impl<S: ReadableStore + Send + Sync + 'static> Prefetch<S> {
+    /// Wrap a store with a cache holding at most `capacity` bytes, fetching
+    /// with as many threads as the configured
+    /// [concurrency](crate::config::Config::concurrency).
+    pub fn new(store: S, capacity: usize) -> Self {
+        let shared = Arc::new(Shared {
+            store,
+            state: Mutex::new(State {
+                capacity,
+                bytes: 0,
+                tick: 0,
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                pending: HashSet::new(),
+                validate: false,
+                closed: false,
+            }),
+            idle: Condvar::new(),
+        });
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..config::config().concurrency)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                let receiver = Arc::clone(&receiver);
+                std::thread::spawn(move || shared.work(&receiver))
+            })
+            .collect();
+
+        Prefetch {
+            shared,
+            sender: Some(sender),
+            workers,
+        }
+    }
+}
+
+impl<S> Prefetch<S> {
+    pub fn get_ref(&self) -> &S {
+        &self.shared.store
+    }
+
+    /// Check each cached value read against the [stat](ReadableStore::stat)
+    /// of its key in the wrapped store, refetching values which may have
+    /// changed, so that writes made to the wrapped store by other means are
+    /// seen.
+    ///
+    /// This still makes a request of the wrapped store for each read, but
+    /// only for the stat rather than the value.
+    pub fn with_validation(self) -> Self {
+        self.shared
+            .state
+            .lock()
+            .expect("Prefetch lock poisoned")
+            .validate = true;
+        self
+    }
+
+    /// Fetch values into the cache in the background, unless already cached
+    /// or pending.
+    pub fn prefetch_keys<I: IntoIterator<Item = String>>(&self, keys: I) {
+        let sender = self.sender.as_ref().expect("Prefetch is open");
+        let mut state = self.shared.state.lock().expect("Prefetch lock poisoned");
+        for key in keys {
+            if !state.entries.contains_key(&key) && state.pending.insert(key.clone()) {
+                // Workers only stop once the sender is dropped.
+                sender.send(key).expect("Prefetch workers are running");
+            }
+        }
+    }
+
+    /// Block until all pending prefetches have completed.
+    pub fn wait(&self) {
+        let state = self.shared.state.lock().expect("Prefetch lock poisoned");
+        let _state = self
+            .shared
+            .idle
+            .wait_while(state, |state| !state.pending.is_empty())
+            .expect("Prefetch lock poisoned");
+    }
+
+    fn invalidate<F: Fn(&str) -> bool>(&self, predicate: F) {
+        let mut state = self.shared.state.lock().expect("Prefetch lock poisoned");
+        let keys: Vec<String> = state
+            .entries
+            .keys()
+            .chain(state.pending.iter())
+            .filter(|key| predicate(key))
+            .cloned()
+            .collect();
+        for key in keys {
+            state.remove(&key);
+        }
+        if state.pending.is_empty() {
+            self.shared.idle.notify_all();
+        }
+    }
+}
+
+impl<S: Hierarchy> Prefetch<S> {
+    /// Fetch chunks of an array into the cache in the background.
+    pub fn prefetch_chunks<C: AsRef<[u64]>, I: IntoIterator<Item = C>>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        grid_positions: I,
+    ) {
+        let formatter = ChunkKeyFormatter::new(path_name, array_meta);
+        self.prefetch_keys(
+            grid_positions
+                .into_iter()
+                .filter(|coord| array_meta.in_bounds(&coord.as_ref().into()))
+                .map(|coord| formatter.key(coord.as_ref())),
+        );
+    }
+
+    /// Fetch the chunks of an array intersecting a region into the cache in
+    /// the background.
+    #[cfg(feature = "use_ndarray")]
+    pub fn prefetch_region(&self, path_name: &str, array_meta: &ArrayMetadata, bbox: &BoundingBox) {
+        self.prefetch_chunks(path_name, array_meta, array_meta.bounded_coord_iter(bbox));
+    }
+
+    /// Fetch the chunks of a sequence of regions into the cache in the
+    /// background, as when the regions will be read in that order.
+    #[cfg(feature = "use_ndarray")]
+    pub fn prefetch_regions<'a, I: IntoIterator<Item = &'a BoundingBox>>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        regions: I,
+    ) {
+        for bbox in regions {
+            self.prefetch_region(path_name, array_meta, bbox);
+        }
+    }
+
+    /// Fetch the chunks of the `count` regions following a region along an
+    /// axis into the cache in the background, as when iterating through an
+    /// array a slab or plane at a time.
+    #[cfg(feature = "use_ndarray")]
+    pub fn prefetch_along(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        axis: usize,
+        count: u64,
+    ) {
+        let step = bbox.shape()[axis];
+        let regions: Vec<BoundingBox> = (1..=count)
+            .map(|i| {
+                let mut offset = crate::GridCoord::from(bbox.offset());
+                offset[axis] = offset[axis].saturating_add(step.saturating_mul(i));
+                BoundingBox::new(offset, bbox.shape().into())
+            })
+            .take_while(|next| next.offset()[axis] < array_meta.get_shape()[axis])
+            .collect();
+        self.prefetch_regions(path_name, array_meta, &regions);
+    }
+}
+
+#[cfg(feature = "use_ndarray")]
+impl<S: ReadableStore + Hierarchy> Prefetch<S> {
+    /// Iterate an array in successive slabs `thickness` elements thick
+    /// along an axis, fetching the chunks of the next slab in the background
+    /// while the current one is processed.
+    ///
+    /// The last slab is thinner if the axis is not a multiple of the
+    /// thickness.
+    pub fn iter_slabs<T>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        axis: usize,
+        thickness: u64,
+    ) -> SlabIter<'_, S, T> {
+        assert!(axis < array_meta.get_ndim(), "Axis is out of bounds");
+        assert!(thickness > 0, "Slabs must have a positive thickness");
+        let mut shape = crate::GridCoord::from(array_meta.get_shape());
+        shape[axis] = thickness;
+        let next = BoundingBox::new(smallvec![0; shape.len()], shape);
+        self.prefetch_region(path_name, array_meta, &next);
+
+        SlabIter {
+            store: self,
+            path_name: path_name.to_owned(),
+            array_meta: array_meta.clone(),
+            axis,
+            next,
+            data_type: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Iterator over the slabs of an array. See
+/// [`Prefetch::iter_slabs`](Prefetch::iter_slabs).
+#[cfg(feature = "use_ndarray")]
+#[derive(Debug)]
+pub struct SlabIter<'a, S, T> {
+    store: &'a Prefetch<S>,
+    path_name: String,
+    array_meta: ArrayMetadata,
+    axis: usize,
+    next: BoundingBox,
+    data_type: std::marker::PhantomData<T>,
+}
+
+#[cfg(feature = "use_ndarray")]
+impl<'a, S, T> Iterator for SlabIter<'a, S, T>
+where
+    S: ReadableStore + Hierarchy,
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+    T: ReflectedType,
+{
+    type Item = Result<(BoundingBox, ndarray::ArrayD<T>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let axis = self.axis;
+        let extent = self.array_meta.get_shape()[axis];
+        if self.next.offset()[axis] >= extent {
+            return None;
+        }
+        let mut bbox = self.next.clone();
+        bbox.intersect(&self.array_meta.get_bounds());
+
+        let mut offset = crate::GridCoord::from(self.next.offset());
+        offset[axis] += self.next.shape()[axis];
+        self.next = BoundingBox::new(offset, self.next.shape().into());
+        if self.next.offset()[axis] < extent {
+            self.store
+                .prefetch_region(&self.path_name, &self.array_meta, &self.next);
+        }
+
+        Some(
+            self.store
+                .read_ndarray(&self.path_name, &self.array_meta, &bbox)
+                .map(|array| (bbox, array)),
+        )
+    }
+}
+
+impl<S> Drop for Prefetch<S> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .lock()
+            .expect("Prefetch lock poisoned")
+            .closed = true;
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<S: Hierarchy> Hierarchy for Prefetch<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.shared.store.get_entry_point_metadata()
+    }
+
+    fn array_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.shared.store.array_metadata_key(path_name)
+    }
+
+    fn group_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.shared.store.group_metadata_key(path_name)
+    }
+
+    fn data_path_key(&self, path_name: &str) -> PathBuf {
+        self.shared.store.data_path_key(path_name)
+    }
+}
+
+impl<S: ReadableStore> Prefetch<S> {
+    /// Get a cached value, or `None` if it is not cached or, if validating,
+    /// may have changed.
+    fn cached(&self, key: &str) -> Result<Option<CachedValue>, Error> {
+        let (cached, validate) = {
+            let mut state = self.shared.state.lock().expect("Prefetch lock poisoned");
+            (state.get(key), state.validate)
+        };
+        match cached {
+            Some((value, stat)) if validate => {
+                let valid = match (stat, self.shared.store.stat(key)?) {
+                    (Some(stat), Some(current)) => !stat.may_differ(&current),
+                    (None, None) => value.is_none(),
+                    _ => false,
+                };
+                Ok(valid.then_some(value))
+            }
+            cached => Ok(cached.map(|(value, _)| value)),
+        }
+    }
+}
+
+impl<S: ReadableStore> ReadableStore for Prefetch<S> {
+    type GetReader = Cursor<Arc<[u8]>>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        match self.cached(key)? {
+            Some(value) => Ok(value.is_some()),
+            None => self.shared.store.exists(key),
+        }
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        let value = match self.cached(key)? {
+            Some(value) => value,
+            None => {
+                let validate = self
+                    .shared
+                    .state
+                    .lock()
+                    .expect("Prefetch lock poisoned")
+                    .validate;
+                let (value, stat) = self.shared.fetch(key, validate)?;
+                let mut state = self.shared.state.lock().expect("Prefetch lock poisoned");
+                state.pending.remove(key);
+                state.insert(key.to_owned(), value.clone(), stat);
+                value
+            }
+        };
+        Ok(value.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.shared.store.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        match self.cached(key)? {
+            Some(value) => Ok(value.map(|v| v.len() as u64)),
+            None => self.shared.store.size(key),
+        }
+    }
+
+    /// Stats are always of the wrapped store, so are current.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.shared.store.stat(key)
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.shared.store.is_read_only(key)
+    }
+
+    /// Values which are not cached are fetched with a single `get_many` of
+    /// the wrapped store, and cached.
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut uncached = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            let value = self.cached(key)?;
+            if value.is_none() {
+                uncached.push(i);
+            }
+            values.push(value.flatten().map(|value| value.to_vec()));
+        }
+        if uncached.is_empty() {
+            return Ok(values);
+        }
+
+        let uncached_keys: Vec<String> = uncached.iter().map(|&i| keys[i].clone()).collect();
+        let validate = self
+            .shared
+            .state
+            .lock()
+            .expect("Prefetch lock poisoned")
+            .validate;
+        // Stat before reading, as for single values.
+        let stats = if validate {
+            uncached_keys
+                .iter()
+                .map(|key| self.shared.store.stat(key))
+                .collect::<Result<Vec<_>, Error>>()?
+        } else {
+            vec![None; uncached_keys.len()]
+        };
+        let fetched = self.shared.store.get_many(&uncached_keys)?;
+
+        let mut state = self.shared.state.lock().expect("Prefetch lock poisoned");
+        for ((i, value), stat) in uncached.into_iter().zip(fetched).zip(stats) {
+            state.pending.remove(&keys[i]);
+            state.insert(keys[i].clone(), value.as_deref().map(Arc::from), stat);
+            values[i] = value;
+        }
+        Ok(values)
+    }
+}
+
+impl<S: ListableStore> ListableStore for Prefetch<S> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.shared.store.list()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.shared.store.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.shared.store.list_dir(prefix)
+    }
+}
+
+impl<S: WriteableStore> WriteableStore for Prefetch<S> {
+    type SetWriter = S::SetWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        let result = self.shared.store.set(key, value);
+        self.invalidate(|k| k == key);
+        result
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        let result = self.shared.store.erase(key);
+        self.invalidate(|k| k == key);
+        result
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        let result = self.shared.store.erase_prefix(key_prefix);
+        self.invalidate(|k| k.starts_with(key_prefix));
+        result
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.shared.store.make_read_only(key_prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem", feature = "use_ndarray"))]
+mod tests {
+    use super::*;
+    use crate::ndarray::ZarrNdarrayWriter;
+    use crate::prelude::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_prefetch() {
+        let dir = tempdir::TempDir::new("rust_zarr_prefetch_tests").unwrap();
+        let h = Prefetch::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            1 << 20,
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![8, 4], i32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        let chunk_data: Vec<i32> = (0..8).collect();
+        for i in 0..4 {
+            h.write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(smallvec![i, 0], &chunk_data),
+            )
+            .unwrap();
+        }
+
+        let bbox = BoundingBox::new(smallvec![0, 0], smallvec![2, 4]);
+        h.prefetch_along("a", &array_meta, &bbox, 0, 2);
+        h.wait();
+        // Remove chunks from the wrapped store, so only cached chunks can
+        // still be read.
+        for i in 0..4 {
+            h.get_ref().delete_chunk("a", &array_meta, &[i, 0]).unwrap();
+        }
+
+        let read = |i| {
+            h.read_chunk::<i32>("a", &array_meta, smallvec![i, 0])
+                .unwrap()
+        };
+        assert!(read(0).is_none());
+        assert_eq!(read(1).unwrap().get_data(), &chunk_data[..]);
+        assert_eq!(read(2).unwrap().get_data(), &chunk_data[..]);
+        assert!(read(3).is_none());
+
+        // Writes invalidate the cache.
+        h.delete_chunk("a", &array_meta, &[1, 0]).unwrap();
+        assert!(read(1).is_none());
+    }
+
+    #[test]
+    fn test_iter_slabs() {
+        let dir = tempdir::TempDir::new("rust_zarr_prefetch_tests").unwrap();
+        let h = Prefetch::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            1 << 20,
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![3, 7], i32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        let array = ndarray::Array::from_shape_vec((3, 7), (0..21).collect())
+            .unwrap()
+            .into_dyn();
+        h.write_ndarray("a", &array_meta, smallvec![0, 0], &array)
+            .unwrap();
+
+        let slabs = h
+            .iter_slabs::<i32>("a", &array_meta, 1, 3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let offsets: Vec<_> = slabs.iter().map(|(bbox, _)| bbox.offset()[1]).collect();
+        assert_eq!(offsets, vec![0, 3, 6]);
+        for (bbox, slab) in &slabs {
+            for (index, &value) in slab.indexed_iter() {
+                let column = index[1] + bbox.offset()[1] as usize;
+                assert_eq!(value, array[[index[0], column]]);
+            }
+        }
+        assert_eq!(slabs[2].1.shape(), &[3, 1]);
+    }
+
+    #[test]
+    fn test_validation() {
+        let dir = tempdir::TempDir::new("rust_zarr_prefetch_tests").unwrap();
+        let h = Prefetch::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            1 << 20,
+        );
+        let read = |h: &Prefetch<FilesystemHierarchy>| {
+            let mut value = vec![];
+            h.get("/x")
+                .unwrap()
+                .unwrap()
+                .read_to_end(&mut value)
+                .unwrap();
+            value
+        };
+        h.get_ref().set("/x", |mut w| w.write_all(b"old")).unwrap();
+        assert_eq!(read(&h), b"old");
+        h.get_ref()
+            .set("/x", |mut w| w.write_all(b"newer"))
+            .unwrap();
+        // Without validation the stale value is read from the cache.
+        assert_eq!(read(&h), b"old");
+
+        let h = Prefetch::new(h.get_ref().clone(), 1 << 20).with_validation();
+        assert_eq!(read(&h), b"newer");
+        h.get_ref().set("/x", |mut w| w.write_all(b"new")).unwrap();
+        assert_eq!(read(&h), b"new");
+        assert_eq!(h.size("/x").unwrap(), Some(3));
+        h.get_ref().erase("/x").unwrap();
+        assert!(!ReadableStore::exists(&h, "/x").unwrap());
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut state = State {
+            capacity: 10,
+            bytes: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            pending: HashSet::new(),
+            validate: false,
+            closed: false,
+        };
+        state.insert("a".to_owned(), Some(vec![0; 4].into()), None);
+        state.insert("b".to_owned(), Some(vec![0; 4].into()), None);
+        assert!(state.get("a").is_some());
+        state.insert("c".to_owned(), None, None);
+        assert_eq!(state.bytes, 6);
+        assert!(state.get("b").is_none());
+        assert!(state.get("a").is_some());
+        state.insert("d".to_owned(), Some(vec![0; 20].into()), None);
+        assert!(state.get("d").is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! A wrapper preventing modification of a store.
+
+use std::io::Error;
+use std::path::PathBuf;
+
+use crate::{
+    storage::{
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A store wrapper exposing only reading and listing.
+///
+/// This does not implement [`WriteableStore`](crate::storage::WriteableStore),
+/// so it is not a [`HierarchyWriter`](crate::HierarchyWriter) and code given
+/// it cannot modify the wrapped hierarchy. All arrays read through it are
+/// [read-only](crate::ArrayMetadata::is_read_only).
+///
+/// ```compile_fail
+/// use zarr::prelude::*;
+/// use zarr::store::read_only::ReadOnly;
+///
+/// let h = ReadOnly::new(FilesystemHierarchy::open("tests/data/zarrita.zr3").unwrap());
+/// h.create_group("foo").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReadOnly<S>(S);
+
+impl<S> ReadOnly<S> {
+    pub fn new(store: S) -> Self {
+        ReadOnly(store)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S: Hierarchy> Hierarchy for ReadOnly<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.0.get_entry_point_metadata()
+    }
+
+    fn array_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.0.array_metadata_key(path_name)
+    }
+
+    fn group_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.0.group_metadata_key(path_name)
+    }
+
+    fn data_path_key(&self, path_name: &str) -> PathBuf {
+        self.0.data_path_key(path_name)
+    }
+}
+
+impl<S: ReadableStore> ReadableStore for ReadOnly<S> {
+    type GetReader = S::GetReader;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.0.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        self.0.get(key)
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.0.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.0.size(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.0.stat(key)
+    }
+
+    fn is_read_only(&self, _key: &str) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        self.0.get_many(keys)
+    }
+}
+
+impl<S: PartialReadStore> PartialReadStore for ReadOnly<S> {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.0.get_range(key, offset, length)
+    }
+}
+
+impl<S: ListableStore> ListableStore for ReadOnly<S> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.0.list()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.0.list_prefix(prefix)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.0.list_dir(prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn read_through_wrapper() {
+        let h = ReadOnly::new(FilesystemHierarchy::open("tests/data/zarrita.zr3").unwrap());
+        let array_meta = h.get_array_metadata("seq/i2").unwrap();
+        assert!(array_meta.is_read_only());
+        assert!(h
+            .read_chunk::<i16>("seq/i2", &array_meta, smallvec![0, 0, 0])
+            .unwrap()
+            .is_some());
+        assert_eq!(h.list_nodes("seq").unwrap(), vec!["i2"]);
+    }
+}
@@ -0,0 +1,318 @@
+//! A store wrapper recording responses to disk, or replaying them offline.
+//!
+//! Integration tests of remote datasets record the responses of the remote
+//! store once, then replay the recording, for example in CI, with no network
+//! access and identical results on every run:
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::replay::ReplayStore;
+//!
+//! let recording = "tests/recordings/remote";
+//! let h = if std::env::var_os("RECORD").is_some() {
+//!     let remote = FilesystemHierarchy::open("/mnt/bucket/a.zr3")?;
+//!     ReplayStore::record(remote, recording)?
+//! } else {
+//!     ReplayStore::<FilesystemHierarchy>::replay(recording)?
+//! };
+//! let array_meta = h.get_array_metadata("raw")?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs;
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{
+    de::DeserializeOwned,
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    storage::{
+        stable_hash,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A response to a store operation, recorded in its own file.
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    /// The operation and its arguments, to tell apart operations with
+    /// colliding hashes.
+    request: String,
+    response: serde_json::Value,
+}
+
+/// A store wrapper which either records the responses of a store, or
+/// replays recorded responses without the store.
+///
+/// Each response is recorded in a JSON file in the recording directory and
+/// replaying an operation which was not recorded fails. Failed operations
+/// are not recorded.
+#[derive(Clone, Debug)]
+pub struct ReplayStore<S> {
+    /// `None` when replaying.
+    store: Option<S>,
+    dir: PathBuf,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+const ENTRY_POINT_REQUEST: &str = "entry_point";
+
+impl<S: Hierarchy> ReplayStore<S> {
+    /// Record the responses of a store in a directory, which is created if
+    /// it does not exist.
+    pub fn record<P: AsRef<Path>>(store: S, dir: P) -> Result<Self, Error> {
+        let replay = ReplayStore {
+            entry_point_metadata: store.get_entry_point_metadata().clone(),
+            store: Some(store),
+            dir: dir.as_ref().to_owned(),
+        };
+        fs::create_dir_all(&replay.dir)?;
+        replay.save(
+            ENTRY_POINT_REQUEST.to_owned(),
+            serde_json::to_value(&replay.entry_point_metadata)?,
+        )?;
+        Ok(replay)
+    }
+}
+
+impl<S> ReplayStore<S> {
+    /// Replay responses recorded in a directory.
+    pub fn replay<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let mut replay = ReplayStore {
+            store: None,
+            dir: dir.as_ref().to_owned(),
+            entry_point_metadata: EntryPointMetadata::default(),
+        };
+        replay.entry_point_metadata = serde_json::from_value(replay.load(ENTRY_POINT_REQUEST)?)?;
+        crate::check_extensions(&replay.entry_point_metadata.extensions)?;
+        Ok(replay)
+    }
+
+    /// Whether responses are replayed rather than recorded.
+    pub fn is_replaying(&self) -> bool {
+        self.store.is_none()
+    }
+
+    /// The recorded store, or `None` when replaying.
+    pub fn get_ref(&self) -> Option<&S> {
+        self.store.as_ref()
+    }
+
+    fn recording_path(&self, request: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", stable_hash(request.as_bytes())))
+    }
+
+    fn save(&self, request: String, response: serde_json::Value) -> Result<(), Error> {
+        let path = self.recording_path(&request);
+        let recording = Recording { request, response };
+        fs::write(path, serde_json::to_vec_pretty(&recording)?)
+    }
+
+    fn load(&self, request: &str) -> Result<serde_json::Value, Error> {
+        let not_recorded = || {
+            Error::other(format!(
+                "No response to {} was recorded in {}",
+                request,
+                self.dir.display()
+            ))
+        };
+        let bytes = match fs::read(self.recording_path(request)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(not_recorded()),
+            Err(e) => return Err(e),
+        };
+        let recording: Recording = serde_json::from_slice(&bytes)?;
+        if recording.request != request {
+            return Err(not_recorded());
+        }
+        Ok(recording.response)
+    }
+
+    /// Respond to a request from the store, recording the response, or from
+    /// its recording.
+    fn respond<T: Serialize + DeserializeOwned>(
+        &self,
+        request: String,
+        call: impl FnOnce(&S) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        match &self.store {
+            Some(store) => {
+                let response = call(store)?;
+                self.save(request, serde_json::to_value(&response)?)?;
+                Ok(response)
+            }
+            None => Ok(serde_json::from_value(self.load(&request)?)?),
+        }
+    }
+
+    /// Values are recorded in base64.
+    fn respond_value(
+        &self,
+        request: String,
+        call: impl FnOnce(&S) -> Result<Option<Vec<u8>>, Error>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let encoded: Option<String> = self.respond(request, |store| {
+            Ok(call(store)?.map(|value| STANDARD.encode(value)))
+        })?;
+        encoded
+            .map(|encoded| {
+                STANDARD
+                    .decode(encoded)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+            })
+            .transpose()
+    }
+}
+
+impl<S> Hierarchy for ReplayStore<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl<S: ReadableStore> ReadableStore for ReplayStore<S> {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.respond(format!("exists {}", key), |store| store.exists(key))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        let value = self.respond_value(format!("get {}", key), |store| {
+            store
+                .get(key)?
+                .map(|mut reader| {
+                    let mut value = vec![];
+                    reader.read_to_end(&mut value)?;
+                    Ok(value)
+                })
+                .transpose()
+        })?;
+        Ok(value.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.respond(format!("uri {}", key), |store| store.uri(key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        self.respond(format!("size {}", key), |store| store.size(key))
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        self.respond(format!("stat {}", key), |store| store.stat(key))
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.respond(format!("is_read_only {}", key), |store| {
+            store.is_read_only(key)
+        })
+    }
+}
+
+impl<S: PartialReadStore> PartialReadStore for ReplayStore<S> {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let request = format!("get_range {} {} {:?}", key, offset, length);
+        self.respond_value(request, |store| store.get_range(key, offset, length))
+    }
+}
+
+impl<S: ListableStore> ListableStore for ReplayStore<S> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.respond("list".to_owned(), |store| store.list())
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.respond(format!("list_prefix {}", prefix), |store| {
+            store.list_prefix(prefix)
+        })
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        self.respond(format!("list_dir {}", prefix), |store| {
+            store.list_dir(prefix)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = tempdir::TempDir::new("rust_zarr_replay_tests").unwrap();
+        let recording = dir.path().join("recording");
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], i16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 4])
+            .build();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![-1i16; 8]);
+
+        let read = |h: &ReplayStore<FilesystemHierarchy>| {
+            let array_meta = h.get_array_metadata("g/a").unwrap();
+            let data = h
+                .read_chunk::<i16>("g/a", &array_meta, smallvec![1, 0])
+                .unwrap()
+                .map(|chunk| chunk.into_data());
+            let missing = h
+                .read_chunk::<i16>("g/a", &array_meta, smallvec![0, 0])
+                .unwrap();
+            (
+                array_meta,
+                data,
+                missing.is_none(),
+                h.list_nodes("g").unwrap(),
+            )
+        };
+
+        let recorded = {
+            let h = FilesystemHierarchy::open_or_create(dir.path().join("a.zr3")).unwrap();
+            h.create_array("g/a", &array_meta).unwrap();
+            h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+            let h = ReplayStore::record(h, &recording).unwrap();
+            assert!(!h.is_replaying());
+            read(&h)
+        };
+        fs::remove_dir_all(dir.path().join("a.zr3")).unwrap();
+
+        let h = ReplayStore::<FilesystemHierarchy>::replay(&recording).unwrap();
+        assert!(h.is_replaying());
+        let replayed = read(&h);
+        assert_eq!(replayed, recorded);
+        assert_eq!(replayed.0, array_meta);
+        assert_eq!(replayed.1, Some(vec![-1; 8]));
+        assert!(replayed.2);
+
+        assert_eq!(
+            h.get_array_metadata("g/b").unwrap_err().kind(),
+            ErrorKind::Other
+        );
+        assert!(ReplayStore::<FilesystemHierarchy>::replay(dir.path().join("missing")).is_err());
+    }
+}
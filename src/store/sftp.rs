@@ -0,0 +1,1386 @@
+//! A store of a hierarchy on a host reachable over SSH, by SFTP.
+//!
+//! Sessions run the `sftp` subsystem of the system's `ssh` command, so hosts
+//! are reached with the user's SSH configuration, keys, agent and jump
+//! hosts, as for `sftp` itself. Idle sessions are pooled for reuse by later
+//! requests, including those of parallel reads and writes.
+//!
+//! Transfers are resumable: if a session fails while reading or writing a
+//! value, the transfer continues from where it stopped on a new session, up
+//! to a configured number of times.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::sftp::SftpStore;
+//!
+//! let h = SftpStore::open("user@cluster.example.com", "/scratch/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("raw").unwrap();
+//! ```
+
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Result,
+    Write,
+};
+use std::process::{
+    Child,
+    Command,
+    Stdio,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    UNIX_EPOCH,
+};
+
+use crate::{
+    storage::{
+        parallel_map,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::{
+        url::check_ssh_destination,
+        write_buffer::BufferWriter,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+const SSH_FXP_INIT: u8 = 1;
+const SSH_FXP_VERSION: u8 = 2;
+const SSH_FXP_OPEN: u8 = 3;
+const SSH_FXP_CLOSE: u8 = 4;
+const SSH_FXP_READ: u8 = 5;
+const SSH_FXP_WRITE: u8 = 6;
+const SSH_FXP_OPENDIR: u8 = 11;
+const SSH_FXP_READDIR: u8 = 12;
+const SSH_FXP_REMOVE: u8 = 13;
+const SSH_FXP_MKDIR: u8 = 14;
+const SSH_FXP_RMDIR: u8 = 15;
+const SSH_FXP_STAT: u8 = 17;
+const SSH_FXP_STATUS: u8 = 101;
+const SSH_FXP_HANDLE: u8 = 102;
+const SSH_FXP_DATA: u8 = 103;
+const SSH_FXP_NAME: u8 = 104;
+const SSH_FXP_ATTRS: u8 = 105;
+
+const SSH_FX_OK: u32 = 0;
+const SSH_FX_EOF: u32 = 1;
+const SSH_FX_NO_SUCH_FILE: u32 = 2;
+const SSH_FX_PERMISSION_DENIED: u32 = 3;
+const SSH_FX_OP_UNSUPPORTED: u32 = 8;
+
+const SSH_FXF_READ: u32 = 0x01;
+const SSH_FXF_WRITE: u32 = 0x02;
+const SSH_FXF_CREAT: u32 = 0x08;
+const SSH_FXF_TRUNC: u32 = 0x10;
+
+const SSH_FILEXFER_ATTR_SIZE: u32 = 0x01;
+const SSH_FILEXFER_ATTR_UIDGID: u32 = 0x02;
+const SSH_FILEXFER_ATTR_PERMISSIONS: u32 = 0x04;
+const SSH_FILEXFER_ATTR_ACMODTIME: u32 = 0x08;
+const SSH_FILEXFER_ATTR_EXTENDED: u32 = 0x8000_0000;
+
+/// Largest number of bytes read or written by one request, which all
+/// servers support.
+const BLOCK_SIZE: usize = 32 * 1024;
+
+/// Largest packet accepted from a server.
+const MAX_PACKET_LEN: usize = 256 * 1024;
+
+/// The byte streams of an SFTP session.
+pub struct SftpConnection {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Option<Child>,
+}
+
+impl SftpConnection {
+    /// A session over streams to a server, such as a socket.
+    pub fn new(reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) -> Self {
+        SftpConnection {
+            reader,
+            writer,
+            child: None,
+        }
+    }
+
+    /// A session over the standard input and output of a command, such as
+    /// `ssh -s host sftp`. The command is killed when the session ends.
+    pub fn spawn(command: &mut Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let writer = child.stdin.take().expect("Piped standard input");
+        let reader = child.stdout.take().expect("Piped standard output");
+        Ok(SftpConnection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: Some(child),
+        })
+    }
+}
+
+impl std::fmt::Debug for SftpConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpConnection")
+            .field("child", &self.child.as_ref().map(Child::id))
+            .finish()
+    }
+}
+
+impl Drop for SftpConnection {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Opens the byte streams of a new session.
+pub type SftpConnector = dyn Fn() -> Result<SftpConnection> + Send + Sync;
+
+/// Options of the sessions of an [`SftpStore`].
+#[derive(Clone)]
+pub struct SftpOptions {
+    /// Program and arguments run to connect to a destination, which are
+    /// followed by `-s`, `--`, the destination and `sftp`.
+    pub ssh_command: Vec<String>,
+    /// Opens sessions instead of [`ssh_command`](Self::ssh_command), for
+    /// other transports. The destination is then unused.
+    pub connector: Option<Arc<SftpConnector>>,
+    /// Largest number of idle sessions kept open for reuse.
+    pub pool_size: usize,
+    /// Number of times a transfer is resumed on a new session after its
+    /// session fails.
+    pub retries: u32,
+}
+
+impl Default for SftpOptions {
+    fn default() -> Self {
+        SftpOptions {
+            ssh_command: vec![
+                "ssh".to_owned(),
+                "-o".to_owned(),
+                "BatchMode=yes".to_owned(),
+            ],
+            connector: None,
+            pool_size: 4,
+            retries: 3,
+        }
+    }
+}
+
+impl std::fmt::Debug for SftpOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpOptions")
+            .field("ssh_command", &self.ssh_command)
+            .field("connector", &self.connector.is_some())
+            .field("pool_size", &self.pool_size)
+            .field("retries", &self.retries)
+            .finish()
+    }
+}
+
+/// Attributes of a remote file.
+#[derive(Clone, Debug, Default)]
+struct Attrs {
+    size: Option<u64>,
+    permissions: Option<u32>,
+    mtime: Option<u32>,
+}
+
+impl Attrs {
+    fn is_dir(&self) -> bool {
+        self.permissions
+            .is_some_and(|permissions| permissions & 0o170_000 == 0o040_000)
+    }
+
+    fn is_file(&self) -> bool {
+        self.permissions
+            .is_none_or(|permissions| permissions & 0o170_000 == 0o100_000)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value);
+}
+
+/// Reads the fields of a packet.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::new(ErrorKind::InvalidData, "Truncated SFTP packet"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn attrs(&mut self) -> Result<Attrs> {
+        let flags = self.u32()?;
+        let mut attrs = Attrs::default();
+        if flags & SSH_FILEXFER_ATTR_SIZE != 0 {
+            attrs.size = Some(self.u64()?);
+        }
+        if flags & SSH_FILEXFER_ATTR_UIDGID != 0 {
+            self.take(8)?;
+        }
+        if flags & SSH_FILEXFER_ATTR_PERMISSIONS != 0 {
+            attrs.permissions = Some(self.u32()?);
+        }
+        if flags & SSH_FILEXFER_ATTR_ACMODTIME != 0 {
+            self.take(4)?;
+            attrs.mtime = Some(self.u32()?);
+        }
+        if flags & SSH_FILEXFER_ATTR_EXTENDED != 0 {
+            for _ in 0..self.u32()? {
+                self.string()?;
+                self.string()?;
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+/// An SFTP session, making one request at a time.
+#[derive(Debug)]
+struct Session {
+    connection: SftpConnection,
+    next_id: u32,
+    /// Whether the transport failed, so the session can not be reused.
+    broken: bool,
+}
+
+impl Session {
+    fn open(connector: &SftpConnector) -> Result<Session> {
+        let mut session = Session {
+            connection: connector()?,
+            next_id: 0,
+            broken: false,
+        };
+        let mut init = vec![SSH_FXP_INIT];
+        put_u32(&mut init, 3);
+        session.send(&init)?;
+        let response = session.receive()?;
+        if response.first() != Some(&SSH_FXP_VERSION) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "SFTP server did not send its version",
+            ));
+        }
+        Ok(session)
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<()> {
+        let mut framed = Vec::with_capacity(packet.len() + 4);
+        put_string(&mut framed, packet);
+        let writer = &mut self.connection.writer;
+        let sent = writer.write_all(&framed).and_then(|_| writer.flush());
+        self.broken |= sent.is_err();
+        sent
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>> {
+        let received = (|| {
+            let mut len = [0; 4];
+            self.connection.reader.read_exact(&mut len)?;
+            let len = u32::from_be_bytes(len) as usize;
+            if len == 0 || len > MAX_PACKET_LEN {
+                return Err(Error::new(ErrorKind::InvalidData, "Invalid SFTP packet"));
+            }
+            let mut packet = vec![0; len];
+            self.connection.reader.read_exact(&mut packet)?;
+            Ok(packet)
+        })();
+        self.broken |= received.is_err();
+        received
+    }
+
+    /// Make a request, returning the type and fields of its response.
+    fn request(&mut self, packet_type: u8, fields: &[u8]) -> Result<(u8, Vec<u8>)> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut packet = Vec::with_capacity(fields.len() + 5);
+        packet.push(packet_type);
+        put_u32(&mut packet, id);
+        packet.extend_from_slice(fields);
+        self.send(&packet)?;
+
+        let response = self.receive()?;
+        let mut decoder = Decoder {
+            data: &response[1..],
+        };
+        if decoder.u32()? != id {
+            self.broken = true;
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "SFTP response does not match request",
+            ));
+        }
+        Ok((response[0], decoder.data.to_vec()))
+    }
+
+    /// Make a request answered by a status, returning its code if it is
+    /// `SSH_FX_OK`, `SSH_FX_EOF` or `SSH_FX_NO_SUCH_FILE`.
+    fn request_status(&mut self, packet_type: u8, fields: &[u8]) -> Result<u32> {
+        match self.request(packet_type, fields)? {
+            (SSH_FXP_STATUS, fields) => status(&fields),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn stat(&mut self, path: &str) -> Result<Option<Attrs>> {
+        let mut fields = vec![];
+        put_string(&mut fields, path.as_bytes());
+        match self.request(SSH_FXP_STAT, &fields)? {
+            (SSH_FXP_ATTRS, fields) => Ok(Some(Decoder { data: &fields }.attrs()?)),
+            (SSH_FXP_STATUS, fields) => match status(&fields)? {
+                SSH_FX_NO_SUCH_FILE => Ok(None),
+                _ => Err(unexpected_response()),
+            },
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    /// Open a file or directory, or `None` if it does not exist.
+    fn open_handle(&mut self, packet_type: u8, path: &str, flags: u32) -> Result<Option<Vec<u8>>> {
+        let mut fields = vec![];
+        put_string(&mut fields, path.as_bytes());
+        if packet_type == SSH_FXP_OPEN {
+            put_u32(&mut fields, flags);
+            put_u32(&mut fields, 0);
+        }
+        match self.request(packet_type, &fields)? {
+            (SSH_FXP_HANDLE, fields) => Ok(Some(Decoder { data: &fields }.string()?.to_vec())),
+            (SSH_FXP_STATUS, fields) => match status(&fields)? {
+                SSH_FX_NO_SUCH_FILE => Ok(None),
+                _ => Err(unexpected_response()),
+            },
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn close(&mut self, handle: &[u8]) -> Result<()> {
+        let mut fields = vec![];
+        put_string(&mut fields, handle);
+        self.request_status(SSH_FXP_CLOSE, &fields)?;
+        Ok(())
+    }
+
+    /// Read up to a block at an offset, or `None` at the end of the file.
+    fn read(&mut self, handle: &[u8], offset: u64, len: usize) -> Result<Option<Vec<u8>>> {
+        let mut fields = vec![];
+        put_string(&mut fields, handle);
+        put_u64(&mut fields, offset);
+        put_u32(&mut fields, std::cmp::min(len, BLOCK_SIZE) as u32);
+        match self.request(SSH_FXP_READ, &fields)? {
+            (SSH_FXP_DATA, fields) => Ok(Some(Decoder { data: &fields }.string()?.to_vec())),
+            (SSH_FXP_STATUS, fields) => match status(&fields)? {
+                SSH_FX_EOF => Ok(None),
+                _ => Err(unexpected_response()),
+            },
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn write(&mut self, handle: &[u8], offset: u64, data: &[u8]) -> Result<()> {
+        let mut fields = vec![];
+        put_string(&mut fields, handle);
+        put_u64(&mut fields, offset);
+        put_string(&mut fields, data);
+        match self.request_status(SSH_FXP_WRITE, &fields)? {
+            SSH_FX_OK => Ok(()),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    /// Names and attributes of the entries of a directory, or `None` if it
+    /// does not exist.
+    fn read_dir(&mut self, path: &str) -> Result<Option<Vec<(String, Attrs)>>> {
+        let handle = match self.open_handle(SSH_FXP_OPENDIR, path, 0)? {
+            Some(handle) => handle,
+            None => return Ok(None),
+        };
+        let mut entries = vec![];
+        let mut fields = vec![];
+        put_string(&mut fields, &handle);
+        loop {
+            match self.request(SSH_FXP_READDIR, &fields)? {
+                (SSH_FXP_NAME, names) => {
+                    let mut decoder = Decoder { data: &names };
+                    for _ in 0..decoder.u32()? {
+                        let name = String::from_utf8_lossy(decoder.string()?).into_owned();
+                        decoder.string()?;
+                        let attrs = decoder.attrs()?;
+                        if name != "." && name != ".." {
+                            entries.push((name, attrs));
+                        }
+                    }
+                }
+                (SSH_FXP_STATUS, fields) if status(&fields)? == SSH_FX_EOF => break,
+                _ => return Err(unexpected_response()),
+            }
+        }
+        self.close(&handle)?;
+        Ok(Some(entries))
+    }
+
+    /// Remove a file or an empty directory, returning whether it no longer
+    /// exists, whether removed or already missing.
+    fn remove(&mut self, packet_type: u8, path: &str) -> Result<bool> {
+        let mut fields = vec![];
+        put_string(&mut fields, path.as_bytes());
+        Ok(matches!(
+            self.request_status(packet_type, &fields)?,
+            SSH_FX_OK | SSH_FX_NO_SUCH_FILE
+        ))
+    }
+
+    /// Create a directory and its missing parents.
+    fn make_dirs(&mut self, path: &str) -> Result<()> {
+        if path.is_empty() || self.stat(path)?.is_some() {
+            return Ok(());
+        }
+        if let Some(i) = path.trim_end_matches('/').rfind('/') {
+            self.make_dirs(&path[..i])?;
+        }
+        let mut fields = vec![];
+        put_string(&mut fields, path.as_bytes());
+        put_u32(&mut fields, 0);
+        match self.request(SSH_FXP_MKDIR, &fields)? {
+            (SSH_FXP_STATUS, fields) => match status(&fields) {
+                Ok(_) => Ok(()),
+                // Another writer created the directory first.
+                Err(_) if self.stat(path)?.is_some_and(|attrs| attrs.is_dir()) => Ok(()),
+                Err(e) => Err(e),
+            },
+            _ => Err(unexpected_response()),
+        }
+    }
+}
+
+/// The code of a status response, failing for codes other than
+/// `SSH_FX_OK`, `SSH_FX_EOF` and `SSH_FX_NO_SUCH_FILE`.
+fn status(fields: &[u8]) -> Result<u32> {
+    let mut decoder = Decoder { data: fields };
+    let code = decoder.u32()?;
+    let message = decoder
+        .string()
+        .map(|message| String::from_utf8_lossy(message).into_owned())
+        .unwrap_or_default();
+    let kind = match code {
+        SSH_FX_OK | SSH_FX_EOF | SSH_FX_NO_SUCH_FILE => return Ok(code),
+        SSH_FX_PERMISSION_DENIED => ErrorKind::PermissionDenied,
+        SSH_FX_OP_UNSUPPORTED => ErrorKind::Unsupported,
+        _ => ErrorKind::Other,
+    };
+    Err(Error::new(
+        kind,
+        format!("SFTP request failed with status {}: {}", code, message),
+    ))
+}
+
+fn unexpected_response() -> Error {
+    Error::new(ErrorKind::InvalidData, "Unexpected SFTP response")
+}
+
+/// Sessions kept open for reuse.
+struct SessionPool {
+    connector: Arc<SftpConnector>,
+    idle: Mutex<Vec<Session>>,
+    pool_size: usize,
+    retries: u32,
+}
+
+impl SessionPool {
+    /// Run an operation on a pooled session, running it again on a new
+    /// session if its session fails. Operations keep their progress across
+    /// runs, so that transfers resume rather than restart.
+    fn with_session<R>(&self, mut operation: impl FnMut(&mut Session) -> Result<R>) -> Result<R> {
+        let mut attempt = 0;
+        loop {
+            let idle = self.idle.lock().unwrap().pop();
+            let result = match idle {
+                Some(session) => Ok(session),
+                None => Session::open(&*self.connector),
+            }
+            .and_then(|mut session| {
+                let result = operation(&mut session);
+                if !session.broken {
+                    let mut idle = self.idle.lock().unwrap();
+                    if idle.len() < self.pool_size {
+                        idle.push(session);
+                    }
+                    return Ok(result);
+                }
+                result.map(Ok)
+            });
+            match result {
+                Ok(result) => return result,
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+}
+
+/// A store of a hierarchy below a path of a host reached by SFTP.
+#[derive(Clone)]
+pub struct SftpStore {
+    pool: Arc<SessionPool>,
+    destination: String,
+    base_path: String,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl std::fmt::Debug for SftpStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpStore")
+            .field("destination", &self.destination)
+            .field("base_path", &self.base_path)
+            .field("idle_sessions", &self.pool.idle.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Hierarchy for SftpStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl SftpStore {
+    /// Open an existing Zarr hierarchy at a path of an SSH destination,
+    /// such as `user@host`.
+    ///
+    /// Destinations whose user or host start with `-` or contain whitespace
+    /// or control characters fail with [`ErrorKind::InvalidInput`], so that
+    /// they are not taken as options of `ssh`.
+    pub fn open(destination: &str, base_path: &str) -> Result<SftpStore> {
+        Self::open_with_options(destination, base_path, &SftpOptions::default())
+    }
+
+    /// Open an existing Zarr hierarchy at a path of an SSH destination,
+    /// with the given options.
+    pub fn open_with_options(
+        destination: &str,
+        base_path: &str,
+        options: &SftpOptions,
+    ) -> Result<SftpStore> {
+        let mut store = Self::connect(destination, base_path, options)?;
+        let reader = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy at {}", store.base_path),
+            )
+        })?;
+        store.set_entry_point_metadata(serde_json::from_reader(reader)?)?;
+        Ok(store)
+    }
+
+    /// Open a Zarr hierarchy at a path of an SSH destination, creating it
+    /// if it does not exist.
+    pub fn open_or_create(destination: &str, base_path: &str) -> Result<SftpStore> {
+        Self::open_or_create_with_options(destination, base_path, &SftpOptions::default())
+    }
+
+    /// Open a Zarr hierarchy at a path of an SSH destination, creating it
+    /// if it does not exist, with the given options.
+    pub fn open_or_create_with_options(
+        destination: &str,
+        base_path: &str,
+        options: &SftpOptions,
+    ) -> Result<SftpStore> {
+        let mut store = Self::connect(destination, base_path, options)?;
+        match store.get(crate::ENTRY_POINT_KEY)? {
+            Some(reader) => store.set_entry_point_metadata(serde_json::from_reader(reader)?)?,
+            None => {
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                store.put(crate::ENTRY_POINT_KEY, &metadata)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn connect(destination: &str, base_path: &str, options: &SftpOptions) -> Result<SftpStore> {
+        let connector = match &options.connector {
+            Some(connector) => Arc::clone(connector),
+            None => {
+                check_ssh_destination(destination)?;
+                let ssh_command = options.ssh_command.clone();
+                let destination = destination.to_owned();
+                Arc::new(move || {
+                    let (program, args) = ssh_command.split_first().ok_or_else(|| {
+                        Error::new(ErrorKind::InvalidInput, "SSH command is empty")
+                    })?;
+                    SftpConnection::spawn(Command::new(program).args(args).args([
+                        "-s",
+                        "--",
+                        &destination,
+                        "sftp",
+                    ]))
+                })
+            }
+        };
+        Ok(SftpStore {
+            pool: Arc::new(SessionPool {
+                connector,
+                idle: Mutex::new(vec![]),
+                pool_size: options.pool_size,
+                retries: options.retries,
+            }),
+            destination: destination.to_owned(),
+            base_path: base_path.trim_end_matches('/').to_owned(),
+            entry_point_metadata: EntryPointMetadata::default(),
+        })
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    /// Remote path of the hierarchy, without a trailing slash.
+    pub fn get_base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!("{}/{}", self.base_path, key.trim_start_matches('/'))
+    }
+
+    /// Read a range of a file, resuming on a new session if one fails.
+    fn read_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key);
+        let mut value = vec![];
+        self.pool.with_session(|session| {
+            let handle = match session.open_handle(SSH_FXP_OPEN, &path, SSH_FXF_READ)? {
+                Some(handle) => handle,
+                None => return Ok(None),
+            };
+            loop {
+                let remaining = match length {
+                    Some(length) if value.len() as u64 >= length => break,
+                    Some(length) => (length - value.len() as u64) as usize,
+                    None => BLOCK_SIZE,
+                };
+                match session.read(&handle, offset + value.len() as u64, remaining)? {
+                    Some(block) if !block.is_empty() => value.extend_from_slice(&block),
+                    _ => break,
+                }
+            }
+            session.close(&handle)?;
+            Ok(Some(std::mem::take(&mut value)))
+        })
+    }
+
+    /// Write a file, resuming on a new session if one fails.
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key);
+        let mut written = 0;
+        self.pool.with_session(|session| {
+            // Truncate only when starting, not when resuming.
+            let mut flags = SSH_FXF_WRITE | SSH_FXF_CREAT;
+            if written == 0 {
+                flags |= SSH_FXF_TRUNC;
+            }
+            let handle = match session.open_handle(SSH_FXP_OPEN, &path, flags)? {
+                Some(handle) => handle,
+                None => {
+                    if let Some(i) = path.rfind('/') {
+                        session.make_dirs(&path[..i])?;
+                    }
+                    session
+                        .open_handle(SSH_FXP_OPEN, &path, flags)?
+                        .ok_or_else(|| Error::new(ErrorKind::NotFound, path.clone()))?
+                }
+            };
+            for block in value[written..].chunks(BLOCK_SIZE) {
+                session.write(&handle, written as u64, block)?;
+                written += block.len();
+            }
+            session.close(&handle)
+        })
+    }
+
+    fn erase_path(&self, session: &mut Session, path: &str) -> Result<()> {
+        match session.stat(path)? {
+            Some(attrs) if attrs.is_dir() => {
+                for (name, _) in session.read_dir(path)?.unwrap_or_default() {
+                    self.erase_path(session, &format!("{}/{}", path, name))?;
+                }
+                session.remove(SSH_FXP_RMDIR, path)?;
+            }
+            Some(_) => {
+                session.remove(SSH_FXP_REMOVE, path)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl ReadableStore for SftpStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.stat(key)?.is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.read_range(key, 0, None)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        Ok(format!("sftp://{}{}", self.destination, self.path(key)))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.stat(key)?.map(|stat| stat.size))
+    }
+
+    /// Stats have the size and modification time, to the second.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        let path = self.path(key);
+        let attrs = match self.pool.with_session(|session| session.stat(&path))? {
+            Some(attrs) if attrs.is_file() => attrs,
+            _ => return Ok(None),
+        };
+        Ok(Some(KeyStat {
+            size: attrs.size.unwrap_or(0),
+            last_modified: attrs
+                .mtime
+                .map(|mtime| UNIX_EPOCH + Duration::from_secs(u64::from(mtime))),
+            etag: None,
+        }))
+    }
+
+    /// Values are read in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency), each on a
+    /// pooled session.
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        parallel_map(keys, |key| self.read_range(key, 0, None))
+    }
+}
+
+impl PartialReadStore for SftpStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        self.read_range(key, offset, length)
+    }
+}
+
+impl ListableStore for SftpStore {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let path = self.path(prefix);
+        let entries = self
+            .pool
+            .with_session(|session| session.read_dir(path.trim_end_matches('/')))?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No directory {}", path)))?;
+        let mut keys = vec![];
+        let mut prefixes = vec![];
+        for (name, attrs) in entries {
+            if attrs.is_dir() {
+                prefixes.push(format!("{}{}/", prefix, name));
+            } else {
+                keys.push(format!("{}{}", prefix, name));
+            }
+        }
+        Ok((keys, prefixes))
+    }
+}
+
+impl WriteableStore for SftpStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        self.put(key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        let path = self.path(key);
+        self.pool
+            .with_session(|session| session.remove(SSH_FXP_REMOVE, &path))
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        let path = self.path(key_prefix);
+        self.pool
+            .with_session(|session| self.erase_path(session, path.trim_end_matches('/')))?;
+        Ok(true)
+    }
+
+    /// Values are written in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency), each on a
+    /// pooled session.
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        parallel_map(pairs, |(key, value)| self.put(key, value))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{
+        BTreeMap,
+        BTreeSet,
+    };
+    use std::net::{
+        TcpListener,
+        TcpStream,
+    };
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    use crate::prelude::*;
+
+    #[derive(Debug, Default)]
+    struct FakeFiles {
+        files: BTreeMap<String, Vec<u8>>,
+        dirs: BTreeSet<String>,
+    }
+
+    fn parent(path: &str) -> &str {
+        &path[..path.rfind('/').unwrap_or(0)]
+    }
+
+    fn reply(stream: &mut TcpStream, packet_type: u8, id: u32, fields: &[u8]) {
+        let mut packet = vec![packet_type];
+        put_u32(&mut packet, id);
+        packet.extend_from_slice(fields);
+        let mut framed = vec![];
+        put_string(&mut framed, &packet);
+        stream.write_all(&framed).unwrap();
+    }
+
+    fn status_fields(code: u32) -> Vec<u8> {
+        let mut fields = vec![];
+        put_u32(&mut fields, code);
+        put_string(&mut fields, b"");
+        put_string(&mut fields, b"");
+        fields
+    }
+
+    fn attrs_fields(size: u64, permissions: u32) -> Vec<u8> {
+        let mut fields = vec![];
+        put_u32(
+            &mut fields,
+            SSH_FILEXFER_ATTR_SIZE | SSH_FILEXFER_ATTR_PERMISSIONS,
+        );
+        put_u64(&mut fields, size);
+        put_u32(&mut fields, permissions);
+        fields
+    }
+
+    /// When a fake server drops connections without replying, counted over
+    /// read and write requests.
+    #[derive(Debug, Default)]
+    struct Drops {
+        /// Requests until the next drop, or zero for none.
+        next: AtomicUsize,
+        /// Requests from each session's first to its drop after the next
+        /// one, or zero for none.
+        every: AtomicUsize,
+    }
+
+    /// Serve an SFTP session of files in memory, dropping the connection
+    /// without replying as `drops` counts down.
+    fn serve_session(mut stream: TcpStream, files: &Mutex<FakeFiles>, drops: &Drops) {
+        let mut listed = BTreeSet::new();
+        loop {
+            let mut len = [0; 4];
+            if stream.read_exact(&mut len).is_err() {
+                return;
+            }
+            let mut packet = vec![0; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut packet).unwrap();
+            if packet[0] == SSH_FXP_INIT {
+                let mut version = vec![SSH_FXP_VERSION];
+                put_u32(&mut version, 3);
+                let mut framed = vec![];
+                put_string(&mut framed, &version);
+                stream.write_all(&framed).unwrap();
+                continue;
+            }
+            if (packet[0] == SSH_FXP_READ || packet[0] == SSH_FXP_WRITE)
+                && drops
+                    .next
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    == Ok(1)
+            {
+                drops
+                    .next
+                    .store(drops.every.load(Ordering::SeqCst), Ordering::SeqCst);
+                return;
+            }
+
+            let mut decoder = Decoder { data: &packet[1..] };
+            let id = decoder.u32().unwrap();
+            let mut files = files.lock().unwrap();
+            let path = String::from_utf8(decoder.string().unwrap().to_vec()).unwrap();
+            // Handles are the paths they open.
+            let (packet_type, fields) = match packet[0] {
+                SSH_FXP_OPEN => {
+                    let flags = decoder.u32().unwrap();
+                    if flags & SSH_FXF_WRITE != 0 && files.dirs.contains(parent(&path)) {
+                        let file = files.files.entry(path.clone()).or_default();
+                        if flags & SSH_FXF_TRUNC != 0 {
+                            file.clear();
+                        }
+                    }
+                    if files.files.contains_key(&path) {
+                        let mut fields = vec![];
+                        put_string(&mut fields, path.as_bytes());
+                        (SSH_FXP_HANDLE, fields)
+                    } else {
+                        (SSH_FXP_STATUS, status_fields(SSH_FX_NO_SUCH_FILE))
+                    }
+                }
+                SSH_FXP_OPENDIR if files.dirs.contains(&path) => {
+                    listed.remove(&path);
+                    let mut fields = vec![];
+                    put_string(&mut fields, path.as_bytes());
+                    (SSH_FXP_HANDLE, fields)
+                }
+                SSH_FXP_READ => {
+                    let offset = decoder.u64().unwrap() as usize;
+                    let len = decoder.u32().unwrap() as usize;
+                    let file = &files.files[&path];
+                    if offset >= file.len() {
+                        (SSH_FXP_STATUS, status_fields(SSH_FX_EOF))
+                    } else {
+                        let mut fields = vec![];
+                        put_string(
+                            &mut fields,
+                            &file[offset..std::cmp::min(offset + len, file.len())],
+                        );
+                        (SSH_FXP_DATA, fields)
+                    }
+                }
+                SSH_FXP_WRITE => {
+                    let offset = decoder.u64().unwrap() as usize;
+                    let data = decoder.string().unwrap();
+                    let file = files.files.get_mut(&path).unwrap();
+                    if file.len() < offset + data.len() {
+                        file.resize(offset + data.len(), 0);
+                    }
+                    file[offset..offset + data.len()].copy_from_slice(data);
+                    (SSH_FXP_STATUS, status_fields(SSH_FX_OK))
+                }
+                SSH_FXP_READDIR if listed.insert(path.clone()) => {
+                    let mut entries = vec![(".".to_owned(), attrs_fields(0, 0o040_755))];
+                    for dir in &files.dirs {
+                        if parent(dir) == path && !dir.is_empty() {
+                            let name = dir[path.len() + 1..].to_owned();
+                            entries.push((name, attrs_fields(0, 0o040_755)));
+                        }
+                    }
+                    for (file, value) in &files.files {
+                        if parent(file) == path {
+                            let name = file[path.len() + 1..].to_owned();
+                            entries.push((name, attrs_fields(value.len() as u64, 0o100_644)));
+                        }
+                    }
+                    let mut fields = vec![];
+                    put_u32(&mut fields, entries.len() as u32);
+                    for (name, attrs) in entries {
+                        put_string(&mut fields, name.as_bytes());
+                        put_string(&mut fields, name.as_bytes());
+                        fields.extend_from_slice(&attrs);
+                    }
+                    (SSH_FXP_NAME, fields)
+                }
+                SSH_FXP_READDIR => (SSH_FXP_STATUS, status_fields(SSH_FX_EOF)),
+                SSH_FXP_CLOSE => (SSH_FXP_STATUS, status_fields(SSH_FX_OK)),
+                SSH_FXP_STAT => match files.files.get(&path) {
+                    Some(file) => (SSH_FXP_ATTRS, attrs_fields(file.len() as u64, 0o100_644)),
+                    None if files.dirs.contains(&path) => {
+                        (SSH_FXP_ATTRS, attrs_fields(0, 0o040_755))
+                    }
+                    None => (SSH_FXP_STATUS, status_fields(SSH_FX_NO_SUCH_FILE)),
+                },
+                SSH_FXP_MKDIR
+                    if files.dirs.contains(parent(&path)) && !files.dirs.contains(&path) =>
+                {
+                    files.dirs.insert(path);
+                    (SSH_FXP_STATUS, status_fields(SSH_FX_OK))
+                }
+                SSH_FXP_REMOVE if files.files.remove(&path).is_some() => {
+                    (SSH_FXP_STATUS, status_fields(SSH_FX_OK))
+                }
+                SSH_FXP_RMDIR
+                    if !files.files.keys().any(|file| parent(file) == path)
+                        && !files.dirs.iter().any(|dir| parent(dir) == path)
+                        && files.dirs.remove(&path) =>
+                {
+                    (SSH_FXP_STATUS, status_fields(SSH_FX_OK))
+                }
+                SSH_FXP_OPENDIR | SSH_FXP_REMOVE => {
+                    (SSH_FXP_STATUS, status_fields(SSH_FX_NO_SUCH_FILE))
+                }
+                _ => (SSH_FXP_STATUS, status_fields(4)),
+            };
+            reply(&mut stream, packet_type, id, &fields);
+        }
+    }
+
+    /// Serve SFTP sessions of files in memory below `/srv`, returning
+    /// options connecting to it, the number of connections made and the
+    /// schedule of dropping connections.
+    fn serve() -> (SftpOptions, Arc<AtomicUsize>, Arc<Drops>) {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let files = Arc::new(Mutex::new(FakeFiles::default()));
+        files
+            .lock()
+            .unwrap()
+            .dirs
+            .extend(["".to_owned(), "/srv".to_owned()]);
+        let drops = Arc::new(Drops::default());
+        let server_drops = Arc::clone(&drops);
+        std::thread::spawn(move || {
+            for stream in server.incoming() {
+                let files = Arc::clone(&files);
+                let drops = Arc::clone(&server_drops);
+                std::thread::spawn(move || serve_session(stream.unwrap(), &files, &drops));
+            }
+        });
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connected = Arc::clone(&connections);
+        let options = SftpOptions {
+            connector: Some(Arc::new(move || {
+                connected.fetch_add(1, Ordering::SeqCst);
+                let stream = TcpStream::connect(address)?;
+                Ok(SftpConnection::new(
+                    Box::new(stream.try_clone()?),
+                    Box::new(stream),
+                ))
+            })),
+            ..Default::default()
+        };
+        (options, connections, drops)
+    }
+
+    #[test]
+    fn test_sftp_store() {
+        let (options, connections, _) = serve();
+        assert_eq!(
+            SftpStore::open_with_options("", "/srv/a.zr3", &options)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        let h = SftpStore::open_or_create_with_options("", "/srv/a.zr3", &options).unwrap();
+
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+        h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+
+        let h = SftpStore::open_with_options("", "/srv/a.zr3", &options).unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        let opened = connections.load(Ordering::SeqCst);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.list_dir("/missing/").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        // Sequential requests reuse a pooled session.
+        assert_eq!(connections.load(Ordering::SeqCst), opened);
+
+        h.put("/data/x", &[1]).unwrap();
+        assert!(h.erase("/data/x").unwrap());
+        assert!(!ReadableStore::exists(&h, "/data/x").unwrap());
+        assert!(h.erase("/data/x").unwrap());
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .is_none());
+        h.erase_prefix("/meta/").unwrap();
+        assert!(!ReadableStore::exists(&h, "/meta/root/g.group.json").unwrap());
+    }
+
+    #[test]
+    fn test_sftp_store_resumes_transfers() {
+        let (options, connections, drops) = serve();
+        let h = SftpStore::open_or_create_with_options("", "/srv/a.zr3", &options).unwrap();
+        let value: Vec<u8> = (0..3 * BLOCK_SIZE + 100).map(|i| i as u8).collect();
+
+        // The second block written is dropped with its session.
+        drops.next.store(2, Ordering::SeqCst);
+        h.put("/data/large", &value).unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        drops.next.store(3, Ordering::SeqCst);
+        assert_eq!(
+            h.read_range("/data/large", 0, None).unwrap().unwrap(),
+            value
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            h.get_range("/data/large", 10, Some(BLOCK_SIZE as u64 + 5))
+                .unwrap()
+                .unwrap(),
+            &value[10..BLOCK_SIZE + 15]
+        );
+
+        // Transfers fail once their retries are exhausted.
+        let options = SftpOptions {
+            retries: 0,
+            ..options
+        };
+        let h = SftpStore::open_with_options("", "/srv/a.zr3", &options).unwrap();
+        drops.next.store(1, Ordering::SeqCst);
+        assert!(h.get("/data/large").is_err());
+    }
+
+    #[test]
+    fn test_sftp_store_resumes_resumed_transfers() {
+        let (options, connections, drops) = serve();
+        let h = SftpStore::open_or_create_with_options("", "/srv/a.zr3", &options).unwrap();
+        let value: Vec<u8> = (0..3 * BLOCK_SIZE + 100).map(|i| (i / 7) as u8).collect();
+        let opened = connections.load(Ordering::SeqCst);
+
+        // Each resumed write gets one block further before its session is
+        // dropped again, until the last block.
+        drops.next.store(2, Ordering::SeqCst);
+        drops.every.store(2, Ordering::SeqCst);
+        h.put("/data/large", &value).unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), opened + 3);
+
+        // Reads of four blocks and the end of the file.
+        drops.next.store(2, Ordering::SeqCst);
+        drops.every.store(3, Ordering::SeqCst);
+        assert_eq!(
+            h.read_range("/data/large", 0, None).unwrap().unwrap(),
+            value
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), opened + 5);
+
+        // Sessions dropped on every request exhaust the retries.
+        drops.next.store(1, Ordering::SeqCst);
+        drops.every.store(1, Ordering::SeqCst);
+        assert!(h.put("/data/large", &value).is_err());
+        assert_eq!(
+            connections.load(Ordering::SeqCst),
+            opened + 5 + options.retries as usize
+        );
+        drops.every.store(0, Ordering::SeqCst);
+        drops.next.store(0, Ordering::SeqCst);
+    }
+
+    fn framed(packet: &[u8]) -> Vec<u8> {
+        let mut framed = vec![];
+        put_string(&mut framed, packet);
+        framed
+    }
+
+    fn response(packet_type: u8, id: u32, fields: &[u8]) -> Vec<u8> {
+        let mut packet = vec![packet_type];
+        put_u32(&mut packet, id);
+        packet.extend_from_slice(fields);
+        framed(&packet)
+    }
+
+    /// A connection to a server which sends `responses`, ignoring requests.
+    fn scripted(responses: Vec<u8>) -> SftpConnection {
+        SftpConnection::new(Box::new(Cursor::new(responses)), Box::new(std::io::sink()))
+    }
+
+    fn scripted_session(responses: Vec<u8>) -> Session {
+        Session {
+            connection: scripted(responses),
+            next_id: 0,
+            broken: false,
+        }
+    }
+
+    #[test]
+    fn test_session_framing() {
+        let read = |responses: Vec<u8>| {
+            let mut session = scripted_session(responses);
+            let kind = session.read(b"h", 0, 1).unwrap_err().kind();
+            (kind, session.broken)
+        };
+
+        // Packets cut short, or whose length is out of range, break the
+        // session.
+        let mut short = 10u32.to_be_bytes().to_vec();
+        short.extend_from_slice(&[SSH_FXP_DATA, 0, 0]);
+        assert_eq!(read(short), (ErrorKind::UnexpectedEof, true));
+        assert_eq!(read(vec![0, 0]), (ErrorKind::UnexpectedEof, true));
+        assert_eq!(read(framed(&[])), (ErrorKind::InvalidData, true));
+        let oversized = (MAX_PACKET_LEN as u32 + 1).to_be_bytes().to_vec();
+        assert_eq!(read(oversized), (ErrorKind::InvalidData, true));
+        assert_eq!(
+            read(response(SSH_FXP_DATA, 1, &[])),
+            (ErrorKind::InvalidData, true)
+        );
+
+        // Malformed fields of whole packets fail the request only.
+        assert_eq!(
+            read(framed(&[SSH_FXP_DATA])),
+            (ErrorKind::InvalidData, false)
+        );
+        let mut data = vec![];
+        put_u32(&mut data, 8);
+        data.extend_from_slice(b"abc");
+        assert_eq!(
+            read(response(SSH_FXP_DATA, 0, &data)),
+            (ErrorKind::InvalidData, false)
+        );
+        assert_eq!(
+            read(response(SSH_FXP_HANDLE, 0, &[])),
+            (ErrorKind::InvalidData, false)
+        );
+
+        let connector: Arc<SftpConnector> =
+            Arc::new(|| Ok(scripted(response(SSH_FXP_STATUS, 0, &status_fields(0)))));
+        assert_eq!(
+            Session::open(&*connector).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_status_errors() {
+        let status_error = |code: u32| {
+            let mut fields = vec![];
+            put_u32(&mut fields, code);
+            put_string(&mut fields, b"refused by policy");
+            put_string(&mut fields, b"en");
+            let mut session = scripted_session(response(SSH_FXP_STATUS, 0, &fields));
+            let error = session.read(b"h", 0, 1).unwrap_err();
+            assert!(!session.broken);
+            error
+        };
+        let error = status_error(SSH_FX_PERMISSION_DENIED);
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert!(error.to_string().contains("refused by policy"));
+        assert_eq!(
+            status_error(SSH_FX_OP_UNSUPPORTED).kind(),
+            ErrorKind::Unsupported
+        );
+        assert_eq!(status_error(4).kind(), ErrorKind::Other);
+        assert_eq!(status(&[0, 0]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(status(&[0, 0, 0, 1]).unwrap(), SSH_FX_EOF);
+
+        // Failed requests are not retried, and their sessions are reused.
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connected = Arc::clone(&connections);
+        let pool = SessionPool {
+            connector: Arc::new(move || {
+                connected.fetch_add(1, Ordering::SeqCst);
+                let mut responses = framed(&[SSH_FXP_VERSION, 0, 0, 0, 3]);
+                let mut fields = vec![];
+                put_u32(&mut fields, SSH_FX_PERMISSION_DENIED);
+                responses.extend(response(SSH_FXP_STATUS, 0, &fields));
+                Ok(scripted(responses))
+            }),
+            idle: Mutex::new(vec![]),
+            pool_size: 1,
+            retries: 3,
+        };
+        assert_eq!(
+            pool.with_session(|session| session.read(b"h", 0, 1))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_hostile_destinations() {
+        for destination in &[
+            "-oProxyCommand=sh",
+            "-oProxyCommand=sh@host",
+            "user@-oProxyCommand=sh",
+            "user@host -oProxyCommand=sh",
+            "user@host\n",
+            "user\t@host",
+            "",
+        ] {
+            assert_eq!(
+                SftpStore::open(destination, "/srv/a.zr3")
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::InvalidInput,
+                "{:?}",
+                destination
+            );
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! A store of a hierarchy in an embedded [sled](https://docs.rs/sled)
+//! key-value database.
+//!
+//! Writing values to one database directory rather than one file each avoids
+//! the per-file overhead of filesystems during high-throughput local ingest.
+//! Writes of several values by [`put_many`](WriteableStore::put_many) are
+//! atomic batches. sled flushes writes to disk in the background; call
+//! [`SledStore::flush`] before relying on them surviving a crash.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::sled::SledStore;
+//!
+//! let h = SledStore::open_or_create("/tmp/volume.zr3.sled").unwrap();
+//! h.create_group("raw").unwrap();
+//! h.flush().unwrap();
+//! ```
+
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Result,
+};
+use std::path::Path;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use crate::{
+    storage::{
+        list_dir_of_keys,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::write_buffer::BufferWriter,
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+fn sled_error(e: sled::Error) -> Error {
+    match e {
+        sled::Error::Io(e) => e,
+        e => Error::other(e),
+    }
+}
+
+/// A store of a hierarchy in a tree of a sled database.
+#[derive(Clone, Debug)]
+pub struct SledStore {
+    tree: sled::Tree,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl Hierarchy for SledStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl SledStore {
+    /// Open an existing Zarr hierarchy in a database directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SledStore> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No database at {}", path.display()),
+            ));
+        }
+        let db = sled::open(path).map_err(sled_error)?;
+        Self::open_tree(&db, "zarr")
+    }
+
+    /// Open a Zarr hierarchy in a database directory, creating the database
+    /// and the hierarchy if they do not exist.
+    pub fn open_or_create<P: AsRef<Path>>(path: P) -> Result<SledStore> {
+        let db = sled::open(path).map_err(sled_error)?;
+        Self::open_or_create_tree(&db, "zarr")
+    }
+
+    /// Open an existing Zarr hierarchy in a named tree of a database, so
+    /// that one database can hold several hierarchies.
+    pub fn open_tree(db: &sled::Db, name: &str) -> Result<SledStore> {
+        let mut store = Self::with_tree(db, name)?;
+        let metadata = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy in tree {}", name),
+            )
+        })?;
+        store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+        Ok(store)
+    }
+
+    /// Open a Zarr hierarchy in a named tree of a database, creating it if
+    /// it does not exist.
+    pub fn open_or_create_tree(db: &sled::Db, name: &str) -> Result<SledStore> {
+        let mut store = Self::with_tree(db, name)?;
+        match store.get(crate::ENTRY_POINT_KEY)? {
+            Some(metadata) => {
+                store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+            }
+            None => {
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                store.put(crate::ENTRY_POINT_KEY, metadata)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn with_tree(db: &sled::Db, name: &str) -> Result<SledStore> {
+        Ok(SledStore {
+            tree: db.open_tree(name).map_err(sled_error)?,
+            entry_point_metadata: EntryPointMetadata::default(),
+        })
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    /// Write all buffered writes to disk, returning the number of bytes
+    /// flushed.
+    pub fn flush(&self) -> Result<usize> {
+        self.tree.flush().map_err(sled_error)
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>) -> Result<()> {
+        self.tree.insert(key, value).map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn read(&self, key: &str) -> Result<Option<sled::IVec>> {
+        self.tree.get(key).map_err(sled_error)
+    }
+}
+
+impl ReadableStore for SledStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        self.tree.contains_key(key).map_err(sled_error)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.read(key)?.map(|value| Cursor::new(value.to_vec())))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        Ok(format!("sled:{}", key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.read(key)?.map(|value| value.len() as u64))
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self.size(key)?.map(KeyStat::new))
+    }
+}
+
+impl PartialReadStore for SledStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        Ok(self.read(key)?.map(|value| {
+            let start = std::cmp::min(offset, value.len() as u64);
+            let end = length.map_or(value.len() as u64, |length| {
+                std::cmp::min(start.saturating_add(length), value.len() as u64)
+            });
+            value[start as usize..end as usize].to_vec()
+        }))
+    }
+}
+
+impl ListableStore for SledStore {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let dir = format!("{}/", prefix.trim_end_matches('/'));
+        let keys = self
+            .tree
+            .scan_prefix(&dir)
+            .keys()
+            .map(|key| {
+                let key = key.map_err(sled_error)?;
+                String::from_utf8(key.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(list_dir_of_keys(prefix, keys.iter().map(String::as_str)))
+    }
+}
+
+impl WriteableStore for SledStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = std::mem::take(&mut *buffer.lock().unwrap());
+        self.put(key, value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        self.tree.remove(key).map_err(sled_error)?;
+        Ok(true)
+    }
+
+    /// Keys below the prefix are removed in one atomic batch.
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        let mut batch = sled::Batch::default();
+        for key in self.tree.scan_prefix(key_prefix).keys() {
+            batch.remove(key.map_err(sled_error)?);
+        }
+        self.tree.apply_batch(batch).map_err(sled_error)?;
+        Ok(true)
+    }
+
+    /// Values are written in one atomic batch.
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for (key, value) in pairs {
+            batch.insert(key.as_bytes(), value.as_slice());
+        }
+        self.tree.apply_batch(batch).map_err(sled_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_sled_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_sled_tests").unwrap();
+        let path = dir.path().join("a.zr3.sled");
+        assert_eq!(
+            SledStore::open(&path).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        // Reopening a database directory races with sled's background
+        // threads releasing it, so the hierarchy is reopened from the one
+        // database handle instead.
+        let db = sled::open(&path).unwrap();
+        assert_eq!(
+            SledStore::open_tree(&db, "zarr").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        {
+            let h = SledStore::open_or_create_tree(&db, "zarr").unwrap();
+            h.create_array("g/a", &array_meta).unwrap();
+            let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+            h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+            h.flush().unwrap();
+        }
+
+        let h = SledStore::open_tree(&db, "zarr").unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        let key = crate::storage::get_chunk_key("g/a", &array_meta, &[1, 0]);
+        assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), vec![2, 3]);
+        assert_eq!(h.size(&key).unwrap(), Some(4));
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.list_dir("/meta/").unwrap(),
+            (vec![], vec!["/meta/root/".to_owned()])
+        );
+
+        h.put_many(&[("/x/1".to_owned(), vec![1]), ("/x/2".to_owned(), vec![2])])
+            .unwrap();
+        assert_eq!(h.list_prefix("/x/").unwrap().len(), 2);
+        h.erase_prefix("/x/").unwrap();
+        assert!(h.list_prefix("/x/").unwrap().is_empty());
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .is_none());
+    }
+}
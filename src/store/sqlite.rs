@@ -0,0 +1,591 @@
+//! A store of a hierarchy in a single SQLite database file.
+//!
+//! Keys and values are rows of a `zarr` table, as in zarr-python's
+//! `SQLiteStore`, with keys stored without their leading slash. Keeping
+//! millions of small chunks in one file avoids the per-file overhead of
+//! filesystems, and the file can be copied or shipped as a whole. Erased and
+//! overwritten values leave free pages in the file until
+//! [`SqliteStore::compact`] rebuilds it.
+//!
+//! Writes of several values by [`put_many`](WriteableStore::put_many) are
+//! atomic, as are all writes made within [`SqliteStore::transaction`]:
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//! use zarr::store::sqlite::SqliteStore;
+//!
+//! let h = SqliteStore::open_or_create("/tmp/volume.zr3.sqlite").unwrap();
+//! let array_meta = ArrayMetadataBuilder::new(smallvec![64, 64], u8::ZARR_TYPE)
+//!     .chunk_shape(smallvec![8, 8])
+//!     .build();
+//! h.transaction(|tx| {
+//!     tx.create_array("labels", &array_meta)?;
+//!     tx.write_chunk("labels", &array_meta, &SliceDataChunk::new(smallvec![0, 0], vec![1u8; 64]))
+//! })
+//! .unwrap();
+//! ```
+
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Result,
+};
+use std::path::Path;
+use std::sync::{
+    Arc,
+    Mutex,
+    MutexGuard,
+};
+
+use rusqlite::{
+    params,
+    Connection,
+    OptionalExtension,
+};
+
+use crate::{
+    storage::{
+        list_dir_of_keys,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::write_buffer::BufferWriter,
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::other(e)
+}
+
+/// Key of a row, without its leading slash.
+fn row_key(key: &str) -> &str {
+    key.trim_start_matches('/')
+}
+
+/// Operations on the `zarr` table of a connection, shared by stores and
+/// their transactions.
+mod table {
+    use super::*;
+
+    pub(super) fn create(connection: &Connection) -> Result<()> {
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS zarr (k TEXT PRIMARY KEY, v BLOB NOT NULL)",
+                [],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    pub(super) fn get(connection: &Connection, key: &str) -> Result<Option<Vec<u8>>> {
+        connection
+            .query_row(
+                "SELECT v FROM zarr WHERE k = ?1",
+                params![row_key(key)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    pub(super) fn get_range(
+        connection: &Connection,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>> {
+        // `substr` of a blob counts bytes from one, and a `NULL` length
+        // reads through the end.
+        let length = length.map(|length| length.min(i32::MAX as u64) as i64);
+        connection
+            .query_row(
+                "SELECT substr(v, ?2, coalesce(?3, length(v))) FROM zarr WHERE k = ?1",
+                params![row_key(key), offset.saturating_add(1) as i64, length],
+                |row| row.get::<_, Option<Vec<u8>>>(0),
+            )
+            .optional()
+            .map(|value| value.map(Option::unwrap_or_default))
+            .map_err(sqlite_error)
+    }
+
+    pub(super) fn size(connection: &Connection, key: &str) -> Result<Option<u64>> {
+        connection
+            .query_row(
+                "SELECT length(v) FROM zarr WHERE k = ?1",
+                params![row_key(key)],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map(|size| size.map(|size| size as u64))
+            .map_err(sqlite_error)
+    }
+
+    pub(super) fn list_dir(
+        connection: &Connection,
+        prefix: &str,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let dir = row_key(prefix).trim_end_matches('/');
+        let dir = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir)
+        };
+        // Rows of keys below the directory, by a range of the primary key.
+        let mut statement = connection
+            .prepare(
+                "SELECT '/' || k FROM zarr \
+                 WHERE k >= ?1 AND substr(k, 1, length(?1)) = ?1 ORDER BY k",
+            )
+            .map_err(sqlite_error)?;
+        let keys = statement
+            .query_map(params![dir], |row| row.get::<_, String>(0))
+            .map_err(sqlite_error)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+        Ok(list_dir_of_keys(prefix, keys.iter().map(String::as_str)))
+    }
+
+    pub(super) fn put(connection: &Connection, key: &str, value: &[u8]) -> Result<()> {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO zarr (k, v) VALUES (?1, ?2)",
+                params![row_key(key), value],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    pub(super) fn erase(connection: &Connection, key: &str) -> Result<()> {
+        connection
+            .execute("DELETE FROM zarr WHERE k = ?1", params![row_key(key)])
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    pub(super) fn erase_prefix(connection: &Connection, key_prefix: &str) -> Result<()> {
+        connection
+            .execute(
+                "DELETE FROM zarr WHERE substr(k, 1, length(?1)) = ?1",
+                params![row_key(key_prefix)],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    /// Run a function in a transaction, committing it if the function
+    /// succeeds and rolling it back otherwise.
+    pub(super) fn in_transaction<R>(
+        connection: &Connection,
+        f: impl FnOnce() -> Result<R>,
+    ) -> Result<R> {
+        connection
+            .execute_batch("BEGIN IMMEDIATE")
+            .map_err(sqlite_error)?;
+        match f() {
+            Ok(result) => {
+                connection.execute_batch("COMMIT").map_err(sqlite_error)?;
+                Ok(result)
+            }
+            Err(e) => {
+                connection.execute_batch("ROLLBACK").map_err(sqlite_error)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A store of a hierarchy in an SQLite database.
+#[derive(Clone, Debug)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl Hierarchy for SqliteStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl SqliteStore {
+    /// Open an existing Zarr hierarchy in a database file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No database at {}", path.display()),
+            ));
+        }
+        let connection = Connection::open(path).map_err(sqlite_error)?;
+        let mut store = Self::with_connection(connection)?;
+        let metadata = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy in {}", path.display()),
+            )
+        })?;
+        store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+        Ok(store)
+    }
+
+    /// Open a Zarr hierarchy in a database file, creating the file and the
+    /// hierarchy if they do not exist.
+    pub fn open_or_create<P: AsRef<Path>>(path: P) -> Result<SqliteStore> {
+        let connection = Connection::open(path).map_err(sqlite_error)?;
+        Self::open_or_create_with_connection(connection)
+    }
+
+    /// Create a Zarr hierarchy in a database in memory.
+    pub fn open_in_memory() -> Result<SqliteStore> {
+        let connection = Connection::open_in_memory().map_err(sqlite_error)?;
+        Self::open_or_create_with_connection(connection)
+    }
+
+    fn open_or_create_with_connection(connection: Connection) -> Result<SqliteStore> {
+        let mut store = Self::with_connection(connection)?;
+        match store.get(crate::ENTRY_POINT_KEY)? {
+            Some(metadata) => {
+                store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+            }
+            None => {
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                table::put(&store.lock(), crate::ENTRY_POINT_KEY, &metadata)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn with_connection(connection: Connection) -> Result<SqliteStore> {
+        // Wait for writers of other connections rather than failing.
+        connection
+            .busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(sqlite_error)?;
+        table::create(&connection)?;
+        Ok(SqliteStore {
+            connection: Arc::new(Mutex::new(connection)),
+            entry_point_metadata: EntryPointMetadata::default(),
+        })
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap()
+    }
+
+    /// Rebuild the database without the free pages left by erased and
+    /// overwritten values, returning the number of bytes reclaimed.
+    pub fn compact(&self) -> Result<u64> {
+        let connection = self.lock();
+        let size = || {
+            connection
+                .query_row(
+                    "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map(|size| size as u64)
+                .map_err(sqlite_error)
+        };
+        let size_before = size()?;
+        connection.execute_batch("VACUUM").map_err(sqlite_error)?;
+        Ok(size_before.saturating_sub(size()?))
+    }
+
+    /// Run a function with a store of a transaction, committing the writes
+    /// made through it if the function succeeds and rolling them back
+    /// otherwise. Other uses of this store wait for the transaction to end.
+    pub fn transaction<R>(&self, f: impl FnOnce(&SqliteTransaction<'_>) -> Result<R>) -> Result<R> {
+        let connection = self.lock();
+        table::in_transaction(&connection, || {
+            f(&SqliteTransaction {
+                connection: &connection,
+                entry_point_metadata: &self.entry_point_metadata,
+            })
+        })
+    }
+}
+
+impl ReadableStore for SqliteStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(table::size(&self.lock(), key)?.is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(table::get(&self.lock(), key)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        let path = self.lock().path().unwrap_or("").to_owned();
+        Ok(format!("sqlite://{}#{}", path, row_key(key)))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        table::size(&self.lock(), key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self.size(key)?.map(KeyStat::new))
+    }
+
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        let connection = self.lock();
+        keys.iter()
+            .map(|key| table::get(&connection, key))
+            .collect()
+    }
+}
+
+impl PartialReadStore for SqliteStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        table::get_range(&self.lock(), key, offset, length)
+    }
+}
+
+impl ListableStore for SqliteStore {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        table::list_dir(&self.lock(), prefix)
+    }
+}
+
+impl WriteableStore for SqliteStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        table::put(&self.lock(), key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        table::erase(&self.lock(), key)?;
+        Ok(true)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        table::erase_prefix(&self.lock(), key_prefix)?;
+        Ok(true)
+    }
+
+    /// Values are written in a single transaction, so either all or none
+    /// of them are written.
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        let connection = self.lock();
+        table::in_transaction(&connection, || {
+            pairs
+                .iter()
+                .try_for_each(|(key, value)| table::put(&connection, key, value))
+        })
+    }
+}
+
+/// A store of a transaction of an [`SqliteStore`], from
+/// [`SqliteStore::transaction`].
+pub struct SqliteTransaction<'a> {
+    connection: &'a Connection,
+    entry_point_metadata: &'a EntryPointMetadata,
+}
+
+impl std::fmt::Debug for SqliteTransaction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteTransaction").finish()
+    }
+}
+
+impl Hierarchy for SqliteTransaction<'_> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.entry_point_metadata
+    }
+}
+
+impl ReadableStore for SqliteTransaction<'_> {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(table::size(self.connection, key)?.is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(table::get(self.connection, key)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        let path = self.connection.path().unwrap_or("");
+        Ok(format!("sqlite://{}#{}", path, row_key(key)))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        table::size(self.connection, key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self.size(key)?.map(KeyStat::new))
+    }
+}
+
+impl PartialReadStore for SqliteTransaction<'_> {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        table::get_range(self.connection, key, offset, length)
+    }
+}
+
+impl ListableStore for SqliteTransaction<'_> {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        table::list_dir(self.connection, prefix)
+    }
+}
+
+impl WriteableStore for SqliteTransaction<'_> {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        table::put(self.connection, key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        table::erase(self.connection, key)?;
+        Ok(true)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        table::erase_prefix(self.connection, key_prefix)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_sqlite_tests").unwrap();
+        let path = dir.path().join("a.zr3.sqlite");
+        assert_eq!(
+            SqliteStore::open(&path).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        let h = SqliteStore::open_or_create(&path).unwrap();
+
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+        h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+
+        let h = SqliteStore::open(&path).unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        let key = crate::storage::get_chunk_key("g/a", &array_meta, &[1, 0]);
+        assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), vec![2, 3]);
+        assert_eq!(
+            h.get_range(&key, 0, None).unwrap().unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(h.get_range(&key, 2, None).unwrap().unwrap(), vec![3, 4]);
+        assert_eq!(
+            h.get_range(&key, 9, None).unwrap().unwrap(),
+            Vec::<u8>::new()
+        );
+        assert_eq!(h.size(&key).unwrap(), Some(4));
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.list_dir("/meta/").unwrap(),
+            (vec![], vec!["/meta/root/".to_owned()])
+        );
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_sqlite_transactions() {
+        let h = SqliteStore::open_in_memory().unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+
+        // A failed transaction writes nothing.
+        let err = h
+            .transaction(|tx| {
+                tx.create_array("a", &array_meta)?;
+                assert!(HierarchyReader::exists(tx, "a")?);
+                Err::<(), _>(Error::other("abort"))
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "abort");
+        assert!(!HierarchyReader::exists(&h, "a").unwrap());
+
+        h.transaction(|tx| {
+            tx.create_array("a", &array_meta)?;
+            let chunk = SliceDataChunk::new(smallvec![0, 0], vec![7u8; 4]);
+            tx.write_chunk("a", &array_meta, &chunk)
+        })
+        .unwrap();
+        assert!(h
+            .read_chunk::<u8>("a", &array_meta, smallvec![0, 0])
+            .unwrap()
+            .is_some());
+
+        h.put_many(&[("/x".to_owned(), vec![1]), ("/y".to_owned(), vec![2])])
+            .unwrap();
+        assert_eq!(h.get_many(&["/y".to_owned()]).unwrap(), vec![Some(vec![2])]);
+    }
+
+    #[test]
+    fn test_sqlite_compact() {
+        let dir = tempdir::TempDir::new("rust_zarr_sqlite_tests").unwrap();
+        let path = dir.path().join("a.zr3.sqlite");
+        let h = SqliteStore::open_or_create(&path).unwrap();
+        let pairs: Vec<_> = (0..16)
+            .map(|i| (format!("/x/{}", i), vec![i as u8; 64 * 1024]))
+            .collect();
+        h.put_many(&pairs).unwrap();
+        h.put_many(&[(pairs[0].0.clone(), vec![1, 2, 3])]).unwrap();
+        h.erase_prefix("/x/1").unwrap();
+
+        let reclaimed = h.compact().unwrap();
+        assert!(reclaimed >= 7 * 64 * 1024);
+        assert_eq!(h.get_range("/x/0", 0, None).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(h.list_prefix("/x/").unwrap().len(), 9);
+        assert_eq!(h.compact().unwrap(), 0);
+    }
+}
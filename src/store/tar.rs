@@ -0,0 +1,647 @@
+//! A read-only store of a hierarchy in a tar archive.
+//!
+//! Archives are indexed once when opened by reading the headers of their
+//! members, after which values are read directly at their offsets in the
+//! archive, so hierarchies archived to tape or other archive systems can be
+//! read without unpacking them. The hierarchy is rooted at the shallowest
+//! `zarr.json` of the archive, so archives of a hierarchy's directory, such as
+//! those of `tar -cf volume.tar volume.zr3`, open as that hierarchy.
+//!
+//! Gzip-compressed archives (`.tar.gz`) can not be read at offsets, so they
+//! are decompressed into memory when opened, which requires the `gzip` or
+//! `gzip_pure` feature. The whole decompressed archive is then held in
+//! memory, so large archives should be decompressed to `.tar` first, and
+//! archives from untrusted sources are limited to
+//! [`MAX_GZIP_ARCHIVE_SIZE`] bytes decompressed.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::tar::TarStore;
+//!
+//! let h = TarStore::open("/archive/volume.tar").unwrap();
+//! let attributes = h.list_attributes("raw").unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{
+    BufReader,
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Result,
+    Seek,
+    SeekFrom,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    SystemTime,
+};
+
+use crate::{
+    storage::{
+        list_dir_of_keys,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+const BLOCK_SIZE: u64 = 512;
+
+/// The largest GNU long name or pax extended header read, in bytes.
+const MAX_EXTENDED_HEADER_SIZE: u64 = 1 << 20;
+
+/// The largest decompressed size of a gzip-compressed archive, in bytes.
+pub const MAX_GZIP_ARCHIVE_SIZE: u64 = 1 << 32;
+
+/// Where the value of a member is in the archive.
+#[derive(Clone, Debug)]
+struct Member {
+    offset: u64,
+    size: u64,
+    mtime: u64,
+}
+
+/// The bytes of an archive.
+#[derive(Debug)]
+enum Archive {
+    File(Mutex<File>),
+    Memory(Vec<u8>),
+}
+
+impl Archive {
+    fn read(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        match self {
+            Archive::File(file) => {
+                let mut file = file.lock().unwrap();
+                let end = offset.checked_add(length).ok_or_else(truncated)?;
+                if end > file.metadata()?.len() {
+                    return Err(truncated());
+                }
+                let mut value = vec![0; usize::try_from(length).map_err(|_| truncated())?];
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut value)?;
+                Ok(value)
+            }
+            Archive::Memory(bytes) => {
+                let start = usize::try_from(offset).map_err(|_| truncated())?;
+                let length = usize::try_from(length).map_err(|_| truncated())?;
+                start
+                    .checked_add(length)
+                    .and_then(|end| bytes.get(start..end))
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(truncated)
+            }
+        }
+    }
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "Truncated tar archive")
+}
+
+fn invalid_header() -> Error {
+    Error::new(ErrorKind::InvalidData, "Invalid tar header")
+}
+
+/// Parse a numeric header field, which is octal text or, for large values,
+/// big-endian base-256 with the high bit of its first byte set.
+fn parse_number(field: &[u8]) -> Result<u64> {
+    if field.first().is_some_and(|byte| byte & 0x80 != 0) {
+        return field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |number, &byte| {
+                Some(number.checked_mul(256)? | u64::from(byte))
+            })
+            .ok_or_else(invalid_header);
+    }
+    let text = std::str::from_utf8(field).map_err(|_| invalid_header())?;
+    let text = text.trim_matches(|c| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid_header())
+}
+
+fn parse_text(field: &[u8]) -> Result<String> {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8(field[..end].to_vec()).map_err(|_| invalid_header())
+}
+
+/// Parse the records of a pax extended header, `<length> <key>=<value>\n`.
+fn parse_pax(mut data: &[u8]) -> Result<BTreeMap<String, String>> {
+    let mut records = BTreeMap::new();
+    while !data.is_empty() {
+        let space = data
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(invalid_header)?;
+        let length: usize = std::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|length| length.parse().ok())
+            .filter(|&length| length > space && length <= data.len())
+            .ok_or_else(invalid_header)?;
+        let record =
+            std::str::from_utf8(&data[space + 1..length - 1]).map_err(|_| invalid_header())?;
+        if let Some((key, value)) = record.split_once('=') {
+            records.insert(key.to_owned(), value.to_owned());
+        }
+        data = &data[length..];
+    }
+    Ok(records)
+}
+
+/// Index the regular files of an archive of `length` bytes by their path.
+///
+/// Members whose values extend past the end of the archive are rejected
+/// before any of their bytes are read.
+fn index<R: Read + Seek>(mut archive: R, length: u64) -> Result<BTreeMap<String, Member>> {
+    let mut members = BTreeMap::new();
+    let mut offset = 0;
+    // Path and size of the next member from a GNU long name or pax header.
+    let mut next_path = None;
+    let mut next_size = None;
+    loop {
+        let mut header = [0; BLOCK_SIZE as usize];
+        match archive.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some writers omit the end-of-archive blocks.
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let checksum = parse_number(&header[148..156])?;
+        let sum: u64 = header[..148]
+            .iter()
+            .chain(&[b' '; 8])
+            .chain(&header[156..])
+            .map(|&byte| u64::from(byte))
+            .sum();
+        if sum != checksum {
+            return Err(invalid_header());
+        }
+
+        let size = next_size.take().unwrap_or(parse_number(&header[124..136])?);
+        let data_offset = offset + BLOCK_SIZE;
+        if data_offset.checked_add(size).is_none_or(|end| end > length) {
+            return Err(truncated());
+        }
+        offset = size
+            .div_ceil(BLOCK_SIZE)
+            .checked_mul(BLOCK_SIZE)
+            .and_then(|padded_size| data_offset.checked_add(padded_size))
+            .ok_or_else(truncated)?;
+        match header[156] {
+            b'L' | b'x' => {
+                if size > MAX_EXTENDED_HEADER_SIZE {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "Tar extended header too large",
+                    ));
+                }
+                let mut data = vec![0; size as usize];
+                archive.read_exact(&mut data)?;
+                archive.seek(SeekFrom::Start(offset))?;
+                if header[156] == b'L' {
+                    next_path = Some(parse_text(&data)?);
+                } else {
+                    let mut records = parse_pax(&data)?;
+                    if let Some(path) = records.remove("path") {
+                        next_path = Some(path);
+                    }
+                    if let Some(size) = records.get("size") {
+                        next_size = Some(size.parse().map_err(|_| invalid_header())?);
+                    }
+                }
+                continue;
+            }
+            b'0' | b'\0' | b'7' => {
+                let path = match next_path.take() {
+                    Some(path) => path,
+                    None if &header[257..262] == b"ustar" && header[345] != 0 => format!(
+                        "{}/{}",
+                        parse_text(&header[345..500])?,
+                        parse_text(&header[..100])?
+                    ),
+                    None => parse_text(&header[..100])?,
+                };
+                members.insert(
+                    path,
+                    Member {
+                        offset: data_offset,
+                        size,
+                        mtime: parse_number(&header[136..148])?,
+                    },
+                );
+            }
+            // Directories, links and global headers have no values.
+            _ => {
+                next_path = None;
+            }
+        }
+        archive.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(members)
+}
+
+/// Key the members of an archive below the directory of its shallowest
+/// `zarr.json`.
+fn hierarchy_members(members: BTreeMap<String, Member>) -> Option<BTreeMap<String, Member>> {
+    let members: Vec<(String, Member)> = members
+        .into_iter()
+        .map(|(path, member)| {
+            let mut path = path.as_str();
+            while let Some(rest) = path.strip_prefix("./") {
+                path = rest;
+            }
+            (path.trim_start_matches('/').to_owned(), member)
+        })
+        .collect();
+    let root = members
+        .iter()
+        .filter_map(|(path, _)| {
+            let root = path.strip_suffix(crate::ENTRY_POINT_KEY)?;
+            (root.is_empty() || root.ends_with('/')).then(|| root.to_owned())
+        })
+        .min_by_key(|root| root.matches('/').count())?;
+    Some(
+        members
+            .into_iter()
+            .filter_map(|(path, member)| Some((format!("/{}", path.strip_prefix(&root)?), member)))
+            .collect(),
+    )
+}
+
+/// A read-only store of a hierarchy in a tar archive.
+#[derive(Clone, Debug)]
+pub struct TarStore {
+    archive: Arc<Archive>,
+    members: Arc<BTreeMap<String, Member>>,
+    path: PathBuf,
+    entry_point_metadata: EntryPointMetadata,
+}
+
+impl Hierarchy for TarStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        &self.entry_point_metadata
+    }
+}
+
+impl TarStore {
+    /// Open the Zarr hierarchy in a tar archive, which may be
+    /// gzip-compressed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TarStore> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+        let mut magic = [0; 2];
+        let gzipped = file.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+        file.seek(SeekFrom::Start(0))?;
+        let (archive, members) = if gzipped {
+            let bytes = Self::decompress(file)?;
+            let members = index(Cursor::new(&bytes), bytes.len() as u64)?;
+            (Archive::Memory(bytes), members)
+        } else {
+            let length = file.metadata()?.len();
+            let members = index(BufReader::new(&mut file), length)?;
+            (Archive::File(Mutex::new(file)), members)
+        };
+        let members = hierarchy_members(members).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy in {}", path.display()),
+            )
+        })?;
+        let mut store = TarStore {
+            archive: Arc::new(archive),
+            members: Arc::new(members),
+            path: path.to_owned(),
+            entry_point_metadata: EntryPointMetadata::default(),
+        };
+        let metadata = store.get(crate::ENTRY_POINT_KEY)?.unwrap();
+        store.set_entry_point_metadata(serde_json::from_reader(metadata)?)?;
+        Ok(store)
+    }
+
+    #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+    fn decompress(file: File) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        flate2::read::MultiGzDecoder::new(BufReader::new(file))
+            .take(MAX_GZIP_ARCHIVE_SIZE + 1)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > MAX_GZIP_ARCHIVE_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Decompressed tar archive too large",
+            ));
+        }
+        Ok(bytes)
+    }
+
+    #[cfg(not(any(feature = "gzip", feature = "gzip_pure")))]
+    fn decompress(_file: File) -> Result<Vec<u8>> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "Reading gzip-compressed archives requires the gzip feature",
+        ))
+    }
+
+    fn set_entry_point_metadata(&mut self, metadata: EntryPointMetadata) -> Result<()> {
+        crate::check_extensions(&metadata.extensions)?;
+        self.entry_point_metadata = metadata;
+
+        let version = self.get_version()?;
+
+        if !version.matches(&crate::VERSION) {
+            return Err(Error::other("TODO: Incompatible version"));
+        }
+
+        Ok(())
+    }
+
+    /// The member of a key, with or without a leading slash.
+    fn member(&self, key: &str) -> Option<&Member> {
+        self.members
+            .get(&format!("/{}", key.trim_start_matches('/')))
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.member(key)
+            .map(|member| self.archive.read(member.offset, member.size))
+            .transpose()
+    }
+}
+
+impl ReadableStore for TarStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.member(key).is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.read(key)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        Ok(format!("tar:{}!{}", self.path.display(), key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.member(key).map(|member| member.size))
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self.member(key).map(|member| KeyStat {
+            last_modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(member.mtime)),
+            ..KeyStat::new(member.size)
+        }))
+    }
+
+    fn is_read_only(&self, _key: &str) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+impl PartialReadStore for TarStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        self.member(key)
+            .map(|member| {
+                let start = std::cmp::min(offset, member.size);
+                let length = length.map_or(member.size - start, |length| {
+                    std::cmp::min(length, member.size - start)
+                });
+                self.archive.read(member.offset + start, length)
+            })
+            .transpose()
+    }
+}
+
+impl ListableStore for TarStore {
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        Ok(list_dir_of_keys(
+            prefix,
+            self.members.keys().map(String::as_str),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    /// A tar header block of a member.
+    fn header(path: &str, size: usize, typeflag: u8) -> Vec<u8> {
+        let mut header = vec![0; BLOCK_SIZE as usize];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        header[136..148].copy_from_slice(format!("{:011o}\0", 1_600_000_000).as_bytes());
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        header
+    }
+
+    fn padded(mut data: Vec<u8>) -> Vec<u8> {
+        data.resize(data.len().div_ceil(512) * 512, 0);
+        data
+    }
+
+    /// Archive the files of a directory below `root`, using GNU long names
+    /// for long paths.
+    fn archive(dir: &Path, root: &str, tar: &mut Vec<u8>) {
+        let mut entries: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            let path = format!("{}/{}", root, entry.file_name().unwrap().to_str().unwrap());
+            if entry.is_dir() {
+                tar.extend(header(&format!("{}/", path), 0, b'5'));
+                archive(&entry, &path, tar);
+                continue;
+            }
+            let data = std::fs::read(&entry).unwrap();
+            if path.len() >= 100 {
+                tar.extend(header("././@LongLink", path.len() + 1, b'L'));
+                tar.extend(padded(format!("{}\0", path).into_bytes()));
+                tar.extend(header(&path[..99], data.len(), b'0'));
+            } else {
+                tar.extend(header(&path, data.len(), b'0'));
+            }
+            tar.extend(padded(data));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_tar_store() {
+        let dir = tempdir::TempDir::new("rust_zarr_tar_tests").unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        let group = "a_group_with_a_name_long_enough_for_its_keys_to_need_gnu_long_names_in_tar";
+        let array = format!("{}/a", group);
+        {
+            let h = crate::store::filesystem::FilesystemHierarchy::open_or_create(
+                dir.path().join("volume.zr3"),
+            )
+            .unwrap();
+            h.create_array(&array, &array_meta).unwrap();
+            let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+            h.write_chunk(&array, &array_meta, &chunk).unwrap();
+        }
+        let mut tar = header("./volume.zr3/", 0, b'5');
+        archive(&dir.path().join("volume.zr3"), "./volume.zr3", &mut tar);
+        tar.extend(vec![0; 2 * BLOCK_SIZE as usize]);
+        let path = dir.path().join("volume.tar");
+        std::fs::write(&path, &tar).unwrap();
+
+        let empty = dir.path().join("empty.tar");
+        std::fs::write(&empty, vec![0; 2 * BLOCK_SIZE as usize]).unwrap();
+        assert_eq!(
+            TarStore::open(&empty).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let mut archives = vec![path];
+        #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+        {
+            use std::io::Write;
+
+            let path = dir.path().join("volume.tar.gz");
+            let mut encoder = flate2::write::GzEncoder::new(
+                File::create(&path).unwrap(),
+                flate2::Compression::default(),
+            );
+            encoder.write_all(&tar).unwrap();
+            encoder.finish().unwrap();
+            archives.push(path);
+        }
+
+        for path in archives {
+            let h = TarStore::open(&path).unwrap();
+            let read: VecDataChunk<u8> = h
+                .read_chunk(&array, &array_meta, smallvec![1, 0])
+                .unwrap()
+                .unwrap();
+            assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+            let key = crate::storage::get_chunk_key(&array, &array_meta, &[1, 0]);
+            let value = h.get(&key).unwrap().unwrap().into_inner();
+            assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), value[1..3]);
+            assert_eq!(h.get_range(&key, 1, None).unwrap().unwrap(), value[1..]);
+            assert_eq!(h.size(&key).unwrap(), Some(value.len() as u64));
+            assert_eq!(
+                h.stat(&key).unwrap().unwrap().last_modified,
+                Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+            );
+            assert!(h.is_read_only(&key).unwrap());
+            assert_eq!(h.list_nodes(group).unwrap(), vec!["a".to_owned()]);
+            assert_eq!(
+                h.list_dir("/meta/").unwrap(),
+                (vec![], vec!["/meta/root/".to_owned()])
+            );
+            assert!(HierarchyReader::exists(&h, &array).unwrap());
+            assert!(!HierarchyReader::exists(&h, "missing").unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        assert_eq!(parse_number(b"0000644\0").unwrap(), 0o644);
+        assert_eq!(parse_number(&[0x80, 0, 0, 1, 0]).unwrap(), 256);
+        let records = parse_pax(b"20 path=long/a/name\n11 size=42\n").unwrap();
+        assert_eq!(records["path"], "long/a/name");
+        assert_eq!(records["size"], "42");
+
+        let mut tar = header("pax", 30, b'x');
+        tar.extend(padded(b"30 path=root/zarr.json.backup\n".to_vec()));
+        tar.extend(header("ignored", 1, b'0'));
+        tar.extend(padded(vec![7]));
+        let members = index(Cursor::new(&tar), tar.len() as u64).unwrap();
+        assert_eq!(members["root/zarr.json.backup"].size, 1);
+        assert!(hierarchy_members(members).is_none());
+
+        let mut corrupt = header("a", 0, b'0');
+        corrupt[0] = b'b';
+        assert_eq!(
+            index(Cursor::new(&corrupt), corrupt.len() as u64)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let error = |tar: &[u8]| {
+            index(Cursor::new(tar), tar.len() as u64)
+                .unwrap_err()
+                .kind()
+        };
+
+        // Members extending past the end of the archive.
+        let mut tar = header("a", 1 << 30, b'0');
+        tar.extend(padded(vec![7]));
+        assert_eq!(error(&tar), ErrorKind::UnexpectedEof);
+        let mut tar = header("././@LongLink", 1 << 30, b'L');
+        tar.extend(padded(vec![7]));
+        assert_eq!(error(&tar), ErrorKind::UnexpectedEof);
+
+        // Sizes whose end or padding overflows.
+        let mut tar = header("a", 0, b'0');
+        tar[124..136].copy_from_slice(&[0xff; 12]);
+        tar[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = tar.iter().map(|&byte| u32::from(byte)).sum();
+        tar[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        assert_eq!(error(&tar), ErrorKind::InvalidData);
+        assert!(parse_number(&[0x81, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        let mut tar = header("pax", 29, b'x');
+        tar.extend(padded(b"29 size=18446744073709551615\n".to_vec()));
+        tar.extend(header("a", 0, b'0'));
+        assert_eq!(error(&tar), ErrorKind::UnexpectedEof);
+
+        // Extended headers larger than the limit, even if present.
+        let size = MAX_EXTENDED_HEADER_SIZE as usize + 1;
+        let mut tar = header("././@LongLink", size, b'L');
+        tar.extend(padded(vec![b'a'; size]));
+        assert_eq!(error(&tar), ErrorKind::InvalidData);
+
+        // Pax records whose length is past the header.
+        let mut tar = header("pax", 10, b'x');
+        tar.extend(padded(b"99 path=a\n".to_vec()));
+        assert_eq!(error(&tar), ErrorKind::InvalidData);
+
+        let archive = Archive::Memory(vec![0; 4]);
+        assert_eq!(archive.read(2, 2).unwrap(), vec![0, 0]);
+        assert_eq!(
+            archive.read(2, u64::MAX).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
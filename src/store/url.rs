@@ -0,0 +1,918 @@
+//! Opening hierarchies by URL, with the store chosen by the URL's scheme.
+//!
+//! - `file:///path/volume.zr3`, or a plain path, opens a
+//!   [`FilesystemHierarchy`](crate::store::filesystem::FilesystemHierarchy),
+//! - `http://` and `https://` URLs open an
+//!   [`HttpStore`](crate::store::http::HttpStore),
+//! - `s3://bucket/path/volume.zr3` opens an HTTP store of the public
+//!   S3 bucket, or of an S3-compatible service at a configured endpoint,
+//! - `dav://` and `davs://` URLs open a
+//!   [`WebDavStore`](crate::store::webdav::WebDavStore) over HTTP and HTTPS,
+//! - `webhdfs://namenode:9870/path/volume.zr3` and `swebhdfs://` URLs open a
+//!   [`WebHdfsStore`](crate::store::webhdfs::WebHdfsStore) over HTTP and
+//!   HTTPS,
+//! - `sftp://user@host:port/path/volume.zr3` opens an
+//!   [`SftpStore`](crate::store::sftp::SftpStore), with paths starting with
+//!   `/~/` relative to the home directory.
+//!
+//! Each scheme is available if the feature of its store is enabled. Other
+//! crates can add schemes with [`register_scheme`](crate::store::plugin::register_scheme).
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::url::open_from_url;
+//!
+//! let h = open_from_url("s3://bucket/path/volume.zr3").unwrap();
+//! let array_meta = h.get_array_metadata("raw").unwrap();
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+#[cfg(feature = "filesystem")]
+use crate::store::filesystem::FilesystemHierarchy;
+#[cfg(feature = "http")]
+use crate::store::http::{
+    HttpOptions,
+    HttpStore,
+};
+#[cfg(feature = "sftp")]
+use crate::store::sftp::{
+    SftpOptions,
+    SftpStore,
+};
+#[cfg(feature = "webdav")]
+use crate::store::webdav::WebDavStore;
+#[cfg(feature = "webhdfs")]
+use crate::store::webhdfs::{
+    WebHdfsOptions,
+    WebHdfsStore,
+};
+use crate::{
+    storage::{
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::{
+        plugin::{
+            scheme_factory,
+            DynStore,
+        },
+        write_buffer::BufferWriter,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// Where a hierarchy is stored, parsed from a URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreLocation {
+    File(PathBuf),
+    Http(String),
+    S3 {
+        bucket: String,
+        /// Path of the hierarchy in the bucket, without leading or trailing
+        /// slashes.
+        prefix: String,
+    },
+    /// The HTTP or HTTPS URL of a WebDAV collection.
+    WebDav(String),
+    /// The WebHDFS REST URL of an HDFS directory.
+    WebHdfs(String),
+    Sftp {
+        /// SSH destination, such as `user@host`.
+        destination: String,
+        port: Option<u16>,
+        /// Remote path of the hierarchy, relative to the home directory if
+        /// it does not start with a slash.
+        path: String,
+    },
+}
+
+impl StoreLocation {
+    /// Parse a URL, or a plain path if it has no scheme.
+    pub fn parse(url: &str) -> Result<Self, Error> {
+        let (scheme, rest) = match url.find("://") {
+            Some(i) => (url[..i].to_ascii_lowercase(), &url[i + 3..]),
+            None => return Ok(StoreLocation::File(url.into())),
+        };
+        match scheme.as_str() {
+            "file" => {
+                let path = rest.strip_prefix("localhost").unwrap_or(rest);
+                if !path.starts_with('/') {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("File URL {} is not of a local absolute path", url),
+                    ));
+                }
+                Ok(StoreLocation::File(path.into()))
+            }
+            "http" | "https" => Ok(StoreLocation::Http(url.to_owned())),
+            "dav" => Ok(StoreLocation::WebDav(format!("http://{}", rest))),
+            "davs" => Ok(StoreLocation::WebDav(format!("https://{}", rest))),
+            "webhdfs" | "swebhdfs" => {
+                let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let protocol = if scheme == "webhdfs" { "http" } else { "https" };
+                Ok(StoreLocation::WebHdfs(format!(
+                    "{}://{}/webhdfs/v1/{}",
+                    protocol, authority, path
+                )))
+            }
+            "sftp" => {
+                let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let (destination, port) = match authority.rsplit_once(':') {
+                    Some((destination, port)) => {
+                        let port = port.parse().map_err(|_| {
+                            Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("SFTP URL {} has an invalid port", url),
+                            )
+                        })?;
+                        (destination, Some(port))
+                    }
+                    None => (authority, None),
+                };
+                if destination.is_empty() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("SFTP URL {} has no host", url),
+                    ));
+                }
+                check_ssh_destination(destination)?;
+                let path = match path.strip_prefix("~/") {
+                    Some(path) => path.to_owned(),
+                    None => format!("/{}", path),
+                };
+                Ok(StoreLocation::Sftp {
+                    destination: destination.to_owned(),
+                    port,
+                    path,
+                })
+            }
+            "s3" => {
+                let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+                if bucket.is_empty() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("S3 URL {} has no bucket", url),
+                    ));
+                }
+                Ok(StoreLocation::S3 {
+                    bucket: bucket.to_owned(),
+                    prefix: prefix.trim_matches('/').to_owned(),
+                })
+            }
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unsupported store URL scheme {}", scheme),
+            )),
+        }
+    }
+}
+
+/// Options of S3 stores.
+#[derive(Clone, Debug)]
+pub struct S3Options {
+    /// Region of buckets on AWS.
+    pub region: String,
+    /// URL of an S3-compatible service, such as MinIO, whose buckets are
+    /// addressed by path rather than by host name. `None` for AWS.
+    pub endpoint: Option<String>,
+    /// Options of requests, including the bucket's credentials.
+    #[cfg(feature = "http")]
+    pub http: HttpOptions,
+}
+
+impl Default for S3Options {
+    fn default() -> Self {
+        S3Options {
+            region: "us-east-1".to_owned(),
+            endpoint: None,
+            #[cfg(feature = "http")]
+            http: HttpOptions::default(),
+        }
+    }
+}
+
+impl S3Options {
+    /// The HTTPS URL of a hierarchy in a bucket.
+    pub fn url(&self, bucket: &str, prefix: &str) -> String {
+        let base = match &self.endpoint {
+            Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), bucket),
+            None => format!("https://{}.s3.{}.amazonaws.com", bucket, self.region),
+        };
+        if prefix.is_empty() {
+            base
+        } else {
+            format!("{}/{}", base, prefix)
+        }
+    }
+}
+
+/// Check the user and host of an SSH destination, `[user@]host`, so that
+/// `ssh` can not take them as options or split them into other arguments.
+pub(crate) fn check_ssh_destination(destination: &str) -> Result<(), Error> {
+    let (user, host) = match destination.rsplit_once('@') {
+        Some((user, host)) => (Some(user), host),
+        None => (None, destination),
+    };
+    let invalid = |part: &str| {
+        part.is_empty()
+            || part.starts_with('-')
+            || part.chars().any(|c| c.is_whitespace() || c.is_control())
+    };
+    if user.into_iter().chain(Some(host)).any(invalid) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid SSH destination {:?}", destination),
+        ));
+    }
+    Ok(())
+}
+
+/// Options of the stores of each scheme opened by [`open_from_url_with`].
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    #[cfg(feature = "http")]
+    pub http: HttpOptions,
+    pub s3: S3Options,
+    #[cfg(feature = "webhdfs")]
+    pub webhdfs: WebHdfsOptions,
+    #[cfg(feature = "sftp")]
+    pub sftp: SftpOptions,
+}
+
+/// A hierarchy in any of the stores [`open_from_url`] opens.
+#[derive(Clone, Debug)]
+pub enum UrlHierarchy {
+    #[cfg(feature = "filesystem")]
+    Filesystem(FilesystemHierarchy),
+    #[cfg(feature = "http")]
+    Http(HttpStore),
+    #[cfg(feature = "webdav")]
+    WebDav(WebDavStore),
+    #[cfg(feature = "webhdfs")]
+    WebHdfs(WebHdfsStore),
+    #[cfg(feature = "sftp")]
+    Sftp(SftpStore),
+    /// A store of a [registered](crate::store::plugin::register_scheme)
+    /// scheme.
+    Plugin(Arc<dyn DynStore>),
+}
+
+/// Open an existing hierarchy by URL, with default options.
+pub fn open_from_url(url: &str) -> Result<UrlHierarchy, Error> {
+    open_from_url_with(url, &OpenOptions::default())
+}
+
+/// Open an existing hierarchy by URL.
+///
+/// Fails with [`ErrorKind::Unsupported`] if the URL's scheme is unknown or
+/// the feature of its store is not enabled.
+#[cfg_attr(not(any(feature = "http", feature = "sftp")), allow(unused_variables))]
+pub fn open_from_url_with(url: &str, options: &OpenOptions) -> Result<UrlHierarchy, Error> {
+    if let Some(factory) = url.find("://").and_then(|i| scheme_factory(&url[..i])) {
+        return Ok(UrlHierarchy::Plugin(factory(url)?));
+    }
+    match StoreLocation::parse(url)? {
+        #[cfg(feature = "filesystem")]
+        StoreLocation::File(path) => Ok(UrlHierarchy::Filesystem(FilesystemHierarchy::open(path)?)),
+        #[cfg(not(feature = "filesystem"))]
+        StoreLocation::File(_) => Err(unsupported("file", "filesystem")),
+        #[cfg(feature = "http")]
+        StoreLocation::Http(url) => Ok(UrlHierarchy::Http(HttpStore::open_with_options(
+            &url,
+            &options.http,
+        )?)),
+        #[cfg(feature = "http")]
+        StoreLocation::S3 { bucket, prefix } => {
+            // Key credentials sign requests for the bucket's region.
+            let mut http = options.s3.http.clone();
+            http.signing_region
+                .get_or_insert_with(|| options.s3.region.clone());
+            Ok(UrlHierarchy::Http(HttpStore::open_with_options(
+                &options.s3.url(&bucket, &prefix),
+                &http,
+            )?))
+        }
+        #[cfg(not(feature = "http"))]
+        StoreLocation::Http(_) | StoreLocation::S3 { .. } => {
+            Err(unsupported("HTTP and S3", "http"))
+        }
+        #[cfg(feature = "webdav")]
+        StoreLocation::WebDav(url) => Ok(UrlHierarchy::WebDav(WebDavStore::open_with_options(
+            &url,
+            &options.http,
+        )?)),
+        #[cfg(not(feature = "webdav"))]
+        StoreLocation::WebDav(_) => Err(unsupported("WebDAV", "webdav")),
+        #[cfg(feature = "webhdfs")]
+        StoreLocation::WebHdfs(url) => Ok(UrlHierarchy::WebHdfs(WebHdfsStore::open_with_options(
+            &url,
+            &options.webhdfs,
+        )?)),
+        #[cfg(not(feature = "webhdfs"))]
+        StoreLocation::WebHdfs(_) => Err(unsupported("WebHDFS", "webhdfs")),
+        #[cfg(feature = "sftp")]
+        StoreLocation::Sftp {
+            destination,
+            port,
+            path,
+        } => {
+            let mut sftp = options.sftp.clone();
+            if let Some(port) = port {
+                sftp.ssh_command.extend(["-p".to_owned(), port.to_string()]);
+            }
+            Ok(UrlHierarchy::Sftp(SftpStore::open_with_options(
+                &destination,
+                &path,
+                &sftp,
+            )?))
+        }
+        #[cfg(not(feature = "sftp"))]
+        StoreLocation::Sftp { .. } => Err(unsupported("SFTP", "sftp")),
+    }
+}
+
+#[cfg(not(all(
+    feature = "filesystem",
+    feature = "webdav",
+    feature = "webhdfs",
+    feature = "sftp"
+)))]
+fn unsupported(scheme: &str, feature: &str) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "Opening {} URLs requires the {} feature of zarr",
+            scheme, feature
+        ),
+    )
+}
+
+macro_rules! delegate {
+    ($self:ident, $store:ident => $expression:expr) => {
+        match $self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem($store) => $expression,
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http($store) => $expression,
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav($store) => $expression,
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs($store) => $expression,
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp($store) => $expression,
+            UrlHierarchy::Plugin($store) => $expression,
+        }
+    };
+}
+
+impl Hierarchy for UrlHierarchy {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        delegate!(self, store => store.get_entry_point_metadata())
+    }
+}
+
+impl ReadableStore for UrlHierarchy {
+    type GetReader = Box<dyn Read + Send + Sync>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        delegate!(self, store => store.exists(key))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => Ok(store
+                .get(key)?
+                .map(|reader| Box::new(reader) as Self::GetReader)),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(store) => Ok(store
+                .get(key)?
+                .map(|reader| Box::new(reader) as Self::GetReader)),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.get(key),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => Ok(store
+                .get(key)?
+                .map(|reader| Box::new(reader) as Self::GetReader)),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => Ok(store
+                .get(key)?
+                .map(|reader| Box::new(reader) as Self::GetReader)),
+            UrlHierarchy::Plugin(store) => Ok(store
+                .get(key)?
+                .map(|value| Box::new(std::io::Cursor::new(value)) as Self::GetReader)),
+        }
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        delegate!(self, store => store.uri(key))
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.size(key),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(store) => store.size(key),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.size(key),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.size(key),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.size(key),
+            UrlHierarchy::Plugin(store) => Ok(store.stat(key)?.map(|stat| stat.size)),
+        }
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        delegate!(self, store => store.stat(key))
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        delegate!(self, store => store.is_read_only(key))
+    }
+
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.get_many(keys),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(store) => store.get_many(keys),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.get_many(keys),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.get_many(keys),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.get_many(keys),
+            UrlHierarchy::Plugin(store) => keys.iter().map(|key| store.get(key)).collect(),
+        }
+    }
+}
+
+impl PartialReadStore for UrlHierarchy {
+    fn get_range(
+        &self,
+        key: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        delegate!(self, store => store.get_range(key, offset, length))
+    }
+}
+
+/// Listing fails with [`ErrorKind::Unsupported`] for HTTP stores.
+impl ListableStore for UrlHierarchy {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.list(),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_listable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.list(),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.list(),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.list(),
+            UrlHierarchy::Plugin(_) => self.list_prefix("/"),
+        }
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.list_prefix(prefix),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_listable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.list_prefix(prefix),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.list_prefix(prefix),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.list_prefix(prefix),
+            UrlHierarchy::Plugin(store) => {
+                let mut to_visit = vec![prefix.to_owned()];
+                let mut result = vec![];
+                while let Some(next) = to_visit.pop() {
+                    let (keys, prefixes) = store.list_dir(&next)?;
+                    result.extend(keys);
+                    to_visit.extend(prefixes);
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.list_dir(prefix),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_listable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.list_dir(prefix),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.list_dir(prefix),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.list_dir(prefix),
+            UrlHierarchy::Plugin(store) => store.list_dir(prefix),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn not_listable() -> Error {
+    Error::new(ErrorKind::Unsupported, "HTTP stores cannot be listed")
+}
+
+/// Writing fails with [`ErrorKind::PermissionDenied`] for HTTP stores.
+#[cfg_attr(not(feature = "filesystem"), allow(unused_variables))]
+impl WriteableStore for UrlHierarchy {
+    type SetWriter = Box<dyn Write>;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => {
+                store.set(key, |writer| value(Box::new(writer) as Self::SetWriter))
+            }
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_writeable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => {
+                store.set(key, |writer| value(Box::new(writer) as Self::SetWriter))
+            }
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => {
+                store.set(key, |writer| value(Box::new(writer) as Self::SetWriter))
+            }
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => {
+                store.set(key, |writer| value(Box::new(writer) as Self::SetWriter))
+            }
+            UrlHierarchy::Plugin(store) => {
+                let buffer = Arc::new(Mutex::new(Vec::new()));
+                value(Box::new(BufferWriter::new(Arc::clone(&buffer))))?;
+                let value = buffer.lock().unwrap();
+                store.set(key, &value)
+            }
+        }
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.erase(key),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_writeable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.erase(key),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.erase(key),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.erase(key),
+            UrlHierarchy::Plugin(store) => store.erase(key),
+        }
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.erase_prefix(key_prefix),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_writeable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.erase_prefix(key_prefix),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.erase_prefix(key_prefix),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.erase_prefix(key_prefix),
+            UrlHierarchy::Plugin(store) => store.erase_prefix(key_prefix),
+        }
+    }
+
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.put_many(pairs),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Err(not_writeable()),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.put_many(pairs),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.put_many(pairs),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.put_many(pairs),
+            UrlHierarchy::Plugin(store) => pairs
+                .iter()
+                .try_for_each(|(key, value)| store.set(key, value)),
+        }
+    }
+
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        match self {
+            #[cfg(feature = "filesystem")]
+            UrlHierarchy::Filesystem(store) => store.make_read_only(key_prefix),
+            #[cfg(feature = "http")]
+            UrlHierarchy::Http(_) => Ok(true),
+            #[cfg(feature = "webdav")]
+            UrlHierarchy::WebDav(store) => store.make_read_only(key_prefix),
+            #[cfg(feature = "webhdfs")]
+            UrlHierarchy::WebHdfs(store) => store.make_read_only(key_prefix),
+            #[cfg(feature = "sftp")]
+            UrlHierarchy::Sftp(store) => store.make_read_only(key_prefix),
+            UrlHierarchy::Plugin(_) => Ok(false),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn not_writeable() -> Error {
+    Error::new(ErrorKind::PermissionDenied, "HTTP stores are read-only")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_store_location() {
+        assert_eq!(
+            StoreLocation::parse("file:///tmp/a.zr3").unwrap(),
+            StoreLocation::File("/tmp/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("file://localhost/tmp/a.zr3").unwrap(),
+            StoreLocation::File("/tmp/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("relative/a.zr3").unwrap(),
+            StoreLocation::File("relative/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("file://host/a.zr3")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            StoreLocation::parse("HTTPS://example.com/a.zr3").unwrap(),
+            StoreLocation::Http("HTTPS://example.com/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("s3://bucket/path/a.zr3/").unwrap(),
+            StoreLocation::S3 {
+                bucket: "bucket".into(),
+                prefix: "path/a.zr3".into(),
+            }
+        );
+        assert_eq!(
+            StoreLocation::parse("davs://cloud.example.com/dav/a.zr3").unwrap(),
+            StoreLocation::WebDav("https://cloud.example.com/dav/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("swebhdfs://namenode:9871/warehouse/a.zr3").unwrap(),
+            StoreLocation::WebHdfs("https://namenode:9871/webhdfs/v1/warehouse/a.zr3".into())
+        );
+        assert_eq!(
+            StoreLocation::parse("sftp://user@cluster:2222/scratch/a.zr3").unwrap(),
+            StoreLocation::Sftp {
+                destination: "user@cluster".into(),
+                port: Some(2222),
+                path: "/scratch/a.zr3".into(),
+            }
+        );
+        assert_eq!(
+            StoreLocation::parse("sftp://cluster/~/a.zr3").unwrap(),
+            StoreLocation::Sftp {
+                destination: "cluster".into(),
+                port: None,
+                path: "a.zr3".into(),
+            }
+        );
+        for hostile in &[
+            "sftp://cluster:ssh/a.zr3",
+            "sftp://cluster:-22/a.zr3",
+            "sftp://-oProxyCommand=touch%20pwned/a.zr3",
+            "sftp://-oProxyCommand=sh@cluster/a.zr3",
+            "sftp://user@-oProxyCommand=sh/a.zr3",
+            "sftp://user@clus ter/a.zr3",
+            "sftp://user\n@cluster/a.zr3",
+            "sftp://@cluster/a.zr3",
+        ] {
+            assert_eq!(
+                StoreLocation::parse(hostile).unwrap_err().kind(),
+                ErrorKind::InvalidInput,
+                "{}",
+                hostile
+            );
+        }
+        assert_eq!(
+            StoreLocation::parse("s3:///a.zr3").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            StoreLocation::parse("gopher://a.zr3").unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+    }
+
+    /// Store of values in memory, as a plugin.
+    #[derive(Debug, Default)]
+    struct MemoryStore {
+        entry_point_metadata: EntryPointMetadata,
+        values: Mutex<std::collections::BTreeMap<String, Vec<u8>>>,
+    }
+
+    impl DynStore for MemoryStore {
+        fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+            &self.entry_point_metadata
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.values.lock().unwrap().get(key).cloned())
+        }
+
+        fn uri(&self, key: &str) -> Result<String, Error> {
+            Ok(format!("mem://{}", key))
+        }
+
+        fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+            let mut keys = vec![];
+            let mut prefixes: Vec<String> = vec![];
+            for key in self.values.lock().unwrap().keys() {
+                if let Some(rest) = key.strip_prefix(prefix) {
+                    match rest.find('/') {
+                        Some(i) => prefixes.push(key[..=prefix.len() + i].to_owned()),
+                        None => keys.push(key.clone()),
+                    }
+                }
+            }
+            prefixes.dedup();
+            Ok((keys, prefixes))
+        }
+
+        fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+            self.values
+                .lock()
+                .unwrap()
+                .insert(key.to_owned(), value.to_vec());
+            Ok(())
+        }
+
+        fn erase(&self, key: &str) -> Result<bool, Error> {
+            self.values.lock().unwrap().remove(key);
+            Ok(true)
+        }
+
+        fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+            self.values
+                .lock()
+                .unwrap()
+                .retain(|key, _| !key.starts_with(key_prefix));
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_open_registered_scheme() {
+        use crate::prelude::*;
+        use crate::store::plugin::register_scheme;
+
+        let store = Arc::new(MemoryStore::default());
+        let registered = Arc::clone(&store);
+        register_scheme("test-mem", move |url| {
+            assert_eq!(url, "TEST-MEM://volume");
+            Ok(Arc::clone(&registered) as Arc<dyn DynStore>)
+        });
+
+        let h = open_from_url("TEST-MEM://volume").unwrap();
+        assert!(matches!(h, UrlHierarchy::Plugin(_)));
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2])
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![1], vec![1u8, 2]),
+        )
+        .unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("a", &array_meta, smallvec![1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2]);
+        assert_eq!(h.list_nodes("").unwrap(), vec!["a".to_owned()]);
+        assert!(store.values.lock().unwrap().contains_key("/data/root/a/c1"));
+
+        assert!(crate::store::plugin::unregister_scheme("test-mem"));
+        assert_eq!(
+            open_from_url("test-mem://volume").unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_s3_url() {
+        let mut options = S3Options::default();
+        assert_eq!(
+            options.url("bucket", "path/a.zr3"),
+            "https://bucket.s3.us-east-1.amazonaws.com/path/a.zr3"
+        );
+        options.endpoint = Some("http://localhost:9000/".into());
+        assert_eq!(options.url("bucket", ""), "http://localhost:9000/bucket");
+    }
+
+    #[cfg(all(feature = "filesystem", feature = "http", feature = "server"))]
+    #[test]
+    fn test_open_from_url() {
+        use crate::prelude::*;
+        use crate::server::StoreServer;
+
+        let dir = tempdir::TempDir::new("rust_zarr_url_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![8], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![4])
+            .compressor(crate::compression::CompressionType::Raw(Default::default()))
+            .build();
+        h.create_array("a", &array_meta).unwrap();
+        h.write_chunk(
+            "a",
+            &array_meta,
+            &SliceDataChunk::new(smallvec![0], vec![1u8, 2, 3, 4]),
+        )
+        .unwrap();
+        let chunk = h.get_range("/data/root/a/c0", 0, None).unwrap().unwrap();
+
+        let file = open_from_url(&format!("file://{}", dir.path().display())).unwrap();
+        assert!(matches!(file, UrlHierarchy::Filesystem(_)));
+        assert_eq!(file.list_nodes("").unwrap(), vec!["a".to_owned()]);
+
+        let server = StoreServer::bind(h, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        let remote = open_from_url(&format!("http://{}/", addr)).unwrap();
+        assert!(matches!(remote, UrlHierarchy::Http(_)));
+        let remote_meta = remote.get_array_metadata("a").unwrap();
+        assert_eq!(remote_meta.get_shape(), array_meta.get_shape());
+        assert!(remote_meta.is_read_only());
+        let read: VecDataChunk<u8> = remote
+            .read_chunk("a", &array_meta, smallvec![0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        assert!(remote
+            .read_chunk::<u8>("a", &array_meta, smallvec![1])
+            .unwrap()
+            .is_none());
+        assert!(ReadableStore::exists(&remote, "/data/root/a/c0").unwrap());
+        assert_eq!(
+            remote.size("/data/root/a/c0").unwrap(),
+            Some(chunk.len() as u64)
+        );
+        assert_eq!(
+            remote.get_range("/data/root/a/c0", 1, Some(2)).unwrap(),
+            Some(chunk[1..3].to_vec())
+        );
+        assert_eq!(
+            remote.get_range("/data/root/a/c0", 100, None).unwrap(),
+            Some(vec![])
+        );
+        assert_eq!(
+            remote.create_group("g").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            remote.list_nodes("").unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+
+        assert_eq!(
+            open_from_url(&format!("http://{}/missing", addr))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
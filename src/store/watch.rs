@@ -0,0 +1,377 @@
+//! Notifications of changes to a filesystem hierarchy by other writers.
+//!
+//! A [`HierarchyWatcher`] subscribes to filesystem events below a
+//! [`FilesystemHierarchy`] and reports them as changes to array and group
+//! metadata or to chunks, so a viewer can redraw regions as an acquisition
+//! pipeline writes them.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::watch::{
+//!     ChangedNode,
+//!     HierarchyWatcher,
+//! };
+//!
+//! let h = FilesystemHierarchy::open("/tmp/live.zr3").unwrap();
+//! let mut watcher = HierarchyWatcher::new(&h).unwrap();
+//! for event in &mut watcher {
+//!     if let ChangedNode::Chunk { path_name, grid_position } = event.unwrap().node {
+//!         println!("chunk {:?} of {} changed", grid_position, path_name);
+//!     }
+//! }
+//! ```
+
+use std::collections::{
+    HashMap,
+    VecDeque,
+};
+use std::io::{
+    Error,
+    ErrorKind,
+    Result,
+};
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+use std::sync::mpsc::{
+    self,
+    Receiver,
+    RecvTimeoutError,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use notify::event::{
+    CreateKind,
+    EventKind,
+    ModifyKind,
+    RenameMode,
+};
+use notify::{
+    RecommendedWatcher,
+    RecursiveMode,
+    Watcher,
+};
+use walkdir::WalkDir;
+
+use crate::storage::parse_chunk_key;
+use crate::store::filesystem::FilesystemHierarchy;
+use crate::{
+    ArrayMetadata,
+    GridCoord,
+    Hierarchy,
+    HierarchyReader,
+};
+
+/// Whether a key was written or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Modified,
+    Removed,
+}
+
+/// Metadata document or chunk a change applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangedNode {
+    ArrayMetadata {
+        path_name: String,
+    },
+    GroupMetadata {
+        path_name: String,
+    },
+    Chunk {
+        path_name: String,
+        grid_position: GridCoord,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub node: ChangedNode,
+}
+
+/// Subscription to changes of a filesystem hierarchy's metadata and chunks.
+///
+/// Events are reported as the platform's filesystem watcher sees them, so a
+/// single write may be reported more than once, for example when the file
+/// is created and again as its contents are written. Keys which are neither
+/// metadata documents nor chunks of an existing array are ignored.
+#[derive(Debug)]
+pub struct HierarchyWatcher {
+    hierarchy: FilesystemHierarchy,
+    /// Base path as given and as reported by the platform, if different.
+    base_paths: Vec<PathBuf>,
+    events: Receiver<notify::Result<notify::Event>>,
+    pending: VecDeque<ChangeEvent>,
+    /// Metadata of arrays chunk keys have been resolved against, or `None`
+    /// for paths that are not arrays.
+    arrays: HashMap<String, Option<ArrayMetadata>>,
+    // Dropping the watcher stops its events, so it must outlive `events`.
+    _watcher: RecommendedWatcher,
+}
+
+impl HierarchyWatcher {
+    /// Watch for changes anywhere in a hierarchy.
+    pub fn new(hierarchy: &FilesystemHierarchy) -> Result<Self> {
+        let base_path = hierarchy.get_base_path().to_owned();
+        let mut base_paths = vec![base_path.clone()];
+        let canonical = base_path.canonicalize()?;
+        if canonical != base_path {
+            base_paths.push(canonical);
+        }
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(Error::other)?;
+        watcher
+            .watch(&base_path, RecursiveMode::Recursive)
+            .map_err(Error::other)?;
+
+        Ok(HierarchyWatcher {
+            hierarchy: hierarchy.clone(),
+            base_paths,
+            events,
+            pending: VecDeque::new(),
+            arrays: HashMap::new(),
+            _watcher: watcher,
+        })
+    }
+
+    /// Wait for the next change.
+    pub fn recv(&mut self) -> Result<ChangeEvent> {
+        self.next_event(None)
+            .map(|event| event.expect("Waits without a deadline"))
+    }
+
+    /// Wait up to a timeout for the next change, returning `None` if there
+    /// is none.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<ChangeEvent>> {
+        self.next_event(Some(Instant::now() + timeout))
+    }
+
+    /// The next change if one has already been seen, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<ChangeEvent>> {
+        self.next_event(Some(Instant::now()))
+    }
+
+    fn next_event(&mut self, deadline: Option<Instant>) -> Result<Option<ChangeEvent>> {
+        let disconnected = || Error::new(ErrorKind::BrokenPipe, "Filesystem watcher stopped");
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            let received = match deadline {
+                None => self.events.recv().map_err(|_| disconnected())?,
+                Some(deadline) => {
+                    match self
+                        .events
+                        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(received) => received,
+                        Err(RecvTimeoutError::Timeout) => return Ok(None),
+                        Err(RecvTimeoutError::Disconnected) => return Err(disconnected()),
+                    }
+                }
+            };
+            let event = received.map_err(Error::other)?;
+
+            if let EventKind::Create(CreateKind::Folder) = event.kind {
+                // Files may be written to a new directory before it is
+                // watched, so report the files it already holds.
+                for path in &event.paths {
+                    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+                        if entry.file_type().is_file() {
+                            if let Some(node) = self.classify(entry.path()) {
+                                self.pending.push_back(ChangeEvent {
+                                    kind: ChangeKind::Modified,
+                                    node,
+                                });
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            let kind = match event.kind {
+                EventKind::Create(_) => ChangeKind::Modified,
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => ChangeKind::Removed,
+                EventKind::Modify(ModifyKind::Metadata(_)) => continue,
+                EventKind::Modify(_) => ChangeKind::Modified,
+                EventKind::Remove(_) => ChangeKind::Removed,
+                EventKind::Access(_) | EventKind::Any | EventKind::Other => continue,
+            };
+            for path in &event.paths {
+                if let Some(node) = self.classify(path) {
+                    self.pending.push_back(ChangeEvent { kind, node });
+                }
+            }
+        }
+    }
+
+    /// The node a filesystem path stores, if any.
+    fn classify(&mut self, path: &Path) -> Option<ChangedNode> {
+        let relative = self
+            .base_paths
+            .iter()
+            .find_map(|base_path| path.strip_prefix(base_path).ok())?;
+        let mut key = String::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => {
+                    key.push('/');
+                    key.push_str(name.to_str()?);
+                }
+                _ => return None,
+            }
+        }
+
+        if let Some(path_name) = self.metadata_path_name(&key, crate::ARRAY_METADATA_KEY_EXT) {
+            // Chunk keys of this array must be resolved against its new
+            // metadata.
+            self.arrays.remove(&path_name);
+            return Some(ChangedNode::ArrayMetadata { path_name });
+        }
+        if let Some(path_name) = self.metadata_path_name(&key, crate::GROUP_METADATA_KEY_EXT) {
+            return Some(ChangedNode::GroupMetadata { path_name });
+        }
+
+        // Chunk keys are `<data path>/c<coordinates>`, where the coordinates'
+        // separator may itself be `/`, so try each component that could
+        // begin the coordinates in turn.
+        let data_path = key.strip_prefix(crate::DATA_ROOT_PATH)?;
+        let names: Vec<&str> = data_path.split('/').skip(1).collect();
+        for (i, name) in names.iter().enumerate() {
+            if !name.starts_with('c') {
+                continue;
+            }
+            let path_name = names[..i].join("/");
+            let hierarchy = &self.hierarchy;
+            let array_meta = self
+                .arrays
+                .entry(path_name.clone())
+                .or_insert_with(|| hierarchy.get_array_metadata(&path_name).ok());
+            if let Some(array_meta) = array_meta {
+                if let Some(grid_position) = parse_chunk_key(&path_name, array_meta, &key) {
+                    return Some(ChangedNode::Chunk {
+                        path_name,
+                        grid_position,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Path name of the node a key is the metadata document of, if it is
+    /// one of the given kind.
+    fn metadata_path_name(&self, key: &str, extension: &str) -> Option<String> {
+        let suffix = &self
+            .hierarchy
+            .get_entry_point_metadata()
+            .metadata_key_suffix;
+        let suffix = format!(
+            ".{}.{}",
+            extension,
+            suffix.strip_prefix('.').unwrap_or(suffix)
+        );
+        let path_name = key
+            .strip_prefix(crate::META_ROOT_PATH)?
+            .strip_suffix(suffix.as_str())?;
+        let path_name = crate::canonicalize_path(path_name).to_owned();
+        let metadata_key = match extension {
+            crate::ARRAY_METADATA_KEY_EXT => self.hierarchy.array_metadata_key(&path_name),
+            _ => self.hierarchy.group_metadata_key(&path_name),
+        };
+        if metadata_key == Path::new(key) {
+            Some(path_name)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for HierarchyWatcher {
+    type Item = Result<ChangeEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.recv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    /// Receive events until one matches, failing if none does in time.
+    fn expect_event(watcher: &mut HierarchyWatcher, expected: &ChangeEvent) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match watcher.recv_timeout(timeout).unwrap() {
+                Some(event) if event == *expected => return,
+                Some(_) => continue,
+                None => panic!("No event {:?}", expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir = tempdir::TempDir::new("rust_zarr_watch_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let mut watcher = HierarchyWatcher::new(&h).unwrap();
+
+        let writer = FilesystemHierarchy::open(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        writer.create_group("g").unwrap();
+        writer.create_array("g/img", &array_meta).unwrap();
+        expect_event(
+            &mut watcher,
+            &ChangeEvent {
+                kind: ChangeKind::Modified,
+                node: ChangedNode::GroupMetadata {
+                    path_name: "g".to_owned(),
+                },
+            },
+        );
+        expect_event(
+            &mut watcher,
+            &ChangeEvent {
+                kind: ChangeKind::Modified,
+                node: ChangedNode::ArrayMetadata {
+                    path_name: "g/img".to_owned(),
+                },
+            },
+        );
+
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8; 4]);
+        writer.write_chunk("g/img", &array_meta, &chunk).unwrap();
+        let chunk_node = ChangedNode::Chunk {
+            path_name: "g/img".to_owned(),
+            grid_position: smallvec![1, 0],
+        };
+        expect_event(
+            &mut watcher,
+            &ChangeEvent {
+                kind: ChangeKind::Modified,
+                node: chunk_node.clone(),
+            },
+        );
+
+        writer.delete_chunk("g/img", &array_meta, &[1, 0]).unwrap();
+        expect_event(
+            &mut watcher,
+            &ChangeEvent {
+                kind: ChangeKind::Removed,
+                node: chunk_node,
+            },
+        );
+    }
+}
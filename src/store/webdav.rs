@@ -0,0 +1,543 @@
+//! A store of a hierarchy on a WebDAV server.
+//!
+//! Many institutional research data servers, such as ownCloud, Nextcloud
+//! and dCache, expose their files only over WebDAV. Keys are read as by an
+//! [`HttpStore`], including partial reads by `Range` requests, listed by
+//! `PROPFIND` requests, written by `PUT` requests, creating collections for
+//! them with `MKCOL` as needed, and erased by `DELETE` requests.
+//!
+//! Servers asking for basic authentication are sent an `Authorization`
+//! header from the [`headers`](HttpOptions::headers) of the options.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::http::HttpOptions;
+//! use zarr::store::webdav::WebDavStore;
+//!
+//! let options = HttpOptions {
+//!     headers: vec![("Authorization".to_owned(), "Basic dXNlcjpwYXNz".to_owned())],
+//!     ..Default::default()
+//! };
+//! let h = WebDavStore::open_or_create_with_options(
+//!     "https://cloud.example.com/remote.php/dav/files/user/volume.zr3",
+//!     &options,
+//! )
+//! .unwrap();
+//! h.create_group("raw").unwrap();
+//! ```
+
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+    Result,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use crate::{
+    storage::{
+        parallel_map,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::{
+        http::{
+            check_response,
+            read_body,
+            HttpOptions,
+            HttpStore,
+        },
+        write_buffer::BufferWriter,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// Body of `PROPFIND` requests, asking only whether resources are
+/// collections.
+const PROPFIND_BODY: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+    <D:propfind xmlns:D=\"DAV:\"><D:prop><D:resourcetype/></D:prop></D:propfind>";
+
+/// A store of a hierarchy below a base URL of a WebDAV server.
+#[derive(Clone, Debug)]
+pub struct WebDavStore {
+    http: HttpStore,
+}
+
+impl Hierarchy for WebDavStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.http.get_entry_point_metadata()
+    }
+}
+
+impl WebDavStore {
+    /// Open an existing Zarr hierarchy by base URL.
+    pub fn open(base_url: &str) -> Result<WebDavStore> {
+        Self::open_with_options(base_url, &HttpOptions::default())
+    }
+
+    /// Open an existing Zarr hierarchy by base URL, making requests with
+    /// the given options.
+    pub fn open_with_options(base_url: &str, options: &HttpOptions) -> Result<WebDavStore> {
+        Ok(WebDavStore {
+            http: HttpStore::open_with_options(base_url, options)?,
+        })
+    }
+
+    /// Open a Zarr hierarchy by base URL, creating it if it does not exist.
+    pub fn open_or_create(base_url: &str) -> Result<WebDavStore> {
+        Self::open_or_create_with_options(base_url, &HttpOptions::default())
+    }
+
+    /// Open a Zarr hierarchy by base URL, creating it if it does not exist,
+    /// making requests with the given options.
+    pub fn open_or_create_with_options(
+        base_url: &str,
+        options: &HttpOptions,
+    ) -> Result<WebDavStore> {
+        let mut http = HttpStore::connect(base_url, options)?;
+        match http.get(crate::ENTRY_POINT_KEY)? {
+            Some(reader) => {
+                let metadata: EntryPointMetadata = serde_json::from_reader(reader)?;
+                http.set_entry_point_metadata(metadata)?;
+                Ok(WebDavStore { http })
+            }
+            None => {
+                let store = WebDavStore { http };
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                store.put(crate::ENTRY_POINT_KEY, &metadata)?;
+                Ok(store)
+            }
+        }
+    }
+
+    /// URL of the hierarchy, without a trailing slash.
+    pub fn get_base_url(&self) -> &str {
+        self.http.get_base_url()
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let request = self.http.request("PUT", key, None)?;
+        let url = request.url().to_owned();
+        match request.send_bytes(value) {
+            // The collections of the key do not exist.
+            Err(ureq::Error::Status(404, _)) | Err(ureq::Error::Status(409, _)) => {
+                self.make_collections(key)?;
+                let request = self.http.request("PUT", key, None)?;
+                check_response(&url, request.send_bytes(value))?;
+            }
+            response => {
+                check_response(&url, response)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the base collection and the collections of a key, from the
+    /// top down, skipping those which exist.
+    fn make_collections(&self, key: &str) -> Result<()> {
+        let mut collection = String::from("/");
+        let mut components: Vec<&str> = key.trim_matches('/').split('/').collect();
+        components.pop();
+        for component in std::iter::once("").chain(components) {
+            if !component.is_empty() {
+                collection.push_str(component);
+                collection.push('/');
+            }
+            let request = self.http.request("MKCOL", &collection, None)?;
+            let url = request.url().to_owned();
+            match request.call() {
+                // The collection already exists.
+                Err(ureq::Error::Status(405, _)) => {}
+                response => {
+                    check_response(&url, response)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let request = self.http.request("DELETE", key, None)?;
+        let url = request.url().to_owned();
+        check_response(&url, request.call())?;
+        Ok(true)
+    }
+}
+
+impl ReadableStore for WebDavStore {
+    type GetReader = Box<dyn Read + Send + Sync>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        ReadableStore::exists(&self.http, key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        self.http.get(key)
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        self.http.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        self.http.size(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        self.http.stat(key)
+    }
+
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.http.get_many(keys)
+    }
+}
+
+impl PartialReadStore for WebDavStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        self.http.get_range(key, offset, length)
+    }
+}
+
+impl ListableStore for WebDavStore {
+    /// Members of a collection are listed by a `PROPFIND` request of depth
+    /// one. Listing a collection which does not exist fails with
+    /// [`ErrorKind::NotFound`].
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let collection = format!("{}/", prefix.trim_end_matches('/'));
+        let request = self
+            .http
+            .request("PROPFIND", &collection, None)?
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8");
+        let url = request.url().to_owned();
+        let response = check_response(&url, request.send_string(PROPFIND_BODY))?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No collection {}", url)))?;
+        let body = String::from_utf8(read_body(response)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let base_path = percent_decode(url_path(self.get_base_url()))?;
+        let mut keys = vec![];
+        let mut prefixes = vec![];
+        for (href, is_collection) in parse_multistatus(&body) {
+            let path = percent_decode(url_path(&href))?;
+            let name = match path
+                .strip_prefix(&base_path)
+                .map(|path| path.trim_start_matches('/'))
+                .and_then(|path| path.strip_prefix(collection.trim_start_matches('/')))
+                .map(|name| name.trim_end_matches('/'))
+            {
+                // Responses include the collection itself.
+                Some("") | None => continue,
+                Some(name) => name,
+            };
+            if is_collection {
+                prefixes.push(format!("{}{}/", prefix, name));
+            } else {
+                keys.push(format!("{}{}", prefix, name));
+            }
+        }
+        Ok((keys, prefixes))
+    }
+}
+
+impl WriteableStore for WebDavStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        self.put(key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        self.delete(key)
+    }
+
+    /// Collections are erased with their members by a single `DELETE`
+    /// request.
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        self.delete(key_prefix)
+    }
+
+    /// Values are written in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        parallel_map(pairs, |(key, value)| self.put(key, value))?;
+        Ok(())
+    }
+}
+
+/// The path of a URL, or the URL itself if it is only a path.
+fn url_path(url: &str) -> &str {
+    match url.find("://") {
+        Some(i) => {
+            let rest = &url[i + 3..];
+            rest.find('/').map_or("", |j| &rest[j..])
+        }
+        None => url,
+    }
+}
+
+/// Decode the percent escapes of a URL path.
+fn percent_decode(path: &str) -> Result<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i..i + 3) {
+            Some([b'%', high, low]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// The hrefs of the resources of a `PROPFIND` multi-status response, each
+/// with whether the resource is a collection.
+///
+/// Elements are matched by local name whatever their namespace prefix, and
+/// only the character entities XML predefines are decoded.
+fn parse_multistatus(body: &str) -> Vec<(String, bool)> {
+    let mut resources = vec![];
+    let mut href = None;
+    let mut is_collection = false;
+    let mut href_start = None;
+    let mut rest = body;
+    let mut offset = 0;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .unwrap_or("");
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+        match (local_name, closing) {
+            ("response", false) => {
+                href = None;
+                is_collection = false;
+            }
+            ("response", true) => {
+                if let Some(href) = href.take() {
+                    resources.push((href, is_collection));
+                }
+            }
+            ("href", false) => href_start = Some(offset + end + 1),
+            ("href", true) => {
+                if let Some(href_start) = href_start.take() {
+                    href = Some(decode_entities(body[href_start..offset + start].trim()));
+                }
+            }
+            ("collection", false) => is_collection = true,
+            _ => {}
+        }
+        offset += end + 1;
+        rest = &rest[end + 1..];
+    }
+    resources
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::{
+        BufRead,
+        BufReader,
+        Write,
+    };
+    use std::net::TcpListener;
+
+    use crate::prelude::*;
+
+    #[test]
+    fn test_parse_multistatus() {
+        let body = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response>
+                <d:href>/dav/a.zr3/data/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+              </d:response>
+              <response xmlns="DAV:">
+                <href>http://host/dav/a.zr3/data/a%20b&amp;c</href>
+                <propstat><prop><resourcetype/></prop></propstat>
+              </response>
+            </d:multistatus>"#;
+        assert_eq!(
+            parse_multistatus(body),
+            vec![
+                ("/dav/a.zr3/data/".to_owned(), true),
+                ("http://host/dav/a.zr3/data/a%20b&c".to_owned(), false),
+            ]
+        );
+        assert_eq!(url_path("http://host/dav/a%20b"), "/dav/a%20b");
+        assert_eq!(url_path("http://host"), "");
+        assert_eq!(percent_decode("/dav/a%20b%2").unwrap(), "/dav/a b%2");
+    }
+
+    /// Serve a minimal WebDAV server of values in memory below `/dav`.
+    fn serve() -> String {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dav/a.zr3", server.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut values: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+            let mut collections = vec!["/dav/".to_owned()];
+            for stream in server.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:")
+                    {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap();
+                let path = parts.next().unwrap().to_owned();
+                let parent = |path: &str| {
+                    let path = path.trim_end_matches('/');
+                    path[..=path.rfind('/').unwrap()].to_owned()
+                };
+                let (status, body) = match method {
+                    "GET" | "HEAD" => match values.get(&path) {
+                        Some(value) => ("200 OK", value.clone()),
+                        None => ("404 Not Found", vec![]),
+                    },
+                    "PUT" if collections.contains(&parent(&path)) => {
+                        values.insert(path, body);
+                        ("201 Created", vec![])
+                    }
+                    "PUT" => ("409 Conflict", vec![]),
+                    "MKCOL" if collections.contains(&path) => ("405 Method Not Allowed", vec![]),
+                    "MKCOL" if collections.contains(&parent(&path)) => {
+                        collections.push(path);
+                        ("201 Created", vec![])
+                    }
+                    "MKCOL" => ("409 Conflict", vec![]),
+                    "DELETE" => {
+                        values.retain(|key, _| !key.starts_with(&path));
+                        collections.retain(|collection| !collection.starts_with(&path));
+                        ("204 No Content", vec![])
+                    }
+                    "PROPFIND" if collections.contains(&path) => {
+                        let mut xml = String::from("<D:multistatus xmlns:D=\"DAV:\">");
+                        let members = collections
+                            .iter()
+                            .map(|collection| (collection, true))
+                            .chain(values.keys().map(|key| (key, false)));
+                        for (member, is_collection) in members {
+                            if member.starts_with(&path)
+                                && !member[path.len()..].trim_end_matches('/').contains('/')
+                            {
+                                let resource_type =
+                                    if is_collection { "<D:collection/>" } else { "" };
+                                xml.push_str(&format!(
+                                    "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+                                     <D:resourcetype>{}</D:resourcetype>\
+                                     </D:prop></D:propstat></D:response>",
+                                    member.replace(' ', "%20"),
+                                    resource_type
+                                ));
+                            }
+                        }
+                        xml.push_str("</D:multistatus>");
+                        ("207 Multi-Status", xml.into_bytes())
+                    }
+                    _ => ("404 Not Found", vec![]),
+                };
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                if method != "HEAD" {
+                    reader.get_mut().write_all(&body).unwrap();
+                }
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_webdav_store() {
+        let url = serve();
+        assert_eq!(
+            WebDavStore::open(&url).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        let h = WebDavStore::open_or_create(&url).unwrap();
+
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+        h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+
+        let h = WebDavStore::open(&url).unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.list_dir("/missing/").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .is_none());
+    }
+}
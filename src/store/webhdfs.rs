@@ -0,0 +1,572 @@
+//! A store of a hierarchy on an HDFS cluster, by the WebHDFS REST API.
+//!
+//! Requests go to the cluster's name node, or to an HttpFS gateway, at a
+//! base URL such as `http://namenode:9870/webhdfs/v1/path/volume.zr3`. Reads
+//! and writes are redirected by the name node to data nodes, which must be
+//! reachable at the addresses it redirects to. Native HDFS RPC and Kerberos
+//! authentication are not supported; secure clusters can be accessed with a
+//! delegation token.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::store::webhdfs::{
+//!     WebHdfsOptions,
+//!     WebHdfsStore,
+//! };
+//!
+//! let options = WebHdfsOptions {
+//!     user: Some("analytics".to_owned()),
+//!     ..Default::default()
+//! };
+//! let h = WebHdfsStore::open_with_options(
+//!     "http://namenode:9870/webhdfs/v1/warehouse/volume.zr3",
+//!     &options,
+//! )
+//! .unwrap();
+//! let array_meta = h.get_array_metadata("raw").unwrap();
+//! ```
+
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Result,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    UNIX_EPOCH,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    storage::{
+        parallel_map,
+        KeyStat,
+        ListableStore,
+        PartialReadStore,
+        ReadableStore,
+        WriteableStore,
+    },
+    store::{
+        http::{
+            check_response,
+            read_body,
+            HttpOptions,
+            HttpStore,
+        },
+        write_buffer::BufferWriter,
+    },
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// Options of the requests of a [`WebHdfsStore`].
+#[derive(Clone, Debug, Default)]
+pub struct WebHdfsOptions {
+    pub http: HttpOptions,
+    /// User making requests to clusters with simple authentication, sent as
+    /// the `user.name` parameter.
+    pub user: Option<String>,
+    /// Delegation token of requests to secure clusters.
+    pub delegation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileStatus {
+    #[serde(default)]
+    path_suffix: String,
+    length: u64,
+    /// Milliseconds since the epoch.
+    modification_time: u64,
+    #[serde(rename = "type")]
+    file_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileStatusResponse {
+    #[serde(rename = "FileStatus")]
+    file_status: FileStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileStatuses {
+    #[serde(rename = "FileStatus")]
+    file_status: Vec<FileStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListStatusResponse {
+    #[serde(rename = "FileStatuses")]
+    file_statuses: FileStatuses,
+}
+
+/// A store of a hierarchy below a WebHDFS base URL.
+#[derive(Clone, Debug)]
+pub struct WebHdfsStore {
+    http: HttpStore,
+    user: Option<String>,
+    delegation: Option<String>,
+}
+
+impl Hierarchy for WebHdfsStore {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.http.get_entry_point_metadata()
+    }
+}
+
+impl WebHdfsStore {
+    /// Open an existing Zarr hierarchy by WebHDFS base URL.
+    pub fn open(base_url: &str) -> Result<WebHdfsStore> {
+        Self::open_with_options(base_url, &WebHdfsOptions::default())
+    }
+
+    /// Open an existing Zarr hierarchy by WebHDFS base URL, making requests
+    /// with the given options.
+    pub fn open_with_options(base_url: &str, options: &WebHdfsOptions) -> Result<WebHdfsStore> {
+        let mut store = Self::connect(base_url, options)?;
+        let reader = store.get(crate::ENTRY_POINT_KEY)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No Zarr hierarchy at {}", store.get_base_url()),
+            )
+        })?;
+        let metadata: EntryPointMetadata = serde_json::from_reader(reader)?;
+        store.http.set_entry_point_metadata(metadata)?;
+        Ok(store)
+    }
+
+    /// Open a Zarr hierarchy by WebHDFS base URL, creating it if it does not
+    /// exist.
+    pub fn open_or_create(base_url: &str) -> Result<WebHdfsStore> {
+        Self::open_or_create_with_options(base_url, &WebHdfsOptions::default())
+    }
+
+    /// Open a Zarr hierarchy by WebHDFS base URL, creating it if it does not
+    /// exist, making requests with the given options.
+    pub fn open_or_create_with_options(
+        base_url: &str,
+        options: &WebHdfsOptions,
+    ) -> Result<WebHdfsStore> {
+        let mut store = Self::connect(base_url, options)?;
+        match store.get(crate::ENTRY_POINT_KEY)? {
+            Some(reader) => {
+                let metadata: EntryPointMetadata = serde_json::from_reader(reader)?;
+                store.http.set_entry_point_metadata(metadata)?;
+            }
+            None => {
+                let metadata = serde_json::to_vec(&EntryPointMetadata::default())?;
+                store.put(crate::ENTRY_POINT_KEY, &metadata)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn connect(base_url: &str, options: &WebHdfsOptions) -> Result<WebHdfsStore> {
+        Ok(WebHdfsStore {
+            http: HttpStore::connect(base_url, &options.http)?,
+            user: options.user.clone(),
+            delegation: options.delegation.clone(),
+        })
+    }
+
+    /// URL of the hierarchy, without a trailing slash.
+    pub fn get_base_url(&self) -> &str {
+        self.http.get_base_url()
+    }
+
+    /// A request of an operation on the path of a key.
+    fn request(&self, method: &str, key: &str, op: &str) -> Result<ureq::Request> {
+        let mut request = self.http.request(method, key, None)?.query("op", op);
+        if let Some(user) = &self.user {
+            request = request.query("user.name", user);
+        }
+        if let Some(delegation) = &self.delegation {
+            request = request.query("delegation", delegation);
+        }
+        Ok(request)
+    }
+
+    /// Status of the file or directory of a key, or `None` if there is none.
+    fn file_status(&self, key: &str) -> Result<Option<FileStatus>> {
+        let request = self.request("GET", key, "GETFILESTATUS")?;
+        let url = request.url().to_owned();
+        match check_response(&url, request.call())? {
+            Some(response) => {
+                let status: FileStatusResponse = serde_json::from_slice(&read_body(response)?)?;
+                Ok(Some(status.file_status))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Files are created in two steps: the name node redirects the request to
+    /// the data node which is sent the value. Missing parent directories are
+    /// created.
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let request = self
+            .request("PUT", key, "CREATE")?
+            .query("overwrite", "true");
+        let url = request.url().to_owned();
+        let response = check_response(&url, request.call())?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Not found: {}", url)))?;
+        let location = response
+            .header("Location")
+            .map(str::to_owned)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("WebHDFS did not redirect the creation of {}", url),
+                )
+            })?;
+        let request = self
+            .http
+            .request_url("PUT", &location, None)?
+            .set("Content-Type", "application/octet-stream");
+        check_response(&location, request.send_bytes(value))?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str, recursive: bool) -> Result<bool> {
+        let request = self
+            .request("DELETE", key, "DELETE")?
+            .query("recursive", if recursive { "true" } else { "false" });
+        let url = request.url().to_owned();
+        check_response(&url, request.call())?;
+        Ok(true)
+    }
+
+    fn read(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        let mut request = self.request("GET", key, "OPEN")?;
+        if offset > 0 {
+            request = request.query("offset", &offset.to_string());
+        }
+        if let Some(length) = length {
+            request = request.query("length", &length.to_string());
+        }
+        let url = request.url().to_owned();
+        check_response(&url, request.call())?
+            .map(read_body)
+            .transpose()
+    }
+}
+
+impl ReadableStore for WebHdfsStore {
+    type GetReader = Cursor<Vec<u8>>;
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.stat(key)?.is_some())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>> {
+        Ok(self.read(key, 0, None)?.map(Cursor::new))
+    }
+
+    fn uri(&self, key: &str) -> Result<String> {
+        self.http.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.stat(key)?.map(|stat| stat.size))
+    }
+
+    /// Stats have the size and modification time of files, from a
+    /// `GETFILESTATUS` request.
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>> {
+        Ok(self
+            .file_status(key)?
+            .filter(|status| status.file_type == "FILE")
+            .map(|status| KeyStat {
+                size: status.length,
+                last_modified: Some(UNIX_EPOCH + Duration::from_millis(status.modification_time)),
+                etag: None,
+            }))
+    }
+
+    /// Values are read in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        parallel_map(keys, |key| self.read(key, 0, None))
+    }
+}
+
+impl PartialReadStore for WebHdfsStore {
+    fn get_range(&self, key: &str, offset: u64, length: Option<u64>) -> Result<Option<Vec<u8>>> {
+        self.read(key, offset, length)
+    }
+}
+
+impl ListableStore for WebHdfsStore {
+    /// Directories are listed by a `LISTSTATUS` request. Listing a directory
+    /// which does not exist fails with [`ErrorKind::NotFound`].
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let request = self.request("GET", prefix.trim_end_matches('/'), "LISTSTATUS")?;
+        let url = request.url().to_owned();
+        let response = check_response(&url, request.call())?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No directory {}", url)))?;
+        let listing: ListStatusResponse = serde_json::from_slice(&read_body(response)?)?;
+
+        let mut keys = vec![];
+        let mut prefixes = vec![];
+        for status in listing.file_statuses.file_status {
+            if status.file_type == "DIRECTORY" {
+                prefixes.push(format!("{}{}/", prefix, status.path_suffix));
+            } else {
+                keys.push(format!("{}{}", prefix, status.path_suffix));
+            }
+        }
+        Ok((keys, prefixes))
+    }
+}
+
+impl WriteableStore for WebHdfsStore {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<()>>(&self, key: &str, value: F) -> Result<()> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value = buffer.lock().unwrap();
+        self.put(key, &value)
+    }
+
+    fn erase(&self, key: &str) -> Result<bool> {
+        self.delete(key, false)
+    }
+
+    /// Directories are erased with their contents by a single recursive
+    /// `DELETE` request.
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool> {
+        self.delete(key_prefix.trim_end_matches('/'), true)
+    }
+
+    /// Values are written in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency).
+    fn put_many(&self, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        parallel_map(pairs, |(key, value)| self.put(key, value))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::{
+        BufRead,
+        BufReader,
+        Read,
+        Write,
+    };
+    use std::net::TcpListener;
+
+    use crate::prelude::*;
+
+    /// Serve a minimal WebHDFS name node and data node of files in memory
+    /// below `/webhdfs/v1`, accepting only requests of the user `alice`.
+    fn serve() -> String {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+            for stream in server.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:")
+                    {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap();
+                let (path, query) = parts.next().unwrap().split_once('?').unwrap();
+                let query: BTreeMap<&str, &str> = query
+                    .split('&')
+                    .filter_map(|parameter| parameter.split_once('='))
+                    .collect();
+                let path = path.trim_end_matches('/').to_owned();
+                let is_dir = |path: &str| {
+                    path == "/webhdfs/v1"
+                        || files.keys().any(|file| {
+                            file.starts_with(path) && file[path.len()..].starts_with('/')
+                        })
+                };
+                let status_json = |suffix: &str, value: Option<&Vec<u8>>| {
+                    format!(
+                        r#"{{"pathSuffix":"{}","length":{},"modificationTime":1600000000000,"type":"{}"}}"#,
+                        suffix,
+                        value.map_or(0, Vec::len),
+                        if value.is_some() { "FILE" } else { "DIRECTORY" }
+                    )
+                };
+
+                let (status, location, body) = if let Some(path) = path.strip_prefix("/datanode") {
+                    match method {
+                        "PUT" => {
+                            files.insert(path.to_owned(), body);
+                            ("201 Created", None, vec![])
+                        }
+                        _ => {
+                            let value = &files[path];
+                            let offset: usize =
+                                query.get("offset").map_or(0, |o| o.parse().unwrap());
+                            let end = query
+                                .get("length")
+                                .map_or(value.len(), |l| offset + l.parse::<usize>().unwrap());
+                            ("200 OK", None, value[offset..end].to_vec())
+                        }
+                    }
+                } else if query.get("user.name") != Some(&"alice") {
+                    ("401 Unauthorized", None, vec![])
+                } else {
+                    let redirect = || {
+                        Some(format!(
+                            "http://{}/datanode{}?{}",
+                            address,
+                            path,
+                            request_line
+                                .split_whitespace()
+                                .nth(1)
+                                .unwrap()
+                                .split_once('?')
+                                .unwrap()
+                                .1
+                        ))
+                    };
+                    match (method, query["op"]) {
+                        ("GET", "OPEN") if files.contains_key(&path) => {
+                            ("307 Temporary Redirect", redirect(), vec![])
+                        }
+                        ("PUT", "CREATE") => ("307 Temporary Redirect", redirect(), vec![]),
+                        ("GET", "GETFILESTATUS") if files.contains_key(&path) || is_dir(&path) => {
+                            let json = format!(
+                                r#"{{"FileStatus":{}}}"#,
+                                status_json("", files.get(&path))
+                            );
+                            ("200 OK", None, json.into_bytes())
+                        }
+                        ("GET", "LISTSTATUS") if is_dir(&path) => {
+                            let mut children = BTreeMap::new();
+                            for (file, value) in &files {
+                                if let Some(rest) = file.strip_prefix(&format!("{}/", path)) {
+                                    match rest.split_once('/') {
+                                        Some((dir, _)) => children.insert(dir.to_owned(), None),
+                                        None => children.insert(rest.to_owned(), Some(value)),
+                                    };
+                                }
+                            }
+                            let statuses: Vec<String> = children
+                                .iter()
+                                .map(|(name, value)| status_json(name, *value))
+                                .collect();
+                            let json = format!(
+                                r#"{{"FileStatuses":{{"FileStatus":[{}]}}}}"#,
+                                statuses.join(",")
+                            );
+                            ("200 OK", None, json.into_bytes())
+                        }
+                        ("DELETE", "DELETE") => {
+                            let recursive = query["recursive"] == "true";
+                            let deleted = files.remove(&path).is_some();
+                            if recursive {
+                                files.retain(|file, _| !file.starts_with(&format!("{}/", path)));
+                            }
+                            let json = format!(r#"{{"boolean":{}}}"#, deleted || recursive);
+                            ("200 OK", None, json.into_bytes())
+                        }
+                        _ => ("404 Not Found", None, vec![]),
+                    }
+                };
+                let stream = reader.get_mut();
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                if let Some(location) = location {
+                    write!(stream, "Location: {}\r\n", location).unwrap();
+                }
+                write!(stream, "Connection: close\r\n\r\n").unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        format!("http://{}/webhdfs/v1/a.zr3", address)
+    }
+
+    #[test]
+    fn test_webhdfs_store() {
+        let url = serve();
+        assert_eq!(
+            WebHdfsStore::open(&url).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        let options = WebHdfsOptions {
+            user: Some("alice".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            WebHdfsStore::open_with_options(&url, &options)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        let h = WebHdfsStore::open_or_create_with_options(&url, &options).unwrap();
+
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .build();
+        h.create_array("g/a", &array_meta).unwrap();
+        let chunk = SliceDataChunk::new(smallvec![1, 0], vec![1u8, 2, 3, 4]);
+        h.write_chunk("g/a", &array_meta, &chunk).unwrap();
+
+        let h = WebHdfsStore::open_with_options(&url, &options).unwrap();
+        let read: VecDataChunk<u8> = h
+            .read_chunk("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.get_data(), &[1, 2, 3, 4]);
+        let key = crate::storage::get_chunk_key("g/a", &array_meta, &[1, 0]);
+        assert_eq!(h.get_range(&key, 1, Some(2)).unwrap().unwrap(), vec![2, 3]);
+        let stat = h.stat(&key).unwrap().unwrap();
+        assert_eq!(stat.size, 4);
+        assert_eq!(
+            stat.last_modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+        );
+        assert_eq!(h.stat("/meta/root/g").unwrap(), None);
+        assert_eq!(h.list_nodes("g").unwrap(), vec!["a".to_owned()]);
+        assert_eq!(
+            h.list_dir("/meta/root/g/").unwrap(),
+            (vec!["/meta/root/g/a.array.json".to_owned()], vec![])
+        );
+        assert_eq!(
+            h.list_dir("/meta/").unwrap(),
+            (vec![], vec!["/meta/root/".to_owned()])
+        );
+        assert_eq!(
+            h.list_dir("/missing/").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        h.remove("g/a").unwrap();
+        assert!(!HierarchyReader::exists(&h, "g/a").unwrap());
+        assert!(h
+            .read_chunk::<u8>("g/a", &array_meta, smallvec![1, 0])
+            .unwrap()
+            .is_none());
+    }
+}
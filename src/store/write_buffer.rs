@@ -0,0 +1,458 @@
+//! A store wrapper buffering writes in memory until flushed.
+
+use std::collections::BTreeMap;
+use std::io::{
+    Cursor,
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use crate::storage::{
+    KeyStat,
+    ListableStore,
+    ReadableStore,
+    WriteableStore,
+};
+use crate::{
+    config,
+    EntryPointMetadata,
+    Hierarchy,
+};
+
+/// A store wrapper collecting written values in memory and writing them to
+/// the wrapped store in parallel when flushed.
+///
+/// Ingesting many small chunks into a high-latency store, such as an object
+/// store, is dominated by the latency of each request. Buffering writes and
+/// issuing them from `concurrency` threads at once on
+/// [`flush`](WriteBufferStore::flush) hides most of that latency. The buffer
+/// is flushed automatically once it holds more than its capacity in bytes,
+/// and when the wrapper is dropped, in which case errors are ignored, so
+/// `flush` should be called to find out whether all writes succeeded.
+///
+/// Reads, listings and sizes through the wrapper include buffered values.
+/// Erasing a key or prefix drops buffered values and is passed to the
+/// wrapped store immediately.
+///
+/// ```
+/// use zarr::prelude::*;
+/// use zarr::smallvec::smallvec;
+/// use zarr::store::write_buffer::WriteBufferStore;
+///
+/// let dir = tempdir::TempDir::new("zarr").unwrap();
+/// let h = WriteBufferStore::new(FilesystemHierarchy::open_or_create(dir.path()).unwrap(), 1 << 26);
+/// let array_meta = ArrayMetadataBuilder::new(smallvec![100, 100], u8::ZARR_TYPE)
+///     .chunk_shape(smallvec![10, 10])
+///     .build();
+/// h.create_array("raw", &array_meta).unwrap();
+/// for coord in array_meta.coord_iter() {
+///     h.write_chunk("raw", &array_meta, &SliceDataChunk::new(coord.into(), vec![1u8; 100]))
+///         .unwrap();
+/// }
+/// h.flush().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct WriteBufferStore<S: WriteableStore + Sync> {
+    store: S,
+    capacity: usize,
+    state: Mutex<State>,
+    /// Held while flushing, so erasures are not overtaken by buffered writes.
+    flushing: Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    values: BTreeMap<String, Arc<[u8]>>,
+    bytes: usize,
+}
+
+impl State {
+    fn insert(&mut self, key: String, value: Arc<[u8]>) {
+        self.bytes += value.len();
+        if let Some(old) = self.values.insert(key, value) {
+            self.bytes -= old.len();
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(old) = self.values.remove(key) {
+            self.bytes -= old.len();
+        }
+    }
+
+    fn keys_with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a String> {
+        self.values
+            .range(prefix.to_owned()..)
+            .map(|(key, _)| key)
+            .take_while(move |key| key.starts_with(prefix))
+    }
+}
+
+impl<S: WriteableStore + Sync> WriteBufferStore<S> {
+    /// Wrap a store, buffering up to `capacity` bytes of values before
+    /// flushing automatically.
+    pub fn new(store: S, capacity: usize) -> Self {
+        WriteBufferStore {
+            store,
+            capacity,
+            state: Mutex::new(State::default()),
+            flushing: Mutex::new(()),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    /// Number of bytes of values currently buffered.
+    pub fn buffered_bytes(&self) -> usize {
+        self.state.lock().unwrap().bytes
+    }
+
+    /// Write all buffered values to the wrapped store.
+    ///
+    /// Values are written in parallel using the configured
+    /// [`concurrency`](crate::config::Config::concurrency). Values which
+    /// fail to be written stay buffered, and the first error is returned.
+    pub fn flush(&self) -> Result<(), Error> {
+        let _flushing = self.flushing.lock().unwrap();
+        let entries: Vec<(String, Arc<[u8]>)> = self
+            .state
+            .lock()
+            .unwrap()
+            .values
+            .iter()
+            .map(|(key, value)| (key.clone(), Arc::clone(value)))
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let workers = std::cmp::min(config::config().concurrency.max(1), entries.len());
+        let queue = Mutex::new(entries.into_iter());
+        let first_error: Mutex<Option<Error>> = Mutex::new(None);
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap().next();
+                    let (key, value) = match next {
+                        Some(entry) => entry,
+                        None => break,
+                    };
+                    match self.store.set(&key, |mut writer| writer.write_all(&value)) {
+                        Ok(()) => {
+                            let mut state = self.state.lock().unwrap();
+                            // Only drop the value if it was not replaced meanwhile.
+                            if state
+                                .values
+                                .get(&key)
+                                .is_some_and(|buffered| Arc::ptr_eq(buffered, &value))
+                            {
+                                state.remove(&key);
+                            }
+                        }
+                        Err(e) => {
+                            first_error.lock().unwrap().get_or_insert(e);
+                        }
+                    }
+                });
+            }
+        });
+
+        match first_error.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: WriteableStore + Sync> Drop for WriteBufferStore<S> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<S: Hierarchy + WriteableStore + Sync> Hierarchy for WriteBufferStore<S> {
+    fn get_entry_point_metadata(&self) -> &EntryPointMetadata {
+        self.store.get_entry_point_metadata()
+    }
+
+    fn array_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.array_metadata_key(path_name)
+    }
+
+    fn group_metadata_key(&self, path_name: &str) -> PathBuf {
+        self.store.group_metadata_key(path_name)
+    }
+
+    fn data_path_key(&self, path_name: &str) -> PathBuf {
+        self.store.data_path_key(path_name)
+    }
+}
+
+/// Reader of a value which is either buffered or in the wrapped store.
+#[derive(Debug)]
+pub enum WriteBufferReader<R> {
+    Buffered(Cursor<Arc<[u8]>>),
+    Store(R),
+}
+
+impl<R: Read> Read for WriteBufferReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self {
+            WriteBufferReader::Buffered(reader) => reader.read(buf),
+            WriteBufferReader::Store(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<S: ReadableStore + WriteableStore + Sync> ReadableStore for WriteBufferStore<S> {
+    type GetReader = WriteBufferReader<S::GetReader>;
+
+    fn exists(&self, key: &str) -> Result<bool, Error> {
+        if self.state.lock().unwrap().values.contains_key(key) {
+            return Ok(true);
+        }
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Self::GetReader>, Error> {
+        if let Some(value) = self.state.lock().unwrap().values.get(key) {
+            return Ok(Some(WriteBufferReader::Buffered(Cursor::new(Arc::clone(
+                value,
+            )))));
+        }
+        Ok(self.store.get(key)?.map(WriteBufferReader::Store))
+    }
+
+    fn uri(&self, key: &str) -> Result<String, Error> {
+        self.store.uri(key)
+    }
+
+    fn size(&self, key: &str) -> Result<Option<u64>, Error> {
+        if let Some(value) = self.state.lock().unwrap().values.get(key) {
+            return Ok(Some(value.len() as u64));
+        }
+        self.store.size(key)
+    }
+
+    fn stat(&self, key: &str) -> Result<Option<KeyStat>, Error> {
+        if let Some(value) = self.state.lock().unwrap().values.get(key) {
+            return Ok(Some(KeyStat::new(value.len() as u64)));
+        }
+        self.store.stat(key)
+    }
+
+    fn is_read_only(&self, key: &str) -> Result<bool, Error> {
+        self.store.is_read_only(key)
+    }
+
+    /// Values which are not buffered are read with a single `get_many` of
+    /// the wrapped store.
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut unbuffered = vec![];
+        {
+            let state = self.state.lock().unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                let value = state.values.get(key).map(|value| value.to_vec());
+                if value.is_none() {
+                    unbuffered.push(i);
+                }
+                values.push(value);
+            }
+        }
+        let unbuffered_keys: Vec<String> = unbuffered.iter().map(|&i| keys[i].clone()).collect();
+        for (i, value) in unbuffered
+            .into_iter()
+            .zip(self.store.get_many(&unbuffered_keys)?)
+        {
+            values[i] = value;
+        }
+        Ok(values)
+    }
+}
+
+/// Treat a missing prefix of the wrapped store as empty, since its only keys
+/// may still be buffered.
+fn or_empty<T: Default>(result: Result<T, Error>) -> Result<T, Error> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+        result => result,
+    }
+}
+
+impl<S: ListableStore + WriteableStore + Sync> ListableStore for WriteBufferStore<S> {
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.list_prefix("")
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut keys = or_empty(self.store.list_prefix(prefix))?;
+        keys.extend(self.state.lock().unwrap().keys_with_prefix(prefix).cloned());
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        let (mut keys, mut prefixes) = or_empty(self.store.list_dir(prefix))?;
+        for key in self.state.lock().unwrap().keys_with_prefix(prefix) {
+            match key[prefix.len()..].find('/') {
+                Some(i) => prefixes.push(key[..=prefix.len() + i].to_owned()),
+                None => keys.push(key.clone()),
+            }
+        }
+        for list in [&mut keys, &mut prefixes].iter_mut() {
+            list.sort();
+            list.dedup();
+        }
+        Ok((keys, prefixes))
+    }
+}
+
+/// Writer collecting a value in memory.
+#[derive(Debug, Default)]
+pub struct BufferWriter {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl BufferWriter {
+    /// Writer appending to a buffer shared with the caller, for stores which
+    /// need a whole value once it is written.
+    pub(crate) fn new(buffer: Arc<Mutex<Vec<u8>>>) -> Self {
+        BufferWriter { buffer }
+    }
+}
+
+impl Write for BufferWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<S: WriteableStore + Sync> WriteableStore for WriteBufferStore<S> {
+    type SetWriter = BufferWriter;
+
+    fn set<F: FnOnce(Self::SetWriter) -> Result<(), Error>>(
+        &self,
+        key: &str,
+        value: F,
+    ) -> Result<(), Error> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        value(BufferWriter::new(Arc::clone(&buffer)))?;
+        let value: Arc<[u8]> = std::mem::take(&mut *buffer.lock().unwrap()).into();
+
+        let full = {
+            let mut state = self.state.lock().unwrap();
+            state.insert(key.to_owned(), value);
+            state.bytes > self.capacity
+        };
+        if full {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn erase(&self, key: &str) -> Result<bool, Error> {
+        let _flushing = self.flushing.lock().unwrap();
+        self.state.lock().unwrap().remove(key);
+        self.store.erase(key)
+    }
+
+    fn erase_prefix(&self, key_prefix: &str) -> Result<bool, Error> {
+        let _flushing = self.flushing.lock().unwrap();
+        {
+            let mut state = self.state.lock().unwrap();
+            let keys: Vec<String> = state.keys_with_prefix(key_prefix).cloned().collect();
+            for key in keys {
+                state.remove(&key);
+            }
+        }
+        self.store.erase_prefix(key_prefix)
+    }
+
+    /// Buffered values are flushed first, so that they are made read-only
+    /// too.
+    fn make_read_only(&self, key_prefix: &str) -> Result<bool, Error> {
+        self.flush()?;
+        self.store.make_read_only(key_prefix)
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::storage::get_chunk_key;
+
+    #[test]
+    fn test_write_buffer() {
+        let dir = tempdir::TempDir::new("rust_zarr_write_buffer_tests").unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(crate::compression::CompressionType::Raw(Default::default()))
+            .build();
+        let h = WriteBufferStore::new(
+            FilesystemHierarchy::open_or_create(dir.path()).unwrap(),
+            1 << 20,
+        );
+        h.create_array("a", &array_meta).unwrap();
+        for coord in array_meta.coord_iter() {
+            let data = vec![coord[0] as u8 * 2 + coord[1] as u8; 4];
+            h.write_chunk("a", &array_meta, &SliceDataChunk::new(coord.into(), data))
+                .unwrap();
+        }
+
+        let key = get_chunk_key("a", &array_meta, &[1, 1]);
+        assert!(!ReadableStore::exists(h.get_ref(), &key).unwrap());
+        assert!(ReadableStore::exists(&h, &key).unwrap());
+        assert_eq!(h.size(&key).unwrap(), Some(4));
+        assert_eq!(h.list_prefix("/data/root/a/").unwrap().len(), 4);
+        let chunk = h
+            .read_chunk::<u8>("a", &array_meta, smallvec![1, 1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.get_data(), &[3, 3, 3, 3]);
+
+        h.erase(&get_chunk_key("a", &array_meta, &[0, 0])).unwrap();
+        h.flush().unwrap();
+        assert_eq!(h.buffered_bytes(), 0);
+        assert!(ReadableStore::exists(h.get_ref(), &key).unwrap());
+        assert!(h.get_ref().array_exists("a").unwrap());
+        assert_eq!(h.get_ref().list_prefix("/data/root/a/").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_write_buffer_capacity() {
+        let dir = tempdir::TempDir::new("rust_zarr_write_buffer_tests").unwrap();
+        let h = WriteBufferStore::new(FilesystemHierarchy::open_or_create(dir.path()).unwrap(), 8);
+        h.set("x/a", |mut w| w.write_all(&[0; 5])).unwrap();
+        assert_eq!(h.buffered_bytes(), 5);
+        assert!(!ReadableStore::exists(h.get_ref(), "x/a").unwrap());
+        let (keys, prefixes) = h.list_dir("").unwrap();
+        assert!(!keys.contains(&"x/a".to_owned()));
+        assert!(prefixes.contains(&"x/".to_owned()));
+
+        h.set("x/b", |mut w| w.write_all(&[0; 5])).unwrap();
+        assert_eq!(h.buffered_bytes(), 0);
+        assert!(ReadableStore::exists(h.get_ref(), "x/a").unwrap());
+        assert!(ReadableStore::exists(h.get_ref(), "x/b").unwrap());
+
+        h.set("x/c", |mut w| w.write_all(&[0; 1])).unwrap();
+        drop(h);
+        assert!(dir.path().join("x/c").exists());
+    }
+}
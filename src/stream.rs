@@ -0,0 +1,451 @@
+//! Streaming whole arrays to and from raw element streams.
+//!
+//! Streams hold the elements of an array or region in C order, encoded in
+//! the byte order of the array's data type, without any header unless
+//! exported in the [NPY format](ExportFormat::Npy). Chunks are read and
+//! written a slab at a time, where a slab is one chunk-row along the first
+//! axis, so memory use is bounded by the size of a slab rather than the size
+//! of the array.
+//!
+//! ```no_run
+//! use zarr::prelude::*;
+//! use zarr::smallvec::smallvec;
+//! use zarr::stream::ZarrStreamWriter;
+//!
+//! let h = FilesystemHierarchy::open_or_create("/tmp/imported.zr3").unwrap();
+//! let array_meta = ArrayMetadataBuilder::new(smallvec![1000, 2000], u16::ZARR_TYPE)
+//!     .chunk_shape(smallvec![100, 100])
+//!     .build();
+//! h.create_array("raw", &array_meta).unwrap();
+//! h.import_stream("raw", &array_meta, std::io::stdin().lock()).unwrap();
+//! ```
+//!
+//! Imports can check elements against a declared valid range, to catch
+//! errors such as wrong units when converting instrument data, and count
+//! them in a [`Histogram`], with
+//! [`import_stream_checked`](ZarrStreamWriter::import_stream_checked).
+
+use std::convert::TryFrom;
+use std::io::{
+    Error,
+    ErrorKind,
+    Read,
+    Write,
+};
+
+use half::f16;
+use ndarray::ArrayView;
+
+use crate::cast::CastElement;
+use crate::ndarray::{
+    BoundingBox,
+    ZarrNdarrayReader,
+    ZarrNdarrayWriter,
+};
+use crate::{
+    ArrayMetadata,
+    DataChunk,
+    DataType,
+    Endian,
+    FloatSize,
+    GridCoord,
+    IntSize,
+    ReadableDataChunk,
+    ReflectedType,
+    ReinitDataChunk,
+    SliceDataChunk,
+    VecDataChunk,
+    WriteableDataChunk,
+};
+
+fn raw_type_error() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "Arrays of raw data types can not be streamed",
+    )
+}
+
+/// Iterate the slabs of a region, aligned to chunk boundaries along the
+/// first axis. A 0-dimensional region is a single slab.
+fn slabs(array_meta: &ArrayMetadata, bbox: &BoundingBox) -> impl Iterator<Item = BoundingBox> {
+    let bbox = bbox.clone();
+    let chunk_rows = array_meta
+        .get_chunk_shape()
+        .first()
+        .map_or(1, |&c| u64::from(c));
+    let (start, end) = match (bbox.offset().first(), bbox.end().next()) {
+        (Some(&start), Some(end)) => (start, end),
+        _ => (0, 1),
+    };
+    let mut next = start;
+    std::iter::from_fn(move || {
+        if next >= end {
+            return None;
+        }
+        let slab_start = next;
+        next = std::cmp::min((slab_start / chunk_rows + 1) * chunk_rows, end);
+        if bbox.offset().is_empty() {
+            return Some(bbox.clone());
+        }
+
+        let mut offset = GridCoord::from(bbox.offset());
+        offset[0] = slab_start;
+        let mut shape = GridCoord::from(bbox.shape());
+        shape[0] = next - slab_start;
+        Some(BoundingBox::new(offset, shape))
+    })
+}
+
+/// Format of an exported stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Elements only.
+    Raw,
+    /// Elements preceded by a NumPy `.npy` version 1.0 header, so the stream
+    /// can be loaded with `numpy.load`.
+    Npy,
+}
+
+/// NumPy type descriptor of a data type.
+fn npy_descr(data_type: &DataType) -> Result<String, Error> {
+    let (kind, endian) = match data_type {
+        DataType::Bool => return Ok("|b1".to_owned()),
+        DataType::Int { endian, .. } => ('i', endian),
+        DataType::UInt { endian, .. } => ('u', endian),
+        DataType::Float { endian, .. } => ('f', endian),
+        DataType::Raw { .. } => return Err(raw_type_error()),
+    };
+    let size = data_type.size_of();
+    let order = match endian {
+        _ if size == 1 => '|',
+        Endian::Big => '>',
+        Endian::Little => '<',
+    };
+    Ok(format!("{}{}{}", order, kind, size))
+}
+
+/// Write a NumPy `.npy` version 1.0 header for a C-order array.
+fn write_npy_header<W: Write>(
+    writer: &mut W,
+    data_type: &DataType,
+    shape: &[u64],
+) -> Result<(), Error> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    let shape = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        npy_descr(data_type)?,
+        shape
+    );
+    // Pad with spaces and a newline so the data is 64-byte aligned.
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.extend(std::iter::repeat_n(' ', (64 - unpadded % 64) % 64));
+    header.push('\n');
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "NPY header is too long"))?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())
+}
+
+/// Counts of elements in equal-width bins over a range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    /// Counts of elements in each bin, from `min` to `max` inclusive.
+    pub counts: Vec<u64>,
+    /// Number of elements less than `min`.
+    pub below: u64,
+    /// Number of elements greater than `max`.
+    pub above: u64,
+    /// Number of NaN elements.
+    pub nan: u64,
+}
+
+impl Histogram {
+    /// An empty histogram with a number of bins over a range.
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        Histogram {
+            min,
+            max,
+            counts: vec![0; bins],
+            below: 0,
+            above: 0,
+            nan: 0,
+        }
+    }
+
+    /// Count an element.
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            self.nan += 1;
+        } else if value < self.min {
+            self.below += 1;
+        } else if value > self.max {
+            self.above += 1;
+        } else if !self.counts.is_empty() {
+            let bins = self.counts.len();
+            let bin = if self.max > self.min {
+                ((value - self.min) / (self.max - self.min) * bins as f64) as usize
+            } else {
+                0
+            };
+            self.counts[bin.min(bins - 1)] += 1;
+        }
+    }
+}
+
+/// Checks of elements as they are imported.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportChecks {
+    /// Declared range of valid elements, inclusive. NaN is never valid.
+    pub valid_range: Option<(f64, f64)>,
+    /// Whether elements outside the valid range fail the import, rather than
+    /// only being counted in the report.
+    pub reject_out_of_range: bool,
+    /// Histogram to count imported elements in.
+    pub histogram: Option<Histogram>,
+}
+
+/// Outcome of the checks of an import.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    /// Number of elements outside the valid range.
+    pub out_of_range: u64,
+    /// Coordinates of the first element outside the valid range.
+    pub first_out_of_range: Option<GridCoord>,
+    /// The histogram of the checks, counting every imported element.
+    pub histogram: Option<Histogram>,
+}
+
+/// Coordinates of the element at an index in C order of a region.
+fn c_order_coord(bbox: &BoundingBox, mut index: u64) -> GridCoord {
+    let mut coord = GridCoord::from(bbox.offset());
+    for (c, &n) in coord.iter_mut().zip(bbox.shape()).rev() {
+        *c += index % n;
+        index /= n;
+    }
+    coord
+}
+
+/// Write the slabs of an array from a stream, checking the elements of
+/// each slab before it is written.
+fn import_slabs<H, T, R>(
+    h: &H,
+    path_name: &str,
+    array_meta: &ArrayMetadata,
+    mut reader: R,
+    mut check: impl FnMut(&BoundingBox, &[T]) -> Result<(), Error>,
+) -> Result<(), Error>
+where
+    H: ZarrNdarrayWriter + ?Sized,
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+    T: ReflectedType,
+    R: Read,
+{
+    let mut slab = VecDataChunk::<T>::new(GridCoord::new(), vec![]);
+
+    for slab_bbox in slabs(array_meta, &array_meta.get_bounds()) {
+        let slab_shape = slab_bbox.shape_ndarray_shape();
+        let mut data = slab.into_data();
+        data.resize(slab_shape.iter().product(), T::default());
+        slab = SliceDataChunk::new(GridCoord::new(), data);
+        slab.read_data(&mut reader, array_meta)?;
+        check(&slab_bbox, slab.get_data())?;
+
+        let view = ArrayView::from_shape(&slab_shape[..], slab.get_data())
+            .expect("Slab length matches its shape");
+        h.write_ndarray(path_name, array_meta, slab_bbox.offset().into(), view)?;
+    }
+
+    Ok(())
+}
+
+pub trait ZarrStreamWriter: ZarrNdarrayWriter {
+    /// Write every element of an array from a stream of its elements in C
+    /// order, encoded in the byte order of the array's data type.
+    ///
+    /// Exactly as many elements as the array holds are consumed from the
+    /// reader. A stream that ends early is an error, leaving the chunks of
+    /// the slabs already read written.
+    fn import_stream<R: Read>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        reader: R,
+    ) -> Result<(), Error> {
+        data_type_match!(
+            array_meta.get_data_type().effective_type()?,
+            DataType::Raw { .. } => Err(raw_type_error()),
+            self.import_stream_as::<RsType, R>(path_name, array_meta, reader)
+        )
+    }
+
+    /// Write every element of an array from a stream of elements of a known
+    /// type. See [`import_stream`](ZarrStreamWriter::import_stream).
+    fn import_stream_as<T, R: Read>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        reader: R,
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+        T: ReflectedType,
+    {
+        import_slabs::<_, T, R>(self, path_name, array_meta, reader, |_, _| Ok(()))
+    }
+
+    /// Write every element of an array from a stream, checking elements as
+    /// they are imported. See [`import_stream`](ZarrStreamWriter::import_stream).
+    ///
+    /// If out-of-range elements are rejected, the import fails with
+    /// [`ErrorKind::InvalidData`] before writing the slab holding the first,
+    /// leaving the slabs before it written.
+    fn import_stream_checked<R: Read>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        reader: R,
+        checks: ImportChecks,
+    ) -> Result<ImportReport, Error> {
+        data_type_match!(
+            array_meta.get_data_type().effective_type()?,
+            DataType::Raw { .. } => Err(raw_type_error()),
+            self.import_stream_checked_as::<RsType, R>(path_name, array_meta, reader, checks)
+        )
+    }
+
+    /// Write every element of an array from a stream of elements of a known
+    /// type, checking elements as they are imported. See
+    /// [`import_stream_checked`](ZarrStreamWriter::import_stream_checked).
+    fn import_stream_checked_as<T, R: Read>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        reader: R,
+        checks: ImportChecks,
+    ) -> Result<ImportReport, Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+        T: CastElement,
+    {
+        let ImportChecks {
+            valid_range,
+            reject_out_of_range,
+            histogram,
+        } = checks;
+        let mut report = ImportReport {
+            histogram,
+            ..ImportReport::default()
+        };
+        import_slabs::<_, T, R>(self, path_name, array_meta, reader, |slab_bbox, data| {
+            for (i, element) in data.iter().enumerate() {
+                let value = element.to_f64();
+                if let Some(histogram) = &mut report.histogram {
+                    histogram.add(value);
+                }
+                let (min, max) = match valid_range {
+                    Some(range) => range,
+                    None => continue,
+                };
+                if value >= min && value <= max {
+                    continue;
+                }
+                let coord = c_order_coord(slab_bbox, i as u64);
+                if reject_out_of_range {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "Element {} at {:?} is outside the valid range [{}, {}]",
+                            value, coord, min, max
+                        ),
+                    ));
+                }
+                report.out_of_range += 1;
+                report.first_out_of_range.get_or_insert(coord);
+            }
+            Ok(())
+        })?;
+        Ok(report)
+    }
+}
+
+impl<T: ZarrNdarrayWriter> ZarrStreamWriter for T {}
+
+pub trait ZarrStreamReader: ZarrNdarrayReader {
+    /// Write the elements of a region of an array to a stream in C order,
+    /// encoded in the byte order of the array's data type, reading chunks as
+    /// the stream is written.
+    ///
+    /// The writer is not flushed.
+    fn export_region<W: Write>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<(), Error> {
+        data_type_match!(
+            array_meta.get_data_type().effective_type()?,
+            DataType::Raw { .. } => Err(raw_type_error()),
+            self.export_region_as::<RsType, W>(path_name, array_meta, bbox, format, writer)
+        )
+    }
+
+    /// Write the elements of a region of an array of a known type to a
+    /// stream. See [`export_region`](ZarrStreamReader::export_region).
+    fn export_region_as<T, W: Write>(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+        bbox: &BoundingBox,
+        format: ExportFormat,
+        mut writer: W,
+    ) -> Result<(), Error>
+    where
+        VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+        T: ReflectedType,
+    {
+        if bbox.offset().len() != array_meta.get_ndim() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Wrong number of dimensions",
+            ));
+        }
+        if format == ExportFormat::Npy {
+            let data_type = array_meta.get_data_type().effective_type()?;
+            write_npy_header(&mut writer, &data_type, bbox.shape())?;
+        }
+
+        let mut slab = VecDataChunk::<T>::new(GridCoord::new(), vec![]);
+        for slab_bbox in slabs(array_meta, bbox) {
+            let array = self.read_ndarray::<T>(path_name, array_meta, &slab_bbox)?;
+            let mut data = slab.into_data();
+            data.clear();
+            match array.as_slice() {
+                Some(s) => data.extend_from_slice(s),
+                None => data.extend(array.iter().cloned()),
+            }
+            slab = SliceDataChunk::new(GridCoord::new(), data);
+            slab.write_data(&mut writer, array_meta)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: ZarrNdarrayReader> ZarrStreamReader for T {}
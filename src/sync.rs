@@ -0,0 +1,283 @@
+//! Incremental copying of values between stores.
+//!
+//! Values are compared by their [stats](crate::storage::ReadableStore::stat)
+//! rather than their contents, so repeating a sync of a mostly unchanged
+//! hierarchy, for example to keep a local copy of a remote hierarchy
+//! current, reads only the values which changed. Values of stores whose
+//! stats cannot tell whether they changed can instead be compared by digest
+//! with [`sync_prefix_by_digest`].
+//!
+//! ```
+//! use zarr::prelude::*;
+//! use zarr::sync::sync_prefix;
+//!
+//! let source = FilesystemHierarchy::open("tests/data/zarrita.zr3").unwrap();
+//! let dir = tempdir::TempDir::new("zarr").unwrap();
+//! let target = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+//! sync_prefix(&source, &target, "/").unwrap();
+//! assert_eq!(sync_prefix(&source, &target, "/").unwrap().copied, 0);
+//! ```
+
+use std::io::{
+    Error,
+    Write,
+};
+
+use crate::hash::Hasher;
+use crate::storage::{
+    read_value,
+    KeyStat,
+    ListableStore,
+    ReadableStore,
+    WriteableStore,
+};
+
+/// Outcome of a [`sync_prefix`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    /// Number of values copied.
+    pub copied: u64,
+    /// Number of values skipped as unchanged.
+    pub unchanged: u64,
+    /// Total size of the values copied in bytes.
+    pub copied_bytes: u64,
+}
+
+/// Whether a value must be copied over its copy in the target, if any, or
+/// `None` if their stats cannot tell.
+///
+/// Copies are newer than their source, so values which the target has with
+/// the same size and a later modification time are unchanged. Stores
+/// without modification times are compared by entity tag, and values of
+/// stores with neither cannot be compared.
+fn compare_stats(source: &KeyStat, target: Option<&KeyStat>) -> Option<bool> {
+    let target = match target {
+        Some(target) if target.size == source.size => target,
+        _ => return Some(true),
+    };
+    match (source.last_modified, target.last_modified) {
+        (Some(source), Some(target)) => Some(source > target),
+        _ => match (&source.etag, &target.etag) {
+            (Some(source), Some(target)) => Some(source != target),
+            _ => None,
+        },
+    }
+}
+
+/// Copy the values with keys below a prefix from one store to another,
+/// skipping values unchanged since a previous sync.
+///
+/// Values in the target which are not in the source are kept.
+pub fn sync_prefix<S, T>(source: &S, target: &T, prefix: &str) -> Result<SyncSummary, Error>
+where
+    S: ReadableStore + ListableStore,
+    T: ReadableStore + WriteableStore,
+{
+    sync(source, target, prefix, None)
+}
+
+/// Like [`sync_prefix`], but values whose stats cannot tell whether they
+/// changed, such as those of stores with neither modification times nor
+/// entity tags, are compared by their digests rather than always copied.
+///
+/// Such values are read from both stores, which pays off for targets which
+/// are slower or costlier to write than to read. Callers choose the hasher,
+/// for example that of the configured
+/// [`hash_algorithm`](crate::config::Config::hash_algorithm).
+pub fn sync_prefix_by_digest<S, T>(
+    source: &S,
+    target: &T,
+    prefix: &str,
+    hasher: &dyn Hasher,
+) -> Result<SyncSummary, Error>
+where
+    S: ReadableStore + ListableStore,
+    T: ReadableStore + WriteableStore,
+{
+    sync(source, target, prefix, Some(hasher))
+}
+
+fn sync<S, T>(
+    source: &S,
+    target: &T,
+    prefix: &str,
+    hasher: Option<&dyn Hasher>,
+) -> Result<SyncSummary, Error>
+where
+    S: ReadableStore + ListableStore,
+    T: ReadableStore + WriteableStore,
+{
+    let mut summary = SyncSummary::default();
+    for key in source.list_prefix(prefix)? {
+        // Keys removed from the source since it was listed are skipped.
+        let stat = match source.stat(&key)? {
+            Some(stat) => stat,
+            None => continue,
+        };
+        let target_stat = target.stat(&key)?;
+        let hasher = match (compare_stats(&stat, target_stat.as_ref()), hasher) {
+            (Some(false), _) => {
+                summary.unchanged += 1;
+                continue;
+            }
+            (None, Some(hasher)) => hasher,
+            _ => {
+                let mut reader = match source.get(&key)? {
+                    Some(reader) => reader,
+                    None => continue,
+                };
+                target.set(&key, |mut writer| {
+                    summary.copied_bytes += std::io::copy(&mut reader, &mut writer)?;
+                    writer.flush()
+                })?;
+                summary.copied += 1;
+                continue;
+            }
+        };
+
+        let value = match read_value(source, &key)? {
+            Some(value) => value,
+            None => continue,
+        };
+        let unchanged = read_value(target, &key)?
+            .is_some_and(|copy| hasher.digest(&copy) == hasher.digest(&value));
+        if unchanged {
+            summary.unchanged += 1;
+            continue;
+        }
+        target.set(&key, |mut writer| {
+            writer.write_all(&value)?;
+            writer.flush()
+        })?;
+        summary.copied += 1;
+        summary.copied_bytes += value.len() as u64;
+    }
+    Ok(summary)
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use std::time::{
+        Duration,
+        UNIX_EPOCH,
+    };
+
+    #[test]
+    fn test_compare_stats() {
+        let at = |secs| KeyStat {
+            size: 4,
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(secs)),
+            etag: None,
+        };
+        assert_eq!(compare_stats(&at(1), None), Some(true));
+        assert_eq!(compare_stats(&at(2), Some(&at(1))), Some(true));
+        assert_eq!(compare_stats(&at(1), Some(&at(2))), Some(false));
+        assert_eq!(compare_stats(&at(1), Some(&KeyStat::new(5))), Some(true));
+        assert_eq!(
+            compare_stats(&KeyStat::new(4), Some(&KeyStat::new(4))),
+            None
+        );
+        let tagged = |etag: &str| KeyStat {
+            etag: Some(etag.to_owned()),
+            ..KeyStat::new(4)
+        };
+        assert_eq!(compare_stats(&tagged("a"), Some(&tagged("a"))), Some(false));
+        assert_eq!(compare_stats(&tagged("a"), Some(&tagged("b"))), Some(true));
+    }
+
+    #[test]
+    fn test_sync_prefix() {
+        let dir = tempdir::TempDir::new("rust_zarr_sync_tests").unwrap();
+        let source = FilesystemHierarchy::open_or_create(dir.path().join("source")).unwrap();
+        let target = FilesystemHierarchy::open_or_create(dir.path().join("target")).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![2])
+            .build();
+        source.create_array("a", &array_meta).unwrap();
+        for i in 0..2 {
+            source
+                .write_chunk(
+                    "a",
+                    &array_meta,
+                    &SliceDataChunk::new(smallvec![i], vec![1u8, 2]),
+                )
+                .unwrap();
+        }
+
+        let summary = sync_prefix(&source, &target, "/data/").unwrap();
+        assert_eq!(
+            summary,
+            SyncSummary {
+                copied: 2,
+                unchanged: 0,
+                copied_bytes: 4,
+            }
+        );
+        sync_prefix(&source, &target, "/meta/").unwrap();
+        assert_eq!(
+            target
+                .read_chunk::<u8>("a", &array_meta, smallvec![1])
+                .unwrap()
+                .unwrap()
+                .get_data(),
+            &[1, 2]
+        );
+
+        // Only the chunk which grew is copied again.
+        let array_meta = ArrayMetadataBuilder::new(smallvec![6], u8::ZARR_TYPE)
+            .chunk_shape(smallvec![3])
+            .build();
+        source
+            .write_chunk(
+                "a",
+                &array_meta,
+                &SliceDataChunk::new(smallvec![1], vec![3u8, 4, 5]),
+            )
+            .unwrap();
+        let summary = sync_prefix(&source, &target, "/data/").unwrap();
+        assert_eq!((summary.copied, summary.unchanged), (1, 1));
+    }
+
+    #[test]
+    fn test_sync_prefix_by_digest() {
+        use crate::hash::HashAlgorithm;
+        use crate::store::write_buffer::WriteBufferStore;
+
+        let dir = tempdir::TempDir::new("rust_zarr_sync_tests").unwrap();
+        let source = FilesystemHierarchy::open_or_create(dir.path().join("source")).unwrap();
+        // Buffered values have no modification time or entity tag.
+        let target = WriteBufferStore::new(
+            FilesystemHierarchy::open_or_create(dir.path().join("target")).unwrap(),
+            1 << 20,
+        );
+        for key in ["/a", "/b"].iter() {
+            source
+                .set(key, |mut writer| writer.write_all(b"ab"))
+                .unwrap();
+        }
+        let hasher = HashAlgorithm::Fnv1a.hasher();
+        assert_eq!(
+            sync_prefix_by_digest(&source, &target, "/", hasher)
+                .unwrap()
+                .copied,
+            2
+        );
+        assert_eq!(sync_prefix(&source, &target, "/").unwrap().copied, 2);
+
+        source
+            .set("/b", |mut writer| writer.write_all(b"cd"))
+            .unwrap();
+        let summary = sync_prefix_by_digest(&source, &target, "/", hasher).unwrap();
+        assert_eq!(
+            summary,
+            SyncSummary {
+                copied: 1,
+                unchanged: 2,
+                copied_bytes: 2,
+            }
+        );
+        assert_eq!(read_value(&target, "/b").unwrap(), Some(b"cd".to_vec()));
+    }
+}
@@ -6,6 +6,7 @@ use super::chunk::{
 use super::*;
 use std::io::{
     Cursor,
+    ErrorKind,
     Result,
 };
 
@@ -53,9 +54,16 @@ fn array_metadata_deserialization() {
             grid_type: REGULAR_GRID_TYPE.into(),
             chunk_shape: smallvec![1000, 100],
             separator: "/".into(),
+            extra_fields: JsonObject::new(),
         },
         chunk_memory_layout: Order::RowMajor,
-        compressor: crate::compression::gzip::GzipCompression { level: 1 }.into(),
+        compressor: crate::compression::gzip::GzipCompression {
+            level: 1,
+            ..Default::default()
+        }
+        .into(),
+        filters: vec![],
+        read_only: false,
         fill_value: Some(json!("NaN")),
         extensions: vec![],
         attributes: vec![
@@ -65,9 +73,14 @@ fn array_metadata_deserialization() {
         ]
         .into_iter()
         .collect(),
+        extra_fields: JsonObject::new(),
     };
 
     assert_eq!(deserialized, expected);
+    assert!(deserialized
+        .get_effective_fill_value::<f64>()
+        .unwrap()
+        .is_nan());
 
     let example_json = r#"
         {
@@ -94,6 +107,106 @@ fn array_metadata_deserialization() {
     assert_eq!(deserialized, expected);
 }
 
+#[test]
+fn metadata_unknown_fields_round_trip() {
+    let example_json = json!({
+        "shape": [10, 10],
+        "data_type": "<i4",
+        "chunk_grid": {
+            "type": "regular",
+            "chunk_shape": [5, 5],
+            "separator": "/",
+            "grid_foo": "bar",
+        },
+        "chunk_memory_layout": "C",
+        "fill_value": null,
+        "extensions": [],
+        "attributes": {},
+        "foo": {"bar": [1, 2, 3]},
+    });
+    let deserialized: ArrayMetadata = serde_json::from_value(example_json.clone()).unwrap();
+    assert_eq!(
+        deserialized.get_extra_fields()["foo"],
+        json!({"bar": [1, 2, 3]})
+    );
+    assert_eq!(serde_json::to_value(&deserialized).unwrap(), example_json);
+
+    let example_json = json!({
+        "extensions": [],
+        "attributes": {},
+        "foo": "bar",
+    });
+    let deserialized: GroupMetadata = serde_json::from_value(example_json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&deserialized).unwrap(), example_json);
+
+    let example_json = json!({
+        "zarr_format": "https://purl.org/zarr/spec/protocol/core/3.0",
+        "metadata_encoding": "https://purl.org/zarr/spec/protocol/core/3.0",
+        "metadata_key_suffix": ".json",
+        "extensions": [],
+        "foo": "bar",
+    });
+    let deserialized: EntryPointMetadata = serde_json::from_value(example_json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&deserialized).unwrap(), example_json);
+}
+
+#[test]
+fn suggest_chunks_bounds() {
+    let target = 1 << 20;
+    let size = |c: &ChunkCoord| c.iter().map(|&d| d as usize).product::<usize>() * 2;
+
+    let chunk_shape = suggest_chunks(&[4096, 4096, 4096], i16::ZARR_TYPE, target);
+    assert!(size(&chunk_shape) < target * 3 / 2);
+    assert!(size(&chunk_shape) * 2 >= target);
+
+    assert!(suggest_chunks(&[], i16::ZARR_TYPE, target).is_empty());
+    assert_eq!(
+        &suggest_chunks(&[0, 5], i16::ZARR_TYPE, target)[..],
+        &[1, 5]
+    );
+    assert_eq!(&suggest_chunks(&[1 << 40], u8::ZARR_TYPE, 1)[..], &[1]);
+    assert_eq!(
+        &suggest_chunks(&[u64::MAX], u8::ZARR_TYPE, usize::MAX)[..],
+        &[1 << 31]
+    );
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![4096, 4096], i16::ZARR_TYPE).build();
+    assert!(array_meta.get_chunk_num_elements() * 2 < config::config().chunk_target_bytes * 3 / 2);
+}
+
+#[test]
+fn element_count_overflow() {
+    let array_meta = ArrayMetadataBuilder::new(smallvec![1 << 40, 1 << 40], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![1 << 20, 1 << 20])
+        .build();
+    assert_eq!(array_meta.get_num_chunks(), 1 << 40);
+    assert!(matches!(
+        array_meta.checked_num_elements(),
+        Err(MetadataError::ShapeOverflow(_))
+    ));
+    assert!(matches!(
+        array_meta.checked_chunk_num_elements(),
+        Err(MetadataError::ChunkTooLarge(_))
+    ));
+    assert!(check_chunk_shape(&array_meta).is_err());
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![1 << 40, 1 << 20], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![1 << 10, 1 << 10])
+        .build();
+    assert_eq!(array_meta.get_num_elements(), 1 << 60);
+    assert_eq!(array_meta.get_chunk_num_elements(), 1 << 20);
+    assert!(check_chunk_shape(&array_meta).is_ok());
+}
+
+#[test]
+fn grid_extent() {
+    let array_meta = ArrayMetadataBuilder::new(smallvec![3, 8, 9], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![4, 4, 4])
+        .build();
+    assert_eq!(&array_meta.get_grid_extent()[..], &[1, 2, 3]);
+    assert_eq!(array_meta.get_num_chunks(), 6);
+}
+
 const DOC_SPEC_CHUNK_DATA: [i16; 6] = [1, 2, 3, 4, 5, 6];
 
 pub(crate) trait ZarrTestable: HierarchyReader + HierarchyWriter {
@@ -188,6 +301,213 @@ pub(crate) fn test_chunk_compression_rw(compression: compression::CompressionTyp
     assert_eq!(chunk_out.get_data(), &chunk_data[..]);
 }
 
+#[cfg(feature = "aligned_buffers")]
+#[test]
+fn chunk_read_into_aligned_buffers() {
+    use crate::chunk::{
+        AlignedDataChunk,
+        AllocatorDataChunk,
+    };
+
+    let array_meta = ArrayMetadata::new(
+        smallvec![10, 10],
+        smallvec![5, 5],
+        u16::ZARR_TYPE,
+        compression::CompressionType::default(),
+    );
+    let chunk_data: Vec<u16> = (0..25).collect();
+    let mut inner: Vec<u8> = Vec::new();
+    <DefaultChunk as DefaultChunkWriter<u16, _, _>>::write_chunk(
+        &mut inner,
+        &array_meta,
+        &SliceDataChunk::new(smallvec![1, 0], &chunk_data),
+    )
+    .expect("write_chunk failed");
+
+    let mut aligned = AlignedDataChunk::<u16>::with_alignment(4096);
+    for _ in 0..2 {
+        <DefaultChunk as DefaultChunkReader<u16, _>>::read_chunk_into(
+            &inner[..],
+            &array_meta,
+            smallvec![1, 0],
+            &mut aligned,
+        )
+        .expect("read_chunk_into failed");
+        assert_eq!(aligned.get_grid_position(), &[1, 0]);
+        assert_eq!(aligned.get_data(), &chunk_data[..]);
+        assert_eq!(aligned.get_data().as_ptr() as usize % 4096, 0);
+    }
+
+    let mut allocated = AllocatorDataChunk::<u16, _>::new_in(allocator_api2::alloc::Global);
+    <DefaultChunk as DefaultChunkReader<u16, _>>::read_chunk_into(
+        &inner[..],
+        &array_meta,
+        smallvec![1, 0],
+        &mut allocated,
+    )
+    .expect("read_chunk_into failed");
+    assert_eq!(allocated.get_data(), &chunk_data[..]);
+}
+
+/// Round trip a one-dimensional chunk through a filter chain and the default
+/// compressor, checking the data read back.
+pub(crate) fn test_chunk_filter_rw<T>(
+    filters: Vec<filter::FilterType>,
+    chunk_data: &[T],
+    expected: &[T],
+) where
+    T: ReflectedType + PartialEq + std::fmt::Debug,
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk,
+    for<'a> SliceDataChunk<T, &'a [T]>: DataChunk<T> + WriteableDataChunk,
+{
+    let n = chunk_data.len();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![n as u64], T::ZARR_TYPE)
+        .chunk_shape(smallvec![n as u32])
+        .compressor(compression::CompressionType::default())
+        .filters(filters)
+        .build();
+    let chunk_in = SliceDataChunk::new(smallvec![0], chunk_data);
+
+    let mut inner: Vec<u8> = Vec::new();
+    <DefaultChunk as DefaultChunkWriter<T, _, _>>::write_chunk(&mut inner, &array_meta, &chunk_in)
+        .expect("write_chunk failed");
+
+    let chunk_out = <DefaultChunk as DefaultChunkReader<T, _>>::read_chunk(
+        &inner[..],
+        &array_meta,
+        smallvec![0],
+    )
+    .expect("read_chunk failed");
+    assert_eq!(chunk_out.get_data(), expected);
+}
+
+#[test]
+fn chunk_filter_quantize_rw() {
+    let filter = filter::quantize::QuantizeFilter {
+        digits: 1,
+        dtype: None,
+        astype: Some(f32::ZARR_TYPE),
+    };
+    test_chunk_filter_rw::<f64>(
+        vec![filter.into()],
+        &[0.1234, -7.77, 100.5],
+        &[0.125, -7.75, 100.5],
+    );
+}
+
+#[test]
+fn chunk_filter_packbits_rw() {
+    let data: Vec<bool> = (0..13).map(|i| i % 3 == 0).collect();
+    test_chunk_filter_rw::<bool>(
+        vec![filter::packbits::PackBitsFilter::default().into()],
+        &data,
+        &data,
+    );
+}
+
+#[test]
+fn chunk_filter_astype_rw() {
+    let filter = filter::astype::AsTypeFilter::new(u16::ZARR_TYPE, f32::ZARR_TYPE);
+    test_chunk_filter_rw::<f32>(
+        vec![filter.into()],
+        &[0.5, 2.0, 65535.9],
+        &[0.0, 2.0, 65535.0],
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn chunk_compression_detection() {
+    let array_meta = ArrayMetadata::new(
+        smallvec![10],
+        smallvec![10],
+        i32::ZARR_TYPE,
+        compression::CompressionType::default(),
+    );
+    let chunk_data: Vec<i32> = (0..10).collect();
+    let chunk_in = SliceDataChunk::new(smallvec![0], &chunk_data);
+    let gzip = compression::CompressionType::new::<compression::gzip::GzipCompression>();
+
+    let mut inner: Vec<u8> = Vec::new();
+    <DefaultChunk as DefaultChunkWriter<i32, _, _>>::write_chunk_with_compression(
+        &mut inner,
+        &array_meta,
+        &chunk_in,
+        &gzip,
+    )
+    .expect("write_chunk failed");
+    assert_eq!(compression::CompressionType::detect(&inner), Some(gzip));
+
+    // The configuration is passed rather than set, since tests share the
+    // crate-wide configuration.
+    let detecting = config::Config {
+        detect_chunk_compression: true,
+        ..config::Config::default()
+    };
+    let mut chunk_out = i32::create_data_chunk(&smallvec![0], 10);
+    super::chunk::read_chunk_data(&inner[..], &array_meta, &[0], &mut chunk_out, &detecting)
+        .expect("read_chunk failed");
+    assert_eq!(chunk_out.get_data(), &chunk_data[..]);
+}
+
+#[cfg(any(feature = "lz", feature = "lz_pure"))]
+#[test]
+fn chunk_declared_size_limit() {
+    let array_meta = ArrayMetadata::new(
+        smallvec![10],
+        smallvec![10],
+        i32::ZARR_TYPE,
+        compression::CompressionType::new::<compression::lz::Lz4Compression>(),
+    );
+    // An lz4 frame header declaring a terabyte of content.
+    #[rustfmt::skip]
+    let inner = [
+        0x04, 0x22, 0x4d, 0x18,
+        0x68, 0x40,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00,
+    ];
+    let error = <DefaultChunk as DefaultChunkReader<i32, _>>::read_chunk(
+        &inner[..],
+        &array_meta,
+        smallvec![0],
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(error.to_string().contains("declares 1099511627776"));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn chunk_decompressed_size_limit() {
+    use crate::compression::Compression;
+    use std::io::Write;
+
+    let gzip = compression::CompressionType::new::<compression::gzip::GzipCompression>();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![3], f32::ZARR_TYPE)
+        .chunk_shape(smallvec![3])
+        .compressor(gzip.clone())
+        .filters(vec![filter::astype::AsTypeFilter::new(
+            u16::ZARR_TYPE,
+            f32::ZARR_TYPE,
+        )
+        .into()])
+        .build();
+    let mut inner: Vec<u8> = Vec::new();
+    gzip.encoder(&mut inner).write_all(&[0; 1 << 20]).unwrap();
+
+    let error = <DefaultChunk as DefaultChunkReader<f32, _>>::read_chunk(
+        &inner[..],
+        &array_meta,
+        smallvec![0],
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(error
+        .to_string()
+        .contains("more than the limit of 12 bytes"));
+}
+
 pub(crate) fn test_varlength_chunk_rw(compression: compression::CompressionType) {
     let array_meta = ArrayMetadata::new(
         smallvec![10, 10, 10],
@@ -242,15 +562,62 @@ pub(crate) fn create_array<N: ZarrTestable>() {
         smallvec![10, 10, 10],
         smallvec![5, 5, 5],
         i32::ZARR_TYPE,
-        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression::default()),
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
     );
     create
         .create_array("foo/bar", &array_meta)
         .expect("Failed to create array");
 
+    create.create_group("baz").expect("Failed to create group");
+
     let read = create.open_reader();
 
     assert_eq!(read.get_array_metadata("foo/bar").unwrap(), array_meta);
+    assert_eq!(read.node_kind("foo/bar").unwrap(), Some(NodeKind::Array));
+    assert!(read.array_exists("foo/bar").unwrap());
+    assert!(!read.group_exists("foo/bar").unwrap());
+    assert_eq!(read.node_kind("baz").unwrap(), Some(NodeKind::Group));
+    assert!(read.group_exists("baz").unwrap());
+    assert!(!read.array_exists("baz").unwrap());
+    assert_eq!(read.node_kind("qux").unwrap(), None);
+}
+
+pub(crate) fn array_extensions<N: ZarrTestable>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let mut array_meta = ArrayMetadata::new(
+        smallvec![10, 10, 10],
+        smallvec![5, 5, 5],
+        i32::ZARR_TYPE,
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
+    );
+    let ext = ExtensionMetadata {
+        extension: "http://example.org/zarr/extension/foo".to_owned(),
+        must_understand: false,
+        configuration: None,
+    };
+    array_meta.extensions.push(ext.clone());
+    create
+        .create_array("foo/bar", &array_meta)
+        .expect("Failed to create array");
+
+    array_meta.extensions[0].must_understand = true;
+    create
+        .create_array("foo/baz", &array_meta)
+        .expect("Failed to create array");
+
+    let read = create.open_reader();
+
+    assert_eq!(
+        read.get_array_metadata("foo/bar").unwrap().get_extensions(),
+        &[ext]
+    );
+    let err = read.get_array_metadata("foo/baz").unwrap_err();
+    assert!(matches!(
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<MetadataError>()),
+        Some(MetadataError::UnknownRequiredExtension(_))
+    ));
 }
 
 pub(crate) fn absolute_relative_paths<N: ZarrTestable>() -> Result<()> {
@@ -260,7 +627,7 @@ pub(crate) fn absolute_relative_paths<N: ZarrTestable>() -> Result<()> {
         smallvec![10, 10, 10],
         smallvec![5, 5, 5],
         i32::ZARR_TYPE,
-        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression::default()),
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
     );
     create
         .create_array("foo/bar", &array_meta)
@@ -361,7 +728,7 @@ pub(crate) fn attributes_rw<N: ZarrTestable>() {
         smallvec![10, 10, 10],
         smallvec![5, 5, 5],
         i32::ZARR_TYPE,
-        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression::default()),
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
     );
     create
         .create_array(array, &array_meta)
@@ -384,7 +751,7 @@ pub(crate) fn create_chunk_rw<N: ZarrTestable>() {
         smallvec![10, 10, 10],
         smallvec![5, 5, 5],
         i32::ZARR_TYPE,
-        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression::default()),
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
     );
     let chunk_data: Vec<i32> = (0..125_i32).collect();
     let chunk_in = crate::SliceDataChunk::new(smallvec![0, 0, 0], &chunk_data);
@@ -416,6 +783,72 @@ pub(crate) fn create_chunk_rw<N: ZarrTestable>() {
         .is_err());
 }
 
+#[cfg(feature = "gzip")]
+pub(crate) fn chunk_compressor_rw<N: ZarrTestable>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadata::new(
+        smallvec![10, 10],
+        smallvec![5, 5],
+        i32::ZARR_TYPE,
+        crate::compression::CompressionType::default(),
+    );
+    let gzip =
+        crate::compression::CompressionType::new::<crate::compression::gzip::GzipCompression>();
+    let chunk_data: Vec<i32> = (0..25_i32).collect();
+    let chunk_in = crate::SliceDataChunk::new(smallvec![1, 0], &chunk_data);
+
+    create
+        .create_array("foo/bar", &array_meta)
+        .expect("Failed to create array");
+    let recorded = create
+        .write_chunk_with_compression("foo/bar", &array_meta, &chunk_in, &gzip)
+        .expect("Failed to write chunk");
+    assert_eq!(
+        recorded.get_chunk_compressor(&[1, 0]).unwrap(),
+        Some(gzip.clone())
+    );
+    assert_eq!(recorded.get_chunk_compressor(&[0, 0]).unwrap(), None);
+
+    // The recorded compressor is used without compression detection.
+    let read = create.open_reader();
+    let stored = read
+        .get_array_metadata("foo/bar")
+        .expect("Failed to read metadata");
+    assert_eq!(stored, recorded);
+    let chunk_out = read
+        .read_chunk::<i32>("foo/bar", &stored, smallvec![1, 0])
+        .expect("Failed to read chunk")
+        .expect("Chunk is empty");
+    assert_eq!(chunk_out.get_data(), &chunk_data[..]);
+
+    // Later writes with the metadata keep the recorded compressor.
+    create
+        .write_chunk("foo/bar", &stored, &chunk_in)
+        .expect("Failed to write chunk");
+    let chunk_out = read
+        .read_chunk::<i32>("foo/bar", &stored, smallvec![1, 0])
+        .expect("Failed to read chunk")
+        .expect("Chunk is empty");
+    assert_eq!(chunk_out.get_data(), &chunk_data[..]);
+
+    // Writing with the array's compressor removes the record.
+    let restored = create
+        .write_chunk_with_compression("foo/bar", &stored, &chunk_in, array_meta.get_compressor())
+        .expect("Failed to write chunk");
+    assert_eq!(restored.get_chunk_compressor(&[1, 0]).unwrap(), None);
+    assert!(restored.get_extensions().is_empty());
+    let stored = read
+        .get_array_metadata("foo/bar")
+        .expect("Failed to read metadata");
+    assert!(stored.get_extensions().is_empty());
+    let chunk_out = read
+        .read_chunk::<i32>("foo/bar", &stored, smallvec![1, 0])
+        .expect("Failed to read chunk")
+        .expect("Chunk is empty");
+    assert_eq!(chunk_out.get_data(), &chunk_data[..]);
+}
+
 pub(crate) fn delete_chunk<N: ZarrTestable>() {
     let wrapper = N::temp_new_rw();
     let create = wrapper.as_ref();
@@ -423,7 +856,7 @@ pub(crate) fn delete_chunk<N: ZarrTestable>() {
         smallvec![10, 100, 100],
         smallvec![5, 5, 5],
         i32::ZARR_TYPE,
-        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression::default()),
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
     );
 
     let coord_a = smallvec![1, 2, 3];
@@ -455,6 +888,204 @@ pub(crate) fn delete_chunk<N: ZarrTestable>() {
         .is_none());
 }
 
+pub(crate) fn batch_chunk_rw<N: ZarrTestable>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadata::new(
+        smallvec![10, 10],
+        smallvec![5, 5],
+        i32::ZARR_TYPE,
+        crate::compression::CompressionType::default(),
+    );
+    let array = "foo/bar";
+    create
+        .create_array(array, &array_meta)
+        .expect("Failed to create array");
+
+    let chunks: Vec<_> = [[0, 0], [1, 0], [1, 1]]
+        .iter()
+        .enumerate()
+        .map(|(i, coord)| crate::VecDataChunk::new(coord[..].into(), vec![i as i32; 25]))
+        .collect();
+    create
+        .write_chunks(array, &array_meta, &chunks)
+        .expect("Failed to write chunks");
+
+    let read = create.open_reader();
+    let grid_positions: Vec<GridCoord> = vec![smallvec![1, 1], smallvec![0, 1], smallvec![0, 0]];
+    let read_chunks = read
+        .read_chunks::<i32>(array, &array_meta, &grid_positions)
+        .expect("Failed to read chunks");
+    let data: Vec<Option<Vec<i32>>> = read_chunks
+        .into_iter()
+        .map(|chunk| chunk.map(|chunk| chunk.into_data()))
+        .collect();
+    assert_eq!(data, vec![Some(vec![2; 25]), None, Some(vec![0; 25])]);
+
+    let grid_positions: Vec<GridCoord> = vec![smallvec![0, 0], smallvec![2, 0]];
+    let err = read
+        .read_chunks::<i32>(array, &array_meta, &grid_positions)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let mut chunk = i32::create_data_chunk(&smallvec![0, 0], 25);
+    let err = read
+        .read_chunks_into(
+            array,
+            &array_meta,
+            &grid_positions,
+            &mut chunk,
+            |_, _, _| Ok(()),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub(crate) fn read_only_array<N: ZarrTestable>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![5, 5])
+        .read_only(true)
+        .build();
+    let array = "foo/bar";
+    create
+        .create_array(array, &array_meta)
+        .expect("Failed to create array");
+
+    let read = create.open_reader();
+    let array_meta = read.get_array_metadata(array).unwrap();
+    assert!(array_meta.is_read_only());
+
+    let chunk_data: Vec<i32> = (0..25_i32).collect();
+    let chunk_in = crate::SliceDataChunk::new(smallvec![0, 0], &chunk_data);
+    let denied = |r: Result<()>| r.unwrap_err().kind() == std::io::ErrorKind::PermissionDenied;
+    assert!(denied(create.write_chunk(array, &array_meta, &chunk_in)));
+    assert!(denied(
+        create.delete_chunk(array, &array_meta, &[0, 0]).map(|_| ())
+    ));
+    assert!(denied(create.set_attribute(array, "foo".to_owned(), "bar")));
+    assert!(denied(create.remove(array)));
+    assert!(read.exists(array).unwrap());
+}
+
+pub(crate) fn seal_array<N: ZarrTestable>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![5, 5])
+        .build();
+    let array = "foo/bar";
+    create
+        .create_array(array, &array_meta)
+        .expect("Failed to create array");
+    let chunk_data: Vec<i32> = (0..25_i32).collect();
+    let chunk_in = crate::SliceDataChunk::new(smallvec![0, 0], &chunk_data);
+    create
+        .write_chunk(array, &array_meta, &chunk_in)
+        .expect("Failed to write chunk");
+
+    let sealed_meta = create.seal(array).expect("Failed to seal array");
+    assert!(sealed_meta.is_read_only());
+    assert_eq!(create.seal(array).unwrap(), sealed_meta);
+
+    let read = create.open_reader();
+    assert!(read.get_array_metadata(array).unwrap().is_read_only());
+    assert_eq!(
+        read.read_chunk::<i32>(array, &sealed_meta, smallvec![0, 0])
+            .unwrap()
+            .unwrap()
+            .get_data(),
+        &chunk_data[..]
+    );
+    let denied = |r: Result<()>| r.unwrap_err().kind() == std::io::ErrorKind::PermissionDenied;
+    assert!(denied(create.write_chunk(array, &sealed_meta, &chunk_in)));
+    assert!(denied(create.set_attribute(array, "foo".to_owned(), "bar")));
+    assert_eq!(
+        create.seal("baz").unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+pub(crate) fn chunk_existence<N: ZarrTestable + HierarchyLister>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadata::new(
+        smallvec![10, 100, 100],
+        smallvec![5, 5, 5],
+        i32::ZARR_TYPE,
+        crate::compression::CompressionType::Raw(crate::compression::raw::RawCompression),
+    );
+    let array = "foo/bar";
+    create
+        .create_array(array, &array_meta)
+        .expect("Failed to create array");
+    assert!(create
+        .initialized_chunks(array, &array_meta)
+        .unwrap()
+        .is_empty());
+
+    let chunk_data: Vec<i32> = (0..125_i32).collect();
+    let coords: Vec<GridCoord> = vec![smallvec![1, 12, 3], smallvec![0, 0, 0], smallvec![1, 2, 3]];
+    for coord in &coords {
+        let chunk_in = crate::SliceDataChunk::new(coord.clone(), &chunk_data);
+        create
+            .write_chunk(array, &array_meta, &chunk_in)
+            .expect("Failed to write chunk");
+    }
+
+    assert!(create.chunk_exists(array, &array_meta, &[1, 2, 3]).unwrap());
+    assert!(!create.chunk_exists(array, &array_meta, &[1, 2, 4]).unwrap());
+    assert_eq!(
+        create.initialized_chunks(array, &array_meta).unwrap(),
+        vec![coords[1].clone(), coords[2].clone(), coords[0].clone()]
+    );
+}
+
+pub(crate) fn zero_dimensional_array<N: ZarrTestable + HierarchyLister>() {
+    let wrapper = N::temp_new_rw();
+    let create = wrapper.as_ref();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![], i32::ZARR_TYPE).build();
+    assert_eq!(array_meta.get_num_chunks(), 1);
+
+    let array = "foo/scalar";
+    create
+        .create_array(array, &array_meta)
+        .expect("Failed to create array");
+    let read = create.open_reader();
+    assert_eq!(read.get_array_metadata(array).unwrap(), array_meta);
+
+    let chunk_data = [42_i32];
+    let chunk_in = crate::SliceDataChunk::new(smallvec![], &chunk_data[..]);
+    create
+        .write_chunk(array, &array_meta, &chunk_in)
+        .expect("Failed to write chunk");
+    let chunk_out = read
+        .read_chunk::<i32>(array, &array_meta, smallvec![])
+        .expect("Failed to read chunk")
+        .expect("Chunk is empty");
+    assert_eq!(chunk_out.get_data(), &chunk_data[..]);
+    assert_eq!(
+        create.initialized_chunks(array, &array_meta).unwrap(),
+        vec![GridCoord::new()]
+    );
+
+    let empty_meta = ArrayMetadataBuilder::new(smallvec![0, 10], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![5, 5])
+        .build();
+    assert_eq!(empty_meta.get_num_chunks(), 0);
+    create
+        .create_array("foo/empty", &empty_meta)
+        .expect("Failed to create array");
+
+    let zero_chunk_meta = ArrayMetadataBuilder::new(smallvec![10, 10], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![5, 0])
+        .build();
+    let err = create
+        .create_array("foo/invalid", &zero_chunk_meta)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
 #[macro_export]
 macro_rules! test_backend {
     ($backend:ty) => {
@@ -468,6 +1099,11 @@ macro_rules! test_backend {
             $crate::tests::create_array::<$backend>()
         }
 
+        #[test]
+        fn array_extensions() {
+            $crate::tests::array_extensions::<$backend>()
+        }
+
         #[test]
         fn absolute_relative_paths() -> Result<()> {
             $crate::tests::absolute_relative_paths::<$backend>()
@@ -483,9 +1119,40 @@ macro_rules! test_backend {
             $crate::tests::create_chunk_rw::<$backend>()
         }
 
+        #[cfg(feature = "gzip")]
+        #[test]
+        fn chunk_compressor_rw() {
+            $crate::tests::chunk_compressor_rw::<$backend>()
+        }
+
         #[test]
         fn delete_chunk() {
             $crate::tests::delete_chunk::<$backend>()
         }
+
+        #[test]
+        fn batch_chunk_rw() {
+            $crate::tests::batch_chunk_rw::<$backend>()
+        }
+
+        #[test]
+        fn read_only_array() {
+            $crate::tests::read_only_array::<$backend>()
+        }
+
+        #[test]
+        fn seal_array() {
+            $crate::tests::seal_array::<$backend>()
+        }
+
+        #[test]
+        fn chunk_existence() {
+            $crate::tests::chunk_existence::<$backend>()
+        }
+
+        #[test]
+        fn zero_dimensional_array() {
+            $crate::tests::zero_dimensional_array::<$backend>()
+        }
     };
 }
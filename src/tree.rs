@@ -0,0 +1,179 @@
+//! Text rendering of the structure of hierarchies.
+//!
+//! ```
+//! use zarr::tree::HierarchyTree;
+//!
+//! fn info<H: HierarchyTree>(h: &H) -> std::io::Result<()> {
+//!     print!("{}", h.tree("")?);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! prints, for example:
+//!
+//! ```text
+//! /
+//! ├── labels (512, 512, 64) <u8 chunks (64, 64, 64) Gzip
+//! └── raw
+//!     ├── s0 (512, 512, 64) <u1 chunks (64, 64, 64) Gzip
+//!     └── s1 (256, 256, 32) <u1 chunks (64, 64, 32) Gzip
+//! ```
+
+use std::fmt;
+use std::io::{
+    Error,
+    ErrorKind,
+};
+
+use crate::{
+    ArrayMetadata,
+    HierarchyLister,
+    HierarchyReader,
+};
+
+/// A group or array and the nodes below it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tree {
+    /// Last component of the node's path, or `/` for the root.
+    pub name: String,
+    /// Metadata of the node if it is an array, or `None` for groups.
+    pub array_meta: Option<ArrayMetadata>,
+    /// Nodes below a group, sorted by name.
+    pub children: Vec<Tree>,
+}
+
+impl Tree {
+    fn label(&self) -> String {
+        let array_meta = match &self.array_meta {
+            Some(array_meta) => array_meta,
+            None => return self.name.clone(),
+        };
+        let data_type = match serde_json::to_value(array_meta.get_data_type()) {
+            Ok(serde_json::Value::String(data_type)) => data_type,
+            Ok(data_type) => data_type.to_string(),
+            Err(_) => "?".to_owned(),
+        };
+        format!(
+            "{} {} {} chunks {} {}",
+            self.name,
+            format_shape(array_meta.get_shape()),
+            data_type,
+            format_shape(array_meta.get_chunk_shape()),
+            array_meta.get_compressor(),
+        )
+    }
+
+    fn fmt_children(&self, f: &mut fmt::Formatter<'_>, indent: &str) -> fmt::Result {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let (branch, continuation) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            writeln!(f, "{}{}{}", indent, branch, child.label())?;
+            child.fmt_children(f, &format!("{}{}", indent, continuation))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shapes are written as tuples, as zarr-python does.
+fn format_shape<T: fmt::Display>(shape: &[T]) -> String {
+    let dims: Vec<String> = shape.iter().map(T::to_string).collect();
+    if dims.len() == 1 {
+        format!("({},)", dims[0])
+    } else {
+        format!("({})", dims.join(", "))
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.label())?;
+        self.fmt_children(f, "")
+    }
+}
+
+pub trait HierarchyTree: HierarchyReader + HierarchyLister {
+    /// The tree of groups and arrays at or below a path.
+    fn tree(&self, path_name: &str) -> Result<Tree, Error> {
+        let path_name = crate::canonicalize_path(path_name);
+        let name = match path_name.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_owned(),
+            _ => "/".to_owned(),
+        };
+
+        match self.get_array_metadata(path_name) {
+            Ok(array_meta) => {
+                return Ok(Tree {
+                    name,
+                    array_meta: Some(array_meta),
+                    children: vec![],
+                })
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut names = self.list_nodes(path_name)?;
+        names.sort();
+        let children = names
+            .iter()
+            .map(|child| {
+                if path_name.is_empty() {
+                    self.tree(child)
+                } else {
+                    self.tree(&format!("{}/{}", path_name, child))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Tree {
+            name,
+            array_meta: None,
+            children,
+        })
+    }
+}
+
+impl<T: HierarchyReader + HierarchyLister> HierarchyTree for T {}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionType;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_tree() {
+        let dir = tempdir::TempDir::new("rust_zarr_tree_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 5], u16::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(CompressionType::Raw(Default::default()))
+            .build();
+        let vector_meta = ArrayMetadataBuilder::new(smallvec![10], f32::ZARR_TYPE)
+            .chunk_shape(smallvec![5])
+            .compressor(CompressionType::Raw(Default::default()))
+            .build();
+        h.create_group("g").unwrap();
+        h.create_array("g/b", &array_meta).unwrap();
+        h.create_array("g/a/v", &vector_meta).unwrap();
+        h.create_array("z", &vector_meta).unwrap();
+
+        assert_eq!(
+            h.tree("").unwrap().to_string(),
+            "/\n\
+             ├── g\n\
+             │   ├── a\n\
+             │   │   └── v (10,) <f4 chunks (5,) Raw\n\
+             │   └── b (4, 5) <u2 chunks (2, 2) Raw\n\
+             └── z (10,) <f4 chunks (5,) Raw\n"
+        );
+        assert_eq!(
+            h.tree("/g/a").unwrap().to_string(),
+            "a\n└── v (10,) <f4 chunks (5,) Raw\n"
+        );
+        assert_eq!(h.tree("z").unwrap().array_meta.as_ref(), Some(&vector_meta));
+    }
+}
@@ -0,0 +1,226 @@
+//! Storage usage reporting for capacity planning.
+//!
+//! ```
+//! use zarr::usage::HierarchyUsage;
+//!
+//! fn report<H: HierarchyUsage>(h: &H) -> std::io::Result<()> {
+//!     for usage in h.du("")? {
+//!         println!(
+//!             "{}: {}/{} chunks, {} bytes, median chunk {:?} bytes",
+//!             usage.path,
+//!             usage.chunks_present,
+//!             usage.chunks_expected,
+//!             usage.stored_bytes,
+//!             usage.chunk_size_percentile(50.0),
+//!         );
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Error;
+use std::time::SystemTime;
+
+use crate::{
+    storage::{
+        list_chunk_keys,
+        ListableStore,
+        ReadableStore,
+    },
+    ArrayMetadata,
+    Hierarchy,
+    HierarchyReader,
+};
+
+/// Storage used by the chunks of an array.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArrayUsage {
+    /// Path of the array in the hierarchy.
+    pub path: String,
+    /// Number of chunks in the array's grid.
+    pub chunks_expected: u64,
+    /// Number of chunks present in the store.
+    pub chunks_present: u64,
+    /// Total stored size of present chunks in bytes.
+    pub stored_bytes: u64,
+    /// Total uncompressed size of present chunks in bytes.
+    pub uncompressed_bytes: u64,
+    /// Stored sizes of present chunks in bytes, in ascending order.
+    pub chunk_sizes: Vec<u64>,
+    /// Latest modification time of a present chunk, if the store has
+    /// modification times.
+    pub last_modified: Option<SystemTime>,
+}
+
+impl ArrayUsage {
+    /// Ratio of uncompressed to stored size of present chunks, or `None` if
+    /// no chunk data is stored.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.stored_bytes == 0 {
+            None
+        } else {
+            Some(self.uncompressed_bytes as f64 / self.stored_bytes as f64)
+        }
+    }
+
+    /// Stored chunk size at a percentile between 0 and 100, using the
+    /// nearest-rank method, or `None` if no chunks are present.
+    pub fn chunk_size_percentile(&self, percentile: f64) -> Option<u64> {
+        if self.chunk_sizes.is_empty() {
+            return None;
+        }
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * self.chunk_sizes.len() as f64).ceil();
+        let index = (rank as usize).max(1) - 1;
+        Some(self.chunk_sizes[index])
+    }
+}
+
+/// Paths of the arrays at or below a path, in ascending order.
+pub(crate) fn array_paths<S: ReadableStore + ListableStore + Hierarchy>(
+    store: &S,
+    path_name: &str,
+) -> Result<Vec<String>, Error> {
+    let mut paths = vec![];
+    let mut to_visit = vec![crate::canonicalize_path(path_name).to_owned()];
+
+    while let Some(path_name) = to_visit.pop() {
+        let array_key = store.array_metadata_key(&path_name);
+        if ReadableStore::exists(store, array_key.to_str().expect("TODO"))? {
+            paths.push(path_name);
+        } else {
+            for name in crate::HierarchyLister::list_nodes(store, &path_name)? {
+                to_visit.push(if path_name.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", path_name, name)
+                });
+            }
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Storage usage reporting for hierarchies over listable stores.
+pub trait HierarchyUsage: HierarchyReader {
+    /// Report storage used by the chunks of each array at or below a path.
+    fn du(&self, path_name: &str) -> Result<Vec<ArrayUsage>, Error>;
+
+    /// Report storage used by the chunks of an array.
+    fn array_usage(&self, path_name: &str, array_meta: &ArrayMetadata)
+        -> Result<ArrayUsage, Error>;
+}
+
+impl<S: ReadableStore + ListableStore + Hierarchy> HierarchyUsage for S {
+    fn du(&self, path_name: &str) -> Result<Vec<ArrayUsage>, Error> {
+        let mut usages = vec![];
+        for path_name in array_paths(self, path_name)? {
+            let array_meta = self.get_array_metadata(&path_name)?;
+            usages.push(self.array_usage(&path_name, &array_meta)?);
+        }
+        Ok(usages)
+    }
+
+    fn array_usage(
+        &self,
+        path_name: &str,
+        array_meta: &ArrayMetadata,
+    ) -> Result<ArrayUsage, Error> {
+        let mut chunk_sizes = vec![];
+        let mut last_modified = None;
+        for (key, _) in list_chunk_keys(self, path_name, array_meta)? {
+            if let Some(stat) = self.stat(&key)? {
+                chunk_sizes.push(stat.size);
+                last_modified = last_modified.max(stat.last_modified);
+            }
+        }
+        chunk_sizes.sort_unstable();
+
+        let chunk_bytes = array_meta.get_chunk_num_elements() as u64
+            * array_meta.get_data_type().effective_type()?.size_of() as u64;
+        let chunks_present = chunk_sizes.len() as u64;
+
+        Ok(ArrayUsage {
+            path: crate::canonicalize_path(path_name).to_owned(),
+            chunks_expected: array_meta.get_num_chunks(),
+            chunks_present,
+            stored_bytes: chunk_sizes.iter().sum(),
+            uncompressed_bytes: chunks_present * chunk_bytes,
+            chunk_sizes,
+            last_modified,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_du() {
+        let dir = tempdir::TempDir::new("rust_zarr_usage_tests").unwrap();
+        let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+        let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], i32::ZARR_TYPE)
+            .chunk_shape(smallvec![2, 2])
+            .compressor(CompressionType::default())
+            .build();
+        h.create_array("foo", &array_meta).unwrap();
+        h.create_array("foo/nested", &array_meta).unwrap();
+        h.create_array("bar/baz", &array_meta).unwrap();
+
+        let chunk_data = vec![0i32; 4];
+        for coord in &[[0, 0], [1, 0], [1, 1]] {
+            let chunk = SliceDataChunk::new(smallvec![coord[0], coord[1]], &chunk_data);
+            h.write_chunk("foo", &array_meta, &chunk).unwrap();
+        }
+        // Chunks of nested arrays are not counted.
+        let chunk = SliceDataChunk::new(smallvec![0, 1], &chunk_data);
+        h.write_chunk("foo/nested", &array_meta, &chunk).unwrap();
+
+        let usages = h.du("").unwrap();
+        assert_eq!(
+            usages.iter().map(|u| u.path.as_str()).collect::<Vec<_>>(),
+            vec!["bar/baz", "foo"]
+        );
+
+        let empty = &usages[0];
+        assert_eq!(empty.chunks_expected, 4);
+        assert_eq!(empty.chunks_present, 0);
+        assert_eq!(empty.compression_ratio(), None);
+        assert_eq!(empty.chunk_size_percentile(50.0), None);
+        assert_eq!(empty.last_modified, None);
+
+        let foo = &usages[1];
+        assert_eq!(foo.chunks_present, 3);
+        assert_eq!(foo.chunk_sizes, vec![16; 3]);
+        assert_eq!(foo.stored_bytes, 48);
+        assert_eq!(foo.uncompressed_bytes, 48);
+        assert_eq!(foo.compression_ratio(), Some(1.0));
+        assert_eq!(foo.chunk_size_percentile(0.0), Some(16));
+        assert_eq!(foo.chunk_size_percentile(100.0), Some(16));
+        assert!(foo.last_modified.is_some());
+
+        assert_eq!(h.du("foo").unwrap(), vec![foo.clone()]);
+    }
+
+    #[test]
+    fn test_chunk_size_percentile() {
+        let usage = ArrayUsage {
+            path: "foo".to_owned(),
+            chunks_expected: 4,
+            chunks_present: 4,
+            stored_bytes: 100,
+            uncompressed_bytes: 400,
+            chunk_sizes: vec![10, 20, 30, 40],
+            last_modified: None,
+        };
+        assert_eq!(usage.compression_ratio(), Some(4.0));
+        assert_eq!(usage.chunk_size_percentile(0.0), Some(10));
+        assert_eq!(usage.chunk_size_percentile(25.0), Some(10));
+        assert_eq!(usage.chunk_size_percentile(50.0), Some(20));
+        assert_eq!(usage.chunk_size_percentile(51.0), Some(30));
+        assert_eq!(usage.chunk_size_percentile(100.0), Some(40));
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "use_ndarray")]
+
+use ndarray::Array;
+use smallvec::smallvec;
+
+use zarr::cast::ZarrCastReader;
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+
+#[test]
+fn test_cast_read() {
+    let dir = tempdir::TempDir::new("rust_zarr_cast_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![5, 6], u16::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 4])
+        .fill_value(serde_json::json!(7))
+        .build();
+    h.create_array("raw", &array_meta).unwrap();
+    let values: Vec<u16> = (0..20).map(|i| i * 3000).collect();
+    let array = Array::from_shape_vec(vec![4, 5], values).unwrap();
+    h.write_ndarray("raw", &array_meta, smallvec![0, 0], &array)
+        .unwrap();
+
+    let bbox = BoundingBox::new(smallvec![1, 1], smallvec![4, 5]);
+    let expected = h.read_ndarray::<u16>("raw", &array_meta, &bbox).unwrap();
+
+    let floats = h
+        .cast::<f32>("raw", &array_meta)
+        .read_ndarray(&bbox)
+        .unwrap();
+    assert_eq!(floats, expected.mapv(f32::from));
+    // The fill value is cast too.
+    assert_eq!(floats[[3, 4]], 7.0);
+
+    let scaled = h
+        .cast::<f64>("raw", &array_meta)
+        .scale(0.5, -1.0)
+        .read_ndarray(&bbox)
+        .unwrap();
+    assert_eq!(scaled, expected.mapv(|v| f64::from(v) * 0.5 - 1.0));
+
+    let bytes = h
+        .cast::<i8>("raw", &array_meta)
+        .read_ndarray(&bbox)
+        .unwrap();
+    assert_eq!(bytes, expected.mapv(|v| v.min(127) as i8));
+}
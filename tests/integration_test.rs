@@ -83,7 +83,7 @@ fn test_zarr_filesystem_dim(dim: usize) {
         FilesystemHierarchy::open_or_create(dir.path()).expect("Failed to create Zarr filesystem");
     test_all_types(
         &n,
-        &CompressionType::Raw(compression::raw::RawCompression::default()),
+        &CompressionType::Raw(compression::raw::RawCompression),
         dim,
     );
 }
@@ -98,7 +98,7 @@ fn test_zarr_filesystem_dims() {
 fn test_all_compressions<Zarr: HierarchyReader + HierarchyWriter>(n: &Zarr) {
     test_all_types(
         n,
-        &CompressionType::Raw(compression::raw::RawCompression::default()),
+        &CompressionType::Raw(compression::raw::RawCompression),
         3,
     );
     #[cfg(feature = "bzip")]
@@ -125,6 +125,12 @@ fn test_all_compressions<Zarr: HierarchyReader + HierarchyWriter>(n: &Zarr) {
         &CompressionType::Xz(compression::xz::XzCompression::default()),
         3,
     );
+    #[cfg(feature = "snappy")]
+    test_all_types(
+        n,
+        &CompressionType::Snappy(compression::snappy::SnappyCompression::default()),
+        3,
+    );
 }
 
 #[test]
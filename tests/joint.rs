@@ -0,0 +1,80 @@
+#![cfg(feature = "use_ndarray")]
+
+use ndarray::Array;
+use smallvec::smallvec;
+
+use zarr::joint::ZarrJointReader;
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+
+#[test]
+fn test_joint_read() {
+    let dir = tempdir::TempDir::new("rust_zarr_joint_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let raw_meta = ArrayMetadataBuilder::new(smallvec![5, 6], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 4])
+        .build();
+    let labels_meta = ArrayMetadataBuilder::new(smallvec![5, 6], u64::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 4])
+        .build();
+    h.create_array("raw", &raw_meta).unwrap();
+    h.create_array("labels", &labels_meta).unwrap();
+    let raw = Array::from_shape_fn(vec![5, 6], |idx| (idx[0] * 6 + idx[1]) as u8);
+    let labels = raw.mapv(|v| u64::from(v) * 1000);
+    h.write_ndarray("raw", &raw_meta, smallvec![0, 0], &raw)
+        .unwrap();
+    // Leave the last row of chunks of labels absent.
+    h.write_ndarray(
+        "labels",
+        &labels_meta,
+        smallvec![0, 0],
+        labels.slice(ndarray::s![..4, ..]).into_dyn(),
+    )
+    .unwrap();
+
+    let reader = h
+        .joint::<(u8, u64)>(&[("raw", &raw_meta), ("labels", &labels_meta)])
+        .unwrap();
+
+    let chunks: Vec<_> = reader.chunks().collect::<Result<_, _>>().unwrap();
+    assert_eq!(chunks.len(), 6);
+    for (grid_position, (raw_chunk, labels_chunk)) in &chunks {
+        let raw_chunk = raw_chunk.as_ref().unwrap();
+        assert_eq!(raw_chunk.get_grid_position(), &grid_position[..]);
+        match labels_chunk {
+            Some(labels_chunk) => {
+                assert!(grid_position[0] < 2);
+                let expected: Vec<u64> = raw_chunk
+                    .get_data()
+                    .iter()
+                    .map(|&v| u64::from(v) * 1000)
+                    .collect();
+                // Overhanging parts of edge chunks are fill values in both.
+                assert_eq!(labels_chunk.get_data(), &expected[..]);
+            }
+            None => assert_eq!(grid_position[0], 2),
+        }
+    }
+
+    let regions: Vec<_> = reader.regions().collect::<Result<_, _>>().unwrap();
+    assert_eq!(regions.len(), 6);
+    let (bbox, (raw_region, labels_region)) = &regions[5];
+    assert_eq!(bbox, &BoundingBox::new(smallvec![4, 4], smallvec![1, 2]));
+    assert_eq!(raw_region, &raw.slice(ndarray::s![4.., 4..]).into_dyn());
+    assert_eq!(labels_region.shape(), &[1, 2]);
+    assert!(labels_region.iter().all(|&v| v == 0));
+
+    let bbox = BoundingBox::new(smallvec![1, 1], smallvec![3, 4]);
+    let (raw_region, labels_region) = reader.read_regions(&bbox).unwrap();
+    assert_eq!(raw_region.mapv(|v| u64::from(v) * 1000), labels_region);
+
+    assert!(h
+        .joint::<(u8,)>(&[("raw", &raw_meta), ("labels", &labels_meta)])
+        .is_err());
+    let other_grid = ArrayMetadataBuilder::new(smallvec![5, 6], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![5, 6])
+        .build();
+    assert!(h
+        .joint::<(u8, u8)>(&[("raw", &raw_meta), ("other", &other_grid)])
+        .is_err());
+}
@@ -154,7 +154,7 @@ fn test_write_read_ndarray() {
     let arr_shape = [3, 35, 15, 7];
     let array: Array<i32, _> =
         Array::from_iter(rng.sample_iter(&Standard).take(arr_shape.iter().product()))
-            .into_shape(arr_shape.clone())
+            .into_shape(arr_shape)
             .unwrap()
             .into_dyn();
     let offset = smallvec![0, 5, 4, 3];
@@ -175,3 +175,190 @@ fn test_write_read_ndarray() {
     assert_eq!(array, a_c);
     assert_eq!(a, a_c);
 }
+
+#[test]
+fn test_read_ndarray_sparse() {
+    let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+
+    let n =
+        FilesystemHierarchy::open_or_create(dir.path()).expect("Failed to create Zarr filesystem");
+
+    let array_meta = ArrayMetadata::new(
+        smallvec![25, 30],
+        smallvec![10, 10],
+        i32::ZARR_TYPE,
+        CompressionType::default(),
+    );
+
+    let path_name = "test/array/group";
+    n.create_array(path_name, &array_meta)
+        .expect("Failed to create array");
+
+    // The chunk in the last row overhangs the array bounds.
+    for coord in &[[0, 1], [2, 2]] {
+        let chunk_data: Vec<i32> = (0..100).map(|v| v + 1000 * coord[0] as i32).collect();
+        let chunk_in = VecDataChunk::new(smallvec![coord[0], coord[1]], chunk_data);
+        n.write_chunk(path_name, &array_meta, &chunk_in)
+            .expect("Failed to write chunk");
+    }
+
+    let bbox = BoundingBox::new(smallvec![5, 5], smallvec![50, 20]);
+    let blocks = n
+        .read_ndarray_sparse::<i32>(path_name, &array_meta, &bbox)
+        .unwrap();
+    let dense = n
+        .read_ndarray::<i32>(path_name, &array_meta, &bbox)
+        .unwrap();
+
+    let bounds: Vec<_> = blocks.iter().map(|(bb, _)| bb.clone()).collect();
+    assert_eq!(
+        bounds,
+        vec![
+            BoundingBox::new(smallvec![5, 10], smallvec![5, 10]),
+            BoundingBox::new(smallvec![20, 20], smallvec![5, 5]),
+        ]
+    );
+    for (bb, block) in &blocks {
+        let dense_bb = bb.clone() - &smallvec![5, 5];
+        let dense_block = dense.slice(
+            ndarray::SliceInfo::<_, ndarray::IxDyn>::new(dense_bb.to_ndarray_slice())
+                .unwrap()
+                .as_ref(),
+        );
+        assert_eq!(block.view(), dense_block);
+    }
+}
+
+#[test]
+fn test_write_read_ndarray_zero_dimensional() {
+    let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+    let n =
+        FilesystemHierarchy::open_or_create(dir.path()).expect("Failed to create Zarr filesystem");
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![], f64::ZARR_TYPE).build();
+    assert_eq!(
+        array_meta.coord_iter().collect::<Vec<_>>(),
+        vec![Vec::<u64>::new()]
+    );
+    n.create_array("scalar", &array_meta)
+        .expect("Failed to create array");
+
+    let array = ndarray::arr0(1.5).into_dyn();
+    n.write_ndarray("scalar", &array_meta, smallvec![], &array)
+        .unwrap();
+    let bbox = BoundingBox::new(smallvec![], smallvec![]);
+    let a = n.read_ndarray::<f64>("scalar", &array_meta, &bbox).unwrap();
+    assert_eq!(array, a);
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![4, 0], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 2])
+        .build();
+    assert_eq!(array_meta.coord_iter().count(), 0);
+    n.create_array("empty", &array_meta)
+        .expect("Failed to create array");
+    let bbox = BoundingBox::new(smallvec![0, 0], smallvec![4, 0]);
+    let a = n.read_ndarray::<i32>("empty", &array_meta, &bbox).unwrap();
+    assert_eq!(a.shape(), &[4, 0]);
+}
+
+#[test]
+fn test_read_ndarray_missing_chunk_policy() {
+    let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+    let n =
+        FilesystemHierarchy::open_or_create(dir.path()).expect("Failed to create Zarr filesystem");
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![4, 4], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 4])
+        .build();
+    n.create_array("a", &array_meta).unwrap();
+    n.write_chunk(
+        "a",
+        &array_meta,
+        &SliceDataChunk::new(smallvec![0, 0], vec![1; 8]),
+    )
+    .unwrap();
+    let bbox = array_meta.get_bounds();
+
+    let filled = n
+        .read_ndarray_with_policy::<i32>("a", &array_meta, &bbox, &MissingChunkPolicy::FillValue)
+        .unwrap();
+    assert_eq!(
+        filled,
+        n.read_ndarray::<i32>("a", &array_meta, &bbox).unwrap()
+    );
+    assert_eq!(filled.iter().sum::<i32>(), 8);
+
+    let err = n
+        .read_ndarray_with_policy::<i32>("a", &array_meta, &bbox, &MissingChunkPolicy::Error)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    let provide = |coord: &[u64]| Ok(Some(SliceDataChunk::new(coord.into(), vec![2; 8])));
+    let provided = n
+        .read_ndarray_with_policy::<i32>(
+            "a",
+            &array_meta,
+            &bbox,
+            &MissingChunkPolicy::Callback(&provide),
+        )
+        .unwrap();
+    assert_eq!(provided.iter().sum::<i32>(), 8 + 16);
+
+    let wrong = |_: &[u64]| Ok(Some(SliceDataChunk::new(smallvec![1, 0], vec![2; 3])));
+    assert!(n
+        .read_ndarray_with_policy::<i32>(
+            "a",
+            &array_meta,
+            &bbox,
+            &MissingChunkPolicy::Callback(&wrong)
+        )
+        .is_err());
+}
+
+#[test]
+fn test_read_ndarray_bounded() {
+    let dir = tempdir::TempDir::new("rust_zarr_ndarray_tests").unwrap();
+    let n =
+        FilesystemHierarchy::open_or_create(dir.path()).expect("Failed to create Zarr filesystem");
+
+    let array_meta = ArrayMetadataBuilder::new(smallvec![10, 10], u16::ZARR_TYPE)
+        .chunk_shape(smallvec![4, 4])
+        .build();
+    n.create_array("a", &array_meta).unwrap();
+    let data = Array::from_shape_fn((10, 10), |(i, j)| (i * 10 + j) as u16).into_dyn();
+    n.write_ndarray("a", &array_meta, smallvec![0, 0], &data)
+        .unwrap();
+    let bbox = BoundingBox::new(smallvec![1, 2], smallvec![20, 7]);
+    let expected = n
+        .read_ndarray::<u16>(
+            "a",
+            &array_meta,
+            &BoundingBox::new(smallvec![1, 2], smallvec![9, 7]),
+        )
+        .unwrap();
+
+    // Rows of chunks, 4 by 7 elements, take 56 bytes and chunk buffers 64.
+    for &(budget, tiles) in &[(130, 3), (100, 9), (10_000, 1)] {
+        let mut read = Array::zeros((9, 7)).into_dyn();
+        let mut count = 0;
+        n.read_ndarray_bounded::<u16, _>("a", &array_meta, &bbox, budget, |tile_bb, tile| {
+            let offset: Vec<usize> = tile_bb.offset().iter().map(|&o| o as usize).collect();
+            let shape = tile_bb.shape_ndarray_shape();
+            read.slice_mut(ndarray::s![
+                offset[0] - 1..offset[0] - 1 + shape[0],
+                offset[1] - 2..offset[1] - 2 + shape[1]
+            ])
+            .assign(&tile);
+            count += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(read, expected);
+        assert_eq!(count, tiles);
+    }
+
+    let err = n
+        .read_ndarray_bounded::<u16, _>("a", &array_meta, &bbox, 64, |_, _| Ok(()))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::OutOfMemory);
+}
@@ -0,0 +1,105 @@
+#!/usr/bin/env python3
+"""Reference stores for testing interoperability with zarr-python.
+
+Requires zarr-python 2.x with its experimental v3 support, which implements
+the same version of the core protocol as this crate.
+
+Usage:
+
+    interop.py generate <store>  Write every case in the matrix under
+                                 /interop in a new hierarchy.
+    interop.py verify <store>    Check every array under /interop, as
+                                 written by the Rust tests.
+
+Element `i` of every array, in C order, holds `i % 100` cast to the array's
+data type.
+"""
+
+import itertools
+import os
+import sys
+
+os.environ.setdefault("ZARR_V3_EXPERIMENTAL_API", "1")
+
+import numcodecs  # noqa: E402
+import numpy as np  # noqa: E402
+import zarr  # noqa: E402
+from zarr.storage import DirectoryStoreV3  # noqa: E402
+
+GROUP = "interop"
+SHAPE = (7, 9, 4)
+CHUNKS = (3, 4, 4)
+
+DTYPES = [
+    "bool",
+    "i1", "<i2", ">i2", "<i4", ">i4", "<i8", ">i8",
+    "u1", "<u2", ">u2", "<u4", ">u4", "<u8", ">u8",
+    "<f2", ">f2", "<f4", ">f4", "<f8", ">f8",
+]
+CODECS = {
+    "raw": None,
+    "gzip": numcodecs.GZip(level=5),
+    "bzip2": numcodecs.BZ2(level=5),
+}
+ORDERS = ["C", "F"]
+SEPARATORS = {"slash": "/", "dot": "."}
+
+
+def case_name(dtype, codec, order, separator):
+    endian = {"<": "le", ">": "be"}.get(dtype[0])
+    name = dtype[1:] if endian else dtype
+    return "{}_{}_{}_{}_{}".format(name, endian or "na", codec, order, separator)
+
+
+def expected(dtype):
+    return (np.arange(np.prod(SHAPE)) % 100).astype(dtype).reshape(SHAPE)
+
+
+def generate(path):
+    store = DirectoryStoreV3(path)
+    for dtype, codec, order, separator in itertools.product(
+        DTYPES, CODECS, ORDERS, SEPARATORS
+    ):
+        array = zarr.open_array(
+            store,
+            mode="w",
+            path="{}/{}".format(GROUP, case_name(dtype, codec, order, separator)),
+            shape=SHAPE,
+            chunks=CHUNKS,
+            dtype=dtype,
+            compressor=CODECS[codec],
+            order=order,
+            dimension_separator=SEPARATORS[separator],
+            zarr_version=3,
+        )
+        array[...] = expected(dtype)
+
+
+def verify(path):
+    store = DirectoryStoreV3(path)
+    group = zarr.open_group(store, mode="r", path=GROUP, zarr_version=3)
+    failures = []
+    names = sorted(group.array_keys())
+    for name in names:
+        array = group[name]
+        if not np.array_equal(array[...], expected(array.dtype)):
+            failures.append(name)
+    if not names:
+        failures.append("(no arrays found)")
+    for name in failures:
+        print("Mismatch: {}".format(name), file=sys.stderr)
+    return 1 if failures else 0
+
+
+def main(argv):
+    if len(argv) != 3 or argv[1] not in ("generate", "verify"):
+        print(__doc__, file=sys.stderr)
+        return 2
+    if argv[1] == "generate":
+        generate(argv[2])
+        return 0
+    return verify(argv[2])
+
+
+if __name__ == "__main__":
+    sys.exit(main(sys.argv))
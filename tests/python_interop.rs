@@ -0,0 +1,226 @@
+//! Interoperability with zarr-python.
+//!
+//! The tests which need Python are ignored by default. They shell out to
+//! `tests/python/interop.py` with the interpreter in `ZARR_PYTHON` (default
+//! `python3`), which must have zarr-python 2.x and numcodecs installed:
+//!
+//! ```sh
+//! cargo test --test python_interop -- --ignored
+//! ```
+//!
+//! Both directions cover the same matrix of data types, compressors, memory
+//! layouts and chunk key separators.
+#![cfg(feature = "use_ndarray")]
+#![cfg(all(feature = "bzip", any(feature = "gzip", feature = "gzip_pure")))]
+
+use std::path::Path;
+use std::process::Command;
+
+use half::f16;
+use ndarray::{
+    Array,
+    IxDyn,
+};
+use serde_json::json;
+
+use zarr::chunk::{
+    ReadableDataChunk,
+    ReinitDataChunk,
+    WriteableDataChunk,
+};
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+use zarr::{
+    data_type_match,
+    data_type_rstype_replace,
+    FloatSize,
+    IntSize,
+};
+
+const GROUP: &str = "interop";
+const SHAPE: [u64; 3] = [7, 9, 4];
+const CHUNKS: [u32; 3] = [3, 4, 4];
+
+const DTYPES: &[&str] = &[
+    "bool", "i1", "<i2", ">i2", "<i4", ">i4", "<i8", ">i8", "u1", "<u2", ">u2", "<u4", ">u4",
+    "<u8", ">u8", "<f2", ">f2", "<f4", ">f4", "<f8", ">f8",
+];
+const CODECS: &[&str] = &["raw", "gzip", "bzip2"];
+const ORDERS: &[&str] = &["C", "F"];
+const SEPARATORS: &[(&str, &str)] = &[("slash", "/"), ("dot", ".")];
+
+/// Array element types that can be constructed from their C-order index,
+/// matching `expected` in `interop.py`.
+trait FromIndex: ReflectedType + PartialEq + std::fmt::Debug {
+    fn from_index(i: usize) -> Self;
+}
+
+macro_rules! from_index_impl {
+    ($($ty_name:ty),*) => {
+        $(
+            impl FromIndex for $ty_name {
+                fn from_index(i: usize) -> Self {
+                    (i % 100) as $ty_name
+                }
+            }
+        )*
+    };
+}
+
+from_index_impl!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl FromIndex for bool {
+    fn from_index(i: usize) -> Self {
+        !i.is_multiple_of(100)
+    }
+}
+
+impl FromIndex for f16 {
+    fn from_index(i: usize) -> Self {
+        f16::from_f32((i % 100) as f32)
+    }
+}
+
+fn expected<T: FromIndex>() -> Array<T, IxDyn> {
+    let shape: Vec<usize> = SHAPE.iter().map(|&s| s as usize).collect();
+    let numel = shape.iter().product();
+    Array::from_shape_vec(shape, (0..numel).map(T::from_index).collect()).unwrap()
+}
+
+fn case_name(dtype: &str, codec: &str, order: &str, separator: &str) -> String {
+    let (name, endian) = if let Some(name) = dtype.strip_prefix('<') {
+        (name, "le")
+    } else if let Some(name) = dtype.strip_prefix('>') {
+        (name, "be")
+    } else {
+        (dtype, "na")
+    };
+    format!("{}_{}_{}_{}_{}", name, endian, codec, order, separator)
+}
+
+fn case_metadata(dtype: &str, codec: &str, order: &str, separator: &str) -> ArrayMetadata {
+    let mut meta = json!({
+        "shape": SHAPE,
+        "data_type": dtype,
+        "chunk_grid": {
+            "type": "regular",
+            "chunk_shape": CHUNKS,
+            "separator": separator,
+        },
+        "chunk_memory_layout": order,
+        "fill_value": null,
+        "extensions": [],
+        "attributes": {},
+    });
+    if codec != "raw" {
+        let compressor: CompressionType = codec.parse().unwrap();
+        meta["compressor"] = serde_json::to_value(compressor).unwrap();
+    }
+    serde_json::from_value(meta).unwrap()
+}
+
+fn data_type(array_meta: &ArrayMetadata) -> DataType {
+    array_meta.get_data_type().effective_type().unwrap()
+}
+
+fn write_case<T: FromIndex>(h: &FilesystemHierarchy, path_name: &str, array_meta: &ArrayMetadata)
+where
+    VecDataChunk<T>: DataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+{
+    h.create_array(path_name, array_meta).unwrap();
+    h.write_ndarray(
+        path_name,
+        array_meta,
+        smallvec::smallvec![0; 3],
+        &expected::<T>(),
+    )
+    .unwrap();
+}
+
+fn check_case<T: FromIndex>(h: &FilesystemHierarchy, path_name: &str, array_meta: &ArrayMetadata)
+where
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk,
+{
+    let array = h
+        .read_ndarray::<T>(path_name, array_meta, &array_meta.get_bounds())
+        .unwrap();
+    assert_eq!(array, expected::<T>(), "Mismatch in {}", path_name);
+}
+
+/// Write every case in the matrix under `GROUP`.
+fn write_matrix(path: &Path) {
+    let h = FilesystemHierarchy::open_or_create(path).unwrap();
+    for dtype in DTYPES {
+        for codec in CODECS {
+            for order in ORDERS {
+                for (separator_name, separator) in SEPARATORS {
+                    let array_meta = case_metadata(dtype, codec, order, separator);
+                    let path_name = format!(
+                        "{}/{}",
+                        GROUP,
+                        case_name(dtype, codec, order, separator_name)
+                    );
+                    data_type_match!(
+                        data_type(&array_meta),
+                        DataType::Raw { .. } => unreachable!(),
+                        write_case::<RsType>(&h, &path_name, &array_meta)
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Check every array under `GROUP`, returning how many were found.
+fn check_matrix(path: &Path) -> usize {
+    let h = FilesystemHierarchy::open(path).unwrap();
+    let names = h.list_nodes(GROUP).unwrap();
+    for name in &names {
+        let path_name = format!("{}/{}", GROUP, name);
+        let array_meta = h.get_array_metadata(&path_name).unwrap();
+        data_type_match!(
+            data_type(&array_meta),
+            DataType::Raw { .. } => panic!("Unexpected raw data type in {}", path_name),
+            check_case::<RsType>(&h, &path_name, &array_meta)
+        );
+    }
+    names.len()
+}
+
+fn num_cases() -> usize {
+    DTYPES.len() * CODECS.len() * ORDERS.len() * SEPARATORS.len()
+}
+
+fn run_python(command: &str, path: &Path) {
+    let python = std::env::var("ZARR_PYTHON").unwrap_or_else(|_| "python3".to_owned());
+    let status = Command::new(python)
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/python/interop.py"))
+        .arg(command)
+        .arg(path)
+        .status()
+        .expect("Failed to run Python");
+    assert!(status.success(), "interop.py {} failed", command);
+}
+
+#[test]
+fn test_matrix_round_trip() {
+    let dir = tempdir::TempDir::new("rust_zarr_interop_tests").unwrap();
+    write_matrix(dir.path());
+    assert_eq!(check_matrix(dir.path()), num_cases());
+}
+
+#[test]
+#[ignore]
+fn test_read_python_written() {
+    let dir = tempdir::TempDir::new("rust_zarr_interop_tests").unwrap();
+    run_python("generate", dir.path());
+    assert_eq!(check_matrix(dir.path()), num_cases());
+}
+
+#[test]
+#[ignore]
+fn test_python_reads_rust_written() {
+    let dir = tempdir::TempDir::new("rust_zarr_interop_tests").unwrap();
+    write_matrix(dir.path());
+    run_python("verify", dir.path());
+}
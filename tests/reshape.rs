@@ -0,0 +1,164 @@
+#![cfg(feature = "use_ndarray")]
+
+use ndarray::Array;
+use smallvec::smallvec;
+
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+use zarr::reshape::{
+    ZarrReshapeReader,
+    ZarrReshapeWriter,
+};
+use zarr::Order;
+
+#[test]
+fn test_reshape_view() {
+    let dir = tempdir::TempDir::new("rust_zarr_reshape_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![3, 4, 5], i32::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 3, 2])
+        .build();
+    h.create_array("a", &array_meta).unwrap();
+    let values: Vec<i32> = (0..60).collect();
+    let array = Array::from_shape_vec(vec![3, 4, 5], values.clone()).unwrap();
+    h.write_ndarray("a", &array_meta, smallvec![0, 0, 0], &array)
+        .unwrap();
+
+    let view = h.reshape("a", &array_meta, smallvec![6, 10]).unwrap();
+    assert_eq!(view.get_shape(), &[6, 10]);
+    let expected = Array::from_shape_vec(vec![6, 10], values)
+        .unwrap()
+        .into_dyn();
+    assert_eq!(
+        view.read_ndarray::<i32>(&view.get_bounds()).unwrap(),
+        expected
+    );
+
+    let bbox = BoundingBox::new(smallvec![1, 3], smallvec![4, 5]);
+    let expected_slice = expected
+        .slice(ndarray::s![1..5, 3..8])
+        .to_owned()
+        .into_dyn();
+    assert_eq!(view.read_ndarray::<i32>(&bbox).unwrap(), expected_slice);
+
+    assert!(view
+        .read_ndarray::<i32>(&BoundingBox::new(smallvec![5, 0], smallvec![2, 10]))
+        .is_err());
+    assert!(h.reshape("a", &array_meta, smallvec![7, 10]).is_err());
+}
+
+#[test]
+fn test_reshape_in_place() {
+    let dir = tempdir::TempDir::new("rust_zarr_reshape_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![6, 4], u8::ZARR_TYPE)
+        .chunk_shape(smallvec![2, 4])
+        .chunk_memory_layout(Order::RowMajor)
+        .build();
+    h.create_array("a", &array_meta).unwrap();
+    let values: Vec<u8> = (0..24).collect();
+    let array = Array::from_shape_vec(vec![6, 4], values.clone()).unwrap();
+    h.write_ndarray("a", &array_meta, smallvec![0, 0], &array)
+        .unwrap();
+
+    let reshaped = h.reshape_in_place("a", smallvec![3, 8]).unwrap();
+    assert_eq!(h.get_array_metadata("a").unwrap(), reshaped);
+    assert_eq!(
+        h.read_ndarray::<u8>("a", &reshaped, &reshaped.get_bounds())
+            .unwrap(),
+        Array::from_shape_vec(vec![3, 8], values)
+            .unwrap()
+            .into_dyn()
+    );
+
+    let err = h.reshape_in_place("a", smallvec![4, 6]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(h.get_array_metadata("a").unwrap(), reshaped);
+}
+
+#[test]
+fn test_reshape_into() {
+    let dir = tempdir::TempDir::new("rust_zarr_reshape_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![6, 4], f32::ZARR_TYPE)
+        .chunk_shape(smallvec![4, 3])
+        .build();
+    h.create_array("a", &array_meta).unwrap();
+    let values: Vec<f32> = (0..24).map(|i| i as f32).collect();
+    let array = Array::from_shape_vec(vec![6, 4], values.clone()).unwrap();
+    h.write_ndarray("a", &array_meta, smallvec![0, 0], &array)
+        .unwrap();
+
+    let dest_meta = ArrayMetadataBuilder::new(smallvec![2, 3, 4], f32::ZARR_TYPE)
+        .chunk_shape(smallvec![1, 2, 3])
+        .build();
+    h.create_array("b", &dest_meta).unwrap();
+    h.reshape_into("a", &array_meta, "b", &dest_meta).unwrap();
+    assert_eq!(
+        h.read_ndarray::<f32>("b", &dest_meta, &dest_meta.get_bounds())
+            .unwrap(),
+        Array::from_shape_vec(vec![2, 3, 4], values)
+            .unwrap()
+            .into_dyn()
+    );
+
+    let wrong_type = ArrayMetadataBuilder::new(smallvec![2, 3, 4], u8::ZARR_TYPE).build();
+    assert!(h.reshape_into("a", &array_meta, "b", &wrong_type).is_err());
+}
+
+#[test]
+fn test_axes_view() {
+    let dir = tempdir::TempDir::new("rust_zarr_reshape_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![1, 1, 1, 6, 5], u16::ZARR_TYPE)
+        .chunk_shape(smallvec![1, 1, 1, 4, 4])
+        .build();
+    h.create_array("ome", &array_meta).unwrap();
+
+    let plane = h.axes("ome", &array_meta).squeeze();
+    assert_eq!(&plane.get_shape()[..], &[6, 5]);
+    let values: Vec<u16> = (0..30).collect();
+    let array = Array::from_shape_vec(vec![6, 5], values)
+        .unwrap()
+        .into_dyn();
+    plane.write_ndarray(smallvec![0, 0], &array).unwrap();
+    assert_eq!(
+        h.read_ndarray::<u16>("ome", &array_meta, &array_meta.get_bounds())
+            .unwrap()
+            .into_shape(vec![6, 5])
+            .unwrap(),
+        array
+    );
+    let bbox = BoundingBox::new(smallvec![2, 1], smallvec![3, 3]);
+    assert_eq!(
+        plane.read_ndarray::<u16>(&bbox).unwrap(),
+        array.slice(ndarray::s![2..5, 1..4]).to_owned().into_dyn()
+    );
+
+    let expanded = h
+        .axes("ome", &array_meta)
+        .squeeze_axis(0)
+        .unwrap()
+        .squeeze_axis(0)
+        .unwrap()
+        .expand_dims(3)
+        .unwrap();
+    assert_eq!(&expanded.get_shape()[..], &[1, 6, 5, 1]);
+    let column = expanded
+        .read_ndarray::<u16>(&BoundingBox::new(
+            smallvec![0, 0, 2, 0],
+            smallvec![1, 6, 1, 1],
+        ))
+        .unwrap();
+    assert_eq!(column.shape(), &[1, 6, 1, 1]);
+    assert_eq!(
+        column.iter().cloned().collect::<Vec<_>>(),
+        vec![2, 7, 12, 17, 22, 27]
+    );
+
+    assert!(h.axes("ome", &array_meta).squeeze_axis(3).is_err());
+    assert!(plane.clone().expand_dims(3).is_err());
+    assert!(plane
+        .read_ndarray::<u16>(&BoundingBox::new(smallvec![5, 0], smallvec![2, 5]))
+        .is_err());
+}
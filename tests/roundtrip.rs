@@ -0,0 +1,192 @@
+//! Property-based round trips of arrays through a filesystem hierarchy.
+//!
+//! Each case generates an array shape, chunk grid, memory layout, fill value
+//! and compressor, writes a random region of the array, then checks that
+//! reading another random region matches an in-memory model in which
+//! unwritten elements hold the fill value.
+#![cfg(feature = "use_ndarray")]
+
+use std::fmt::Debug;
+
+use ndarray::{
+    Array,
+    IxDyn,
+    SliceInfo,
+};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use zarr::chunk::{
+    ReadableDataChunk,
+    ReinitDataChunk,
+    WriteableDataChunk,
+};
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+use zarr::Order;
+
+#[derive(Clone, Debug)]
+struct Case {
+    shape: GridCoord,
+    chunk_shape: ChunkCoord,
+    order: Order,
+    compressor: CompressionType,
+    /// Offset and shape of the written region.
+    write: (GridCoord, GridCoord),
+    read: BoundingBox,
+}
+
+#[cfg_attr(not(feature = "pcodec"), allow(unused_variables))]
+fn compressors(data_type: &DataType) -> Vec<CompressionType> {
+    let mut compressors = vec![CompressionType::default()];
+    #[cfg(feature = "bzip")]
+    compressors.push(CompressionType::new::<compression::bzip::Bzip2Compression>());
+    #[cfg(any(feature = "gzip", feature = "gzip_pure"))]
+    compressors.push(CompressionType::new::<compression::gzip::GzipCompression>());
+    #[cfg(any(feature = "lz", feature = "lz_pure"))]
+    compressors.push(CompressionType::new::<compression::lz::Lz4Compression>());
+    #[cfg(feature = "xz")]
+    compressors.push(CompressionType::new::<compression::xz::XzCompression>());
+    #[cfg(feature = "snappy")]
+    compressors.push(CompressionType::new::<compression::snappy::SnappyCompression>());
+    #[cfg(feature = "pcodec")]
+    if *data_type != DataType::Bool {
+        compressors.push(CompressionType::new::<compression::pcodec::PcodecCompression>());
+    }
+    #[cfg(feature = "zstd")]
+    compressors.push(CompressionType::new::<compression::zstd::ZstdCompression>());
+    compressors
+}
+
+/// Offset and shape of a non-empty region within an array of the given shape.
+fn region_within(shape: &[u64]) -> impl Strategy<Value = (GridCoord, GridCoord)> {
+    shape
+        .iter()
+        .map(|&dim| (0..dim).prop_flat_map(move |offset| (Just(offset), 1..=dim - offset)))
+        .collect::<Vec<_>>()
+        .prop_map(|dims| dims.into_iter().unzip())
+}
+
+fn case(data_type: DataType) -> impl Strategy<Value = Case> {
+    let order = prop_oneof![Just(Order::RowMajor), Just(Order::ColumnMajor)];
+    (
+        prop::collection::vec(1..=12u64, 1..=3),
+        order,
+        prop::sample::select(compressors(&data_type)),
+    )
+        .prop_flat_map(|(shape, order, compressor)| {
+            let chunk_shape = shape.iter().map(|&dim| 1..=dim as u32).collect::<Vec<_>>();
+            (
+                Just(shape.clone()),
+                chunk_shape,
+                Just(order),
+                Just(compressor),
+                region_within(&shape),
+                region_within(&shape),
+            )
+        })
+        .prop_map(
+            |(shape, chunk_shape, order, compressor, write, read)| Case {
+                shape: shape.into(),
+                chunk_shape: chunk_shape.into(),
+                order,
+                compressor,
+                write,
+                read: BoundingBox::new(read.0, read.1),
+            },
+        )
+}
+
+/// A case along with a fill value and data for its written region.
+fn case_with_data<T, S>(values: S) -> impl Strategy<Value = (Case, T, Vec<T>)>
+where
+    T: ReflectedType + Debug,
+    S: Strategy<Value = T> + Clone,
+{
+    case(T::ZARR_TYPE).prop_flat_map(move |case| {
+        let numel = case.write.1.iter().product::<u64>() as usize;
+        (
+            Just(case),
+            values.clone(),
+            prop::collection::vec(values.clone(), numel),
+        )
+    })
+}
+
+fn check_round_trip<T>(case: &Case, fill_value: T, data: Vec<T>) -> Result<(), TestCaseError>
+where
+    T: ReflectedType + PartialEq + Debug + serde::Serialize,
+    VecDataChunk<T>: DataChunk<T> + ReinitDataChunk<T> + ReadableDataChunk + WriteableDataChunk,
+{
+    let dir = tempdir::TempDir::new("rust_zarr_roundtrip_tests").unwrap();
+    let n = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+
+    let array_meta = ArrayMetadataBuilder::new(case.shape.clone(), T::ZARR_TYPE)
+        .chunk_shape(case.chunk_shape.clone())
+        .chunk_memory_layout(case.order.clone())
+        .fill_value(serde_json::to_value(&fill_value).unwrap())
+        .compressor(case.compressor.clone())
+        .build();
+    let path_name = "array";
+    n.create_array(path_name, &array_meta).unwrap();
+
+    let (write_offset, write_shape) = case.write.clone();
+    let write_bbox = BoundingBox::new(write_offset.clone(), write_shape);
+    let written = Array::from_shape_vec(&write_bbox.shape_ndarray_shape()[..], data).unwrap();
+    n.write_ndarray(path_name, &array_meta, write_offset, &written)
+        .unwrap();
+
+    let mut expected = Array::from_elem(
+        &case.shape.iter().map(|&d| d as usize).collect::<Vec<_>>()[..],
+        fill_value,
+    );
+    expected
+        .slice_mut(
+            SliceInfo::<_, IxDyn>::new(write_bbox.to_ndarray_slice())
+                .unwrap()
+                .as_ref(),
+        )
+        .assign(&written);
+    let expected = expected.slice(
+        SliceInfo::<_, IxDyn>::new(case.read.to_ndarray_slice())
+            .unwrap()
+            .as_ref(),
+    );
+
+    let read = n
+        .read_ndarray::<T>(path_name, &array_meta, &case.read)
+        .unwrap();
+    prop_assert_eq!(read.view(), expected);
+
+    Ok(())
+}
+
+macro_rules! round_trip_test {
+    ($name:ident, $ty:ty, $values:expr) => {
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn $name((case, fill_value, data) in case_with_data::<$ty, _>($values)) {
+                check_round_trip(&case, fill_value, data)?;
+            }
+        }
+    };
+}
+
+round_trip_test!(test_round_trip_bool, bool, any::<bool>());
+round_trip_test!(test_round_trip_u8, u8, any::<u8>());
+round_trip_test!(test_round_trip_i16, i16, any::<i16>());
+round_trip_test!(test_round_trip_u32, u32, any::<u32>());
+round_trip_test!(test_round_trip_i64, i64, any::<i64>());
+// Fill values are stored as JSON, which cannot represent NaN or infinities.
+round_trip_test!(
+    test_round_trip_f32,
+    f32,
+    prop::num::f32::NORMAL | prop::num::f32::ZERO
+);
+round_trip_test!(
+    test_round_trip_f64,
+    f64,
+    prop::num::f64::NORMAL | prop::num::f64::ZERO
+);
@@ -0,0 +1,217 @@
+#![cfg(feature = "use_ndarray")]
+
+use std::io::ErrorKind;
+
+use ndarray::Array;
+use smallvec::smallvec;
+
+use zarr::ndarray::prelude::*;
+use zarr::prelude::*;
+use zarr::stream::{
+    ExportFormat,
+    Histogram,
+    ImportChecks,
+    ZarrStreamReader,
+    ZarrStreamWriter,
+};
+use zarr::{
+    Endian,
+    IntSize,
+};
+
+fn create_array(h: &FilesystemHierarchy, data_type: DataType) -> ArrayMetadata {
+    let array_meta = ArrayMetadataBuilder::new(smallvec![5, 7, 3], data_type)
+        .chunk_shape(smallvec![2, 3, 2])
+        .build();
+    h.create_array("stream", &array_meta).unwrap();
+    array_meta
+}
+
+#[test]
+fn test_import_stream() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let data_type = DataType::Int {
+        size: IntSize::B4,
+        endian: Endian::Big,
+    };
+    let array_meta = create_array(&h, data_type);
+
+    let values: Vec<i32> = (0..105).map(|i| i * 1000 - 7).collect();
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+    h.import_stream("stream", &array_meta, &bytes[..]).unwrap();
+
+    let expected = Array::from_shape_vec(vec![5, 7, 3], values).unwrap();
+    let read = h
+        .read_ndarray::<i32>("stream", &array_meta, &array_meta.get_bounds())
+        .unwrap();
+    assert_eq!(read, expected);
+}
+
+#[test]
+fn test_import_stream_short() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = create_array(&h, u8::ZARR_TYPE);
+
+    let bytes = [1u8; 104];
+    let err = h
+        .import_stream("stream", &array_meta, &bytes[..])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    // Slabs before the end of the stream are written.
+    assert!(h
+        .read_chunk::<u8>("stream", &array_meta, smallvec![1, 2, 1])
+        .unwrap()
+        .is_some());
+    assert!(h
+        .read_chunk::<u8>("stream", &array_meta, smallvec![2, 0, 0])
+        .unwrap()
+        .is_none());
+}
+
+fn import_sequence(
+    h: &FilesystemHierarchy,
+    data_type: DataType,
+) -> (ArrayMetadata, Array<i16, ndarray::IxDyn>) {
+    let array_meta = create_array(h, data_type);
+    let values: Vec<i16> = (0..105).collect();
+    let bytes: Vec<u8> = match data_type.endian() {
+        Endian::Big => values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        Endian::Little => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+    };
+    h.import_stream("stream", &array_meta, &bytes[..]).unwrap();
+    (
+        array_meta,
+        Array::from_shape_vec(vec![5, 7, 3], values).unwrap(),
+    )
+}
+
+#[test]
+fn test_export_region() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let data_type = DataType::Int {
+        size: IntSize::B2,
+        endian: Endian::Big,
+    };
+    let (array_meta, expected) = import_sequence(&h, data_type);
+
+    let bbox = BoundingBox::new(smallvec![1, 2, 0], smallvec![3, 4, 3]);
+    let mut bytes = vec![];
+    h.export_region("stream", &array_meta, &bbox, ExportFormat::Raw, &mut bytes)
+        .unwrap();
+
+    let expected: Vec<u8> = expected
+        .slice(ndarray::s![1..4, 2..6, ..])
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect();
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_export_region_npy() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let (array_meta, _) = import_sequence(&h, i16::ZARR_TYPE);
+
+    let mut bytes = vec![];
+    h.export_region(
+        "stream",
+        &array_meta,
+        &array_meta.get_bounds(),
+        ExportFormat::Npy,
+        &mut bytes,
+    )
+    .unwrap();
+
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    let descr = if cfg!(target_endian = "little") {
+        "<i2"
+    } else {
+        ">i2"
+    };
+    assert_eq!(
+        header.trim_end(),
+        format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': (5, 7, 3), }}",
+            descr
+        )
+    );
+    assert!(header.ends_with('\n'));
+    assert_eq!(bytes.len(), 10 + header_len + 105 * 2);
+}
+
+#[test]
+fn test_stream_zero_dimensional() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = ArrayMetadataBuilder::new(smallvec![], u32::ZARR_TYPE).build();
+    h.create_array("scalar", &array_meta).unwrap();
+
+    let bytes = 7u32.to_ne_bytes();
+    h.import_stream("scalar", &array_meta, &bytes[..]).unwrap();
+
+    let mut exported = vec![];
+    h.export_region(
+        "scalar",
+        &array_meta,
+        &array_meta.get_bounds(),
+        ExportFormat::Raw,
+        &mut exported,
+    )
+    .unwrap();
+    assert_eq!(exported, bytes);
+}
+
+#[test]
+fn test_import_stream_checked() {
+    let dir = tempdir::TempDir::new("rust_zarr_stream_tests").unwrap();
+    let h = FilesystemHierarchy::open_or_create(dir.path()).unwrap();
+    let array_meta = create_array(&h, u16::ZARR_TYPE);
+
+    // Element 50, at [2, 2, 2], is out of range.
+    let mut values: Vec<u16> = (0..105).map(|i| i % 10).collect();
+    values[50] = 4000;
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let checks = ImportChecks {
+        valid_range: Some((0.0, 9.0)),
+        reject_out_of_range: false,
+        histogram: Some(Histogram::new(0.0, 10.0, 2)),
+    };
+    let report = h
+        .import_stream_checked("stream", &array_meta, &bytes[..], checks.clone())
+        .unwrap();
+    assert_eq!(report.out_of_range, 1);
+    assert_eq!(
+        report.first_out_of_range,
+        Some(smallvec::SmallVec::from_slice(&[2, 2, 2]))
+    );
+    let histogram = report.histogram.unwrap();
+    assert_eq!(histogram.counts, vec![54, 50]);
+    assert_eq!(histogram.above, 1);
+
+    h.remove("stream").unwrap();
+    let array_meta = create_array(&h, u16::ZARR_TYPE);
+    let checks = ImportChecks {
+        reject_out_of_range: true,
+        ..checks
+    };
+    let err = h
+        .import_stream_checked("stream", &array_meta, &bytes[..], checks)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    // Slabs before the one holding the out-of-range element are written.
+    assert!(h
+        .read_chunk::<u16>("stream", &array_meta, smallvec![0, 0, 0])
+        .unwrap()
+        .is_some());
+    assert!(h
+        .read_chunk::<u16>("stream", &array_meta, smallvec![1, 0, 0])
+        .unwrap()
+        .is_none());
+}
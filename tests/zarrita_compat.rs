@@ -18,7 +18,7 @@ where
     usize: TryInto<T>,
     <usize as TryInto<T>>::Error: std::fmt::Debug,
 {
-    let expected: Vec<T> = (0..(array_meta.get_num_elements()))
+    let expected: Vec<T> = (0..(array_meta.get_num_elements() as usize))
         .map(TryInto::try_into)
         .collect::<Result<Vec<T>, _>>()
         .unwrap();